@@ -235,6 +235,8 @@ impl From<Header> for BlockHeader {
             blob_gas_used: val.blob_gas_used.map(|x| x.as_u64()),
             excess_blob_gas: val.excess_blob_gas.map(|x| x.as_u64()),
             parent_beacon_block_root: val.parent_beacon_block_root,
+            // Only networks up to Cancun are tested
+            requests_hash: None,
         }
     }
 }
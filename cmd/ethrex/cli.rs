@@ -1,5 +1,8 @@
 use clap::{Arg, ArgAction, Command};
-use ethrex_net::bootnode::BootNode;
+use ethrex_blockchain::payload::MAX_EXTRA_DATA_SIZE;
+use ethrex_core::{Address, Bytes, H256};
+use ethrex_net::{ban_list::BanTarget, bootnode::BootNode};
+use ethrex_rpc::{api_tokens::ALL_NAMESPACES, HTTP_NAMESPACES};
 use tracing::Level;
 
 pub fn cli() -> Command {
@@ -20,6 +23,65 @@ pub fn cli() -> Command {
                 .value_name("PORT")
                 .action(ArgAction::Set),
         )
+        .arg(
+            Arg::new("http.tls-cert")
+                .long("http.tls-cert")
+                .required(false)
+                .requires("http.tls-key")
+                .value_name("CERT_PATH")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("http.tls-key")
+                .long("http.tls-key")
+                .required(false)
+                .requires("http.tls-cert")
+                .value_name("KEY_PATH")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("http.tls-client-ca")
+                .long("http.tls-client-ca")
+                .required(false)
+                .requires("http.tls-cert")
+                .value_name("CA_PATH")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("http.api-tokens")
+                .long("http.api-tokens")
+                .required(false)
+                .value_name("TOKENS_FILE_PATH")
+                .help("Require the HTTP RPC requests to carry one of the bearer tokens of the file, created with `ethrex rpc issue-token`")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("gpo.blocks")
+                .long("gpo.blocks")
+                .required(false)
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .action(ArgAction::Set)
+                .help("Number of recent blocks sampled to suggest gas prices and priority fees"),
+        )
+        .arg(
+            Arg::new("gpo.percentile")
+                .long("gpo.percentile")
+                .required(false)
+                .value_name("PERCENTILE")
+                .value_parser(clap::value_parser!(u64).range(0..=100))
+                .action(ArgAction::Set)
+                .help("Percentile of the sampled tips suggested as gas price and priority fee"),
+        )
+        .arg(
+            Arg::new("builder.deadline")
+                .long("builder.deadline")
+                .required(false)
+                .value_name("MILLISECONDS")
+                .value_parser(clap::value_parser!(u64).range(0..12_000))
+                .action(ArgAction::Set)
+                .help("Time into the slot by which the consensus client retrieves the payloads it proposes, they stop being improved in time to be ready by then. Defaults to 4000"),
+        )
         .arg(
             Arg::new("log.level")
                 .long("log.level")
@@ -110,6 +172,100 @@ pub fn cli() -> Command {
                 .required(false)
                 .value_name("SYNC_MODE"),
         )
+        .arg(
+            Arg::new("sync.rpc-url")
+                .long("sync.rpc-url")
+                .required(false)
+                .value_name("RPC_URL"),
+        )
+        .arg(
+            Arg::new("webhook.url")
+                .long("webhook.url")
+                .required(false)
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("builder.gas-limit")
+                .long("builder.gas-limit")
+                .alias("builder.gaslimit")
+                .required(false)
+                .value_name("GAS_LIMIT")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set)
+                .help("Gas limit built payloads move towards, within the bounds allowed on each block"),
+        )
+        .arg(
+            Arg::new("builder.extradata")
+                .long("builder.extradata")
+                .required(false)
+                .value_name("EXTRA_DATA")
+                .value_parser(parse_extra_data)
+                .action(ArgAction::Set)
+                .help("Extra data of the built payloads, at most 32 bytes"),
+        )
+        .arg(
+            Arg::new("builder.force-include-addresses")
+                .long("builder.force-include-addresses")
+                .required(false)
+                .value_name("ADDRESS_LIST")
+                .value_parser(clap::value_parser!(Address))
+                .value_delimiter(',')
+                .num_args(1..)
+                .action(ArgAction::Set)
+                .help("Transactions sent from or to these addresses are tried first in every built payload"),
+        )
+        .arg(
+            Arg::new("builder.force-include-txs")
+                .long("builder.force-include-txs")
+                .required(false)
+                .value_name("TX_HASH_LIST")
+                .value_parser(clap::value_parser!(H256))
+                .value_delimiter(',')
+                .num_args(1..)
+                .action(ArgAction::Set)
+                .help("Transactions tried first in every built payload"),
+        )
+        .arg(
+            Arg::new("max-reorg-depth")
+                .long("max-reorg-depth")
+                .required(false)
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("blob-sidecars-retention")
+                .long("blob-sidecars-retention")
+                .required(false)
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("state-access-stats")
+                .long("state-access-stats")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict-validation")
+                .long("strict-validation")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("index-addresses")
+                .long("index-addresses")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("era-dir")
+                .long("era-dir")
+                .required(false)
+                .value_name("ERA1_FILES_DIRECTORY")
+                .help("Directory of era1 files the pruned pre-merge history is served from"),
+        )
         .arg(
             Arg::new("import_dir")
                 .long("import_dir")
@@ -124,4 +280,201 @@ pub fn cli() -> Command {
                     .action(ArgAction::Set),
             ),
         )
+        .subcommand(
+            Command::new("repair-receipt-blooms")
+                .about("Recompute the blooms of the stored receipts from their logs")
+                .arg(
+                    Arg::new("datadir")
+                        .long("datadir")
+                        .value_name("DATABASE_DIRECTORY")
+                        .action(ArgAction::Set),
+                ),
+        )
+        .subcommand(
+            Command::new("db")
+                .about("Inspect and maintain the database")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check the integrity of the stored canonical chain")
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("state-sample-interval")
+                                .long("state-sample-interval")
+                                .value_name("BLOCKS")
+                                .value_parser(clap::value_parser!(u64))
+                                .action(ArgAction::Set),
+                        )
+                        .arg(Arg::new("repair").long("repair").action(ArgAction::SetTrue)),
+                )
+                .subcommand(
+                    Command::new("prune-history")
+                        .about("Drop the bodies and receipts of the pre-merge blocks, keeping their headers")
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("state")
+                .about("Export and import the full state of a block")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("export")
+                        .about("Dump the state of a canonical block to a file")
+                        .arg(
+                            Arg::new("block")
+                                .required(true)
+                                .value_name("BLOCK_NUMBER")
+                                .value_parser(clap::value_parser!(u64)),
+                        )
+                        .arg(Arg::new("file").required(true).value_name("DUMP_FILE_PATH"))
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(
+                    Command::new("import")
+                        .about("Rebuild the state tries of a dump in the database")
+                        .arg(Arg::new("file").required(true).value_name("DUMP_FILE_PATH"))
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("rpc")
+                .about("Manage the tokens of the HTTP RPC")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("issue-token")
+                        .about("Create a token giving access to some namespaces")
+                        .arg(Arg::new("file").required(true).value_name("TOKENS_FILE_PATH"))
+                        .arg(
+                            Arg::new("namespaces")
+                                .long("namespaces")
+                                .required(true)
+                                .value_name("NAMESPACES")
+                                .help("Comma separated namespaces the token gives access to, `*` for all of them")
+                                .value_delimiter(',')
+                                .value_parser(parse_namespace)
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(
+                    Command::new("revoke-token")
+                        .about("Remove a token")
+                        .arg(Arg::new("file").required(true).value_name("TOKENS_FILE_PATH"))
+                        .arg(Arg::new("token").required(true).value_name("TOKEN")),
+                ),
+        )
+        .subcommand(
+            Command::new("p2p")
+                .about("Manage the peers banned by the node")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("ban")
+                        .about("Stop talking to a node or to the nodes at an ip")
+                        .arg(
+                            Arg::new("target")
+                                .required(true)
+                                .value_name("NODE_ID_OR_IP")
+                                .value_parser(clap::value_parser!(BanTarget)),
+                        )
+                        .arg(
+                            Arg::new("duration")
+                                .long("duration")
+                                .value_name("SECONDS")
+                                .help("Lift the ban after the given seconds, by default the ban is permanent")
+                                .value_parser(clap::value_parser!(u64))
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(
+                    Command::new("unban")
+                        .about("Lift the ban of a node or ip")
+                        .arg(
+                            Arg::new("target")
+                                .required(true)
+                                .value_name("NODE_ID_OR_IP")
+                                .value_parser(clap::value_parser!(BanTarget)),
+                        )
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(
+                    Command::new("list-bans")
+                        .about("List the banned nodes and ips")
+                        .arg(
+                            Arg::new("datadir")
+                                .long("datadir")
+                                .value_name("DATABASE_DIRECTORY")
+                                .action(ArgAction::Set),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("export-state-access-stats")
+                .about("Export the state access stats collected with --state-access-stats as CSV")
+                .arg(
+                    Arg::new("datadir")
+                        .long("datadir")
+                        .value_name("DATABASE_DIRECTORY")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .required(true)
+                        .value_name("CSV_FILE_PATH")
+                        .action(ArgAction::Set),
+                ),
+        )
+}
+
+/// Parses a namespace of the HTTP RPC an API token gives access to
+fn parse_namespace(namespace: &str) -> Result<String, String> {
+    if namespace == ALL_NAMESPACES || HTTP_NAMESPACES.contains(&namespace) {
+        Ok(namespace.to_owned())
+    } else {
+        Err(format!(
+            "unknown namespace {namespace}, expected one of {}",
+            HTTP_NAMESPACES.join(", ")
+        ))
+    }
+}
+
+/// Parses the extra data of built payloads, a text used as is
+fn parse_extra_data(extra_data: &str) -> Result<Bytes, String> {
+    if extra_data.len() > MAX_EXTRA_DATA_SIZE {
+        return Err(format!(
+            "the extra data is {} bytes long, at most {MAX_EXTRA_DATA_SIZE} are allowed",
+            extra_data.len()
+        ));
+    }
+    Ok(Bytes::copy_from_slice(extra_data.as_bytes()))
 }
@@ -1,31 +1,32 @@
 use bytes::Bytes;
 use directories::ProjectDirs;
-use ethrex_blockchain::{add_block, fork_choice::apply_fork_choice};
+use ethrex::{NodeBuilder, SyncMode};
+use ethrex_blockchain::{access_stats, add_block, fork_choice::apply_fork_choice, payload};
 use ethrex_core::{
     types::{Block, Genesis},
-    H256,
+    Address, H256,
 };
 use ethrex_net::{
-    bootnode::BootNode, node_id_from_signing_key, peer_table, sync::SyncManager, types::Node,
+    ban_list::{self, BanTarget},
+    bootnode::BootNode,
 };
 use ethrex_rlp::decode::RLPDecode;
-use ethrex_storage::{EngineType, Store};
+use ethrex_rpc::{api_tokens, tls::TlsConfig, GasPriceOracleConfig};
+use ethrex_storage::{verify::VerifyOptions, EngineType, Store};
 use k256::ecdsa::SigningKey;
-use local_ip_address::local_ip;
 use std::{
     fs::{self, File},
-    future::IntoFuture,
     io,
-    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
+    net::{SocketAddr, ToSocketAddrs},
     path::Path,
     str::FromStr as _,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tokio_util::task::TaskTracker;
 use tracing::{error, info, warn};
 use tracing_subscriber::{filter::Directive, EnvFilter, FmtSubscriber};
 mod cli;
 mod decode;
+mod webhook;
 
 const DEFAULT_DATADIR: &str = "ethrex";
 #[tokio::main]
@@ -45,6 +46,24 @@ async fn main() {
         return;
     }
 
+    if let Some(matches) = matches.subcommand_matches("export-state-access-stats") {
+        let data_dir = matches
+            .get_one::<String>("datadir")
+            .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+        let output = matches
+            .get_one::<String>("output")
+            .expect("output is required");
+        let stats = access_stats::read_state_access_stats(
+            &Path::new(&data_dir).join(access_stats::STATE_ACCESS_STATS_FILE_NAME),
+        )
+        .expect("Failed to read state access stats");
+        let file = File::create(output).expect("Failed to create output file");
+        stats
+            .write_csv(std::io::BufWriter::new(file))
+            .expect("Failed to export state access stats");
+        return;
+    }
+
     let log_level = matches
         .get_one::<String>("log.level")
         .expect("shouldn't happen, log.level is used with a default value");
@@ -58,6 +77,184 @@ async fn main() {
         .finish();
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
+    if let Some(matches) = matches.subcommand_matches("repair-receipt-blooms") {
+        let data_dir = matches
+            .get_one::<String>("datadir")
+            .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+        let store = open_store(&data_dir);
+        let repaired = store
+            .rebuild_receipt_blooms()
+            .expect("Failed to repair receipt blooms");
+        info!("Repaired the bloom of {repaired} receipts");
+        return;
+    }
+
+    if let Some(matches) = matches
+        .subcommand_matches("db")
+        .and_then(|matches| matches.subcommand_matches("verify"))
+    {
+        let data_dir = matches
+            .get_one::<String>("datadir")
+            .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+        let store = open_store(&data_dir);
+        let options = VerifyOptions {
+            state_sample_interval: matches.get_one::<u64>("state-sample-interval").copied(),
+            repair: matches.get_flag("repair"),
+        };
+        let report = store
+            .verify_chain(&options)
+            .expect("Failed to verify the database");
+        for issue in &report.issues {
+            warn!("{issue}");
+        }
+        info!(
+            "Checked {} blocks, found {} issues, repaired {}",
+            report.checked_blocks,
+            report.issues.len(),
+            report.repaired
+        );
+        if !report.issues.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(matches) = matches
+        .subcommand_matches("db")
+        .and_then(|matches| matches.subcommand_matches("prune-history"))
+    {
+        let data_dir = matches
+            .get_one::<String>("datadir")
+            .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+        let store = open_store(&data_dir);
+        let pruned = store
+            .prune_pre_merge_history()
+            .expect("Failed to prune the pre-merge history");
+        info!(
+            "Pruned the history of {pruned} pre-merge blocks, it is served from block {}",
+            store
+                .get_history_start_block_number()
+                .expect("Failed to read the history start")
+        );
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("state") {
+        match matches.subcommand() {
+            Some(("export", matches)) => {
+                let data_dir = matches
+                    .get_one::<String>("datadir")
+                    .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+                let block_number = *matches.get_one::<u64>("block").expect("block is required");
+                let path = matches.get_one::<String>("file").expect("file is required");
+                let store = open_store(&data_dir);
+                let file = File::create(path).expect("Failed to create dump file");
+                let exported = store
+                    .export_state(block_number, std::io::BufWriter::new(file))
+                    .expect("Failed to export state");
+                info!("Exported {exported} accounts of block {block_number} to {path}");
+            }
+            Some(("import", matches)) => {
+                let data_dir = matches
+                    .get_one::<String>("datadir")
+                    .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+                let path = matches.get_one::<String>("file").expect("file is required");
+                let store = open_store(&data_dir);
+                let file = File::open(path).expect("Failed to open dump file");
+                let header = store
+                    .import_state(std::io::BufReader::new(file))
+                    .expect("Failed to import state");
+                info!(
+                    "Imported the state of block {} with root {:#x}",
+                    header.block_number, header.state_root
+                );
+            }
+            _ => unreachable!("subcommand is required"),
+        }
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("rpc") {
+        let (command, matches) = matches.subcommand().expect("subcommand is required");
+        let path = Path::new(matches.get_one::<String>("file").expect("file is required"));
+        let mut tokens = api_tokens::read_api_tokens(path).expect("Failed to read API tokens");
+        match command {
+            "issue-token" => {
+                let namespaces: Vec<String> = matches
+                    .get_many::<String>("namespaces")
+                    .expect("namespaces are required")
+                    .cloned()
+                    .collect();
+                let token = tokens.issue(namespaces);
+                println!("{token}");
+            }
+            "revoke-token" => {
+                let token = matches
+                    .get_one::<String>("token")
+                    .expect("token is required");
+                if !tokens.revoke(token) {
+                    warn!("The token is not in {}", path.display());
+                    return;
+                }
+            }
+            _ => unreachable!("subcommand is required"),
+        }
+        api_tokens::write_api_tokens(path, &tokens).expect("Failed to write API tokens");
+        info!("Restart the node for the change to the tokens to apply");
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("p2p") {
+        let (command, matches) = matches.subcommand().expect("subcommand is required");
+        let data_dir = matches
+            .get_one::<String>("datadir")
+            .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
+        let path = ban_list::ban_list_path(&data_dir);
+        let mut list = ban_list::read_ban_list(&path).expect("Failed to read banned peers");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time is before the unix epoch")
+            .as_secs();
+        list.remove_expired(now);
+        match command {
+            "ban" => {
+                let target = *matches
+                    .get_one::<BanTarget>("target")
+                    .expect("target is required");
+                let expires_at = matches
+                    .get_one::<u64>("duration")
+                    .map(|duration| now + duration);
+                list.ban(target, expires_at);
+                info!("Banned {target}");
+            }
+            "unban" => {
+                let target = *matches
+                    .get_one::<BanTarget>("target")
+                    .expect("target is required");
+                if list.unban(target) {
+                    info!("Lifted the ban of {target}");
+                } else {
+                    warn!("{target} is not banned");
+                }
+            }
+            "list-bans" => {
+                for ban in list.bans() {
+                    match ban.expires_at {
+                        Some(expires_at) => {
+                            println!("{} for {} seconds", ban.target, expires_at - now)
+                        }
+                        None => println!("{} permanently", ban.target),
+                    }
+                }
+                return;
+            }
+            _ => unreachable!("subcommand is required"),
+        }
+        fs::create_dir_all(&data_dir).expect("Failed to create the data directory");
+        ban_list::write_ban_list(&path, &list).expect("Failed to write banned peers");
+        return;
+    }
+
     let http_addr = matches
         .get_one::<String>("http.addr")
         .expect("http.addr is required");
@@ -111,29 +308,147 @@ async fn main() {
     let tcp_socket_addr =
         parse_socket_addr(tcp_addr, tcp_port).expect("Failed to parse addr and port");
 
+    let http_tls = matches
+        .get_one::<String>("http.tls-cert")
+        .zip(matches.get_one::<String>("http.tls-key"))
+        .map(|(cert_path, key_path)| TlsConfig {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            client_ca_path: matches
+                .get_one::<String>("http.tls-client-ca")
+                .map(Into::into),
+        });
+
     let data_dir = matches
         .get_one::<String>("datadir")
         .map_or(set_datadir(DEFAULT_DATADIR), |datadir| set_datadir(datadir));
 
+    let state_access_stats_path =
+        Path::new(&data_dir).join(access_stats::STATE_ACCESS_STATS_FILE_NAME);
+
+    let mut payload_builder = payload::PayloadBuilderConfig::default();
+    if let Some(gas_limit) = matches.get_one::<u64>("builder.gas-limit") {
+        payload_builder.gas_ceil = *gas_limit;
+        info!("Moving the gas limit of built payloads towards {gas_limit}");
+    }
+
+    if let Some(extra_data) = matches.get_one::<Bytes>("builder.extradata") {
+        payload_builder.extra_data = extra_data.clone();
+        info!("Setting the extra data of built payloads to {extra_data:?}");
+    }
+
+    payload_builder.forced_inclusion_list = payload::ForcedInclusionList {
+        addresses: matches
+            .get_many::<Address>("builder.force-include-addresses")
+            .map(|addresses| addresses.copied().collect())
+            .unwrap_or_default(),
+        tx_hashes: matches
+            .get_many::<H256>("builder.force-include-txs")
+            .map(|tx_hashes| tx_hashes.copied().collect())
+            .unwrap_or_default(),
+    };
+    let forced_inclusion_list = &payload_builder.forced_inclusion_list;
+    if !forced_inclusion_list.addresses.is_empty() || !forced_inclusion_list.tx_hashes.is_empty() {
+        info!(
+            "Forcing the inclusion of the transactions of {} addresses and {} transactions",
+            forced_inclusion_list.addresses.len(),
+            forced_inclusion_list.tx_hashes.len()
+        );
+    }
+
     let snap_sync = is_snap_sync(&matches);
     if snap_sync {
         info!("snap-sync not available, defaulting to full-sync");
     }
 
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "redb")] {
-            let store = Store::new(&data_dir, EngineType::RedB).expect("Failed to create Store");
-        } else if #[cfg(feature = "libmdbx")] {
-            let store = Store::new(&data_dir, EngineType::Libmdbx).expect("Failed to create Store");
+    let jwt_secret = read_jwtsecret_file(authrpc_jwtsecret);
+
+    // TODO Learn how should the key be created
+    // https://github.com/lambdaclass/ethrex/issues/836
+    //let signer = SigningKey::random(&mut OsRng);
+    let key_bytes =
+        H256::from_str("577d8278cc7748fad214b5378669b420f8221afb45ce930b7f22da49cbc545f3").unwrap();
+    let signer = SigningKey::from_slice(key_bytes.as_bytes()).unwrap();
+
+    let genesis = read_genesis_file(genesis_file_path);
+    if genesis.config.prague_time.is_some() && genesis.config.deposit_contract_address.is_none() {
+        warn!("Prague is scheduled but the genesis file doesn't set depositContractAddress, Prague blocks will be handled as Cancun ones");
+    }
+    let mut node_builder = NodeBuilder::new(genesis)
+        .storage(&data_dir, engine_type())
+        .address_index(matches.get_flag("index-addresses"))
+        .strict_validation(matches.get_flag("strict-validation"))
+        .http(http_socket_addr)
+        .authrpc(authrpc_socket_addr, jwt_secret)
+        .p2p(tcp_socket_addr, udp_socket_addr)
+        .bootnodes(bootnodes)
+        .signer(signer)
+        .sync_mode(if snap_sync {
+            SyncMode::Snap
         } else {
-            let store = Store::new(&data_dir, EngineType::InMemory).expect("Failed to create Store");
+            SyncMode::Full
+        })
+        // We do not want to start the networking module if the l2 or dev features are enabled.
+        .networking(cfg!(not(any(feature = "l2", feature = "dev"))));
+    if let Some(http_tls) = http_tls {
+        node_builder = node_builder.http_tls(http_tls);
+    }
+    if let Some(path) = matches.get_one::<String>("http.api-tokens") {
+        let tokens =
+            api_tokens::read_api_tokens(Path::new(path)).expect("Failed to read API tokens");
+        if tokens.tokens().is_empty() {
+            warn!("There are no API tokens in {path}, every HTTP RPC request will be rejected");
         }
+        node_builder = node_builder.http_api_tokens(tokens);
+    }
+    let mut gas_price_oracle = GasPriceOracleConfig::default();
+    if let Some(blocks) = matches.get_one::<u64>("gpo.blocks") {
+        gas_price_oracle.blocks = *blocks;
+    }
+    if let Some(percentile) = matches.get_one::<u64>("gpo.percentile") {
+        gas_price_oracle.percentile = *percentile;
+    }
+    node_builder = node_builder
+        .gas_price_oracle(gas_price_oracle)
+        .payload_builder(payload_builder);
+    if let Some(deadline) = matches.get_one::<u64>("builder.deadline") {
+        node_builder = node_builder.get_payload_deadline(Duration::from_millis(*deadline));
+    }
+    if let Some(url) = matches.get_one::<String>("sync.rpc-url") {
+        node_builder = node_builder.rpc_backfill(url.clone());
+    }
+    if let Some(depth) = matches.get_one::<u64>("max-reorg-depth") {
+        info!("Refusing fork choice updates that reorg more than {depth} blocks");
+        node_builder = node_builder.max_reorg_depth(*depth);
+    }
+    if let Some(retention) = matches.get_one::<u64>("blob-sidecars-retention") {
+        info!("Keeping the archived blob sidecars for {retention} seconds");
+        node_builder = node_builder.blob_sidecars_retention(*retention);
     }
+    if let Some(url) = matches.get_one::<String>("webhook.url") {
+        info!("Posting the changes of the canonical chain to {url}");
+        node_builder = node_builder.chain_event_listener(webhook::start_webhook(url.clone()));
+    }
+    if matches.get_flag("state-access-stats") {
+        let stats = access_stats::read_state_access_stats(&state_access_stats_path)
+            .expect("Failed to read state access stats");
+        info!("Collecting state access stats");
+        node_builder = node_builder.state_access_stats(stats);
+    }
+    if let Some(era_dir) = matches.get_one::<String>("era-dir") {
+        info!("Serving the pruned history from the era1 files of {era_dir}");
+        node_builder = node_builder.era_archive(era_dir);
+    }
+    let mut node = node_builder.build().expect("Failed to create node");
+    let store = node.store().clone();
 
-    let genesis = read_genesis_file(genesis_file_path);
-    store
-        .add_initial_state(genesis.clone())
-        .expect("Failed to create genesis block");
+    if store.address_index_enabled() {
+        info!("Indexing transactions by address");
+    }
+
+    if store.strict_validation_enabled() {
+        info!("Checking the roots and gas used of every imported block");
+    }
 
     if let Some(chain_rlp_path) = matches.get_one::<String>("import") {
         info!("Importing blocks from chain file: {}", chain_rlp_path);
@@ -160,62 +475,22 @@ async fn main() {
         import_blocks(&store, &blocks);
     }
 
-    let jwt_secret = read_jwtsecret_file(authrpc_jwtsecret);
-
-    // TODO Learn how should the key be created
-    // https://github.com/lambdaclass/ethrex/issues/836
-    //let signer = SigningKey::random(&mut OsRng);
-    let key_bytes =
-        H256::from_str("577d8278cc7748fad214b5378669b420f8221afb45ce930b7f22da49cbc545f3").unwrap();
-    let signer = SigningKey::from_slice(key_bytes.as_bytes()).unwrap();
-    let local_node_id = node_id_from_signing_key(&signer);
-
-    // TODO: If hhtp.addr is 0.0.0.0 we get the local ip as the one of the node, otherwise we use the provided one.
-    // This is fine for now, but we might need to support more options in the future.
-    let p2p_node_ip = if udp_socket_addr.ip() == Ipv4Addr::new(0, 0, 0, 0) {
-        local_ip().expect("Failed to get local ip")
-    } else {
-        udp_socket_addr.ip()
-    };
-
-    let local_p2p_node = Node {
-        ip: p2p_node_ip,
-        udp_port: udp_socket_addr.port(),
-        tcp_port: tcp_socket_addr.port(),
-        node_id: local_node_id,
-    };
-    // Create Kademlia Table here so we can access it from rpc server (for syncing)
-    let peer_table = peer_table(signer.clone());
-    // Create SyncManager
-    let syncer = SyncManager::new(peer_table.clone(), snap_sync);
-
     // TODO: Check every module starts properly.
-    let tracker = TaskTracker::new();
-    let rpc_api = ethrex_rpc::start_api(
-        http_socket_addr,
-        authrpc_socket_addr,
-        store.clone(),
-        jwt_secret,
-        local_p2p_node,
-        syncer,
-    )
-    .into_future();
-
-    // TODO Find a proper place to show node information
-    // https://github.com/lambdaclass/ethrex/issues/836
-    let enode = local_p2p_node.enode_url();
-    info!("Node: {enode}");
-
-    tracker.spawn(rpc_api);
+    node.start();
 
-    // We do not want to start the networking module if the l2 feature is enabled.
     cfg_if::cfg_if! {
         if #[cfg(feature = "l2")] {
+            use std::future::IntoFuture;
+            use tokio_util::task::TaskTracker;
+
+            let tracker = TaskTracker::new();
             let l2_proposer = ethrex_l2::start_proposer(store).into_future();
             tracker.spawn(l2_proposer);
         } else if #[cfg(feature = "dev")] {
             use ethrex_dev;
+            use tokio_util::task::TaskTracker;
 
+            let tracker = TaskTracker::new();
             let authrpc_jwtsecret = std::fs::read(authrpc_jwtsecret).expect("Failed to read JWT secret");
             let head_block_hash = {
                 let current_block_number = store.get_latest_block_number().unwrap().unwrap();
@@ -223,25 +498,20 @@ async fn main() {
             };
             let max_tries = 3;
             let url = format!("http://{authrpc_socket_addr}");
-            let block_producer_engine = ethrex_dev::block_producer::start_block_producer(url, authrpc_jwtsecret.into(), head_block_hash, max_tries, 1000, ethrex_core::Address::default());
+            let block_producer_engine = ethrex_dev::block_producer::start_block_producer(url, authrpc_jwtsecret.into(), head_block_hash, max_tries, 1000, ethrex_core::Address::default(), None);
             tracker.spawn(block_producer_engine);
-        } else {
-            let networking = ethrex_net::start_network(
-                udp_socket_addr,
-                tcp_socket_addr,
-                bootnodes,
-                signer,
-                peer_table,
-                store,
-            )
-            .into_future();
-            tracker.spawn(networking);
         }
     }
 
     tokio::select! {
         _ = tokio::signal::ctrl_c() => {
             info!("Server shut down started...");
+            node.stop().await;
+            if let Some(stats) = access_stats::state_access_stats(node.store()) {
+                if let Err(error) = access_stats::write_state_access_stats(&state_access_stats_path, &stats) {
+                    warn!("Failed to store state access stats: {error}");
+                }
+            }
             tokio::time::sleep(Duration::from_secs(1)).await;
             info!("Server shutting down!");
             return;
@@ -321,6 +591,22 @@ fn set_datadir(datadir: &str) -> String {
         .to_owned()
 }
 
+fn engine_type() -> EngineType {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "redb")] {
+            EngineType::RedB
+        } else if #[cfg(feature = "libmdbx")] {
+            EngineType::Libmdbx
+        } else {
+            EngineType::InMemory
+        }
+    }
+}
+
+fn open_store(data_dir: &str) -> Store {
+    Store::new(data_dir, engine_type()).expect("Failed to create Store")
+}
+
 fn import_blocks(store: &Store, blocks: &Vec<Block>) {
     let size = blocks.len();
     for block in blocks {
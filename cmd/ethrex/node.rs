@@ -0,0 +1,443 @@
+//! Library API to run an ethrex node embedded in another Rust project,
+//! such as tests or L2 stacks, without spawning the `ethrex` binary.
+//!
+//! ```no_run
+//! # async fn run(genesis: ethrex_core::types::Genesis) {
+//! use ethrex::{NodeBuilder, SyncMode};
+//! use ethrex_storage::EngineType;
+//!
+//! let mut node = NodeBuilder::new(genesis)
+//!     .storage("ethrex-data", EngineType::Libmdbx)
+//!     .http("127.0.0.1:8545".parse().unwrap())
+//!     .sync_mode(SyncMode::Full)
+//!     .build()
+//!     .expect("Failed to create node");
+//! node.start();
+//! // ...
+//! node.stop().await;
+//! # }
+//! ```
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use ethrex_blockchain::{
+    payload::PayloadBuilderConfig, payload_manager::DEFAULT_GET_PAYLOAD_DEADLINE,
+};
+use ethrex_core::types::{ChainEvent, Genesis, StateAccessStats};
+use ethrex_net::{
+    bootnode::BootNode, node_id_from_signing_key, peer_table, rpc_backfill::RpcBackfillSource,
+    sync::SyncManager, types::Node, KademliaTable,
+};
+use ethrex_rpc::{api_tokens::ApiTokens, tls::TlsConfig, GasPriceOracleConfig};
+use ethrex_storage::{error::StoreError, EngineType, Store};
+use k256::ecdsa::SigningKey;
+use local_ip_address::local_ip;
+use rand::rngs::OsRng;
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::info;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NodeError {
+    #[error("The data directory is not valid unicode")]
+    InvalidDataDir,
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("Failed to get the local ip: {0}")]
+    LocalIp(#[from] local_ip_address::Error),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    #[default]
+    Full,
+    Snap,
+}
+
+/// Configuration of an embedded node, every setting defaults to the one of the `ethrex` binary,
+/// except the storage which is kept in memory and the node key which is random
+pub struct NodeBuilder {
+    genesis: Genesis,
+    data_dir: PathBuf,
+    engine_type: EngineType,
+    address_index: bool,
+    strict_validation: bool,
+    max_reorg_depth: Option<u64>,
+    blob_sidecars_retention: Option<u64>,
+    chain_event_listener: Option<Box<dyn Fn(ChainEvent) + Send + Sync>>,
+    state_access_stats: Option<StateAccessStats>,
+    era_dir: Option<PathBuf>,
+    http_addr: SocketAddr,
+    http_tls: Option<TlsConfig>,
+    http_api_tokens: Option<ApiTokens>,
+    gas_price_oracle: GasPriceOracleConfig,
+    get_payload_deadline: Duration,
+    payload_builder: PayloadBuilderConfig,
+    authrpc_addr: SocketAddr,
+    jwt_secret: Bytes,
+    networking: bool,
+    tcp_addr: SocketAddr,
+    udp_addr: SocketAddr,
+    bootnodes: Vec<BootNode>,
+    signer: SigningKey,
+    sync_mode: SyncMode,
+    rpc_backfill_url: Option<String>,
+}
+
+impl NodeBuilder {
+    pub fn new(genesis: Genesis) -> Self {
+        let localhost = Ipv4Addr::LOCALHOST.into();
+        Self {
+            genesis,
+            data_dir: std::env::temp_dir().join("ethrex"),
+            engine_type: EngineType::InMemory,
+            address_index: false,
+            strict_validation: false,
+            max_reorg_depth: None,
+            blob_sidecars_retention: None,
+            chain_event_listener: None,
+            state_access_stats: None,
+            era_dir: None,
+            http_addr: SocketAddr::new(localhost, 8545),
+            http_tls: None,
+            http_api_tokens: None,
+            gas_price_oracle: GasPriceOracleConfig::default(),
+            get_payload_deadline: DEFAULT_GET_PAYLOAD_DEADLINE,
+            payload_builder: PayloadBuilderConfig::default(),
+            authrpc_addr: SocketAddr::new(localhost, 8551),
+            jwt_secret: rand::random::<[u8; 32]>().to_vec().into(),
+            networking: true,
+            tcp_addr: SocketAddr::new(localhost, 30303),
+            udp_addr: SocketAddr::new(localhost, 30303),
+            bootnodes: vec![],
+            signer: SigningKey::random(&mut OsRng),
+            sync_mode: SyncMode::default(),
+            rpc_backfill_url: None,
+        }
+    }
+
+    /// Sets the genesis of the network the node is part of
+    pub fn network(mut self, genesis: Genesis) -> Self {
+        self.genesis = genesis;
+        self
+    }
+
+    /// Sets the directory the store and the known peers are kept in, and the engine of the store
+    pub fn storage(mut self, data_dir: impl Into<PathBuf>, engine_type: EngineType) -> Self {
+        self.data_dir = data_dir.into();
+        self.engine_type = engine_type;
+        self
+    }
+
+    /// Indexes the transactions of every added block by their sender and recipient
+    pub fn address_index(mut self, enabled: bool) -> Self {
+        self.address_index = enabled;
+        self
+    }
+
+    /// Checks the roots and gas used in the header of every added block against its body and
+    /// receipts
+    pub fn strict_validation(mut self, enabled: bool) -> Self {
+        self.strict_validation = enabled;
+        self
+    }
+
+    /// Refuses the fork choice updates that remove more than the given amount of canonical blocks
+    pub fn max_reorg_depth(mut self, depth: u64) -> Self {
+        self.max_reorg_depth = Some(depth);
+        self
+    }
+
+    /// Sets the seconds the blob sidecars archived from the mempool are kept for
+    pub fn blob_sidecars_retention(mut self, seconds: u64) -> Self {
+        self.blob_sidecars_retention = Some(seconds);
+        self
+    }
+
+    /// Sends the changes of the canonical chain made by fork choice updates to the given
+    /// function, which should hand them off quickly
+    pub fn chain_event_listener(
+        mut self,
+        listener: impl Fn(ChainEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.chain_event_listener = Some(Box::new(listener));
+        self
+    }
+
+    /// Collects the last block each account and storage slot was accessed in by the executed
+    /// blocks, starting from the given stats
+    pub fn state_access_stats(mut self, initial: StateAccessStats) -> Self {
+        self.state_access_stats = Some(initial);
+        self
+    }
+
+    /// Serves the pruned pre-merge history from the era1 files of the given directory
+    pub fn era_archive(mut self, era_dir: impl Into<PathBuf>) -> Self {
+        self.era_dir = Some(era_dir.into());
+        self
+    }
+
+    pub fn http(mut self, addr: SocketAddr) -> Self {
+        self.http_addr = addr;
+        self
+    }
+
+    pub fn http_tls(mut self, tls: TlsConfig) -> Self {
+        self.http_tls = Some(tls);
+        self
+    }
+
+    /// Requires the requests to the HTTP RPC to carry one of the tokens, which restricts the
+    /// namespaces they can call
+    pub fn http_api_tokens(mut self, tokens: ApiTokens) -> Self {
+        self.http_api_tokens = Some(tokens);
+        self
+    }
+
+    /// Sets how the tips suggested by `eth_gasPrice` and `eth_maxPriorityFeePerGas` are estimated
+    pub fn gas_price_oracle(mut self, config: GasPriceOracleConfig) -> Self {
+        self.gas_price_oracle = config;
+        self
+    }
+
+    /// Sets the time into the slot by which the payloads built for the consensus client have to
+    /// be ready, they stop being improved before then
+    pub fn get_payload_deadline(mut self, deadline: Duration) -> Self {
+        self.get_payload_deadline = deadline;
+        self
+    }
+
+    /// Sets how the payloads built for the consensus client are filled
+    pub fn payload_builder(mut self, config: PayloadBuilderConfig) -> Self {
+        self.payload_builder = config;
+        self
+    }
+
+    pub fn authrpc(mut self, addr: SocketAddr, jwt_secret: Bytes) -> Self {
+        self.authrpc_addr = addr;
+        self.jwt_secret = jwt_secret;
+        self
+    }
+
+    /// Enables or disables the discovery and p2p services, they are enabled by default
+    pub fn networking(mut self, enabled: bool) -> Self {
+        self.networking = enabled;
+        self
+    }
+
+    pub fn p2p(mut self, tcp_addr: SocketAddr, udp_addr: SocketAddr) -> Self {
+        self.tcp_addr = tcp_addr;
+        self.udp_addr = udp_addr;
+        self
+    }
+
+    pub fn bootnodes(mut self, bootnodes: Vec<BootNode>) -> Self {
+        self.bootnodes = bootnodes;
+        self
+    }
+
+    /// Sets the key the node is identified with in the network
+    pub fn signer(mut self, signer: SigningKey) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    pub fn sync_mode(mut self, sync_mode: SyncMode) -> Self {
+        self.sync_mode = sync_mode;
+        self
+    }
+
+    /// Backfills the blocks peers can't serve from the given RPC endpoint
+    pub fn rpc_backfill(mut self, url: String) -> Self {
+        self.rpc_backfill_url = Some(url);
+        self
+    }
+
+    /// Opens the store and adds the genesis state to it, the services are not started
+    pub fn build(mut self) -> Result<EthrexNode, NodeError> {
+        let data_dir = self
+            .data_dir
+            .to_str()
+            .ok_or(NodeError::InvalidDataDir)?
+            .to_owned();
+        let mut store = Store::new(&data_dir, self.engine_type)?;
+        if self.address_index {
+            store.enable_address_index();
+        }
+        if self.strict_validation {
+            store.enable_strict_validation();
+        }
+        if let Some(depth) = self.max_reorg_depth {
+            store.set_max_reorg_depth(depth);
+        }
+        if let Some(retention) = self.blob_sidecars_retention {
+            store.set_blob_sidecars_retention(retention);
+        }
+        if let Some(listener) = self.chain_event_listener.take() {
+            store.set_chain_event_listener(listener);
+        }
+        if let Some(stats) = self.state_access_stats.take() {
+            store.enable_state_access_stats(stats);
+        }
+        if let Some(era_dir) = &self.era_dir {
+            store.set_era_archive(era_dir.clone());
+        }
+        store.add_initial_state(self.genesis.clone())?;
+
+        // When listening on every interface, the node is advertised with its local ip
+        let ip = if self.udp_addr.ip().is_unspecified() {
+            local_ip()?
+        } else {
+            self.udp_addr.ip()
+        };
+        let local_p2p_node = Node {
+            ip,
+            udp_port: self.udp_addr.port(),
+            tcp_port: self.tcp_addr.port(),
+            node_id: node_id_from_signing_key(&self.signer),
+        };
+        Ok(EthrexNode {
+            store,
+            local_p2p_node,
+            peer_table: peer_table(self.signer.clone()),
+            known_peers_path: ethrex_net::known_peers::known_peers_path(&data_dir),
+            ban_list_path: ethrex_net::ban_list::ban_list_path(&data_dir),
+            config: self,
+            tasks: vec![],
+        })
+    }
+}
+
+/// A node created by the [NodeBuilder], its store can be used before and while its services run
+pub struct EthrexNode {
+    store: Store,
+    local_p2p_node: Node,
+    peer_table: Arc<Mutex<KademliaTable>>,
+    known_peers_path: PathBuf,
+    ban_list_path: PathBuf,
+    config: NodeBuilder,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl EthrexNode {
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn local_p2p_node(&self) -> Node {
+        self.local_p2p_node
+    }
+
+    /// Spawns the RPC servers and, if enabled, the networking services on the current runtime.
+    /// Has no effect if the node was already started.
+    pub fn start(&mut self) {
+        if !self.tasks.is_empty() {
+            return;
+        }
+        let config = &self.config;
+        let rpc_backfill = config.rpc_backfill_url.clone().map(RpcBackfillSource::new);
+        let syncer = SyncManager::new(
+            self.peer_table.clone(),
+            config.sync_mode == SyncMode::Snap,
+            rpc_backfill,
+        );
+        self.tasks.push(tokio::spawn(ethrex_rpc::start_api(
+            config.http_addr,
+            config.authrpc_addr,
+            self.store.clone(),
+            config.jwt_secret.clone(),
+            self.local_p2p_node,
+            syncer,
+            config.http_tls.clone(),
+            config.http_api_tokens.clone(),
+            config.gas_price_oracle,
+            config.get_payload_deadline,
+            config.payload_builder.clone(),
+        )));
+        info!("Node: {}", self.local_p2p_node.enode_url());
+
+        if config.networking {
+            self.tasks.push(tokio::spawn(ethrex_net::start_network(
+                config.udp_addr,
+                config.tcp_addr,
+                config.bootnodes.clone(),
+                self.known_peers_path.clone(),
+                self.ban_list_path.clone(),
+                config.signer.clone(),
+                self.peer_table.clone(),
+                self.store.clone(),
+            )));
+        }
+    }
+
+    /// Stops the services of the node, storing the known peers so they are used on the next start
+    pub async fn stop(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+            let _ = task.await;
+        }
+        if self.config.networking {
+            ethrex_net::known_peers::store_known_peers(&self.peer_table, &self.known_peers_path)
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs::File,
+        io::{BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+    };
+
+    fn free_addr() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn embedded_node_serves_rpc_requests() {
+        let file = File::open("../../test_data/genesis-execution-api.json").unwrap();
+        let genesis: Genesis = serde_json::from_reader(BufReader::new(file)).unwrap();
+        let chain_id = genesis.config.chain_id;
+        let http_addr = free_addr();
+        let mut node = NodeBuilder::new(genesis)
+            .http(http_addr)
+            .authrpc(free_addr(), Bytes::from_static(&[0; 32]))
+            .networking(false)
+            .build()
+            .unwrap();
+        assert_eq!(node.store().get_latest_block_number().unwrap(), Some(0));
+        node.start();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_chainId","params":[]}"#;
+        let response = tokio::task::spawn_blocking(move || {
+            let mut stream = TcpStream::connect(http_addr).unwrap();
+            write!(
+                stream,
+                "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        })
+        .await
+        .unwrap();
+        assert!(response.ends_with(&format!(r#""result":"{chain_id:#x}"}}"#)));
+
+        node.stop().await;
+        assert!(TcpStream::connect(http_addr).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use ethrex_blockchain::events::ChainEvent;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Time a webhook request may take before it is given up on
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Posts the changes of the canonical chain as JSON events to the given URL, one request per
+/// event and in the order they are made. Events that can't be delivered are dropped.
+/// Returns the listener the events have to be sent to.
+pub fn start_webhook(url: String) -> impl Fn(ChainEvent) + Send + Sync + 'static {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        while let Some(event) = receiver.recv().await {
+            let result = client
+                .post(&url)
+                .timeout(WEBHOOK_TIMEOUT)
+                .json(&event_json(&event))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(error) = result {
+                warn!("Failed to post chain event to webhook: {error}");
+            }
+        }
+    });
+    move |event| {
+        let _ = sender.send(event);
+    }
+}
+
+fn event_json(event: &ChainEvent) -> Value {
+    match event {
+        ChainEvent::NewHead { number, hash } => json!({
+            "type": "newHead",
+            "number": format!("{number:#x}"),
+            "hash": hash,
+        }),
+        ChainEvent::Reorg {
+            number,
+            hash,
+            depth,
+            dropped,
+        } => json!({
+            "type": "reorg",
+            "number": format!("{number:#x}"),
+            "hash": hash,
+            "depth": format!("{depth:#x}"),
+            "droppedHashes": dropped,
+        }),
+        ChainEvent::Finalized { number, hash } => json!({
+            "type": "finalized",
+            "number": format!("{number:#x}"),
+            "hash": hash,
+        }),
+    }
+}
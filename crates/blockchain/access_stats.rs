@@ -0,0 +1,48 @@
+use std::{fs, io, path::Path};
+
+use ethrex_core::{types::BlockNumber, Address, H256};
+use ethrex_rlp::{decode::RLPDecode, encode::RLPEncode};
+use ethrex_storage::Store;
+
+pub use ethrex_core::types::StateAccessStats;
+
+/// Name of the file inside the data directory where the collected stats are persisted
+pub const STATE_ACCESS_STATS_FILE_NAME: &str = "state_access_stats.rlp";
+
+/// Returns a copy of the stats collected so far, if the collection is enabled in the store
+pub fn state_access_stats(store: &Store) -> Option<StateAccessStats> {
+    let stats = store.state_access_stats()?.lock().ok()?;
+    Some(stats.clone())
+}
+
+/// Records the state accessed while executing the given block, if the collection is enabled.
+/// The accessed state is only computed if it is going to be recorded.
+pub(crate) fn record_state_access(
+    store: &Store,
+    block_number: BlockNumber,
+    accessed: impl FnOnce() -> Vec<(Address, Vec<H256>)>,
+) {
+    if let Some(stats) = store.state_access_stats() {
+        if let Ok(mut stats) = stats.lock() {
+            stats.record(block_number, accessed());
+        }
+    }
+}
+
+/// Reads the stats stored in the given file, a missing file means no stats were collected yet
+pub fn read_state_access_stats(path: &Path) -> io::Result<StateAccessStats> {
+    let encoded = match fs::read(path) {
+        Ok(encoded) => encoded,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+        Err(err) => return Err(err),
+    };
+    StateAccessStats::decode(&encoded)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+/// Writes the stats to the given file, replacing its previous content
+pub fn write_state_access_stats(path: &Path, stats: &StateAccessStats) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, stats.encode_to_vec())?;
+    fs::rename(tmp_path, path)
+}
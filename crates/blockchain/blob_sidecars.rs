@@ -0,0 +1,105 @@
+use ethrex_core::types::{BlobsBundle, Block, BlockHash, Transaction};
+use ethrex_storage::{error::StoreError, Store};
+use tracing::{debug, info};
+
+/// Time the consensus layer has to serve blob sidecars for, MIN_EPOCHS_FOR_BLOB_SIDECARS_REQUESTS
+/// epochs of 32 slots of 12 seconds, around 18 days
+pub const BLOB_SIDECARS_RETENTION_SECONDS: u64 = 4096 * 32 * 12;
+/// The archived blob sidecars are pruned once every this amount of blocks
+pub const BLOB_SIDECARS_PRUNE_INTERVAL: u64 = 256;
+
+/// Returns the time the archived blob sidecars of the store are kept for
+pub fn blob_sidecars_retention(storage: &Store) -> u64 {
+    storage
+        .blob_sidecars_retention()
+        .unwrap_or(BLOB_SIDECARS_RETENTION_SECONDS)
+}
+
+/// Archives the sidecars of the blob transactions of a stored block that are known from the
+/// mempool, so they can still be served once the transactions leave it. Every
+/// [BLOB_SIDECARS_PRUNE_INTERVAL] blocks, the sidecars older than the retention window are pruned.
+pub fn archive_blob_sidecars(
+    block: &Block,
+    block_hash: BlockHash,
+    storage: &Store,
+) -> Result<(), StoreError> {
+    let mut sidecars = BlobsBundle::default();
+    for transaction in &block.body.transactions {
+        let Transaction::EIP4844Transaction(_) = transaction else {
+            continue;
+        };
+        match storage.get_blobs_bundle_from_pool(transaction.compute_hash())? {
+            Some(bundle) => sidecars += bundle,
+            None => debug!(
+                "Sidecar of blob transaction {:#x} is unknown, it won't be archived",
+                transaction.compute_hash()
+            ),
+        }
+    }
+    if !sidecars.blobs.is_empty() {
+        storage.add_blob_sidecars(block_hash, block.header.timestamp, sidecars)?;
+    }
+
+    if block
+        .header
+        .number
+        .is_multiple_of(BLOB_SIDECARS_PRUNE_INTERVAL)
+    {
+        let pruned = storage.prune_blob_sidecars(
+            block
+                .header
+                .timestamp
+                .saturating_sub(blob_sidecars_retention(storage)),
+        )?;
+        if pruned > 0 {
+            info!("Pruned the blob sidecars of {pruned} blocks");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethrex_core::types::{BlockHeader, EIP4844Transaction, BYTES_PER_BLOB};
+    use ethrex_storage::EngineType;
+
+    #[test]
+    fn sidecars_known_from_the_mempool_are_archived_and_pruned() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let transaction = Transaction::EIP4844Transaction(EIP4844Transaction::default());
+        let bundle = BlobsBundle {
+            blobs: vec![[1; BYTES_PER_BLOB]],
+            commitments: vec![[2; 48]],
+            proofs: vec![[3; 48]],
+        };
+        storage
+            .add_blobs_bundle_to_pool(transaction.compute_hash(), bundle.clone())
+            .unwrap();
+        let mut block = Block::new(
+            BlockHeader {
+                number: 1,
+                timestamp: 100,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        block.body.transactions.push(transaction);
+        let block_hash = block.hash();
+        archive_blob_sidecars(&block, block_hash, &storage).unwrap();
+        assert_eq!(storage.get_blob_sidecars(block_hash).unwrap(), Some(bundle));
+
+        // The next pruning is after the retention window
+        let later_block = Block::new(
+            BlockHeader {
+                number: BLOB_SIDECARS_PRUNE_INTERVAL,
+                timestamp: 101 + BLOB_SIDECARS_RETENTION_SECONDS,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        archive_blob_sidecars(&later_block, later_block.hash(), &storage).unwrap();
+        assert!(storage.get_blob_sidecars(block_hash).unwrap().is_none());
+    }
+}
@@ -1,13 +1,19 @@
+pub mod access_stats;
+pub mod blob_sidecars;
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod fork_choice;
 pub mod mempool;
 pub mod payload;
+pub mod payload_job;
+pub mod payload_manager;
 mod smoke_test;
 
 use constants::{GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK, MAX_BLOB_NUMBER_PER_BLOCK};
 use error::{ChainError, InvalidBlockError};
 use ethrex_core::types::{
+    compute_logs_bloom, compute_receipts_root, compute_transactions_root, compute_withdrawals_root,
     validate_block_header, validate_cancun_header_fields, validate_no_cancun_header_fields, Block,
     BlockHash, BlockHeader, BlockNumber, EIP4844Transaction, Receipt, Transaction,
 };
@@ -16,6 +22,7 @@ use ethrex_core::H256;
 use ethrex_storage::error::StoreError;
 use ethrex_storage::Store;
 use ethrex_vm::{evm_state, execute_block, spec_id, EvmState, SpecId};
+use tracing::info_span;
 
 //TODO: Implement a struct Chain or BlockChain to encapsulate
 //functionality and canonical chain state and config
@@ -30,6 +37,7 @@ pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
     use ethrex_vm::get_state_transitions;
 
     let block_hash = block.header.compute_block_hash();
+    let _span = info_span!("add_block", number = block.header.number, hash = ?block_hash).entered();
 
     // Validate if it can be the new head and find the parent
     let Ok(parent_header) = find_parent_header(&block.header, storage) else {
@@ -43,8 +51,17 @@ pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
     validate_block(block, &parent_header, &state)?;
 
     let receipts = execute_block(block, &mut state)?;
+    validate_requests_hash(&block.header, &mut state, &receipts)?;
 
     validate_gas_used(&receipts, &block.header)?;
+    validate_logs_bloom(&receipts, &block.header)?;
+    if storage.strict_validation_enabled() {
+        validate_block_commitments(block, &receipts)?;
+    }
+
+    access_stats::record_state_access(storage, block.header.number, || {
+        ethrex_vm::get_accessed_state(&state)
+    });
 
     let account_updates = get_state_transitions(&mut state);
 
@@ -57,9 +74,12 @@ pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
 
     // Check state root matches the one in block header after execution
     validate_state_root(&block.header, new_state_root)?;
+    storage.add_state_diff_layer(&block.header, block_hash, &account_updates)?;
+    storage.advance_snapshot(&block.header, block_hash, &account_updates)?;
 
     store_block(storage, block.clone())?;
     store_receipts(storage, receipts, block_hash)?;
+    blob_sidecars::archive_blob_sidecars(block, block_hash, storage)?;
 
     Ok(())
 }
@@ -72,6 +92,7 @@ pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
 #[cfg(feature = "levm")]
 pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
     let block_hash = block.header.compute_block_hash();
+    let _span = info_span!("add_block", number = block.header.number, hash = ?block_hash).entered();
 
     // Validate if it can be the new head and find the parent
     let Ok(parent_header) = find_parent_header(&block.header, storage) else {
@@ -84,12 +105,28 @@ pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
     // Validate the block pre-execution
     validate_block(block, &parent_header, &state)?;
 
+    // The requests of Prague blocks are not extracted when executing with levm yet
     let (receipts, account_updates) = execute_block(block, &mut state)?;
 
     // Note: these is commented because it is still being used in development.
     // dbg!(&account_updates);
 
     validate_gas_used(&receipts, &block.header)?;
+    validate_logs_bloom(&receipts, &block.header)?;
+    if storage.strict_validation_enabled() {
+        validate_block_commitments(block, &receipts)?;
+    }
+
+    // Only the state written by the block is known when executing with levm
+    access_stats::record_state_access(storage, block.header.number, || {
+        account_updates
+            .iter()
+            .map(|update| {
+                let slots = update.added_storage.keys().copied().collect();
+                (update.address, slots)
+            })
+            .collect()
+    });
 
     // Apply the account updates over the last block's state and compute the new state root
     let new_state_root = state
@@ -100,9 +137,12 @@ pub fn add_block(block: &Block, storage: &Store) -> Result<(), ChainError> {
 
     // Check state root matches the one in block header after execution
     validate_state_root(&block.header, new_state_root)?;
+    storage.add_state_diff_layer(&block.header, block_hash, &account_updates)?;
+    storage.advance_snapshot(&block.header, block_hash, &account_updates)?;
 
     store_block(storage, block.clone())?;
     store_receipts(storage, receipts, block_hash)?;
+    blob_sidecars::archive_blob_sidecars(block, block_hash, storage)?;
 
     Ok(())
 }
@@ -139,6 +179,29 @@ pub fn validate_state_root(
     }
 }
 
+/// Checks the requests hash of a Prague block against the requests made during its execution.
+/// Must be called after executing the block, as reading the requests dequeues them.
+#[cfg(not(feature = "levm"))]
+pub fn validate_requests_hash(
+    block_header: &BlockHeader,
+    state: &mut EvmState,
+    receipts: &[Receipt],
+) -> Result<(), ChainError> {
+    use ethrex_core::types::compute_requests_hash;
+
+    let Some(requests_hash) = block_header.requests_hash else {
+        return Ok(());
+    };
+    let spec_id = spec_id(&state.chain_config()?, block_header.timestamp);
+    let requests = ethrex_vm::extract_requests(state, block_header, spec_id, receipts)?;
+    if compute_requests_hash(&requests) != requests_hash {
+        return Err(ChainError::InvalidBlock(
+            InvalidBlockError::RequestsHashMismatch,
+        ));
+    }
+    Ok(())
+}
+
 // Returns the hash of the head of the canonical chain (the latest valid hash).
 pub fn latest_canonical_block_hash(storage: &Store) -> Result<H256, ChainError> {
     if let Some(latest_block_number) = storage.get_latest_block_number()? {
@@ -199,10 +262,7 @@ pub fn is_canonical(
     block_number: BlockNumber,
     block_hash: BlockHash,
 ) -> Result<bool, StoreError> {
-    match store.get_canonical_block_hash(block_number)? {
-        Some(hash) if hash == block_hash => Ok(true),
-        _ => Ok(false),
-    }
+    Ok(store.get_canonical_block_number(block_hash)? == Some(block_number))
 }
 
 pub fn validate_gas_used(
@@ -220,6 +280,60 @@ pub fn validate_gas_used(
     Ok(())
 }
 
+/// Checks the header's logs bloom is the union of the blooms of the block's receipts
+pub fn validate_logs_bloom(
+    receipts: &[Receipt],
+    block_header: &BlockHeader,
+) -> Result<(), ChainError> {
+    if compute_logs_bloom(receipts) != block_header.logs_bloom {
+        return Err(ChainError::InvalidBlock(
+            InvalidBlockError::LogsBloomMismatch,
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the transactions, receipts and withdrawals roots and the gas used of the header
+/// against the block's body and the receipts obtained by executing it.
+/// The block hash already commits to these fields, so this is only needed to detect
+/// inconsistencies in blocks produced by other clients or previous releases.
+pub fn validate_block_commitments(block: &Block, receipts: &[Receipt]) -> Result<(), ChainError> {
+    let header = &block.header;
+    if compute_transactions_root(&block.body.transactions) != header.transactions_root {
+        return Err(ChainError::InvalidBlock(
+            InvalidBlockError::TransactionsRootMismatch,
+        ));
+    }
+    if compute_receipts_root(receipts) != header.receipts_root {
+        return Err(ChainError::InvalidBlock(
+            InvalidBlockError::ReceiptsRootMismatch,
+        ));
+    }
+    if block
+        .body
+        .withdrawals
+        .as_deref()
+        .map(compute_withdrawals_root)
+        != header.withdrawals_root
+    {
+        return Err(ChainError::InvalidBlock(
+            InvalidBlockError::WithdrawalsRootMismatch,
+        ));
+    }
+    // Gas used by each transaction must add up to the header's, even for empty blocks
+    let mut gas_used = 0;
+    for receipt in receipts {
+        if receipt.cumulative_gas_used < gas_used {
+            return Err(ChainError::InvalidBlock(InvalidBlockError::GasUsedMismatch));
+        }
+        gas_used = receipt.cumulative_gas_used;
+    }
+    if receipts.len() != block.body.transactions.len() || gas_used != header.gas_used {
+        return Err(ChainError::InvalidBlock(InvalidBlockError::GasUsedMismatch));
+    }
+    Ok(())
+}
+
 fn verify_blob_gas_usage(block: &Block) -> Result<(), ChainError> {
     let mut blob_gas_used = 0_u64;
     let mut blobs_in_block = 0_u64;
@@ -59,3 +59,6 @@ pub const MIN_BASE_FEE_PER_BLOB_GAS: u64 = 1;
 pub const GAS_LIMIT_BOUND_DIVISOR: u64 = 1024;
 
 pub const MIN_GAS_LIMIT: u64 = 5000;
+
+/// Maximum number of withdrawals per payload, MAX_WITHDRAWALS_PER_PAYLOAD of the consensus specs
+pub const MAX_WITHDRAWALS_PER_PAYLOAD: usize = 16;
@@ -1,7 +1,13 @@
-use crate::utils::engine_client::{errors::EngineClientError, EngineClient};
+use crate::utils::{
+    engine_client::{errors::EngineClientError, EngineClient},
+    relay_client::{choose_payload, PayloadSource, RelayClient},
+};
 use bytes::Bytes;
 use ethereum_types::{Address, H256};
-use ethrex_rpc::types::fork_choice::{ForkChoiceState, PayloadAttributesV3};
+use ethrex_rpc::types::{
+    fork_choice::{ForkChoiceState, PayloadAttributes},
+    payload::PayloadValidationStatus,
+};
 use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -12,8 +18,10 @@ pub async fn start_block_producer(
     max_tries: u32,
     block_production_interval_ms: u64,
     coinbase_address: Address,
+    relay_url: Option<String>,
 ) -> Result<(), EngineClientError> {
     let engine_client = EngineClient::new(&execution_client_auth_url, jwt_secret);
+    let relay_client = relay_url.as_deref().map(RelayClient::new);
 
     let mut head_block_hash: H256 = head_block_hash;
     let mut tries = 0;
@@ -25,13 +33,15 @@ pub async fn start_block_producer(
             finalized_block_hash: head_block_hash,
         };
 
-        let payload_attributes = PayloadAttributesV3 {
+        let payload_attributes = PayloadAttributes {
             timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
             suggested_fee_recipient: coinbase_address,
+            withdrawals: Some(vec![]),
+            parent_beacon_block_root: Some(H256::zero()),
             ..Default::default()
         };
         let fork_choice_response = match engine_client
-            .engine_forkchoice_updated_v3(fork_choice_state, Some(payload_attributes))
+            .engine_forkchoice_updated_v3(fork_choice_state, Some(payload_attributes.clone()))
             .await
         {
             Ok(response) => response,
@@ -57,33 +67,86 @@ pub async fn start_block_producer(
                 continue;
             }
         };
-        let payload_status = match engine_client
-            .engine_new_payload_v3(
-                execution_payload_response.execution_payload,
-                execution_payload_response
-                    .blobs_bundle
-                    .commitments
-                    .iter()
-                    .map(|commitment| {
-                        let mut hasher = Sha256::new();
-                        hasher.update(commitment);
-                        let mut hash = hasher.finalize();
-                        hash[0] = 0x01;
-                        H256::from_slice(&hash)
-                    })
-                    .collect(),
-                Default::default(),
-            )
-            .await
-        {
-            Ok(response) => response,
-            Err(error) => {
-                tracing::error!(
-                    "Failed to produce block: error sending engine_newPayloadV3: {error}"
-                );
-                tries += 1;
-                continue;
+        // Propose the relay payload if it is worth more, falling back to the local one if it is rejected
+        let mut relay_payload_response = None;
+        if let Some(relay_client) = &relay_client {
+            match relay_client
+                .builder_get_payload_v3(head_block_hash, &payload_attributes)
+                .await
+            {
+                Ok(Some(response)) => relay_payload_response = Some(response),
+                Ok(None) => tracing::info!(
+                    "Relay {} has no payload on top of block {head_block_hash:#x}, proposing local payload",
+                    relay_client.url()
+                ),
+                Err(error) => tracing::warn!(
+                    "Failed to get payload from relay {}, proposing local payload: {error}",
+                    relay_client.url()
+                ),
+            }
+        }
+        let source = choose_payload(
+            &execution_payload_response,
+            relay_payload_response.as_ref(),
+            head_block_hash,
+            payload_attributes.timestamp,
+        );
+        let mut candidates = vec![];
+        if let Some(relay_payload_response) = relay_payload_response {
+            tracing::info!(
+                "Payload choice on top of block {head_block_hash:#x}: local block value {}, relay block value {}, proposing {source} payload",
+                execution_payload_response.block_value,
+                relay_payload_response.block_value
+            );
+            if source == PayloadSource::Relay {
+                candidates.push((PayloadSource::Relay, relay_payload_response));
+            }
+        }
+        candidates.push((PayloadSource::Local, execution_payload_response));
+
+        let mut payload_status = None;
+        for (source, candidate) in candidates {
+            let expected_blob_versioned_hashes = candidate
+                .blobs_bundle
+                .commitments
+                .iter()
+                .map(|commitment| {
+                    let mut hasher = Sha256::new();
+                    hasher.update(commitment);
+                    let mut hash = hasher.finalize();
+                    hash[0] = 0x01;
+                    H256::from_slice(&hash)
+                })
+                .collect();
+            match engine_client
+                .engine_new_payload_v3(
+                    candidate.execution_payload,
+                    expected_blob_versioned_hashes,
+                    Default::default(),
+                )
+                .await
+            {
+                Ok(response) if source == PayloadSource::Local => {
+                    payload_status = Some(response);
+                    break;
+                }
+                Ok(response) if matches!(response.status, PayloadValidationStatus::Valid) => {
+                    payload_status = Some(response);
+                    break;
+                }
+                Ok(response) => tracing::warn!(
+                    "Relay payload was rejected with status {:?}, proposing local payload: {}",
+                    response.status,
+                    response.validation_error.unwrap_or_default()
+                ),
+                Err(error) => tracing::error!(
+                    "Failed to produce block: error sending engine_newPayloadV3 with {source} payload: {error}"
+                ),
             }
+        }
+        let Some(payload_status) = payload_status else {
+            tries += 1;
+            continue;
         };
         let produced_block_hash = payload_status
             .latest_valid_hash
@@ -11,7 +11,7 @@ use ethrex_rpc::{
         ExchangeCapabilitiesRequest,
     },
     types::{
-        fork_choice::{ForkChoiceResponse, ForkChoiceState, PayloadAttributesV3},
+        fork_choice::{ForkChoiceResponse, ForkChoiceState, PayloadAttributes},
         payload::{ExecutionPayloadResponse, ExecutionPayloadV3, PayloadStatus},
     },
     utils::{RpcErrorResponse, RpcRequest, RpcSuccessResponse},
@@ -77,7 +77,7 @@ impl EngineClient {
     pub async fn engine_forkchoice_updated_v3(
         &self,
         state: ForkChoiceState,
-        payload_attributes: Option<PayloadAttributesV3>,
+        payload_attributes: Option<PayloadAttributes>,
     ) -> Result<ForkChoiceResponse, EngineClientError> {
         let request = ForkChoiceUpdatedV3 {
             fork_choice_state: state,
@@ -1 +1,2 @@
 pub mod engine_client;
+pub mod relay_client;
@@ -0,0 +1,176 @@
+use ethereum_types::H256;
+use ethrex_rpc::{
+    types::{fork_choice::PayloadAttributes, payload::ExecutionPayloadResponse},
+    utils::RpcRequest,
+};
+use reqwest::Client;
+use serde_json::json;
+use std::fmt;
+
+use crate::utils::engine_client::RpcResponse;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelayClientError {
+    #[error("reqwest error: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("{0}")]
+    SerdeJSONError(#[from] serde_json::Error),
+    #[error("{0}")]
+    RPCError(String),
+}
+
+/// Client for an external builder relay, which offers payloads built outside of the node
+/// that can be proposed instead of the locally built ones if they are worth more
+pub struct RelayClient {
+    client: Client,
+    relay_url: String,
+}
+
+/// Where the proposed payload was built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadSource {
+    Local,
+    Relay,
+}
+
+impl fmt::Display for PayloadSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadSource::Local => write!(f, "local"),
+            PayloadSource::Relay => write!(f, "relay"),
+        }
+    }
+}
+
+impl RelayClient {
+    pub fn new(relay_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            relay_url: relay_url.to_string(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.relay_url
+    }
+
+    /// Requests the best payload the relay built on top of the given block with the given attributes.
+    /// Returns `None` if the relay has no payload to offer.
+    pub async fn builder_get_payload_v3(
+        &self,
+        parent_hash: H256,
+        payload_attributes: &PayloadAttributes,
+    ) -> Result<Option<ExecutionPayloadResponse>, RelayClientError> {
+        let request = RpcRequest {
+            method: "builder_getPayloadV3".to_string(),
+            params: Some(vec![json!(parent_hash), json!(payload_attributes)]),
+            ..Default::default()
+        };
+        let response = self
+            .client
+            .post(&self.relay_url)
+            .header("content-type", "application/json")
+            .body(serde_json::ser::to_string(&request)?)
+            .send()
+            .await?
+            .json::<RpcResponse>()
+            .await?;
+        match response {
+            RpcResponse::Success(result) => Ok(serde_json::from_value(result.result)?),
+            RpcResponse::Error(error_response) => {
+                Err(RelayClientError::RPCError(error_response.error.message))
+            }
+        }
+    }
+}
+
+/// Chooses which payload to propose. The relay payload, if the relay offered one, is only chosen if
+/// it builds on top of the expected block with the requested timestamp and its block value is
+/// higher than the local one.
+pub fn choose_payload(
+    local: &ExecutionPayloadResponse,
+    relay: Option<&ExecutionPayloadResponse>,
+    parent_hash: H256,
+    timestamp: u64,
+) -> PayloadSource {
+    let Some(relay) = relay else {
+        return PayloadSource::Local;
+    };
+    if relay.execution_payload.parent_hash != parent_hash
+        || relay.execution_payload.timestamp != timestamp
+    {
+        tracing::warn!(
+            "Ignoring relay payload {:#x}: it does not build on block {parent_hash:#x} at timestamp {timestamp}",
+            relay.execution_payload.block_hash
+        );
+        return PayloadSource::Local;
+    }
+    if relay.block_value > local.block_value {
+        PayloadSource::Relay
+    } else {
+        PayloadSource::Local
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::U256;
+    use ethrex_core::types::{Block, BlockBody, BlockHeader};
+    use ethrex_rpc::types::payload::ExecutionPayloadV3;
+
+    const PARENT_HASH: H256 = H256::repeat_byte(1);
+    const TIMESTAMP: u64 = 12;
+
+    fn payload(block_value: u64) -> ExecutionPayloadResponse {
+        let header = BlockHeader {
+            parent_hash: PARENT_HASH,
+            timestamp: TIMESTAMP,
+            ..Default::default()
+        };
+        ExecutionPayloadResponse {
+            execution_payload: ExecutionPayloadV3::from_block(Block::new(
+                header,
+                BlockBody::default(),
+            )),
+            block_value: U256::from(block_value),
+            blobs_bundle: Default::default(),
+            should_override_builder: false,
+        }
+    }
+
+    fn choose(local: u64, relay: Option<u64>) -> PayloadSource {
+        let relay = relay.map(payload);
+        choose_payload(&payload(local), relay.as_ref(), PARENT_HASH, TIMESTAMP)
+    }
+
+    #[test]
+    fn highest_block_value_is_chosen() {
+        assert_eq!(choose(10, Some(11)), PayloadSource::Relay);
+        assert_eq!(choose(11, Some(10)), PayloadSource::Local);
+    }
+
+    #[test]
+    fn ties_keep_the_local_payload() {
+        assert_eq!(choose(10, Some(10)), PayloadSource::Local);
+        assert_eq!(choose(0, Some(0)), PayloadSource::Local);
+    }
+
+    #[test]
+    fn local_payload_is_chosen_without_relay_payload() {
+        assert_eq!(choose(0, None), PayloadSource::Local);
+    }
+
+    #[test]
+    fn relay_payload_must_build_on_the_expected_block() {
+        let relay = payload(11);
+        assert_eq!(
+            choose_payload(&payload(10), Some(&relay), H256::zero(), TIMESTAMP),
+            PayloadSource::Local
+        );
+        assert_eq!(
+            choose_payload(&payload(10), Some(&relay), PARENT_HASH, TIMESTAMP + 1),
+            PayloadSource::Local
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use ethrex_core::types::{BlobsBundleError, InvalidBlockHeaderError};
+use ethrex_core::{
+    errors::{CodedError, ErrorCode},
+    types::{BlobsBundleError, BlockNumber, InvalidBlockHeaderError},
+};
 use ethrex_storage::error::StoreError;
 use ethrex_vm::EvmError;
 
@@ -16,6 +19,21 @@ pub enum ChainError {
     StoreError(#[from] StoreError),
     #[error("EVM error: {0}")]
     EvmError(#[from] EvmError),
+    #[error("The payload build was interrupted")]
+    BuildInterrupted,
+}
+
+impl CodedError for ChainError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            ChainError::InvalidBlock(_) => ErrorCode::Invalid,
+            // The block can be added once its parent is synced
+            ChainError::ParentNotFound | ChainError::ParentStateNotFound => ErrorCode::Unavailable,
+            ChainError::StoreError(error) => error.code(),
+            ChainError::EvmError(error) => error.code(),
+            ChainError::BuildInterrupted => ErrorCode::Internal,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -30,8 +48,18 @@ pub enum InvalidBlockError {
     ExceededMaxBlobNumberPerBlock,
     #[error("Gas used doesn't match value in header")]
     GasUsedMismatch,
+    #[error("Logs bloom doesn't match the receipts' blooms")]
+    LogsBloomMismatch,
+    #[error("Transactions root doesn't match the block's transactions")]
+    TransactionsRootMismatch,
+    #[error("Receipts root doesn't match the block's receipts")]
+    ReceiptsRootMismatch,
+    #[error("Withdrawals root doesn't match the block's withdrawals")]
+    WithdrawalsRootMismatch,
     #[error("Blob gas used doesn't match value in header")]
     BlobGasUsedMismatch,
+    #[error("Requests hash doesn't match the requests of the block")]
+    RequestsHashMismatch,
     #[error("Invalid transaction: {0}")]
     InvalidTransaction(String),
 }
@@ -66,6 +94,29 @@ pub enum MempoolError {
     NotEnoughBalance,
     #[error("Transaction gas fields are invalid")]
     InvalidTxGasvalues,
+    #[error(
+        "Transaction conditions cost exceeded, at most {0} storage roots and slots can be checked"
+    )]
+    TxConditionsCostExceeded(usize),
+    #[error("Transaction conditions not met")]
+    TxConditionsNotMet,
+    #[error("Account has too many blob transactions, at most {0} can be pending")]
+    TooManyBlobTxs(usize),
+    #[error(
+        "Blob pool is full and the transaction doesn't pay a higher blob fee than the pending ones"
+    )]
+    BlobPoolFull,
+}
+
+impl CodedError for MempoolError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            MempoolError::StoreError(error) => error.code(),
+            // Room may be made for the transaction as blob transactions are included
+            MempoolError::NoBlockHeaderError | MempoolError::BlobPoolFull => ErrorCode::Unavailable,
+            _ => ErrorCode::Invalid,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -95,4 +146,34 @@ pub enum InvalidForkChoice {
     Disconnected(ForkChoiceElement, ForkChoiceElement),
     #[error("Requested head is an invalid block.")]
     InvalidHead,
+    #[error("Requested head would reorg block {0}, which is not after the finalized block {1}.")]
+    ReorgBelowFinalized(BlockNumber, BlockNumber),
+    #[error("Requested head would reorg {0} blocks, more than the maximum of {1}.")]
+    ReorgTooDeep(u64, u64),
+}
+
+impl CodedError for InvalidForkChoice {
+    fn code(&self) -> ErrorCode {
+        match self {
+            InvalidForkChoice::StoreError(error) => error.code(),
+            InvalidForkChoice::Syncing => ErrorCode::Unavailable,
+            _ => ErrorCode::Invalid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapped_errors_keep_their_code() {
+        let store_error = ChainError::StoreError(StoreError::DecodeError);
+        assert_eq!(store_error.code(), ErrorCode::Corrupted);
+        assert!(!store_error.is_retryable());
+        let evm_error = ChainError::EvmError(EvmError::DB(StoreError::MissingStore));
+        assert_eq!(evm_error.code(), ErrorCode::Internal);
+        assert!(ChainError::ParentNotFound.is_retryable());
+        assert!(!ChainError::InvalidBlock(InvalidBlockError::GasUsedMismatch).is_retryable());
+    }
 }
@@ -0,0 +1,14 @@
+pub use ethrex_core::types::ChainEvent;
+use ethrex_storage::Store;
+
+/// Whether something listens to the changes of the canonical chain of the store, so they are
+/// only computed if needed
+pub(crate) fn listening(store: &Store) -> bool {
+    store.chain_event_listener().is_some()
+}
+
+pub(crate) fn emit(store: &Store, event: ChainEvent) {
+    if let Some(listener) = store.chain_event_listener() {
+        listener(event)
+    }
+}
@@ -6,14 +6,16 @@ use ethrex_storage::{error::StoreError, Store};
 
 use crate::{
     error::{self, InvalidForkChoice},
+    events::{self, ChainEvent},
     is_canonical,
 };
-use tracing::error;
+use tracing::{error, info_span};
 
 /// Applies new fork choice data to the current blockchain. It performs validity checks:
 /// - The finalized, safe and head hashes must correspond to already saved blocks.
 /// - The saved blocks should be in the correct order (finalized <= safe <= head).
-/// - They must be connected.
+/// - The finalized and safe blocks must be ancestors of the head, or the head itself.
+/// - The head must not reorg the finalized block nor more blocks than the configured maximum depth.
 ///
 /// After the validity checks, the canonical chain is updated so that all head's ancestors
 /// and itself are made canonical.
@@ -25,35 +27,14 @@ pub fn apply_fork_choice(
     safe_hash: H256,
     finalized_hash: H256,
 ) -> Result<BlockHeader, InvalidForkChoice> {
+    let _span = info_span!("apply_fork_choice", head = ?head_hash).entered();
     if head_hash.is_zero() {
         return Err(InvalidForkChoice::InvalidHeadHash);
     }
 
     // We get the block bodies even if we only use headers them so we check that they are
     // stored too.
-
-    let finalized_res = if !finalized_hash.is_zero() {
-        store.get_block_by_hash(finalized_hash)?
-    } else {
-        None
-    };
-
-    let safe_res = if !safe_hash.is_zero() {
-        store.get_block_by_hash(safe_hash)?
-    } else {
-        None
-    };
-
     let head_res = store.get_block_by_hash(head_hash)?;
-
-    if !safe_hash.is_zero() {
-        check_order(&safe_res, &head_res)?;
-    }
-
-    if !finalized_hash.is_zero() && !safe_hash.is_zero() {
-        check_order(&finalized_res, &safe_res)?;
-    }
-
     let Some(head_block) = head_res else {
         if let Some(block) = store.get_pending_block(head_hash)? {
             trigger_sync(block);
@@ -63,6 +44,15 @@ pub fn apply_fork_choice(
 
     let head = head_block.header;
 
+    // Once the head is known, so are its ancestors, so unknown safe and finalized blocks can't be
+    // part of its chain.
+    let finalized_res =
+        get_fork_choice_element(store, finalized_hash, error::ForkChoiceElement::Finalized)?;
+    let safe_res = get_fork_choice_element(store, safe_hash, error::ForkChoiceElement::Safe)?;
+    check_order(finalized_res.as_ref(), safe_res.as_ref())?;
+    check_order(safe_res.as_ref(), Some(&head))?;
+    check_order(finalized_res.as_ref(), Some(&head))?;
+
     total_difficulty_check(&head_hash, &head, store)?;
 
     // TODO(#791): should we panic here? We should never not have a latest block number.
@@ -71,7 +61,8 @@ pub fn apply_fork_choice(
     };
 
     // If the head block is an already present head ancestor, skip the update.
-    if is_canonical(store, head.number, head_hash)? && head.number < latest {
+    let head_is_canonical = is_canonical(store, head.number, head_hash)?;
+    if head_is_canonical && head.number < latest {
         return Err(InvalidForkChoice::NewHeadAlreadyCanonical);
     }
 
@@ -88,36 +79,60 @@ pub fn apply_fork_choice(
         None => head.number,
     };
 
-    // Check that finalized and safe blocks are part of the new canonical chain.
-    if let Some(ref finalized_block) = finalized_res {
-        let finalized = &finalized_block.header;
-        if !((is_canonical(store, finalized.number, finalized_hash)?
-            && finalized.number <= link_block_number)
-            || (finalized.number == head.number && finalized_hash == head_hash)
-            || new_canonical_blocks.contains(&(finalized.number, finalized_hash)))
-        {
-            return Err(InvalidForkChoice::Disconnected(
-                error::ForkChoiceElement::Head,
-                error::ForkChoiceElement::Finalized,
-            ));
-        };
+    if !head_is_canonical {
+        check_reorg(
+            link_block_number,
+            latest,
+            store.get_finalized_block_number()?,
+            store.max_reorg_depth(),
+        )?;
     }
 
-    if let Some(ref safe_block) = safe_res {
-        let safe = &safe_block.header;
-        if !((is_canonical(store, safe.number, safe_hash)? && safe.number <= link_block_number)
-            || (safe.number == head.number && safe_hash == head_hash)
-            || new_canonical_blocks.contains(&(safe.number, safe_hash)))
+    // Check that finalized and safe blocks are ancestors of the head in the new canonical chain.
+    // Being ordered, the finalized block is then an ancestor of the safe one too.
+    // On a reorg, the canonical block at the link number is the first one replaced by the new head.
+    for (element, block, hash) in [
+        (
+            error::ForkChoiceElement::Finalized,
+            &finalized_res,
+            finalized_hash,
+        ),
+        (error::ForkChoiceElement::Safe, &safe_res, safe_hash),
+    ] {
+        let Some(number) = block.as_ref().map(|header| header.number) else {
+            continue;
+        };
+        let kept_by_head = if head_is_canonical {
+            number <= link_block_number
+        } else {
+            number < link_block_number
+        };
+        if !((kept_by_head && is_canonical(store, number, hash)?)
+            || (number == head.number && hash == head_hash)
+            || new_canonical_blocks.contains(&(number, hash)))
         {
             return Err(InvalidForkChoice::Disconnected(
                 error::ForkChoiceElement::Head,
-                error::ForkChoiceElement::Safe,
+                element,
             ));
-        };
+        }
     }
 
     // Finished all validations.
 
+    // The replaced blocks and the previous finalized block are only needed for the events
+    let (dropped, previous_finalized) = if events::listening(store) {
+        let mut dropped = vec![];
+        if !head_is_canonical {
+            for number in link_block_number..=latest {
+                dropped.extend(store.get_canonical_block_hash(number)?);
+            }
+        }
+        (dropped, store.get_finalized_block_number()?)
+    } else {
+        Default::default()
+    };
+
     // Make all ancestors to head canonical.
     for (number, hash) in new_canonical_blocks {
         store.set_canonical_block(number, hash)?;
@@ -130,17 +145,77 @@ pub fn apply_fork_choice(
 
     // Make head canonical and label all special blocks correctly.
     store.set_canonical_block(head.number, head_hash)?;
-    if let Some(finalized) = finalized_res {
-        store.update_finalized_block_number(finalized.header.number)?;
+    if let Some(ref finalized) = finalized_res {
+        store.update_finalized_block_number(finalized.number)?;
     }
     if let Some(safe) = safe_res {
-        store.update_safe_block_number(safe.header.number)?;
+        store.update_safe_block_number(safe.number)?;
     }
     store.update_latest_block_number(head.number)?;
+    store.restart_snapshot_off_canonical_chain(&head)?;
+
+    if events::listening(store) {
+        if !dropped.is_empty() {
+            events::emit(
+                store,
+                ChainEvent::Reorg {
+                    number: head.number,
+                    hash: head_hash,
+                    depth: dropped.len() as u64,
+                    dropped,
+                },
+            );
+        } else if !head_is_canonical {
+            events::emit(
+                store,
+                ChainEvent::NewHead {
+                    number: head.number,
+                    hash: head_hash,
+                },
+            );
+        }
+        if let Some(finalized) = finalized_res.filter(|finalized| {
+            previous_finalized.is_none_or(|previous| finalized.number > previous)
+        }) {
+            events::emit(
+                store,
+                ChainEvent::Finalized {
+                    number: finalized.number,
+                    hash: finalized_hash,
+                },
+            );
+        }
+    }
 
     Ok(head)
 }
 
+// Checks that replacing the canonical blocks from the given one up to the latest one doesn't
+// remove the finalized block nor more blocks than the maximum depth.
+fn check_reorg(
+    first_replaced: BlockNumber,
+    latest: BlockNumber,
+    finalized: Option<BlockNumber>,
+    max_depth: Option<u64>,
+) -> Result<(), InvalidForkChoice> {
+    if first_replaced > latest {
+        return Ok(());
+    }
+    if let Some(finalized) = finalized.filter(|finalized| first_replaced <= *finalized) {
+        error!("Refusing to reorg block {first_replaced}, the finalized block is {finalized}");
+        return Err(InvalidForkChoice::ReorgBelowFinalized(
+            first_replaced,
+            finalized,
+        ));
+    }
+    let depth = latest - first_replaced + 1;
+    if let Some(max_depth) = max_depth.filter(|max_depth| depth > *max_depth) {
+        error!("Refusing to reorg {depth} blocks, the maximum reorg depth is {max_depth}");
+        return Err(InvalidForkChoice::ReorgTooDeep(depth, max_depth));
+    }
+    Ok(())
+}
+
 // Trigger a backfill sync from the block until we find a valid block that we're familiar with or
 // something goes wrong.
 fn trigger_sync(head_block: Block) {
@@ -151,21 +226,30 @@ fn trigger_sync(head_block: Block) {
     );
 }
 
-// Checks that block 1 is prior to block 2 and that if the second is present, the first one is too.
-fn check_order(block_1: &Option<Block>, block_2: &Option<Block>) -> Result<(), InvalidForkChoice> {
-    // We don't need to perform the check if the hashes are null
+// Returns the block of a fork choice element, if its hash is not null.
+// The element is not found if its hash is not null and the block is not stored.
+fn get_fork_choice_element(
+    store: &Store,
+    hash: BlockHash,
+    element: error::ForkChoiceElement,
+) -> Result<Option<BlockHeader>, InvalidForkChoice> {
+    if hash.is_zero() {
+        return Ok(None);
+    }
+    match store.get_block_by_hash(hash)? {
+        Some(block) => Ok(Some(block.header)),
+        None => Err(InvalidForkChoice::ElementNotFound(element)),
+    }
+}
+
+// Checks that block 1 is not after block 2, if both are present.
+fn check_order(
+    block_1: Option<&BlockHeader>,
+    block_2: Option<&BlockHeader>,
+) -> Result<(), InvalidForkChoice> {
     match (block_1, block_2) {
-        (None, Some(_)) => Err(InvalidForkChoice::ElementNotFound(
-            error::ForkChoiceElement::Finalized,
-        )),
-        (Some(b1), Some(b2)) => {
-            if b1.header.number > b2.header.number {
-                Err(InvalidForkChoice::Unordered)
-            } else {
-                Ok(())
-            }
-        }
-        _ => Err(InvalidForkChoice::Syncing),
+        (Some(b1), Some(b2)) if b1.number > b2.number => Err(InvalidForkChoice::Unordered),
+        _ => Ok(()),
     }
 }
 
@@ -202,18 +286,19 @@ fn find_link_with_canonical_chain(
         block_number -= 1;
         let parent_hash = header.parent_hash;
 
+        // A canonical parent is found through the canonical numbers index, only the headers of
+        // the branch are read.
+        if is_canonical(store, block_number, parent_hash)? {
+            return Ok(Some(branch));
+        }
+
         // Check that the parent exists.
         let parent_header = match store.get_block_header_by_hash(parent_hash) {
             Ok(Some(header)) => header,
             Ok(None) => return Ok(None),
             Err(error) => return Err(error),
         };
-
-        if is_canonical(store, block_number, parent_hash)? {
-            return Ok(Some(branch));
-        } else {
-            branch.push((block_number, parent_hash));
-        }
+        branch.push((block_number, parent_hash));
 
         header = parent_header;
     }
@@ -271,3 +356,24 @@ fn total_difficulty_check<'a>(
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_reorg_protects_finalized_block_and_max_depth() {
+        // Extending the chain doesn't remove any block
+        assert!(check_reorg(11, 10, Some(10), Some(0)).is_ok());
+        assert!(check_reorg(8, 10, Some(7), None).is_ok());
+        assert!(matches!(
+            check_reorg(7, 10, Some(7), None),
+            Err(InvalidForkChoice::ReorgBelowFinalized(7, 7))
+        ));
+        assert!(check_reorg(8, 10, None, Some(3)).is_ok());
+        assert!(matches!(
+            check_reorg(7, 10, None, Some(3)),
+            Err(InvalidForkChoice::ReorgTooDeep(4, 3))
+        ));
+    }
+}
@@ -2,21 +2,28 @@ use std::collections::HashMap;
 
 use crate::{
     constants::{
-        MAX_INITCODE_SIZE, MIN_BASE_FEE_PER_BLOB_GAS, TX_ACCESS_LIST_ADDRESS_GAS,
+        GAS_PER_BLOB, MAX_INITCODE_SIZE, MIN_BASE_FEE_PER_BLOB_GAS, TX_ACCESS_LIST_ADDRESS_GAS,
         TX_ACCESS_LIST_STORAGE_KEY_GAS, TX_CREATE_GAS_COST, TX_DATA_NON_ZERO_GAS,
         TX_DATA_NON_ZERO_GAS_EIP2028, TX_DATA_ZERO_GAS_COST, TX_GAS_COST,
         TX_INIT_CODE_WORD_GAS_COST,
     },
     error::MempoolError,
+    payload::project_fees,
 };
 use ethrex_core::{
     types::{
-        BlobsBundle, BlockHeader, ChainConfig, EIP4844Transaction, MempoolTransaction, Transaction,
+        BlobsBundle, BlockHash, BlockHeader, ChainConfig, EIP4844Transaction, KnownAccount,
+        MempoolTransaction, Transaction, TransactionConditions, MAX_KNOWN_ACCOUNTS_COST,
     },
     Address, H256, U256,
 };
 use ethrex_storage::{error::StoreError, Store};
 
+/// Maximum amount of blob transactions of a single account in the mempool
+pub const MAX_BLOB_TXS_PER_ACCOUNT: usize = 16;
+/// Maximum amount of blobs in the mempool, the sidecar of each blob takes about 128KiB
+pub const MAX_POOL_BLOBS: usize = 4096;
+
 /// Add a blob transaction and its blobs bundle to the mempool
 #[cfg(feature = "c-kzg")]
 pub fn add_blob_transaction(
@@ -28,10 +35,11 @@ pub fn add_blob_transaction(
     blobs_bundle.validate(&transaction)?;
 
     let transaction = Transaction::EIP4844Transaction(transaction);
-    let sender = transaction.sender();
+    let sender = store.transaction_sender(&transaction);
 
     // Validate transaction
     validate_transaction(&transaction, sender, store.clone())?;
+    make_room_for_blobs(&transaction, sender, &store)?;
 
     // Add transaction and blobs bundle to storage
     let hash = transaction.compute_hash();
@@ -40,13 +48,69 @@ pub fn add_blob_transaction(
     Ok(hash)
 }
 
+/// Checks the blob transaction fits within the limits of the pool, evicting the blob transactions
+/// paying the lowest blob fee if the pool is full. Fails if the sender already has
+/// [MAX_BLOB_TXS_PER_ACCOUNT] blob transactions or if the transaction doesn't pay a higher blob fee
+/// than the ones that would have to be evicted.
+#[cfg(feature = "c-kzg")]
+fn make_room_for_blobs(
+    transaction: &Transaction,
+    sender: Address,
+    store: &Store,
+) -> Result<(), MempoolError> {
+    let hash = transaction.compute_hash();
+    let blob_txs = store.filter_pool_transactions(&|tx| {
+        matches!(tx, Transaction::EIP4844Transaction(_)) && tx.compute_hash() != hash
+    })?;
+    if blob_txs
+        .get(&sender)
+        .is_some_and(|txs| txs.len() >= MAX_BLOB_TXS_PER_ACCOUNT)
+    {
+        return Err(MempoolError::TooManyBlobTxs(MAX_BLOB_TXS_PER_ACCOUNT));
+    }
+    let mut pool: Vec<MempoolTransaction> = blob_txs.into_values().flatten().collect();
+    let pooled_blobs: usize = pool.iter().map(|tx| tx.blob_versioned_hashes().len()).sum();
+    let mut excess =
+        (pooled_blobs + transaction.blob_versioned_hashes().len()).saturating_sub(MAX_POOL_BLOBS);
+    if excess == 0 {
+        return Ok(());
+    }
+    let blob_fee = transaction.max_fee_per_blob_gas().unwrap_or_default();
+    // Cheapest first, the latest nonces of a sender before the earlier ones they depend on
+    pool.sort_by_key(|tx| {
+        (
+            tx.max_fee_per_blob_gas().unwrap_or_default(),
+            std::cmp::Reverse(tx.nonce()),
+        )
+    });
+    let mut evicted = vec![];
+    for tx in pool {
+        if excess == 0 {
+            break;
+        }
+        if tx.max_fee_per_blob_gas().unwrap_or_default() >= blob_fee {
+            return Err(MempoolError::BlobPoolFull);
+        }
+        excess = excess.saturating_sub(tx.blob_versioned_hashes().len());
+        evicted.push(tx.compute_hash());
+    }
+    if excess > 0 {
+        return Err(MempoolError::BlobPoolFull);
+    }
+    for evicted_hash in evicted {
+        tracing::debug!("Evicting blob transaction {evicted_hash:#x} for {hash:#x}");
+        store.remove_transaction_from_pool(&evicted_hash)?;
+    }
+    Ok(())
+}
+
 /// Add a transaction to the mempool
 pub fn add_transaction(transaction: Transaction, store: &Store) -> Result<H256, MempoolError> {
     // Blob transactions should be submitted via add_blob_transaction along with the corresponding blobs bundle
     if matches!(transaction, Transaction::EIP4844Transaction(_)) {
         return Err(MempoolError::BlobTxNoBlobsBundle);
     }
-    let sender = transaction.sender();
+    let sender = store.transaction_sender(&transaction);
     // Validate transaction
     validate_transaction(&transaction, sender, store.clone())?;
 
@@ -58,6 +122,76 @@ pub fn add_transaction(transaction: Transaction, store: &Store) -> Result<H256,
     Ok(hash)
 }
 
+/// Add a transaction to the mempool along with the conditions it must meet to be included in a block.
+/// The conditions must hold for the latest block, and are checked again when building each payload
+pub fn add_conditional_transaction(
+    transaction: Transaction,
+    conditions: TransactionConditions,
+    store: &Store,
+) -> Result<H256, MempoolError> {
+    if conditions.known_accounts_cost() > MAX_KNOWN_ACCOUNTS_COST {
+        return Err(MempoolError::TxConditionsCostExceeded(
+            MAX_KNOWN_ACCOUNTS_COST,
+        ));
+    }
+    let header_no = store
+        .get_latest_block_number()?
+        .ok_or(MempoolError::NoBlockHeaderError)?;
+    let header = store
+        .get_block_header(header_no)?
+        .ok_or(MempoolError::NoBlockHeaderError)?;
+    // The transaction can't be included in a block older than the next one
+    if conditions
+        .block_number_max
+        .is_some_and(|max| max <= header.number)
+        || conditions
+            .timestamp_max
+            .is_some_and(|max| max <= header.timestamp)
+    {
+        return Err(MempoolError::TxConditionsNotMet);
+    }
+    if !known_accounts_match(&conditions, header.compute_block_hash(), store)? {
+        return Err(MempoolError::TxConditionsNotMet);
+    }
+    // Conditions are stored first so the transaction is never seen without them
+    let hash = transaction.compute_hash();
+    store.add_transaction_conditions_to_pool(hash, conditions)?;
+    add_transaction(transaction, store).inspect_err(|_| {
+        let _ = store.remove_transaction_conditions_from_pool(&hash);
+    })
+}
+
+/// Checks the known accounts of the conditions against the state of the given block
+pub fn known_accounts_match(
+    conditions: &TransactionConditions,
+    block_hash: BlockHash,
+    store: &Store,
+) -> Result<bool, StoreError> {
+    for (address, known_account) in &conditions.known_accounts {
+        match known_account {
+            KnownAccount::StorageRoot(storage_root) => {
+                let Some(account) = store.get_account_state_by_hash(block_hash, *address)? else {
+                    return Ok(false);
+                };
+                if account.storage_root != *storage_root {
+                    return Ok(false);
+                }
+            }
+            KnownAccount::Slots(slots) => {
+                for (key, value) in slots {
+                    let stored = store
+                        .get_storage_at_hash(block_hash, *address, *key)?
+                        .unwrap_or_default();
+                    if stored != U256::from_big_endian(value.as_bytes()) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
 /// Fetch a blobs bundle from the mempool given its blob transaction hash
 pub fn get_blobs_bundle(tx_hash: H256, store: Store) -> Result<Option<BlobsBundle>, MempoolError> {
     Ok(store.get_blobs_bundle_from_pool(tx_hash)?)
@@ -125,6 +259,195 @@ pub fn get_nonce(address: &Address, store: &Store) -> Result<Option<u64>, Mempoo
     Ok(nonce)
 }
 
+/// Range of nonces missing from the pending transactions of a sender
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonceGap {
+    pub from: u64,
+    pub to: u64,
+    /// Pending transaction right after the gap, which can't be included until it is filled
+    pub blocked_transaction: H256,
+}
+
+/// Returns the nonces missing between the nonce of the account in the latest block
+/// and the pending transactions of the sender
+pub fn nonce_gaps(address: &Address, store: &Store) -> Result<Vec<NonceGap>, MempoolError> {
+    let latest_block_number = store.get_latest_block_number()?.unwrap_or_default();
+    let mut next_nonce = store
+        .get_account_info(latest_block_number, *address)?
+        .map(|account| account.nonce)
+        .unwrap_or_default();
+    let pending_txs = store.filter_pool_transactions(&|_| true)?;
+    let mut gaps = vec![];
+    for tx in pending_txs.get(address).into_iter().flatten() {
+        // Transactions replaced or already included don't fill nonces
+        if tx.nonce() < next_nonce {
+            continue;
+        }
+        if tx.nonce() > next_nonce {
+            gaps.push(NonceGap {
+                from: next_nonce,
+                to: tx.nonce() - 1,
+                blocked_transaction: tx.compute_hash(),
+            });
+        }
+        next_nonce = tx.nonce() + 1;
+    }
+    Ok(gaps)
+}
+
+/// Returns the pending transactions of the sender that can be executed one after the other
+/// from the given nonce, up to the first missing nonce
+pub fn executable_transactions(
+    address: &Address,
+    mut next_nonce: u64,
+    store: &Store,
+) -> Result<Vec<Transaction>, MempoolError> {
+    let pending_txs = store.filter_pool_transactions(&|_| true)?;
+    let mut executable = vec![];
+    for tx in pending_txs.get(address).into_iter().flatten() {
+        if tx.nonce() < next_nonce {
+            continue;
+        }
+        if tx.nonce() > next_nonce {
+            break;
+        }
+        executable.push(Transaction::clone(tx));
+        next_nonce += 1;
+    }
+    Ok(executable)
+}
+
+/// Reason why a pending transaction can't be included in the next built payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InclusionBlocker {
+    /// Nonces of the sender that are missing before the one of the transaction
+    NonceGap { from: u64, to: u64 },
+    /// The nonce was already used by an included transaction
+    NonceTooLow { account_nonce: u64 },
+    /// The sender can't pay for the gas and value of the transaction
+    InsufficientBalance { balance: U256, cost: U256 },
+    /// The fee cap is below the base fee of the next block
+    FeeCapBelowBaseFee { base_fee: u64 },
+    /// The blob fee cap is below the blob base fee of the next block
+    BlobFeeCapBelowBaseFee { blob_base_fee: u64 },
+}
+
+/// State of a pending transaction as seen by the payload builder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionInspection {
+    pub sender: Address,
+    pub nonce: u64,
+    /// Tip paid per gas at the base fee of the next block, None if the fee cap is below it
+    pub effective_tip: Option<u64>,
+    /// Amount of pending transactions the builder picks before this one, which are the ones
+    /// paying a higher tip and the previous ones of the sender. Only set if nothing blocks it.
+    pub priority_position: Option<usize>,
+    pub blockers: Vec<InclusionBlocker>,
+}
+
+/// Returns why the pending transaction with the given hash is or isn't included in the next
+/// built payload, or None if it is not in the mempool
+pub fn inspect_transaction(
+    hash: H256,
+    store: &Store,
+) -> Result<Option<TransactionInspection>, MempoolError> {
+    let Some(tx) = store.get_transaction_from_pool(hash)? else {
+        return Ok(None);
+    };
+    let sender = tx.sender();
+    let nonce = tx.nonce();
+    let latest_block_number = store
+        .get_latest_block_number()?
+        .ok_or(MempoolError::NoBlockHeaderError)?;
+    let latest_header = store
+        .get_block_header(latest_block_number)?
+        .ok_or(MempoolError::NoBlockHeaderError)?;
+    // The gas limit of the next block doesn't change its base fee
+    let next_fees = project_fees(&latest_header, 1, 0, 0, latest_header.gas_limit)
+        .pop()
+        .ok_or(MempoolError::InvalidTxGasvalues)?;
+    let account = store
+        .get_account_info(latest_block_number, sender)?
+        .unwrap_or_default();
+
+    let mut blockers = vec![];
+    if nonce < account.nonce {
+        blockers.push(InclusionBlocker::NonceTooLow {
+            account_nonce: account.nonce,
+        });
+    }
+    blockers.extend(
+        nonce_gaps(&sender, store)?
+            .into_iter()
+            .filter(|gap| gap.to < nonce)
+            .map(|gap| InclusionBlocker::NonceGap {
+                from: gap.from,
+                to: gap.to,
+            }),
+    );
+    let blob_gas = tx.blob_versioned_hashes().len() as u64 * GAS_PER_BLOB;
+    let cost = tx
+        .cost_without_base_fee()
+        .ok_or(MempoolError::InvalidTxGasvalues)?
+        .saturating_add(
+            tx.max_fee_per_blob_gas()
+                .unwrap_or_default()
+                .saturating_mul(blob_gas.into()),
+        );
+    if cost > account.balance {
+        blockers.push(InclusionBlocker::InsufficientBalance {
+            balance: account.balance,
+            cost,
+        });
+    }
+    let effective_tip = tx.effective_gas_tip(Some(next_fees.base_fee_per_gas));
+    if effective_tip.is_none() {
+        blockers.push(InclusionBlocker::FeeCapBelowBaseFee {
+            base_fee: next_fees.base_fee_per_gas,
+        });
+    }
+    if let (Some(fee_cap), Some(blob_base_fee)) =
+        (tx.max_fee_per_blob_gas(), next_fees.base_fee_per_blob_gas)
+    {
+        if fee_cap < blob_base_fee.into() {
+            blockers.push(InclusionBlocker::BlobFeeCapBelowBaseFee { blob_base_fee });
+        }
+    }
+
+    let priority_position = match effective_tip {
+        Some(tip) if blockers.is_empty() => {
+            let filter = PendingTxFilter {
+                base_fee: Some(next_fees.base_fee_per_gas),
+                ..Default::default()
+            };
+            let ahead = filter_transactions(&filter, store)?
+                .into_iter()
+                .flat_map(|(pending_sender, txs)| {
+                    txs.into_iter().filter(move |pending| {
+                        if pending_sender == sender {
+                            pending.nonce() < nonce
+                        } else {
+                            pending
+                                .effective_gas_tip(Some(next_fees.base_fee_per_gas))
+                                .is_some_and(|pending_tip| pending_tip > tip)
+                        }
+                    })
+                })
+                .count();
+            Some(ahead)
+        }
+        _ => None,
+    };
+
+    Ok(Some(TransactionInspection {
+        sender,
+        nonce,
+        effective_tip,
+        priority_position,
+        blockers,
+    }))
+}
+
 #[derive(Debug, Default)]
 pub struct PendingTxFilter {
     pub min_tip: Option<u64>,
@@ -311,13 +634,20 @@ mod tests {
         TX_DATA_ZERO_GAS_COST, TX_GAS_COST, TX_INIT_CODE_WORD_GAS_COST,
     };
 
-    use super::{transaction_intrinsic_gas, validate_transaction};
+    use super::{
+        add_conditional_transaction, executable_transactions, inspect_transaction, nonce_gaps,
+        transaction_intrinsic_gas, validate_transaction, InclusionBlocker, NonceGap,
+    };
+    use crate::payload::project_fees;
     use ethrex_core::types::{
-        BlockHeader, ChainConfig, EIP1559Transaction, EIP4844Transaction, Transaction, TxKind,
+        BlockHeader, ChainConfig, EIP1559Transaction, EIP4844Transaction, GenesisAccount,
+        KnownAccount, MempoolTransaction, Transaction, TransactionConditions, TxKind,
+        EMPTY_TRIE_HASH, MAX_KNOWN_ACCOUNTS_COST,
     };
     use ethrex_core::{Address, Bytes, H256, U256};
     use ethrex_storage::EngineType;
     use ethrex_storage::{error::StoreError, Store};
+    use std::collections::HashMap;
 
     fn setup_storage(config: ChainConfig, header: BlockHeader) -> Result<Store, StoreError> {
         let store = Store::new("test", EngineType::InMemory)?;
@@ -659,4 +989,220 @@ mod tests {
             Err(MempoolError::TxBlobBaseFeeTooLowError)
         ));
     }
+
+    #[test]
+    fn conditional_transaction_with_unreachable_conditions_fails() {
+        let (config, header) = build_basic_config_and_header(false, false);
+        let store = setup_storage(config, header).expect("Storage setup");
+        let tx = Transaction::EIP1559Transaction(EIP1559Transaction::default());
+
+        // The latest block is the 5th one, so the transaction can only be included from the 6th
+        let expired = TransactionConditions {
+            block_number_max: Some(5),
+            ..Default::default()
+        };
+        assert!(matches!(
+            add_conditional_transaction(tx.clone(), expired, &store),
+            Err(MempoolError::TxConditionsNotMet)
+        ));
+
+        let too_expensive = TransactionConditions {
+            known_accounts: HashMap::from([(
+                Address::random(),
+                KnownAccount::Slots(
+                    (0..=MAX_KNOWN_ACCOUNTS_COST as u64)
+                        .map(|i| (H256::from_low_u64_be(i), H256::zero()))
+                        .collect(),
+                ),
+            )]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            add_conditional_transaction(tx.clone(), too_expensive, &store),
+            Err(MempoolError::TxConditionsCostExceeded(_))
+        ));
+        assert!(store
+            .get_transaction_from_pool(tx.compute_hash())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn nonce_gaps_report_missing_nonces() {
+        let (config, mut header) = build_basic_config_and_header(false, false);
+        header.state_root = *EMPTY_TRIE_HASH;
+        let store = setup_storage(config, header).expect("Storage setup");
+        let sender = Address::random();
+        let mut hashes = HashMap::new();
+        for nonce in [0, 1, 4, 5, 7] {
+            let tx = Transaction::EIP1559Transaction(EIP1559Transaction {
+                nonce,
+                ..Default::default()
+            });
+            hashes.insert(nonce, tx.compute_hash());
+            store
+                .add_transaction_to_pool(tx.compute_hash(), MempoolTransaction::new(tx, sender))
+                .unwrap();
+        }
+        assert_eq!(
+            nonce_gaps(&sender, &store).unwrap(),
+            vec![
+                NonceGap {
+                    from: 2,
+                    to: 3,
+                    blocked_transaction: hashes[&4],
+                },
+                NonceGap {
+                    from: 6,
+                    to: 6,
+                    blocked_transaction: hashes[&7],
+                },
+            ]
+        );
+        assert!(nonce_gaps(&Address::random(), &store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn executable_transactions_stop_at_missing_nonces() {
+        let (config, mut header) = build_basic_config_and_header(false, false);
+        header.state_root = *EMPTY_TRIE_HASH;
+        let store = setup_storage(config, header).expect("Storage setup");
+        let sender = Address::random();
+        for nonce in [0, 1, 2, 4] {
+            let tx = Transaction::EIP1559Transaction(EIP1559Transaction {
+                nonce,
+                ..Default::default()
+            });
+            store
+                .add_transaction_to_pool(tx.compute_hash(), MempoolTransaction::new(tx, sender))
+                .unwrap();
+        }
+        let nonces = |next_nonce| -> Vec<u64> {
+            executable_transactions(&sender, next_nonce, &store)
+                .unwrap()
+                .iter()
+                .map(Transaction::nonce)
+                .collect()
+        };
+        assert_eq!(nonces(0), vec![0, 1, 2]);
+        assert_eq!(nonces(1), vec![1, 2]);
+        assert!(nonces(3).is_empty());
+    }
+
+    #[test]
+    fn inspected_transactions_report_their_blockers_and_priority() {
+        let (config, mut header) = build_basic_config_and_header(false, false);
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let (funded, rich, poor) = (Address::random(), Address::random(), Address::random());
+        let account = GenesisAccount {
+            code: Bytes::new(),
+            storage: HashMap::new(),
+            balance: U256::from(10).pow(18.into()),
+            nonce: 0,
+        };
+        header.base_fee_per_gas = Some(1000);
+        header.state_root = store
+            .setup_genesis_state_trie(HashMap::from([(funded, account.clone()), (rich, account)]))
+            .unwrap();
+        let hash = header.compute_block_hash();
+        store.add_block_header(hash, header.clone()).unwrap();
+        store.set_canonical_block(header.number, hash).unwrap();
+        store.update_latest_block_number(header.number).unwrap();
+        store.set_chain_config(&config).unwrap();
+        let base_fee = project_fees(&header, 1, 0, 0, header.gas_limit)[0].base_fee_per_gas;
+
+        let add = |sender, nonce, max_priority_fee_per_gas, max_fee_per_gas| {
+            let tx = Transaction::EIP1559Transaction(EIP1559Transaction {
+                nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas_limit: 21_000,
+                ..Default::default()
+            });
+            store
+                .add_transaction_to_pool(
+                    tx.compute_hash(),
+                    MempoolTransaction::new(tx.clone(), sender),
+                )
+                .unwrap();
+            tx.compute_hash()
+        };
+        add(rich, 0, 5, 2000);
+        let first = add(funded, 0, 2, 2000);
+        let second = add(funded, 1, 2, 2000);
+        let blocked = add(poor, 1, 2, base_fee - 1);
+
+        // The transactions paying a higher tip and the previous ones of the sender go first
+        let inspection = inspect_transaction(first, &store).unwrap().unwrap();
+        assert_eq!(inspection.effective_tip, Some(2));
+        assert_eq!(inspection.priority_position, Some(1));
+        assert!(inspection.blockers.is_empty());
+        let inspection = inspect_transaction(second, &store).unwrap().unwrap();
+        assert_eq!(inspection.priority_position, Some(2));
+
+        let inspection = inspect_transaction(blocked, &store).unwrap().unwrap();
+        assert_eq!(
+            (inspection.effective_tip, inspection.priority_position),
+            (None, None)
+        );
+        assert_eq!(
+            inspection.blockers,
+            vec![
+                InclusionBlocker::NonceGap { from: 0, to: 0 },
+                InclusionBlocker::InsufficientBalance {
+                    balance: U256::zero(),
+                    cost: U256::from(21_000 * (base_fee - 1)),
+                },
+                InclusionBlocker::FeeCapBelowBaseFee { base_fee },
+            ]
+        );
+        assert!(inspect_transaction(H256::random(), &store)
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "c-kzg")]
+    #[test]
+    fn blob_pool_evicts_the_lowest_blob_fees_when_full() {
+        use super::{make_room_for_blobs, MAX_BLOB_TXS_PER_ACCOUNT, MAX_POOL_BLOBS};
+
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let blob_tx = |nonce, max_fee_per_blob_gas: u64| {
+            Transaction::EIP4844Transaction(EIP4844Transaction {
+                nonce,
+                max_fee_per_blob_gas: max_fee_per_blob_gas.into(),
+                blob_versioned_hashes: vec![H256::zero(); 4],
+                ..Default::default()
+            })
+        };
+        let add = |tx: Transaction, sender| {
+            store
+                .add_transaction_to_pool(tx.compute_hash(), MempoolTransaction::new(tx, sender))
+                .unwrap();
+        };
+        // The pool is filled by a sender at its limit and others paying increasing blob fees
+        let busy_sender = Address::random();
+        for nonce in 0..MAX_BLOB_TXS_PER_ACCOUNT as u64 {
+            add(blob_tx(nonce, 5000), busy_sender);
+        }
+        let cheapest = blob_tx(0, 2);
+        add(cheapest.clone(), Address::random());
+        for fee in 3..(MAX_POOL_BLOBS / 4 - MAX_BLOB_TXS_PER_ACCOUNT) as u64 + 2 {
+            add(blob_tx(0, fee), Address::random());
+        }
+
+        assert!(matches!(
+            make_room_for_blobs(&blob_tx(16, 5000), busy_sender, &store),
+            Err(MempoolError::TooManyBlobTxs(_))
+        ));
+        assert!(matches!(
+            make_room_for_blobs(&blob_tx(1, 2), Address::random(), &store),
+            Err(MempoolError::BlobPoolFull)
+        ));
+        make_room_for_blobs(&blob_tx(1, 3), Address::random(), &store).unwrap();
+        assert!(store
+            .get_transaction_from_pool(cheapest.compute_hash())
+            .unwrap()
+            .is_none());
+    }
 }
@@ -1,35 +1,84 @@
 use std::{
-    cmp::{min, Ordering},
-    collections::HashMap,
+    cmp::{max, Ordering},
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering as AtomicOrdering},
 };
 
 use ethrex_core::{
     types::{
         calculate_base_fee_per_blob_gas, calculate_base_fee_per_gas, compute_receipts_root,
-        compute_transactions_root, compute_withdrawals_root, BlobsBundle, Block, BlockBody,
-        BlockHash, BlockHeader, BlockNumber, ChainConfig, MempoolTransaction, Receipt, Transaction,
-        Withdrawal, DEFAULT_OMMERS_HASH,
+        compute_receipts_root_and_logs_bloom, compute_requests_hash, compute_transactions_root,
+        compute_withdrawals_root, BlobsBundle, Block, BlockBody, BlockHash, BlockHeader,
+        BlockNumber, ChainConfig, EncodedRequests, MempoolTransaction, Receipt, Transaction,
+        TxKind, Withdrawal, DEFAULT_OMMERS_HASH,
     },
     Address, Bloom, Bytes, H256, U256,
 };
 use ethrex_rlp::encode::RLPEncode;
 use ethrex_storage::{error::StoreError, Store};
 use ethrex_vm::{
-    beacon_root_contract_call, evm_state, execute_tx, get_state_transitions, process_withdrawals,
-    spec_id, EvmError, EvmState, SpecId,
+    beacon_root_contract_call, evm_state, execute_tx, extract_requests, get_state_transitions,
+    process_withdrawals, spec_id, EvmError, EvmState, SpecId,
 };
 use sha3::{Digest, Keccak256};
 
 use crate::{
     constants::{
-        GAS_LIMIT_BOUND_DIVISOR, GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK, MIN_GAS_LIMIT,
-        TARGET_BLOB_GAS_PER_BLOCK, TX_GAS_COST,
+        GAS_LIMIT_BOUND_DIVISOR, GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK, MAX_BLOB_NUMBER_PER_BLOCK,
+        MIN_GAS_LIMIT, TARGET_BLOB_GAS_PER_BLOCK, TX_GAS_COST,
     },
     error::{ChainError, InvalidBlockError},
     mempool::{self, PendingTxFilter},
 };
 
-use tracing::debug;
+use tracing::{debug, info_span, warn};
+
+/// Gas limit built payloads move towards when no target was set by the operator
+pub const DEFAULT_BUILDER_GAS_CEIL: u64 = 30_000_000;
+
+/// Settings of the payloads built by the node, set by its operator
+#[derive(Debug, Clone)]
+pub struct PayloadBuilderConfig {
+    /// Gas limit built payloads move towards, within the bounds allowed on each block
+    pub gas_ceil: u64,
+    /// Extra data of built payloads, to brand their blocks, at most [MAX_EXTRA_DATA_SIZE] bytes
+    pub extra_data: Bytes,
+    pub forced_inclusion_list: ForcedInclusionList,
+}
+
+impl Default for PayloadBuilderConfig {
+    fn default() -> Self {
+        PayloadBuilderConfig {
+            gas_ceil: DEFAULT_BUILDER_GAS_CEIL,
+            extra_data: Bytes::new(),
+            forced_inclusion_list: ForcedInclusionList::default(),
+        }
+    }
+}
+
+/// Maximum size of the extra data of a block header
+pub const MAX_EXTRA_DATA_SIZE: usize = 32;
+
+/// Transactions the operator wants attempted in every built payload before any other,
+/// regardless of their tip, such as deposits or forced exits
+#[derive(Debug, Clone, Default)]
+pub struct ForcedInclusionList {
+    /// The transactions sent from or to these addresses are forced
+    pub addresses: HashSet<Address>,
+    pub tx_hashes: HashSet<H256>,
+}
+
+impl ForcedInclusionList {
+    fn includes(&self, tx: &MempoolTransaction) -> bool {
+        let recipient = match tx.to() {
+            TxKind::Call(to) => Some(to),
+            TxKind::Create => None,
+        };
+        self.addresses.contains(&tx.sender())
+            || recipient.is_some_and(|to| self.addresses.contains(&to))
+            || self.tx_hashes.contains(&tx.compute_hash())
+    }
+}
 
 pub struct BuildPayloadArgs {
     pub parent: BlockHash,
@@ -39,6 +88,10 @@ pub struct BuildPayloadArgs {
     pub withdrawals: Vec<Withdrawal>,
     pub beacon_root: Option<H256>,
     pub version: u8,
+    /// Extra data of the payload's header
+    pub extra_data: Bytes,
+    /// Gas limit the payload moves towards from the parent's, within the bounds allowed
+    pub gas_ceil: u64,
 }
 
 impl BuildPayloadArgs {
@@ -62,13 +115,11 @@ impl BuildPayloadArgs {
 /// Creates a new payload based on the payload arguments
 // Basic payload block building, can and should be improved
 pub fn create_payload(args: &BuildPayloadArgs, storage: &Store) -> Result<Block, ChainError> {
-    // TODO: check where we should get builder values from
-    const DEFAULT_BUILDER_GAS_CEIL: u64 = 30_000_000;
     let parent_block = storage
         .get_block_header_by_hash(args.parent)?
         .ok_or_else(|| ChainError::ParentNotFound)?;
     let chain_config = storage.get_chain_config()?;
-    let gas_limit = calc_gas_limit(parent_block.gas_limit, DEFAULT_BUILDER_GAS_CEIL);
+    let gas_limit = calc_gas_limit(parent_block.gas_limit, args.gas_ceil);
 
     let header = BlockHeader {
         parent_hash: args.parent,
@@ -83,8 +134,7 @@ pub fn create_payload(args: &BuildPayloadArgs, storage: &Store) -> Result<Block,
         gas_limit,
         gas_used: 0,
         timestamp: args.timestamp,
-        // TODO: should use builder config's extra_data
-        extra_data: Bytes::new(),
+        extra_data: args.extra_data.clone(),
         prev_randao: args.random,
         nonce: 0,
         base_fee_per_gas: calculate_base_fee_per_gas(
@@ -96,7 +146,9 @@ pub fn create_payload(args: &BuildPayloadArgs, storage: &Store) -> Result<Block,
         withdrawals_root: chain_config
             .is_shanghai_activated(args.timestamp)
             .then_some(compute_withdrawals_root(&args.withdrawals)),
-        blob_gas_used: Some(0),
+        blob_gas_used: chain_config
+            .is_cancun_activated(args.timestamp)
+            .then_some(0),
         excess_blob_gas: chain_config.is_cancun_activated(args.timestamp).then_some(
             calc_excess_blob_gas(
                 parent_block.excess_blob_gas.unwrap_or_default(),
@@ -104,22 +156,30 @@ pub fn create_payload(args: &BuildPayloadArgs, storage: &Store) -> Result<Block,
             ),
         ),
         parent_beacon_block_root: args.beacon_root,
+        // Computed once the payload's transactions are known
+        requests_hash: chain_config
+            .is_prague_activated(args.timestamp)
+            .then_some(compute_requests_hash(&[])),
     };
 
     let body = BlockBody {
         transactions: Vec::new(),
         ommers: Vec::new(),
-        withdrawals: Some(args.withdrawals.clone()),
+        withdrawals: chain_config
+            .is_shanghai_activated(args.timestamp)
+            .then(|| args.withdrawals.clone()),
     };
 
     // Delay applying withdrawals until the payload is requested and built
     Ok(Block::new(header, body))
 }
 
+/// Computes the gas limit of the child of a block with the given gas limit, moving it towards the
+/// desired one by less than 1/1024 of the parent's gas limit, as allowed by the protocol
 fn calc_gas_limit(parent_gas_limit: u64, desired_limit: u64) -> u64 {
-    let delta = parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR - 1;
+    let delta = (parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR).saturating_sub(1);
     let mut limit = parent_gas_limit;
-    let desired_limit = min(desired_limit, MIN_GAS_LIMIT);
+    let desired_limit = max(desired_limit, MIN_GAS_LIMIT);
     if limit < desired_limit {
         limit = parent_gas_limit + delta;
         if limit > desired_limit {
@@ -136,7 +196,7 @@ fn calc_gas_limit(parent_gas_limit: u64, desired_limit: u64) -> u64 {
     limit
 }
 
-fn calc_excess_blob_gas(parent_excess_blob_gas: u64, parent_blob_gas_used: u64) -> u64 {
+pub fn calc_excess_blob_gas(parent_excess_blob_gas: u64, parent_blob_gas_used: u64) -> u64 {
     let excess_blob_gas = parent_excess_blob_gas + parent_blob_gas_used;
     if excess_blob_gas < TARGET_BLOB_GAS_PER_BLOCK {
         0
@@ -145,6 +205,59 @@ fn calc_excess_blob_gas(parent_excess_blob_gas: u64, parent_blob_gas_used: u64)
     }
 }
 
+/// Base fees of a block projected by [project_fees]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeProjection {
+    pub number: BlockNumber,
+    pub base_fee_per_gas: u64,
+    /// Only set if Cancun is active on the parent block
+    pub base_fee_per_blob_gas: Option<u64>,
+}
+
+/// Projects the base fees of the next blocks built on top of the given header, assuming each
+/// of them uses the given percentage of its gas limit and holds the given amount of blobs.
+/// The gas limit of the projected blocks moves towards the given one as in built payloads.
+/// The projection stops early if the base fee grows too big to be computed.
+pub fn project_fees(
+    parent: &BlockHeader,
+    block_count: u64,
+    gas_used_percent: u64,
+    blobs_per_block: u64,
+    gas_ceil: u64,
+) -> Vec<FeeProjection> {
+    let gas_used_percent = gas_used_percent.min(100);
+    let blob_gas_used_per_block = blobs_per_block.min(MAX_BLOB_NUMBER_PER_BLOCK) * GAS_PER_BLOB;
+    let mut projections = vec![];
+    let mut gas_limit = parent.gas_limit;
+    let mut gas_used = parent.gas_used;
+    let mut base_fee = parent.base_fee_per_gas.unwrap_or_default();
+    let mut excess_blob_gas = parent.excess_blob_gas;
+    let mut blob_gas_used = parent.blob_gas_used.unwrap_or_default();
+    for number in parent.number + 1..=parent.number.saturating_add(block_count) {
+        // The products in the base fee update are bounded by the base fee times the gas limit
+        if base_fee.checked_mul(gas_limit).is_none() {
+            break;
+        }
+        let next_gas_limit = calc_gas_limit(gas_limit, gas_ceil);
+        let Some(next_base_fee) =
+            calculate_base_fee_per_gas(next_gas_limit, gas_limit, gas_used, base_fee)
+        else {
+            break;
+        };
+        excess_blob_gas = excess_blob_gas.map(|excess| calc_excess_blob_gas(excess, blob_gas_used));
+        projections.push(FeeProjection {
+            number,
+            base_fee_per_gas: next_base_fee,
+            base_fee_per_blob_gas: excess_blob_gas.map(calculate_base_fee_per_blob_gas),
+        });
+        gas_limit = next_gas_limit;
+        gas_used = (gas_limit as u128 * gas_used_percent as u128 / 100) as u64;
+        base_fee = next_base_fee;
+        blob_gas_used = blob_gas_used_per_block;
+    }
+    projections
+}
+
 pub struct PayloadBuildContext<'a> {
     pub payload: &'a mut Block,
     pub evm_state: &'a mut EvmState,
@@ -153,10 +266,18 @@ pub struct PayloadBuildContext<'a> {
     pub block_value: U256,
     base_fee_per_blob_gas: U256,
     pub blobs_bundle: BlobsBundle,
+    pub requests: Vec<EncodedRequests>,
+    /// Set to stop filling the payload, as it was superseded
+    interrupt: Option<&'a AtomicBool>,
+    config: &'a PayloadBuilderConfig,
 }
 
 impl<'a> PayloadBuildContext<'a> {
-    fn new(payload: &'a mut Block, evm_state: &'a mut EvmState) -> Self {
+    fn new(
+        payload: &'a mut Block,
+        evm_state: &'a mut EvmState,
+        config: &'a PayloadBuilderConfig,
+    ) -> Self {
         PayloadBuildContext {
             remaining_gas: payload.header.gas_limit,
             receipts: vec![],
@@ -167,6 +288,9 @@ impl<'a> PayloadBuildContext<'a> {
             payload,
             evm_state,
             blobs_bundle: BlobsBundle::default(),
+            requests: vec![],
+            interrupt: None,
+            config,
         }
     }
 }
@@ -191,20 +315,96 @@ impl<'a> PayloadBuildContext<'a> {
     fn base_fee_per_gas(&self) -> Option<u64> {
         self.payload.header.base_fee_per_gas
     }
+
+    fn is_interrupted(&self) -> bool {
+        self.interrupt
+            .is_some_and(|interrupt| interrupt.load(AtomicOrdering::Relaxed))
+    }
+
+    /// Logs the forced transactions that were left out of the payload
+    fn audit_excluded(&self, txs: Vec<MempoolTransaction>, reason: &str) {
+        let forced = &self.config.forced_inclusion_list;
+        for tx in txs.iter().filter(|tx| forced.includes(tx)) {
+            warn!(
+                "Forced transaction {:#x} from {:#x} was left out of payload {}: {reason}",
+                tx.compute_hash(),
+                tx.sender(),
+                self.block_number()
+            );
+        }
+    }
+}
+
+/// What the consensus client needs from a built payload besides the block
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PayloadBuildOutput {
+    pub blobs_bundle: BlobsBundle,
+    pub block_value: U256,
+    /// Requests queued by the transactions of a Prague block, only the types with requests
+    pub requests: Vec<EncodedRequests>,
 }
 
-/// Completes the payload building process, return the block value
+/// Completes the payload building process and takes its transactions out of the mempool
 pub fn build_payload(
     payload: &mut Block,
     store: &Store,
-) -> Result<(BlobsBundle, U256), ChainError> {
+    config: &PayloadBuilderConfig,
+) -> Result<PayloadBuildOutput, ChainError> {
+    let built = fill_payload(payload, store, config)?;
+    remove_included_transactions(payload, store)?;
+    Ok(built)
+}
+
+/// Completes the payload building process leaving its transactions in the mempool, so the
+/// payload can be built again from the same template once more transactions arrive
+pub fn fill_payload(
+    payload: &mut Block,
+    store: &Store,
+    config: &PayloadBuilderConfig,
+) -> Result<PayloadBuildOutput, ChainError> {
+    fill_payload_inner(payload, store, config, None)
+}
+
+/// Fills the payload like [fill_payload], failing with [ChainError::BuildInterrupted] as soon as
+/// the interrupt flag is set between two transactions.
+/// Used to stop builds on top of a parent that is no longer the head without waiting for them.
+pub fn fill_payload_interruptible(
+    payload: &mut Block,
+    store: &Store,
+    config: &PayloadBuilderConfig,
+    interrupt: &AtomicBool,
+) -> Result<PayloadBuildOutput, ChainError> {
+    fill_payload_inner(payload, store, config, Some(interrupt))
+}
+
+fn fill_payload_inner(
+    payload: &mut Block,
+    store: &Store,
+    config: &PayloadBuilderConfig,
+    interrupt: Option<&AtomicBool>,
+) -> Result<PayloadBuildOutput, ChainError> {
+    let _span = info_span!("build_payload", number = payload.header.number).entered();
     debug!("Building payload");
     let mut evm_state = evm_state(store.clone(), payload.header.parent_hash);
-    let mut context = PayloadBuildContext::new(payload, &mut evm_state);
+    let mut context = PayloadBuildContext::new(payload, &mut evm_state, config);
+    context.interrupt = interrupt;
     apply_withdrawals(&mut context)?;
     fill_transactions(&mut context)?;
+    apply_requests(&mut context)?;
     finalize_payload(&mut context)?;
-    Ok((context.blobs_bundle, context.block_value))
+    Ok(PayloadBuildOutput {
+        blobs_bundle: context.blobs_bundle,
+        block_value: context.block_value,
+        requests: context.requests,
+    })
+}
+
+/// Pulls the transactions of a built payload from the mempool
+pub fn remove_included_transactions(payload: &Block, store: &Store) -> Result<(), StoreError> {
+    for transaction in &payload.body.transactions {
+        mempool::remove_transaction(&transaction.compute_hash(), store)?;
+    }
+    Ok(())
 }
 
 pub fn apply_withdrawals(context: &mut PayloadBuildContext) -> Result<(), EvmError> {
@@ -218,6 +418,26 @@ pub fn apply_withdrawals(context: &mut PayloadBuildContext) -> Result<(), EvmErr
     Ok(())
 }
 
+/// Commits the payload of a Prague block to the requests queued by its transactions
+pub fn apply_requests(context: &mut PayloadBuildContext) -> Result<(), EvmError> {
+    if context.payload.header.requests_hash.is_none() {
+        return Ok(());
+    }
+    let spec_id = spec_id(&context.chain_config()?, context.payload.header.timestamp);
+    let requests = extract_requests(
+        context.evm_state,
+        &context.payload.header,
+        spec_id,
+        &context.receipts,
+    )?;
+    context.payload.header.requests_hash = Some(compute_requests_hash(&requests));
+    context.requests = requests
+        .into_iter()
+        .filter(|requests| !requests.is_empty())
+        .collect();
+    Ok(())
+}
+
 /// Fetches suitable transactions from the mempool
 /// Returns two transaction queues, one for plain and one for blob txs
 fn fetch_mempool_transactions(
@@ -245,11 +465,13 @@ fn fetch_mempool_transactions(
         TransactionQueue::new(
             mempool::filter_transactions(&plain_tx_filter, store)?,
             context.base_fee_per_gas(),
+            &context.config.forced_inclusion_list,
         )?,
         // Blob txs
         TransactionQueue::new(
             mempool::filter_transactions(&blob_tx_filter, store)?,
             context.base_fee_per_gas(),
+            &context.config.forced_inclusion_list,
         )?,
     ))
 }
@@ -263,16 +485,23 @@ pub fn fill_transactions(context: &mut PayloadBuildContext) -> Result<(), ChainE
     let (mut plain_txs, mut blob_txs) = fetch_mempool_transactions(context)?;
     // Execute and add transactions to payload (if suitable)
     loop {
+        if context.is_interrupted() {
+            debug!("Payload build interrupted");
+            return Err(ChainError::BuildInterrupted);
+        }
         // Check if we have enough gas to run more transactions
         if context.remaining_gas < TX_GAS_COST {
             debug!("No more gas to run transactions");
+            let mut left_out = plain_txs.clear();
+            left_out.extend(blob_txs.clear());
+            context.audit_excluded(left_out, "no gas left");
             break;
         };
         if !blob_txs.is_empty()
             && context.blobs_bundle.blobs.len() as u64 * GAS_PER_BLOB >= MAX_BLOB_GAS_PER_BLOCK
         {
             debug!("No more blob gas to run blob transactions");
-            blob_txs.clear();
+            context.audit_excluded(blob_txs.clear(), "no blob gas left");
         }
         // Fetch the next transactions
         let (head_tx, is_blob) = match (plain_txs.peek(), blob_txs.peek()) {
@@ -296,7 +525,14 @@ pub fn fill_transactions(context: &mut PayloadBuildContext) -> Result<(), ChainE
                 head_tx.tx.compute_hash()
             );
             // We don't have enough gas left for the transaction, so we skip all txs from this account
-            txs.pop();
+            context.audit_excluded(
+                txs.pop(),
+                &format!(
+                    "gas limit {} over the remaining {}",
+                    head_tx.tx.gas_limit(),
+                    context.remaining_gas
+                ),
+            );
             continue;
         }
 
@@ -308,7 +544,7 @@ pub fn fill_transactions(context: &mut PayloadBuildContext) -> Result<(), ChainE
             // Ignore replay protected tx & all txs from the sender
             // Pull transaction from the mempool
             debug!("Ignoring replay-protected transaction: {}", tx_hash);
-            txs.pop();
+            context.audit_excluded(txs.pop(), "replay protection is not active");
             mempool::remove_transaction(
                 &head_tx.tx.compute_hash(),
                 context
@@ -317,23 +553,42 @@ pub fn fill_transactions(context: &mut PayloadBuildContext) -> Result<(), ChainE
             )?;
             continue;
         }
+        // Check the inclusion conditions of conditional transactions
+        let store = context
+            .store()
+            .ok_or(ChainError::StoreError(StoreError::MissingStore))?;
+        if let Some(conditions) = store.get_transaction_conditions_from_pool(tx_hash)? {
+            if !conditions.matches_header(&context.payload.header) {
+                debug!("Skipping transaction: {tx_hash}, block conditions not met");
+                context.audit_excluded(txs.pop(), "block conditions not met");
+                if conditions
+                    .block_number_max
+                    .is_some_and(|max| context.block_number() > max)
+                    || conditions
+                        .timestamp_max
+                        .is_some_and(|max| context.payload.header.timestamp > max)
+                {
+                    mempool::remove_transaction(&tx_hash, store)?;
+                }
+                continue;
+            }
+            if !mempool::known_accounts_match(&conditions, context.parent_hash(), store)? {
+                debug!("Ignoring transaction: {tx_hash}, known accounts changed");
+                context.audit_excluded(txs.pop(), "known accounts changed");
+                mempool::remove_transaction(&tx_hash, store)?;
+                continue;
+            }
+        }
         // Execute tx
         let receipt = match apply_transaction(&head_tx, context) {
             Ok(receipt) => {
-                txs.shift()?;
-                // Pull transaction from the mempool
-                mempool::remove_transaction(
-                    &head_tx.tx.compute_hash(),
-                    context
-                        .store()
-                        .ok_or(ChainError::StoreError(StoreError::MissingStore))?,
-                )?;
+                txs.shift(&context.config.forced_inclusion_list)?;
                 receipt
             }
             // Ignore following txs from sender
             Err(e) => {
                 debug!("Failed to execute transaction: {}, {e}", tx_hash);
-                txs.pop();
+                context.audit_excluded(txs.pop(), &format!("execution failed: {e}"));
                 continue;
             }
         };
@@ -427,7 +682,10 @@ fn finalize_payload(context: &mut PayloadBuildContext) -> Result<(), StoreError>
         .unwrap_or_default();
     context.payload.header.transactions_root =
         compute_transactions_root(&context.payload.body.transactions);
-    context.payload.header.receipts_root = compute_receipts_root(&context.receipts);
+    (
+        context.payload.header.receipts_root,
+        context.payload.header.logs_bloom,
+    ) = compute_receipts_root_and_logs_bloom(&context.receipts);
     context.payload.header.gas_used = context.payload.header.gas_limit - context.remaining_gas;
     Ok(())
 }
@@ -448,6 +706,9 @@ struct HeadTransaction {
     tx: MempoolTransaction,
     sender: Address,
     tip: u64,
+    /// Whether a transaction of the sender is in the forced inclusion list, so its
+    /// transactions go before the ones of the other senders
+    forced: bool,
 }
 
 impl std::ops::Deref for HeadTransaction {
@@ -469,12 +730,15 @@ impl TransactionQueue {
     fn new(
         mut txs: HashMap<Address, Vec<MempoolTransaction>>,
         base_fee: Option<u64>,
+        forced_inclusion_list: &ForcedInclusionList,
     ) -> Result<Self, ChainError> {
         let mut heads = Vec::new();
         for (address, txs) in txs.iter_mut() {
             // Pull the first tx from each list and add it to the heads list
             // This should be a newly filtered tx list so we are guaranteed to have a first element
             let head_tx = txs.remove(0);
+            let forced = forced_inclusion_list.includes(&head_tx)
+                || txs.iter().any(|tx| forced_inclusion_list.includes(tx));
             heads.push(HeadTransaction {
                 // We already ran this method when filtering the transactions from the mempool so it shouldn't fail
                 tip: head_tx
@@ -484,6 +748,7 @@ impl TransactionQueue {
                     ))?,
                 tx: head_tx,
                 sender: *address,
+                forced,
             });
         }
         // Sort heads by higest tip (and lowest timestamp if tip is equal)
@@ -495,10 +760,11 @@ impl TransactionQueue {
         })
     }
 
-    /// Remove all transactions from the queue
-    fn clear(&mut self) {
-        self.heads.clear();
-        self.txs.clear();
+    /// Remove all transactions from the queue, returning them
+    fn clear(&mut self) -> Vec<MempoolTransaction> {
+        let mut removed: Vec<_> = self.heads.drain(..).map(|head| head.tx).collect();
+        removed.extend(self.txs.drain().flat_map(|(_, txs)| txs));
+        removed
     }
 
     /// Returns true if there are no more transactions in the queue
@@ -512,22 +778,28 @@ impl TransactionQueue {
         self.heads.first().cloned()
     }
 
-    /// Removes current head transaction and all transactions from the given sender
-    fn pop(&mut self) {
-        if !self.is_empty() {
-            let sender = self.heads.remove(0).sender;
-            self.txs.remove(&sender);
+    /// Removes current head transaction and all transactions from the given sender,
+    /// returning them
+    fn pop(&mut self) -> Vec<MempoolTransaction> {
+        if self.is_empty() {
+            return vec![];
         }
+        let head = self.heads.remove(0);
+        let mut removed = vec![head.tx];
+        removed.extend(self.txs.remove(&head.sender).unwrap_or_default());
+        removed
     }
 
     /// Remove the top transaction
     /// Add a tx from the same sender to the head transactions
-    fn shift(&mut self) -> Result<(), ChainError> {
+    fn shift(&mut self, forced_inclusion_list: &ForcedInclusionList) -> Result<(), ChainError> {
         let tx = self.heads.remove(0);
         if let Some(txs) = self.txs.get_mut(&tx.sender) {
             // Fetch next head
             if !txs.is_empty() {
                 let head_tx = txs.remove(0);
+                let forced = forced_inclusion_list.includes(&head_tx)
+                    || txs.iter().any(|tx| forced_inclusion_list.includes(tx));
                 let head = HeadTransaction {
                     // We already ran this method when filtering the transactions from the mempool so it shouldn't fail
                     tip: head_tx.effective_gas_tip(self.base_fee).ok_or(
@@ -537,6 +809,7 @@ impl TransactionQueue {
                     )?,
                     tx: head_tx,
                     sender: tx.sender,
+                    forced,
                 };
                 // Insert head into heads list while maintaing order
                 let index = match self.heads.binary_search(&head) {
@@ -550,10 +823,14 @@ impl TransactionQueue {
     }
 }
 
-// Orders transactions by highest tip, if tip is equal, orders by lowest timestamp
+// Orders the forced transactions first, then by highest tip, and if tip is equal, by lowest timestamp
 impl Ord for HeadTransaction {
     fn cmp(&self, other: &Self) -> Ordering {
-        match other.tip.cmp(&self.tip) {
+        match other
+            .forced
+            .cmp(&self.forced)
+            .then(other.tip.cmp(&self.tip))
+        {
             Ordering::Equal => self.tx.time().cmp(&other.tx.time()),
             ordering => ordering,
         }
@@ -565,3 +842,121 @@ impl PartialOrd for HeadTransaction {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calc_gas_limit_moves_towards_target_within_bound() {
+        let parent_gas_limit = 30_000_000;
+        let delta = parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR - 1;
+        assert_eq!(
+            calc_gas_limit(parent_gas_limit, 36_000_000),
+            parent_gas_limit + delta
+        );
+        assert_eq!(
+            calc_gas_limit(parent_gas_limit, 20_000_000),
+            parent_gas_limit - delta
+        );
+        assert_eq!(
+            calc_gas_limit(parent_gas_limit, parent_gas_limit + 10),
+            parent_gas_limit + 10
+        );
+        assert_eq!(
+            calc_gas_limit(parent_gas_limit, parent_gas_limit),
+            parent_gas_limit
+        );
+        // The gas limit never goes below the protocol minimum
+        assert_eq!(calc_gas_limit(MIN_GAS_LIMIT + 1, 0), MIN_GAS_LIMIT);
+    }
+
+    #[test]
+    fn project_fees_follows_block_fullness() {
+        let parent = BlockHeader {
+            number: 10,
+            gas_limit: DEFAULT_BUILDER_GAS_CEIL,
+            gas_used: DEFAULT_BUILDER_GAS_CEIL / 2,
+            base_fee_per_gas: Some(1_000_000_000),
+            excess_blob_gas: Some(10_000_000),
+            blob_gas_used: Some(MAX_BLOB_GAS_PER_BLOCK),
+            ..Default::default()
+        };
+        // Blocks at the gas target keep the base fee, while full blob blocks raise the blob fee
+        let at_target = project_fees(
+            &parent,
+            3,
+            50,
+            MAX_BLOB_NUMBER_PER_BLOCK,
+            DEFAULT_BUILDER_GAS_CEIL,
+        );
+        assert_eq!(
+            at_target.iter().map(|fees| fees.number).collect::<Vec<_>>(),
+            vec![11, 12, 13]
+        );
+        assert!(at_target
+            .iter()
+            .all(|fees| fees.base_fee_per_gas == 1_000_000_000));
+        let blob_fees: Vec<_> = at_target
+            .iter()
+            .map(|fees| fees.base_fee_per_blob_gas.unwrap())
+            .collect();
+        assert!(blob_fees[0] < blob_fees[1] && blob_fees[1] < blob_fees[2]);
+
+        // Full blocks raise the base fee by 1/8 and empty ones lower it by 1/8
+        let full = project_fees(&parent, 2, 100, 0, DEFAULT_BUILDER_GAS_CEIL);
+        assert_eq!(full[0].base_fee_per_gas, 1_000_000_000);
+        assert_eq!(full[1].base_fee_per_gas, 1_125_000_000);
+        let empty = project_fees(&parent, 2, 0, 0, DEFAULT_BUILDER_GAS_CEIL);
+        assert_eq!(empty[1].base_fee_per_gas, 875_000_000);
+        assert_eq!(
+            empty[1].base_fee_per_blob_gas,
+            full[1].base_fee_per_blob_gas
+        );
+
+        let pre_cancun = BlockHeader {
+            excess_blob_gas: None,
+            ..parent
+        };
+        assert!(
+            project_fees(&pre_cancun, 1, 50, 0, DEFAULT_BUILDER_GAS_CEIL)[0]
+                .base_fee_per_blob_gas
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn forced_transactions_go_before_higher_tips() {
+        let forced_address = Address::repeat_byte(1);
+        let list = ForcedInclusionList {
+            addresses: HashSet::from([forced_address]),
+            ..Default::default()
+        };
+        let transaction = |to| {
+            Transaction::EIP1559Transaction(ethrex_core::types::EIP1559Transaction {
+                to: TxKind::Call(to),
+                ..Default::default()
+            })
+        };
+        let to_forced = MempoolTransaction::new(transaction(forced_address), Address::zero());
+        let from_forced = MempoolTransaction::new(transaction(Address::zero()), forced_address);
+        let other = MempoolTransaction::new(transaction(Address::zero()), Address::zero());
+        assert!(list.includes(&to_forced) && list.includes(&from_forced));
+        assert!(!list.includes(&other));
+        let by_hash = ForcedInclusionList {
+            tx_hashes: HashSet::from([other.compute_hash()]),
+            ..Default::default()
+        };
+        assert!(by_hash.includes(&other));
+
+        let head = |tx: &MempoolTransaction, tip, forced| HeadTransaction {
+            tx: tx.clone(),
+            sender: tx.sender(),
+            tip,
+            forced,
+        };
+        let mut heads = [head(&other, 10, false), head(&to_forced, 1, true)];
+        heads.sort();
+        assert!(heads[0].forced);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use ethrex_core::types::{Block, BlockHash};
+use ethrex_storage::Store;
+use tracing::{debug, warn};
+
+use crate::{
+    error::ChainError,
+    payload::{
+        fill_payload_interruptible, remove_included_transactions, PayloadBuildOutput,
+        PayloadBuilderConfig,
+    },
+};
+
+/// Time between the builds of a payload, so it picks up the transactions that arrived since
+pub const PAYLOAD_REBUILD_INTERVAL: Duration = Duration::from_secs(1);
+/// Time after which a payload stops being rebuilt if it was never requested
+pub const PAYLOAD_BUILD_TIMEOUT: Duration = Duration::from_secs(12);
+
+/// A payload filled with the transactions of the mempool
+#[derive(Debug, Clone)]
+pub struct BuiltPayload {
+    pub block: Block,
+    pub output: PayloadBuildOutput,
+    /// Time it took to fill the payload
+    pub build_time: Duration,
+    /// Milliseconds left until the getPayload deadline of the slot when the payload was sealed,
+    /// negative if it was sealed late. None if the payload wasn't built against a deadline
+    pub deadline_margin_ms: Option<i64>,
+}
+
+/// Payload rebuilt in the background every [PAYLOAD_REBUILD_INTERVAL] from its template, keeping
+/// the most valuable build, until it is finished, cancelled or [PAYLOAD_BUILD_TIMEOUT] passes.
+/// The rebuilds also stop once the next one wouldn't be done by the time the consensus client is
+/// expected to retrieve the payload, so the best build is ready by then.
+/// Dropping the job stops the rebuilds once the one in progress is done.
+#[derive(Debug)]
+pub struct PayloadJob {
+    template: Block,
+    config: Arc<PayloadBuilderConfig>,
+    deadline: SystemTime,
+    best: Arc<Mutex<Option<BuiltPayload>>>,
+    stop: mpsc::Sender<()>,
+    interrupt: Arc<AtomicBool>,
+    builder: JoinHandle<()>,
+}
+
+impl PayloadJob {
+    /// Starts building the payload given by the template in the background, improving it until
+    /// the given deadline
+    pub fn start(
+        template: Block,
+        config: Arc<PayloadBuilderConfig>,
+        store: Store,
+        deadline: SystemTime,
+    ) -> Self {
+        let best = Arc::new(Mutex::new(None));
+        let (stop, stopped) = mpsc::channel();
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let builder = {
+            let template = template.clone();
+            let config = config.clone();
+            let best = best.clone();
+            let interrupt = interrupt.clone();
+            std::thread::spawn(move || {
+                let time_left = deadline
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                let deadline = Instant::now() + time_left.min(PAYLOAD_BUILD_TIMEOUT);
+                loop {
+                    let build_time = match build(&template, &config, &store, &interrupt) {
+                        Ok(built) => {
+                            let build_time = built.build_time;
+                            keep_best(&best, built);
+                            build_time
+                        }
+                        Err(ChainError::BuildInterrupted) => {
+                            debug!("Payload {} build was cancelled", template.header.number);
+                            break;
+                        }
+                        Err(error) => {
+                            warn!(
+                                "Failed to build payload {}: {error}",
+                                template.header.number
+                            );
+                            Duration::ZERO
+                        }
+                    };
+                    // The next build is assumed to take as long as the last one
+                    if Instant::now() + PAYLOAD_REBUILD_INTERVAL + build_time > deadline {
+                        debug!("Payload {} is no longer rebuilt", template.header.number);
+                        break;
+                    }
+                    match stopped.recv_timeout(PAYLOAD_REBUILD_INTERVAL) {
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        _ => break,
+                    }
+                }
+            })
+        };
+        Self {
+            template,
+            config,
+            deadline,
+            best,
+            stop,
+            interrupt,
+            builder,
+        }
+    }
+
+    /// Stops the rebuilds, interrupting the one in progress, as the payload won't be requested.
+    /// Used when the parent of the payload is no longer the head, so the consensus client would
+    /// reject it.
+    pub fn cancel(&self) {
+        self.interrupt.store(true, Ordering::Relaxed);
+        let _ = self.stop.send(());
+    }
+
+    /// Timestamp of the payload being built
+    pub fn timestamp(&self) -> u64 {
+        self.template.header.timestamp
+    }
+
+    /// Hash of the block the payload is built on top of
+    pub fn parent_hash(&self) -> BlockHash {
+        self.template.header.parent_hash
+    }
+
+    /// Stops the rebuilds and returns the most valuable build, waiting for the first build if it
+    /// didn't finish yet. The transactions of the returned payload are pulled from the mempool.
+    pub fn finish(self, store: &Store) -> Result<BuiltPayload, ChainError> {
+        let _ = self.stop.send(());
+        let mut best = take(&self.best);
+        if best.is_none() {
+            if self.builder.join().is_err() {
+                warn!("Payload {} builder panicked", self.template.header.number);
+            }
+            best = take(&self.best);
+        }
+        let mut built = match best {
+            Some(built) => built,
+            // Every build failed, try again to surface the error
+            None => build(&self.template, &self.config, store, &self.interrupt)?,
+        };
+        built.deadline_margin_ms = Some(millis_until(self.deadline));
+        remove_included_transactions(&built.block, store)?;
+        Ok(built)
+    }
+}
+
+fn build(
+    template: &Block,
+    config: &PayloadBuilderConfig,
+    store: &Store,
+    interrupt: &AtomicBool,
+) -> Result<BuiltPayload, ChainError> {
+    let start = Instant::now();
+    let mut block = template.clone();
+    let output = fill_payload_interruptible(&mut block, store, config, interrupt)?;
+    Ok(BuiltPayload {
+        block,
+        output,
+        build_time: start.elapsed(),
+        deadline_margin_ms: None,
+    })
+}
+
+/// Milliseconds from now until the given time, negative if it already passed
+fn millis_until(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::now()) {
+        Ok(left) => left.as_millis().try_into().unwrap_or(i64::MAX),
+        Err(passed) => -passed.duration().as_millis().try_into().unwrap_or(i64::MAX),
+    }
+}
+
+fn keep_best(best: &Mutex<Option<BuiltPayload>>, built: BuiltPayload) {
+    let mut best = best.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if best
+        .as_ref()
+        .is_none_or(|best| built.output.block_value >= best.output.block_value)
+    {
+        *best = Some(built);
+    }
+}
+
+fn take(best: &Mutex<Option<BuiltPayload>>) -> Option<BuiltPayload> {
+    best.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
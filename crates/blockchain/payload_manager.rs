@@ -0,0 +1,209 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use ethrex_core::types::{Block, BlockHash};
+use ethrex_storage::{error::StoreError, Store};
+use tracing::debug;
+
+use crate::{payload::PayloadBuilderConfig, payload_job::PayloadJob};
+
+/// Seconds between two slots of the consensus layer. A payload is retrieved within the slot it
+/// is built for, so the ones more than a slot older than a new payload are garbage-collected.
+pub const SLOT_DURATION_SECONDS: u64 = 12;
+
+/// Time into its slot by which the consensus client is expected to retrieve a payload, a third
+/// of the slot as the block has to be propagated before the attestations of the slot
+pub const DEFAULT_GET_PAYLOAD_DEADLINE: Duration = Duration::from_secs(4);
+
+/// Tracks the payloads being built in the background by id, until the consensus client
+/// retrieves them, a newer fork choice supersedes them or they expire
+#[derive(Debug, Clone)]
+pub struct PayloadManager {
+    jobs: Arc<Mutex<HashMap<u64, PayloadJob>>>,
+    /// Time into the slot of a payload by which it has to be ready to be retrieved
+    get_payload_deadline: Duration,
+    config: Arc<PayloadBuilderConfig>,
+}
+
+impl Default for PayloadManager {
+    fn default() -> Self {
+        PayloadManager::new(DEFAULT_GET_PAYLOAD_DEADLINE, Default::default())
+    }
+}
+
+impl PayloadManager {
+    pub fn new(get_payload_deadline: Duration, config: PayloadBuilderConfig) -> Self {
+        PayloadManager {
+            jobs: Default::default(),
+            get_payload_deadline,
+            config: Arc::new(config),
+        }
+    }
+
+    /// Settings the payloads are built with
+    pub fn config(&self) -> &PayloadBuilderConfig {
+        &self.config
+    }
+
+    /// Stores the payload and starts building it, unless it is already being built.
+    /// The payloads and jobs more than [SLOT_DURATION_SECONDS] older than it are removed.
+    pub fn start(&self, payload_id: u64, payload: Block, store: &Store) -> Result<(), StoreError> {
+        let expiry = payload
+            .header
+            .timestamp
+            .saturating_sub(SLOT_DURATION_SECONDS);
+        store.remove_payloads_older_than(expiry)?;
+        store.add_payload(payload_id, payload.clone())?;
+        let mut jobs = self.lock();
+        jobs.retain(|_, job| {
+            let expired = job.timestamp() < expiry;
+            if expired {
+                job.cancel();
+            }
+            !expired
+        });
+        // The timestamp of a payload is the start of its slot
+        let deadline =
+            UNIX_EPOCH + Duration::from_secs(payload.header.timestamp) + self.get_payload_deadline;
+        jobs.entry(payload_id).or_insert_with(|| {
+            PayloadJob::start(payload, self.config.clone(), store.clone(), deadline)
+        });
+        Ok(())
+    }
+
+    /// Cancels the jobs that don't build on top of the new head, interrupting their builds, and
+    /// removes their payloads, as the consensus client would reject them
+    pub fn cancel_superseded(&self, head_hash: BlockHash, store: &Store) -> Result<(), StoreError> {
+        let mut jobs = self.lock();
+        let superseded: Vec<u64> = jobs
+            .iter()
+            .filter(|(_, job)| job.parent_hash() != head_hash)
+            .map(|(payload_id, _)| *payload_id)
+            .collect();
+        for payload_id in superseded {
+            debug!("Payload {payload_id:#018x} was superseded by the new head {head_hash:#x}");
+            if let Some(job) = jobs.remove(&payload_id) {
+                job.cancel();
+            }
+            store.remove_payload(payload_id)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the timestamp of the payload being built with the given id
+    pub fn timestamp(&self, payload_id: u64) -> Option<u64> {
+        self.lock().get(&payload_id).map(PayloadJob::timestamp)
+    }
+
+    /// Removes the job building the payload with the given id, so it can be finished
+    pub fn take(&self, payload_id: u64) -> Option<PayloadJob> {
+        self.lock().remove(&payload_id)
+    }
+
+    /// Returns the ids of the payloads being built
+    pub fn payload_ids(&self) -> Vec<u64> {
+        self.lock().keys().copied().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, PayloadJob>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::{create_payload, BuildPayloadArgs, DEFAULT_BUILDER_GAS_CEIL};
+    use ethrex_core::{
+        types::{BlockHeader, ChainConfig},
+        H256,
+    };
+    use ethrex_storage::EngineType;
+    use std::time::SystemTime;
+
+    fn payload(parent_hash: BlockHash, timestamp: u64) -> Block {
+        Block::new(
+            BlockHeader {
+                parent_hash,
+                timestamp,
+                ..Default::default()
+            },
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn superseded_and_expired_payloads_are_removed() {
+        let store = Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        // The jobs build the payloads in the background, which reads the chain config
+        store.set_chain_config(&ChainConfig::default()).unwrap();
+        let manager = PayloadManager::default();
+        let (head, other_head) = (H256::repeat_byte(1), H256::repeat_byte(2));
+        manager.start(1, payload(head, 100), &store).unwrap();
+        manager.start(2, payload(other_head, 100), &store).unwrap();
+
+        manager.cancel_superseded(head, &store).unwrap();
+        assert_eq!(manager.payload_ids(), vec![1]);
+        assert!(store.get_payload(2).unwrap().is_none());
+
+        // The payload of the previous slot is kept, older ones are collected
+        manager
+            .start(3, payload(head, 100 + SLOT_DURATION_SECONDS), &store)
+            .unwrap();
+        assert_eq!(manager.timestamp(1), Some(100));
+        manager
+            .start(4, payload(head, 100 + SLOT_DURATION_SECONDS * 2), &store)
+            .unwrap();
+        assert!(manager.take(1).is_none());
+        assert!(store.get_payload(1).unwrap().is_none());
+        assert!(manager.take(3).is_some());
+        assert_eq!(manager.payload_ids(), vec![4]);
+    }
+
+    #[test]
+    fn payloads_report_how_close_to_the_deadline_they_were_sealed() {
+        let store = Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let genesis =
+            serde_json::from_str(include_str!("../../test_data/genesis-execution-api.json"))
+                .expect("Failed to deserialize genesis file");
+        store.add_initial_state(genesis).unwrap();
+        let parent = store.get_canonical_block_hash(0).unwrap().unwrap();
+        let payload = |timestamp| {
+            let args = BuildPayloadArgs {
+                parent,
+                timestamp,
+                fee_recipient: Default::default(),
+                random: Default::default(),
+                withdrawals: vec![],
+                beacon_root: Some(Default::default()),
+                version: 3,
+                extra_data: Default::default(),
+                gas_ceil: DEFAULT_BUILDER_GAS_CEIL,
+            };
+            create_payload(&args, &store).unwrap()
+        };
+        let manager = PayloadManager::new(Duration::from_secs(4), Default::default());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        manager.start(1, payload(now), &store).unwrap();
+        manager
+            .start(2, payload(now - SLOT_DURATION_SECONDS + 1), &store)
+            .unwrap();
+
+        // The payload of the current slot is retrieved before its deadline
+        let built = manager.take(1).unwrap().finish(&store).unwrap();
+        let margin = built.deadline_margin_ms.unwrap();
+        assert!(margin > 0 && margin <= 4000);
+
+        // The deadline of the payload of the previous slot already passed
+        let built = manager.take(2).unwrap().finish(&store).unwrap();
+        assert!(built.deadline_margin_ms.unwrap() < 0);
+    }
+}
@@ -1,20 +1,31 @@
 #[cfg(test)]
 mod blockchain_integration_test {
-    use std::{fs::File, io::BufReader};
+    use std::{
+        fs::File,
+        io::BufReader,
+        sync::{Arc, Mutex},
+    };
 
     use crate::{
         add_block,
-        error::{ChainError, InvalidForkChoice},
+        error::{ChainError, ForkChoiceElement, InvalidBlockError, InvalidForkChoice},
+        events::ChainEvent,
         fork_choice::apply_fork_choice,
-        is_canonical, latest_canonical_block_hash,
-        payload::{build_payload, create_payload, BuildPayloadArgs},
+        is_canonical, latest_canonical_block_hash, mempool,
+        payload::{build_payload, create_payload, BuildPayloadArgs, DEFAULT_BUILDER_GAS_CEIL},
+        validate_block_commitments,
     };
 
     use ethrex_core::{
-        types::{Block, BlockHeader},
-        H160, H256,
+        types::{
+            compute_requests_hash, Block, BlockHeader, DepositRequest, EIP1559Transaction,
+            EncodedRequests, Genesis, GenesisAccount, Signable, Transaction, TxKind,
+            DEPOSIT_EVENT_TOPIC, DEPOSIT_REQUEST_TYPE,
+        },
+        Bytes, H160, H256, U256,
     };
     use ethrex_storage::{EngineType, Store};
+    use secp256k1::SecretKey;
 
     #[test]
     fn test_small_to_long_reorg() {
@@ -189,6 +200,135 @@ mod blockchain_integration_test {
         assert!(store.get_latest_block_number().unwrap() == Some(2));
     }
 
+    #[test]
+    fn reorg_of_finalized_block_should_fail() {
+        // Store and genesis
+        let store = test_store();
+        let genesis_header = store.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis_header.compute_block_hash();
+
+        // Make a chain of two blocks canonical with the first one finalized.
+        let block_1a = new_block(&store, &genesis_header);
+        let hash_1a = block_1a.hash();
+        add_block(&block_1a, &store).expect("Could not add block 1a.");
+        let block_2a = new_block(&store, &block_1a.header);
+        let hash_2a = block_2a.hash();
+        add_block(&block_2a, &store).expect("Could not add block 2a.");
+        apply_fork_choice(&store, hash_2a, hash_1a, hash_1a).unwrap();
+
+        // A head on a fork from genesis would remove the finalized block.
+        let block_1b = new_block(&store, &genesis_header);
+        let hash_1b = block_1b.hash();
+        add_block(&block_1b, &store).expect("Could not add block 1b.");
+        let result = apply_fork_choice(&store, hash_1b, genesis_hash, genesis_hash);
+
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::ReorgBelowFinalized(1, 1))
+        ));
+        assert!(is_canonical(&store, 1, hash_1a).unwrap());
+        assert!(is_canonical(&store, 2, hash_2a).unwrap());
+        assert!(!is_canonical(&store, 1, hash_1b).unwrap());
+    }
+
+    #[test]
+    fn reorg_deeper_than_the_maximum_should_fail() {
+        let mut store = test_store();
+        store.set_max_reorg_depth(1);
+        let genesis_header = store.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis_header.compute_block_hash();
+
+        let block_1a = new_block(&store, &genesis_header);
+        add_block(&block_1a, &store).expect("Could not add block 1a.");
+        let block_2a = new_block(&store, &block_1a.header);
+        let hash_2a = block_2a.hash();
+        add_block(&block_2a, &store).expect("Could not add block 2a.");
+        apply_fork_choice(&store, hash_2a, genesis_hash, genesis_hash).unwrap();
+
+        // A head on a fork from genesis would remove both blocks
+        let block_1b = new_block(&store, &genesis_header);
+        let hash_1b = block_1b.hash();
+        add_block(&block_1b, &store).expect("Could not add block 1b.");
+        let result = apply_fork_choice(&store, hash_1b, genesis_hash, genesis_hash);
+
+        assert!(matches!(result, Err(InvalidForkChoice::ReorgTooDeep(2, 1))));
+        assert!(is_canonical(&store, 2, hash_2a).unwrap());
+    }
+
+    #[test]
+    fn safe_and_finalized_blocks_must_be_ancestors_of_the_head() {
+        let store = test_store();
+        let genesis_header = store.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis_header.compute_block_hash();
+
+        let block_1a = new_block(&store, &genesis_header);
+        add_block(&block_1a, &store).unwrap();
+        let block_2a = new_block(&store, &block_1a.header);
+        add_block(&block_2a, &store).unwrap();
+        let block_1b = new_block(&store, &genesis_header);
+        add_block(&block_1b, &store).unwrap();
+        let (hash_1a, hash_2a, hash_1b) = (block_1a.hash(), block_2a.hash(), block_1b.hash());
+
+        // Unknown blocks can't be part of the head's chain, even when the safe hash is null
+        let result = apply_fork_choice(&store, hash_2a, H256::zero(), H256::random());
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::ElementNotFound(
+                ForkChoiceElement::Finalized
+            ))
+        ));
+        let result = apply_fork_choice(&store, hash_2a, H256::random(), genesis_hash);
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::ElementNotFound(ForkChoiceElement::Safe))
+        ));
+        // The finalized block can't be after the safe one nor after the head
+        let result = apply_fork_choice(&store, hash_2a, hash_1a, hash_2a);
+        assert!(matches!(result, Err(InvalidForkChoice::Unordered)));
+        let result = apply_fork_choice(&store, hash_1a, H256::zero(), hash_2a);
+        assert!(matches!(result, Err(InvalidForkChoice::Unordered)));
+        // Blocks of another branch are not ancestors of the head
+        let result = apply_fork_choice(&store, hash_2a, hash_1b, genesis_hash);
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::Disconnected(
+                ForkChoiceElement::Head,
+                ForkChoiceElement::Safe
+            ))
+        ));
+        let result = apply_fork_choice(&store, hash_2a, H256::zero(), hash_1b);
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::Disconnected(
+                ForkChoiceElement::Head,
+                ForkChoiceElement::Finalized
+            ))
+        ));
+        // None of the rejected states changed the canonical chain markers
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(0));
+        assert_eq!(store.get_finalized_block_number().unwrap(), None);
+
+        apply_fork_choice(&store, hash_2a, hash_1a, genesis_hash).unwrap();
+        assert_eq!(store.get_safe_block_number().unwrap(), Some(1));
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(2));
+
+        // Canonical blocks replaced by the new head are not its ancestors
+        let result = apply_fork_choice(&store, hash_1b, hash_1a, genesis_hash);
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::Disconnected(
+                ForkChoiceElement::Head,
+                ForkChoiceElement::Safe
+            ))
+        ));
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(2));
+        assert_eq!(store.get_canonical_block_hash(1).unwrap(), Some(hash_1a));
+
+        apply_fork_choice(&store, hash_1b, genesis_hash, genesis_hash).unwrap();
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(1));
+        assert_eq!(store.get_canonical_block_hash(1).unwrap(), Some(hash_1b));
+    }
+
     #[test]
     fn latest_block_number_should_always_be_the_canonical_head() {
         // Goal: put a, b in the same branch, both canonical.
@@ -230,6 +370,178 @@ mod blockchain_integration_test {
         assert_eq!(latest_canonical_block_hash(&store).unwrap(), hash_b);
     }
 
+    #[test]
+    fn block_commitments_should_match_the_body() {
+        let store = test_store();
+        let genesis_header = store.get_block_header(0).unwrap().unwrap();
+        let block = new_block(&store, &genesis_header);
+        assert!(validate_block_commitments(&block, &[]).is_ok());
+
+        let mut tampered = block.clone();
+        tampered.header.transactions_root = H256::random();
+        assert!(matches!(
+            validate_block_commitments(&tampered, &[]),
+            Err(ChainError::InvalidBlock(
+                InvalidBlockError::TransactionsRootMismatch
+            ))
+        ));
+
+        let mut tampered = block.clone();
+        tampered.body.withdrawals = None;
+        assert!(matches!(
+            validate_block_commitments(&tampered, &[]),
+            Err(ChainError::InvalidBlock(
+                InvalidBlockError::WithdrawalsRootMismatch
+            ))
+        ));
+
+        let mut tampered = block;
+        tampered.header.gas_used = 21000;
+        assert!(matches!(
+            validate_block_commitments(&tampered, &[]),
+            Err(ChainError::InvalidBlock(InvalidBlockError::GasUsedMismatch))
+        ));
+    }
+
+    #[test]
+    fn fork_choice_updates_emit_chain_events() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let listened = events.clone();
+        let mut store = test_store();
+        store.set_chain_event_listener(move |event| listened.lock().unwrap().push(event));
+        let genesis_header = store.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis_header.compute_block_hash();
+        let block_1a = new_block(&store, &genesis_header);
+        add_block(&block_1a, &store).unwrap();
+        let block_2a = new_block(&store, &block_1a.header);
+        add_block(&block_2a, &store).unwrap();
+        let block_1b = new_block(&store, &genesis_header);
+        add_block(&block_1b, &store).unwrap();
+
+        apply_fork_choice(&store, block_2a.hash(), genesis_hash, genesis_hash).unwrap();
+        apply_fork_choice(&store, block_1b.hash(), block_1b.hash(), block_1b.hash()).unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ChainEvent::NewHead {
+                    number: 2,
+                    hash: block_2a.hash()
+                },
+                // No block was finalized before
+                ChainEvent::Finalized {
+                    number: 0,
+                    hash: genesis_hash
+                },
+                ChainEvent::Reorg {
+                    number: 1,
+                    hash: block_1b.hash(),
+                    depth: 2,
+                    dropped: vec![block_1a.hash(), block_2a.hash()],
+                },
+                ChainEvent::Finalized {
+                    number: 1,
+                    hash: block_1b.hash()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn prague_block_with_deposit() {
+        let deposit_contract = H160::from_low_u64_be(0xde9051);
+        let deposit = DepositRequest {
+            pubkey: [1; 48],
+            withdrawal_credentials: H256::repeat_byte(2),
+            amount: 32_000_000_000,
+            signature: [3; 96],
+            index: 7,
+        };
+        let secret_key = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let mut tx = Transaction::EIP1559Transaction(EIP1559Transaction {
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: 1_000_000_000,
+            gas_limit: 100_000,
+            to: TxKind::Call(deposit_contract),
+            ..Default::default()
+        });
+
+        let mut genesis: Genesis = serde_json::from_reader(BufReader::new(
+            File::open("../../test_data/genesis-execution-api.json")
+                .expect("Failed to open genesis file"),
+        ))
+        .expect("Failed to deserialize genesis file");
+        genesis.config.prague_time = Some(0);
+        genesis.config.deposit_contract_address = Some(deposit_contract);
+        if let Transaction::EIP1559Transaction(tx) = &mut tx {
+            tx.chain_id = genesis.config.chain_id;
+        }
+        tx.sign_inplace(&secret_key);
+        genesis.alloc.insert(
+            tx.sender(),
+            GenesisAccount {
+                code: Bytes::new(),
+                storage: Default::default(),
+                balance: U256::from(10).pow(U256::from(18)),
+                nonce: 0,
+            },
+        );
+        genesis.alloc.insert(
+            deposit_contract,
+            GenesisAccount {
+                code: deposit_contract_code(&deposit.encode_log_data()),
+                storage: Default::default(),
+                balance: U256::zero(),
+                nonce: 0,
+            },
+        );
+        let store =
+            Store::new("store.db", EngineType::InMemory).expect("Failed to build DB for testing");
+        store.add_initial_state(genesis).unwrap();
+        let genesis_header = store.get_block_header(0).unwrap().unwrap();
+        mempool::add_transaction(tx, &store).unwrap();
+
+        // The payload commits to the deposit made by its transaction
+        let block = new_block(&store, &genesis_header);
+        assert_eq!(block.body.transactions.len(), 1);
+        let requests = [EncodedRequests::new(
+            DEPOSIT_REQUEST_TYPE,
+            &deposit.encode(),
+        )];
+        assert_eq!(
+            block.header.requests_hash,
+            Some(compute_requests_hash(&requests))
+        );
+        add_block(&block, &store).unwrap();
+
+        // A block leaving the deposit out is rejected
+        let mut block = block;
+        block.header.requests_hash = Some(compute_requests_hash(&[]));
+        assert!(matches!(
+            add_block(&block, &store),
+            Err(ChainError::InvalidBlock(
+                InvalidBlockError::RequestsHashMismatch
+            ))
+        ));
+    }
+
+    /// Code that emits a `DepositEvent` log with the given data
+    fn deposit_contract_code(event_data: &[u8]) -> Bytes {
+        let size = (event_data.len() as u16).to_be_bytes();
+        let code_size = 48_u8;
+        [
+            // Copy the data appended to the code to memory
+            &[0x61, size[0], size[1], 0x60, code_size, 0x60, 0x00, 0x39][..],
+            // Emit the log and stop
+            &[0x7f],
+            DEPOSIT_EVENT_TOPIC.as_bytes(),
+            &[0x61, size[0], size[1], 0x60, 0x00, 0xa1, 0x00],
+            event_data,
+        ]
+        .concat()
+        .into()
+    }
+
     fn new_block(store: &Store, parent: &BlockHeader) -> Block {
         let args = BuildPayloadArgs {
             parent: parent.compute_block_hash(),
@@ -239,10 +551,12 @@ mod blockchain_integration_test {
             withdrawals: Vec::new(),
             beacon_root: Some(H256::random()),
             version: 1,
+            extra_data: Bytes::new(),
+            gas_ceil: DEFAULT_BUILDER_GAS_CEIL,
         };
 
         let mut block = create_payload(&args, store).unwrap();
-        build_payload(&mut block, store).unwrap();
+        build_payload(&mut block, store, &Default::default()).unwrap();
         block
     }
 
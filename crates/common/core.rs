@@ -1,4 +1,5 @@
 pub use ethereum_types::*;
+pub mod errors;
 pub mod serde_utils;
 pub mod types;
 pub use bytes::Bytes;
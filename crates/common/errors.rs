@@ -0,0 +1,91 @@
+use std::fmt::{self, Display};
+
+use ethrex_trie::TrieError;
+
+/// Machine-readable classification of an error, kept as the error is propagated from the storage
+/// up to the RPC and the syncer, so they can react to it without matching on the variants of
+/// every layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The database failed to read or write
+    Database,
+    /// Stored data couldn't be decoded
+    Corrupted,
+    /// Data needed by the operation is not available yet, such as the parent of a block
+    Unavailable,
+    /// A block, transaction or request failed validation
+    Invalid,
+    /// The execution of a block or transaction failed
+    Execution,
+    /// An unexpected failure
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Database => "DATABASE",
+            ErrorCode::Corrupted => "CORRUPTED",
+            ErrorCode::Unavailable => "UNAVAILABLE",
+            ErrorCode::Invalid => "INVALID",
+            ErrorCode::Execution => "EXECUTION",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+
+    /// Whether an operation failing with this code may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorCode::Database | ErrorCode::Unavailable)
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error carrying its [ErrorCode]. Errors wrapping the error of a lower layer return the
+/// code of the wrapped error.
+pub trait CodedError: std::error::Error {
+    fn code(&self) -> ErrorCode;
+
+    fn is_retryable(&self) -> bool {
+        self.code().is_retryable()
+    }
+}
+
+impl CodedError for TrieError {
+    fn code(&self) -> ErrorCode {
+        if matches!(
+            self,
+            TrieError::RLPDecode(_) | TrieError::Verify(_) | TrieError::InconsistentTree
+        ) {
+            ErrorCode::Corrupted
+        } else {
+            ErrorCode::Database
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_transient_failures_are_retryable() {
+        let retryable: Vec<ErrorCode> = [
+            ErrorCode::Database,
+            ErrorCode::Corrupted,
+            ErrorCode::Unavailable,
+            ErrorCode::Invalid,
+            ErrorCode::Execution,
+            ErrorCode::Internal,
+        ]
+        .into_iter()
+        .filter(ErrorCode::is_retryable)
+        .collect();
+        assert_eq!(retryable, [ErrorCode::Database, ErrorCode::Unavailable]);
+        assert_eq!(TrieError::InconsistentTree.code(), ErrorCode::Corrupted);
+    }
+}
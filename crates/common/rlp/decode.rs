@@ -388,14 +388,14 @@ pub fn decode_rlp_item(data: &[u8]) -> Result<(bool, &[u8], &[u8]), RLPDecodeErr
             }
             let length_bytes = &data[1..length_of_length + 1];
             let length = usize::from_be_bytes(static_left_pad(length_bytes)?);
-            if data.len() < length_of_length + length + 1 {
+            // The length comes from untrusted input, so it could overflow
+            let end = length
+                .checked_add(length_of_length + 1)
+                .ok_or(RLPDecodeError::InvalidLength)?;
+            if data.len() < end {
                 return Err(RLPDecodeError::InvalidLength);
             }
-            Ok((
-                false,
-                &data[length_of_length + 1..length_of_length + length + 1],
-                &data[length_of_length + length + 1..],
-            ))
+            Ok((false, &data[length_of_length + 1..end], &data[end..]))
         }
         RLP_EMPTY_LIST..=0xF7 => {
             let length = (first_byte - RLP_EMPTY_LIST) as usize;
@@ -411,14 +411,14 @@ pub fn decode_rlp_item(data: &[u8]) -> Result<(bool, &[u8], &[u8]), RLPDecodeErr
             }
             let length_bytes = &data[1..list_length + 1];
             let payload_length = usize::from_be_bytes(static_left_pad(length_bytes)?);
-            if data.len() < list_length + payload_length + 1 {
+            // The length comes from untrusted input, so it could overflow
+            let end = payload_length
+                .checked_add(list_length + 1)
+                .ok_or(RLPDecodeError::InvalidLength)?;
+            if data.len() < end {
                 return Err(RLPDecodeError::InvalidLength);
             }
-            Ok((
-                true,
-                &data[list_length + 1..list_length + payload_length + 1],
-                &data[list_length + payload_length + 1..],
-            ))
+            Ok((true, &data[list_length + 1..end], &data[end..]))
         }
     }
 }
@@ -453,7 +453,11 @@ pub fn get_item_with_prefix(data: &[u8]) -> Result<(&[u8], &[u8]), RLPDecodeErro
             }
             let length_bytes = &data[1..length_of_length + 1];
             let length = usize::from_be_bytes(static_left_pad(length_bytes)?);
-            if data.len() < length_of_length + length + 1 {
+            // The length comes from untrusted input, so it could overflow
+            let end = length
+                .checked_add(length_of_length + 1)
+                .ok_or(RLPDecodeError::InvalidLength)?;
+            if data.len() < end {
                 return Err(RLPDecodeError::InvalidLength);
             }
             Ok((
@@ -475,7 +479,11 @@ pub fn get_item_with_prefix(data: &[u8]) -> Result<(&[u8], &[u8]), RLPDecodeErro
             }
             let length_bytes = &data[1..list_length + 1];
             let payload_length = usize::from_be_bytes(static_left_pad(length_bytes)?);
-            if data.len() < list_length + payload_length + 1 {
+            // The length comes from untrusted input, so it could overflow
+            let end = payload_length
+                .checked_add(list_length + 1)
+                .ok_or(RLPDecodeError::InvalidLength)?;
+            if data.len() < end {
                 return Err(RLPDecodeError::InvalidLength);
             }
             Ok((
@@ -810,4 +818,15 @@ mod tests {
         // It should fail because a list is not a string
         assert!(decoded.is_err());
     }
+
+    #[test]
+    fn test_decode_rlp_item_with_overflowing_length() {
+        // Long string and long list prefixes whose length is usize::MAX
+        let mut string = vec![0xBF];
+        string.extend_from_slice(&[0xFF; 8]);
+        assert!(decode_rlp_item(&string).is_err());
+        let mut list = vec![0xFF];
+        list.extend_from_slice(&[0xFF; 8]);
+        assert!(decode_rlp_item(&list).is_err());
+    }
 }
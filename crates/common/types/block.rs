@@ -14,11 +14,14 @@ use ethrex_rlp::{
     error::RLPDecodeError,
     structs::{Decoder, Encoder},
 };
-use ethrex_trie::Trie;
+use ethrex_trie::{Trie, TrieError};
 use keccak_hash::keccak;
 use serde::{Deserialize, Serialize};
 
-use std::cmp::{max, Ordering};
+use std::{
+    cmp::{max, Ordering},
+    num::NonZeroUsize,
+};
 
 pub type BlockNumber = u64;
 pub type BlockHash = H256;
@@ -125,6 +128,9 @@ pub struct BlockHeader {
     )]
     pub excess_blob_gas: Option<u64>,
     pub parent_beacon_block_root: Option<H256>,
+    /// Commitment to the execution layer requests of the block, see [EIP-7685](https://eips.ethereum.org/EIPS/eip-7685)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub requests_hash: Option<H256>,
 }
 
 impl RLPEncode for BlockHeader {
@@ -150,6 +156,7 @@ impl RLPEncode for BlockHeader {
             .encode_optional_field(&self.blob_gas_used)
             .encode_optional_field(&self.excess_blob_gas)
             .encode_optional_field(&self.parent_beacon_block_root)
+            .encode_optional_field(&self.requests_hash)
             .finish();
     }
 }
@@ -178,6 +185,7 @@ impl RLPDecode for BlockHeader {
         let (blob_gas_used, decoder) = decoder.decode_optional_field();
         let (excess_blob_gas, decoder) = decoder.decode_optional_field();
         let (parent_beacon_block_root, decoder) = decoder.decode_optional_field();
+        let (requests_hash, decoder) = decoder.decode_optional_field();
 
         Ok((
             BlockHeader {
@@ -201,6 +209,7 @@ impl RLPDecode for BlockHeader {
                 blob_gas_used,
                 excess_blob_gas,
                 parent_beacon_block_root,
+                requests_hash,
             },
             decoder.finish()?,
         ))
@@ -238,11 +247,101 @@ pub fn compute_transactions_root(transactions: &[Transaction]) -> H256 {
 }
 
 pub fn compute_receipts_root(receipts: &[Receipt]) -> H256 {
+    compute_receipts_root_and_logs_bloom(receipts).0
+}
+
+/// Minimum amount of receipts for them to be encoded and merkleized in parallel,
+/// the threads cost more than they save on smaller blocks
+pub const PARALLEL_RECEIPTS_THRESHOLD: usize = 256;
+
+/// Computes the receipts root and the logs bloom of a block in a single pass over its receipts.
+/// On large blocks, the receipts are encoded and their blooms aggregated in chunks on separate
+/// threads, and the subtries of the receipts trie are built in parallel.
+pub fn compute_receipts_root_and_logs_bloom(receipts: &[Receipt]) -> (H256, Bloom) {
+    if receipts.len() < PARALLEL_RECEIPTS_THRESHOLD {
+        let iter = receipts
+            .iter()
+            .enumerate()
+            .map(|(idx, receipt)| (idx.encode_to_vec(), receipt.encode_to_vec()));
+        return (
+            Trie::compute_hash_from_unsorted_iter(iter),
+            compute_logs_bloom(receipts),
+        );
+    }
+    let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = receipts.len().div_ceil(threads);
+    let chunks = std::thread::scope(|scope| {
+        let encoders: Vec<_> = receipts
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, receipts)| {
+                scope.spawn(move || {
+                    let encoded: Vec<_> = receipts
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, receipt)| {
+                            let idx = chunk * chunk_size + idx;
+                            (idx.encode_to_vec(), receipt.encode_to_vec())
+                        })
+                        .collect();
+                    (encoded, compute_logs_bloom(receipts))
+                })
+            })
+            .collect();
+        encoders
+            .into_iter()
+            .map(|encoder| encoder.join().expect("Receipts encoder panicked"))
+            .collect::<Vec<_>>()
+    });
+    let mut encoded = Vec::with_capacity(receipts.len());
+    let mut bloom = Bloom::zero();
+    for (chunk, chunk_bloom) in chunks {
+        encoded.extend(chunk);
+        bloom.accrue_bloom(&chunk_bloom);
+    }
+    (Trie::compute_hash_in_parallel(encoded), bloom)
+}
+
+/// Computes the proof of the receipt with the given index in the receipts trie of a block,
+/// which can be checked against the receipts root of its header
+pub fn compute_receipt_proof(
+    receipts: &[Receipt],
+    index: usize,
+) -> Result<Vec<Vec<u8>>, TrieError> {
     let iter = receipts
         .iter()
         .enumerate()
         .map(|(idx, receipt)| (idx.encode_to_vec(), receipt.encode_to_vec()));
-    Trie::compute_hash_from_unsorted_iter(iter)
+    Trie::compute_proof_from_unsorted_iter(iter, &index.encode_to_vec())
+}
+
+/// Computes the logs bloom of a block, the union of the blooms of all its receipts
+pub fn compute_logs_bloom(receipts: &[Receipt]) -> Bloom {
+    receipts.iter().fold(Bloom::zero(), |mut bloom, receipt| {
+        bloom.accrue_bloom(&receipt.bloom);
+        bloom
+    })
+}
+
+/// Computes the priority fees paid to the fee recipient of a block by its transactions,
+/// the part of the fees that is not burned as per EIP-1559
+pub fn compute_priority_fees(
+    transactions: &[Transaction],
+    receipts: &[Receipt],
+    base_fee_per_gas: Option<u64>,
+) -> U256 {
+    let mut previous_cumulative_gas_used = 0;
+    transactions
+        .iter()
+        .zip(receipts)
+        .fold(U256::zero(), |fees, (tx, receipt)| {
+            let gas_used = receipt
+                .cumulative_gas_used
+                .saturating_sub(previous_cumulative_gas_used);
+            previous_cumulative_gas_used = receipt.cumulative_gas_used;
+            let tip = tx.effective_gas_tip(base_fee_per_gas).unwrap_or_default();
+            fees + U256::from(gas_used) * U256::from(tip)
+        })
 }
 
 // See [EIP-4895](https://eips.ethereum.org/EIPS/eip-4895)
@@ -633,6 +732,7 @@ mod test {
             blob_gas_used: Some(0x00),
             excess_blob_gas: Some(0x00),
             parent_beacon_block_root: Some(H256::zero()),
+            requests_hash: None,
         };
         let block = BlockHeader {
             parent_hash: H256::from_str(
@@ -675,6 +775,7 @@ mod test {
             blob_gas_used: Some(0x00),
             excess_blob_gas: Some(0x00),
             parent_beacon_block_root: Some(H256::zero()),
+            requests_hash: None,
         };
         assert!(validate_block_header(&block, &parent_block).is_ok())
     }
@@ -701,4 +802,137 @@ mod test {
         );
         assert_eq!(transactions_root, expected_root);
     }
+
+    #[test]
+    fn logs_bloom_is_the_union_of_receipt_blooms() {
+        use crate::types::{Log, TxType};
+        let log = |address| Log {
+            address: Address::from_low_u64_be(address),
+            topics: vec![H256::from_low_u64_be(address)],
+            data: Bytes::new(),
+        };
+        let receipts = vec![
+            Receipt::new(TxType::Legacy, true, 21000, vec![log(1)]),
+            Receipt::new(TxType::EIP1559, true, 42000, vec![]),
+            Receipt::new(TxType::EIP1559, true, 63000, vec![log(2)]),
+        ];
+        let bloom = compute_logs_bloom(&receipts);
+        assert!(bloom.contains_bloom(&receipts[0].bloom));
+        assert!(bloom.contains_bloom(&receipts[2].bloom));
+        assert_ne!(bloom, receipts[0].bloom);
+        assert_eq!(compute_logs_bloom(&[]), Bloom::zero());
+
+        let mut receipt = receipts[0].clone();
+        assert!(!receipt.rebuild_bloom());
+        receipt.bloom = Bloom::zero();
+        assert!(receipt.rebuild_bloom());
+        assert_eq!(receipt, receipts[0]);
+    }
+
+    #[test]
+    fn receipts_of_large_blocks_are_merkleized_in_parallel() {
+        use crate::types::{Log, TxType};
+        let receipts: Vec<Receipt> = (1..=PARALLEL_RECEIPTS_THRESHOLD as u64 * 4)
+            .map(|i| {
+                let logs = (i % 3 == 0)
+                    .then(|| Log {
+                        address: Address::from_low_u64_be(i),
+                        topics: vec![H256::from_low_u64_be(i)],
+                        data: Bytes::new(),
+                    })
+                    .into_iter()
+                    .collect();
+                Receipt::new(TxType::EIP1559, i % 5 != 0, 21000 * i, logs)
+            })
+            .collect();
+        let expected_root = Trie::compute_hash_from_unsorted_iter(
+            receipts
+                .iter()
+                .enumerate()
+                .map(|(idx, receipt)| (idx.encode_to_vec(), receipt.encode_to_vec())),
+        );
+        let expected_bloom = receipts.iter().fold(Bloom::zero(), |mut bloom, receipt| {
+            bloom.accrue_bloom(&receipt.bloom);
+            bloom
+        });
+        assert_eq!(
+            compute_receipts_root_and_logs_bloom(&receipts),
+            (expected_root, expected_bloom)
+        );
+        assert_eq!(compute_logs_bloom(&receipts), expected_bloom);
+    }
+
+    #[test]
+    fn receipt_proofs_resolve_to_the_receipts_root() {
+        use crate::types::TxType;
+        let receipts: Vec<Receipt> = (1..=20)
+            .map(|i| Receipt::new(TxType::EIP1559, true, 21000 * i, vec![]))
+            .collect();
+        let receipts_root = compute_receipts_root(&receipts);
+        for index in [0, 7, 19] {
+            let proof = compute_receipt_proof(&receipts, index).unwrap();
+            let trie = Trie::from_nodes(proof.first(), &proof[1..]).unwrap();
+            assert_eq!(trie.hash_no_commit(), receipts_root);
+            assert_eq!(
+                trie.get(&index.encode_to_vec()).unwrap(),
+                Some(receipts[index].encode_to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn requests_hash_is_part_of_the_header() {
+        use ethrex_rlp::decode::RLPDecode;
+        let header = BlockHeader {
+            base_fee_per_gas: Some(7),
+            withdrawals_root: Some(H256::zero()),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(H256::zero()),
+            requests_hash: Some(H256::repeat_byte(1)),
+            ..Default::default()
+        };
+        let decoded = BlockHeader::decode(&header.encode_to_vec()).unwrap();
+        assert_eq!(decoded, header);
+        let pre_prague_header = BlockHeader {
+            requests_hash: None,
+            ..header.clone()
+        };
+        assert_ne!(
+            pre_prague_header.compute_block_hash(),
+            header.compute_block_hash()
+        );
+    }
+
+    #[test]
+    fn priority_fees_use_the_gas_used_by_each_transaction() {
+        use crate::types::{EIP1559Transaction, LegacyTransaction, TxType};
+        let transactions = vec![
+            Transaction::LegacyTransaction(LegacyTransaction {
+                gas_price: 15,
+                ..Default::default()
+            }),
+            Transaction::EIP1559Transaction(EIP1559Transaction {
+                max_priority_fee_per_gas: 2,
+                max_fee_per_gas: 20,
+                ..Default::default()
+            }),
+            Transaction::EIP1559Transaction(EIP1559Transaction {
+                max_priority_fee_per_gas: 8,
+                max_fee_per_gas: 14,
+                ..Default::default()
+            }),
+        ];
+        let receipts = vec![
+            Receipt::new(TxType::Legacy, true, 21000, vec![]),
+            Receipt::new(TxType::EIP1559, true, 71000, vec![]),
+            Receipt::new(TxType::EIP1559, false, 100000, vec![]),
+        ];
+        // Base fee of 10: tips are 5, 2 and 4
+        assert_eq!(
+            compute_priority_fees(&transactions, &receipts, Some(10)),
+            U256::from(21000 * 5 + 50000 * 2 + 29000 * 4)
+        );
+        assert_eq!(compute_priority_fees(&[], &[], Some(10)), U256::zero());
+    }
 }
@@ -0,0 +1,24 @@
+use super::{BlockHash, BlockNumber};
+
+/// Change of the canonical chain made by a fork choice update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// The head moved to a descendant of the previous one
+    NewHead {
+        number: BlockNumber,
+        hash: BlockHash,
+    },
+    /// The head moved to another branch, removing `depth` blocks from the canonical chain
+    Reorg {
+        number: BlockNumber,
+        hash: BlockHash,
+        depth: u64,
+        /// Hashes of the removed blocks, lowest first
+        dropped: Vec<BlockHash>,
+    },
+    /// A new block was finalized
+    Finalized {
+        number: BlockNumber,
+        hash: BlockHash,
+    },
+}
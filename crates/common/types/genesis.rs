@@ -8,8 +8,9 @@ use std::collections::HashMap;
 use ethrex_rlp::encode::RLPEncode;
 
 use super::{
-    compute_receipts_root, compute_transactions_root, compute_withdrawals_root, AccountState,
-    Block, BlockBody, BlockHeader, BlockNumber, DEFAULT_OMMERS_HASH, INITIAL_BASE_FEE,
+    compute_receipts_root, compute_requests_hash, compute_transactions_root,
+    compute_withdrawals_root, AccountState, Block, BlockBody, BlockHeader, BlockNumber,
+    DEFAULT_OMMERS_HASH, INITIAL_BASE_FEE,
 };
 
 #[allow(unused)]
@@ -85,6 +86,12 @@ pub struct ChainConfig {
     /// Network has already passed the terminal total difficult
     #[serde(default)]
     pub terminal_total_difficulty_passed: bool,
+
+    /// Contract whose deposit logs are the deposit requests of Prague blocks, see EIP-6110.
+    /// Prague is only activated if it is set, so configs that schedule Prague without it keep
+    /// following the Cancun rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deposit_contract_address: Option<Address>,
 }
 
 #[derive(Debug, PartialEq, PartialOrd)]
@@ -92,6 +99,7 @@ pub enum Fork {
     Paris = 0,
     Shanghai = 1,
     Cancun = 2,
+    Prague = 3,
 }
 
 impl ChainConfig {
@@ -104,6 +112,11 @@ impl ChainConfig {
         self.cancun_time.is_some_and(|time| time <= block_timestamp)
     }
 
+    pub fn is_prague_activated(&self, block_timestamp: u64) -> bool {
+        self.deposit_contract_address.is_some()
+            && self.prague_time.is_some_and(|time| time <= block_timestamp)
+    }
+
     pub fn is_istanbul_activated(&self, block_number: BlockNumber) -> bool {
         self.istanbul_block.is_some_and(|num| num <= block_number)
     }
@@ -113,7 +126,9 @@ impl ChainConfig {
     }
 
     pub fn get_fork(&self, block_timestamp: u64) -> Fork {
-        if self.is_cancun_activated(block_timestamp) {
+        if self.is_prague_activated(block_timestamp) {
+            Fork::Prague
+        } else if self.is_cancun_activated(block_timestamp) {
             Fork::Cancun
         } else if self.is_shanghai_activated(block_timestamp) {
             Fork::Shanghai
@@ -154,6 +169,85 @@ impl ChainConfig {
 
         (block_number_based_forks, timestamp_based_forks)
     }
+
+    pub fn fork_schedule(&self) -> ForkSchedule {
+        ForkSchedule {
+            shanghai_time: self.shanghai_time,
+            cancun_time: self.cancun_time,
+            prague_time: self.prague_time,
+            verkle_time: self.verkle_time,
+        }
+    }
+
+    /// Returns the config with the activation times of the given schedule, checking that the
+    /// forks active at the given timestamp are not changed and that the changed forks are
+    /// scheduled after it, in order
+    pub fn reschedule_forks(
+        &self,
+        schedule: ForkSchedule,
+        timestamp: u64,
+    ) -> Result<ChainConfig, ForkScheduleError> {
+        let current = self.fork_schedule();
+        let mut last_activation = 0;
+        for ((fork, current_time), (_, new_time)) in
+            current.forks().into_iter().zip(schedule.forks())
+        {
+            if current_time != new_time {
+                if current_time.is_some_and(|time| time <= timestamp) {
+                    return Err(ForkScheduleError::ActiveForkChanged(fork));
+                }
+                if let Some(time) = new_time.filter(|time| *time <= timestamp) {
+                    return Err(ForkScheduleError::ActivationInThePast(fork, time));
+                }
+            }
+            if let Some(time) = new_time {
+                if time < last_activation {
+                    return Err(ForkScheduleError::ActivationOutOfOrder(fork, time));
+                }
+                last_activation = time;
+            }
+        }
+        Ok(ChainConfig {
+            shanghai_time: schedule.shanghai_time,
+            cancun_time: schedule.cancun_time,
+            prague_time: schedule.prague_time,
+            verkle_time: schedule.verkle_time,
+            ..*self
+        })
+    }
+}
+
+/// Activation times of the timestamp based forks, which can be changed on a running node
+/// to schedule a fork on a custom network without restarting it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkSchedule {
+    pub shanghai_time: Option<u64>,
+    pub cancun_time: Option<u64>,
+    pub prague_time: Option<u64>,
+    pub verkle_time: Option<u64>,
+}
+
+impl ForkSchedule {
+    /// Name and activation time of each fork, in activation order
+    fn forks(&self) -> [(&'static str, Option<u64>); 4] {
+        [
+            ("shanghai", self.shanghai_time),
+            ("cancun", self.cancun_time),
+            ("prague", self.prague_time),
+            ("verkle", self.verkle_time),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ForkScheduleError {
+    #[error("Fork {0} is already active and can't be rescheduled")]
+    ActiveForkChanged(&'static str),
+    #[error("Fork {0} can't be scheduled at {1}, which is not after the latest block")]
+    ActivationInThePast(&'static str, u64),
+    #[error("Fork {0} can't be scheduled at {1}, before the forks preceding it")]
+    ActivationOutOfOrder(&'static str, u64),
 }
 
 #[allow(unused)]
@@ -212,6 +306,10 @@ impl Genesis {
                 .config
                 .is_cancun_activated(self.timestamp)
                 .then_some(H256::zero()),
+            requests_hash: self
+                .config
+                .is_prague_activated(self.timestamp)
+                .then_some(compute_requests_hash(&[])),
         }
     }
 
@@ -407,4 +505,61 @@ mod tests {
                 .unwrap();
         assert_eq!(genesis_block_hash, computed_block_hash)
     }
+
+    #[test]
+    fn only_future_forks_can_be_rescheduled() {
+        let config = ChainConfig {
+            shanghai_time: Some(0),
+            cancun_time: Some(100),
+            prague_time: Some(200),
+            ..Default::default()
+        };
+        let schedule = ForkSchedule {
+            prague_time: Some(300),
+            verkle_time: Some(400),
+            ..config.fork_schedule()
+        };
+        let rescheduled = config.reschedule_forks(schedule, 150).unwrap();
+        assert_eq!(rescheduled.fork_schedule(), schedule);
+        assert_eq!(rescheduled.chain_id, config.chain_id);
+
+        let active_fork_changed = ForkSchedule {
+            cancun_time: Some(160),
+            ..schedule
+        };
+        assert_eq!(
+            config.reschedule_forks(active_fork_changed, 150),
+            Err(ForkScheduleError::ActiveForkChanged("cancun"))
+        );
+        let past_activation = ForkSchedule {
+            prague_time: Some(120),
+            ..schedule
+        };
+        assert_eq!(
+            config.reschedule_forks(past_activation, 150),
+            Err(ForkScheduleError::ActivationInThePast("prague", 120))
+        );
+        let out_of_order = ForkSchedule {
+            verkle_time: Some(250),
+            ..schedule
+        };
+        assert_eq!(
+            config.reschedule_forks(out_of_order, 150),
+            Err(ForkScheduleError::ActivationOutOfOrder("verkle", 250))
+        );
+    }
+
+    #[test]
+    fn prague_is_only_activated_with_a_deposit_contract() {
+        let mut config = ChainConfig {
+            shanghai_time: Some(0),
+            cancun_time: Some(0),
+            prague_time: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(config.get_fork(150), Fork::Cancun);
+        config.deposit_contract_address = Some(Address::repeat_byte(0x42));
+        assert_eq!(config.get_fork(50), Fork::Cancun);
+        assert_eq!(config.get_fork(150), Fork::Prague);
+    }
 }
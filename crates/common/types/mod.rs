@@ -1,17 +1,25 @@
 mod account;
 pub mod blobs_bundle;
 mod block;
+mod chain_event;
 mod constants;
 mod fork_id;
 mod genesis;
 mod receipt;
+mod requests;
+mod state_access_stats;
 pub mod transaction;
+mod transaction_conditions;
 
 pub use account::*;
 pub use blobs_bundle::*;
 pub use block::*;
+pub use chain_event::*;
 pub use constants::*;
 pub use fork_id::*;
 pub use genesis::*;
 pub use receipt::*;
+pub use requests::*;
+pub use state_access_stats::*;
 pub use transaction::*;
+pub use transaction_conditions::*;
@@ -31,6 +31,14 @@ impl Receipt {
             logs,
         }
     }
+
+    /// Recomputes the bloom from the receipt's logs, returning whether it changed
+    pub fn rebuild_bloom(&mut self) -> bool {
+        let bloom = bloom_from_logs(&self.logs);
+        let changed = bloom != self.bloom;
+        self.bloom = bloom;
+        changed
+    }
 }
 
 fn bloom_from_logs(logs: &[Log]) -> Bloom {
@@ -0,0 +1,316 @@
+use k256::sha2::{Digest, Sha256};
+use lazy_static::lazy_static;
+
+use super::Receipt;
+use crate::{Address, Bytes, H256};
+
+/// Request type of the deposits made to the beacon chain deposit contract, see [EIP-6110](https://eips.ethereum.org/EIPS/eip-6110)
+pub const DEPOSIT_REQUEST_TYPE: u8 = 0x00;
+/// Request type of the withdrawals triggered from the execution layer, see [EIP-7002](https://eips.ethereum.org/EIPS/eip-7002)
+pub const WITHDRAWAL_REQUEST_TYPE: u8 = 0x01;
+/// Request type of the consolidations triggered from the execution layer, see [EIP-7251](https://eips.ethereum.org/EIPS/eip-7251)
+pub const CONSOLIDATION_REQUEST_TYPE: u8 = 0x02;
+
+/// Size of the data of a `DepositEvent` log: the offsets of its five fields followed by the
+/// length and padded value of each of them
+const DEPOSIT_EVENT_DATA_SIZE: usize = 576;
+const WITHDRAWAL_REQUEST_SIZE: usize = 20 + 48 + 8;
+const CONSOLIDATION_REQUEST_SIZE: usize = 20 + 48 + 48;
+
+lazy_static! {
+    pub static ref WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS: Address =
+        Address::from_slice(&hex::decode("00000961Ef480Eb55e80D19ad83579A64c007002").unwrap());
+    pub static ref CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS: Address =
+        Address::from_slice(&hex::decode("0000BBdDc7CE488642fb579F8B00f3a590007251").unwrap());
+    /// Topic of `DepositEvent(bytes,bytes,bytes,bytes,bytes)`, emitted by the deposit contract
+    pub static ref DEPOSIT_EVENT_TOPIC: H256 = H256::from_slice(
+        &hex::decode("649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5").unwrap()
+    );
+}
+
+pub type BlsPublicKey = [u8; 48];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepositRequest {
+    pub pubkey: BlsPublicKey,
+    pub withdrawal_credentials: H256,
+    /// Amount deposited in gwei
+    pub amount: u64,
+    pub signature: [u8; 96],
+    pub index: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawalRequest {
+    pub source_address: Address,
+    pub validator_pubkey: BlsPublicKey,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsolidationRequest {
+    pub source_address: Address,
+    pub source_pubkey: BlsPublicKey,
+    pub target_pubkey: BlsPublicKey,
+}
+
+impl DepositRequest {
+    /// Parses the data of a `DepositEvent` log of the deposit contract, returns `None` if its
+    /// layout is not the one of the deposit contract
+    pub fn decode_log_data(data: &[u8]) -> Option<Self> {
+        if data.len() != DEPOSIT_EVENT_DATA_SIZE {
+            return None;
+        }
+        // Each field is stored at a fixed offset as its length followed by its value
+        let field = |index: usize, offset: usize, size: usize| {
+            let word = |start: usize| data.get(start..start + 32);
+            let is_word = |word: &[u8], value: usize| {
+                word[..24].iter().all(|byte| *byte == 0)
+                    && u64::from_be_bytes(word[24..].try_into().unwrap()) == value as u64
+            };
+            if !is_word(word(index * 32)?, offset) || !is_word(word(offset)?, size) {
+                return None;
+            }
+            data.get(offset + 32..offset + 32 + size)
+        };
+        let pubkey = field(0, 160, 48)?;
+        let withdrawal_credentials = field(1, 256, 32)?;
+        let amount = field(2, 320, 8)?;
+        let signature = field(3, 384, 96)?;
+        let index = field(4, 512, 8)?;
+        Some(Self {
+            pubkey: pubkey.try_into().ok()?,
+            withdrawal_credentials: H256::from_slice(withdrawal_credentials),
+            // The amount and index are SSZ encoded, so they are little endian
+            amount: u64::from_le_bytes(amount.try_into().ok()?),
+            signature: signature.try_into().ok()?,
+            index: u64::from_le_bytes(index.try_into().ok()?),
+        })
+    }
+
+    /// Encodes the request as the data of the `DepositEvent` log the deposit contract emits
+    /// for it, the inverse of [DepositRequest::decode_log_data]
+    pub fn encode_log_data(&self) -> Vec<u8> {
+        let word = |value: usize| H256::from_low_u64_be(value as u64).0.to_vec();
+        // The length of a field followed by its value, padded to a whole number of words
+        let field = |value: &[u8]| {
+            let mut field = word(value.len());
+            field.extend(value);
+            field.resize(32 + value.len().div_ceil(32) * 32, 0);
+            field
+        };
+        [
+            word(160),
+            word(256),
+            word(320),
+            word(384),
+            word(512),
+            field(&self.pubkey),
+            field(self.withdrawal_credentials.as_bytes()),
+            field(&self.amount.to_le_bytes()),
+            field(&self.signature),
+            field(&self.index.to_le_bytes()),
+        ]
+        .concat()
+    }
+
+    /// Encodes the request as committed to by the requests hash
+    pub fn encode(&self) -> Vec<u8> {
+        [
+            &self.pubkey[..],
+            self.withdrawal_credentials.as_bytes(),
+            &self.amount.to_le_bytes(),
+            &self.signature,
+            &self.index.to_le_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// Collects the deposit requests of a block from the `DepositEvent` logs the deposit contract
+/// emitted during its execution, returns `None` if any of the logs is malformed, which makes
+/// the block invalid
+pub fn deposit_requests(
+    receipts: &[Receipt],
+    deposit_contract: Address,
+) -> Option<EncodedRequests> {
+    let mut data = vec![];
+    for log in receipts.iter().flat_map(|receipt| &receipt.logs) {
+        if log.address == deposit_contract && log.topics.first() == Some(&*DEPOSIT_EVENT_TOPIC) {
+            data.extend(DepositRequest::decode_log_data(&log.data)?.encode());
+        }
+    }
+    Some(EncodedRequests::new(DEPOSIT_REQUEST_TYPE, &data))
+}
+
+impl WithdrawalRequest {
+    /// Parses the requests returned by the system call to the withdrawal requests contract,
+    /// returns `None` if the output is not a list of requests
+    pub fn decode_list(data: &[u8]) -> Option<Vec<Self>> {
+        if !data.len().is_multiple_of(WITHDRAWAL_REQUEST_SIZE) {
+            return None;
+        }
+        data.chunks_exact(WITHDRAWAL_REQUEST_SIZE)
+            .map(|request| {
+                Some(Self {
+                    source_address: Address::from_slice(&request[..20]),
+                    validator_pubkey: request[20..68].try_into().ok()?,
+                    amount: u64::from_be_bytes(request[68..].try_into().ok()?),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ConsolidationRequest {
+    /// Parses the requests returned by the system call to the consolidation requests contract,
+    /// returns `None` if the output is not a list of requests
+    pub fn decode_list(data: &[u8]) -> Option<Vec<Self>> {
+        if !data.len().is_multiple_of(CONSOLIDATION_REQUEST_SIZE) {
+            return None;
+        }
+        data.chunks_exact(CONSOLIDATION_REQUEST_SIZE)
+            .map(|request| {
+                Some(Self {
+                    source_address: Address::from_slice(&request[..20]),
+                    source_pubkey: request[20..68].try_into().ok()?,
+                    target_pubkey: request[68..].try_into().ok()?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Requests of a single type as committed to by the requests hash of [EIP-7685](https://eips.ethereum.org/EIPS/eip-7685):
+/// the request type followed by the concatenation of the data of its requests
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedRequests(pub Bytes);
+
+impl EncodedRequests {
+    pub fn new(request_type: u8, data: &[u8]) -> Self {
+        Self([&[request_type], data].concat().into())
+    }
+
+    pub fn request_type(&self) -> Option<u8> {
+        self.0.first().copied()
+    }
+
+    /// Returns true if there are no requests of the type
+    pub fn is_empty(&self) -> bool {
+        self.0.len() <= 1
+    }
+}
+
+/// Computes the requests hash of a header, request types without any request are skipped
+pub fn compute_requests_hash(requests: &[EncodedRequests]) -> H256 {
+    let mut hasher = Sha256::new();
+    for request in requests.iter().filter(|request| !request.is_empty()) {
+        hasher.update(Sha256::digest(&request.0));
+    }
+    H256::from_slice(&hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn requests_hash_skips_empty_requests() {
+        // sha256 of the empty string
+        let empty_hash = H256(hex!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        ));
+        assert_eq!(compute_requests_hash(&[]), empty_hash);
+        let withdrawals = EncodedRequests::new(WITHDRAWAL_REQUEST_TYPE, &[]);
+        let consolidations = EncodedRequests::new(CONSOLIDATION_REQUEST_TYPE, &[1; 116]);
+        assert!(withdrawals.is_empty());
+        assert_eq!(
+            compute_requests_hash(std::slice::from_ref(&withdrawals)),
+            empty_hash
+        );
+
+        let expected = H256::from_slice(&Sha256::digest(Sha256::digest(&consolidations.0)));
+        assert_eq!(
+            compute_requests_hash(&[withdrawals, consolidations]),
+            expected
+        );
+    }
+
+    #[test]
+    fn decode_system_contract_requests() {
+        let address = Address::from_low_u64_be(0xdead);
+        let withdrawal = [address.as_bytes(), &[2; 48], &5_u64.to_be_bytes()].concat();
+        let requests = WithdrawalRequest::decode_list(&[withdrawal.clone(), withdrawal].concat());
+        assert_eq!(
+            requests,
+            Some(vec![
+                WithdrawalRequest {
+                    source_address: address,
+                    validator_pubkey: [2; 48],
+                    amount: 5,
+                };
+                2
+            ])
+        );
+        assert_eq!(WithdrawalRequest::decode_list(&[1; 75]), None);
+
+        let consolidation = [address.as_bytes(), &[3; 48], &[4; 48]].concat();
+        assert_eq!(
+            ConsolidationRequest::decode_list(&consolidation),
+            Some(vec![ConsolidationRequest {
+                source_address: address,
+                source_pubkey: [3; 48],
+                target_pubkey: [4; 48],
+            }])
+        );
+        assert_eq!(ConsolidationRequest::decode_list(&[]), Some(vec![]));
+    }
+
+    #[test]
+    fn decode_deposit_event_logs() {
+        let deposit = DepositRequest {
+            pubkey: [1; 48],
+            withdrawal_credentials: H256::repeat_byte(2),
+            amount: 32_000_000_000,
+            signature: [3; 96],
+            index: 5,
+        };
+        let data = deposit.encode_log_data();
+        assert_eq!(data.len(), DEPOSIT_EVENT_DATA_SIZE);
+        // The amount is the third field, its value follows its length at the offset it points to
+        assert_eq!(&data[64..96], H256::from_low_u64_be(320).as_bytes());
+        assert_eq!(&data[352..360], &deposit.amount.to_le_bytes());
+        assert_eq!(
+            DepositRequest::decode_log_data(&data),
+            Some(deposit.clone())
+        );
+        assert_eq!(deposit.encode().len(), 192);
+        assert_eq!(DepositRequest::decode_log_data(&data[..575]), None);
+        let mut bad_offset = data.clone();
+        bad_offset[31] = 161;
+        assert_eq!(DepositRequest::decode_log_data(&bad_offset), None);
+
+        // Only the deposit logs of the deposit contract are requests
+        let deposit_contract = Address::from_low_u64_be(0xde9051);
+        let log = |address, data: &[u8]| crate::types::Log {
+            address,
+            topics: vec![*DEPOSIT_EVENT_TOPIC],
+            data: data.to_vec().into(),
+        };
+        let receipt = |logs| Receipt::new(crate::types::TxType::EIP1559, true, 0, logs);
+        let receipts = [
+            receipt(vec![log(deposit_contract, &data)]),
+            receipt(vec![log(Address::zero(), &data)]),
+        ];
+        assert_eq!(
+            deposit_requests(&receipts, deposit_contract),
+            Some(EncodedRequests::new(
+                DEPOSIT_REQUEST_TYPE,
+                &deposit.encode()
+            ))
+        );
+        let receipts = [receipt(vec![log(deposit_contract, &bad_offset)])];
+        assert_eq!(deposit_requests(&receipts, deposit_contract), None);
+    }
+}
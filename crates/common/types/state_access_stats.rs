@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use bytes::BufMut;
+use ethrex_rlp::{
+    decode::RLPDecode,
+    encode::RLPEncode,
+    error::RLPDecodeError,
+    structs::{Decoder, Encoder},
+};
+
+use crate::{types::BlockNumber, Address, H256};
+
+/// Last block number in which each account and storage slot was accessed during execution,
+/// meant for research into state expiry and verkle migration planning
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StateAccessStats {
+    accounts: HashMap<Address, BlockNumber>,
+    storage: HashMap<(Address, H256), BlockNumber>,
+}
+
+impl StateAccessStats {
+    /// Records the accounts and storage slots accessed while executing the given block.
+    /// Blocks from other forks may be executed after higher ones, so the highest number is kept.
+    pub fn record(&mut self, block_number: BlockNumber, accessed: Vec<(Address, Vec<H256>)>) {
+        for (address, slots) in accessed {
+            let last_access = self.accounts.entry(address).or_default();
+            *last_access = (*last_access).max(block_number);
+            for slot in slots {
+                let last_access = self.storage.entry((address, slot)).or_default();
+                *last_access = (*last_access).max(block_number);
+            }
+        }
+    }
+
+    pub fn account_last_access(&self, address: Address) -> Option<BlockNumber> {
+        self.accounts.get(&address).copied()
+    }
+
+    pub fn slot_last_access(&self, address: Address, slot: H256) -> Option<BlockNumber> {
+        self.storage.get(&(address, slot)).copied()
+    }
+
+    /// Writes the stats as CSV, with one row per account and storage slot.
+    /// Account rows have an empty slot column.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "address,slot,last_access_block")?;
+        let mut accounts: Vec<_> = self.accounts.iter().collect();
+        accounts.sort();
+        for (address, block_number) in accounts {
+            writeln!(writer, "{address:#x},,{block_number}")?;
+        }
+        let mut storage: Vec<_> = self.storage.iter().collect();
+        storage.sort();
+        for ((address, slot), block_number) in storage {
+            writeln!(writer, "{address:#x},{slot:#x},{block_number}")?;
+        }
+        Ok(())
+    }
+}
+
+impl RLPEncode for StateAccessStats {
+    fn encode(&self, buf: &mut dyn BufMut) {
+        let accounts: Vec<(Address, BlockNumber)> =
+            self.accounts.iter().map(|(k, v)| (*k, *v)).collect();
+        let storage: Vec<(Address, H256, BlockNumber)> = self
+            .storage
+            .iter()
+            .map(|((address, slot), v)| (*address, *slot, *v))
+            .collect();
+        Encoder::new(buf)
+            .encode_field(&accounts)
+            .encode_field(&storage)
+            .finish();
+    }
+}
+
+impl RLPDecode for StateAccessStats {
+    fn decode_unfinished(rlp: &[u8]) -> Result<(Self, &[u8]), RLPDecodeError> {
+        let decoder = Decoder::new(rlp)?;
+        let (accounts, decoder): (Vec<(Address, BlockNumber)>, _) =
+            decoder.decode_field("accounts")?;
+        let (storage, decoder): (Vec<(Address, H256, BlockNumber)>, _) =
+            decoder.decode_field("storage")?;
+        let stats = StateAccessStats {
+            accounts: accounts.into_iter().collect(),
+            storage: storage
+                .into_iter()
+                .map(|(address, slot, v)| ((address, slot), v))
+                .collect(),
+        };
+        Ok((stats, decoder.finish()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_keeps_latest_access() {
+        let address = Address::random();
+        let slot = H256::random();
+        let mut stats = StateAccessStats::default();
+        stats.record(10, vec![(address, vec![slot])]);
+        stats.record(5, vec![(address, vec![])]);
+        stats.record(12, vec![(address, vec![])]);
+        assert_eq!(stats.account_last_access(address), Some(12));
+        assert_eq!(stats.slot_last_access(address, slot), Some(10));
+        assert_eq!(stats.slot_last_access(address, H256::zero()), None);
+    }
+
+    #[test]
+    fn stats_rlp_roundtrip() {
+        let mut stats = StateAccessStats::default();
+        stats.record(1, vec![(Address::random(), vec![H256::random()])]);
+        stats.record(2, vec![(Address::random(), vec![])]);
+        let decoded = StateAccessStats::decode(&stats.encode_to_vec()).unwrap();
+        assert_eq!(decoded, stats);
+    }
+
+    #[test]
+    fn write_csv_lists_accounts_and_slots() {
+        let address = Address::from_low_u64_be(1);
+        let mut stats = StateAccessStats::default();
+        stats.record(7, vec![(address, vec![H256::from_low_u64_be(2)])]);
+        let mut csv = vec![];
+        stats.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            format!(
+                "address,slot,last_access_block\n{address:#x},,7\n{address:#x},{:#x},7\n",
+                H256::from_low_u64_be(2)
+            )
+        );
+    }
+}
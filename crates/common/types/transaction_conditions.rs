@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    serde_utils,
+    types::{BlockHeader, BlockNumber},
+    Address, H256,
+};
+
+/// Maximum cost of the known accounts of a conditional transaction,
+/// each storage root or storage slot to check costs one
+pub const MAX_KNOWN_ACCOUNTS_COST: usize = 1000;
+
+/// Conditions a transaction submitted via `eth_sendRawTransactionConditional` must meet
+/// in order to be included in a block
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionConditions {
+    #[serde(default)]
+    pub known_accounts: HashMap<Address, KnownAccount>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub block_number_min: Option<BlockNumber>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub block_number_max: Option<BlockNumber>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub timestamp_min: Option<u64>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub timestamp_max: Option<u64>,
+}
+
+/// Expected state of an account, either its whole storage or only some of its slots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KnownAccount {
+    StorageRoot(H256),
+    Slots(HashMap<H256, H256>),
+}
+
+impl TransactionConditions {
+    /// Amount of storage roots and slots that have to be checked
+    pub fn known_accounts_cost(&self) -> usize {
+        self.known_accounts
+            .values()
+            .map(|account| match account {
+                KnownAccount::StorageRoot(_) => 1,
+                KnownAccount::Slots(slots) => slots.len(),
+            })
+            .sum()
+    }
+
+    /// Checks the block number and timestamp conditions against the header of the block
+    /// the transaction would be included in
+    pub fn matches_header(&self, header: &BlockHeader) -> bool {
+        self.block_number_min.is_none_or(|min| header.number >= min)
+            && self.block_number_max.is_none_or(|max| header.number <= max)
+            && self.timestamp_min.is_none_or(|min| header.timestamp >= min)
+            && self.timestamp_max.is_none_or(|max| header.timestamp <= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_transaction_conditions() {
+        let conditions: TransactionConditions = serde_json::from_str(
+            r#"{
+                "knownAccounts": {
+                    "0x000000000000000000000000000000000000dead": "0x0000000000000000000000000000000000000000000000000000000000000001",
+                    "0x000000000000000000000000000000000000beef": {
+                        "0x0000000000000000000000000000000000000000000000000000000000000002": "0x0000000000000000000000000000000000000000000000000000000000000003"
+                    }
+                },
+                "blockNumberMin": "0x5",
+                "timestampMax": "0x10"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            conditions.known_accounts[&Address::from_low_u64_be(0xdead)],
+            KnownAccount::StorageRoot(H256::from_low_u64_be(1))
+        );
+        assert_eq!(
+            conditions.known_accounts[&Address::from_low_u64_be(0xbeef)],
+            KnownAccount::Slots(HashMap::from([(
+                H256::from_low_u64_be(2),
+                H256::from_low_u64_be(3)
+            )]))
+        );
+        assert_eq!(conditions.known_accounts_cost(), 2);
+        assert_eq!(conditions.block_number_min, Some(5));
+        assert_eq!(conditions.block_number_max, None);
+        assert_eq!(conditions.timestamp_max, Some(16));
+    }
+
+    #[test]
+    fn matches_header_checks_block_range() {
+        let conditions = TransactionConditions {
+            block_number_min: Some(5),
+            block_number_max: Some(10),
+            timestamp_min: Some(100),
+            ..Default::default()
+        };
+        let header = |number, timestamp| BlockHeader {
+            number,
+            timestamp,
+            ..Default::default()
+        };
+        assert!(conditions.matches_header(&header(5, 100)));
+        assert!(conditions.matches_header(&header(10, 200)));
+        assert!(!conditions.matches_header(&header(4, 100)));
+        assert!(!conditions.matches_header(&header(11, 100)));
+        assert!(!conditions.matches_header(&header(7, 99)));
+    }
+}
@@ -21,6 +21,7 @@ pub struct Proposer {
     block_production_interval: u64,
     coinbase_address: Address,
     jwt_secret: Vec<u8>,
+    relay_url: Option<String>,
 }
 
 pub async fn start_proposer(store: Store) {
@@ -75,6 +76,7 @@ impl Proposer {
             block_production_interval: proposer_config.interval_ms,
             coinbase_address: proposer_config.coinbase_address,
             jwt_secret,
+            relay_url: proposer_config.relay_url.clone(),
         })
     }
 
@@ -105,6 +107,7 @@ impl Proposer {
             10,
             self.block_production_interval,
             self.coinbase_address,
+            self.relay_url.clone(),
         )
         .await?;
 
@@ -7,6 +7,8 @@ use super::errors::ConfigError;
 pub struct ProposerConfig {
     pub interval_ms: u64,
     pub coinbase_address: Address,
+    /// External builder relay queried for payloads worth more than the locally built ones
+    pub relay_url: Option<String>,
 }
 
 impl ProposerConfig {
@@ -0,0 +1,267 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    io::{self, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::BufMut;
+use ethrex_core::H512;
+use ethrex_rlp::{
+    decode::RLPDecode,
+    encode::RLPEncode,
+    error::RLPDecodeError,
+    structs::{Decoder, Encoder},
+};
+use tracing::{debug, info, warn};
+
+use crate::discv4::time_now_unix;
+
+/// Name of the file inside the data directory where banned peers are persisted
+pub const BANNED_PEERS_FILE_NAME: &str = "banned_peers.rlp";
+/// Time between reloads of the banned peers file, so the bans managed from the CLI apply to a
+/// running node
+const BAN_LIST_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+const NODE_TARGET: u8 = 0;
+const IP_TARGET: u8 = 1;
+
+/// What a ban applies to: a single node, or every node connecting from or advertised at an IP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    Node(H512),
+    Ip(IpAddr),
+}
+
+impl FromStr for BanTarget {
+    type Err = String;
+
+    /// Parses an IP address or a hex encoded node id
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        if let Ok(ip) = IpAddr::from_str(target) {
+            return Ok(BanTarget::Ip(ip));
+        }
+        H512::from_str(target.strip_prefix("0x").unwrap_or(target))
+            .map(BanTarget::Node)
+            .map_err(|_| format!("{target} is neither an IP address nor a node id"))
+    }
+}
+
+impl Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanTarget::Node(node_id) => write!(f, "node {node_id:#x}"),
+            BanTarget::Ip(ip) => write!(f, "ip {ip}"),
+        }
+    }
+}
+
+/// A banned node or IP, kept until the ban expires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ban {
+    pub target: BanTarget,
+    /// Unix time in seconds at which the ban expires, the ban is permanent if not set
+    pub expires_at: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+impl RLPEncode for Ban {
+    fn encode(&self, buf: &mut dyn BufMut) {
+        let encoder = Encoder::new(buf);
+        let encoder = match &self.target {
+            BanTarget::Node(node_id) => encoder.encode_field(&NODE_TARGET).encode_field(node_id),
+            BanTarget::Ip(ip) => encoder.encode_field(&IP_TARGET).encode_field(ip),
+        };
+        encoder.encode_optional_field(&self.expires_at).finish();
+    }
+}
+
+impl RLPDecode for Ban {
+    fn decode_unfinished(rlp: &[u8]) -> Result<(Self, &[u8]), RLPDecodeError> {
+        let decoder = Decoder::new(rlp)?;
+        let (kind, decoder): (u8, _) = decoder.decode_field("kind")?;
+        let (target, decoder) = match kind {
+            NODE_TARGET => {
+                let (node_id, decoder) = decoder.decode_field("node_id")?;
+                (BanTarget::Node(node_id), decoder)
+            }
+            IP_TARGET => {
+                let (ip, decoder) = decoder.decode_field("ip")?;
+                (BanTarget::Ip(ip), decoder)
+            }
+            _ => return Err(RLPDecodeError::MalformedData),
+        };
+        let (expires_at, decoder) = decoder.decode_optional_field();
+        Ok((Ban { target, expires_at }, decoder.finish()?))
+    }
+}
+
+/// Nodes and IPs the node doesn't talk to, neither in the discovery nor in RLPx connections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BanList {
+    bans: Vec<Ban>,
+}
+
+impl BanList {
+    /// Bans the target until the given unix time, or forever, replacing its previous ban
+    pub fn ban(&mut self, target: BanTarget, expires_at: Option<u64>) {
+        self.unban(target);
+        self.bans.push(Ban { target, expires_at });
+    }
+
+    /// Lifts the ban of the target, returning whether it was banned
+    pub fn unban(&mut self, target: BanTarget) -> bool {
+        let len = self.bans.len();
+        self.bans.retain(|ban| ban.target != target);
+        self.bans.len() != len
+    }
+
+    pub fn is_banned(&self, target: BanTarget, now: u64) -> bool {
+        self.bans
+            .iter()
+            .any(|ban| ban.target == target && ban.is_active(now))
+    }
+
+    pub fn bans(&self) -> &[Ban] {
+        &self.bans
+    }
+
+    /// Forgets the bans that expired
+    pub fn remove_expired(&mut self, now: u64) {
+        self.bans.retain(|ban| ban.is_active(now));
+    }
+}
+
+pub fn ban_list_path(data_dir: &str) -> PathBuf {
+    Path::new(data_dir).join(BANNED_PEERS_FILE_NAME)
+}
+
+/// Reads the bans stored in the given file, a missing file holds no bans.
+/// Unlike the known peers, a corrupted file is an error so the bans are not lost silently.
+pub fn read_ban_list(path: &Path) -> io::Result<BanList> {
+    let encoded = match fs::read(path) {
+        Ok(encoded) => encoded,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(BanList::default()),
+        Err(err) => return Err(err),
+    };
+    let bans = Vec::<Ban>::decode(&encoded)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(BanList { bans })
+}
+
+/// Writes the bans to the file, replacing its previous content.
+/// The bans are written to a temporary file first so a crash never leaves a truncated file behind.
+pub fn write_ban_list(path: &Path, list: &BanList) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(&list.bans.encode_to_vec())?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+/// [BanList] shared by the discovery and the connection acceptor
+#[derive(Debug, Clone, Default)]
+pub struct SharedBanList(Arc<Mutex<BanList>>);
+
+impl SharedBanList {
+    pub fn new(list: BanList) -> Self {
+        Self(Arc::new(Mutex::new(list)))
+    }
+
+    pub fn is_node_banned(&self, node_id: H512) -> bool {
+        self.is_banned(BanTarget::Node(node_id))
+    }
+
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        self.is_banned(BanTarget::Ip(ip))
+    }
+
+    fn is_banned(&self, target: BanTarget) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .is_banned(target, time_now_unix())
+    }
+
+    fn replace(&self, list: BanList) {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = list;
+    }
+}
+
+/// Reads the banned peers file, so the bans added or lifted while the node runs are applied.
+/// The current bans are kept if the file can't be read.
+pub(crate) async fn periodically_reload_ban_list(bans: SharedBanList, path: PathBuf) {
+    info!("Reading banned peers from {}", path.display());
+    let mut interval = tokio::time::interval(BAN_LIST_RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        match read_ban_list(&path) {
+            Ok(mut list) => {
+                list.remove_expired(time_now_unix());
+                debug!("Loaded {} banned peers", list.bans().len());
+                bans.replace(list);
+            }
+            Err(err) => warn!("Failed to read banned peers from {}: {err}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn bans_roundtrip_and_expire() {
+        let dir = std::env::temp_dir().join(format!("ethrex-ban-list-{}", H512::random()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(BANNED_PEERS_FILE_NAME);
+        assert_eq!(read_ban_list(&path).unwrap(), BanList::default());
+
+        let node = BanTarget::Node(H512::random());
+        let ip = BanTarget::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let mut list = BanList::default();
+        list.ban(node, None);
+        list.ban(ip, Some(100));
+        write_ban_list(&path, &list).unwrap();
+        let mut list = read_ban_list(&path).unwrap();
+        assert!(list.is_banned(node, u64::MAX));
+        assert!(list.is_banned(ip, 99));
+        assert!(!list.is_banned(ip, 100));
+
+        list.remove_expired(100);
+        assert_eq!(list.bans().len(), 1);
+        assert!(list.unban(node));
+        assert!(!list.unban(node));
+
+        fs::write(&path, b"not rlp").unwrap();
+        assert!(read_ban_list(&path).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn targets_are_parsed_from_ips_and_node_ids() {
+        let node_id = H512::random();
+        assert_eq!(
+            format!("{node_id:#x}").parse::<BanTarget>(),
+            Ok(BanTarget::Node(node_id))
+        );
+        assert_eq!(
+            "::1".parse::<BanTarget>(),
+            Ok(BanTarget::Ip("::1".parse().unwrap()))
+        );
+        assert!("not a peer".parse::<BanTarget>().is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+/// Maximum amount of RLPx handshakes that can be in progress at the same time from a single IP
+pub const MAX_CONCURRENT_HANDSHAKES_PER_IP: usize = 2;
+/// Maximum amount of inbound RLPx handshakes started per second among all IPs
+pub const MAX_HANDSHAKES_PER_SECOND: usize = 32;
+/// Amount of consecutive failed handshakes after which an IP is banned
+pub const MAX_HANDSHAKE_FAILURES: usize = 5;
+/// Time during which connections from a banned IP are dropped right after being accepted
+pub const BAN_DURATION: Duration = Duration::from_secs(10 * 60);
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Reason an inbound connection was dropped before starting the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HandshakeRejection {
+    Banned,
+    TooManyFromIp,
+    RateLimited,
+}
+
+/// Limits the inbound RLPx handshakes so connection floods can't exhaust the node:
+/// each IP can only have a few handshakes in progress, the rate of handshakes among all
+/// IPs is capped, and IPs whose handshakes keep failing are banned for a while.
+#[derive(Debug)]
+pub(crate) struct HandshakeLimits {
+    in_progress: HashMap<IpAddr, usize>,
+    /// Consecutive failed handshakes of each IP and the time of the last one
+    failures: HashMap<IpAddr, (usize, Instant)>,
+    /// Time at which the ban of each IP expires
+    banned: HashMap<IpAddr, Instant>,
+    window_start: Instant,
+    window_handshakes: usize,
+}
+
+impl HandshakeLimits {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            in_progress: HashMap::new(),
+            failures: HashMap::new(),
+            banned: HashMap::new(),
+            window_start: now,
+            window_handshakes: 0,
+        }
+    }
+
+    /// Checks if a handshake with the IP can start now, counting it as in progress if so
+    pub fn try_start(&mut self, ip: IpAddr, now: Instant) -> Result<(), HandshakeRejection> {
+        if now.duration_since(self.window_start) >= RATE_WINDOW {
+            self.window_start = now;
+            self.window_handshakes = 0;
+            self.expire(now);
+        }
+        if self.banned.get(&ip).is_some_and(|until| *until > now) {
+            return Err(HandshakeRejection::Banned);
+        }
+        let in_progress = self.in_progress.get(&ip).copied().unwrap_or_default();
+        if in_progress >= MAX_CONCURRENT_HANDSHAKES_PER_IP {
+            return Err(HandshakeRejection::TooManyFromIp);
+        }
+        if self.window_handshakes >= MAX_HANDSHAKES_PER_SECOND {
+            return Err(HandshakeRejection::RateLimited);
+        }
+        self.window_handshakes += 1;
+        self.in_progress.insert(ip, in_progress + 1);
+        Ok(())
+    }
+
+    /// Marks a handshake with the IP as finished, banning the IP if it failed too many times in a row
+    pub fn finish(&mut self, ip: IpAddr, succeeded: bool, now: Instant) {
+        if let Some(in_progress) = self.in_progress.get_mut(&ip) {
+            *in_progress -= 1;
+            if *in_progress == 0 {
+                self.in_progress.remove(&ip);
+            }
+        }
+        if succeeded {
+            self.failures.remove(&ip);
+            return;
+        }
+        let failures = self.failures.entry(ip).or_insert((0, now));
+        *failures = (failures.0 + 1, now);
+        if failures.0 >= MAX_HANDSHAKE_FAILURES {
+            debug!("Banning {ip} after {MAX_HANDSHAKE_FAILURES} failed handshakes");
+            self.failures.remove(&ip);
+            self.banned.insert(ip, now + BAN_DURATION);
+        }
+    }
+
+    /// Forgets the expired bans and the failures of IPs that didn't fail for a while
+    fn expire(&mut self, now: Instant) {
+        self.banned.retain(|_, until| *until > now);
+        self.failures
+            .retain(|_, (_, last_failure)| now.duration_since(*last_failure) < BAN_DURATION);
+    }
+}
+
+/// Shared [HandshakeLimits] of the inbound connections
+#[derive(Debug, Clone)]
+pub(crate) struct HandshakeLimiter(Arc<Mutex<HandshakeLimits>>);
+
+impl HandshakeLimiter {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(HandshakeLimits::new(Instant::now()))))
+    }
+
+    /// Returns a permit to handshake with the IP, which has to be kept until the handshake is done
+    pub fn try_start(&self, ip: IpAddr) -> Result<HandshakePermit, HandshakeRejection> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .try_start(ip, Instant::now())?;
+        Ok(HandshakePermit {
+            limiter: self.clone(),
+            ip,
+            failed: false,
+        })
+    }
+}
+
+/// Handshake in progress with an IP, finished when dropped
+#[derive(Debug)]
+pub(crate) struct HandshakePermit {
+    limiter: HandshakeLimiter,
+    ip: IpAddr,
+    failed: bool,
+}
+
+impl HandshakePermit {
+    /// Finishes the handshake, counting it as a failure of its IP
+    pub fn fail(mut self) {
+        self.failed = true;
+    }
+}
+
+impl Drop for HandshakePermit {
+    fn drop(&mut self) {
+        self.limiter
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .finish(self.ip, !self.failed, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn ip(last: u8) -> IpAddr {
+        Ipv4Addr::new(10, 0, 0, last).into()
+    }
+
+    #[test]
+    fn concurrent_handshakes_are_limited_per_ip() {
+        let now = Instant::now();
+        let mut limits = HandshakeLimits::new(now);
+        for _ in 0..MAX_CONCURRENT_HANDSHAKES_PER_IP {
+            assert!(limits.try_start(ip(1), now).is_ok());
+        }
+        assert_eq!(
+            limits.try_start(ip(1), now),
+            Err(HandshakeRejection::TooManyFromIp)
+        );
+        assert!(limits.try_start(ip(2), now).is_ok());
+
+        limits.finish(ip(1), true, now);
+        assert!(limits.try_start(ip(1), now).is_ok());
+    }
+
+    #[test]
+    fn handshakes_are_rate_limited() {
+        let now = Instant::now();
+        let mut limits = HandshakeLimits::new(now);
+        for last in 0..MAX_HANDSHAKES_PER_SECOND {
+            assert!(limits.try_start(ip(last as u8), now).is_ok());
+        }
+        assert_eq!(
+            limits.try_start(ip(255), now),
+            Err(HandshakeRejection::RateLimited)
+        );
+        assert!(limits.try_start(ip(255), now + RATE_WINDOW).is_ok());
+    }
+
+    #[test]
+    fn failing_ips_are_banned() {
+        let now = Instant::now();
+        let mut limits = HandshakeLimits::new(now);
+        for _ in 0..MAX_HANDSHAKE_FAILURES {
+            assert!(limits.try_start(ip(1), now).is_ok());
+            limits.finish(ip(1), false, now);
+        }
+        assert_eq!(
+            limits.try_start(ip(1), now),
+            Err(HandshakeRejection::Banned)
+        );
+        assert!(limits.try_start(ip(1), now + BAN_DURATION).is_ok());
+    }
+
+    #[test]
+    fn dropped_permits_finish_the_handshake() {
+        let limiter = HandshakeLimiter::new();
+        let permits: Vec<_> = (0..MAX_CONCURRENT_HANDSHAKES_PER_IP)
+            .map(|_| limiter.try_start(ip(1)).unwrap())
+            .collect();
+        assert_eq!(
+            limiter.try_start(ip(1)).unwrap_err(),
+            HandshakeRejection::TooManyFromIp
+        );
+        drop(permits);
+        for _ in 0..MAX_HANDSHAKE_FAILURES {
+            limiter.try_start(ip(1)).unwrap().fail();
+        }
+        assert_eq!(
+            limiter.try_start(ip(1)).unwrap_err(),
+            HandshakeRejection::Banned
+        );
+    }
+}
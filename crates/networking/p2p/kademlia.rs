@@ -1,10 +1,13 @@
 use crate::{
     discv4::{time_now_unix, FindNodeRequest},
+    known_peers::KnownPeer,
     peer_channels::PeerChannels,
+    rlpx::p2p::Capability,
     types::Node,
 };
 use ethrex_core::{H256, H512, U256};
 use sha3::{Digest, Keccak256};
+use std::cmp::Reverse;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::info;
 
@@ -123,6 +126,24 @@ impl KademliaTable {
             .collect();
     }
 
+    /// Returns up to `limit` proven peers, the ones with the highest liveness first
+    pub fn known_peers(&self, limit: usize) -> Vec<KnownPeer> {
+        let mut peers: Vec<KnownPeer> = self
+            .buckets
+            .iter()
+            .flat_map(|bucket| bucket.peers.iter())
+            .filter(|peer| peer.is_proven)
+            .map(|peer| KnownPeer {
+                node: peer.node,
+                liveness: peer.liveness,
+                last_pong: peer.last_pong,
+            })
+            .collect();
+        peers.sort_by_key(|peer| Reverse(peer.liveness));
+        peers.truncate(limit);
+        peers
+    }
+
     pub fn get_closest_nodes(&self, node_id: H512) -> Vec<Node> {
         let mut nodes: Vec<(Node, usize)> = vec![];
 
@@ -257,9 +278,15 @@ impl KademliaTable {
         None
     }
 
-    /// Set the sender end of the channel between the kademlia table and the peer's active connection
+    /// Set the sender end of the channel between the kademlia table and the peer's active connection,
+    /// along with the capabilities negotiated with the peer.
     /// This function should be called each time a connection is established so the backend can send requests to the peers
-    pub fn set_channels(&mut self, node_id: H512, channels: PeerChannels) {
+    pub(crate) fn set_channels(
+        &mut self,
+        node_id: H512,
+        channels: PeerChannels,
+        capabilities: Vec<Capability>,
+    ) {
         let bucket_idx = bucket_number(self.local_node_id, node_id);
         if let Some(peer) = self.buckets.get_mut(bucket_idx).and_then(|bucket| {
             bucket
@@ -267,7 +294,8 @@ impl KademliaTable {
                 .iter_mut()
                 .find(|peer| peer.node.node_id == node_id)
         }) {
-            peer.channels = Some(channels)
+            peer.channels = Some(channels);
+            peer.capabilities = capabilities;
         }
     }
 
@@ -276,16 +304,37 @@ impl KademliaTable {
         self.get_least_recently_pinged_peers(1).pop()
     }
 
-    /// Returns the channel ends to an active peer connection
-    /// The peer is selected randomly (TODO), and doesn't guarantee that the selected peer is not currenlty busy
+    /// Returns the channels of the connected peer supporting the capability that answers the fastest.
+    /// Peers whose response time wasn't measured yet are preferred so they get measured, and for
+    /// eth requests peers without snap are preferred so snap peers are free to serve state.
+    pub(crate) fn get_peer_channels_with_capability(
+        &self,
+        capability: &Capability,
+    ) -> Option<PeerChannels> {
+        self.buckets
+            .iter()
+            .flat_map(|bucket| bucket.peers.iter())
+            .filter(|peer| peer.capabilities.contains(capability))
+            .filter_map(|peer| {
+                let channels = peer.channels.as_ref()?;
+                let serves_state =
+                    *capability == Capability::Eth && peer.capabilities.contains(&Capability::Snap);
+                Some(((serves_state, channels.response_time()), channels))
+            })
+            .min_by_key(|(key, _)| *key)
+            .map(|(_, channels)| channels.clone())
+    }
+
+    /// Returns the channel ends to an active peer connection supporting the capability
+    /// The selected peer is not guaranteed to not be currently busy
     /// If no peer is found, this method will try again after 10 seconds
-    /// TODO: Filter peers by capabilities, set max amount of retries
-    pub async fn get_peer_channels(&self) -> PeerChannels {
+    /// TODO: set max amount of retries
+    pub(crate) async fn get_peer_channels(&self, capability: Capability) -> PeerChannels {
         loop {
-            if let Some(channels) = self.get_peer().and_then(|peer| peer.channels) {
+            if let Some(channels) = self.get_peer_channels_with_capability(&capability) {
                 return channels;
             }
-            info!("[Sync] No peers available, retrying in 10 sec");
+            info!("[Sync] No {capability:?} peers available, retrying in 10 sec");
             // This is the unlikely case where we just started the node and don't have peers, wait a bit and try again
             tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
         }
@@ -317,6 +366,8 @@ pub struct PeerData {
     pub revalidation: Option<bool>,
     /// communication channels between the peer data and its active connection
     pub channels: Option<PeerChannels>,
+    /// capabilities supported by both the peer and this node, set once connected
+    pub(crate) capabilities: Vec<Capability>,
 }
 
 impl PeerData {
@@ -331,6 +382,7 @@ impl PeerData {
             find_node_request: None,
             revalidation: None,
             channels: None,
+            capabilities: vec![],
         }
     }
 
@@ -539,4 +591,60 @@ mod tests {
         assert!(replacement.is_none());
         assert!(len_before - 1 == len_after);
     }
+
+    #[test]
+    fn known_peers_should_return_proven_peers_by_liveness() {
+        let mut table = get_test_table();
+        for (i, liveness) in [2, 5, 1].into_iter().enumerate() {
+            insert_random_node_on_custom_bucket(&mut table, 0);
+            let peer = &mut table.buckets[0].peers[i];
+            peer.is_proven = true;
+            peer.liveness = liveness;
+        }
+        // Unproven peers are never returned
+        insert_random_node_on_custom_bucket(&mut table, 1);
+
+        let liveness: Vec<u16> = table.known_peers(2).iter().map(|p| p.liveness).collect();
+        assert_eq!(liveness, vec![5, 2]);
+        assert_eq!(table.known_peers(10).len(), 3);
+    }
+
+    #[test]
+    fn peers_are_selected_by_capability_and_response_time() {
+        let mut table = get_test_table();
+        let peers = [
+            (
+                vec![Capability::P2p, Capability::Eth, Capability::Snap],
+                None,
+            ),
+            (vec![Capability::P2p, Capability::Eth], Some(false)),
+            (vec![Capability::P2p, Capability::Eth], Some(true)),
+        ];
+        for (i, (capabilities, answered)) in peers.into_iter().enumerate() {
+            insert_random_node_on_custom_bucket(&mut table, 0);
+            let (channels, _, _) = PeerChannels::create();
+            if let Some(answered) = answered {
+                channels.record_response(std::time::Instant::now(), answered);
+            }
+            let peer = &mut table.buckets[0].peers[i];
+            peer.channels = Some(channels);
+            peer.capabilities = capabilities;
+        }
+        let response_time = |table: &KademliaTable, capability| {
+            table
+                .get_peer_channels_with_capability(&capability)
+                .map(|channels| channels.response_time())
+        };
+        // Only the first peer serves snap, it wasn't measured yet
+        assert_eq!(response_time(&table, Capability::Snap), Some(None));
+        // The fastest eth-only peer is used for eth requests
+        assert!(response_time(&table, Capability::Eth)
+            .flatten()
+            .is_some_and(|time| time < crate::peer_channels::PEER_REPLY_TIMOUT));
+
+        table.buckets[0].peers.truncate(1);
+        assert_eq!(response_time(&table, Capability::Eth), Some(None));
+        table.buckets[0].peers[0].channels = None;
+        assert_eq!(response_time(&table, Capability::Snap), None);
+    }
 }
@@ -0,0 +1,161 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::BufMut;
+use ethrex_rlp::{
+    decode::RLPDecode,
+    encode::RLPEncode,
+    error::RLPDecodeError,
+    structs::{Decoder, Encoder},
+};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::{kademlia::KademliaTable, types::Node};
+
+/// Name of the file inside the data directory where known peers are persisted
+pub const KNOWN_PEERS_FILE_NAME: &str = "known_peers.rlp";
+/// Maximum amount of peers persisted
+pub const MAX_KNOWN_PEERS: usize = 256;
+const KNOWN_PEERS_STORE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// A peer that answered our pings, along with its liveness stats, so it can be
+/// used to seed the peer table when the node is restarted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownPeer {
+    pub node: Node,
+    pub liveness: u16,
+    pub last_pong: u64,
+}
+
+impl RLPEncode for KnownPeer {
+    fn encode(&self, buf: &mut dyn BufMut) {
+        Encoder::new(buf)
+            .encode_field(&self.node)
+            .encode_field(&self.liveness)
+            .encode_field(&self.last_pong)
+            .finish();
+    }
+}
+
+impl RLPDecode for KnownPeer {
+    fn decode_unfinished(rlp: &[u8]) -> Result<(Self, &[u8]), RLPDecodeError> {
+        let decoder = Decoder::new(rlp)?;
+        let (node, decoder) = decoder.decode_field("node")?;
+        let (liveness, decoder) = decoder.decode_field("liveness")?;
+        let (last_pong, decoder) = decoder.decode_field("last_pong")?;
+        let known_peer = KnownPeer {
+            node,
+            liveness,
+            last_pong,
+        };
+        Ok((known_peer, decoder.finish()?))
+    }
+}
+
+pub fn known_peers_path(data_dir: &str) -> PathBuf {
+    Path::new(data_dir).join(KNOWN_PEERS_FILE_NAME)
+}
+
+/// Reads the peers stored in the given file.
+/// A missing or corrupted file is not an error, the node just starts without known peers.
+pub fn read_known_peers(path: &Path) -> Vec<KnownPeer> {
+    let encoded = match fs::read(path) {
+        Ok(encoded) => encoded,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(err) => {
+            warn!("Failed to read known peers from {}: {err}", path.display());
+            return vec![];
+        }
+    };
+    match Vec::<KnownPeer>::decode(&encoded) {
+        Ok(peers) => peers,
+        Err(err) => {
+            warn!(
+                "Failed to decode known peers from {}: {err}",
+                path.display()
+            );
+            vec![]
+        }
+    }
+}
+
+/// Writes the given peers to the file, replacing its previous content.
+/// The peers are written to a temporary file first so a crash never leaves a truncated file behind.
+pub fn write_known_peers(path: &Path, peers: &[KnownPeer]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(&peers.to_vec().encode_to_vec())?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+/// Stores the best known peers of the table in the given file.
+/// If there are no proven peers the previous file is kept, as it is still a better
+/// starting point than nothing.
+pub async fn store_known_peers(table: &Arc<Mutex<KademliaTable>>, path: &Path) {
+    let peers = table.lock().await.known_peers(MAX_KNOWN_PEERS);
+    if peers.is_empty() {
+        return;
+    }
+    match write_known_peers(path, &peers) {
+        Ok(()) => debug!("Stored {} known peers in {}", peers.len(), path.display()),
+        Err(err) => warn!("Failed to store known peers in {}: {err}", path.display()),
+    }
+}
+
+/// Periodically stores the known peers, so they are not lost if the node crashes
+pub(crate) async fn periodically_store_known_peers(
+    table: Arc<Mutex<KademliaTable>>,
+    path: PathBuf,
+) {
+    info!("Persisting known peers to {}", path.display());
+    let mut interval = tokio::time::interval(KNOWN_PEERS_STORE_INTERVAL);
+    // The first tick completes immediately and the table is still empty at that point
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        store_known_peers(&table, &path).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethrex_core::H512;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn known_peer(port: u16) -> KnownPeer {
+        KnownPeer {
+            node: Node {
+                ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                udp_port: port,
+                tcp_port: port,
+                node_id: H512::random(),
+            },
+            liveness: port,
+            last_pong: 1000,
+        }
+    }
+
+    #[test]
+    fn known_peers_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("ethrex-known-peers-{}", H512::random()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(KNOWN_PEERS_FILE_NAME);
+        assert!(read_known_peers(&path).is_empty());
+
+        let peers = vec![known_peer(30303), known_peer(30304)];
+        write_known_peers(&path, &peers).unwrap();
+        assert_eq!(read_known_peers(&path), peers);
+
+        fs::write(&path, b"not rlp").unwrap();
+        assert!(read_known_peers(&path).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,10 +1,12 @@
 use std::{
     collections::HashSet,
     net::SocketAddr,
+    path::PathBuf,
     sync::Arc,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use ban_list::{periodically_reload_ban_list, read_ban_list, SharedBanList};
 use bootnode::BootNode;
 use discv4::{
     get_expiration, is_expired, time_now_unix, time_since_in_hs, FindNodeMessage, Message,
@@ -12,12 +14,14 @@ use discv4::{
 };
 use ethrex_core::{H256, H512};
 use ethrex_storage::Store;
+use handshake_limits::{HandshakeLimiter, HandshakePermit};
 use k256::{
     ecdsa::SigningKey,
     elliptic_curve::{sec1::ToEncodedPoint, PublicKey},
 };
 pub use kademlia::KademliaTable;
 use kademlia::{bucket_number, MAX_NODES_PER_BUCKET};
+use known_peers::{periodically_store_known_peers, read_known_peers, KnownPeer};
 use rand::rngs::OsRng;
 use rlpx::{connection::RLPxConnection, message::Message as RLPxMessage};
 use tokio::{
@@ -26,15 +30,22 @@ use tokio::{
     try_join,
 };
 use tracing::{debug, error, info};
+use tx_fetcher::{run_tx_fetcher, TxFetcherHandle};
 use types::{Endpoint, Node};
 
+pub mod ban_list;
 pub mod bootnode;
 pub(crate) mod discv4;
+pub(crate) mod handshake_limits;
 pub(crate) mod kademlia;
+pub mod known_peers;
 pub mod peer_channels;
 pub mod rlpx;
+pub mod rpc_backfill;
 pub(crate) mod snap;
+pub mod snapshot_generator;
 pub mod sync;
+pub(crate) mod tx_fetcher;
 pub mod types;
 
 const MAX_DISC_PACKET_SIZE: usize = 1280;
@@ -50,46 +61,92 @@ pub fn peer_table(signer: SigningKey) -> Arc<Mutex<KademliaTable>> {
     Arc::new(Mutex::new(KademliaTable::new(local_node_id)))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn start_network(
     udp_addr: SocketAddr,
     tcp_addr: SocketAddr,
     bootnodes: Vec<BootNode>,
+    known_peers_path: PathBuf,
+    ban_list_path: PathBuf,
     signer: SigningKey,
     peer_table: Arc<Mutex<KademliaTable>>,
     storage: Store,
 ) {
     info!("Starting discovery service at {udp_addr}");
     info!("Listening for requests at {tcp_addr}");
+    let known_peers = read_known_peers(&known_peers_path);
+    info!(
+        "Loaded {} known peers from {}",
+        known_peers.len(),
+        known_peers_path.display()
+    );
+    let ban_list = match read_ban_list(&ban_list_path) {
+        Ok(list) => list,
+        Err(err) => {
+            error!(
+                "Failed to read banned peers from {}: {err}",
+                ban_list_path.display()
+            );
+            Default::default()
+        }
+    };
+    let ban_list = SharedBanList::new(ban_list);
     let (channel_broadcast_send_end, _) = tokio::sync::broadcast::channel::<(
         tokio::task::Id,
         Arc<RLPxMessage>,
     )>(MAX_MESSAGES_TO_BROADCAST);
+    let (tx_fetcher, tx_fetcher_events) = TxFetcherHandle::create();
+    let tx_fetcher_handle = tokio::spawn(run_tx_fetcher(tx_fetcher_events, peer_table.clone()));
     let discovery_handle = tokio::spawn(discover_peers(
         udp_addr,
         signer.clone(),
         storage.clone(),
         peer_table.clone(),
         bootnodes,
+        known_peers,
+        ban_list.clone(),
         channel_broadcast_send_end.clone(),
+        tx_fetcher.clone(),
+    ));
+    let known_peers_handle = tokio::spawn(periodically_store_known_peers(
+        peer_table.clone(),
+        known_peers_path,
+    ));
+    let ban_list_handle = tokio::spawn(periodically_reload_ban_list(
+        ban_list.clone(),
+        ban_list_path,
     ));
     let server_handle = tokio::spawn(serve_requests(
         tcp_addr,
         signer.clone(),
         storage.clone(),
         peer_table.clone(),
+        ban_list,
         channel_broadcast_send_end,
+        tx_fetcher,
     ));
 
-    try_join!(discovery_handle, server_handle).unwrap();
+    try_join!(
+        discovery_handle,
+        server_handle,
+        known_peers_handle,
+        ban_list_handle,
+        tx_fetcher_handle
+    )
+    .unwrap();
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn discover_peers(
     udp_addr: SocketAddr,
     signer: SigningKey,
     storage: Store,
     table: Arc<Mutex<KademliaTable>>,
     bootnodes: Vec<BootNode>,
+    known_peers: Vec<KnownPeer>,
+    ban_list: SharedBanList,
     connection_broadcast: broadcast::Sender<(tokio::task::Id, Arc<RLPxMessage>)>,
+    tx_fetcher: TxFetcherHandle,
 ) {
     let udp_socket = Arc::new(UdpSocket::bind(udp_addr).await.unwrap());
 
@@ -99,7 +156,9 @@ async fn discover_peers(
         storage,
         table.clone(),
         signer.clone(),
+        ban_list,
         connection_broadcast,
+        tx_fetcher,
     ));
     let revalidation_handler = tokio::spawn(peers_revalidation(
         udp_addr,
@@ -115,6 +174,7 @@ async fn discover_peers(
         table.clone(),
         signer.clone(),
         bootnodes,
+        known_peers,
     )
     .await;
 
@@ -132,13 +192,16 @@ async fn discover_peers(
     try_join!(server_handler, revalidation_handler, lookup_handler).unwrap();
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn discover_peers_server(
     udp_addr: SocketAddr,
     udp_socket: Arc<UdpSocket>,
     storage: Store,
     table: Arc<Mutex<KademliaTable>>,
     signer: SigningKey,
+    ban_list: SharedBanList,
     tx_broadcaster_send: broadcast::Sender<(tokio::task::Id, Arc<RLPxMessage>)>,
+    tx_fetcher: TxFetcherHandle,
 ) {
     let mut buf = vec![0; MAX_DISC_PACKET_SIZE];
 
@@ -153,6 +216,11 @@ async fn discover_peers_server(
         }
         let packet = packet.unwrap();
 
+        if ban_list.is_ip_banned(from.ip()) || ban_list.is_node_banned(packet.get_node_id()) {
+            debug!("Ignoring packet from banned peer {}", packet.get_node_id());
+            continue;
+        }
+
         let msg = packet.get_message();
         debug!("Message: {:?} from {}", msg, packet.get_node_id());
 
@@ -225,6 +293,7 @@ async fn discover_peers_server(
                         let signer = signer.clone();
                         let storage = storage.clone();
                         let broadcaster = tx_broadcaster_send.clone();
+                        let tx_fetcher = tx_fetcher.clone();
                         tokio::spawn(async move {
                             handle_peer_as_initiator(
                                 signer,
@@ -233,6 +302,7 @@ async fn discover_peers_server(
                                 storage,
                                 table,
                                 broadcaster,
+                                tx_fetcher,
                             )
                             .await;
                         });
@@ -321,6 +391,9 @@ async fn discover_peers_server(
 
                 if let Some(nodes) = nodes_to_insert {
                     for node in nodes {
+                        if ban_list.is_ip_banned(node.ip) || ban_list.is_node_banned(node.node_id) {
+                            continue;
+                        }
                         let (peer, inserted_to_table) = table.insert_node(node);
                         if inserted_to_table && peer.is_some() {
                             let peer = peer.unwrap();
@@ -346,7 +419,27 @@ async fn discovery_startup(
     table: Arc<Mutex<KademliaTable>>,
     signer: SigningKey,
     bootnodes: Vec<BootNode>,
+    known_peers: Vec<KnownPeer>,
 ) {
+    // Peers known from previous runs are pinged first, as they are likely to still be alive
+    for known_peer in known_peers {
+        let node = known_peer.node;
+        let (_, inserted_to_table) = table.lock().await.insert_node(node);
+        if !inserted_to_table {
+            continue;
+        }
+        if let Some(peer) = table.lock().await.get_by_node_id_mut(node.node_id) {
+            peer.liveness = known_peer.liveness.max(1);
+        }
+        let ping_hash = ping(
+            &udp_socket,
+            udp_addr,
+            SocketAddr::new(node.ip, node.udp_port),
+            &signer,
+        )
+        .await;
+        table.lock().await.update_peer_ping(node.node_id, ping_hash);
+    }
     for bootnode in bootnodes {
         table.lock().await.insert_node(Node {
             ip: bootnode.socket_address.ip(),
@@ -764,32 +857,57 @@ async fn serve_requests(
     signer: SigningKey,
     storage: Store,
     table: Arc<Mutex<KademliaTable>>,
+    ban_list: SharedBanList,
     connection_broadcast: broadcast::Sender<(tokio::task::Id, Arc<RLPxMessage>)>,
+    tx_fetcher: TxFetcherHandle,
 ) {
     let tcp_socket = TcpSocket::new_v4().unwrap();
     tcp_socket.bind(tcp_addr).unwrap();
     let listener = tcp_socket.listen(50).unwrap();
+    let handshake_limiter = HandshakeLimiter::new();
     loop {
-        let (stream, _peer_addr) = listener.accept().await.unwrap();
+        let (stream, peer_addr) = listener.accept().await.unwrap();
+        if ban_list.is_ip_banned(peer_addr.ip()) {
+            debug!("Dropping connection from banned ip {peer_addr}");
+            continue;
+        }
+        // Drop the connection right away instead of spending resources on its handshake
+        let handshake_permit = match handshake_limiter.try_start(peer_addr.ip()) {
+            Ok(permit) => permit,
+            Err(rejection) => {
+                debug!("Dropping connection from {peer_addr}: {rejection:?}");
+                continue;
+            }
+        };
 
         tokio::spawn(handle_peer_as_receiver(
             signer.clone(),
             stream,
+            handshake_permit,
             storage.clone(),
             table.clone(),
+            ban_list.clone(),
             connection_broadcast.clone(),
+            tx_fetcher.clone(),
         ));
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_peer_as_receiver(
     signer: SigningKey,
     stream: TcpStream,
+    handshake_permit: HandshakePermit,
     storage: Store,
     table: Arc<Mutex<KademliaTable>>,
+    ban_list: SharedBanList,
     connection_broadcast: broadcast::Sender<(tokio::task::Id, Arc<RLPxMessage>)>,
+    tx_fetcher: TxFetcherHandle,
 ) {
-    let mut conn = RLPxConnection::receiver(signer, stream, storage, connection_broadcast);
+    let mut conn =
+        RLPxConnection::receiver(signer, stream, storage, connection_broadcast, tx_fetcher)
+            .with_handshake_permit(handshake_permit)
+            .with_ban_list(ban_list);
     conn.start_peer(table).await;
 }
 
@@ -800,6 +918,7 @@ async fn handle_peer_as_initiator(
     storage: Store,
     table: Arc<Mutex<KademliaTable>>,
     connection_broadcast: broadcast::Sender<(tokio::task::Id, Arc<RLPxMessage>)>,
+    tx_fetcher: TxFetcherHandle,
 ) {
     debug!("Trying RLPx connection with {node:?}");
     let stream = TcpSocket::new_v4()
@@ -807,7 +926,16 @@ async fn handle_peer_as_initiator(
         .connect(SocketAddr::new(node.ip, node.tcp_port))
         .await
         .unwrap();
-    match RLPxConnection::initiator(signer, msg, stream, storage, connection_broadcast).await {
+    match RLPxConnection::initiator(
+        signer,
+        msg,
+        stream,
+        storage,
+        connection_broadcast,
+        tx_fetcher,
+    )
+    .await
+    {
         Ok(mut conn) => conn.start_peer(table).await,
         Err(e) => {
             error!("Error: {e}, Could not start connection with {node:?}");
@@ -885,7 +1013,9 @@ mod tests {
                 storage.clone(),
                 table.clone(),
                 signer.clone(),
+                SharedBanList::default(),
                 channel_broadcast_send_end,
+                TxFetcherHandle::create().0,
             ));
         }
 
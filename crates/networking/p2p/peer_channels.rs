@@ -1,4 +1,7 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
 
 use ethrex_core::{
     types::{BlockBody, BlockHeader},
@@ -21,6 +24,8 @@ pub const MAX_MESSAGES_IN_PEER_CHANNEL: usize = 25;
 pub struct PeerChannels {
     sender: mpsc::Sender<RLPxMessage>,
     receiver: Arc<Mutex<mpsc::Receiver<RLPxMessage>>>,
+    /// Moving average of the time the peer takes to answer requests, none until it answers one
+    response_time: Arc<StdMutex<Option<Duration>>>,
 }
 
 impl PeerChannels {
@@ -35,12 +40,47 @@ impl PeerChannels {
             Self {
                 sender,
                 receiver: Arc::new(Mutex::new(receiver)),
+                response_time: Default::default(),
             },
             connection_sender,
             connection_receiver,
         )
     }
 
+    /// Returns the average time the peer takes to answer requests, none if it wasn't measured yet
+    pub(crate) fn response_time(&self) -> Option<Duration> {
+        *self
+            .response_time
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Updates the average response time of the peer with a request sent at the given time,
+    /// requests that weren't answered count as taking the whole reply timeout
+    pub(crate) fn record_response(&self, sent_at: Instant, answered: bool) {
+        let elapsed = if answered {
+            sent_at.elapsed()
+        } else {
+            PEER_REPLY_TIMOUT
+        };
+        let mut response_time = self
+            .response_time
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *response_time = Some(match *response_time {
+            Some(average) => (average * 3 + elapsed) / 4,
+            None => elapsed,
+        });
+    }
+
+    /// Sends a message to the peer through its active connection, without waiting for a reply
+    pub(crate) async fn send(
+        &self,
+        message: RLPxMessage,
+    ) -> Result<(), mpsc::error::SendError<RLPxMessage>> {
+        self.sender.send(message).await
+    }
+
     /// Requests block headers from the peer
     /// Returns the response message or None if:
     /// - There are no available peers (the node just started up or was rejected by all other nodes)
@@ -57,6 +97,7 @@ impl PeerChannels {
         });
         self.sender.send(request).await.ok()?;
         let mut receiver = self.receiver.lock().await;
+        let sent_at = Instant::now();
         let block_headers = tokio::time::timeout(PEER_REPLY_TIMOUT, async move {
             loop {
                 match receiver.recv().await {
@@ -72,8 +113,10 @@ impl PeerChannels {
             }
         })
         .await
-        .ok()??;
-        (!block_headers.is_empty()).then_some(block_headers)
+        .ok()
+        .flatten();
+        self.record_response(sent_at, block_headers.is_some());
+        block_headers.filter(|block_headers| !block_headers.is_empty())
     }
 
     /// Requests block headers from the peer
@@ -90,6 +133,7 @@ impl PeerChannels {
         });
         self.sender.send(request).await.ok()?;
         let mut receiver = self.receiver.lock().await;
+        let sent_at = Instant::now();
         let block_bodies = tokio::time::timeout(PEER_REPLY_TIMOUT, async move {
             loop {
                 match receiver.recv().await {
@@ -105,8 +149,12 @@ impl PeerChannels {
             }
         })
         .await
-        .ok()??;
+        .ok()
+        .flatten();
+        self.record_response(sent_at, block_bodies.is_some());
         // Check that the response is not empty and does not contain more bodies than the ones requested
-        (!block_bodies.is_empty() && block_bodies.len() <= block_hashes_len).then_some(block_bodies)
+        block_bodies.filter(|block_bodies| {
+            !block_bodies.is_empty() && block_bodies.len() <= block_hashes_len
+        })
     }
 }
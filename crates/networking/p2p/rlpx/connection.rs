@@ -1,12 +1,15 @@
 use std::sync::Arc;
 
 use crate::{
+    ban_list::SharedBanList,
+    handshake_limits::HandshakePermit,
     peer_channels::PeerChannels,
     rlpx::{
         eth::{
-            backend,
+            backend::{self, ETH_VERSION},
             blocks::{BlockBodies, BlockHeaders},
-            transactions::Transactions,
+            status::BlockRange,
+            transactions::{PooledTransactions, Transactions},
         },
         handshake::encode_ack_message,
         message::Message,
@@ -17,6 +20,7 @@ use crate::{
         process_account_range_request, process_byte_codes_request, process_storage_ranges_request,
         process_trie_nodes_request,
     },
+    tx_fetcher::{TxFetcherEvent, TxFetcherHandle},
     MAX_DISC_PACKET_SIZE,
 };
 
@@ -30,7 +34,10 @@ use super::{
 };
 use aes::cipher::KeyIvInit;
 use ethrex_blockchain::mempool;
-use ethrex_core::{H256, H512};
+use ethrex_core::{
+    types::{Transaction, TxType},
+    H256, H512,
+};
 use ethrex_rlp::decode::RLPDecode;
 use ethrex_storage::Store;
 use k256::{
@@ -49,9 +56,12 @@ use tokio::{
 };
 use tracing::{debug, error};
 const CAP_P2P: (Capability, u8) = (Capability::P2p, 5);
-const CAP_ETH: (Capability, u8) = (Capability::Eth, 68);
+const CAP_ETH_67: (Capability, u8) = (Capability::Eth, 67);
+const CAP_ETH_68: (Capability, u8) = (Capability::Eth, 68);
+const CAP_ETH_69: (Capability, u8) = (Capability::Eth, 69);
 const CAP_SNAP: (Capability, u8) = (Capability::Snap, 1);
-const SUPPORTED_CAPABILITIES: [(Capability, u8); 3] = [CAP_P2P, CAP_ETH, CAP_SNAP];
+const SUPPORTED_CAPABILITIES: [(Capability, u8); 5] =
+    [CAP_P2P, CAP_ETH_67, CAP_ETH_68, CAP_ETH_69, CAP_SNAP];
 const PERIODIC_TASKS_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
 
 pub(crate) type Aes256Ctr64BE = ctr::Ctr64BE<aes::Aes256>;
@@ -63,6 +73,10 @@ pub(crate) struct RLPxConnection<S> {
     stream: S,
     storage: Store,
     capabilities: Vec<(Capability, u8)>,
+    /// Highest eth protocol version supported by both ends, none if the peer doesn't support eth
+    eth_version: Option<u8>,
+    /// Range of blocks we last advertised to the peer, only sent from eth/69 on
+    advertised_block_range: Option<BlockRange>,
     next_periodic_task_check: Instant,
     /// Send end of the channel used to broadcast messages
     /// to other connected peers, is ok to have it here,
@@ -73,6 +87,13 @@ pub(crate) struct RLPxConnection<S> {
     /// The receive end is instantiated after the handshake is completed
     /// under `handle_peer`.
     connection_broadcast_send: broadcast::Sender<(task::Id, Arc<Message>)>,
+    /// Used to report announced and delivered transactions to the transaction fetcher,
+    /// which schedules the requests for them among all connected peers
+    tx_fetcher: TxFetcherHandle,
+    /// Counts an inbound handshake as in progress until it is done
+    handshake_permit: Option<HandshakePermit>,
+    /// Peers whose inbound handshakes are rejected once their node id is known
+    ban_list: Option<SharedBanList>,
 }
 
 impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
@@ -82,6 +103,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         state: RLPxConnectionState,
         storage: Store,
         connection_broadcast: broadcast::Sender<(task::Id, Arc<Message>)>,
+        tx_fetcher: TxFetcherHandle,
     ) -> Self {
         Self {
             signer,
@@ -89,16 +111,32 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
             stream,
             storage,
             capabilities: vec![],
+            eth_version: None,
+            advertised_block_range: None,
             next_periodic_task_check: Instant::now() + PERIODIC_TASKS_CHECK_INTERVAL,
             connection_broadcast_send: connection_broadcast,
+            tx_fetcher,
+            handshake_permit: None,
+            ban_list: None,
         }
     }
 
+    pub fn with_handshake_permit(mut self, permit: HandshakePermit) -> Self {
+        self.handshake_permit = Some(permit);
+        self
+    }
+
+    pub fn with_ban_list(mut self, ban_list: SharedBanList) -> Self {
+        self.ban_list = Some(ban_list);
+        self
+    }
+
     pub fn receiver(
         signer: SigningKey,
         stream: S,
         storage: Store,
         connection_broadcast: broadcast::Sender<(task::Id, Arc<Message>)>,
+        tx_fetcher: TxFetcherHandle,
     ) -> Self {
         let mut rng = rand::thread_rng();
         Self::new(
@@ -110,6 +148,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
             )),
             storage,
             connection_broadcast,
+            tx_fetcher,
         )
     }
 
@@ -119,6 +158,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         stream: S,
         storage: Store,
         connection_broadcast_send: broadcast::Sender<(task::Id, Arc<Message>)>,
+        tx_fetcher: TxFetcherHandle,
     ) -> Result<Self, RLPxError> {
         let mut rng = rand::thread_rng();
         let digest = Keccak256::digest(msg.get(65..).ok_or(RLPxError::InvalidMessageLength())?);
@@ -141,6 +181,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
             state,
             storage,
             connection_broadcast_send,
+            tx_fetcher,
         ))
     }
 
@@ -148,7 +189,13 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
     /// It runs in it's own task and blocks until the connection is dropped
     pub async fn start_peer(&mut self, table: Arc<Mutex<crate::kademlia::KademliaTable>>) {
         // Perform handshake
-        if let Err(e) = self.handshake().await {
+        let handshake = self.handshake().await;
+        if let Some(permit) = self.handshake_permit.take() {
+            if handshake.is_err() {
+                permit.fail();
+            }
+        }
+        if let Err(e) = handshake {
             self.peer_conn_failed("Handshake failed", e, table).await;
         } else {
             // Handshake OK: handle connection
@@ -163,7 +210,10 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
                     )
                     .await;
             };
-            table.lock().await.set_channels(node_id, peer_channels);
+            table
+                .lock()
+                .await
+                .set_channels(node_id, peer_channels, self.shared_capabilities());
             if let Err(e) = self.handle_peer_conn(sender, receiver).await {
                 self.peer_conn_failed("Error during RLPx connection", e, table)
                     .await;
@@ -171,6 +221,19 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         }
     }
 
+    /// Returns the capabilities advertised by the peer that this node also supports
+    fn shared_capabilities(&self) -> Vec<Capability> {
+        let mut shared: Vec<Capability> = vec![];
+        for (capability, version) in &self.capabilities {
+            if SUPPORTED_CAPABILITIES.contains(&(capability.clone(), *version))
+                && !shared.contains(capability)
+            {
+                shared.push(capability.clone());
+            }
+        }
+        shared
+    }
+
     async fn peer_conn_failed(
         &mut self,
         error_text: &str,
@@ -183,6 +246,8 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         .await
         .unwrap_or_else(|e| debug!("Could not send Disconnect message: ({e})"));
         if let Ok(node_id) = self.get_remote_node_id() {
+            self.tx_fetcher
+                .notify(TxFetcherEvent::Disconnected(node_id));
             // Discard peer from kademlia table
             debug!("{error_text}: ({error}), discarding peer {node_id}");
             table.lock().await.replace_peer(node_id);
@@ -193,7 +258,8 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
 
     fn match_disconnect_reason(&self, error: &RLPxError) -> Option<u8> {
         match error {
-            RLPxError::RLPDecodeError(_) => Some(2_u8),
+            // Breach of protocol
+            RLPxError::RLPDecodeError(_) | RLPxError::InvalidMessageLength() => Some(2_u8),
             // TODO build a proper matching between error types and disconnection reasons
             _ => None,
         }
@@ -232,6 +298,12 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         // Receive Hello message
         if let Message::Hello(hello_message) = self.receive().await? {
             self.capabilities = hello_message.capabilities;
+            self.eth_version = backend::negotiate_eth_version(
+                self.capabilities
+                    .iter()
+                    .filter(|(cap, _)| *cap == Capability::Eth)
+                    .map(|(_, version)| *version),
+            );
 
             // Check if we have any capability in common
             for cap in self.capabilities.clone() {
@@ -261,7 +333,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
             debug!("Started peer main loop");
             // Wait for eth status message or timeout.
             let mut broadcaster_receive = {
-                if self.capabilities.contains(&CAP_ETH) {
+                if self.eth_version.is_some() {
                     Some(self.connection_broadcast_send.subscribe())
                 } else {
                     None
@@ -323,6 +395,13 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         if Instant::now() >= self.next_periodic_task_check {
             self.send(Message::Ping(PingMessage {})).await?;
             debug!("Ping sent");
+            if let Some(advertised) = self.advertised_block_range {
+                if let Some(update) = backend::get_block_range_update(&self.storage, advertised)? {
+                    self.advertised_block_range = Some(update.block_range);
+                    self.send(Message::BlockRangeUpdate(update)).await?;
+                    debug!("BlockRangeUpdate sent");
+                }
+            }
             self.next_periodic_task_check = Instant::now() + PERIODIC_TASKS_CHECK_INTERVAL;
         };
         Ok(())
@@ -333,7 +412,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
         message: Message,
         sender: mpsc::Sender<Message>,
     ) -> Result<(), RLPxError> {
-        let peer_supports_eth = self.capabilities.contains(&CAP_ETH);
+        let peer_supports_eth = self.eth_version.is_some();
         match message {
             Message::Disconnect(msg_data) => {
                 debug!("Received Disconnect: {:?}", msg_data.reason);
@@ -350,7 +429,14 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
             }
             Message::Status(msg_data) if !peer_supports_eth => {
                 debug!("Received Status");
-                backend::validate_status(msg_data, &self.storage)?
+                backend::validate_status(msg_data, &self.storage, ETH_VERSION)?
+            }
+            Message::BlockRangeUpdate(msg_data) if peer_supports_eth => {
+                backend::validate_block_range(msg_data.block_range)?;
+                debug!(
+                    "Peer serves blocks {} to {}",
+                    msg_data.block_range.earliest, msg_data.block_range.latest
+                );
             }
             Message::GetAccountRange(req) => {
                 let response = process_account_range_request(req, self.storage.clone())?;
@@ -363,6 +449,63 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
                 }
                 self.broadcast_message(Message::Transactions(txs)).await?;
             }
+            Message::NewPooledTransactionHashes(msg_data) if peer_supports_eth => {
+                // Blob transactions are not requested as they can't be added to the mempool
+                // without their blobs bundle
+                let transaction_hashes = match msg_data.transaction_types() {
+                    Some(types) => msg_data
+                        .transaction_hashes()
+                        .iter()
+                        .zip(types)
+                        .filter(|(_, tx_type)| **tx_type != TxType::EIP4844 as u8)
+                        .map(|(hash, _)| *hash)
+                        .collect(),
+                    None => msg_data.transaction_hashes().to_vec(),
+                };
+                let mut unknown_hashes = vec![];
+                for hash in transaction_hashes {
+                    if self.storage.get_transaction_from_pool(hash)?.is_none() {
+                        unknown_hashes.push(hash);
+                    }
+                }
+                // The fetcher decides when and from which peer they are requested
+                if !unknown_hashes.is_empty() {
+                    self.tx_fetcher.notify(TxFetcherEvent::Announced(
+                        self.get_remote_node_id()?,
+                        unknown_hashes,
+                    ));
+                }
+            }
+            Message::GetPooledTransactions(msg_data) if peer_supports_eth => {
+                let mut pooled_transactions = vec![];
+                for hash in &msg_data.transaction_hashes {
+                    match self.storage.get_transaction_from_pool(*hash)? {
+                        // Blob transactions must be sent along with their blobs, which is not supported yet
+                        Some(tx) if !matches!(*tx, Transaction::EIP4844Transaction(_)) => {
+                            pooled_transactions.push(tx.into())
+                        }
+                        _ => {}
+                    }
+                }
+                let response = PooledTransactions::new(msg_data.id, pooled_transactions);
+                self.send(Message::PooledTransactions(response)).await?;
+            }
+            Message::PooledTransactions(msg_data) if peer_supports_eth => {
+                let delivered_hashes = msg_data
+                    .pooled_transactions
+                    .iter()
+                    .map(Transaction::compute_hash)
+                    .collect();
+                self.tx_fetcher.notify(TxFetcherEvent::Delivered(
+                    self.get_remote_node_id()?,
+                    delivered_hashes,
+                ));
+                for tx in msg_data.pooled_transactions {
+                    if let Err(error) = mempool::add_transaction(tx, &self.storage) {
+                        debug!("Discarded pooled transaction: {error}");
+                    }
+                }
+            }
             Message::GetBlockHeaders(msg_data) if peer_supports_eth => {
                 let response = BlockHeaders {
                     id: msg_data.id,
@@ -431,8 +574,10 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
 
     async fn init_peer_conn(&mut self) -> Result<(), RLPxError> {
         // Sending eth Status if peer supports it
-        if self.capabilities.contains(&CAP_ETH) {
-            let status = backend::get_status(&self.storage)?;
+        if let Some(eth_version) = self.eth_version {
+            debug!("Negotiated eth/{eth_version}");
+            let status = backend::get_status(&self.storage, eth_version)?;
+            self.advertised_block_range = status.block_range;
             debug!("Sending status");
             self.send(Message::Status(status)).await?;
             // The next immediate message in the ETH protocol is the
@@ -442,7 +587,7 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
                 Message::Status(msg_data) => {
                     // TODO: Check message status is correct.
                     debug!("Received Status");
-                    backend::validate_status(msg_data, &self.storage)?
+                    backend::validate_status(msg_data, &self.storage, eth_version)?
                 }
                 _msg => {
                     return Err(RLPxError::HandshakeError(
@@ -519,6 +664,16 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
                 .get(2..)
                 .ok_or(RLPxError::InvalidMessageLength())?;
             let (auth, remote_ephemeral_key) = decode_auth_message(&secret_key, msg, size_data)?;
+            if self
+                .ban_list
+                .as_ref()
+                .is_some_and(|ban_list| ban_list.is_node_banned(auth.node_id))
+            {
+                return Err(RLPxError::HandshakeError(format!(
+                    "Peer {} is banned",
+                    auth.node_id
+                )));
+            }
 
             // Build next state
             self.state = RLPxConnectionState::ReceivedAuth(ReceivedAuth::new(
@@ -592,9 +747,10 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
     }
 
     async fn send(&mut self, message: rlpx::Message) -> Result<(), RLPxError> {
+        let eth_version = self.eth_version.unwrap_or(ETH_VERSION);
         if let RLPxConnectionState::Established(state) = &mut self.state {
             let mut frame_buffer = vec![];
-            message.encode(&mut frame_buffer)?;
+            message.encode(&mut frame_buffer, eth_version)?;
             frame::write(frame_buffer, state, &mut self.stream).await?;
             Ok(())
         } else {
@@ -603,10 +759,11 @@ impl<S: AsyncWrite + AsyncRead + std::marker::Unpin> RLPxConnection<S> {
     }
 
     async fn receive(&mut self) -> Result<rlpx::Message, RLPxError> {
+        let eth_version = self.eth_version.unwrap_or(ETH_VERSION);
         if let RLPxConnectionState::Established(state) = &mut self.state {
             let frame_data = frame::read(state, &mut self.stream).await?;
             let (msg_id, msg_data): (u8, _) = RLPDecode::decode_unfinished(&frame_data)?;
-            Ok(rlpx::Message::decode(msg_id, msg_data)?)
+            Ok(rlpx::Message::decode(msg_id, msg_data, eth_version)?)
         } else {
             Err(RLPxError::InvalidState())
         }
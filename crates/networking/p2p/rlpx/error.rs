@@ -2,7 +2,7 @@ use ethrex_blockchain::error::MempoolError;
 use ethrex_rlp::error::{RLPDecodeError, RLPEncodeError};
 use ethrex_storage::error::StoreError;
 use thiserror::Error;
-use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast::error::RecvError, mpsc::error::SendError};
 
 use super::message::Message;
 
@@ -42,11 +42,18 @@ pub(crate) enum RLPxError {
     #[error(transparent)]
     RecvError(#[from] RecvError),
     #[error(transparent)]
-    Send(#[from] tokio::sync::mpsc::error::SendError<Message>),
+    Send(Box<SendError<Message>>),
     #[error("Error when inserting transaction in the mempool: {0}")]
     MempoolError(#[from] MempoolError),
 }
 
+// Boxed as messages are much larger than the rest of the errors
+impl From<SendError<Message>> for RLPxError {
+    fn from(e: SendError<Message>) -> Self {
+        RLPxError::Send(Box::new(e))
+    }
+}
+
 // Grouping all cryptographic related errors in a single CryptographicError variant
 // We can improve this to individual errors if required
 impl From<k256::ecdsa::Error> for RLPxError {
@@ -3,11 +3,57 @@ use ethrex_storage::Store;
 
 use crate::rlpx::error::RLPxError;
 
-use super::status::StatusMessage;
+use super::status::{BlockRange, BlockRangeUpdate, StatusMessage, BLOCK_RANGE_ETH_VERSION};
 
-pub const ETH_VERSION: u32 = 68;
+/// Latest supported version of the eth protocol
+pub const ETH_VERSION: u8 = 69;
+/// Supported versions of the eth protocol, the highest one shared with a peer is used
+pub const SUPPORTED_ETH_VERSIONS: [u8; 3] = [67, 68, ETH_VERSION];
+/// Amount of blocks the head has to advance for the new block range to be sent to eth/69 peers
+pub const BLOCK_RANGE_UPDATE_INTERVAL: u64 = 32;
 
-pub fn get_status(storage: &Store) -> Result<StatusMessage, RLPxError> {
+/// Returns the highest eth protocol version supported both by us and the peer,
+/// given the eth versions advertised by the peer in its Hello message
+pub fn negotiate_eth_version(peer_versions: impl IntoIterator<Item = u8>) -> Option<u8> {
+    peer_versions
+        .into_iter()
+        .filter(|version| SUPPORTED_ETH_VERSIONS.contains(version))
+        .max()
+}
+
+/// Returns the range of blocks whose bodies and receipts we can serve, which starts
+/// after the genesis if the node was synced from a later block or pruned its history
+pub fn get_block_range(storage: &Store) -> Result<BlockRange, RLPxError> {
+    let latest = storage
+        .get_latest_block_number()?
+        .ok_or(RLPxError::NotFound("Latest Block Number".to_string()))?;
+    let earliest = storage.get_history_start_block_number()?;
+    Ok(BlockRange { earliest, latest })
+}
+
+/// Returns the BlockRangeUpdate to send to a peer given the range last advertised to it,
+/// none if the range didn't change enough to be worth sending
+pub fn get_block_range_update(
+    storage: &Store,
+    advertised: BlockRange,
+) -> Result<Option<BlockRangeUpdate>, RLPxError> {
+    let block_range = get_block_range(storage)?;
+    if block_range.earliest == advertised.earliest
+        && block_range.latest < advertised.latest + BLOCK_RANGE_UPDATE_INTERVAL
+    {
+        return Ok(None);
+    }
+    let latest_block_hash = storage
+        .get_block_header(block_range.latest)?
+        .ok_or(RLPxError::NotFound(format!("Block {}", block_range.latest)))?
+        .compute_block_hash();
+    Ok(Some(BlockRangeUpdate {
+        block_range,
+        latest_block_hash,
+    }))
+}
+
+pub fn get_status(storage: &Store, eth_version: u8) -> Result<StatusMessage, RLPxError> {
     let chain_config = storage.get_chain_config()?;
     let total_difficulty = U256::from(chain_config.terminal_total_difficulty.unwrap_or_default());
     let network_id = chain_config.chain_id;
@@ -26,17 +72,27 @@ pub fn get_status(storage: &Store) -> Result<StatusMessage, RLPxError> {
     let genesis = genesis_header.compute_block_hash();
     let block_hash = block_header.compute_block_hash();
     let fork_id = ForkId::new(chain_config, genesis, block_header.timestamp, block_number);
+    let block_range = (eth_version as u32 >= BLOCK_RANGE_ETH_VERSION)
+        .then(|| get_block_range(storage))
+        .transpose()?;
     Ok(StatusMessage {
-        eth_version: ETH_VERSION,
+        eth_version: eth_version as u32,
         network_id,
         total_difficulty,
         block_hash,
         genesis,
         fork_id,
+        block_range,
     })
 }
 
-pub fn validate_status(msg_data: StatusMessage, storage: &Store) -> Result<(), RLPxError> {
+/// Validates the Status message received from a peer, `eth_version` being the
+/// version negotiated with it
+pub fn validate_status(
+    msg_data: StatusMessage,
+    storage: &Store,
+    eth_version: u8,
+) -> Result<(), RLPxError> {
     let chain_config = storage.get_chain_config()?;
 
     // These blocks must always be available
@@ -60,7 +116,7 @@ pub fn validate_status(msg_data: StatusMessage, storage: &Store) -> Result<(), R
         ));
     }
     //Check Protocol Version
-    if msg_data.eth_version != ETH_VERSION {
+    if msg_data.eth_version != eth_version as u32 {
         return Err(RLPxError::HandshakeError(
             "Eth protocol version does not match".to_string(),
         ));
@@ -77,14 +133,29 @@ pub fn validate_status(msg_data: StatusMessage, storage: &Store) -> Result<(), R
             "Fork Id does not match".to_string(),
         ));
     }
+    // Check the advertised block range
+    if let Some(block_range) = msg_data.block_range {
+        validate_block_range(block_range)?;
+    }
 
     Ok(())
 }
 
+/// Checks the range of blocks a peer advertises, either in its Status or in a BlockRangeUpdate
+pub(crate) fn validate_block_range(block_range: BlockRange) -> Result<(), RLPxError> {
+    if block_range.earliest > block_range.latest {
+        return Err(RLPxError::BadRequest(format!(
+            "Earliest block {} is after the latest block {}",
+            block_range.earliest, block_range.latest
+        )));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::validate_status;
-    use crate::rlpx::eth::status::StatusMessage;
+    use super::{negotiate_eth_version, validate_block_range, validate_status};
+    use crate::rlpx::eth::status::{BlockRange, StatusMessage};
     use ethrex_core::{
         types::{ForkId, Genesis},
         H256, U256,
@@ -119,8 +190,32 @@ mod tests {
             block_hash: H256::random(),
             genesis: genesis_hash,
             fork_id,
+            block_range: None,
         };
-        let result = validate_status(message, &storage);
+        let result = validate_status(message, &storage, 68);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn block_ranges_must_not_end_before_they_start() {
+        assert!(validate_block_range(BlockRange {
+            earliest: 5,
+            latest: 10
+        })
+        .is_ok());
+        assert!(validate_block_range(BlockRange {
+            earliest: 10,
+            latest: 5
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn negotiate_eth_version_picks_highest_shared_version() {
+        assert_eq!(negotiate_eth_version([66, 67, 68]), Some(68));
+        assert_eq!(negotiate_eth_version([67, 68, 69]), Some(69));
+        assert_eq!(negotiate_eth_version([67]), Some(67));
+        assert_eq!(negotiate_eth_version([66, 70]), None);
+        assert_eq!(negotiate_eth_version([]), None);
+    }
 }
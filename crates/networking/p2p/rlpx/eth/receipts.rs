@@ -3,12 +3,17 @@ use crate::rlpx::{
     utils::{snappy_compress, snappy_decompress},
 };
 use bytes::BufMut;
-use ethrex_core::types::{BlockHash, Receipt};
+use ethrex_core::types::{BlockHash, Log, Receipt, TxType};
 use ethrex_rlp::{
+    decode::RLPDecode,
+    encode::RLPEncode,
     error::{RLPDecodeError, RLPEncodeError},
     structs::{Decoder, Encoder},
 };
 
+/// First eth protocol version where receipts are sent without their bloom
+pub const RECEIPTS_WITHOUT_BLOOM_ETH_VERSION: u8 = 69;
+
 // https://github.com/ethereum/devp2p/blob/master/caps/eth.md#getreceipts-0x0f
 #[derive(Debug)]
 pub(crate) struct GetReceipts {
@@ -60,6 +65,88 @@ impl Receipts {
     pub fn new(id: u64, receipts: Vec<Vec<Receipt>>) -> Self {
         Self { receipts, id }
     }
+
+    /// Encodes the message in the format used by the given eth protocol version
+    pub fn encode_for_version(
+        &self,
+        buf: &mut dyn BufMut,
+        eth_version: u8,
+    ) -> Result<(), RLPEncodeError> {
+        if eth_version < RECEIPTS_WITHOUT_BLOOM_ETH_VERSION {
+            return self.encode(buf);
+        }
+        // TODO: avoid cloning the receipts
+        let receipts: Vec<Vec<ReceiptWithoutBloom>> = self
+            .receipts
+            .iter()
+            .map(|block_receipts| {
+                block_receipts
+                    .iter()
+                    .cloned()
+                    .map(ReceiptWithoutBloom)
+                    .collect()
+            })
+            .collect();
+        let mut encoded_data = vec![];
+        Encoder::new(&mut encoded_data)
+            .encode_field(&self.id)
+            .encode_field(&receipts)
+            .finish();
+
+        let msg_data = snappy_compress(encoded_data)?;
+        buf.put_slice(&msg_data);
+        Ok(())
+    }
+
+    /// Decodes the message in the format used by the given eth protocol version
+    pub fn decode_for_version(msg_data: &[u8], eth_version: u8) -> Result<Self, RLPDecodeError> {
+        if eth_version < RECEIPTS_WITHOUT_BLOOM_ETH_VERSION {
+            return Self::decode(msg_data);
+        }
+        let decompressed_data = snappy_decompress(msg_data)?;
+        let decoder = Decoder::new(&decompressed_data)?;
+        let (id, decoder): (u64, _) = decoder.decode_field("request-id")?;
+        let (receipts, _): (Vec<Vec<ReceiptWithoutBloom>>, _) = decoder.decode_field("receipts")?;
+        let receipts = receipts
+            .into_iter()
+            .map(|block_receipts| block_receipts.into_iter().map(|r| r.0).collect())
+            .collect();
+
+        Ok(Self::new(id, receipts))
+    }
+}
+
+/// Receipt as sent from eth/69 on: the transaction type is a plain field instead of a
+/// prefix and the bloom is left out, as it can be computed from the logs
+struct ReceiptWithoutBloom(Receipt);
+
+impl RLPEncode for ReceiptWithoutBloom {
+    fn encode(&self, buf: &mut dyn BufMut) {
+        Encoder::new(buf)
+            .encode_field(&u8::from(self.0.tx_type))
+            .encode_field(&self.0.succeeded)
+            .encode_field(&self.0.cumulative_gas_used)
+            .encode_field(&self.0.logs)
+            .finish();
+    }
+}
+
+impl RLPDecode for ReceiptWithoutBloom {
+    fn decode_unfinished(rlp: &[u8]) -> Result<(Self, &[u8]), RLPDecodeError> {
+        let decoder = Decoder::new(rlp)?;
+        let (tx_type, decoder): (u8, _) = decoder.decode_field("tx-type")?;
+        let tx_type = TxType::from_u8(tx_type).ok_or(RLPDecodeError::Custom(format!(
+            "Invalid transaction type {tx_type}"
+        )))?;
+        let (succeeded, decoder): (bool, _) = decoder.decode_field("status")?;
+        let (cumulative_gas_used, decoder): (u64, _) = decoder.decode_field("cumulative-gas")?;
+        let (logs, decoder): (Vec<Log>, _) = decoder.decode_field("logs")?;
+
+        Ok((
+            Self(Receipt::new(tx_type, succeeded, cumulative_gas_used, logs)),
+            decoder.finish()?,
+        ))
+    }
 }
 
 impl RLPxMessage for Receipts {
@@ -87,7 +174,11 @@ impl RLPxMessage for Receipts {
 
 #[cfg(test)]
 mod tests {
-    use ethrex_core::types::{BlockHash, Receipt};
+    use bytes::Bytes;
+    use ethrex_core::{
+        types::{BlockHash, Log, Receipt, TxType},
+        Address, H256,
+    };
 
     use crate::rlpx::{
         eth::receipts::{GetReceipts, Receipts},
@@ -136,4 +227,28 @@ mod tests {
         assert_eq!(decoded.id, 1);
         assert_eq!(decoded.receipts, Vec::<Vec<Receipt>>::new());
     }
+
+    #[test]
+    fn eth_69_receipts_rebuild_the_bloom() {
+        let log = Log {
+            address: Address::repeat_byte(1),
+            topics: vec![H256::repeat_byte(2)],
+            data: Bytes::from_static(b"data"),
+        };
+        let receipts = vec![vec![
+            Receipt::new(TxType::Legacy, true, 21_000, vec![]),
+            Receipt::new(TxType::EIP1559, false, 50_000, vec![log]),
+        ]];
+        let message = Receipts::new(1, receipts.clone());
+
+        let mut eth_68 = Vec::new();
+        message.encode_for_version(&mut eth_68, 68).unwrap();
+        let mut eth_69 = Vec::new();
+        message.encode_for_version(&mut eth_69, 69).unwrap();
+        assert!(eth_69.len() < eth_68.len());
+
+        let decoded = Receipts::decode_for_version(&eth_69, 69).unwrap();
+        assert_eq!(decoded.id, 1);
+        assert_eq!(decoded.receipts, receipts);
+    }
 }
@@ -4,7 +4,7 @@ use crate::rlpx::{
 };
 use bytes::BufMut;
 use ethrex_core::{
-    types::{BlockHash, ForkId},
+    types::{BlockHash, BlockNumber, ForkId},
     U256,
 };
 use ethrex_rlp::{
@@ -12,27 +12,55 @@ use ethrex_rlp::{
     structs::{Decoder, Encoder},
 };
 
+/// First eth protocol version where the Status message advertises the range of
+/// available blocks instead of the total difficulty
+pub const BLOCK_RANGE_ETH_VERSION: u32 = 69;
+
+/// Range of blocks whose bodies and receipts a peer can serve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlockRange {
+    pub(crate) earliest: BlockNumber,
+    pub(crate) latest: BlockNumber,
+}
+
 #[derive(Debug)]
 pub(crate) struct StatusMessage {
     pub(crate) eth_version: u32,
     pub(crate) network_id: u64,
+    /// Only sent up to eth/68, decoded as zero from eth/69 on
     pub(crate) total_difficulty: U256,
     pub(crate) block_hash: BlockHash,
     pub(crate) genesis: BlockHash,
     pub(crate) fork_id: ForkId,
+    /// Only sent from eth/69 on
+    pub(crate) block_range: Option<BlockRange>,
 }
 
 impl RLPxMessage for StatusMessage {
     fn encode(&self, buf: &mut dyn BufMut) -> Result<(), RLPEncodeError> {
         let mut encoded_data = vec![];
-        Encoder::new(&mut encoded_data)
+        let encoder = Encoder::new(&mut encoded_data)
             .encode_field(&self.eth_version)
-            .encode_field(&self.network_id)
-            .encode_field(&self.total_difficulty)
-            .encode_field(&self.block_hash)
-            .encode_field(&self.genesis)
-            .encode_field(&self.fork_id)
-            .finish();
+            .encode_field(&self.network_id);
+        if self.eth_version >= BLOCK_RANGE_ETH_VERSION {
+            let block_range = self.block_range.ok_or(RLPEncodeError::Custom(
+                "Missing block range in eth/69 status".to_string(),
+            ))?;
+            encoder
+                .encode_field(&self.genesis)
+                .encode_field(&self.fork_id)
+                .encode_field(&block_range.earliest)
+                .encode_field(&block_range.latest)
+                .encode_field(&self.block_hash)
+                .finish();
+        } else {
+            encoder
+                .encode_field(&self.total_difficulty)
+                .encode_field(&self.block_hash)
+                .encode_field(&self.genesis)
+                .encode_field(&self.fork_id)
+                .finish();
+        }
 
         let msg_data = snappy_compress(encoded_data)?;
         buf.put_slice(&msg_data);
@@ -42,25 +70,133 @@ impl RLPxMessage for StatusMessage {
     fn decode(msg_data: &[u8]) -> Result<Self, RLPDecodeError> {
         let decompressed_data = snappy_decompress(msg_data)?;
         let decoder = Decoder::new(&decompressed_data)?;
+        // The version is checked against the negotiated one when validating the status
         let (eth_version, decoder): (u32, _) = decoder.decode_field("protocolVersion")?;
+        let (network_id, decoder): (u64, _) = decoder.decode_field("networkId")?;
+        let status = if eth_version >= BLOCK_RANGE_ETH_VERSION {
+            let (genesis, decoder): (BlockHash, _) = decoder.decode_field("genesis")?;
+            let (fork_id, decoder): (ForkId, _) = decoder.decode_field("forkId")?;
+            let (earliest, decoder): (BlockNumber, _) = decoder.decode_field("earliestBlock")?;
+            let (latest, decoder): (BlockNumber, _) = decoder.decode_field("latestBlock")?;
+            let (block_hash, decoder): (BlockHash, _) = decoder.decode_field("latestBlockHash")?;
+            // Implementations must ignore any additional list elements
+            let _padding = decoder.finish_unchecked();
+            Self {
+                eth_version,
+                network_id,
+                total_difficulty: U256::zero(),
+                block_hash,
+                genesis,
+                fork_id,
+                block_range: Some(BlockRange { earliest, latest }),
+            }
+        } else {
+            let (total_difficulty, decoder): (U256, _) = decoder.decode_field("totalDifficulty")?;
+            let (block_hash, decoder): (BlockHash, _) = decoder.decode_field("blockHash")?;
+            let (genesis, decoder): (BlockHash, _) = decoder.decode_field("genesis")?;
+            let (fork_id, decoder): (ForkId, _) = decoder.decode_field("forkId")?;
+            let _padding = decoder.finish_unchecked();
+            Self {
+                eth_version,
+                network_id,
+                total_difficulty,
+                block_hash,
+                genesis,
+                fork_id,
+                block_range: None,
+            }
+        };
+        Ok(status)
+    }
+}
 
-        assert_eq!(eth_version, 68, "only eth version 68 is supported");
+// https://github.com/ethereum/devp2p/blob/master/caps/eth.md#blockrangeupdate-0x11
+/// Sent from eth/69 on when the range of blocks the node can serve changes
+#[derive(Debug)]
+pub(crate) struct BlockRangeUpdate {
+    pub(crate) block_range: BlockRange,
+    pub(crate) latest_block_hash: BlockHash,
+}
 
-        let (network_id, decoder): (u64, _) = decoder.decode_field("networkId")?;
-        let (total_difficulty, decoder): (U256, _) = decoder.decode_field("totalDifficulty")?;
-        let (block_hash, decoder): (BlockHash, _) = decoder.decode_field("blockHash")?;
-        let (genesis, decoder): (BlockHash, _) = decoder.decode_field("genesis")?;
-        let (fork_id, decoder): (ForkId, _) = decoder.decode_field("forkId")?;
-        // Implementations must ignore any additional list elements
+impl RLPxMessage for BlockRangeUpdate {
+    fn encode(&self, buf: &mut dyn BufMut) -> Result<(), RLPEncodeError> {
+        let mut encoded_data = vec![];
+        Encoder::new(&mut encoded_data)
+            .encode_field(&self.block_range.earliest)
+            .encode_field(&self.block_range.latest)
+            .encode_field(&self.latest_block_hash)
+            .finish();
+
+        let msg_data = snappy_compress(encoded_data)?;
+        buf.put_slice(&msg_data);
+        Ok(())
+    }
+
+    fn decode(msg_data: &[u8]) -> Result<Self, RLPDecodeError> {
+        let decompressed_data = snappy_decompress(msg_data)?;
+        let decoder = Decoder::new(&decompressed_data)?;
+        let (earliest, decoder): (BlockNumber, _) = decoder.decode_field("earliestBlock")?;
+        let (latest, decoder): (BlockNumber, _) = decoder.decode_field("latestBlock")?;
+        let (latest_block_hash, decoder): (BlockHash, _) =
+            decoder.decode_field("latestBlockHash")?;
         let _padding = decoder.finish_unchecked();
 
         Ok(Self {
-            eth_version,
-            network_id,
-            total_difficulty,
-            block_hash,
-            genesis,
-            fork_id,
+            block_range: BlockRange { earliest, latest },
+            latest_block_hash,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethrex_core::{types::ChainConfig, H256};
+
+    fn status(eth_version: u32) -> StatusMessage {
+        StatusMessage {
+            eth_version,
+            network_id: 1,
+            total_difficulty: U256::from(17),
+            block_hash: H256::repeat_byte(1),
+            genesis: H256::repeat_byte(2),
+            fork_id: ForkId::new(ChainConfig::default(), H256::repeat_byte(2), 0, 0),
+            block_range: Some(BlockRange {
+                earliest: 10,
+                latest: 20,
+            }),
+        }
+    }
+
+    #[test]
+    fn eth_68_status_sends_the_total_difficulty() {
+        let mut buf = vec![];
+        status(68).encode(&mut buf).unwrap();
+        let decoded = StatusMessage::decode(&buf).unwrap();
+        assert_eq!(decoded.total_difficulty, U256::from(17));
+        assert_eq!(decoded.block_hash, H256::repeat_byte(1));
+        assert_eq!(decoded.genesis, H256::repeat_byte(2));
+        assert_eq!(decoded.block_range, None);
+    }
+
+    #[test]
+    fn eth_69_status_sends_the_block_range() {
+        let mut buf = vec![];
+        status(69).encode(&mut buf).unwrap();
+        let decoded = StatusMessage::decode(&buf).unwrap();
+        assert_eq!(decoded.total_difficulty, U256::zero());
+        assert_eq!(decoded.block_hash, H256::repeat_byte(1));
+        assert_eq!(decoded.genesis, H256::repeat_byte(2));
+        assert_eq!(
+            decoded.fork_id,
+            ForkId::new(ChainConfig::default(), H256::repeat_byte(2), 0, 0)
+        );
+        assert_eq!(
+            decoded.block_range,
+            Some(BlockRange {
+                earliest: 10,
+                latest: 20
+            })
+        );
+    }
+}
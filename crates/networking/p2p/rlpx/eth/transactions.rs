@@ -1,6 +1,8 @@
 use bytes::BufMut;
 use ethrex_core::{types::Transaction, H256};
 use ethrex_rlp::{
+    decode::RLPDecode,
+    encode::RLPEncode,
     error::{RLPDecodeError, RLPEncodeError},
     structs::{Decoder, Encoder},
 };
@@ -19,6 +21,8 @@ pub(crate) struct Transactions {
 // TODO(#1132): Also limit transactions by message byte-size.
 // Limit taken from here: https://github.com/ethereum/go-ethereum/blob/df182a742cec68adcc034d4747afa5182fc75ca3/eth/fetcher/tx_fetcher.go#L49
 pub const TRANSACTION_LIMIT: usize = 256;
+// Same limit geth uses for the amount of transactions announced in a single message
+pub const TRANSACTION_ANNOUNCEMENT_LIMIT: usize = 4096;
 
 impl Transactions {
     pub fn new(transactions: Vec<Transaction>) -> Self {
@@ -62,6 +66,8 @@ impl RLPxMessage for Transactions {
 
 // https://github.com/ethereum/devp2p/blob/master/caps/eth.md#newpooledtransactionhashes-0x08
 // Broadcast message
+// Before eth/68 the message only contained the transaction hashes, in that case the
+// types and sizes are left empty.
 #[derive(Debug)]
 pub(crate) struct NewPooledTransactionHashes {
     transaction_types: Vec<u8>,
@@ -93,6 +99,51 @@ impl NewPooledTransactionHashes {
             transaction_hashes,
         }
     }
+
+    pub fn transaction_hashes(&self) -> &[H256] {
+        &self.transaction_hashes
+    }
+
+    /// Returns the type of each announced transaction, if the peer sent them (eth/68)
+    pub fn transaction_types(&self) -> Option<&[u8]> {
+        (self.transaction_types.len() == self.transaction_hashes.len())
+            .then_some(self.transaction_types.as_slice())
+    }
+
+    /// Encodes the message in the format used by the given eth protocol version
+    pub fn encode_for_version(
+        &self,
+        buf: &mut dyn BufMut,
+        eth_version: u8,
+    ) -> Result<(), RLPEncodeError> {
+        if eth_version >= 68 {
+            return self.encode(buf);
+        }
+        let mut encoded_data = vec![];
+        self.transaction_hashes.encode(&mut encoded_data);
+        let msg_data = snappy_compress(encoded_data)?;
+        buf.put_slice(&msg_data);
+        Ok(())
+    }
+
+    /// Decodes the message in the format used by the given eth protocol version
+    pub fn decode_for_version(msg_data: &[u8], eth_version: u8) -> Result<Self, RLPDecodeError> {
+        if eth_version >= 68 {
+            return Self::decode(msg_data);
+        }
+        let decompressed_data = snappy_decompress(msg_data)?;
+        let transaction_hashes = Vec::<H256>::decode(&decompressed_data)?;
+        if transaction_hashes.len() > TRANSACTION_ANNOUNCEMENT_LIMIT {
+            return Err(RLPDecodeError::Custom(
+                "Too many transactions announced".to_string(),
+            ));
+        }
+        Ok(Self {
+            transaction_types: vec![],
+            transaction_sizes: vec![],
+            transaction_hashes,
+        })
+    }
 }
 
 impl RLPxMessage for NewPooledTransactionHashes {
@@ -118,6 +169,11 @@ impl RLPxMessage for NewPooledTransactionHashes {
             decoder.decode_field("transactionSizes")?;
         let (transaction_hashes, _): (Vec<H256>, _) = decoder.decode_field("transactionHashes")?;
 
+        if transaction_hashes.len() > TRANSACTION_ANNOUNCEMENT_LIMIT {
+            return Err(RLPDecodeError::Custom(
+                "Too many transactions announced".to_string(),
+            ));
+        }
         if transaction_hashes.len() == transaction_sizes.len()
             && transaction_sizes.len() == transaction_types.len()
         {
@@ -140,8 +196,8 @@ impl RLPxMessage for NewPooledTransactionHashes {
 pub(crate) struct GetPooledTransactions {
     // id is a u64 chosen by the requesting peer, the responding peer must mirror the value for the response
     // https://github.com/ethereum/devp2p/blob/master/caps/eth.md#protocol-messages
-    id: u64,
-    transaction_hashes: Vec<H256>,
+    pub(crate) id: u64,
+    pub(crate) transaction_hashes: Vec<H256>,
 }
 
 impl GetPooledTransactions {
@@ -177,11 +233,12 @@ impl RLPxMessage for GetPooledTransactions {
 }
 
 // https://github.com/ethereum/devp2p/blob/master/caps/eth.md#pooledtransactions-0x0a
+#[derive(Debug)]
 pub(crate) struct PooledTransactions {
     // id is a u64 chosen by the requesting peer, the responding peer must mirror the value for the response
     // https://github.com/ethereum/devp2p/blob/master/caps/eth.md#protocol-messages
-    id: u64,
-    pooled_transactions: Vec<Transaction>,
+    pub(crate) id: u64,
+    pub(crate) pooled_transactions: Vec<Transaction>,
 }
 
 impl PooledTransactions {
@@ -221,7 +278,9 @@ mod tests {
     use ethrex_core::{types::Transaction, H256};
 
     use crate::rlpx::{
-        eth::transactions::{GetPooledTransactions, PooledTransactions},
+        eth::transactions::{
+            GetPooledTransactions, NewPooledTransactionHashes, PooledTransactions,
+        },
         message::RLPxMessage,
     };
 
@@ -267,4 +326,28 @@ mod tests {
         assert_eq!(decoded.id, 1);
         assert_eq!(decoded.pooled_transactions, vec![transaction1]);
     }
+
+    #[test]
+    fn new_pooled_transaction_hashes_for_each_eth_version() {
+        let transactions = vec![
+            Transaction::LegacyTransaction(Default::default()),
+            Transaction::EIP1559Transaction(Default::default()),
+        ];
+        let hashes: Vec<H256> = transactions.iter().map(|tx| tx.compute_hash()).collect();
+        let message = NewPooledTransactionHashes::new(transactions);
+
+        let mut buf = Vec::new();
+        message.encode_for_version(&mut buf, 68).unwrap();
+        let decoded = NewPooledTransactionHashes::decode_for_version(&buf, 68).unwrap();
+        assert_eq!(decoded.transaction_hashes(), hashes);
+        assert_eq!(decoded.transaction_types(), Some([0, 2].as_slice()));
+        // The eth/68 format can't be decoded as eth/67
+        assert!(NewPooledTransactionHashes::decode_for_version(&buf, 67).is_err());
+
+        let mut buf = Vec::new();
+        message.encode_for_version(&mut buf, 67).unwrap();
+        let decoded = NewPooledTransactionHashes::decode_for_version(&buf, 67).unwrap();
+        assert_eq!(decoded.transaction_hashes(), hashes);
+        assert_eq!(decoded.transaction_types(), None);
+    }
 }
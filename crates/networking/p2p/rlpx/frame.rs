@@ -7,7 +7,7 @@ use ethrex_rlp::encode::RLPEncode as _;
 use sha3::Digest as _;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use super::{connection::Established, error::RLPxError};
+use super::{connection::Established, error::RLPxError, utils::MAX_MESSAGE_SIZE};
 
 pub(crate) async fn write<S: AsyncWrite + std::marker::Unpin>(
     mut frame_data: Vec<u8>,
@@ -123,18 +123,30 @@ pub(crate) async fn read<S: AsyncRead + std::marker::Unpin>(
             .map_err(|_| RLPxError::CryptographyError("Invalid header mac".to_owned()))?,
     );
 
-    assert_eq!(header_mac, expected_header_mac.0);
+    if header_mac != expected_header_mac.0 {
+        return Err(RLPxError::CryptographyError(
+            "Invalid header mac".to_owned(),
+        ));
+    }
 
     let header_text = header_ciphertext;
     state.ingress_aes.apply_keystream(header_text);
 
     // header-data = [capability-id, context-id]
     // Both are unused, and always zero
-    assert_eq!(&header_text[3..6], &(0_u8, 0_u8).encode_to_vec());
+    if header_text[3..6] != (0_u8, 0_u8).encode_to_vec() {
+        return Err(RLPxError::ConnectionError(
+            "Invalid frame header data".to_owned(),
+        ));
+    }
 
     let frame_size: usize = u32::from_be_bytes([0, header_text[0], header_text[1], header_text[2]])
         .try_into()
         .map_err(|_| RLPxError::CryptographyError("Invalid frame size".to_owned()))?;
+    // Reject oversized frames before allocating a buffer for them
+    if frame_size > MAX_MESSAGE_SIZE {
+        return Err(RLPxError::InvalidMessageLength());
+    }
     // Receive the hello message
     let padded_size = frame_size.next_multiple_of(16);
     let mut frame_data = vec![0; padded_size + 16];
@@ -160,7 +172,9 @@ pub(crate) async fn read<S: AsyncRead + std::marker::Unpin>(
         .try_into()
         .map_err(|_| RLPxError::CryptographyError("Invalid frame mac".to_owned()))?;
 
-    assert_eq!(frame_mac, expected_frame_mac);
+    if frame_mac != expected_frame_mac {
+        return Err(RLPxError::CryptographyError("Invalid frame mac".to_owned()));
+    }
 
     // decrypt frame
     state.ingress_aes.apply_keystream(frame_ciphertext);
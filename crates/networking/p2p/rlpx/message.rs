@@ -4,8 +4,10 @@ use std::fmt::Display;
 
 use super::eth::blocks::{BlockBodies, BlockHeaders, GetBlockBodies, GetBlockHeaders};
 use super::eth::receipts::Receipts;
-use super::eth::status::StatusMessage;
-use super::eth::transactions::Transactions;
+use super::eth::status::{BlockRangeUpdate, StatusMessage, BLOCK_RANGE_ETH_VERSION};
+use super::eth::transactions::{
+    GetPooledTransactions, NewPooledTransactionHashes, PooledTransactions, Transactions,
+};
 use super::p2p::{DisconnectMessage, HelloMessage, PingMessage, PongMessage};
 use super::snap::{
     AccountRange, ByteCodes, GetAccountRange, GetByteCodes, GetStorageRanges, GetTrieNodes,
@@ -14,6 +16,16 @@ use super::snap::{
 
 use ethrex_rlp::encode::RLPEncode;
 
+/// Returns the id the snap capability messages start at, right after the eth ones,
+/// as eth/69 added the BlockRangeUpdate message
+pub fn snap_capability_offset(eth_version: u8) -> u8 {
+    if eth_version as u32 >= BLOCK_RANGE_ETH_VERSION {
+        0x22
+    } else {
+        0x21
+    }
+}
+
 pub trait RLPxMessage: Sized {
     fn encode(&self, buf: &mut dyn BufMut) -> Result<(), RLPEncodeError>;
 
@@ -32,7 +44,11 @@ pub(crate) enum Message {
     Transactions(Transactions),
     GetBlockBodies(GetBlockBodies),
     BlockBodies(BlockBodies),
+    NewPooledTransactionHashes(NewPooledTransactionHashes),
+    GetPooledTransactions(GetPooledTransactions),
+    PooledTransactions(PooledTransactions),
     Receipts(Receipts),
+    BlockRangeUpdate(BlockRangeUpdate),
     // snap capability
     GetAccountRange(GetAccountRange),
     AccountRange(AccountRange),
@@ -45,7 +61,10 @@ pub(crate) enum Message {
 }
 
 impl Message {
-    pub fn decode(msg_id: u8, msg_data: &[u8]) -> Result<Message, RLPDecodeError> {
+    /// Decodes a message with the given id.
+    /// The eth protocol version negotiated with the peer is needed as the format of some
+    /// messages changed between versions.
+    pub fn decode(msg_id: u8, msg_data: &[u8], eth_version: u8) -> Result<Message, RLPDecodeError> {
         match msg_id {
             0x00 => Ok(Message::Hello(HelloMessage::decode(msg_data)?)),
             0x01 => Ok(Message::Disconnect(DisconnectMessage::decode(msg_data)?)),
@@ -67,22 +86,44 @@ impl Message {
             0x14 => Ok(Message::BlockHeaders(BlockHeaders::decode(msg_data)?)),
             0x15 => Ok(Message::GetBlockBodies(GetBlockBodies::decode(msg_data)?)),
             0x16 => Ok(Message::BlockBodies(BlockBodies::decode(msg_data)?)),
-            0x20 => Ok(Message::Receipts(Receipts::decode(msg_data)?)),
-            0x21 => Ok(Message::GetAccountRange(GetAccountRange::decode(msg_data)?)),
-            0x22 => Ok(Message::AccountRange(AccountRange::decode(msg_data)?)),
-            0x23 => Ok(Message::GetStorageRanges(GetStorageRanges::decode(
+            0x18 => Ok(Message::NewPooledTransactionHashes(
+                NewPooledTransactionHashes::decode_for_version(msg_data, eth_version)?,
+            )),
+            0x19 => Ok(Message::GetPooledTransactions(
+                GetPooledTransactions::decode(msg_data)?,
+            )),
+            0x1a => Ok(Message::PooledTransactions(PooledTransactions::decode(
+                msg_data,
+            )?)),
+            0x20 => Ok(Message::Receipts(Receipts::decode_for_version(
                 msg_data,
+                eth_version,
             )?)),
-            0x24 => Ok(Message::StorageRanges(StorageRanges::decode(msg_data)?)),
-            0x25 => Ok(Message::GetByteCodes(GetByteCodes::decode(msg_data)?)),
-            0x26 => Ok(Message::ByteCodes(ByteCodes::decode(msg_data)?)),
-            0x27 => Ok(Message::GetTrieNodes(GetTrieNodes::decode(msg_data)?)),
-            0x28 => Ok(Message::TrieNodes(TrieNodes::decode(msg_data)?)),
+            0x21 if eth_version as u32 >= BLOCK_RANGE_ETH_VERSION => Ok(Message::BlockRangeUpdate(
+                BlockRangeUpdate::decode(msg_data)?,
+            )),
+            snap_id if snap_id >= snap_capability_offset(eth_version) => {
+                match snap_id - snap_capability_offset(eth_version) {
+                    0x00 => Ok(Message::GetAccountRange(GetAccountRange::decode(msg_data)?)),
+                    0x01 => Ok(Message::AccountRange(AccountRange::decode(msg_data)?)),
+                    0x02 => Ok(Message::GetStorageRanges(GetStorageRanges::decode(
+                        msg_data,
+                    )?)),
+                    0x03 => Ok(Message::StorageRanges(StorageRanges::decode(msg_data)?)),
+                    0x04 => Ok(Message::GetByteCodes(GetByteCodes::decode(msg_data)?)),
+                    0x05 => Ok(Message::ByteCodes(ByteCodes::decode(msg_data)?)),
+                    0x06 => Ok(Message::GetTrieNodes(GetTrieNodes::decode(msg_data)?)),
+                    0x07 => Ok(Message::TrieNodes(TrieNodes::decode(msg_data)?)),
+                    _ => Err(RLPDecodeError::MalformedData),
+                }
+            }
             _ => Err(RLPDecodeError::MalformedData),
         }
     }
 
-    pub fn encode(&self, buf: &mut dyn BufMut) -> Result<(), RLPEncodeError> {
+    /// Encodes the message in the format used by the given eth protocol version
+    pub fn encode(&self, buf: &mut dyn BufMut, eth_version: u8) -> Result<(), RLPEncodeError> {
+        let snap_offset = snap_capability_offset(eth_version);
         match self {
             Message::Hello(msg) => {
                 0x00_u8.encode(buf);
@@ -124,40 +165,56 @@ impl Message {
                 0x16_u8.encode(buf);
                 msg.encode(buf)
             }
+            Message::NewPooledTransactionHashes(msg) => {
+                0x18_u8.encode(buf);
+                msg.encode_for_version(buf, eth_version)
+            }
+            Message::GetPooledTransactions(msg) => {
+                0x19_u8.encode(buf);
+                msg.encode(buf)
+            }
+            Message::PooledTransactions(msg) => {
+                0x1a_u8.encode(buf);
+                msg.encode(buf)
+            }
             Message::Receipts(msg) => {
                 0x20_u8.encode(buf);
+                msg.encode_for_version(buf, eth_version)
+            }
+            Message::BlockRangeUpdate(msg) => {
+                0x21_u8.encode(buf);
                 msg.encode(buf)
             }
             Message::GetAccountRange(msg) => {
-                0x21_u8.encode(buf);
+                snap_offset.encode(buf);
                 msg.encode(buf)
             }
             Message::AccountRange(msg) => {
-                0x22_u8.encode(buf);
+                (snap_offset + 1).encode(buf);
                 msg.encode(buf)
             }
             Message::GetStorageRanges(msg) => {
-                0x23_u8.encode(buf);
+                (snap_offset + 2).encode(buf);
                 msg.encode(buf)
             }
             Message::StorageRanges(msg) => {
-                0x24_u8.encode(buf);
+                (snap_offset + 3).encode(buf);
                 msg.encode(buf)
             }
             Message::GetByteCodes(msg) => {
-                0x25_u8.encode(buf);
+                (snap_offset + 4).encode(buf);
                 msg.encode(buf)
             }
             Message::ByteCodes(msg) => {
-                0x26_u8.encode(buf);
+                (snap_offset + 5).encode(buf);
                 msg.encode(buf)
             }
             Message::GetTrieNodes(msg) => {
-                0x27_u8.encode(buf);
+                (snap_offset + 6).encode(buf);
                 msg.encode(buf)
             }
             Message::TrieNodes(msg) => {
-                0x28_u8.encode(buf);
+                (snap_offset + 7).encode(buf);
                 msg.encode(buf)
             }
         }
@@ -177,7 +234,11 @@ impl Display for Message {
             Message::BlockBodies(_) => "eth:BlockBodies".fmt(f),
             Message::Transactions(_) => "eth:TransactionsMessage".fmt(f),
             Message::GetBlockBodies(_) => "eth:GetBlockBodies".fmt(f),
+            Message::NewPooledTransactionHashes(_) => "eth:NewPooledTransactionHashes".fmt(f),
+            Message::GetPooledTransactions(_) => "eth:GetPooledTransactions".fmt(f),
+            Message::PooledTransactions(_) => "eth:PooledTransactions".fmt(f),
             Message::Receipts(_) => "eth:Receipts".fmt(f),
+            Message::BlockRangeUpdate(_) => "eth:BlockRangeUpdate".fmt(f),
             Message::GetAccountRange(_) => "snap:GetAccountRange".fmt(f),
             Message::AccountRange(_) => "snap:AccountRange".fmt(f),
             Message::GetStorageRanges(_) => "snap:GetStorageRanges".fmt(f),
@@ -189,3 +250,38 @@ impl Display for Message {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rlpx::p2p::PingMessage;
+    use ethrex_rlp::decode::RLPDecode;
+
+    fn encoded_id(message: &Message, eth_version: u8) -> u8 {
+        let mut buf = vec![];
+        message.encode(&mut buf, eth_version).unwrap();
+        u8::decode_unfinished(&buf).unwrap().0
+    }
+
+    #[test]
+    fn snap_messages_start_after_the_eth_messages_of_the_negotiated_version() {
+        let ping = Message::Ping(PingMessage::new());
+        assert_eq!(encoded_id(&ping, 69), 0x02);
+        let get_trie_nodes = Message::GetTrieNodes(GetTrieNodes {
+            id: 1,
+            root_hash: Default::default(),
+            paths: vec![],
+            bytes: 0,
+        });
+        assert_eq!(encoded_id(&get_trie_nodes, 68), 0x27);
+        assert_eq!(encoded_id(&get_trie_nodes, 69), 0x28);
+
+        let mut buf = vec![];
+        get_trie_nodes.encode(&mut buf, 69).unwrap();
+        let (msg_id, msg_data): (u8, _) = RLPDecode::decode_unfinished(&buf).unwrap();
+        assert!(matches!(
+            Message::decode(msg_id, msg_data, 69).unwrap(),
+            Message::GetTrieNodes(_)
+        ));
+    }
+}
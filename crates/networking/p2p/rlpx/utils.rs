@@ -1,10 +1,15 @@
 use ethrex_core::H512;
-use ethrex_rlp::error::{RLPDecodeError, RLPEncodeError};
+use ethrex_rlp::{
+    decode::decode_rlp_item,
+    error::{RLPDecodeError, RLPEncodeError},
+};
 use k256::{
     elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint},
     EncodedPoint, PublicKey, SecretKey,
 };
-use snap::raw::{max_compress_len, Decoder as SnappyDecoder, Encoder as SnappyEncoder};
+use snap::raw::{
+    decompress_len, max_compress_len, Decoder as SnappyDecoder, Encoder as SnappyEncoder,
+};
 
 pub fn sha256(data: &[u8]) -> [u8; 32] {
     use k256::sha2::Digest;
@@ -59,9 +64,62 @@ pub fn snappy_compress(encoded_data: Vec<u8>) -> Result<Vec<u8>, RLPEncodeError>
     Ok(msg_data)
 }
 
+/// Maximum size of a message, both for the received frame and the decompressed payload.
+/// Same limit used by geth for the eth protocol.
+pub const MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
+/// Maximum nesting of lists allowed in a message, no devp2p message comes close to it
+pub const MAX_RLP_DEPTH: usize = 16;
+/// Maximum amount of RLP items, at any depth, allowed in a message.
+/// Each item is decoded into a value that takes more memory than its encoding, so this
+/// bounds how much memory a single message can make us allocate.
+pub const MAX_RLP_ELEMENTS: usize = 1 << 20;
+
+/// Decompresses the payload of a message, checking it is not bigger than [MAX_MESSAGE_SIZE]
+/// and it is within the RLP decoding budget before it is decoded into typed values
 pub fn snappy_decompress(msg_data: &[u8]) -> Result<Vec<u8>, RLPDecodeError> {
+    // The length is read from the snappy header, so a small message can't make us
+    // allocate a big buffer
+    let decompressed_len = decompress_len(msg_data)?;
+    if decompressed_len > MAX_MESSAGE_SIZE {
+        return Err(RLPDecodeError::Custom(format!(
+            "Decompressed message is too big: {decompressed_len} bytes"
+        )));
+    }
     let mut snappy_decoder = SnappyDecoder::new();
-    Ok(snappy_decoder.decompress_vec(msg_data)?)
+    let decompressed_data = snappy_decoder.decompress_vec(msg_data)?;
+    check_rlp_budget(&decompressed_data)?;
+    Ok(decompressed_data)
+}
+
+/// Walks the RLP structure of the given data without decoding it, checking it doesn't
+/// nest lists deeper than [MAX_RLP_DEPTH] or contain more than [MAX_RLP_ELEMENTS] items
+pub fn check_rlp_budget(data: &[u8]) -> Result<(), RLPDecodeError> {
+    let mut elements = 0;
+    // Remaining items of each list being walked, the last one is the innermost
+    let mut lists = vec![data];
+    while let Some(list) = lists.last_mut() {
+        if list.is_empty() {
+            lists.pop();
+            continue;
+        }
+        let (is_list, payload, rest) = decode_rlp_item(list)?;
+        *list = rest;
+        elements += 1;
+        if elements > MAX_RLP_ELEMENTS {
+            return Err(RLPDecodeError::Custom(format!(
+                "Message has more than {MAX_RLP_ELEMENTS} RLP items"
+            )));
+        }
+        if is_list {
+            if lists.len() > MAX_RLP_DEPTH {
+                return Err(RLPDecodeError::Custom(format!(
+                    "Message nests lists deeper than {MAX_RLP_DEPTH} levels"
+                )));
+            }
+            lists.push(payload);
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -93,4 +151,38 @@ mod tests {
         let id = pubkey2id(&pk);
         let _pk2 = id2pubkey(id).unwrap();
     }
+
+    /// Wraps the given items in an RLP list
+    fn rlp_list(payload: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![];
+        ethrex_rlp::structs::Encoder::new(&mut encoded)
+            .encode_raw(payload)
+            .finish();
+        encoded
+    }
+
+    #[test]
+    fn check_rlp_budget_rejects_deep_nesting() {
+        let mut data = vec![];
+        for _ in 0..MAX_RLP_DEPTH {
+            data = rlp_list(&data);
+        }
+        assert!(check_rlp_budget(&data).is_ok());
+        assert!(check_rlp_budget(&rlp_list(&data)).is_err());
+    }
+
+    #[test]
+    fn check_rlp_budget_rejects_too_many_elements() {
+        let data = rlp_list(&vec![0x80; MAX_RLP_ELEMENTS - 1]);
+        assert!(check_rlp_budget(&data).is_ok());
+        let data = rlp_list(&vec![0x80; MAX_RLP_ELEMENTS]);
+        assert!(check_rlp_budget(&data).is_err());
+    }
+
+    #[test]
+    fn snappy_decompress_rejects_oversized_messages() {
+        let data = rlp_list(&vec![0x01; MAX_MESSAGE_SIZE]);
+        let compressed = snappy_compress(data).unwrap();
+        assert!(snappy_decompress(&compressed).is_err());
+    }
 }
@@ -0,0 +1,180 @@
+use ethrex_blockchain::error::ChainError;
+use ethrex_core::{
+    errors::{CodedError, ErrorCode},
+    types::{Block, BlockHash, BlockNumber},
+    H256,
+};
+use ethrex_rlp::decode::RLPDecode;
+use ethrex_storage::{error::StoreError, Store};
+use serde_json::{json, Value};
+use tracing::{debug, info};
+
+use crate::sync::SyncStatus;
+
+/// Trusted execution RPC endpoint blocks are downloaded from instead of the p2p network
+#[derive(Debug, Clone)]
+pub struct RpcBackfillSource {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RpcBackfillError {
+    #[error("Request to the backfill RPC failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Backfill RPC returned an error: {0}")]
+    Rpc(String),
+    #[error("Invalid response from the backfill RPC: {0}")]
+    InvalidResponse(String),
+    #[error("Block {0} does not extend the current chain")]
+    InvalidChain(BlockNumber),
+    #[error("Block {0} was not found in the backfill RPC")]
+    NotFound(String),
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+impl CodedError for RpcBackfillError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            // The endpoint may be unreachable or lagging behind for a while
+            RpcBackfillError::Request(_)
+            | RpcBackfillError::Rpc(_)
+            | RpcBackfillError::NotFound(_) => ErrorCode::Unavailable,
+            RpcBackfillError::InvalidResponse(_) | RpcBackfillError::InvalidChain(_) => {
+                ErrorCode::Invalid
+            }
+            RpcBackfillError::Chain(error) => error.code(),
+            RpcBackfillError::Store(error) => error.code(),
+        }
+    }
+}
+
+impl RpcBackfillSource {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, RpcBackfillError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let mut response: Value = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await?
+            .json()
+            .await?;
+        if let Some(error) = response.get("error") {
+            return Err(RpcBackfillError::Rpc(error.to_string()));
+        }
+        Ok(response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or_default())
+    }
+
+    /// Returns the number of the block with the given hash
+    pub async fn get_block_number(&self, hash: BlockHash) -> Result<BlockNumber, RpcBackfillError> {
+        let block = self
+            .request("eth_getBlockByHash", json!([hash, false]))
+            .await?;
+        if block.is_null() {
+            return Err(RpcBackfillError::NotFound(format!("{hash:#x}")));
+        }
+        let number = block.get("number").and_then(Value::as_str).ok_or(
+            RpcBackfillError::InvalidResponse("Missing block number".to_string()),
+        )?;
+        u64::from_str_radix(number.trim_start_matches("0x"), 16)
+            .map_err(|error| RpcBackfillError::InvalidResponse(error.to_string()))
+    }
+
+    /// Downloads the block with the given number, as returned by `debug_getRawBlock`
+    pub async fn get_block(&self, number: BlockNumber) -> Result<Block, RpcBackfillError> {
+        let raw_block = self
+            .request("debug_getRawBlock", json!([format!("{number:#x}")]))
+            .await?;
+        let raw_block = raw_block
+            .as_str()
+            .ok_or(RpcBackfillError::NotFound(number.to_string()))?;
+        decode_raw_block(raw_block)
+    }
+}
+
+/// Decodes a hex encoded RLP block
+fn decode_raw_block(raw_block: &str) -> Result<Block, RpcBackfillError> {
+    let bytes = hex::decode(raw_block.trim_start_matches("0x"))
+        .map_err(|error| RpcBackfillError::InvalidResponse(error.to_string()))?;
+    Block::decode(&bytes).map_err(|error| RpcBackfillError::InvalidResponse(error.to_string()))
+}
+
+/// Downloads all blocks between the current head and the sync head from the trusted RPC,
+/// executing and storing them in order.
+/// Each block must be a child of the previous one and the last one must be the sync head,
+/// besides going through the same validations as blocks received from peers.
+pub(crate) async fn backfill_blocks(
+    source: &RpcBackfillSource,
+    current_head: H256,
+    sync_head: H256,
+    store: &Store,
+    status: &SyncStatus,
+) -> Result<(), RpcBackfillError> {
+    let current_number = store
+        .get_block_header_by_hash(current_head)?
+        .ok_or(RpcBackfillError::NotFound(format!("{current_head:#x}")))?
+        .number;
+    let sync_head_number = source.get_block_number(sync_head).await?;
+    status.update(|progress| progress.highest_block = sync_head_number);
+    info!(
+        "Backfilling blocks {} to {sync_head_number} from {}",
+        current_number + 1,
+        source.url()
+    );
+    let mut parent_hash = current_head;
+    for number in current_number + 1..=sync_head_number {
+        let block = source.get_block(number).await?;
+        let hash = block.hash();
+        if block.header.number != number
+            || block.header.parent_hash != parent_hash
+            || (number == sync_head_number && hash != sync_head)
+        {
+            return Err(RpcBackfillError::InvalidChain(number));
+        }
+        ethrex_blockchain::add_block(&block, store)?;
+        store.set_canonical_block(number, hash)?;
+        store.update_latest_block_number(number)?;
+        status.update(|progress| progress.current_block = number);
+        debug!("Backfilled block {number} with hash {hash:#x}");
+        parent_hash = hash;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethrex_core::types::BlockBody;
+    use ethrex_rlp::encode::RLPEncode;
+
+    #[test]
+    fn decode_raw_block_roundtrip() {
+        let block = Block::new(Default::default(), BlockBody::default());
+        let raw_block = format!("0x{}", hex::encode(block.encode_to_vec()));
+        assert_eq!(decode_raw_block(&raw_block).unwrap().hash(), block.hash());
+        assert!(decode_raw_block("0x1234").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use bytes::Bytes;
+use ethrex_core::{types::AccountState, H256, U256};
 use ethrex_rlp::encode::RLPEncode;
 use ethrex_storage::{error::StoreError, Store};
 
@@ -18,12 +19,16 @@ pub fn process_account_range_request(
 ) -> Result<AccountRange, StoreError> {
     let mut accounts = vec![];
     let mut bytes_used = 0;
-    for (hash, account) in store.iter_accounts(request.root_hash) {
-        if hash >= request.starting_hash {
-            let account = AccountStateSlim::from(account);
-            bytes_used += 32 + account.length() as u64;
-            accounts.push(AccountRangeUnit { hash, account });
-        }
+    // Ranges of the state held by the snapshot are read from it instead of the trie
+    let accounts_iter: Box<dyn Iterator<Item = (H256, AccountState)>> =
+        match store.iter_snapshot_accounts_from(request.root_hash, request.starting_hash)? {
+            Some(iter) => Box::new(iter),
+            None => Box::new(store.iter_accounts_from(request.root_hash, request.starting_hash)?),
+        };
+    for (hash, account) in accounts_iter {
+        let account = AccountStateSlim::from(account);
+        bytes_used += 32 + account.length() as u64;
+        accounts.push(AccountRangeUnit { hash, account });
         if hash >= request.limit_hash || bytes_used >= request.response_bytes {
             break;
         }
@@ -52,12 +57,18 @@ pub fn process_storage_ranges_request(
         let mut account_slots = vec![];
         let mut res_capped = false;
 
-        if let Some(storage_iter) = store.iter_storage(request.root_hash, hashed_address)? {
+        let storage_iter: Option<Box<dyn Iterator<Item = (H256, U256)>>> = match store
+            .iter_snapshot_storage_from(request.root_hash, hashed_address, request.starting_hash)?
+        {
+            Some(iter) => Some(Box::new(iter)),
+            None => store
+                .iter_storage_from(request.root_hash, hashed_address, request.starting_hash)?
+                .map(|iter| Box::new(iter) as Box<dyn Iterator<Item = (H256, U256)>>),
+        };
+        if let Some(storage_iter) = storage_iter {
             for (hash, data) in storage_iter {
-                if hash >= request.starting_hash {
-                    bytes_used += 64_u64; // slot size
-                    account_slots.push(StorageSlot { hash, data });
-                }
+                bytes_used += 64_u64; // slot size
+                account_slots.push(StorageSlot { hash, data });
                 if hash >= request.limit_hash || bytes_used >= request.response_bytes {
                     if bytes_used >= request.response_bytes {
                         res_capped = true;
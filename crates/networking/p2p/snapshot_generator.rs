@@ -0,0 +1,98 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use ethrex_storage::{error::StoreError, Store};
+use tracing::{info, warn};
+
+/// Time between the logs reporting the progress of the generation
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Generates the snapshot of the state in a background task once the node is synced, so it can
+/// serve snap requests from the snapshot. At most one generation runs at a time.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotGenerator {
+    running: Arc<AtomicBool>,
+}
+
+impl SnapshotGenerator {
+    /// Resumes the generation of a snapshot interrupted by a restart, if any
+    pub fn resume(&self, store: &Store) {
+        self.spawn(store, false)
+    }
+
+    /// Starts generating the snapshot of the latest state, unless the snapshot already follows
+    /// the canonical chain, and resumes it if its generation was interrupted
+    pub fn start(&self, store: &Store) {
+        self.spawn(store, true)
+    }
+
+    fn spawn(&self, store: &Store, start_new: bool) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let running = self.running.clone();
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(error) = generate_snapshot(&store, start_new) {
+                warn!("Snapshot generation failed: {error}");
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+fn generate_snapshot(store: &Store, start_new: bool) -> Result<(), StoreError> {
+    let status = match store.snapshot_status()? {
+        Some(status) if store.snapshot_follows_canonical_chain(&status)? => status,
+        _ if !start_new => return Ok(()),
+        // There is no snapshot, or the one stored was left on a side chain by a reorg
+        _ => {
+            let Some(latest) = store.get_latest_block_number()? else {
+                return Ok(());
+            };
+            let Some(header) = store.get_block_header(latest)? else {
+                return Ok(());
+            };
+            if !store.has_state(latest)? {
+                return Ok(());
+            }
+            store.start_snapshot(&header)?
+        }
+    };
+    if status.is_complete() {
+        return Ok(());
+    }
+    info!(
+        "Generating snapshot of block {}, {} accounts already generated",
+        status.block_number, status.accounts
+    );
+    let start_time = Instant::now();
+    let mut last_log = Instant::now();
+    while let Some(status) = store.generate_snapshot_batch()? {
+        if status.is_complete() {
+            info!(
+                "Snapshot generated at block {}: {} accounts and {} storage slots, time elapsed: {} secs",
+                status.block_number,
+                status.accounts,
+                status.storage_slots,
+                start_time.elapsed().as_secs()
+            );
+            break;
+        }
+        if last_log.elapsed() >= PROGRESS_LOG_INTERVAL {
+            last_log = Instant::now();
+            info!(
+                "Generating snapshot: {} accounts and {} storage slots, next account {:#x}",
+                status.accounts,
+                status.storage_slots,
+                status.generator_marker.unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
@@ -2,42 +2,251 @@ use std::sync::Arc;
 
 use ethrex_blockchain::error::ChainError;
 use ethrex_core::{
-    types::{Block, BlockHash, BlockHeader},
+    errors::{CodedError, ErrorCode},
+    types::{Block, BlockHash, BlockHeader, BlockNumber},
     H256,
 };
-use ethrex_storage::Store;
-use tokio::{sync::Mutex, time::Instant};
+use ethrex_storage::{error::StoreError, Store};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Mutex,
+    },
+    time::Instant,
+};
 use tracing::{debug, info, warn};
 
-use crate::kademlia::KademliaTable;
+use crate::{
+    kademlia::KademliaTable,
+    rlpx::p2p::Capability,
+    rpc_backfill::{backfill_blocks, RpcBackfillError, RpcBackfillSource},
+    snapshot_generator::SnapshotGenerator,
+};
 
 /// Manager in charge the sync process
 /// Only performs full-sync but will also be in charge of snap-sync in the future
+/// Amount of sync heads that can be queued while the syncer is busy, only the latest one is used
+pub const SYNC_HEADS_CAPACITY: usize = 64;
+/// Times a sync cycle is attempted when it fails with a retryable error
+pub const MAX_SYNC_ATTEMPTS: usize = 3;
+
+/// How the node downloads the blocks it is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncStrategy {
+    #[default]
+    Full,
+    Snap,
+    RpcBackfill,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+    #[error(transparent)]
+    RpcBackfill(#[from] RpcBackfillError),
+    #[error("Sync task stopped: {0}")]
+    Task(String),
+}
+
+impl CodedError for SyncError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            SyncError::Chain(error) => error.code(),
+            SyncError::RpcBackfill(error) => error.code(),
+            SyncError::Task(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Progress of a sync cycle, as reported by `eth_syncing`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncProgress {
+    /// Latest block when the cycle started
+    pub starting_block: BlockNumber,
+    /// Latest block imported by the cycle
+    pub current_block: BlockNumber,
+    /// Highest block known to be part of the chain being synced
+    pub highest_block: BlockNumber,
+    /// Progress of the state download, only updated by snap sync
+    pub snap: SnapSyncProgress,
+}
+
+/// Counters of the state downloaded and healed by snap sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapSyncProgress {
+    pub synced_accounts: u64,
+    pub synced_storage_slots: u64,
+    pub healed_trie_nodes: u64,
+    /// Trie nodes known to be missing that are yet to be healed
+    pub pending_trie_nodes: u64,
+}
+
+/// Progress of the sync cycle in progress, updated by the syncer and read by the RPC.
+/// Holds nothing while the node is not syncing.
+#[derive(Debug, Clone, Default)]
+pub struct SyncStatus(Arc<std::sync::Mutex<Option<SyncProgress>>>);
+
+impl SyncStatus {
+    /// Returns the progress of the sync cycle in progress, if any
+    pub fn progress(&self) -> Option<SyncProgress> {
+        *self.lock()
+    }
+
+    /// Marks the start of a sync cycle from the given block.
+    /// A cycle already in progress, such as one being retried, keeps its starting block.
+    pub fn start(&self, starting_block: BlockNumber) {
+        self.lock().get_or_insert(SyncProgress {
+            starting_block,
+            current_block: starting_block,
+            highest_block: starting_block,
+            snap: SnapSyncProgress::default(),
+        });
+    }
+
+    /// Updates the progress of the cycle in progress, nothing is done if there is none
+    pub fn update(&self, update: impl FnOnce(&mut SyncProgress)) {
+        if let Some(progress) = self.lock().as_mut() {
+            update(progress);
+        }
+    }
+
+    /// Marks the end of the sync cycle in progress
+    pub fn finish(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<SyncProgress>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
 #[derive(Debug)]
 pub struct SyncManager {
     // true: syncmode = snap, false = syncmode = full
-    #[allow(unused)]
     snap_mode: bool,
     peers: Arc<Mutex<KademliaTable>>,
+    /// If set, blocks are downloaded from this trusted RPC endpoint instead of from peers
+    rpc_backfill: Option<RpcBackfillSource>,
+    status: SyncStatus,
+    snapshot_generator: SnapshotGenerator,
 }
 
 impl SyncManager {
-    pub fn new(peers: Arc<Mutex<KademliaTable>>, snap_mode: bool) -> Self {
-        Self { snap_mode, peers }
+    pub fn new(
+        peers: Arc<Mutex<KademliaTable>>,
+        snap_mode: bool,
+        rpc_backfill: Option<RpcBackfillSource>,
+    ) -> Self {
+        Self {
+            snap_mode,
+            peers,
+            rpc_backfill,
+            status: SyncStatus::default(),
+            snapshot_generator: SnapshotGenerator::default(),
+        }
+    }
+
+    pub fn strategy(&self) -> SyncStrategy {
+        if self.rpc_backfill.is_some() {
+            SyncStrategy::RpcBackfill
+        } else if self.snap_mode {
+            SyncStrategy::Snap
+        } else {
+            SyncStrategy::Full
+        }
     }
 
-    /// Starts a sync cycle, updating the state with all blocks between the current head and the sync head
+    /// Runs sync cycles towards the sync heads received until all the handles are dropped.
+    /// A cycle failing with a retryable error is started again from the new current head, at most
+    /// [MAX_SYNC_ATTEMPTS] times, other failures abort the sync until the next sync head.
+    /// The snapshot of the state is generated in the background after each successful cycle.
+    async fn run(mut self, mut sync_heads: mpsc::Receiver<H256>, store: Store) {
+        self.snapshot_generator.resume(&store);
+        while let Some(mut sync_head) = sync_heads.recv().await {
+            for attempt in 1..=MAX_SYNC_ATTEMPTS {
+                let current_head = match latest_canonical_hash(&store) {
+                    Ok(current_head) => current_head,
+                    Err(error) => {
+                        warn!("Failed to read the current head, sync skipped: {error}");
+                        break;
+                    }
+                };
+                let start_time = Instant::now();
+                match self
+                    .start_sync(current_head, &mut sync_head, store.clone(), &mut sync_heads)
+                    .await
+                {
+                    Ok(()) => {
+                        self.snapshot_generator.start(&store);
+                        break;
+                    }
+                    Err(error) if error.is_retryable() && attempt < MAX_SYNC_ATTEMPTS => warn!(
+                        "Sync failed due to {error} ({}), retrying, time elapsed: {} secs",
+                        error.code(),
+                        start_time.elapsed().as_secs()
+                    ),
+                    Err(error) => {
+                        warn!(
+                            "Sync failed due to {error} ({}), time elapsed: {} secs",
+                            error.code(),
+                            start_time.elapsed().as_secs()
+                        );
+                        break;
+                    }
+                }
+            }
+            self.status.finish();
+        }
+    }
+
+    /// Starts a sync cycle, updating the state with all blocks between the current head and the sync head.
+    /// Sync heads received while the headers are fetched replace the sync head.
     /// TODO: only uses full sync, should also process snap sync once implemented
-    pub async fn start_sync(&mut self, mut current_head: H256, sync_head: H256, store: Store) {
+    async fn start_sync(
+        &mut self,
+        mut current_head: H256,
+        sync_head: &mut H256,
+        store: Store,
+        sync_heads: &mut mpsc::Receiver<H256>,
+    ) -> Result<(), SyncError> {
+        update_sync_head(sync_head, sync_heads);
+        // The head may have been queued again while the previous cycle was syncing to it
+        if let Ok(Some(_)) = store.get_block_number(*sync_head) {
+            debug!("Sync head {sync_head} is already stored, sync skipped");
+            return Ok(());
+        }
         info!("Syncing from current head {current_head} to sync_head {sync_head}");
+        if let Some(header) = store
+            .get_block_header_by_hash(current_head)
+            .map_err(ChainError::from)?
+        {
+            self.status.start(header.number);
+        }
         let start_time = Instant::now();
+        if let Some(source) = &self.rpc_backfill {
+            backfill_blocks(source, current_head, *sync_head, &store, &self.status).await?;
+            info!(
+                "RPC backfill finished, time elapsed: {} secs",
+                start_time.elapsed().as_secs()
+            );
+            return Ok(());
+        }
         // Request all block headers between the current head and the sync head
         // We will begin from the current head so that we download the earliest state first
         // This step is not parallelized
         let mut all_block_headers = vec![];
         let mut all_block_hashes = vec![];
         loop {
-            let peer = self.peers.lock().await.get_peer_channels().await;
+            update_sync_head(sync_head, sync_heads);
+            let peer = self
+                .peers
+                .lock()
+                .await
+                .get_peer_channels(Capability::Eth)
+                .await;
             debug!("Requesting Block Headers from {current_head}");
             // Request Block Headers from Peer
             if let Some(block_headers) = peer.request_block_headers(current_head).await {
@@ -50,9 +259,14 @@ impl SyncManager {
                 // Discard the first header as we already have it
                 all_block_headers.extend_from_slice(&block_headers[1..]);
                 all_block_hashes.extend_from_slice(&block_hashes[1..]);
+                if let Some(last_header) = block_headers.last() {
+                    self.status.update(|progress| {
+                        progress.highest_block = progress.highest_block.max(last_header.number)
+                    });
+                }
 
                 // Check if we already reached our sync head or if we need to fetch more blocks
-                if !block_hashes.contains(&sync_head) {
+                if !block_hashes.contains(sync_head) {
                     // Update the request to fetch the next batch
                     current_head = *block_hashes.last().unwrap();
                 } else {
@@ -64,27 +278,20 @@ impl SyncManager {
         // We finished fetching all headers, now we can process them
         // TODO: snap-sync: launch tasks to fetch blocks and state in parallel
         // full-sync: Fetch all block bodies and execute them sequentially to build the state
-        match tokio::spawn(download_and_run_blocks(
+        tokio::spawn(download_and_run_blocks(
             all_block_hashes,
             all_block_headers,
             self.peers.clone(),
             store.clone(),
+            self.status.clone(),
         ))
         .await
-        {
-            Ok(Ok(())) => info!(
-                "Sync finished, time elapsed: {} secs",
-                start_time.elapsed().as_secs()
-            ),
-            Ok(Err(error)) => warn!(
-                "Sync failed due to {error}, time elapsed: {} secs ",
-                start_time.elapsed().as_secs()
-            ),
-            _ => warn!(
-                "Sync failed due to internal error, time elapsed: {} secs",
-                start_time.elapsed().as_secs()
-            ),
-        }
+        .map_err(|error| SyncError::Task(error.to_string()))??;
+        info!(
+            "Sync finished, time elapsed: {} secs",
+            start_time.elapsed().as_secs()
+        );
+        Ok(())
     }
 
     /// Creates a dummy SyncManager for tests where syncing is not needed
@@ -94,10 +301,86 @@ impl SyncManager {
         Self {
             snap_mode: false,
             peers: dummy_peer_table,
+            rpc_backfill: None,
+            status: SyncStatus::default(),
+            snapshot_generator: SnapshotGenerator::default(),
         }
     }
 }
 
+/// Handle to the task running the sync cycles, sync heads sent through it are
+/// picked up by the cycle in progress or by the next one
+#[derive(Debug, Clone)]
+pub struct SyncHandle {
+    sync_heads: mpsc::Sender<H256>,
+    strategy: SyncStrategy,
+    status: SyncStatus,
+}
+
+impl SyncHandle {
+    /// Spawns the task running the sync cycles of the syncer
+    pub fn spawn(syncer: SyncManager, store: Store) -> Self {
+        let (sync_heads, receiver) = mpsc::channel(SYNC_HEADS_CAPACITY);
+        let strategy = syncer.strategy();
+        let status = syncer.status.clone();
+        tokio::spawn(syncer.run(receiver, store));
+        Self {
+            sync_heads,
+            strategy,
+            status,
+        }
+    }
+
+    /// Asks the syncer to sync up to the given head
+    pub fn sync_to(&self, sync_head: H256) {
+        match self.sync_heads.try_send(sync_head) {
+            Ok(()) => {}
+            // The queued heads will be synced first, the consensus client will send newer ones
+            Err(TrySendError::Full(_)) => debug!("Sync heads queue is full, {sync_head} dropped"),
+            Err(TrySendError::Closed(_)) => warn!("Syncer is not running, {sync_head} dropped"),
+        }
+    }
+
+    pub fn strategy(&self) -> SyncStrategy {
+        self.strategy
+    }
+
+    pub fn status(&self) -> &SyncStatus {
+        &self.status
+    }
+
+    /// Creates a handle without a syncer for tests where syncing is not needed
+    pub fn dummy() -> Self {
+        let (sync_heads, _) = mpsc::channel(1);
+        Self {
+            sync_heads,
+            strategy: SyncStrategy::Full,
+            status: SyncStatus::default(),
+        }
+    }
+}
+
+/// Replaces the sync head with the latest one received, if any
+fn update_sync_head(sync_head: &mut H256, sync_heads: &mut mpsc::Receiver<H256>) {
+    while let Ok(newer_head) = sync_heads.try_recv() {
+        if newer_head != *sync_head {
+            debug!("Sync head updated to {newer_head}");
+            *sync_head = newer_head;
+        }
+    }
+}
+
+fn latest_canonical_hash(store: &Store) -> Result<BlockHash, StoreError> {
+    let latest_number = store.get_latest_block_number()?.ok_or(StoreError::Custom(
+        "Missing latest block number".to_string(),
+    ))?;
+    store
+        .get_canonical_block_hash(latest_number)?
+        .ok_or(StoreError::Custom(
+            "Missing latest canonical block".to_string(),
+        ))
+}
+
 /// Requests block bodies from peers via p2p, executes and stores them
 /// Returns an error if there was a problem while executing or validating the blocks
 async fn download_and_run_blocks(
@@ -105,9 +388,10 @@ async fn download_and_run_blocks(
     mut block_headers: Vec<BlockHeader>,
     peers: Arc<Mutex<KademliaTable>>,
     store: Store,
+    status: SyncStatus,
 ) -> Result<(), ChainError> {
     loop {
-        let peer = peers.lock().await.get_peer_channels().await;
+        let peer = peers.lock().await.get_peer_channels(Capability::Eth).await;
         debug!("Requesting Block Bodies ");
         if let Some(block_bodies) = peer.request_block_bodies(block_hashes.clone()).await {
             let block_bodies_len = block_bodies.len();
@@ -125,6 +409,7 @@ async fn download_and_run_blocks(
                 }
                 store.set_canonical_block(number, hash)?;
                 store.update_latest_block_number(number)?;
+                status.update(|progress| progress.current_block = number);
             }
             debug!("Executed & stored {} blocks", block_bodies_len);
             // Check if we need to ask for another batch
@@ -135,3 +420,48 @@ async fn download_and_run_blocks(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_head_is_replaced_by_the_latest_one_received() {
+        let (sender, mut receiver) = mpsc::channel(SYNC_HEADS_CAPACITY);
+        let mut sync_head = H256::repeat_byte(1);
+        update_sync_head(&mut sync_head, &mut receiver);
+        assert_eq!(sync_head, H256::repeat_byte(1));
+
+        sender.try_send(H256::repeat_byte(2)).unwrap();
+        sender.try_send(H256::repeat_byte(3)).unwrap();
+        update_sync_head(&mut sync_head, &mut receiver);
+        assert_eq!(sync_head, H256::repeat_byte(3));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn sync_status_keeps_the_starting_block_until_finished() {
+        let status = SyncStatus::default();
+        status.update(|progress| progress.current_block = 5);
+        assert_eq!(status.progress(), None);
+
+        status.start(10);
+        status.update(|progress| {
+            progress.current_block = 12;
+            progress.highest_block = 20;
+        });
+        // A retried cycle starts again from the current head
+        status.start(12);
+        let progress = status.progress().unwrap();
+        assert_eq!(
+            (
+                progress.starting_block,
+                progress.current_block,
+                progress.highest_block
+            ),
+            (10, 12, 20)
+        );
+        status.finish();
+        assert_eq!(status.progress(), None);
+    }
+}
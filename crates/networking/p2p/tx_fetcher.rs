@@ -0,0 +1,293 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ethrex_core::{H256, H512};
+use tokio::sync::{mpsc, Mutex};
+use tracing::debug;
+
+use crate::{kademlia::KademliaTable, rlpx::eth::transactions::GetPooledTransactions, RLPxMessage};
+
+/// Maximum amount of transactions requested from a single peer that can be awaiting a reply
+pub const MAX_PEER_IN_FLIGHT_HASHES: usize = 256;
+/// Maximum amount of announced hashes kept per peer waiting to be requested
+pub const MAX_PEER_QUEUED_HASHES: usize = 4096;
+/// Time a peer has to deliver a requested transaction before it can be requested from another one
+pub const TX_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time during which delivered transactions are not requested again
+pub const RECENTLY_SEEN_WINDOW: Duration = Duration::from_secs(60);
+/// Maximum amount of events waiting to be processed by the fetcher
+pub const MAX_TX_FETCHER_EVENTS: usize = 1024;
+const TX_FETCHER_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Events reported by the peer connections to the transaction fetcher
+#[derive(Debug)]
+pub(crate) enum TxFetcherEvent {
+    /// The peer announced transactions that are not in the mempool
+    Announced(H512, Vec<H256>),
+    /// The peer delivered the transactions with the given hashes
+    Delivered(H512, Vec<H256>),
+    /// The connection with the peer was closed
+    Disconnected(H512),
+}
+
+/// Sending end of the channel the peer connections report events to the fetcher through
+#[derive(Debug, Clone)]
+pub(crate) struct TxFetcherHandle(mpsc::Sender<TxFetcherEvent>);
+
+impl TxFetcherHandle {
+    pub fn create() -> (Self, mpsc::Receiver<TxFetcherEvent>) {
+        let (sender, receiver) = mpsc::channel(MAX_TX_FETCHER_EVENTS);
+        (Self(sender), receiver)
+    }
+
+    /// Reports an event to the fetcher. Events are dropped if the fetcher is lagging behind,
+    /// as announcements are only a hint and hashes can be announced again by other peers.
+    pub fn notify(&self, event: TxFetcherEvent) {
+        if let Err(error) = self.0.try_send(event) {
+            debug!("Dropped transaction fetcher event: {error}");
+        }
+    }
+}
+
+/// Schedules the requests of announced transactions, so each one is only requested from a
+/// single peer at a time, no peer has too many requests awaiting a reply, and transactions
+/// that were recently delivered are not requested again.
+/// A transaction announced by many peers is requested from the next one if the first one
+/// doesn't deliver it in time.
+#[derive(Debug, Default)]
+pub(crate) struct TxFetcher {
+    peers: HashMap<H512, PeerFetchState>,
+    /// Peer each hash awaiting a reply was requested from
+    in_flight: HashMap<H256, H512>,
+    /// Time at which each recently delivered transaction was received
+    recently_seen: HashMap<H256, Instant>,
+}
+
+#[derive(Debug, Default)]
+struct PeerFetchState {
+    /// Time at which each hash awaiting a reply was requested
+    in_flight: HashMap<H256, Instant>,
+    /// Announced hashes that were not requested from this peer yet
+    queued: VecDeque<H256>,
+    queued_set: HashSet<H256>,
+}
+
+impl TxFetcher {
+    /// Queues the hashes announced by the peer, skipping recently delivered ones
+    pub fn announced(&mut self, peer: H512, hashes: Vec<H256>) {
+        let state = self.peers.entry(peer).or_default();
+        for hash in hashes {
+            if state.queued.len() >= MAX_PEER_QUEUED_HASHES {
+                debug!("Too many queued transaction announcements from peer {peer}");
+                break;
+            }
+            if self.recently_seen.contains_key(&hash)
+                || state.in_flight.contains_key(&hash)
+                || !state.queued_set.insert(hash)
+            {
+                continue;
+            }
+            state.queued.push_back(hash);
+        }
+    }
+
+    /// Marks the transactions as received, so they are not requested again for a while
+    pub fn delivered(&mut self, peer: H512, hashes: Vec<H256>, now: Instant) {
+        for hash in hashes {
+            if let Some(state) = self.peers.get_mut(&peer) {
+                state.in_flight.remove(&hash);
+            }
+            if let Some(requested_from) = self.in_flight.remove(&hash) {
+                if requested_from != peer {
+                    if let Some(state) = self.peers.get_mut(&requested_from) {
+                        state.in_flight.remove(&hash);
+                    }
+                }
+            }
+            self.recently_seen.insert(hash, now);
+        }
+    }
+
+    /// Drops all the state of the peer, its requests awaiting a reply can be sent to other peers
+    pub fn disconnected(&mut self, peer: H512) {
+        if let Some(state) = self.peers.remove(&peer) {
+            for hash in state.in_flight.keys() {
+                self.in_flight.remove(hash);
+            }
+        }
+    }
+
+    pub fn handle(&mut self, event: TxFetcherEvent, now: Instant) {
+        match event {
+            TxFetcherEvent::Announced(peer, hashes) => self.announced(peer, hashes),
+            TxFetcherEvent::Delivered(peer, hashes) => self.delivered(peer, hashes, now),
+            TxFetcherEvent::Disconnected(peer) => self.disconnected(peer),
+        }
+    }
+
+    /// Releases the requests that were not replied in time and forgets old deliveries
+    pub fn expire(&mut self, now: Instant) {
+        for state in self.peers.values_mut() {
+            state.in_flight.retain(|hash, requested_at| {
+                let expired = now.duration_since(*requested_at) >= TX_FETCH_TIMEOUT;
+                if expired {
+                    self.in_flight.remove(hash);
+                }
+                !expired
+            });
+        }
+        self.recently_seen
+            .retain(|_, seen_at| now.duration_since(*seen_at) < RECENTLY_SEEN_WINDOW);
+    }
+
+    /// Returns the hashes that should be requested from each peer now, within their in-flight limit.
+    /// Hashes already requested from other peers are kept queued in case those don't deliver them.
+    pub fn schedule(&mut self, now: Instant) -> Vec<(H512, Vec<H256>)> {
+        let mut requests = vec![];
+        for (peer, state) in self.peers.iter_mut() {
+            let mut hashes = vec![];
+            let mut waiting = VecDeque::new();
+            while state.in_flight.len() < MAX_PEER_IN_FLIGHT_HASHES {
+                let Some(hash) = state.queued.pop_front() else {
+                    break;
+                };
+                if self.recently_seen.contains_key(&hash) {
+                    state.queued_set.remove(&hash);
+                } else if let Entry::Vacant(entry) = self.in_flight.entry(hash) {
+                    entry.insert(*peer);
+                    state.queued_set.remove(&hash);
+                    state.in_flight.insert(hash, now);
+                    hashes.push(hash);
+                } else {
+                    waiting.push_back(hash);
+                }
+            }
+            waiting.append(&mut state.queued);
+            state.queued = waiting;
+            if !hashes.is_empty() {
+                requests.push((*peer, hashes));
+            }
+        }
+        requests
+    }
+}
+
+/// Runs the transaction fetcher, processing the events reported by the peer connections and
+/// sending the scheduled requests to the peers through their connection channels
+pub(crate) async fn run_tx_fetcher(
+    mut events: mpsc::Receiver<TxFetcherEvent>,
+    table: Arc<Mutex<KademliaTable>>,
+) {
+    let mut fetcher = TxFetcher::default();
+    let mut interval = tokio::time::interval(TX_FETCHER_TICK_INTERVAL);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => fetcher.handle(event, Instant::now()),
+                None => return,
+            },
+            _ = interval.tick() => fetcher.expire(Instant::now()),
+        }
+        for (peer, hashes) in fetcher.schedule(Instant::now()) {
+            let channels = table
+                .lock()
+                .await
+                .get_by_node_id(peer)
+                .and_then(|peer| peer.channels.clone());
+            let Some(channels) = channels else {
+                fetcher.disconnected(peer);
+                continue;
+            };
+            let request = GetPooledTransactions::new(rand::random(), hashes);
+            if channels
+                .send(RLPxMessage::GetPooledTransactions(request))
+                .await
+                .is_err()
+            {
+                fetcher.disconnected(peer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_are_requested_from_a_single_peer() {
+        let (peer_a, peer_b) = (H512::random(), H512::random());
+        let hash = H256::random();
+        let now = Instant::now();
+        let mut fetcher = TxFetcher::default();
+        fetcher.announced(peer_a, vec![hash]);
+        fetcher.announced(peer_b, vec![hash]);
+        let requests = fetcher.schedule(now);
+        assert_eq!(requests.len(), 1);
+        let (first_peer, hashes) = &requests[0];
+        assert_eq!(hashes, &vec![hash]);
+        assert!(fetcher.schedule(now).is_empty());
+
+        // Once the request times out the hash is requested from the other peer
+        fetcher.expire(now + TX_FETCH_TIMEOUT);
+        let requests = fetcher.schedule(now + TX_FETCH_TIMEOUT);
+        assert_eq!(requests.len(), 1);
+        assert_ne!(&requests[0].0, first_peer);
+    }
+
+    #[test]
+    fn delivered_hashes_are_not_refetched() {
+        let peer = H512::random();
+        let hash = H256::random();
+        let now = Instant::now();
+        let mut fetcher = TxFetcher::default();
+        fetcher.announced(peer, vec![hash]);
+        assert_eq!(fetcher.schedule(now).len(), 1);
+        fetcher.delivered(peer, vec![hash], now);
+
+        fetcher.announced(peer, vec![hash]);
+        fetcher.announced(H512::random(), vec![hash]);
+        assert!(fetcher.schedule(now).is_empty());
+
+        // After the window the hash can be requested again
+        fetcher.expire(now + RECENTLY_SEEN_WINDOW);
+        fetcher.announced(peer, vec![hash]);
+        assert_eq!(fetcher.schedule(now + RECENTLY_SEEN_WINDOW).len(), 1);
+    }
+
+    #[test]
+    fn in_flight_requests_are_capped_per_peer() {
+        let peer = H512::random();
+        let now = Instant::now();
+        let mut fetcher = TxFetcher::default();
+        let hashes: Vec<H256> = (0..MAX_PEER_IN_FLIGHT_HASHES + 10)
+            .map(|_| H256::random())
+            .collect();
+        fetcher.announced(peer, hashes.clone());
+        let requests = fetcher.schedule(now);
+        assert_eq!(requests[0].1.len(), MAX_PEER_IN_FLIGHT_HASHES);
+        assert!(fetcher.schedule(now).is_empty());
+
+        fetcher.delivered(peer, hashes[..10].to_vec(), now);
+        let requests = fetcher.schedule(now);
+        assert_eq!(requests[0].1, hashes[MAX_PEER_IN_FLIGHT_HASHES..].to_vec());
+    }
+
+    #[test]
+    fn disconnected_peer_requests_are_released() {
+        let (peer_a, peer_b) = (H512::random(), H512::random());
+        let hash = H256::random();
+        let now = Instant::now();
+        let mut fetcher = TxFetcher::default();
+        fetcher.announced(peer_a, vec![hash]);
+        assert_eq!(fetcher.schedule(now).len(), 1);
+        fetcher.announced(peer_b, vec![hash]);
+        assert!(fetcher.schedule(now).is_empty());
+
+        fetcher.disconnected(peer_a);
+        assert_eq!(fetcher.schedule(now), vec![(peer_b, vec![hash])]);
+    }
+}
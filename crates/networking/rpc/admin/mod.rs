@@ -1,11 +1,12 @@
-use ethrex_core::types::ChainConfig;
+use ethrex_core::types::{ChainConfig, ForkSchedule};
 use ethrex_net::types::Node;
 use ethrex_storage::Store;
 use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use tracing::info;
 
-use crate::utils::RpcErr;
+use crate::{utils::RpcErr, RpcApiContext, RpcHandler};
 
 #[derive(Serialize, Debug)]
 struct NodeInfo {
@@ -51,3 +52,46 @@ pub fn node_info(storage: Store, local_node: Node) -> Result<Value, RpcErr> {
     };
     serde_json::to_value(node_info).map_err(|error| RpcErr::Internal(error.to_string()))
 }
+
+/// Changes the activation times of the forks that are not active yet, so a fork can be
+/// scheduled on a running custom network. Only served by the authenticated RPC.
+pub struct UpdateForkScheduleRequest {
+    pub schedule: ForkSchedule,
+}
+
+impl RpcHandler for UpdateForkScheduleRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 1 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one param and {} were provided",
+                params.len()
+            )));
+        };
+        Ok(UpdateForkScheduleRequest {
+            schedule: serde_json::from_value(params[0].clone())?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let storage = &context.storage;
+        let latest_number = storage.get_latest_block_number()?.ok_or(RpcErr::Internal(
+            "Could not get latest block number".to_owned(),
+        ))?;
+        let latest = storage
+            .get_block_header(latest_number)?
+            .ok_or(RpcErr::Internal(format!(
+                "Could not get header for block {latest_number}"
+            )))?;
+        let chain_config = storage
+            .get_chain_config()?
+            .reschedule_forks(self.schedule, latest.timestamp)
+            .map_err(|error| RpcErr::BadParams(error.to_string()))?;
+        storage.set_chain_config(&chain_config)?;
+        info!("Updated the fork schedule to {:?}", self.schedule);
+        serde_json::to_value(chain_config.fork_schedule())
+            .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
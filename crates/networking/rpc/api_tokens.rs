@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::authentication::AuthenticationError;
+
+/// Namespace of a token giving access to every namespace
+pub const ALL_NAMESPACES: &str = "*";
+
+/// Bearer token accepted by the HTTP RPC for the methods of some namespaces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub token: String,
+    /// Namespaces the token gives access to, such as `eth` or `debug`
+    pub namespaces: Vec<String>,
+}
+
+impl ApiToken {
+    pub fn allows(&self, namespace: &str) -> bool {
+        self.namespaces
+            .iter()
+            .any(|allowed| allowed == namespace || allowed == ALL_NAMESPACES)
+    }
+}
+
+/// Tokens required to call the HTTP RPC, so a public endpoint can give each consumer access to
+/// different namespaces, for example the `eth` namespace to every consumer and `debug` to a few
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ApiTokens {
+    tokens: Vec<ApiToken>,
+}
+
+impl ApiTokens {
+    /// Creates a random token giving access to the given namespaces and returns it
+    pub fn issue(&mut self, namespaces: Vec<String>) -> String {
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        self.tokens.push(ApiToken {
+            token: token.clone(),
+            namespaces,
+        });
+        token
+    }
+
+    /// Revokes the token, returning whether it existed
+    pub fn revoke(&mut self, token: &str) -> bool {
+        let len = self.tokens.len();
+        self.tokens.retain(|api_token| api_token.token != token);
+        self.tokens.len() != len
+    }
+
+    pub fn tokens(&self) -> &[ApiToken] {
+        &self.tokens
+    }
+
+    /// Checks that the token of the request gives access to the namespace of the called method
+    pub fn authorize(
+        &self,
+        token: Option<&str>,
+        namespace: &str,
+    ) -> Result<(), AuthenticationError> {
+        let token = token.ok_or(AuthenticationError::MissingAuthentication)?;
+        let api_token = self
+            .tokens
+            .iter()
+            .find(|api_token| constant_time_eq(api_token.token.as_bytes(), token.as_bytes()))
+            .ok_or(AuthenticationError::UnknownApiToken)?;
+        if !api_token.allows(namespace) {
+            return Err(AuthenticationError::NamespaceNotAllowed(
+                namespace.to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Compares the tokens without returning early, so the time taken doesn't reveal how much of a
+/// guessed token matches
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Reads the tokens stored in the given file, a missing file holds no tokens
+pub fn read_api_tokens(path: &Path) -> io::Result<ApiTokens> {
+    let encoded = match fs::read(path) {
+        Ok(encoded) => encoded,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(ApiTokens::default()),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_slice(&encoded).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Writes the tokens to the file, replacing its previous content.
+/// The tokens are written to a temporary file first so a crash never leaves a truncated file behind,
+/// only readable by its owner as the tokens are secrets.
+pub fn write_api_tokens(path: &Path, tokens: &ApiTokens) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(&serde_json::to_vec_pretty(tokens)?)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_only_give_access_to_their_namespaces() {
+        let mut tokens = ApiTokens::default();
+        let eth = tokens.issue(vec!["eth".to_owned(), "web3".to_owned()]);
+        let admin = tokens.issue(vec![ALL_NAMESPACES.to_owned()]);
+        assert!(tokens.authorize(Some(&eth), "eth").is_ok());
+        assert!(matches!(
+            tokens.authorize(Some(&eth), "debug"),
+            Err(AuthenticationError::NamespaceNotAllowed(namespace)) if namespace == "debug"
+        ));
+        assert!(tokens.authorize(Some(&admin), "debug").is_ok());
+        assert!(matches!(
+            tokens.authorize(None, "eth"),
+            Err(AuthenticationError::MissingAuthentication)
+        ));
+
+        let dir = std::env::temp_dir().join(format!("ethrex-api-tokens-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("api_tokens.json");
+        assert_eq!(read_api_tokens(&path).unwrap(), ApiTokens::default());
+        assert!(tokens.revoke(&admin));
+        write_api_tokens(&path, &tokens).unwrap();
+        let tokens = read_api_tokens(&path).unwrap();
+        assert!(matches!(
+            tokens.authorize(Some(&admin), "eth"),
+            Err(AuthenticationError::UnknownApiToken)
+        ));
+        assert!(tokens.authorize(Some(&eth), "web3").is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::RpcErr;
+use crate::{api_tokens::ApiTokens, RpcErr};
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
@@ -13,6 +13,9 @@ pub enum AuthenticationError {
     InvalidIssuedAtClaim,
     TokenDecodingError,
     MissingAuthentication,
+    UnknownApiToken,
+    /// The API token doesn't give access to the namespace
+    NamespaceNotAllowed(String),
 }
 
 pub fn authenticate(
@@ -30,6 +33,19 @@ pub fn authenticate(
     }
 }
 
+/// Checks that the bearer token of a request to the HTTP RPC gives access to the called method
+pub fn authorize_api_token(
+    api_tokens: &ApiTokens,
+    auth_header: Option<&TypedHeader<Authorization<Bearer>>>,
+    method: &str,
+) -> Result<(), RpcErr> {
+    let token = auth_header.map(|TypedHeader(auth_header)| auth_header.token());
+    let namespace = method.split('_').next().unwrap_or_default();
+    api_tokens
+        .authorize(token, namespace)
+        .map_err(RpcErr::AuthenticationError)
+}
+
 // JWT claims struct
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
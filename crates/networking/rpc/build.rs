@@ -0,0 +1,22 @@
+use std::{path::Path, process::Command};
+
+/// Embeds the commit the node is built from, reported by the client version endpoints
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=ETHREX_GIT_COMMIT={commit}");
+
+    // Only watch the git files that exist, a missing one would rerun the script on every build
+    for git_file in ["HEAD", "refs", "packed-refs"] {
+        let path = Path::new("../../../.git").join(git_file);
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
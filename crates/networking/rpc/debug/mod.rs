@@ -0,0 +1,2 @@
+pub(crate) mod receipts;
+pub(crate) mod trace;
@@ -0,0 +1,193 @@
+use ethrex_core::{
+    serde_utils,
+    types::{BlockNumber, Index},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    eth::block::get_all_block_rpc_receipts, types::block_identifier::BlockIdentifier,
+    types::receipt::RpcReceipt, utils::RpcErr, RpcApiContext, RpcHandler,
+};
+
+/// Maximum amount of receipts returned in a single page
+pub const MAX_RECEIPTS_RANGE_PAGE_SIZE: usize = 10_000;
+/// Maximum amount of blocks read to fill a single page, so ranges of empty blocks
+/// are also split in pages
+pub const MAX_RECEIPTS_RANGE_PAGE_BLOCKS: u64 = 1024;
+
+/// Exports the receipts of a range of canonical blocks in pages, each page returns the
+/// cursor the next one has to be requested from, so clients fetch at their own pace
+pub struct GetReceiptsRangeRequest {
+    pub from: BlockIdentifier,
+    pub to: BlockIdentifier,
+    pub cursor: Option<ReceiptsCursor>,
+}
+
+/// Position of the first receipt of a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptsCursor {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub block_number: BlockNumber,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub transaction_index: Index,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptsPage {
+    pub receipts: Vec<RpcReceipt>,
+    /// Not set once the whole range was exported
+    pub next_cursor: Option<ReceiptsCursor>,
+}
+
+impl RpcHandler for GetReceiptsRangeRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetReceiptsRangeRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 2 && params.len() != 3 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected two or three params and {} were provided",
+                params.len()
+            )));
+        };
+        let cursor = match params.get(2) {
+            Some(Value::Null) | None => None,
+            Some(cursor) => Some(
+                serde_json::from_value(cursor.clone())
+                    .map_err(|_| RpcErr::WrongParam("cursor".to_owned()))?,
+            ),
+        };
+        Ok(GetReceiptsRangeRequest {
+            from: BlockIdentifier::parse(params[0].clone(), 0)?,
+            to: BlockIdentifier::parse(params[1].clone(), 1)?,
+            cursor,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let storage = &context.storage;
+        let from = self
+            .from
+            .resolve_block_number(storage)?
+            .ok_or(RpcErr::WrongParam("fromBlock".to_owned()))?;
+        let to = self
+            .to
+            .resolve_block_number(storage)?
+            .ok_or(RpcErr::WrongParam("toBlock".to_owned()))?;
+        if from > to {
+            return Err(RpcErr::BadParams("Invalid block range".to_owned()));
+        }
+        let start = self.cursor.unwrap_or(ReceiptsCursor {
+            block_number: from,
+            transaction_index: 0,
+        });
+        if !(from..=to).contains(&start.block_number) {
+            return Err(RpcErr::BadParams(
+                "Cursor is outside of the block range".to_owned(),
+            ));
+        }
+        info!(
+            "Requested receipts of blocks {from} to {to} from block {} index {}",
+            start.block_number, start.transaction_index
+        );
+
+        let last = to.min(start.block_number + MAX_RECEIPTS_RANGE_PAGE_BLOCKS - 1);
+        let mut page = ReceiptsPage {
+            receipts: vec![],
+            next_cursor: None,
+        };
+        for number in start.block_number..=last {
+            let header = storage
+                .get_block_header(number)?
+                .ok_or(RpcErr::Internal(format!(
+                    "Could not get header for block {number}"
+                )))?;
+            let body = storage
+                .get_block_body(number)?
+                .ok_or(RpcErr::Internal(format!(
+                    "Could not get body for block {number}"
+                )))?;
+            // The receipts are built for the whole block so their log indexes are right
+            let skipped = if number == start.block_number {
+                start.transaction_index as usize
+            } else {
+                0
+            };
+            let receipts = get_all_block_rpc_receipts(header, body, storage)?;
+            let remaining = MAX_RECEIPTS_RANGE_PAGE_SIZE - page.receipts.len();
+            let receipts = receipts.into_iter().skip(skipped);
+            if receipts.len() > remaining {
+                page.receipts.extend(receipts.take(remaining));
+                page.next_cursor = Some(ReceiptsCursor {
+                    block_number: number,
+                    transaction_index: (skipped + remaining) as Index,
+                });
+                break;
+            }
+            page.receipts.extend(receipts);
+        }
+        if page.next_cursor.is_none() && last < to {
+            page.next_cursor = Some(ReceiptsCursor {
+                block_number: last + 1,
+                transaction_index: 0,
+            });
+        }
+        serde_json::to_value(page).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::context_with_genesis;
+
+    fn receipts_range(params: Value, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let params = serde_json::from_value(params).unwrap();
+        GetReceiptsRangeRequest::parse(&Some(params))?.handle(context)
+    }
+
+    #[test]
+    fn receipts_range_exports_the_whole_range() {
+        let page = receipts_range(serde_json::json!(["0x0", "latest"]), context_with_genesis());
+        assert_eq!(
+            page.unwrap(),
+            serde_json::json!({"receipts": [], "nextCursor": null})
+        );
+        let cursor = serde_json::json!({"blockNumber": "0x0", "transactionIndex": "0x0"});
+        let page = receipts_range(
+            serde_json::json!(["0x0", "0x0", cursor]),
+            context_with_genesis(),
+        );
+        assert_eq!(
+            page.unwrap(),
+            serde_json::json!({"receipts": [], "nextCursor": null})
+        );
+    }
+
+    #[test]
+    fn receipts_range_rejects_invalid_cursors() {
+        let cursor = serde_json::json!({"blockNumber": "0x1", "transactionIndex": "0x0"});
+        assert!(matches!(
+            receipts_range(
+                serde_json::json!(["0x0", "0x0", cursor]),
+                context_with_genesis()
+            ),
+            Err(RpcErr::BadParams(_))
+        ));
+        assert!(matches!(
+            receipts_range(
+                serde_json::json!(["0x0", "0x0", "0x1"]),
+                context_with_genesis()
+            ),
+            Err(RpcErr::WrongParam(_))
+        ));
+        assert!(matches!(
+            receipts_range(serde_json::json!(["0x1", "0x0"]), context_with_genesis()),
+            Err(RpcErr::BadParams(_))
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+use std::{convert::Infallible, ops::RangeInclusive, time::Duration};
+
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use ethrex_core::{
+    serde_utils,
+    types::{Block, BlockHash, BlockNumber},
+    H256,
+};
+use ethrex_storage::Store;
+use ethrex_vm::{evm_state, CallTrace, EvmError, TraceLimits};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{error, info};
+
+use crate::{
+    rpc_response,
+    types::block_identifier::BlockIdentifier,
+    utils::{RpcErr, RpcErrorMetadata, RpcRequest},
+    RpcApiContext, RpcHandler,
+};
+
+/// Maximum amount of blocks that can be traced in a single request
+pub const MAX_TRACE_CHAIN_RANGE: u64 = 10_000;
+/// Maximum amount of `debug_traceChain` requests that can be served at the same time,
+/// further requests are rejected until one of them finishes
+pub const MAX_CONCURRENT_TRACE_CHAIN_REQUESTS: usize = 4;
+/// Amount of traced blocks that can be waiting to be sent to the client,
+/// tracing is paused once the buffer is full
+const TRACE_CHAIN_BUFFER_SIZE: usize = 16;
+/// Time a transaction can be traced for when the request doesn't set a timeout, the same
+/// default as geth's tracers
+pub const DEFAULT_TRACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static TRACE_CHAIN_PERMITS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_TRACE_CHAIN_REQUESTS);
+
+pub struct TraceChainRequest {
+    pub from: BlockIdentifier,
+    pub to: BlockIdentifier,
+    /// Maximum time each transaction can be traced for
+    pub timeout: Duration,
+}
+
+/// Options of the tracing, following geth's `TraceConfig`
+#[derive(Debug, Default, Deserialize)]
+struct TraceConfig {
+    /// Duration such as `500ms` or `10s`
+    timeout: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTrace {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub block: BlockNumber,
+    pub hash: BlockHash,
+    pub traces: Vec<TxTrace>,
+    /// Why the tracing stopped before the end of the block, the last trace being the partial trace
+    /// of the interrupted transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxTrace {
+    pub tx_hash: H256,
+    pub result: CallTrace,
+}
+
+impl TraceChainRequest {
+    /// Resolves the requested block range, checking it is not empty or too long
+    fn block_range(&self, storage: &Store) -> Result<RangeInclusive<BlockNumber>, RpcErr> {
+        let from = self
+            .from
+            .resolve_block_number(storage)?
+            .ok_or(RpcErr::WrongParam("fromBlock".to_string()))?;
+        let to = self
+            .to
+            .resolve_block_number(storage)?
+            .ok_or(RpcErr::WrongParam("toBlock".to_string()))?;
+        if from > to {
+            return Err(RpcErr::BadParams("Invalid block range".to_string()));
+        }
+        if to - from >= MAX_TRACE_CHAIN_RANGE {
+            return Err(RpcErr::BadParams(format!(
+                "Block range is too long, at most {MAX_TRACE_CHAIN_RANGE} blocks can be traced"
+            )));
+        }
+        Ok(from..=to)
+    }
+}
+
+impl RpcHandler for TraceChainRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<TraceChainRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 2 && params.len() != 3 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected two or three params and {} were provided",
+                params.len()
+            )));
+        };
+        let config: TraceConfig = match params.get(2) {
+            Some(config) => serde_json::from_value(config.clone())
+                .map_err(|error| RpcErr::BadParams(error.to_string()))?,
+            None => TraceConfig::default(),
+        };
+        let timeout = match config.timeout {
+            Some(timeout) => parse_duration(&timeout)
+                .ok_or(RpcErr::BadParams(format!("Invalid timeout {timeout}")))?,
+            None => DEFAULT_TRACE_TIMEOUT,
+        };
+        Ok(TraceChainRequest {
+            from: BlockIdentifier::parse(params[0].clone(), 0)?,
+            to: BlockIdentifier::parse(params[1].clone(), 1)?,
+            timeout,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let range = self.block_range(&context.storage)?;
+        info!("Requested traces of blocks {range:?}");
+        let limits = TraceLimits::with_timeout(self.timeout);
+        let mut traces = Vec::new();
+        for number in range {
+            let trace = trace_block(&context.storage, number, limits)?;
+            if let Some(error) = trace.error {
+                return Err(RpcErr::Vm(format!(
+                    "Tracing of block {number} stopped: {error}"
+                )));
+            }
+            traces.push(trace);
+        }
+        serde_json::to_value(traces).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Parses a duration made of a decimal number and a unit, `ms`, `s`, `m` or `h`
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let unit_start = duration.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = duration.split_at(unit_start);
+    let value: f64 = value.parse().ok()?;
+    let unit_secs = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(value * unit_secs).ok()
+}
+
+/// Re-executes the canonical block with the given number on top of its parent's state,
+/// returning the call trace of each transaction.
+/// If the trace limits are exceeded, the traces up to the interrupted transaction are returned
+/// along with the reason of the interruption.
+pub(crate) fn trace_block(
+    storage: &Store,
+    number: BlockNumber,
+    limits: TraceLimits,
+) -> Result<BlockTrace, RpcErr> {
+    let header = storage
+        .get_block_header(number)?
+        .ok_or(RpcErr::Internal(format!(
+            "Could not get header for block {number}"
+        )))?;
+    let body = storage
+        .get_block_body(number)?
+        .ok_or(RpcErr::Internal(format!(
+            "Could not get body for block {number}"
+        )))?;
+    let block = Block::new(header, body);
+    let mut state = evm_state(storage.clone(), block.header.parent_hash);
+    let (call_traces, error) = match ethrex_vm::trace_block_calls(&block, &mut state, limits) {
+        Ok(traces) => (traces, None),
+        Err(EvmError::TraceInterrupted { reason, traces }) => (traces, Some(reason.to_string())),
+        Err(error) => return Err(error.into()),
+    };
+    let traces = block
+        .body
+        .transactions
+        .iter()
+        .zip(call_traces)
+        .map(|(tx, result)| TxTrace {
+            tx_hash: tx.compute_hash(),
+            result,
+        })
+        .collect();
+    Ok(BlockTrace {
+        block: number,
+        hash: block.hash(),
+        traces,
+        error,
+    })
+}
+
+/// Serves `debug_traceChain` over HTTP, streaming the response as each block is traced
+/// instead of building it all in memory.
+/// The body is a regular JSON-RPC response whose result is the array of block traces. If a
+/// block fails to be traced, an element with its number and the error is sent and the
+/// array is closed, the same happens after the partial trace of a block whose tracing timed out.
+/// Tracing stops as soon as the client closes the connection.
+pub(crate) fn stream_trace_chain(req: RpcRequest, context: RpcApiContext) -> Response {
+    let (range, timeout) = match TraceChainRequest::parse(&req.params)
+        .and_then(|request| Ok((request.block_range(&context.storage)?, request.timeout)))
+    {
+        Ok(request) => request,
+        Err(error) => return rpc_response(req.id, Err(error)).into_response(),
+    };
+    let Ok(permit) = TRACE_CHAIN_PERMITS.try_acquire() else {
+        return rpc_response(
+            req.id,
+            Err(RpcErr::Internal(
+                "Too many debug_traceChain requests in progress".to_string(),
+            )),
+        )
+        .into_response();
+    };
+    info!("Requested streamed traces of blocks {range:?}");
+    let id = serde_json::to_string(&req.id).unwrap_or_else(|_| "null".to_string());
+    let (sender, receiver) = mpsc::channel::<Result<Bytes, Infallible>>(TRACE_CHAIN_BUFFER_SIZE);
+    tokio::task::spawn_blocking(move || {
+        // Hold the permit until the whole range is traced
+        let _permit = permit;
+        // Sending only fails if the client closed the connection
+        let send = |chunk: String| sender.blocking_send(Ok(Bytes::from(chunk))).is_ok();
+        let is_cancelled = || sender.is_closed();
+        let limits = TraceLimits {
+            timeout,
+            is_cancelled: &is_cancelled,
+        };
+        if !send(format!(r#"{{"jsonrpc":"2.0","id":{id},"result":["#)) {
+            return;
+        }
+        for (i, number) in range.enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let trace = trace_block(&context.storage, number, limits).and_then(|trace| {
+                let interrupted = trace.error.is_some();
+                serde_json::to_string(&trace)
+                    .map(|trace| (trace, interrupted))
+                    .map_err(|error| RpcErr::Internal(error.to_string()))
+            });
+            match trace {
+                Ok((trace, interrupted)) => {
+                    if !send(format!("{separator}{trace}")) {
+                        return;
+                    }
+                    if interrupted {
+                        error!("Tracing of block {number} was interrupted");
+                        break;
+                    }
+                }
+                Err(err) => {
+                    let err: RpcErrorMetadata = err.into();
+                    error!("Failed to trace block {number}: {}", err.message);
+                    let error = serde_json::json!({
+                        "block": format!("{number:#x}"),
+                        "error": err.message,
+                    });
+                    send(format!("{separator}{error}"));
+                    break;
+                }
+            }
+        }
+        send("]}".to_string());
+    });
+    let body = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::context_with_genesis;
+
+    fn trace_chain_request(from: &str, to: &str) -> RpcRequest {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "debug_traceChain",
+            "params": [from, to]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn trace_chain_rejects_invalid_ranges() {
+        let context = context_with_genesis();
+        let request = TraceChainRequest::parse(&trace_chain_request("0x1", "0x0").params).unwrap();
+        assert!(matches!(
+            request.block_range(&context.storage),
+            Err(RpcErr::BadParams(_))
+        ));
+        let request = TraceChainRequest::parse(
+            &trace_chain_request("0x0", &format!("{MAX_TRACE_CHAIN_RANGE:#x}")).params,
+        )
+        .unwrap();
+        assert!(matches!(
+            request.block_range(&context.storage),
+            Err(RpcErr::BadParams(_))
+        ));
+    }
+
+    #[test]
+    fn trace_chain_parses_the_timeout() {
+        let mut request = trace_chain_request("0x0", "0x0");
+        let parsed = TraceChainRequest::parse(&request.params).unwrap();
+        assert_eq!(parsed.timeout, DEFAULT_TRACE_TIMEOUT);
+        request
+            .params
+            .as_mut()
+            .unwrap()
+            .push(serde_json::json!({"timeout": "1.5s"}));
+        let parsed = TraceChainRequest::parse(&request.params).unwrap();
+        assert_eq!(parsed.timeout, Duration::from_millis(1500));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("-1s"), None);
+    }
+
+    #[tokio::test]
+    async fn stream_trace_chain_returns_a_valid_response() {
+        let context = context_with_genesis();
+        let genesis_hash = context
+            .storage
+            .get_canonical_block_hash(0)
+            .unwrap()
+            .unwrap();
+        let response = stream_trace_chain(trace_chain_request("0x0", "0x0"), context);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": [{"block": "0x0", "hash": genesis_hash, "traces": []}]
+            })
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ethrex_blockchain::payload_manager::SLOT_DURATION_SECONDS;
+use ethrex_core::types::{BlockHash, BlockHeader, BlockNumber};
+use tracing::warn;
+
+use super::metrics;
+use crate::RpcApiContext;
+
+/// Blocks the head can move back on a fork choice update before it is reported
+pub const HEAD_MOVED_BACK_WARNING_BLOCKS: u64 = 8;
+/// Seconds a head can be ahead of the local clock before it is reported, clocks of the
+/// consensus and execution clients are expected to be at most a few seconds apart
+pub const FUTURE_HEAD_WARNING_SECONDS: u64 = 2 * SLOT_DURATION_SECONDS;
+/// Slots of an epoch of the consensus layer
+pub const SLOTS_PER_EPOCH: u64 = 32;
+/// Epochs the finalized block can lag behind the head before finality is reported as stalled,
+/// a healthy chain finalizes the blocks of two epochs ago
+pub const FINALITY_STALL_WARNING_EPOCHS: u64 = 4;
+
+/// Finalized block of the last finality stall reported, so it is only reported once
+pub type ReportedStall = Arc<Mutex<Option<BlockHash>>>;
+
+/// Consensus anomalies spotted on an applied fork choice update, operators should be alerted
+/// as the node or its consensus client may be following a bad chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForkChoiceAnomaly {
+    /// The head moved back more than [HEAD_MOVED_BACK_WARNING_BLOCKS] blocks
+    HeadMovedBack { from: BlockNumber, to: BlockNumber },
+    /// The head is more than [FUTURE_HEAD_WARNING_SECONDS] ahead of the local clock
+    FutureHead { timestamp: u64, now: u64 },
+    /// The finalized block is more than [FINALITY_STALL_WARNING_EPOCHS] epochs behind the head
+    FinalityStalled {
+        finalized: BlockNumber,
+        finalized_hash: BlockHash,
+        epochs: u64,
+    },
+}
+
+/// Returns the anomalies of moving the head from the previous one, `now` being the current
+/// unix time in seconds
+pub fn detect_anomalies(
+    previous_head: Option<&BlockHeader>,
+    head: &BlockHeader,
+    finalized: Option<&BlockHeader>,
+    now: u64,
+) -> Vec<ForkChoiceAnomaly> {
+    let mut anomalies = vec![];
+    if let Some(previous_head) = previous_head {
+        if previous_head.number > head.number + HEAD_MOVED_BACK_WARNING_BLOCKS {
+            anomalies.push(ForkChoiceAnomaly::HeadMovedBack {
+                from: previous_head.number,
+                to: head.number,
+            });
+        }
+    }
+    if head.timestamp > now + FUTURE_HEAD_WARNING_SECONDS {
+        anomalies.push(ForkChoiceAnomaly::FutureHead {
+            timestamp: head.timestamp,
+            now,
+        });
+    }
+    if let Some(finalized) = finalized {
+        let epochs = head.timestamp.saturating_sub(finalized.timestamp)
+            / (SLOTS_PER_EPOCH * SLOT_DURATION_SECONDS);
+        if epochs > FINALITY_STALL_WARNING_EPOCHS {
+            anomalies.push(ForkChoiceAnomaly::FinalityStalled {
+                finalized: finalized.number,
+                finalized_hash: finalized.compute_block_hash(),
+                epochs,
+            });
+        }
+    }
+    anomalies
+}
+
+/// Warns about and counts the anomalies of moving the head from the previous one
+pub(crate) fn report_anomalies(
+    context: &RpcApiContext,
+    previous_head: Option<&BlockHeader>,
+    head: &BlockHeader,
+    finalized: Option<&BlockHeader>,
+) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    for anomaly in detect_anomalies(previous_head, head, finalized, now) {
+        match anomaly {
+            ForkChoiceAnomaly::HeadMovedBack { from, to } => {
+                warn!("Fork choice moved the head back {} blocks, from {from} to {to}", from - to)
+            }
+            ForkChoiceAnomaly::FutureHead { timestamp, now } => warn!(
+                "Fork choice head {} has timestamp {timestamp}, {} seconds ahead of the local clock",
+                head.number,
+                timestamp - now
+            ),
+            ForkChoiceAnomaly::FinalityStalled {
+                finalized,
+                finalized_hash,
+                epochs,
+            } => {
+                let mut reported = context
+                    .reported_stall
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if *reported == Some(finalized_hash) {
+                    continue;
+                }
+                *reported = Some(finalized_hash);
+                warn!(
+                    "Finality stalled: the finalized block {finalized} is {epochs} epochs behind the head {}",
+                    head.number
+                );
+            }
+        }
+        metrics::record_fork_choice_anomaly(context, &anomaly);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(number: BlockNumber, timestamp: u64) -> BlockHeader {
+        BlockHeader {
+            number,
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn anomalies_are_detected_past_their_thresholds() {
+        let now = 1_000_000;
+        let head = header(100, now);
+        let epoch_seconds = SLOTS_PER_EPOCH * SLOT_DURATION_SECONDS;
+        let finalized = header(40, now - FINALITY_STALL_WARNING_EPOCHS * epoch_seconds);
+        let previous = header(100 + HEAD_MOVED_BACK_WARNING_BLOCKS, now);
+        assert!(detect_anomalies(Some(&previous), &head, Some(&finalized), now).is_empty());
+
+        let stalled = header(
+            30,
+            now - (FINALITY_STALL_WARNING_EPOCHS + 1) * epoch_seconds,
+        );
+        let previous = header(101 + HEAD_MOVED_BACK_WARNING_BLOCKS, now);
+        let future_head = header(100, now + FUTURE_HEAD_WARNING_SECONDS + 1);
+        assert_eq!(
+            detect_anomalies(Some(&previous), &future_head, None, now),
+            vec![
+                ForkChoiceAnomaly::HeadMovedBack {
+                    from: 101 + HEAD_MOVED_BACK_WARNING_BLOCKS,
+                    to: 100
+                },
+                ForkChoiceAnomaly::FutureHead {
+                    timestamp: now + FUTURE_HEAD_WARNING_SECONDS + 1,
+                    now
+                }
+            ]
+        );
+        assert_eq!(
+            detect_anomalies(None, &head, Some(&stalled), now),
+            vec![ForkChoiceAnomaly::FinalityStalled {
+                finalized: 30,
+                finalized_hash: stalled.compute_block_hash(),
+                epochs: FINALITY_STALL_WARNING_EPOCHS + 1
+            }]
+        );
+    }
+}
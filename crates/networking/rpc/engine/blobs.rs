@@ -0,0 +1,41 @@
+use ethrex_core::H256;
+use serde_json::Value;
+use tracing::info;
+
+use crate::{types::payload::BlobAndProofV1, RpcApiContext, RpcErr, RpcHandler};
+
+/// Maximum amount of versioned hashes engine_getBlobsV1 can be asked for
+pub const GET_BLOBS_REQUEST_MAX_SIZE: usize = 128;
+
+pub struct GetBlobsV1Request {
+    pub versioned_hashes: Vec<H256>,
+}
+
+impl RpcHandler for GetBlobsV1Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 1 {
+            return Err(RpcErr::BadParams("Expected 1 param".to_owned()));
+        };
+        let versioned_hashes: Vec<H256> = serde_json::from_value(params[0].clone())
+            .map_err(|_| RpcErr::WrongParam("blob_versioned_hashes".to_string()))?;
+        if versioned_hashes.len() > GET_BLOBS_REQUEST_MAX_SIZE {
+            return Err(RpcErr::TooLargeRequest(GET_BLOBS_REQUEST_MAX_SIZE));
+        }
+        Ok(GetBlobsV1Request { versioned_hashes })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!("Requested {} blobs", self.versioned_hashes.len());
+        // The blobs that are not in the pool are returned as null
+        let blobs: Vec<Option<BlobAndProofV1>> = context
+            .storage
+            .get_blobs_and_proofs_from_pool(&self.versioned_hashes)?
+            .into_iter()
+            .map(|found| found.map(|(blob, proof)| BlobAndProofV1::new(&blob, &proof)))
+            .collect();
+        serde_json::to_value(blobs).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
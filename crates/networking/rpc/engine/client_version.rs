@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{utils::RpcErr, version, RpcApiContext, RpcHandler};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientVersionV1 {
+    pub code: String,
+    pub name: String,
+    pub version: String,
+    /// First four bytes of the commit the client was built from
+    pub commit: String,
+}
+
+impl ClientVersionV1 {
+    /// Version of this node
+    pub fn ethrex() -> Self {
+        Self {
+            code: version::CLIENT_CODE.to_string(),
+            name: version::CLIENT_NAME.to_string(),
+            version: format!("v{}", version::CLIENT_VERSION),
+            commit: version::short_commit(),
+        }
+    }
+}
+
+/// Exchanges the client versions with the consensus client, which sends its own one
+pub struct GetClientVersionV1Request {
+    pub consensus_client: ClientVersionV1,
+}
+
+impl RpcHandler for GetClientVersionV1Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 1 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one param and {} were provided",
+                params.len()
+            )));
+        };
+        Ok(GetClientVersionV1Request {
+            consensus_client: serde_json::from_value(params[0].clone())?,
+        })
+    }
+
+    fn handle(&self, _context: RpcApiContext) -> Result<Value, RpcErr> {
+        let cl = &self.consensus_client;
+        info!(
+            "Consensus client is {} {} ({}) built from {}",
+            cl.name, cl.version, cl.code, cl.commit
+        );
+        serde_json::to_value([ClientVersionV1::ethrex()])
+            .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn commit_is_sent_as_four_bytes() {
+        let ethrex = ClientVersionV1::ethrex();
+        assert_eq!(ethrex.code, "EX");
+        assert_eq!(ethrex.commit.len(), 10);
+        assert!(ethrex.commit.starts_with("0x"));
+
+        let request = GetClientVersionV1Request::parse(&Some(vec![json!({
+            "code": "LH",
+            "name": "Lighthouse",
+            "version": "v5.3.0",
+            "commit": "0xd6ba8c39"
+        })]))
+        .unwrap();
+        assert_eq!(request.consensus_client.code, "LH");
+        assert!(GetClientVersionV1Request::parse(&Some(vec![])).is_err());
+    }
+}
@@ -1,26 +1,50 @@
 use ethrex_blockchain::{
-    error::{ChainError, InvalidForkChoice},
+    constants::MAX_WITHDRAWALS_PER_PAYLOAD,
+    error::InvalidForkChoice,
     fork_choice::apply_fork_choice,
     latest_canonical_block_hash,
     payload::{create_payload, BuildPayloadArgs},
 };
+use ethrex_core::types::{BlockHeader, ChainConfig};
 use serde_json::Value;
-use tracing::{info, warn};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tracing::{debug, info, warn};
 
+use super::{anomalies, metrics};
 use crate::{
     types::{
-        fork_choice::{ForkChoiceResponse, ForkChoiceState, PayloadAttributesV3},
+        fork_choice::{ForkChoiceResponse, ForkChoiceState, PayloadAttributes},
         payload::PayloadStatus,
     },
     utils::RpcRequest,
     RpcApiContext, RpcErr, RpcHandler,
 };
 
+/// Last fork choice state that was successfully applied.
+/// Consensus clients send the same state every slot until the head changes,
+/// so repeated updates can skip the checks and writes of applying it again.
+pub type LastForkChoice = Arc<Mutex<Option<ForkChoiceState>>>;
+
+#[derive(Debug)]
+pub struct ForkChoiceUpdatedV1 {
+    pub fork_choice_state: ForkChoiceState,
+    pub payload_attributes: Result<Option<PayloadAttributes>, String>,
+}
+
+#[derive(Debug)]
+pub struct ForkChoiceUpdatedV2 {
+    pub fork_choice_state: ForkChoiceState,
+    pub payload_attributes: Result<Option<PayloadAttributes>, String>,
+}
+
 #[derive(Debug)]
 pub struct ForkChoiceUpdatedV3 {
     pub fork_choice_state: ForkChoiceState,
     #[allow(unused)]
-    pub payload_attributes: Result<Option<PayloadAttributesV3>, String>,
+    pub payload_attributes: Result<Option<PayloadAttributes>, String>,
 }
 
 impl TryFrom<ForkChoiceUpdatedV3> for RpcRequest {
@@ -41,119 +65,603 @@ impl TryFrom<ForkChoiceUpdatedV3> for RpcRequest {
     }
 }
 
+impl RpcHandler for ForkChoiceUpdatedV1 {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let (fork_choice_state, payload_attributes) = parse(params)?;
+        Ok(ForkChoiceUpdatedV1 {
+            fork_choice_state,
+            payload_attributes,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        update_fork_choice(
+            &self.fork_choice_state,
+            &self.payload_attributes,
+            1,
+            context,
+        )
+    }
+}
+
+impl RpcHandler for ForkChoiceUpdatedV2 {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let (fork_choice_state, payload_attributes) = parse(params)?;
+        Ok(ForkChoiceUpdatedV2 {
+            fork_choice_state,
+            payload_attributes,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        update_fork_choice(
+            &self.fork_choice_state,
+            &self.payload_attributes,
+            2,
+            context,
+        )
+    }
+}
+
 impl RpcHandler for ForkChoiceUpdatedV3 {
-    // TODO(#853): Allow fork choice to be executed even if fork choice updated v3 was not correctly parsed.
     fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
-        let params = params
-            .as_ref()
-            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
-        if params.len() != 2 {
-            return Err(RpcErr::BadParams("Expected 2 params".to_owned()));
-        }
+        let (fork_choice_state, payload_attributes) = parse(params)?;
         Ok(ForkChoiceUpdatedV3 {
-            fork_choice_state: serde_json::from_value(params[0].clone())?,
-            payload_attributes: serde_json::from_value(params[1].clone())
-                .map_err(|e| e.to_string()),
+            fork_choice_state,
+            payload_attributes,
         })
     }
 
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
-        info!(
-            "New fork choice request with head: {}, safe: {}, finalized: {}.",
-            self.fork_choice_state.head_block_hash,
-            self.fork_choice_state.safe_block_hash,
-            self.fork_choice_state.finalized_block_hash
-        );
+        update_fork_choice(
+            &self.fork_choice_state,
+            &self.payload_attributes,
+            3,
+            context,
+        )
+    }
+}
 
-        let head_block = match apply_fork_choice(
-            &context.storage,
-            self.fork_choice_state.head_block_hash,
-            self.fork_choice_state.safe_block_hash,
-            self.fork_choice_state.finalized_block_hash,
-        ) {
-            Ok(head) => head,
-            Err(error) => {
-                let fork_choice_response = match error {
-                    InvalidForkChoice::NewHeadAlreadyCanonical => {
-                        ForkChoiceResponse::from(PayloadStatus::valid_with_hash(
-                            latest_canonical_block_hash(&context.storage).unwrap(),
-                        ))
-                    }
-                    InvalidForkChoice::Syncing => {
-                        // Start sync
-                        let current_number = context.storage.get_latest_block_number()?.unwrap();
-                        let Some(current_head) =
-                            context.storage.get_canonical_block_hash(current_number)?
-                        else {
-                            return Err(RpcErr::Internal(
-                                "Missing latest canonical block".to_owned(),
-                            ));
-                        };
-                        let sync_head = self.fork_choice_state.head_block_hash;
-                        tokio::spawn(async move {
-                            // If we can't get hold of the syncer, then it means that there is an active sync in process
-                            if let Ok(mut syncer) = context.syncer.try_lock() {
-                                syncer
-                                    .start_sync(current_head, sync_head, context.storage.clone())
-                                    .await
-                            }
-                        });
-                        ForkChoiceResponse::from(PayloadStatus::syncing())
-                    }
-                    reason => {
-                        warn!("Invalid fork choice state. Reason: {:#?}", reason);
-                        return Err(RpcErr::InvalidForkChoiceState(reason.to_string()));
+/// Parses the fork choice state and the optional payload attributes. Malformed attributes
+/// don't fail the request, as the fork choice state has to be applied before reporting them.
+fn parse(
+    params: &Option<Vec<Value>>,
+) -> Result<(ForkChoiceState, Result<Option<PayloadAttributes>, String>), RpcErr> {
+    let params = params
+        .as_ref()
+        .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+    if params.is_empty() || params.len() > 2 {
+        return Err(RpcErr::BadParams("Expected 1 or 2 params".to_owned()));
+    }
+    let payload_attributes = match params.get(1) {
+        Some(attributes) => {
+            serde_json::from_value(attributes.clone()).map_err(|error| error.to_string())
+        }
+        None => Ok(None),
+    };
+    Ok((
+        serde_json::from_value(params[0].clone())?,
+        payload_attributes,
+    ))
+}
+
+/// Applies the fork choice state and, if there are payload attributes, starts building a
+/// payload on top of the new head. `version` is the one of the engine_forkchoiceUpdated
+/// method, which determines the forks the payload can be built for.
+fn update_fork_choice(
+    fork_choice_state: &ForkChoiceState,
+    payload_attributes: &Result<Option<PayloadAttributes>, String>,
+    version: u8,
+    context: RpcApiContext,
+) -> Result<Value, RpcErr> {
+    info!(
+        "New fork choice request with head: {}, safe: {}, finalized: {}.",
+        fork_choice_state.head_block_hash,
+        fork_choice_state.safe_block_hash,
+        fork_choice_state.finalized_block_hash
+    );
+
+    let fork_choice_result = match already_applied_head(fork_choice_state, &context)? {
+        Some(head) => {
+            debug!("Fork choice state was already applied, skipping it");
+            Ok(head)
+        }
+        None => {
+            let previous_head = latest_head(&context)?;
+            let start = Instant::now();
+            let result = apply_fork_choice(
+                &context.storage,
+                fork_choice_state.head_block_hash,
+                fork_choice_state.safe_block_hash,
+                fork_choice_state.finalized_block_hash,
+            );
+            metrics::record_fork_choice_latency(&context, start.elapsed());
+            if let Ok(head) = &result {
+                let finalized = context
+                    .storage
+                    .get_block_header_by_hash(fork_choice_state.finalized_block_hash)?;
+                anomalies::report_anomalies(
+                    &context,
+                    previous_head.as_ref(),
+                    head,
+                    finalized.as_ref(),
+                );
+            }
+            result
+        }
+    };
+    let head_block = match fork_choice_result {
+        Ok(head) => head,
+        Err(error) => {
+            let fork_choice_response = match error {
+                InvalidForkChoice::NewHeadAlreadyCanonical => {
+                    ForkChoiceResponse::from(PayloadStatus::valid_with_hash(
+                        latest_canonical_block_hash(&context.storage).unwrap(),
+                    ))
+                }
+                InvalidForkChoice::Syncing => {
+                    // A sync in progress picks up the new head instead of starting another one
+                    context.syncer.sync_to(fork_choice_state.head_block_hash);
+                    ForkChoiceResponse::from(PayloadStatus::syncing())
+                }
+                reason => {
+                    warn!("Invalid fork choice state. Reason: {:#?}", reason);
+                    metrics::record_invalid_fork_choice(&context);
+                    if matches!(
+                        reason,
+                        InvalidForkChoice::ReorgTooDeep(..)
+                            | InvalidForkChoice::ReorgBelowFinalized(..)
+                    ) {
+                        metrics::record_refused_reorg(&context);
                     }
-                };
-                return serde_json::to_value(fork_choice_response)
-                    .map_err(|error| RpcErr::Internal(error.to_string()));
+                    return Err(RpcErr::InvalidForkChoiceState(reason.to_string()));
+                }
+            };
+            metrics::record_fork_choice_status(&context, &fork_choice_response.payload_status);
+            return serde_json::to_value(fork_choice_response)
+                .map_err(|error| RpcErr::Internal(error.to_string()));
+        }
+    };
+
+    *context
+        .last_fork_choice
+        .lock()
+        .map_err(|error| RpcErr::Internal(error.to_string()))? = Some(fork_choice_state.clone());
+    // Payloads still being built on a previous head are dropped mid-build, if the payload
+    // attributes are sent again the build restarts below on top of the new head
+    context
+        .payload_manager
+        .cancel_superseded(fork_choice_state.head_block_hash, &context.storage)?;
+
+    // Build block from received payload. This step is skipped if applying the fork choice state failed
+    let mut response = ForkChoiceResponse::from(PayloadStatus::valid_with_hash(
+        fork_choice_state.head_block_hash,
+    ));
+
+    match payload_attributes {
+        // Payload may be invalid but we had to apply fork choice state nevertheless.
+        Err(e) => return Err(RpcErr::InvalidPayloadAttributes(e.into())),
+        Ok(None) => (),
+        Ok(Some(attributes)) => {
+            info!("Fork choice updated includes payload attributes. Creating a new payload.");
+            let chain_config = context.storage.get_chain_config()?;
+            validate_fork(attributes, &chain_config, version)?;
+            if attributes.timestamp <= head_block.timestamp {
+                return Err(RpcErr::InvalidPayloadAttributes(
+                    "invalid timestamp".to_string(),
+                ));
             }
+            validate_withdrawals(attributes, &chain_config)?;
+            let args = BuildPayloadArgs {
+                parent: fork_choice_state.head_block_hash,
+                timestamp: attributes.timestamp,
+                fee_recipient: attributes.suggested_fee_recipient,
+                random: attributes.prev_randao,
+                withdrawals: attributes.withdrawals.clone().unwrap_or_default(),
+                beacon_root: attributes.parent_beacon_block_root,
+                version,
+                extra_data: context.payload_manager.config().extra_data.clone(),
+                gas_ceil: context.payload_manager.config().gas_ceil,
+            };
+            let payload_id = args.id();
+            response.set_id(payload_id);
+            // Parent block is guaranteed to be present at this point,
+            // so the only errors that may be returned are execution and storage errors
+            let payload = create_payload(&args, &context.storage)?;
+            context
+                .payload_manager
+                .start(payload_id, payload, &context.storage)?;
+        }
+    }
+
+    serde_json::to_value(response).map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+/// Returns the header of the current canonical head
+fn latest_head(context: &RpcApiContext) -> Result<Option<BlockHeader>, RpcErr> {
+    match context.storage.get_latest_block_number()? {
+        Some(latest) => Ok(context.storage.get_block_header(latest)?),
+        None => Ok(None),
+    }
+}
+
+/// Returns the header of the head if the fork choice state is the last applied one
+/// and its head is still the canonical head, meaning there is nothing to update
+fn already_applied_head(
+    fork_choice_state: &ForkChoiceState,
+    context: &RpcApiContext,
+) -> Result<Option<BlockHeader>, RpcErr> {
+    let last_fork_choice = context
+        .last_fork_choice
+        .lock()
+        .map_err(|error| RpcErr::Internal(error.to_string()))?;
+    if last_fork_choice.as_ref() != Some(fork_choice_state) {
+        return Ok(None);
+    }
+    let head_hash = fork_choice_state.head_block_hash;
+    let Some(latest) = context.storage.get_latest_block_number()? else {
+        return Ok(None);
+    };
+    if context.storage.get_canonical_block_hash(latest)? != Some(head_hash) {
+        return Ok(None);
+    }
+    Ok(context.storage.get_block_header_by_hash(head_hash)?)
+}
+
+/// Checks the payload is built for the forks supported by the given version of
+/// engine_forkchoiceUpdated and that its attributes have their shape: V1 has no withdrawals,
+/// and only V3, used from Cancun on, has the parent beacon block root
+fn validate_fork(
+    attributes: &PayloadAttributes,
+    chain_config: &ChainConfig,
+    version: u8,
+) -> Result<(), RpcErr> {
+    let supported_fork = match version {
+        1 => !chain_config.is_shanghai_activated(attributes.timestamp),
+        2 => !chain_config.is_cancun_activated(attributes.timestamp),
+        _ => chain_config.is_cancun_activated(attributes.timestamp),
+    };
+    if !supported_fork {
+        return Err(RpcErr::UnsuportedFork(format!(
+            "forkchoiceUpdatedV{version} used to build a {:?} payload",
+            chain_config.get_fork(attributes.timestamp)
+        )));
+    }
+    if version == 1 && attributes.withdrawals.is_some() {
+        return Err(RpcErr::InvalidPayloadAttributes(
+            "withdrawals present in forkchoiceUpdatedV1".to_string(),
+        ));
+    }
+    match (version, attributes.parent_beacon_block_root) {
+        (3, None) => Err(RpcErr::InvalidPayloadAttributes(
+            "parent beacon block root missing".to_string(),
+        )),
+        (1 | 2, Some(_)) => Err(RpcErr::InvalidPayloadAttributes(format!(
+            "parent beacon block root present in forkchoiceUpdatedV{version}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the withdrawals of the payload attributes are only present from Shanghai on
+/// and fit in a payload
+fn validate_withdrawals(
+    attributes: &PayloadAttributes,
+    chain_config: &ChainConfig,
+) -> Result<(), RpcErr> {
+    let shanghai_activated = chain_config.is_shanghai_activated(attributes.timestamp);
+    match &attributes.withdrawals {
+        None if shanghai_activated => Err(RpcErr::InvalidPayloadAttributes(
+            "withdrawals missing after Shanghai".to_string(),
+        )),
+        Some(_) if !shanghai_activated => Err(RpcErr::InvalidPayloadAttributes(
+            "withdrawals present before Shanghai".to_string(),
+        )),
+        Some(withdrawals) if withdrawals.len() > MAX_WITHDRAWALS_PER_PAYLOAD => {
+            Err(RpcErr::InvalidPayloadAttributes(format!(
+                "{} withdrawals exceed the maximum of {MAX_WITHDRAWALS_PER_PAYLOAD}",
+                withdrawals.len()
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::payload::{GetPayloadV3Request, GetPayloadV4Request},
+        utils::test_utils::context_with_genesis,
+    };
+    use ethrex_blockchain::{
+        add_block, is_canonical,
+        payload::{build_payload, DEFAULT_BUILDER_GAS_CEIL},
+    };
+    use ethrex_core::{
+        types::{Block, Withdrawal},
+        Bytes, H160, H256,
+    };
+    use ethrex_storage::Store;
+
+    fn new_block(storage: &Store, parent: &BlockHeader) -> Block {
+        let args = BuildPayloadArgs {
+            parent: parent.compute_block_hash(),
+            timestamp: parent.timestamp + 12,
+            fee_recipient: H160::random(),
+            random: H256::random(),
+            withdrawals: Vec::new(),
+            beacon_root: Some(H256::random()),
+            version: 1,
+            extra_data: Bytes::new(),
+            gas_ceil: DEFAULT_BUILDER_GAS_CEIL,
         };
+        let mut block = create_payload(&args, storage).unwrap();
+        build_payload(&mut block, storage, &Default::default()).unwrap();
+        add_block(&block, storage).unwrap();
+        block
+    }
+
+    fn fork_choice_update(head: H256, genesis: H256) -> ForkChoiceUpdatedV3 {
+        ForkChoiceUpdatedV3 {
+            fork_choice_state: ForkChoiceState {
+                head_block_hash: head,
+                safe_block_hash: genesis,
+                finalized_block_hash: genesis,
+            },
+            payload_attributes: Ok(None),
+        }
+    }
+
+    #[test]
+    fn repeated_fork_choice_updates_are_not_applied_again() {
+        let context = context_with_genesis();
+        let genesis = context.storage.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis.compute_block_hash();
+        let block_1a = new_block(&context.storage, &genesis);
+        let block_1b = new_block(&context.storage, &genesis);
+
+        let update = fork_choice_update(block_1a.hash(), genesis_hash);
+        assert!(already_applied_head(&update.fork_choice_state, &context)
+            .unwrap()
+            .is_none());
+        let response = update.handle(context.clone()).unwrap();
+        assert_eq!(
+            already_applied_head(&update.fork_choice_state, &context).unwrap(),
+            Some(block_1a.header.clone())
+        );
+        assert_eq!(update.handle(context.clone()).unwrap(), response);
 
-        // Build block from received payload. This step is skipped if applying the fork choice state failed
-        let mut response = ForkChoiceResponse::from(PayloadStatus::valid_with_hash(
-            self.fork_choice_state.head_block_hash,
+        // Once the head changes the same state has to be applied again
+        fork_choice_update(block_1b.hash(), genesis_hash)
+            .handle(context.clone())
+            .unwrap();
+        assert!(already_applied_head(&update.fork_choice_state, &context)
+            .unwrap()
+            .is_none());
+        assert_eq!(update.handle(context.clone()).unwrap(), response);
+        assert!(is_canonical(&context.storage, 1, block_1a.hash()).unwrap());
+    }
+
+    #[test]
+    fn withdrawals_must_follow_the_fork_rules() {
+        let chain_config = ChainConfig {
+            shanghai_time: Some(100),
+            ..Default::default()
+        };
+        let attributes = |timestamp, withdrawals| PayloadAttributes {
+            timestamp,
+            withdrawals,
+            ..Default::default()
+        };
+        let validate = |attributes| validate_withdrawals(&attributes, &chain_config);
+        assert!(validate(attributes(100, Some(vec![]))).is_ok());
+        assert!(validate(attributes(99, None)).is_ok());
+        assert!(matches!(
+            validate(attributes(100, None)),
+            Err(RpcErr::InvalidPayloadAttributes(_))
         ));
+        assert!(matches!(
+            validate(attributes(99, Some(vec![]))),
+            Err(RpcErr::InvalidPayloadAttributes(_))
+        ));
+        let withdrawal = Withdrawal {
+            index: 0,
+            validator_index: 0,
+            address: H160::zero(),
+            amount: 1,
+        };
+        let too_many = vec![withdrawal; MAX_WITHDRAWALS_PER_PAYLOAD + 1];
+        assert!(matches!(
+            validate(attributes(100, Some(too_many))),
+            Err(RpcErr::InvalidPayloadAttributes(_))
+        ));
+    }
 
-        match &self.payload_attributes {
-            // Payload may be invalid but we had to apply fork choice state nevertheless.
-            Err(e) => return Err(RpcErr::InvalidPayloadAttributes(e.into())),
-            Ok(None) => (),
-            Ok(Some(attributes)) => {
-                info!("Fork choice updated includes payload attributes. Creating a new payload.");
-                let chain_config = context.storage.get_chain_config()?;
-                if !chain_config.is_cancun_activated(attributes.timestamp) {
-                    return Err(RpcErr::UnsuportedFork(
-                        "forkChoiceV3 used to build pre-Cancun payload".to_string(),
-                    ));
-                }
-                if attributes.timestamp <= head_block.timestamp {
-                    return Err(RpcErr::InvalidPayloadAttributes(
-                        "invalid timestamp".to_string(),
-                    ));
-                }
-                let args = BuildPayloadArgs {
-                    parent: self.fork_choice_state.head_block_hash,
-                    timestamp: attributes.timestamp,
-                    fee_recipient: attributes.suggested_fee_recipient,
-                    random: attributes.prev_randao,
-                    withdrawals: attributes.withdrawals.clone(),
-                    beacon_root: Some(attributes.parent_beacon_block_root),
-                    version: 3,
-                };
-                let payload_id = args.id();
-                response.set_id(payload_id);
-                let payload = match create_payload(&args, &context.storage) {
-                    Ok(payload) => payload,
-                    Err(ChainError::EvmError(error)) => return Err(error.into()),
-                    // Parent block is guaranteed to be present at this point,
-                    // so the only errors that may be returned are internal storage errors
-                    Err(error) => return Err(RpcErr::Internal(error.to_string())),
-                };
-                context.storage.add_payload(payload_id, payload)?;
+    #[test]
+    fn each_version_builds_payloads_for_its_forks() {
+        let chain_config = ChainConfig {
+            shanghai_time: Some(100),
+            cancun_time: Some(200),
+            ..Default::default()
+        };
+        let attributes = |timestamp, withdrawals, parent_beacon_block_root| PayloadAttributes {
+            timestamp,
+            withdrawals,
+            parent_beacon_block_root,
+            ..Default::default()
+        };
+        let validate = |attributes, version| validate_fork(&attributes, &chain_config, version);
+        assert!(validate(attributes(50, None, None), 1).is_ok());
+        assert!(validate(attributes(150, Some(vec![]), None), 2).is_ok());
+        assert!(validate(attributes(250, Some(vec![]), Some(H256::zero())), 3).is_ok());
+
+        assert!(matches!(
+            validate(attributes(150, None, None), 1),
+            Err(RpcErr::UnsuportedFork(_))
+        ));
+        assert!(matches!(
+            validate(attributes(250, Some(vec![]), None), 2),
+            Err(RpcErr::UnsuportedFork(_))
+        ));
+        assert!(matches!(
+            validate(attributes(150, Some(vec![]), Some(H256::zero())), 3),
+            Err(RpcErr::UnsuportedFork(_))
+        ));
+        assert!(matches!(
+            validate(attributes(50, Some(vec![]), None), 1),
+            Err(RpcErr::InvalidPayloadAttributes(_))
+        ));
+        assert!(matches!(
+            validate(attributes(150, Some(vec![]), Some(H256::zero())), 2),
+            Err(RpcErr::InvalidPayloadAttributes(_))
+        ));
+        assert!(matches!(
+            validate(attributes(250, Some(vec![]), None), 3),
+            Err(RpcErr::InvalidPayloadAttributes(_))
+        ));
+    }
+
+    #[test]
+    fn fork_choice_is_applied_before_reporting_malformed_attributes() {
+        let context = context_with_genesis();
+        let genesis = context.storage.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis.compute_block_hash();
+        let block = new_block(&context.storage, &genesis);
+        let state = ForkChoiceState {
+            head_block_hash: block.hash(),
+            safe_block_hash: genesis_hash,
+            finalized_block_hash: genesis_hash,
+        };
+        let params = Some(vec![
+            serde_json::json!(state),
+            serde_json::json!({ "timestamp": "not a number" }),
+        ]);
+        let update = ForkChoiceUpdatedV3::parse(&params).unwrap();
+        assert!(matches!(
+            update.handle(context.clone()),
+            Err(RpcErr::InvalidPayloadAttributes(_))
+        ));
+        assert!(is_canonical(&context.storage, 1, block.hash()).unwrap());
+
+        // The attributes can be left out
+        let update = ForkChoiceUpdatedV3::parse(&Some(vec![serde_json::json!(state)])).unwrap();
+        assert!(update.handle(context).is_ok());
+    }
+
+    #[test]
+    fn payloads_are_built_in_the_background_until_retrieved() {
+        let context = context_with_genesis();
+        let genesis = context.storage.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis.compute_block_hash();
+        let update = ForkChoiceUpdatedV3 {
+            payload_attributes: Ok(Some(PayloadAttributes {
+                timestamp: genesis.timestamp + 12,
+                withdrawals: Some(vec![]),
+                parent_beacon_block_root: Some(H256::zero()),
+                ..Default::default()
+            })),
+            ..fork_choice_update(genesis_hash, genesis_hash)
+        };
+        update.handle(context.clone()).unwrap();
+        let payload_ids = context.payload_manager.payload_ids();
+        assert_eq!(payload_ids.len(), 1);
+
+        let response = GetPayloadV3Request {
+            payload_id: payload_ids[0],
+        }
+        .handle(context.clone())
+        .unwrap();
+        assert_eq!(
+            response["executionPayload"]["parentHash"],
+            serde_json::json!(genesis_hash)
+        );
+        // The job is done once the payload is retrieved
+        assert!(context.payload_manager.payload_ids().is_empty());
+    }
+
+    #[test]
+    fn payloads_restart_on_top_of_a_new_head() {
+        let context = context_with_genesis();
+        let genesis = context.storage.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis.compute_block_hash();
+        let block = new_block(&context.storage, &genesis);
+        let attributes = PayloadAttributes {
+            timestamp: genesis.timestamp + 24,
+            withdrawals: Some(vec![]),
+            parent_beacon_block_root: Some(H256::zero()),
+            ..Default::default()
+        };
+        ForkChoiceUpdatedV3 {
+            payload_attributes: Ok(Some(attributes.clone())),
+            ..fork_choice_update(genesis_hash, genesis_hash)
+        }
+        .handle(context.clone())
+        .unwrap();
+        let stale_payload_id = context.payload_manager.payload_ids()[0];
+
+        // The head moves mid-slot and the same attributes are sent again
+        ForkChoiceUpdatedV3 {
+            payload_attributes: Ok(Some(attributes)),
+            ..fork_choice_update(block.hash(), genesis_hash)
+        }
+        .handle(context.clone())
+        .unwrap();
+        let payload_ids = context.payload_manager.payload_ids();
+        assert_eq!(payload_ids.len(), 1);
+        assert_ne!(payload_ids[0], stale_payload_id);
+        assert!(matches!(
+            GetPayloadV3Request {
+                payload_id: stale_payload_id
             }
+            .handle(context.clone()),
+            Err(RpcErr::UnknownPayload(_))
+        ));
+        let response = GetPayloadV3Request {
+            payload_id: payload_ids[0],
         }
+        .handle(context.clone())
+        .unwrap();
+        assert_eq!(
+            response["executionPayload"]["parentHash"],
+            serde_json::json!(block.hash())
+        );
+    }
 
-        serde_json::to_value(response).map_err(|error| RpcErr::Internal(error.to_string()))
+    #[test]
+    fn prague_payloads_are_retrieved_with_get_payload_v4() {
+        let context = context_with_genesis();
+        let mut chain_config = context.storage.get_chain_config().unwrap();
+        chain_config.prague_time = Some(0);
+        chain_config.deposit_contract_address = Some(H160::repeat_byte(0x42));
+        context.storage.set_chain_config(&chain_config).unwrap();
+        let genesis = context.storage.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis.compute_block_hash();
+        let update = ForkChoiceUpdatedV3 {
+            payload_attributes: Ok(Some(PayloadAttributes {
+                timestamp: genesis.timestamp + 12,
+                withdrawals: Some(vec![]),
+                parent_beacon_block_root: Some(H256::zero()),
+                ..Default::default()
+            })),
+            ..fork_choice_update(genesis_hash, genesis_hash)
+        };
+        update.handle(context.clone()).unwrap();
+        let payload_id = context.payload_manager.payload_ids()[0];
+
+        // The payload can still be retrieved after asking for it with the wrong version
+        assert!(matches!(
+            GetPayloadV3Request { payload_id }.handle(context.clone()),
+            Err(RpcErr::UnsuportedFork(_))
+        ));
+        let response = GetPayloadV4Request { payload_id }
+            .handle(context.clone())
+            .unwrap();
+        assert_eq!(response["executionRequests"], serde_json::json!([]));
+        assert_eq!(response["blobsBundle"]["blobs"], serde_json::json!([]));
+        assert!(matches!(
+            GetPayloadV4Request { payload_id }.handle(context),
+            Err(RpcErr::UnknownPayload(_))
+        ));
     }
 }
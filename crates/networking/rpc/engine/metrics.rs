@@ -0,0 +1,217 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ethrex_core::U256;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::anomalies::ForkChoiceAnomaly;
+use crate::{
+    types::payload::{PayloadStatus, PayloadValidationStatus},
+    utils::RpcErr,
+    RpcApiContext, RpcHandler,
+};
+
+/// Upper bounds in milliseconds of the buckets of the latency histograms,
+/// latencies above the last one are only counted in the total
+pub const LATENCY_BUCKETS_MS: [u64; 10] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+/// Metrics of the engine API handlers since the node started, so operators can tell if the node
+/// is too slow to answer the consensus client in time for a proposal
+pub type SharedEngineMetrics = Arc<Mutex<EngineMetrics>>;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineMetrics {
+    pub fork_choice_latency: LatencyHistogram,
+    pub payload_build_time: LatencyHistogram,
+    /// Priority fees paid to the fee recipient by the last built payload
+    pub last_payload_value: U256,
+    /// Milliseconds left until the getPayload deadline when the last payload was sealed,
+    /// negative if it was sealed late
+    pub last_payload_deadline_margin_ms: Option<i64>,
+    /// Payloads sealed after the getPayload deadline of their slot
+    pub late_payloads: u64,
+    pub new_payload: StatusCounts,
+    pub fork_choice_updated: StatusCounts,
+    /// Fork choice updates refused as they reorged the finalized block or more blocks than the
+    /// maximum reorg depth, also counted as invalid
+    pub refused_reorgs: u64,
+    pub fork_choice_anomalies: AnomalyCounts,
+}
+
+/// Amount of consensus anomalies spotted on fork choice updates, see [ForkChoiceAnomaly]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnomalyCounts {
+    pub head_moved_back: u64,
+    /// Most blocks the head moved back on a single update
+    pub deepest_head_move_back: u64,
+    pub future_heads: u64,
+    pub finality_stalls: u64,
+}
+
+/// Amount of INVALID and SYNCING responses of an engine method
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatusCounts {
+    pub invalid: u64,
+    pub syncing: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyHistogram {
+    /// Amount of recorded latencies lower or equal than each of the [LATENCY_BUCKETS_MS]
+    pub buckets: [u64; LATENCY_BUCKETS_MS.len()],
+    pub count: u64,
+    pub sum_ms: u64,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let ms = latency.as_millis().try_into().unwrap_or(u64::MAX);
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS_MS) {
+            if ms <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum_ms = self.sum_ms.saturating_add(ms);
+    }
+}
+
+impl AnomalyCounts {
+    pub fn record(&mut self, anomaly: &ForkChoiceAnomaly) {
+        match anomaly {
+            ForkChoiceAnomaly::HeadMovedBack { from, to } => {
+                self.head_moved_back += 1;
+                self.deepest_head_move_back = self.deepest_head_move_back.max(from - to);
+            }
+            ForkChoiceAnomaly::FutureHead { .. } => self.future_heads += 1,
+            ForkChoiceAnomaly::FinalityStalled { .. } => self.finality_stalls += 1,
+        }
+    }
+}
+
+impl StatusCounts {
+    pub fn record(&mut self, status: &PayloadStatus) {
+        match status.status {
+            PayloadValidationStatus::Invalid => self.invalid += 1,
+            PayloadValidationStatus::Syncing => self.syncing += 1,
+            _ => (),
+        }
+    }
+}
+
+fn update(context: &RpcApiContext, f: impl FnOnce(&mut EngineMetrics)) {
+    f(&mut context
+        .engine_metrics
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+pub(crate) fn record_fork_choice_latency(context: &RpcApiContext, latency: Duration) {
+    update(context, |metrics| {
+        metrics.fork_choice_latency.record(latency)
+    })
+}
+
+pub(crate) fn record_fork_choice_status(context: &RpcApiContext, status: &PayloadStatus) {
+    update(context, |metrics| {
+        metrics.fork_choice_updated.record(status)
+    })
+}
+
+pub(crate) fn record_invalid_fork_choice(context: &RpcApiContext) {
+    update(context, |metrics| metrics.fork_choice_updated.invalid += 1)
+}
+
+pub(crate) fn record_refused_reorg(context: &RpcApiContext) {
+    update(context, |metrics| metrics.refused_reorgs += 1)
+}
+
+pub(crate) fn record_fork_choice_anomaly(context: &RpcApiContext, anomaly: &ForkChoiceAnomaly) {
+    update(context, |metrics| {
+        metrics.fork_choice_anomalies.record(anomaly)
+    })
+}
+
+pub(crate) fn record_new_payload_status(context: &RpcApiContext, status: &PayloadStatus) {
+    update(context, |metrics| metrics.new_payload.record(status))
+}
+
+pub(crate) fn record_built_payload(
+    context: &RpcApiContext,
+    build_time: Duration,
+    value: U256,
+    deadline_margin_ms: Option<i64>,
+) {
+    update(context, |metrics| {
+        metrics.payload_build_time.record(build_time);
+        metrics.last_payload_value = value;
+        if deadline_margin_ms.is_some() {
+            metrics.last_payload_deadline_margin_ms = deadline_margin_ms;
+        }
+        if deadline_margin_ms.is_some_and(|margin| margin < 0) {
+            metrics.late_payloads += 1;
+        }
+    })
+}
+
+pub fn engine_metrics(context: &RpcApiContext) -> EngineMetrics {
+    context
+        .engine_metrics
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+pub struct GetEngineMetricsRequest;
+
+impl RpcHandler for GetEngineMetricsRequest {
+    fn parse(_params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        Ok(GetEngineMetricsRequest)
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        serde_json::to_value(engine_metrics(&context))
+            .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::default_context_with_storage;
+    use ethrex_storage::{EngineType, Store};
+
+    #[test]
+    fn latencies_are_counted_in_every_bucket_above_them() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_millis(30));
+        histogram.record(Duration::from_secs(10));
+        assert_eq!(histogram.buckets, [0, 0, 0, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!((histogram.count, histogram.sum_ms), (2, 10_030));
+    }
+
+    #[test]
+    fn only_invalid_and_syncing_statuses_are_counted() {
+        let mut counts = StatusCounts::default();
+        counts.record(&PayloadStatus::syncing());
+        counts.record(&PayloadStatus::valid_with_hash(Default::default()));
+        counts.record(&PayloadStatus::invalid_with_err("bad"));
+        assert_eq!((counts.invalid, counts.syncing), (1, 1));
+    }
+
+    #[test]
+    fn metrics_are_kept_per_node() {
+        let storage = Store::new("", EngineType::InMemory).unwrap();
+        let node = default_context_with_storage(storage.clone());
+        let other_node = default_context_with_storage(storage);
+        record_refused_reorg(&node);
+        assert_eq!(engine_metrics(&node).refused_reorgs, 1);
+        assert_eq!(engine_metrics(&other_node).refused_reorgs, 0);
+    }
+}
@@ -1,10 +1,33 @@
+pub mod anomalies;
+pub mod blobs;
+pub mod client_version;
 pub mod exchange_transition_config;
 pub mod fork_choice;
+pub mod metrics;
 pub mod payload;
 
 use crate::{utils::RpcRequest, RpcApiContext, RpcErr, RpcHandler};
 use serde_json::{json, Value};
+use tracing::{debug, warn};
 
+/// Engine methods served by the node, returned to the consensus client on
+/// engine_exchangeCapabilities, which is not listed itself
+pub const CAPABILITIES: [&str; 12] = [
+    "engine_forkchoiceUpdatedV1",
+    "engine_forkchoiceUpdatedV2",
+    "engine_forkchoiceUpdatedV3",
+    "engine_newPayloadV3",
+    "engine_newPayloadV4",
+    "engine_exchangeTransitionConfigurationV1",
+    "engine_getPayloadV3",
+    "engine_getPayloadV4",
+    "engine_getPayloadBodiesByHashV1",
+    "engine_getPayloadBodiesByRangeV1",
+    "engine_getClientVersionV1",
+    "engine_getBlobsV1",
+];
+
+/// Engine methods supported by the consensus client
 pub type ExchangeCapabilitiesRequest = Vec<String>;
 
 impl From<ExchangeCapabilitiesRequest> for RpcRequest {
@@ -31,6 +54,21 @@ impl RpcHandler for ExchangeCapabilitiesRequest {
     }
 
     fn handle(&self, _context: RpcApiContext) -> Result<Value, RpcErr> {
-        Ok(json!(*self))
+        let unsupported: Vec<&str> = self
+            .iter()
+            .map(String::as_str)
+            .filter(|method| !CAPABILITIES.contains(method))
+            .collect();
+        if !unsupported.is_empty() {
+            warn!("Consensus client methods not supported by the node: {unsupported:?}");
+        }
+        let unused: Vec<&str> = CAPABILITIES
+            .into_iter()
+            .filter(|method| !self.iter().any(|cl_method| cl_method == method))
+            .collect();
+        if !unused.is_empty() {
+            debug!("Node methods not supported by the consensus client: {unused:?}");
+        }
+        Ok(json!(CAPABILITIES))
     }
 }
@@ -1,12 +1,27 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use ethrex_blockchain::add_block;
 use ethrex_blockchain::error::ChainError;
-use ethrex_blockchain::payload::build_payload;
-use ethrex_core::types::Fork;
-use ethrex_core::{H256, U256};
+use ethrex_blockchain::{payload::build_payload, payload_job::BuiltPayload};
+use ethrex_core::types::{
+    compute_requests_hash, Block, BlockHash, BlockNumber, EncodedRequests, Fork,
+};
+use ethrex_core::{
+    errors::{CodedError, ErrorCode},
+    serde_utils, H256, U256,
+};
+use ethrex_storage::Store;
 use serde_json::Value;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn, Span};
 
-use crate::types::payload::ExecutionPayloadResponse;
+use super::metrics;
+use crate::types::payload::{
+    ExecutionPayloadBodyV1, ExecutionPayloadResponse, ExecutionPayloadResponseV4,
+    PayloadValidationStatus,
+};
 use crate::utils::RpcRequest;
 use crate::RpcApiContext;
 use crate::{
@@ -14,6 +29,88 @@ use crate::{
     RpcErr, RpcHandler,
 };
 
+/// Time a payload can take to be executed before answering SYNCING and finishing its validation
+/// in the background, consensus clients time out engine_newPayload calls after 8 seconds
+pub const NEW_PAYLOAD_EXECUTION_BUDGET: Duration = Duration::from_secs(6);
+/// Maximum amount of validated payloads whose status is remembered
+pub const MAX_RECENT_PAYLOADS: usize = 512;
+/// Maximum amount of invalid blocks tracked to reject their descendants
+pub const MAX_INVALID_BLOCKS: usize = 512;
+/// Maximum amount of block bodies that can be requested at once with engine_getPayloadBodies
+pub const GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE: usize = 1024;
+
+/// Payloads being validated in the background and the status of the last validated ones.
+/// Consensus clients often send the same payload again, whose status is then returned without
+/// decoding or executing it again.
+#[derive(Debug, Default)]
+pub struct PayloadValidations {
+    in_progress: HashSet<BlockHash>,
+    /// Status of the last payloads found to be valid or invalid, oldest first
+    recent: VecDeque<(BlockHash, PayloadStatus)>,
+    /// Latest valid ancestor of the blocks found to be invalid or to descend from an invalid one,
+    /// if they have one in the canonical chain
+    invalid_blocks: HashMap<BlockHash, Option<BlockHash>>,
+    /// Invalid blocks in the order they were found, so the oldest one is forgotten first
+    invalid_order: VecDeque<BlockHash>,
+}
+
+pub type PayloadValidationCache = Arc<Mutex<PayloadValidations>>;
+
+impl PayloadValidations {
+    fn recent_status(&self, block_hash: BlockHash) -> Option<PayloadStatus> {
+        self.recent
+            .iter()
+            .find(|(hash, _)| *hash == block_hash)
+            .map(|(_, status)| status.clone())
+    }
+
+    /// Records the result of a finished validation, a syncing payload is validated again
+    fn finish(&mut self, block_hash: BlockHash, status: &Result<PayloadStatus, RpcErr>) {
+        self.in_progress.remove(&block_hash);
+        if let Ok(
+            status @ PayloadStatus {
+                status: PayloadValidationStatus::Valid | PayloadValidationStatus::Invalid,
+                ..
+            },
+        ) = status
+        {
+            if self.recent.len() >= MAX_RECENT_PAYLOADS {
+                self.recent.pop_front();
+            }
+            self.recent.push_back((block_hash, status.clone()));
+            if let PayloadStatus {
+                status: PayloadValidationStatus::Invalid,
+                latest_valid_hash,
+                ..
+            } = status
+            {
+                self.mark_invalid(block_hash, *latest_valid_hash);
+            }
+        }
+    }
+
+    /// Returns the latest valid ancestor of the block if it is invalid or descends from an invalid block
+    fn invalid_ancestor(&self, block_hash: BlockHash) -> Option<Option<BlockHash>> {
+        self.invalid_blocks.get(&block_hash).copied()
+    }
+
+    fn mark_invalid(&mut self, block_hash: BlockHash, latest_valid_hash: Option<BlockHash>) {
+        if self
+            .invalid_blocks
+            .insert(block_hash, latest_valid_hash)
+            .is_some()
+        {
+            return;
+        }
+        self.invalid_order.push_back(block_hash);
+        if self.invalid_order.len() > MAX_INVALID_BLOCKS {
+            if let Some(oldest) = self.invalid_order.pop_front() {
+                self.invalid_blocks.remove(&oldest);
+            }
+        }
+    }
+}
+
 pub struct NewPayloadV3Request {
     pub payload: ExecutionPayloadV3,
     pub expected_blob_versioned_hashes: Vec<H256>,
@@ -57,105 +154,310 @@ impl RpcHandler for NewPayloadV3Request {
     }
 
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
-        let storage = &context.storage;
+        let payload_status = self.validate(context.clone())?;
+        metrics::record_new_payload_status(&context, &payload_status);
+        serde_json::to_value(payload_status).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
 
+impl NewPayloadV3Request {
+    fn validate(&self, context: RpcApiContext) -> Result<PayloadStatus, RpcErr> {
         let block_hash = self.payload.block_hash;
         info!("Received new payload with block hash: {block_hash:#x}");
 
         let block = match self
             .payload
             .clone()
-            .into_block(self.parent_beacon_block_root)
+            .into_block(self.parent_beacon_block_root, None)
         {
             Ok(block) => block,
             Err(error) => {
-                let result = PayloadStatus::invalid_with_err(&error.to_string());
-                return serde_json::to_value(result)
-                    .map_err(|error| RpcErr::Internal(error.to_string()));
+                return Ok(PayloadStatus::invalid_with_err(&error.to_string()));
             }
         };
 
-        // Payload Validation
-
-        // Check timestamp is post Cancun fork
-        let chain_config = storage.get_chain_config()?;
+        // Check timestamp is in the Cancun fork, Prague payloads are sent with engine_newPayloadV4
+        let chain_config = context.storage.get_chain_config()?;
         let current_fork = chain_config.get_fork(block.header.timestamp);
-        if current_fork < Fork::Cancun {
+        if current_fork != Fork::Cancun {
             return Err(RpcErr::UnsuportedFork(format!("{current_fork:?}")));
         }
 
-        // Check that block_hash is valid
-        let actual_block_hash = block.hash();
-        if block_hash != actual_block_hash {
-            let result = PayloadStatus::invalid_with_err("Invalid block hash");
-            return serde_json::to_value(result)
-                .map_err(|error| RpcErr::Internal(error.to_string()));
-        }
+        validate_payload(
+            block,
+            block_hash,
+            &self.expected_blob_versioned_hashes,
+            context,
+        )
+    }
+}
 
-        info!("Block hash {block_hash} is valid");
-        // Concatenate blob versioned hashes lists (tx.blob_versioned_hashes) of each blob transaction included in the payload, respecting the order of inclusion
-        // and check that the resulting array matches expected_blob_versioned_hashes
-        let blob_versioned_hashes: Vec<H256> = block
-            .body
-            .transactions
+pub struct NewPayloadV4Request {
+    pub payload: ExecutionPayloadV3,
+    pub expected_blob_versioned_hashes: Vec<H256>,
+    pub parent_beacon_block_root: H256,
+    /// Requests of each type triggered by the payload, prefixed by their type
+    pub execution_requests: Vec<EncodedRequests>,
+}
+
+impl From<NewPayloadV4Request> for RpcRequest {
+    fn from(val: NewPayloadV4Request) -> Self {
+        let execution_requests: Vec<String> = val
+            .execution_requests
             .iter()
-            .flat_map(|tx| tx.blob_versioned_hashes())
+            .map(|requests| format!("0x{}", hex::encode(&requests.0)))
             .collect();
-        if self.expected_blob_versioned_hashes != blob_versioned_hashes {
-            let result = PayloadStatus::invalid_with_err("Invalid blob_versioned_hashes");
-            return serde_json::to_value(result)
-                .map_err(|error| RpcErr::Internal(error.to_string()));
-        }
-
-        // Return the valid message directly if we have it.
-        if storage.get_block_header_by_hash(block_hash)?.is_some() {
-            let result = PayloadStatus::valid_with_hash(block_hash);
-            return serde_json::to_value(result)
-                .map_err(|error| RpcErr::Internal(error.to_string()));
-        }
-
-        // Execute and store the block
-        info!("Executing payload with block hash: {block_hash:#x}");
-        let payload_status = match add_block(&block, storage) {
-            Err(ChainError::ParentNotFound) => Ok(PayloadStatus::syncing()),
-            // Under the current implementation this is not possible: we always calculate the state
-            // transition of any new payload as long as the parent is present. If we received the
-            // parent payload but it was stashed, then new payload would stash this one too, with a
-            // ParentNotFoundError.
-            Err(ChainError::ParentStateNotFound) => {
-                let e = "Failed to obtain parent state";
-                error!("{e} for block {block_hash}");
-                Err(RpcErr::Internal(e.to_string()))
-            }
-            Err(ChainError::InvalidBlock(error)) => {
-                warn!("Error adding block: {error}");
-                // TODO(#982): this is only valid for the cases where the parent was found, but fully invalid ones may also happen.
-                Ok(PayloadStatus::invalid_with(
-                    block.header.parent_hash,
-                    error.to_string(),
-                ))
-            }
-            Err(ChainError::EvmError(error)) => {
-                warn!("Error executing block: {error}");
-                Ok(PayloadStatus::invalid_with(
-                    block.header.parent_hash,
-                    error.to_string(),
-                ))
+        RpcRequest {
+            method: "engine_newPayloadV4".to_string(),
+            params: Some(vec![
+                serde_json::json!(val.payload),
+                serde_json::json!(val.expected_blob_versioned_hashes),
+                serde_json::json!(val.parent_beacon_block_root),
+                serde_json::json!(execution_requests),
+            ]),
+            ..Default::default()
+        }
+    }
+}
+
+impl RpcHandler for NewPayloadV4Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 4 {
+            return Err(RpcErr::BadParams("Expected 4 params".to_owned()));
+        }
+        let execution_requests: Vec<EncodedRequests> =
+            serde_utils::bytes::vec::deserialize(params[3].clone())
+                .map_err(|_| RpcErr::WrongParam("execution_requests".to_string()))?
+                .into_iter()
+                .map(EncodedRequests)
+                .collect();
+        // Each request type must be sent once, in ascending order, and only if it has requests
+        let mut previous_type = None;
+        for requests in &execution_requests {
+            if requests.is_empty() || requests.request_type() <= previous_type {
+                return Err(RpcErr::WrongParam("execution_requests".to_string()));
             }
-            Err(ChainError::StoreError(error)) => {
-                warn!("Error storing block: {error}");
-                Err(RpcErr::Internal(error.to_string()))
+            previous_type = requests.request_type();
+        }
+        Ok(NewPayloadV4Request {
+            payload: serde_json::from_value(params[0].clone())
+                .map_err(|_| RpcErr::WrongParam("payload".to_string()))?,
+            expected_blob_versioned_hashes: serde_json::from_value(params[1].clone())
+                .map_err(|_| RpcErr::WrongParam("expected_blob_versioned_hashes".to_string()))?,
+            parent_beacon_block_root: serde_json::from_value(params[2].clone())
+                .map_err(|_| RpcErr::WrongParam("parent_beacon_block_root".to_string()))?,
+            execution_requests,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let payload_status = self.validate(context.clone())?;
+        metrics::record_new_payload_status(&context, &payload_status);
+        serde_json::to_value(payload_status).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+impl NewPayloadV4Request {
+    fn validate(&self, context: RpcApiContext) -> Result<PayloadStatus, RpcErr> {
+        let block_hash = self.payload.block_hash;
+        info!("Received new payload with block hash: {block_hash:#x}");
+
+        // The block hash only matches if the header commits to the received requests,
+        // executing the block then checks they are the ones it triggers
+        let requests_hash = compute_requests_hash(&self.execution_requests);
+        let block = match self
+            .payload
+            .clone()
+            .into_block(self.parent_beacon_block_root, Some(requests_hash))
+        {
+            Ok(block) => block,
+            Err(error) => {
+                return Ok(PayloadStatus::invalid_with_err(&error.to_string()));
             }
-            Ok(()) => {
-                info!("Block with hash {block_hash} executed and added to storage succesfully");
-                Ok(PayloadStatus::valid_with_hash(block_hash))
+        };
+
+        // Check timestamp is post Prague fork
+        let chain_config = context.storage.get_chain_config()?;
+        let current_fork = chain_config.get_fork(block.header.timestamp);
+        if current_fork < Fork::Prague {
+            return Err(RpcErr::UnsuportedFork(format!("{current_fork:?}")));
+        }
+
+        validate_payload(
+            block,
+            block_hash,
+            &self.expected_blob_versioned_hashes,
+            context,
+        )
+    }
+}
+
+/// Checks the concatenation of the blob versioned hashes of each blob transaction included in
+/// the payload, respecting the order of inclusion, matches the expected blob versioned hashes
+fn check_blob_versioned_hashes(block: &Block, expected: &[H256]) -> Result<(), String> {
+    let blob_versioned_hashes: Vec<H256> = block
+        .body
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.blob_versioned_hashes())
+        .collect();
+    if blob_versioned_hashes.len() != expected.len() {
+        return Err(format!(
+            "Invalid blob_versioned_hashes: expected {} hashes, the payload's blob transactions have {}",
+            expected.len(),
+            blob_versioned_hashes.len()
+        ));
+    }
+    match blob_versioned_hashes
+        .iter()
+        .zip(expected)
+        .position(|(hash, expected)| hash != expected)
+    {
+        Some(index) => Err(format!(
+            "Invalid blob_versioned_hashes: expected {:#x} at index {index}, the payload has {:#x}",
+            expected[index], blob_versioned_hashes[index]
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Validates the payload's block, executing it and storing it if it wasn't known
+fn validate_payload(
+    block: Block,
+    block_hash: BlockHash,
+    expected_blob_versioned_hashes: &[H256],
+    context: RpcApiContext,
+) -> Result<PayloadStatus, RpcErr> {
+    let storage = &context.storage;
+
+    // Check that block_hash is valid
+    let actual_block_hash = block.hash();
+    if block_hash != actual_block_hash {
+        return Ok(PayloadStatus::invalid_with_err("Invalid block hash"));
+    }
+
+    info!("Block hash {block_hash} is valid");
+    if let Err(error) = check_blob_versioned_hashes(&block, expected_blob_versioned_hashes) {
+        return Ok(PayloadStatus::invalid_with_err(&error));
+    }
+
+    // Return the valid message directly if we have it.
+    if storage.get_block_header_by_hash(block_hash)?.is_some() {
+        return Ok(PayloadStatus::valid_with_hash(block_hash));
+    }
+
+    let validations = &context.payload_validations;
+    {
+        let mut validations = validations
+            .lock()
+            .map_err(|error| RpcErr::Internal(error.to_string()))?;
+        // The cached status is only returned once the payload is known to have the given hash
+        if let Some(status) = validations.recent_status(block_hash) {
+            debug!("Payload {block_hash:#x} was already validated");
+            return Ok(status);
+        }
+        // Descendants of an invalid block are invalid too, without having to execute them
+        let parent_hash = block.header.parent_hash;
+        if let Some(latest_valid_hash) = validations.invalid_ancestor(parent_hash) {
+            let error = format!("Links to previously rejected block {parent_hash:#x}");
+            let status = match latest_valid_hash {
+                Some(latest_valid_hash) => PayloadStatus::invalid_with(latest_valid_hash, error),
+                None => PayloadStatus::invalid_with_err(&error),
+            };
+            validations.finish(block_hash, &Ok(status.clone()));
+            return Ok(status);
+        }
+        if !validations.in_progress.insert(block_hash) {
+            return Ok(PayloadStatus::syncing());
+        }
+    }
+
+    // Execute and store the block in the background, so payloads that take too long to execute
+    // can be answered with SYNCING before the consensus client times out
+    info!("Executing payload with block hash: {block_hash:#x}");
+    let (sender, receiver) = mpsc::channel();
+    let (storage, validations) = (storage.clone(), validations.clone());
+    // The validation keeps the span of the request even if it outlives it
+    let span = Span::current();
+    std::thread::spawn(move || {
+        let _span = span.entered();
+        let payload_status = execute_payload(&block, &storage);
+        match validations.lock() {
+            Ok(mut validations) => validations.finish(block_hash, &payload_status),
+            Err(error) => {
+                error!("Failed to record validation of payload {block_hash:#x}: {error}")
             }
-        }?;
+        }
+        let _ = sender.send(payload_status);
+    });
+    let payload_status = match receiver.recv_timeout(NEW_PAYLOAD_EXECUTION_BUDGET) {
+        Ok(payload_status) => payload_status,
+        Err(RecvTimeoutError::Timeout) => {
+            warn!("Payload {block_hash:#x} is taking too long to execute, validating it in the background");
+            Ok(PayloadStatus::syncing())
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(RpcErr::Internal(
+            "Payload validation stopped unexpectedly".to_owned(),
+        )),
+    }?;
 
-        serde_json::to_value(payload_status).map_err(|error| RpcErr::Internal(error.to_string()))
+    Ok(payload_status)
+}
+
+/// Executes the payload's block and stores it if it is valid
+fn execute_payload(block: &Block, storage: &Store) -> Result<PayloadStatus, RpcErr> {
+    let block_hash = block.hash();
+    match add_block(block, storage) {
+        Err(ChainError::ParentNotFound) => Ok(PayloadStatus::syncing()),
+        // Under the current implementation this is not possible: we always calculate the state
+        // transition of any new payload as long as the parent is present. If we received the
+        // parent payload but it was stashed, then new payload would stash this one too, with a
+        // ParentNotFoundError.
+        Err(ChainError::ParentStateNotFound) => {
+            let e = "Failed to obtain parent state";
+            error!("{e} for block {block_hash}");
+            Err(RpcErr::Internal(e.to_string()))
+        }
+        Err(ChainError::InvalidBlock(error)) => {
+            warn!("Error adding block: {error}");
+            invalid_payload_status(block, storage, error.to_string())
+        }
+        // The execution may also fail reading the state, which doesn't make the block invalid
+        Err(ChainError::EvmError(error))
+            if matches!(error.code(), ErrorCode::Invalid | ErrorCode::Execution) =>
+        {
+            warn!("Error executing block: {error}");
+            invalid_payload_status(block, storage, error.to_string())
+        }
+        Err(error) => {
+            warn!("Error storing block: {error}");
+            Err(RpcErr::from_coded(&error))
+        }
+        Ok(()) => {
+            info!("Block with hash {block_hash} executed and added to storage succesfully");
+            Ok(PayloadStatus::valid_with_hash(block_hash))
+        }
     }
 }
 
+/// Status of a payload that failed its execution, pointing to its latest valid ancestor in the
+/// canonical chain
+fn invalid_payload_status(
+    block: &Block,
+    storage: &Store,
+    error: String,
+) -> Result<PayloadStatus, RpcErr> {
+    Ok(
+        match storage.find_canonical_ancestor(block.header.parent_hash)? {
+            Some(latest_valid_hash) => PayloadStatus::invalid_with(latest_valid_hash, error),
+            None => PayloadStatus::invalid_with_err(&error),
+        },
+    )
+}
+
 impl From<GetPayloadV3Request> for RpcRequest {
     fn from(val: GetPayloadV3Request) -> Self {
         RpcRequest {
@@ -167,6 +469,165 @@ impl From<GetPayloadV3Request> for RpcRequest {
 }
 
 impl RpcHandler for GetPayloadV3Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        Ok(GetPayloadV3Request {
+            payload_id: parse_payload_id(params)?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let built = retrieve_payload(self.payload_id, &context, |fork| *fork == Fork::Cancun)?;
+        serde_json::to_value(ExecutionPayloadResponse {
+            execution_payload: ExecutionPayloadV3::from_block(built.block),
+            block_value: built.output.block_value,
+            blobs_bundle: built.output.blobs_bundle,
+            should_override_builder: false,
+        })
+        .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+pub struct GetPayloadV4Request {
+    pub payload_id: u64,
+}
+
+impl From<GetPayloadV4Request> for RpcRequest {
+    fn from(val: GetPayloadV4Request) -> Self {
+        RpcRequest {
+            method: "engine_getPayloadV4".to_string(),
+            params: Some(vec![serde_json::json!(U256::from(val.payload_id))]),
+            ..Default::default()
+        }
+    }
+}
+
+impl RpcHandler for GetPayloadV4Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        Ok(GetPayloadV4Request {
+            payload_id: parse_payload_id(params)?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let built = retrieve_payload(self.payload_id, &context, |fork| *fork >= Fork::Prague)?;
+        serde_json::to_value(ExecutionPayloadResponseV4 {
+            response: ExecutionPayloadResponse {
+                execution_payload: ExecutionPayloadV3::from_block(built.block),
+                block_value: built.output.block_value,
+                blobs_bundle: built.output.blobs_bundle,
+                should_override_builder: false,
+            },
+            execution_requests: built
+                .output
+                .requests
+                .into_iter()
+                .map(|requests| requests.0)
+                .collect(),
+        })
+        .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+fn parse_payload_id(params: &Option<Vec<Value>>) -> Result<u64, RpcErr> {
+    let params = params
+        .as_ref()
+        .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+    if params.len() != 1 {
+        return Err(RpcErr::BadParams("Expected 1 param".to_owned()));
+    };
+    let Ok(hex_str) = serde_json::from_value::<String>(params[0].clone()) else {
+        return Err(RpcErr::BadParams(
+            "Expected param to be a string".to_owned(),
+        ));
+    };
+    // Check that the hex string is 0x prefixed
+    let Some(hex_str) = hex_str.strip_prefix("0x") else {
+        return Err(RpcErr::BadHexFormat(0));
+    };
+    // Parse hex string
+    let Ok(payload_id) = u64::from_str_radix(hex_str, 16) else {
+        return Err(RpcErr::BadHexFormat(0));
+    };
+    Ok(payload_id)
+}
+
+/// Stops building the payload with the given id and returns it, if it belongs to a fork served
+/// by the requested version of engine_getPayload. Otherwise the payload is left to be
+/// retrieved with the right version.
+fn retrieve_payload(
+    payload_id: u64,
+    context: &RpcApiContext,
+    supported_fork: impl Fn(&Fork) -> bool,
+) -> Result<BuiltPayload, RpcErr> {
+    info!("Requested payload with id: {payload_id:#018x}");
+    let chain_config = context.storage.get_chain_config()?;
+    let check_fork = |timestamp| {
+        let fork = chain_config.get_fork(timestamp);
+        if supported_fork(&fork) {
+            Ok(())
+        } else {
+            Err(RpcErr::UnsuportedFork(format!("{fork:?}")))
+        }
+    };
+    if let Some(timestamp) = context.payload_manager.timestamp(payload_id) {
+        check_fork(timestamp)?;
+    }
+    let built = match context.payload_manager.take(payload_id) {
+        Some(job) => job.finish(&context.storage),
+        // Payloads stored before a restart are no longer being built
+        None => {
+            let Some(mut payload) = context.storage.get_payload(payload_id)? else {
+                return Err(RpcErr::UnknownPayload(format!(
+                    "Payload with id {payload_id:#018x} not found"
+                )));
+            };
+            check_fork(payload.header.timestamp)?;
+            let build_start = Instant::now();
+            build_payload(
+                &mut payload,
+                &context.storage,
+                context.payload_manager.config(),
+            )
+            .map(|output| BuiltPayload {
+                block: payload,
+                output,
+                build_time: build_start.elapsed(),
+                deadline_margin_ms: None,
+            })
+        }
+    }
+    .map_err(|err| RpcErr::Internal(err.to_string()))?;
+    metrics::record_built_payload(
+        context,
+        built.build_time,
+        built.output.block_value,
+        built.deadline_margin_ms,
+    );
+    // The payload is only kept until the consensus client retrieves it
+    context.storage.remove_payload(payload_id)?;
+    info!(
+        "Built block {} paying {} wei in priority fees to fee recipient {:#x}",
+        built.block.header.number, built.output.block_value, built.block.header.coinbase
+    );
+    match built.deadline_margin_ms {
+        Some(margin) if margin < 0 => warn!(
+            "Block {} was sealed {}ms after the getPayload deadline",
+            built.block.header.number, -margin
+        ),
+        Some(margin) => info!(
+            "Block {} was sealed {margin}ms before the getPayload deadline",
+            built.block.header.number
+        ),
+        None => {}
+    }
+    Ok(built)
+}
+
+pub struct GetPayloadBodiesByHashV1Request {
+    pub hashes: Vec<BlockHash>,
+}
+
+impl RpcHandler for GetPayloadBodiesByHashV1Request {
     fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
         let params = params
             .as_ref()
@@ -174,38 +635,273 @@ impl RpcHandler for GetPayloadV3Request {
         if params.len() != 1 {
             return Err(RpcErr::BadParams("Expected 1 param".to_owned()));
         };
-        let Ok(hex_str) = serde_json::from_value::<String>(params[0].clone()) else {
-            return Err(RpcErr::BadParams(
-                "Expected param to be a string".to_owned(),
-            ));
+        let hashes: Vec<BlockHash> = serde_json::from_value(params[0].clone())
+            .map_err(|_| RpcErr::WrongParam("block_hashes".to_string()))?;
+        if hashes.len() > GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE {
+            return Err(RpcErr::TooLargeRequest(GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE));
+        }
+        Ok(GetPayloadBodiesByHashV1Request { hashes })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!("Requested {} payload bodies by hash", self.hashes.len());
+        // Unknown blocks are returned as null
+        let bodies = self
+            .hashes
+            .iter()
+            .map(|hash| {
+                Ok(context
+                    .storage
+                    .get_block_body_by_hash(*hash)?
+                    .map(ExecutionPayloadBodyV1::from))
+            })
+            .collect::<Result<Vec<_>, RpcErr>>()?;
+        serde_json::to_value(bodies).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+pub struct GetPayloadBodiesByRangeV1Request {
+    pub start: BlockNumber,
+    pub count: u64,
+}
+
+impl RpcHandler for GetPayloadBodiesByRangeV1Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 2 {
+            return Err(RpcErr::BadParams("Expected 2 params".to_owned()));
         };
-        // Check that the hex string is 0x prefixed
-        let Some(hex_str) = hex_str.strip_prefix("0x") else {
-            return Err(RpcErr::BadHexFormat(0));
+        let start = serde_utils::u64::hex_str::deserialize(params[0].clone())
+            .map_err(|_| RpcErr::WrongParam("start".to_string()))?;
+        let count = serde_utils::u64::hex_str::deserialize(params[1].clone())
+            .map_err(|_| RpcErr::WrongParam("count".to_string()))?;
+        if start < 1 {
+            return Err(RpcErr::WrongParam("start".to_string()));
+        }
+        if count < 1 {
+            return Err(RpcErr::WrongParam("count".to_string()));
+        }
+        if count > GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE as u64 {
+            return Err(RpcErr::TooLargeRequest(GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE));
+        }
+        Ok(GetPayloadBodiesByRangeV1Request { start, count })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested {} payload bodies starting at block {}",
+            self.count, self.start
+        );
+        // The range is cut at the latest block instead of returning trailing nulls
+        let latest = context
+            .storage
+            .get_latest_block_number()?
+            .unwrap_or_default();
+        let last = latest.min(self.start.saturating_add(self.count - 1));
+        let bodies = (self.start..=last)
+            .map(|number| {
+                Ok(context
+                    .storage
+                    .get_block_body(number)?
+                    .map(ExecutionPayloadBodyV1::from))
+            })
+            .collect::<Result<Vec<_>, RpcErr>>()?;
+        serde_json::to_value(bodies).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::context_with_genesis;
+
+    #[test]
+    fn execution_requests_are_sent_once_per_type_in_order() {
+        let parse = |execution_requests: &[&str]| {
+            let params = vec![
+                serde_json::to_value(ExecutionPayloadV3::from_block(Block::default())).unwrap(),
+                serde_json::json!([]),
+                serde_json::json!(H256::zero()),
+                serde_json::json!(execution_requests),
+            ];
+            NewPayloadV4Request::parse(&Some(params))
         };
-        // Parse hex string
-        let Ok(payload_id) = u64::from_str_radix(hex_str, 16) else {
-            return Err(RpcErr::BadHexFormat(0));
+        let request = parse(&["0x00aa", "0x02bb"]).unwrap();
+        assert_eq!(
+            request.execution_requests,
+            vec![
+                EncodedRequests::new(0, &[0xaa]),
+                EncodedRequests::new(2, &[0xbb])
+            ]
+        );
+        assert!(parse(&[]).unwrap().execution_requests.is_empty());
+        // Out of order, repeated and empty request types are rejected
+        assert!(parse(&["0x02bb", "0x00aa"]).is_err());
+        assert!(parse(&["0x00aa", "0x00bb"]).is_err());
+        assert!(parse(&["0x01"]).is_err());
+    }
+
+    #[test]
+    fn blob_versioned_hashes_must_match_the_payload() {
+        use ethrex_core::types::{EIP4844Transaction, Transaction};
+        let blob_transaction = |hashes: Vec<H256>| {
+            Transaction::EIP4844Transaction(EIP4844Transaction {
+                blob_versioned_hashes: hashes,
+                ..Default::default()
+            })
         };
-        Ok(GetPayloadV3Request { payload_id })
+        let hashes: Vec<H256> = (1..=3).map(H256::repeat_byte).collect();
+        let mut block = Block::default();
+        block.body.transactions = vec![
+            blob_transaction(hashes[..2].to_vec()),
+            blob_transaction(hashes[2..].to_vec()),
+        ];
+        assert!(check_blob_versioned_hashes(&block, &hashes).is_ok());
+        let error = check_blob_versioned_hashes(&block, &hashes[..2]).unwrap_err();
+        assert!(error.contains("expected 2 hashes"));
+        let reordered = [hashes[0], hashes[2], hashes[1]];
+        let error = check_blob_versioned_hashes(&block, &reordered).unwrap_err();
+        assert!(error.contains("at index 1"));
+        assert!(check_blob_versioned_hashes(&Block::default(), &[]).is_ok());
     }
 
-    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
-        info!("Requested payload with id: {:#018x}", self.payload_id);
-        let Some(mut payload) = context.storage.get_payload(self.payload_id)? else {
-            return Err(RpcErr::UnknownPayload(format!(
-                "Payload with id {:#018x} not found",
-                self.payload_id
-            )));
+    #[test]
+    fn validated_payloads_are_remembered() {
+        let mut validations = PayloadValidations::default();
+        let (valid, invalid, syncing) = (H256::random(), H256::random(), H256::random());
+        validations.in_progress.extend([valid, invalid, syncing]);
+        validations.finish(valid, &Ok(PayloadStatus::valid_with_hash(valid)));
+        validations.finish(
+            invalid,
+            &Ok(PayloadStatus::invalid_with(H256::zero(), "bad".to_owned())),
+        );
+        validations.finish(syncing, &Ok(PayloadStatus::syncing()));
+        assert!(validations.in_progress.is_empty());
+        assert!(validations.recent_status(syncing).is_none());
+        assert!(matches!(
+            validations.recent_status(valid),
+            Some(PayloadStatus {
+                status: PayloadValidationStatus::Valid,
+                ..
+            })
+        ));
+        assert!(matches!(
+            validations.recent_status(invalid),
+            Some(PayloadStatus {
+                status: PayloadValidationStatus::Invalid,
+                ..
+            })
+        ));
+
+        // The oldest payloads are forgotten first
+        for _ in 0..MAX_RECENT_PAYLOADS {
+            let hash = H256::random();
+            validations.finish(
+                hash,
+                &Ok(PayloadStatus::invalid_with(H256::zero(), "bad".to_owned())),
+            );
+        }
+        assert_eq!(validations.recent.len(), MAX_RECENT_PAYLOADS);
+        assert!(validations.recent_status(invalid).is_none());
+    }
+
+    #[test]
+    fn descendants_of_invalid_payloads_are_invalid() {
+        let mut validations = PayloadValidations::default();
+        let (latest_valid, invalid, descendant) = (H256::random(), H256::random(), H256::random());
+        validations.finish(
+            invalid,
+            &Ok(PayloadStatus::invalid_with(latest_valid, "bad".to_owned())),
+        );
+        assert_eq!(
+            validations.invalid_ancestor(invalid),
+            Some(Some(latest_valid))
+        );
+        validations.mark_invalid(descendant, Some(latest_valid));
+        assert_eq!(
+            validations.invalid_ancestor(descendant),
+            Some(Some(latest_valid))
+        );
+        // Invalid blocks without a canonical ancestor are tracked too
+        let orphan = H256::random();
+        validations.finish(orphan, &Ok(PayloadStatus::invalid_with_err("bad")));
+        assert_eq!(validations.invalid_ancestor(orphan), Some(None));
+        assert_eq!(validations.invalid_blocks.len(), 3);
+
+        for _ in 0..MAX_INVALID_BLOCKS {
+            validations.mark_invalid(H256::random(), Some(latest_valid));
+        }
+        assert!(validations.invalid_ancestor(invalid).is_none());
+        assert_eq!(validations.invalid_blocks.len(), MAX_INVALID_BLOCKS);
+        assert_eq!(validations.invalid_order.len(), MAX_INVALID_BLOCKS);
+    }
+
+    #[test]
+    fn descendants_of_invalid_payloads_without_canonical_ancestor_are_rejected() {
+        let context = context_with_genesis();
+        let invalid = H256::random();
+        context
+            .payload_validations
+            .lock()
+            .unwrap()
+            .finish(invalid, &Ok(PayloadStatus::invalid_with_err("bad")));
+        let mut block = Block::default();
+        block.header.parent_hash = invalid;
+        let block_hash = block.hash();
+        let status = validate_payload(block, block_hash, &[], context.clone()).unwrap();
+        assert!(matches!(
+            status,
+            PayloadStatus {
+                status: PayloadValidationStatus::Invalid,
+                latest_valid_hash: None,
+                ..
+            }
+        ));
+        // The descendant is tracked as invalid as well
+        assert_eq!(
+            context
+                .payload_validations
+                .lock()
+                .unwrap()
+                .invalid_ancestor(block_hash),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn cached_statuses_are_only_returned_for_matching_payloads() {
+        let context = context_with_genesis();
+        let genesis = context.storage.get_block_header(0).unwrap().unwrap();
+        let genesis_hash = genesis.compute_block_hash();
+        context.payload_validations.lock().unwrap().finish(
+            genesis_hash,
+            &Ok(PayloadStatus::valid_with_hash(genesis_hash)),
+        );
+
+        // A payload claiming a cached hash is still checked against it
+        let status =
+            validate_payload(Block::default(), genesis_hash, &[], context.clone()).unwrap();
+        assert!(matches!(
+            status,
+            PayloadStatus {
+                status: PayloadValidationStatus::Invalid,
+                ..
+            }
+        ));
+        // And the fork of a cached payload is still checked
+        let genesis_block =
+            Block::new(genesis, context.storage.get_block_body(0).unwrap().unwrap());
+        let request = NewPayloadV4Request {
+            payload: ExecutionPayloadV3::from_block(genesis_block),
+            expected_blob_versioned_hashes: vec![],
+            parent_beacon_block_root: H256::zero(),
+            execution_requests: vec![],
         };
-        let (blobs_bundle, block_value) = build_payload(&mut payload, &context.storage)
-            .map_err(|err| RpcErr::Internal(err.to_string()))?;
-        serde_json::to_value(ExecutionPayloadResponse {
-            execution_payload: ExecutionPayloadV3::from_block(payload),
-            block_value,
-            blobs_bundle,
-            should_override_builder: false,
-        })
-        .map_err(|error| RpcErr::Internal(error.to_string()))
+        assert!(matches!(
+            request.validate(context),
+            Err(RpcErr::UnsuportedFork(_))
+        ));
     }
 }
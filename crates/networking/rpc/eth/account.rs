@@ -54,15 +54,9 @@ impl RpcHandler for GetBalanceRequest {
             self.address, self.block
         );
 
-        let Some(block_number) = self.block.resolve_block_number(&context.storage)? else {
-            return Err(RpcErr::Internal(
-                "Could not resolve block number".to_owned(),
-            )); // Should we return Null here?
-        };
+        let state = self.block.resolve_state(&context.storage)?;
 
-        let account = context
-            .storage
-            .get_account_info(block_number, self.address)?;
+        let account = state.get_account_info(self.address)?;
         let balance = account.map(|acc| acc.balance).unwrap_or_default();
 
         serde_json::to_value(format!("{:#x}", balance))
@@ -89,16 +83,9 @@ impl RpcHandler for GetCodeRequest {
             self.address, self.block
         );
 
-        let Some(block_number) = self.block.resolve_block_number(&context.storage)? else {
-            return Err(RpcErr::Internal(
-                "Could not resolve block number".to_owned(),
-            )); // Should we return Null here?
-        };
+        let state = self.block.resolve_state(&context.storage)?;
 
-        let code = context
-            .storage
-            .get_code_by_account_address(block_number, self.address)?
-            .unwrap_or_default();
+        let code = state.get_code(self.address)?.unwrap_or_default();
 
         serde_json::to_value(format!("0x{:x}", code))
             .map_err(|error| RpcErr::Internal(error.to_string()))
@@ -125,15 +112,10 @@ impl RpcHandler for GetStorageAtRequest {
             self.storage_slot, self.address, self.block
         );
 
-        let Some(block_number) = self.block.resolve_block_number(&context.storage)? else {
-            return Err(RpcErr::Internal(
-                "Could not resolve block number".to_owned(),
-            )); // Should we return Null here?
-        };
+        let state = self.block.resolve_state(&context.storage)?;
 
-        let storage_value = context
-            .storage
-            .get_storage_at(block_number, self.address, self.storage_slot)?
+        let storage_value = state
+            .get_storage_at(self.address, self.storage_slot)?
             .unwrap_or_default();
         let storage_value = H256::from_uint(&storage_value);
         serde_json::to_value(format!("{:#x}", storage_value))
@@ -170,14 +152,10 @@ impl RpcHandler for GetTransactionCountRequest {
         let nonce = match pending_nonce {
             Some(nonce) => nonce,
             None => {
-                let Some(block_number) = self.block.resolve_block_number(&context.storage)? else {
-                    return serde_json::to_value("0x0")
-                        .map_err(|error| RpcErr::Internal(error.to_string()));
-                };
-
-                context
-                    .storage
-                    .get_nonce_by_account_address(block_number, self.address)?
+                let state = self.block.resolve_state(&context.storage)?;
+                state
+                    .get_account_info(self.address)?
+                    .map(|info| info.nonce)
                     .unwrap_or_default()
             }
         };
@@ -205,29 +183,22 @@ impl RpcHandler for GetProofRequest {
     }
 
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
-        let storage = &context.storage;
         info!(
             "Requested proof for account {} at block {} with storage keys: {:?}",
             self.address, self.block, self.storage_keys
         );
-        let Some(block_number) = self.block.resolve_block_number(storage)? else {
-            return Ok(Value::Null);
-        };
-        // Create account proof
-        let Some(account) = storage.get_account_state(block_number, self.address)? else {
-            return Ok(Value::Null);
-        };
-        let Some(account_proof) = storage.get_account_proof(block_number, &self.address)? else {
-            return Err(RpcErr::Internal("Could not get account proof".to_owned()));
-        };
+        let state = self.block.resolve_state(&context.storage)?;
+        // Missing accounts are proven absent by the path of their key, and reported as empty
+        // as required by EIP-1186
+        let account = state.get_account_state(self.address)?.unwrap_or_default();
+        let account_proof = state.get_account_proof(self.address)?;
         // Create storage proofs for all provided storage keys
         let mut storage_proofs = Vec::new();
         for storage_key in self.storage_keys.iter() {
-            let value = storage
-                .get_storage_at(block_number, self.address, *storage_key)?
+            let value = state
+                .get_storage_at(self.address, *storage_key)?
                 .unwrap_or_default();
-            let proof =
-                storage.get_storage_proof(self.address, account.storage_root, storage_key)?;
+            let proof = state.get_storage_proof(self.address, *storage_key)?;
             let storage_proof = StorageProof {
                 key: storage_key.into_uint(),
                 proof,
@@ -1,4 +1,4 @@
-use ethrex_blockchain::find_parent_header;
+use ethrex_blockchain::payload::calc_excess_blob_gas;
 use ethrex_rlp::encode::RLPEncode;
 use serde_json::Value;
 use tracing::info;
@@ -6,7 +6,7 @@ use tracing::info;
 use crate::{
     types::{
         block::RpcBlock,
-        block_identifier::{BlockIdentifier, BlockIdentifierOrHash},
+        block_identifier::{ensure_history_available, BlockIdentifier, BlockIdentifierOrHash},
         receipt::{RpcReceipt, RpcReceiptBlockInfo, RpcReceiptTxInfo},
     },
     utils::RpcErr,
@@ -75,6 +75,7 @@ impl RpcHandler for GetBlockByNumberRequest {
             Some(block_number) => block_number,
             _ => return Ok(Value::Null),
         };
+        ensure_history_available(storage, block_number)?;
         let header = storage.get_block_header(block_number)?;
         let body = storage.get_block_body(block_number)?;
         let (header, body) = match (header, body) {
@@ -91,6 +92,7 @@ impl RpcHandler for GetBlockByNumberRequest {
             hash,
             self.hydrated,
             total_difficulty.unwrap_or(U256::zero()),
+            storage,
         );
 
         serde_json::to_value(&block).map_err(|error| RpcErr::Internal(error.to_string()))
@@ -113,27 +115,31 @@ impl RpcHandler for GetBlockByHashRequest {
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
         let storage = &context.storage;
         info!("Requested block with hash: {:#x}", self.block);
-        let block_number = match storage.get_block_number(self.block)? {
-            Some(number) => number,
-            _ => return Ok(Value::Null),
-        };
-        let header = storage.get_block_header(block_number)?;
-        let body = storage.get_block_body(block_number)?;
+        // The block is looked up by hash so blocks of other forks are also served
+        let header = storage.get_block_header_by_hash(self.block)?;
+        let body = storage.get_block_body_by_hash(self.block)?;
         let (header, body) = match (header, body) {
             (Some(header), Some(body)) => (header, body),
+            (Some(header), None) => {
+                ensure_history_available(storage, header.number)?;
+                return Ok(Value::Null);
+            }
             // Block not found
             _ => return Ok(Value::Null),
         };
-        let hash = header.compute_block_hash();
+        let hash = self.block;
+        let canonical = storage.get_canonical_block_hash(header.number)? == Some(hash);
         // TODO (#307): Remove TotalDifficulty.
         let total_difficulty = storage.get_block_total_difficulty(hash)?;
-        let block = RpcBlock::build(
+        let mut block = RpcBlock::build(
             header,
             body,
             hash,
             self.hydrated,
             total_difficulty.unwrap_or(U256::zero()),
+            storage,
         );
+        block.canonical = canonical;
         serde_json::to_value(&block).map_err(|error| RpcErr::Internal(error.to_string()))
     }
 }
@@ -160,6 +166,7 @@ impl RpcHandler for GetBlockTransactionCountRequest {
             Some(block_number) => block_number,
             _ => return Ok(Value::Null),
         };
+        ensure_history_available(&context.storage, block_number)?;
         let block_body = match context.storage.get_block_body(block_number)? {
             Some(block_body) => block_body,
             _ => return Ok(Value::Null),
@@ -186,19 +193,34 @@ impl RpcHandler for GetBlockReceiptsRequest {
 
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
         let storage = &context.storage;
-        info!("Requested receipts for block with number: {}", self.block);
-        let block_number = match self.block.resolve_block_number(storage)? {
-            Some(block_number) => block_number,
-            _ => return Ok(Value::Null),
+        info!("Requested receipts for block: {}", self.block);
+        let (header, body) = match &self.block {
+            // The block is looked up by hash so the receipts of blocks of other forks are also served
+            BlockIdentifierOrHash::Hash(block_hash) => (
+                storage.get_block_header_by_hash(*block_hash)?,
+                storage.get_block_body_by_hash(*block_hash)?,
+            ),
+            BlockIdentifierOrHash::Identifier(block) => {
+                let Some(block_number) = block.resolve_block_number(storage)? else {
+                    return Ok(Value::Null);
+                };
+                (
+                    storage.get_block_header(block_number)?,
+                    storage.get_block_body(block_number)?,
+                )
+            }
         };
-        let header = storage.get_block_header(block_number)?;
-        let body = storage.get_block_body(block_number)?;
         let (header, body) = match (header, body) {
             (Some(header), Some(body)) => (header, body),
+            (Some(header), None) => {
+                ensure_history_available(storage, header.number)?;
+                return Ok(Value::Null);
+            }
             // Block not found
             _ => return Ok(Value::Null),
         };
-        let receipts = get_all_block_rpc_receipts(block_number, header, body, storage)?;
+        ensure_history_available(storage, header.number)?;
+        let receipts = get_all_block_rpc_receipts(header, body, storage)?;
 
         serde_json::to_value(&receipts).map_err(|error| RpcErr::Internal(error.to_string()))
     }
@@ -256,6 +278,7 @@ impl RpcHandler for GetRawBlockRequest {
             Some(block_number) => block_number,
             _ => return Ok(Value::Null),
         };
+        ensure_history_available(&context.storage, block_number)?;
         let header = context.storage.get_block_header(block_number)?;
         let body = context.storage.get_block_body(block_number)?;
         let (header, body) = match (header, body) {
@@ -289,6 +312,7 @@ impl RpcHandler for GetRawReceipts {
             Some(block_number) => block_number,
             _ => return Ok(Value::Null),
         };
+        ensure_history_available(storage, block_number)?;
         let header = storage.get_block_header(block_number)?;
         let body = storage.get_block_body(block_number)?;
         let (header, body) = match (header, body) {
@@ -324,32 +348,28 @@ impl RpcHandler for GetBlobBaseFee {
         Ok(Self {})
     }
 
+    /// Returns the blob base fee of the block to be built on top of the latest one, or null
+    /// if Cancun is not active on the latest block
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
         info!("Requested blob gas price");
-        match context.storage.get_latest_block_number() {
-            Ok(Some(block_number)) => {
-                let header = match context.storage.get_block_header(block_number)? {
-                    Some(header) => header,
-                    _ => return Err(RpcErr::Internal("Could not get block header".to_owned())),
-                };
-                let parent_header = match find_parent_header(&header, &context.storage) {
-                    Ok(header) => header,
-                    Err(error) => return Err(RpcErr::Internal(error.to_string())),
-                };
-                let blob_base_fee = calculate_base_fee_per_blob_gas(
-                    parent_header.excess_blob_gas.unwrap_or_default(),
-                );
-                serde_json::to_value(format!("{:#x}", blob_base_fee))
-                    .map_err(|error| RpcErr::Internal(error.to_string()))
-            }
-            Ok(None) => Err(RpcErr::Internal("No blocks found".to_owned())),
-            Err(error) => Err(RpcErr::Internal(error.to_string())),
-        }
+        let Some(latest_block_number) = context.storage.get_latest_block_number()? else {
+            return Err(RpcErr::Internal("No blocks found".to_owned()));
+        };
+        let Some(header) = context.storage.get_block_header(latest_block_number)? else {
+            return Err(RpcErr::Internal("Could not get block header".to_owned()));
+        };
+        let blob_base_fee = header.excess_blob_gas.map(|excess_blob_gas| {
+            calculate_base_fee_per_blob_gas(calc_excess_blob_gas(
+                excess_blob_gas,
+                header.blob_gas_used.unwrap_or_default(),
+            ))
+        });
+        serde_json::to_value(blob_base_fee.map(|fee| format!("{:#x}", fee)))
+            .map_err(|error| RpcErr::Internal(error.to_string()))
     }
 }
 
 pub fn get_all_block_rpc_receipts(
-    block_number: BlockNumber,
     header: BlockHeader,
     body: BlockBody,
     storage: &Store,
@@ -368,13 +388,19 @@ pub fn get_all_block_rpc_receipts(
     let mut current_log_index = 0;
     for (index, tx) in body.transactions.iter().enumerate() {
         let index = index as u64;
-        let receipt = match storage.get_receipt(block_number, index)? {
+        // Read by hash, the block may not be the canonical one at its number
+        let receipt = match storage.get_receipt_by_hash(block_info.block_hash, index)? {
             Some(receipt) => receipt,
             _ => return Err(RpcErr::Internal("Could not get receipt".to_owned())),
         };
         let gas_used = receipt.cumulative_gas_used - last_cumulative_gas_used;
-        let tx_info =
-            RpcReceiptTxInfo::from_transaction(tx.clone(), index, gas_used, blob_gas_price);
+        let tx_info = RpcReceiptTxInfo::from_transaction(
+            tx.clone(),
+            index,
+            gas_used,
+            blob_gas_price,
+            storage,
+        );
         let receipt = RpcReceipt::new(
             receipt.clone(),
             tx_info,
@@ -1,3 +1,6 @@
+use ethrex_core::serde_utils;
+use ethrex_net::sync::SyncProgress;
+use serde::Serialize;
 use serde_json::Value;
 use tracing::info;
 
@@ -21,12 +24,52 @@ impl RpcHandler for ChainId {
 }
 
 pub struct Syncing;
+
+/// Progress returned by `eth_syncing` while the node is syncing, in the format used by geth
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncingStatus {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub starting_block: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub current_block: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub highest_block: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub synced_accounts: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub synced_storage: u64,
+    #[serde(rename = "healedTrienodes", with = "serde_utils::u64::hex_str")]
+    pub healed_trie_nodes: u64,
+    #[serde(rename = "healingTrienodes", with = "serde_utils::u64::hex_str")]
+    pub healing_trie_nodes: u64,
+}
+
+impl From<SyncProgress> for SyncingStatus {
+    fn from(progress: SyncProgress) -> Self {
+        SyncingStatus {
+            starting_block: progress.starting_block,
+            current_block: progress.current_block,
+            highest_block: progress.highest_block,
+            synced_accounts: progress.snap.synced_accounts,
+            synced_storage: progress.snap.synced_storage_slots,
+            healed_trie_nodes: progress.snap.healed_trie_nodes,
+            healing_trie_nodes: progress.snap.pending_trie_nodes,
+        }
+    }
+}
+
 impl RpcHandler for Syncing {
     fn parse(_params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
         Ok(Self {})
     }
 
-    fn handle(&self, _context: RpcApiContext) -> Result<Value, RpcErr> {
-        Ok(Value::Bool(false))
+    /// Returns the progress of the sync cycle in progress, or false if the node is not syncing
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        match context.syncer.status().progress() {
+            Some(progress) => serde_json::to_value(SyncingStatus::from(progress))
+                .map_err(|error| RpcErr::Internal(error.to_string())),
+            None => Ok(Value::Bool(false)),
+        }
     }
 }
@@ -1,18 +1,26 @@
-use ethrex_blockchain::constants::MAX_BLOB_GAS_PER_BLOCK;
-use ethrex_core::types::{Block, Transaction};
+use ethrex_blockchain::{constants::MAX_BLOB_GAS_PER_BLOCK, payload::project_fees};
+use ethrex_core::types::{BlockHeader, BlockNumber};
 use serde::Serialize;
 use serde_json::Value;
 use tracing::info;
 
-use crate::{types::block_identifier::BlockIdentifier, utils::RpcErr, RpcApiContext, RpcHandler};
+use crate::{
+    types::block_identifier::{ensure_history_available, BlockIdentifier},
+    utils::RpcErr,
+    RpcApiContext, RpcHandler,
+};
 use ethrex_core::types::calculate_base_fee_per_blob_gas;
 use ethrex_storage::Store;
 
+/// Maximum amount of blocks whose fees are returned by a single request, larger requests are
+/// served the most recent ones
+pub const FEE_HISTORY_MAX_BLOCKS: u64 = 1024;
+
 #[derive(Clone, Debug)]
 pub struct FeeHistoryRequest {
     pub block_count: u64,
     pub newest_block: BlockIdentifier,
-    pub reward_percentiles: Option<Vec<f32>>,
+    pub reward_percentiles: Option<Vec<f64>>,
 }
 
 #[derive(Serialize, Default, Clone, Debug)]
@@ -38,31 +46,39 @@ impl RpcHandler for FeeHistoryRequest {
             )));
         };
 
+        // Clients send the block count either as a quantity or as a number
+        let block_count = match &params[0] {
+            Value::String(block_count) => {
+                let block_count = block_count.strip_prefix("0x").ok_or(RpcErr::BadParams(
+                    "Expected param to be 0x prefixed".to_owned(),
+                ))?;
+                u64::from_str_radix(block_count, 16)
+                    .map_err(|error| RpcErr::BadParams(error.to_string()))?
+            }
+            block_count => serde_json::from_value(block_count.clone())?,
+        };
+
         let reward_percentiles = match params.get(2).cloned() {
+            Some(Value::Null) | None => None,
             Some(rp) => {
-                let rp: Vec<f32> = serde_json::from_value(rp)?;
-                let all_ok = rp
-                    .windows(2)
-                    .all(|w| w[0] <= w[1] || w[0] >= 0.0 && w[0] <= 100.0);
-                // We want to return None if any value is wrong
-                Some(
-                    all_ok
-                        .then_some(rp)
-                        .ok_or(RpcErr::BadParams("Some of the params are wrong".to_owned()))?,
-                )
+                let rp: Vec<f64> = serde_json::from_value(rp)?;
+                if rp.iter().any(|p| !(0.0..=100.0).contains(p)) {
+                    return Err(RpcErr::BadParams(
+                        "Reward percentiles must be between 0 and 100".to_owned(),
+                    ));
+                }
+                if rp.windows(2).any(|w| w[0] > w[1]) {
+                    return Err(RpcErr::BadParams(
+                        "Reward percentiles must be monotonically increasing".to_owned(),
+                    ));
+                }
+                Some(rp)
             }
-            None => None,
         };
 
-        let block_count_str: String = serde_json::from_value(params[0].clone())?;
-        let block_count_str = block_count_str.strip_prefix("0x").ok_or(RpcErr::BadParams(
-            "Expected param to be 0x prefixed".to_owned(),
-        ))?;
-
         Ok(FeeHistoryRequest {
-            block_count: u64::from_str_radix(block_count_str, 16)
-                .map_err(|error| RpcErr::BadParams(error.to_string()))?,
-            newest_block: BlockIdentifier::parse(params[0].clone(), 0)?,
+            block_count,
+            newest_block: BlockIdentifier::parse(params[1].clone(), 1)?,
             reward_percentiles,
         })
     }
@@ -70,7 +86,7 @@ impl RpcHandler for FeeHistoryRequest {
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
         let storage = &context.storage;
         info!(
-            "Requested fee history for {} blocks starting from {}",
+            "Requested fee history for {} blocks up to {}",
             self.block_count, self.newest_block
         );
 
@@ -79,58 +95,65 @@ impl RpcHandler for FeeHistoryRequest {
                 .map_err(|error| RpcErr::Internal(error.to_string()));
         }
 
-        let (start_block, end_block) =
-            Self::get_range(storage, self.block_count, &self.newest_block)?;
-        let oldest_block = start_block;
-        let block_count = (end_block - start_block) as usize;
+        let (start_block, end_block) = Self::get_range(
+            storage,
+            self.block_count.min(FEE_HISTORY_MAX_BLOCKS),
+            &self.newest_block,
+        )?;
+        let block_count = (end_block - start_block + 1) as usize;
         let mut base_fee_per_gas = Vec::<u64>::with_capacity(block_count + 1);
         let mut base_fee_per_blob_gas = Vec::<u64>::with_capacity(block_count + 1);
         let mut gas_used_ratio = Vec::<f64>::with_capacity(block_count);
         let mut blob_gas_used_ratio = Vec::<f64>::with_capacity(block_count);
         let mut reward = Vec::<Vec<u64>>::with_capacity(block_count);
 
-        for block_number in start_block..end_block {
+        let mut newest_header = None;
+        for block_number in start_block..=end_block {
             let header = storage
                 .get_block_header(block_number)?
                 .ok_or(RpcErr::Internal(format!(
                     "Could not get header for block {block_number}"
                 )))?;
-            let body = storage
-                .get_block_body(block_number)?
-                .ok_or(RpcErr::Internal(format!(
-                    "Could not get body for block {block_number}"
-                )))?;
-            let blob_base_fee =
-                calculate_base_fee_per_blob_gas(header.excess_blob_gas.unwrap_or_default());
 
             base_fee_per_gas.push(header.base_fee_per_gas.unwrap_or_default());
-            base_fee_per_blob_gas.push(blob_base_fee);
-            gas_used_ratio.push(header.gas_used as f64 / header.gas_limit as f64);
+            base_fee_per_blob_gas.push(Self::base_fee_per_blob_gas(&header));
+            gas_used_ratio.push(if header.gas_limit == 0 {
+                0.0
+            } else {
+                header.gas_used as f64 / header.gas_limit as f64
+            });
             blob_gas_used_ratio.push(
                 header.blob_gas_used.unwrap_or_default() as f64 / MAX_BLOB_GAS_PER_BLOCK as f64,
             );
 
             if let Some(percentiles) = &self.reward_percentiles {
-                let block = Block::new(header, body);
-                reward.push(Self::calculate_percentiles_for_block(block, percentiles));
+                reward.push(Self::calculate_percentiles_for_block(
+                    storage,
+                    &header,
+                    percentiles,
+                )?);
             }
+            newest_header = Some(header);
         }
 
-        // Now we project base_fee_per_gas and base_fee_per_blob_gas from last block
-        let header = storage
-            .get_block_header(end_block)?
-            .ok_or(RpcErr::Internal(format!(
-                "Could not get header for block {end_block}"
-            )))?;
-
-        let blob_base_fee =
-            calculate_base_fee_per_blob_gas(header.excess_blob_gas.unwrap_or_default());
-        base_fee_per_gas.push(header.base_fee_per_gas.unwrap_or_default());
-        base_fee_per_blob_gas.push(blob_base_fee);
+        // The fees of the block following the newest one are projected from it
+        if let Some(header) = newest_header {
+            match project_fees(&header, 1, 0, 0, context.payload_manager.config().gas_ceil).first()
+            {
+                Some(next) => {
+                    base_fee_per_gas.push(next.base_fee_per_gas);
+                    base_fee_per_blob_gas.push(next.base_fee_per_blob_gas.unwrap_or_default());
+                }
+                None => {
+                    base_fee_per_gas.push(header.base_fee_per_gas.unwrap_or_default());
+                    base_fee_per_blob_gas.push(Self::base_fee_per_blob_gas(&header));
+                }
+            }
+        }
 
         let u64_to_hex_str = |x: u64| format!("0x{:x}", x);
         let response = FeeHistoryResponse {
-            oldest_block: u64_to_hex_str(oldest_block),
+            oldest_block: u64_to_hex_str(start_block),
             base_fee_per_gas: base_fee_per_gas.into_iter().map(u64_to_hex_str).collect(),
             base_fee_per_blob_gas: base_fee_per_blob_gas
                 .into_iter()
@@ -149,13 +172,12 @@ impl RpcHandler for FeeHistoryRequest {
 }
 
 impl FeeHistoryRequest {
+    /// Returns the first and last blocks of the range, both included
     fn get_range(
         storage: &Store,
-        block_num: u64,
-        finish_block: &BlockIdentifier,
-    ) -> Result<(u64, u64), RpcErr> {
-        // TODO: We should probably restrict how many blocks we are fetching to a certain limit
-
+        block_count: u64,
+        newest_block: &BlockIdentifier,
+    ) -> Result<(BlockNumber, BlockNumber), RpcErr> {
         // Get earliest block
         let earliest_block_num = storage
             .get_earliest_block_number()?
@@ -168,54 +190,216 @@ impl FeeHistoryRequest {
             "Could not get latest block number".to_owned(),
         ))?;
 
-        // Get finish_block number
-        let finish_block = finish_block
+        // Get newest block number, which has to be <= latest_block
+        let newest_block = newest_block
             .resolve_block_number(storage)?
             .ok_or(RpcErr::Internal(
                 "Could not resolve block number".to_owned(),
-            ))?;
+            ))?
+            .min(latest_block_num);
+
+        // The newest block is included in the range, start_block has to be >= earliest_block
+        let start_block = earliest_block_num.max((newest_block + 1).saturating_sub(block_count));
 
-        // finish block has to be included in the range
-        let finish_block = finish_block + 1;
+        Ok((start_block, newest_block))
+    }
+
+    fn base_fee_per_blob_gas(header: &BlockHeader) -> u64 {
+        header
+            .excess_blob_gas
+            .map(calculate_base_fee_per_blob_gas)
+            .unwrap_or_default()
+    }
 
-        // Acomodate finish_block to be <= latest_block
-        let finish_block = finish_block.min(latest_block_num);
+    /// Returns the effective priority fees paid at the given percentiles of the gas used by
+    /// the block, the fees of the transactions being weighted by the gas they used
+    fn calculate_percentiles_for_block(
+        storage: &Store,
+        header: &BlockHeader,
+        percentiles: &[f64],
+    ) -> Result<Vec<u64>, RpcErr> {
+        ensure_history_available(storage, header.number)?;
+        let body = storage
+            .get_block_body(header.number)?
+            .ok_or(RpcErr::Internal(format!(
+                "Could not get body for block {}",
+                header.number
+            )))?;
+        if body.transactions.is_empty() {
+            return Ok(vec![0; percentiles.len()]);
+        }
 
-        // Acomodate start_block to be >= earliest_block
-        let start_block = earliest_block_num.max(finish_block.saturating_sub(block_num));
+        // Pairs of effective priority fee and gas used of the transactions of the block
+        let mut fees = Vec::with_capacity(body.transactions.len());
+        let mut cumulative_gas_used = 0;
+        for (index, tx) in body.transactions.iter().enumerate() {
+            let receipt =
+                storage
+                    .get_receipt(header.number, index as u64)?
+                    .ok_or(RpcErr::Internal(format!(
+                        "Could not get receipt {index} of block {}",
+                        header.number
+                    )))?;
+            let gas_used = receipt
+                .cumulative_gas_used
+                .saturating_sub(cumulative_gas_used);
+            cumulative_gas_used = receipt.cumulative_gas_used;
+            let tip = tx
+                .effective_gas_tip(header.base_fee_per_gas)
+                .unwrap_or_default();
+            fees.push((tip, gas_used));
+        }
+        fees.sort();
 
-        Ok((start_block, finish_block))
+        let mut rewards = Vec::with_capacity(percentiles.len());
+        let mut index = 0;
+        let mut gas_covered = fees[0].1;
+        for percentile in percentiles {
+            let threshold = (header.gas_used as f64 * percentile / 100.0) as u64;
+            while gas_covered < threshold && index < fees.len() - 1 {
+                index += 1;
+                gas_covered += fees[index].1;
+            }
+            rewards.push(fees[index].0);
+        }
+        Ok(rewards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        map_http_requests,
+        utils::{test_utils::default_context_with_storage, RpcRequest},
+    };
+    use ethrex_blockchain::constants::{MAX_BLOB_GAS_PER_BLOCK, TARGET_BLOB_GAS_PER_BLOCK};
+    use ethrex_core::types::{
+        calculate_base_fee_per_blob_gas, Block, BlockBody, BlockHeader, EIP1559Transaction,
+        Genesis, Receipt, Transaction, TxKind, TxType,
+    };
+    use ethrex_storage::{EngineType, Store};
+    use serde_json::Value;
+
+    fn tx_with_tip(nonce: u64, tip: u64) -> Transaction {
+        Transaction::EIP1559Transaction(EIP1559Transaction {
+            nonce,
+            max_fee_per_gas: 1000 + tip,
+            max_priority_fee_per_gas: tip,
+            to: TxKind::Create,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn rewards_are_weighted_by_gas_used() {
+        let genesis: Genesis =
+            serde_json::from_str(include_str!("../../../../test_data/genesis-l1.json"))
+                .expect("Fatal: test config is invalid");
+        let storage = Store::new("test-store", EngineType::InMemory).unwrap();
+        storage.add_initial_state(genesis).unwrap();
+        // Transactions paying higher tips use most of the gas of the block
+        let txs = vec![tx_with_tip(0, 3), tx_with_tip(1, 1), tx_with_tip(2, 2)];
+        let cumulative_gas_used = [800, 900, 1000];
+        let header = BlockHeader {
+            number: 1,
+            gas_limit: 30_000_000,
+            gas_used: 1000,
+            base_fee_per_gas: Some(1000),
+            ..Default::default()
+        };
+        let hash = header.compute_block_hash();
+        let body = BlockBody {
+            transactions: txs,
+            ..Default::default()
+        };
+        storage.add_block(Block::new(header, body)).unwrap();
+        for (index, gas_used) in cumulative_gas_used.into_iter().enumerate() {
+            let receipt = Receipt::new(TxType::EIP1559, true, gas_used, vec![]);
+            storage.add_receipt(hash, index as u64, receipt).unwrap();
+        }
+        storage.set_canonical_block(1, hash).unwrap();
+        storage.update_latest_block_number(1).unwrap();
+        let context = default_context_with_storage(storage);
+        let fee_history = |params: &str| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_feeHistory","params":{params}}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            map_http_requests(&request, context.clone())
+        };
+
+        let history = fee_history(r#"["0x5","latest",[10,50,100]]"#).unwrap();
+        // The range is bounded by the genesis block
+        assert_eq!(history["oldestBlock"], "0x0");
+        assert_eq!(history["gasUsedRatio"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            history["reward"][0],
+            serde_json::json!(["0x0", "0x0", "0x0"])
+        );
+        assert_eq!(
+            history["reward"][1],
+            serde_json::json!(["0x1", "0x3", "0x3"])
+        );
+        // The base fee of the next block is projected, it drops as the block is almost empty
+        let base_fees = history["baseFeePerGas"].as_array().unwrap();
+        assert_eq!(base_fees.len(), 3);
+        assert_eq!(base_fees[1], "0x3e8");
+        assert!(
+            u64::from_str_radix(base_fees[2].as_str().unwrap().trim_start_matches("0x"), 16)
+                .unwrap()
+                < 1000
+        );
+
+        let history = fee_history(r#"[1,"0x1"]"#).unwrap();
+        assert_eq!(history["oldestBlock"], "0x1");
+        assert_eq!(history["reward"], Value::Array(vec![]));
+        assert!(fee_history(r#"["0x1","latest",[50,10]]"#).is_err());
     }
 
-    fn calculate_percentiles_for_block(block: Block, percentiles: &[f32]) -> Vec<u64> {
-        let base_fee_per_gas = block.header.base_fee_per_gas.unwrap_or_default();
-        let mut effective_priority_fees: Vec<u64> = block
-            .body
-            .transactions
-            .into_iter()
-            .map(|t: Transaction| match t {
-                Transaction::LegacyTransaction(_) | Transaction::EIP2930Transaction(_) => 0,
-                Transaction::EIP1559Transaction(t) => t
-                    .max_priority_fee_per_gas
-                    .min(t.max_fee_per_gas.saturating_sub(base_fee_per_gas)),
-                Transaction::EIP4844Transaction(t) => t
-                    .max_priority_fee_per_gas
-                    .min(t.max_fee_per_gas.saturating_sub(base_fee_per_gas)),
-                Transaction::PrivilegedL2Transaction(t) => t
-                    .max_priority_fee_per_gas
-                    .min(t.max_fee_per_gas.saturating_sub(base_fee_per_gas)),
-            })
-            .collect();
-
-        effective_priority_fees.sort();
-        let t_len = effective_priority_fees.len() as f32;
-
-        percentiles
-            .iter()
-            .map(|x: &f32| {
-                let i = (x * t_len / 100_f32) as usize;
-                effective_priority_fees.get(i).cloned().unwrap_or_default()
-            })
-            .collect()
+    #[test]
+    fn blob_fees_of_the_next_block_are_projected() {
+        let genesis: Genesis =
+            serde_json::from_str(include_str!("../../../../test_data/genesis-l1.json"))
+                .expect("Fatal: test config is invalid");
+        let storage = Store::new("test-store", EngineType::InMemory).unwrap();
+        storage.add_initial_state(genesis).unwrap();
+        // The block is full of blobs, so the blob base fee grows
+        let excess_blob_gas = 10_000_000;
+        let header = BlockHeader {
+            number: 1,
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(1000),
+            blob_gas_used: Some(MAX_BLOB_GAS_PER_BLOCK),
+            excess_blob_gas: Some(excess_blob_gas),
+            ..Default::default()
+        };
+        let hash = header.compute_block_hash();
+        storage
+            .add_block(Block::new(header, BlockBody::default()))
+            .unwrap();
+        storage.set_canonical_block(1, hash).unwrap();
+        storage.update_latest_block_number(1).unwrap();
+        let context = default_context_with_storage(storage);
+        let call = |method: &str, params: &str| {
+            let body =
+                format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{method}","params":{params}}}"#);
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            map_http_requests(&request, context.clone()).unwrap()
+        };
+        let hex = |fee: u64| Value::String(format!("{:#x}", fee));
+        let current_fee = calculate_base_fee_per_blob_gas(excess_blob_gas);
+        let next_fee = calculate_base_fee_per_blob_gas(
+            excess_blob_gas + MAX_BLOB_GAS_PER_BLOCK - TARGET_BLOB_GAS_PER_BLOCK,
+        );
+        assert!(next_fee > current_fee);
+
+        assert_eq!(call("eth_blobBaseFee", "[]"), hex(next_fee));
+
+        let history = call("eth_feeHistory", r#"["0x1","latest"]"#);
+        assert_eq!(
+            history["baseFeePerBlobGas"],
+            Value::Array(vec![hex(current_fee), hex(next_fee)])
+        );
+        assert_eq!(history["blobGasUsedRatio"], serde_json::json!([1.0]));
     }
 }
@@ -2,7 +2,7 @@
 // - Manually testing the behaviour deploying contracts on the Sepolia test network.
 // - Go-Ethereum, specifically: https://github.com/ethereum/go-ethereum/blob/368e16f39d6c7e5cce72a92ec289adbfbaed4854/eth/filters/filter.go
 // - Ethereum's reference: https://ethereum.org/en/developers/docs/apis/json-rpc/#eth_newfilter
-use ethrex_core::types::BlockNumber;
+use ethrex_core::types::{BlockHash, BlockNumber};
 use ethrex_storage::Store;
 use std::{
     collections::HashMap,
@@ -19,7 +19,7 @@ use crate::{
 use rand::prelude::*;
 use serde_json::{json, Value};
 
-use super::logs::{fetch_logs_with_filter, LogsFilter};
+use super::logs::{fetch_logs_with_filter, fetch_removed_logs, LogsFilter};
 
 #[derive(Debug, Clone)]
 pub struct NewFilterRequest {
@@ -54,6 +54,9 @@ pub struct PollableFilter {
     /// the log will be applied from this
     /// block number up to the latest one.
     pub last_block_number: BlockNumber,
+    /// Hash of the block at `last_block_number` when this filter was
+    /// last polled, used to detect reorgs between polls.
+    pub last_block_hash: BlockHash,
     pub filter_data: LogsFilter,
 }
 
@@ -89,6 +92,10 @@ impl NewFilterRequest {
             error!("Latest block number was requested but it does not exist");
             return Err(RpcErr::Internal("Failed to create filter".to_string()));
         };
+        let Some(last_block_hash) = storage.get_canonical_block_hash(last_block_number)? else {
+            error!("Latest block hash was requested but it does not exist");
+            return Err(RpcErr::Internal("Failed to create filter".to_string()));
+        };
         let id: u64 = random();
         let timestamp = Instant::now();
         let mut active_filters_guard = filters.lock().unwrap_or_else(|mut poisoned_guard| {
@@ -103,6 +110,7 @@ impl NewFilterRequest {
                 timestamp,
                 PollableFilter {
                     last_block_number,
+                    last_block_hash,
                     filter_data: self.request_data.clone(),
                 },
             ),
@@ -192,6 +200,10 @@ impl FilterChangesRequest {
             error!("Latest block number was requested but it does not exist");
             return Err(RpcErr::Internal("Failed to create filter".to_string()));
         };
+        let Some(latest_block_hash) = storage.get_canonical_block_hash(latest_block_num)? else {
+            error!("Latest block hash was requested but it does not exist");
+            return Err(RpcErr::Internal("Failed to create filter".to_string()));
+        };
         let mut active_filters_guard = filters.lock().unwrap_or_else(|mut poisoned_guard| {
             error!("THREAD CRASHED WITH MUTEX TAKEN; SYSTEM MIGHT BE UNSTABLE");
             **poisoned_guard.get_mut() = HashMap::new();
@@ -214,16 +226,29 @@ impl FilterChangesRequest {
                 // Since the filter was polled, updated its timestamp, so
                 // it does not expire.
                 *timestamp = Instant::now();
-                // Update this filter so the current query
-                // starts from the last polled block.
-                filter.filter_data.from_block = BlockIdentifier::Number(filter.last_block_number);
+                let last_block_number = filter.last_block_number;
+                let last_block_hash = filter.last_block_hash;
                 filter.last_block_number = latest_block_num;
+                filter.last_block_hash = latest_block_hash;
                 let mut filter = filter.clone();
-                filter.filter_data.to_block = BlockIdentifier::Number(latest_block_num);
                 // Drop the lock early to process this filter's query
                 // and not keep the lock more than we should.
                 drop(active_filters_guard);
-                let logs = fetch_logs_with_filter(&filter.filter_data, storage)?;
+                // If the last polled block is no longer canonical, a reorg happened
+                // since the last poll: report the logs of the orphaned blocks as removed
+                // and fetch the new ones from the common ancestor onwards.
+                let (mut logs, common_ancestor) =
+                    fetch_removed_logs(&filter.filter_data, last_block_hash, &storage)?;
+                let from = common_ancestor.map_or(last_block_number, |ancestor| {
+                    (ancestor + 1).min(last_block_number)
+                });
+                // Update this filter so the current query
+                // starts from the last polled block.
+                filter.filter_data.from_block = BlockIdentifier::Number(from);
+                filter.filter_data.to_block = BlockIdentifier::Number(latest_block_num);
+                if from <= latest_block_num {
+                    logs.extend(fetch_logs_with_filter(&filter.filter_data, storage)?);
+                }
                 serde_json::to_value(logs).map_err(|error| {
                     tracing::error!("Log filtering request failed with: {error}");
                     RpcErr::Internal("Failed to filter logs".to_string())
@@ -257,9 +282,8 @@ mod tests {
         sync::{Arc, Mutex},
         time::{Duration, Instant},
     };
-    use tokio::sync::Mutex as TokioMutex;
 
-    use super::ActiveFilters;
+    use super::{fetch_removed_logs, ActiveFilters};
     use crate::{
         eth::{
             filter::PollableFilter,
@@ -270,11 +294,16 @@ mod tests {
         RpcApiContext, FILTER_DURATION,
     };
     use crate::{
-        types::block_identifier::BlockIdentifier,
-        utils::{test_utils::example_p2p_node, RpcRequest},
+        types::block_identifier::{BlockIdentifier, BlockTag},
+        utils::{test_utils::default_context_with_storage, RpcRequest},
+    };
+    use bytes::Bytes;
+    use ethrex_core::{
+        types::{
+            Block, BlockBody, BlockHeader, BlockNumber, Genesis, Log, Receipt, Transaction, TxType,
+        },
+        Address, H256,
     };
-    use ethrex_core::types::Genesis;
-    use ethrex_net::sync::SyncManager;
     use ethrex_storage::{EngineType, Store};
 
     use serde_json::{json, Value};
@@ -439,12 +468,11 @@ mod tests {
         filters_pointer: ActiveFilters,
     ) -> u64 {
         let context = RpcApiContext {
-            storage: Store::new("in-mem", EngineType::InMemory)
-                .expect("Fatal: could not create in memory test db"),
-            jwt_secret: Default::default(),
-            local_p2p_node: example_p2p_node(),
             active_filters: filters_pointer.clone(),
-            syncer: Arc::new(TokioMutex::new(SyncManager::dummy())),
+            ..default_context_with_storage(
+                Store::new("in-mem", EngineType::InMemory)
+                    .expect("Fatal: could not create in memory test db"),
+            )
         };
         let request: RpcRequest = serde_json::from_value(json_req).expect("Test json is incorrect");
         let genesis_config: Genesis =
@@ -482,6 +510,7 @@ mod tests {
                 Instant::now(),
                 PollableFilter {
                     last_block_number: 0,
+                    last_block_hash: Default::default(),
                     filter_data: LogsFilter {
                         from_block: BlockIdentifier::Number(1),
                         to_block: BlockIdentifier::Number(2),
@@ -493,11 +522,8 @@ mod tests {
         );
         let active_filters = Arc::new(Mutex::new(HashMap::from([filter])));
         let context = RpcApiContext {
-            storage: Store::new("in-mem", EngineType::InMemory).unwrap(),
-            local_p2p_node: example_p2p_node(),
-            jwt_secret: Default::default(),
             active_filters: active_filters.clone(),
-            syncer: Arc::new(TokioMutex::new(SyncManager::dummy())),
+            ..default_context_with_storage(Store::new("in-mem", EngineType::InMemory).unwrap())
         };
 
         map_http_requests(&uninstall_filter_req, context).unwrap();
@@ -513,11 +539,8 @@ mod tests {
         let active_filters = Arc::new(Mutex::new(HashMap::new()));
 
         let context = RpcApiContext {
-            storage: Store::new("in-mem", EngineType::InMemory).unwrap(),
-            local_p2p_node: example_p2p_node(),
             active_filters: active_filters.clone(),
-            jwt_secret: Default::default(),
-            syncer: Arc::new(TokioMutex::new(SyncManager::dummy())),
+            ..default_context_with_storage(Store::new("in-mem", EngineType::InMemory).unwrap())
         };
         let uninstall_filter_req: RpcRequest = serde_json::from_value(json!(
         {
@@ -534,6 +557,138 @@ mod tests {
         assert!(matches!(res, serde_json::Value::Bool(false)));
     }
 
+    fn add_block_with_log(
+        storage: &Store,
+        number: BlockNumber,
+        parent_hash: H256,
+        extra_data: u8,
+    ) -> H256 {
+        let block = Block::new(
+            BlockHeader {
+                number,
+                parent_hash,
+                extra_data: Bytes::from(vec![extra_data]),
+                ..Default::default()
+            },
+            BlockBody {
+                transactions: vec![Transaction::EIP1559Transaction(Default::default())],
+                ..Default::default()
+            },
+        );
+        let hash = block.hash();
+        let log = Log {
+            address: Address::from_low_u64_be(extra_data as u64),
+            topics: vec![],
+            data: Bytes::new(),
+        };
+        storage.add_block(block).unwrap();
+        storage
+            .add_receipt(
+                hash,
+                0,
+                Receipt::new(TxType::EIP1559, true, 21000, vec![log]),
+            )
+            .unwrap();
+        storage.set_canonical_block(number, hash).unwrap();
+        storage.update_latest_block_number(number).unwrap();
+        hash
+    }
+
+    #[test]
+    fn filter_changes_reports_removed_logs_after_reorg() {
+        let storage = Store::new("in-mem", EngineType::InMemory).unwrap();
+        let genesis: Genesis = serde_json::from_str(TEST_GENESIS).unwrap();
+        storage.add_initial_state(genesis).unwrap();
+        let genesis_hash = storage.get_canonical_block_hash(0).unwrap().unwrap();
+        let old_hash = add_block_with_log(&storage, 1, genesis_hash, 1);
+
+        let filter = (
+            0xFF,
+            (
+                Instant::now(),
+                PollableFilter {
+                    last_block_number: 1,
+                    last_block_hash: old_hash,
+                    filter_data: LogsFilter {
+                        from_block: BlockIdentifier::Number(1),
+                        to_block: BlockIdentifier::Tag(BlockTag::Latest),
+                        address_filters: None,
+                        topics: vec![],
+                    },
+                },
+            ),
+        );
+        let active_filters = Arc::new(Mutex::new(HashMap::from([filter])));
+        // Replace block 1 with a sibling
+        let new_hash = add_block_with_log(&storage, 1, genesis_hash, 2);
+
+        let context = RpcApiContext {
+            active_filters: active_filters.clone(),
+            ..default_context_with_storage(storage)
+        };
+        let filter_changes_req: RpcRequest = serde_json::from_value(json!(
+        {
+            "jsonrpc":"2.0",
+            "method":"eth_getFilterChanges",
+            "params": ["0xFF"],
+            "id":1
+        }))
+        .unwrap();
+        let logs = map_http_requests(&filter_changes_req, context.clone()).unwrap();
+        let logs = logs.as_array().unwrap();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0]["removed"], true);
+        assert_eq!(logs[0]["blockHash"], json!(old_hash));
+        assert_eq!(logs[1]["removed"], false);
+        assert_eq!(logs[1]["blockHash"], json!(new_hash));
+
+        // Once reported, the orphaned logs are not returned again
+        let logs = map_http_requests(&filter_changes_req, context).unwrap();
+        let logs = logs.as_array().unwrap();
+        assert!(logs.iter().all(|log| log["removed"] == false));
+        assert_eq!(
+            active_filters
+                .lock()
+                .unwrap()
+                .get(&0xFF)
+                .unwrap()
+                .1
+                .last_block_hash,
+            new_hash
+        );
+    }
+
+    #[test]
+    fn removed_logs_are_bounded_by_the_max_reorg_depth() {
+        let mut storage = Store::new("in-mem", EngineType::InMemory).unwrap();
+        storage.set_max_reorg_depth(1);
+        let genesis: Genesis = serde_json::from_str(TEST_GENESIS).unwrap();
+        storage.add_initial_state(genesis).unwrap();
+        let genesis_hash = storage.get_canonical_block_hash(0).unwrap().unwrap();
+        let first_hash = add_block_with_log(&storage, 1, genesis_hash, 1);
+        let second_hash = add_block_with_log(&storage, 2, first_hash, 2);
+        let filter = LogsFilter {
+            from_block: BlockIdentifier::Number(1),
+            to_block: BlockIdentifier::Tag(BlockTag::Latest),
+            address_filters: None,
+            topics: vec![],
+        };
+
+        // Replace block 2 with a sibling
+        add_block_with_log(&storage, 2, first_hash, 3);
+        let (logs, common_ancestor) = fetch_removed_logs(&filter, second_hash, &storage).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(common_ancestor, Some(1));
+
+        // Replace both blocks
+        add_block_with_log(&storage, 1, genesis_hash, 4);
+        storage.unset_canonical_block(2).unwrap();
+        let (logs, common_ancestor) = fetch_removed_logs(&filter, second_hash, &storage).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_hash, second_hash);
+        assert_eq!(common_ancestor, Some(0));
+    }
+
     #[tokio::test]
     async fn background_job_removes_filter_smoke_test() {
         // Start a test server to start the cleanup
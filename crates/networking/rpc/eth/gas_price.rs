@@ -1,6 +1,3 @@
-use ethrex_blockchain::constants::MIN_GAS_LIMIT;
-use tracing::error;
-
 use crate::utils::RpcErr;
 use crate::{RpcApiContext, RpcHandler};
 use serde_json::Value;
@@ -13,97 +10,50 @@ use serde_json::Value;
 #[derive(Debug, Clone)]
 pub struct GasPrice;
 
-// TODO: Maybe these constants should be some kind of config.
-// How many transactions to take as a price sample from a block.
-const TXS_SAMPLE_SIZE: usize = 3;
-// How many blocks we'll go back to calculate the estimate.
-const BLOCK_RANGE_LOWER_BOUND_DEC: u64 = 20;
+#[derive(Debug, Clone)]
+pub struct MaxPriorityFeePerGas;
 
 impl RpcHandler for GasPrice {
     fn parse(_: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
         Ok(GasPrice {})
     }
 
-    // Disclaimer:
-    // This estimation is somewhat based on how currently go-ethereum does it.
-    // Reference: https://github.com/ethereum/go-ethereum/blob/368e16f39d6c7e5cce72a92ec289adbfbaed4854/eth/gasprice/gasprice.go#L153
-    // Although it will (probably) not yield the same result.
-    // The idea here is to:
-    // - Take the last 20 blocks (100% arbitrary, this could be more or less blocks)
-    // - For each block, take the 3 txs with the lowest gas price (100% arbitrary)
-    // - Join every fetched tx into a single vec and sort it.
-    // - Return the one in the middle (what is also known as the 'median sample')
-    // The intuition here is that we're sampling already accepted transactions,
-    // fetched from recent blocks, so they should be real, representative values.
-    // This specific implementation probably is not the best way to do this
-    // but it works for now for a simple estimation, in the future
-    // we can look into more sophisticated estimation methods, if needed.
     /// Estimate Gas Price based on already accepted transactions,
     /// as per the spec, this will be returned in wei.
+    /// It is the tip suggested by the gas price oracle on top of the base fee of the latest block,
+    /// like geth does.
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
-        let Some(latest_block_number) = context.storage.get_latest_block_number()? else {
-            error!("FATAL: LATEST BLOCK NUMBER IS MISSING");
-            return Err(RpcErr::Internal("Error calculating gas price".to_string()));
-        };
-        let block_range_lower_bound =
-            latest_block_number.saturating_sub(BLOCK_RANGE_LOWER_BOUND_DEC);
-        // These are the blocks we'll use to estimate the price.
-        let block_range = block_range_lower_bound..=latest_block_number;
-        if block_range.is_empty() {
-            error!(
-                "Calculated block range from block {} \
-                    up to block {} for gas price estimation is empty",
-                block_range_lower_bound, latest_block_number
-            );
-            return Err(RpcErr::Internal("Error calculating gas price".to_string()));
-        }
-        let mut results = vec![];
-        // TODO: Estimating gas price involves querying multiple blocks
-        // and doing some calculations with each of them, let's consider
-        // caching this result, also we can have a specific DB method
-        // that returns a block range to not query them one-by-one.
-        for block_num in block_range {
-            let Some(block_body) = context.storage.get_block_body(block_num)? else {
-                error!("Block body for block number {block_num} is missing but is below the latest known block!");
-                return Err(RpcErr::Internal(
-                    "Error calculating gas price: missing data".to_string(),
-                ));
-            };
-            let mut gas_price_samples = block_body
-                .transactions
-                .into_iter()
-                .map(|tx| tx.gas_price())
-                .collect::<Vec<u64>>();
-            gas_price_samples.sort();
-            results.extend(gas_price_samples.into_iter().take(TXS_SAMPLE_SIZE));
-        }
-        results.sort();
+        let tip = context.gas_price_oracle.suggest_tip(&context.storage)?;
+        let base_fee = context
+            .storage
+            .get_latest_block_number()?
+            .and_then(|number| context.storage.get_block_header(number).ok().flatten())
+            .and_then(|header| header.base_fee_per_gas)
+            .unwrap_or_default();
+        let gas_as_hex = format!("0x{:x}", tip.saturating_add(base_fee));
+        Ok(serde_json::Value::String(gas_as_hex))
+    }
+}
 
-        let sample_gas = match results.get(results.len() / 2) {
-            Some(gas) => *gas,
-            None => {
-                // If we don't have enough samples, we'll return the base fee or the min gas limit as a default.
-                context
-                    .storage
-                    .get_block_header(latest_block_number)
-                    .ok()
-                    .flatten()
-                    .and_then(|header| header.base_fee_per_gas)
-                    .unwrap_or(MIN_GAS_LIMIT)
-            }
-        };
+impl RpcHandler for MaxPriorityFeePerGas {
+    fn parse(_: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        Ok(MaxPriorityFeePerGas {})
+    }
 
-        let gas_as_hex = format!("0x{:x}", sample_gas);
-        Ok(serde_json::Value::String(gas_as_hex))
+    /// Suggests a priority fee for dynamic fee transactions, in wei
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let tip = context.gas_price_oracle.suggest_tip(&context.storage)?;
+        Ok(serde_json::Value::String(format!("0x{tip:x}")))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::GasPrice;
+    use super::{GasPrice, MaxPriorityFeePerGas};
     use crate::{
+        eth::gas_price_oracle::{GasPriceOracle, GasPriceOracleConfig},
         map_http_requests,
-        utils::{parse_json_hex, test_utils::example_p2p_node, RpcRequest},
+        utils::{parse_json_hex, test_utils::default_context_with_storage, RpcRequest},
         RpcApiContext, RpcHandler,
     };
     use bytes::Bytes;
@@ -114,12 +64,10 @@ mod tests {
         },
         Address, Bloom, H256, U256,
     };
-    use ethrex_net::{sync::SyncManager, types::Node};
     use ethrex_storage::{EngineType, Store};
     use hex_literal::hex;
     use serde_json::json;
-    use std::{net::Ipv4Addr, str::FromStr, sync::Arc};
-    use tokio::sync::Mutex;
+    use std::{str::FromStr, sync::Arc};
     // Base price for each test transaction.
     const BASE_PRICE_IN_WEI: u64 = 10_u64.pow(9);
     fn test_header(block_num: u64) -> BlockHeader {
@@ -164,6 +112,7 @@ mod tests {
             blob_gas_used: Some(0x00),
             excess_blob_gas: Some(0x00),
             parent_beacon_block_root: Some(H256::zero()),
+            requests_hash: None,
         }
     }
     fn legacy_tx_for_test(nonce: u64) -> Transaction {
@@ -349,8 +298,7 @@ mod tests {
         });
         let expected_response = json!("0x3b9aca00");
         let request: RpcRequest = serde_json::from_value(raw_json).expect("Test json is not valid");
-        let mut context = default_context();
-        context.local_p2p_node = example_p2p_node();
+        let context = default_context();
 
         for block_num in 1..100 {
             let txs = vec![legacy_tx_for_test(1)];
@@ -375,18 +323,45 @@ mod tests {
         assert_eq!(response, expected_response)
     }
 
-    fn default_context() -> RpcApiContext {
-        RpcApiContext {
-            storage: setup_store(),
-            jwt_secret: Default::default(),
-            local_p2p_node: Node {
-                ip: std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-                udp_port: Default::default(),
-                tcp_port: Default::default(),
-                node_id: Default::default(),
-            },
-            active_filters: Default::default(),
-            syncer: Arc::new(Mutex::new(SyncManager::dummy())),
+    #[test]
+    fn tips_are_suggested_over_the_base_fee() {
+        let mut context = default_context();
+        for block_num in 1..30 {
+            let block_body = BlockBody {
+                transactions: (2..=4).map(eip1559_tx_for_test).collect(),
+                ommers: Default::default(),
+                withdrawals: Default::default(),
+            };
+            let block_header = BlockHeader {
+                base_fee_per_gas: Some(BASE_PRICE_IN_WEI),
+                ..test_header(block_num)
+            };
+            let block = Block::new(block_header.clone(), block_body);
+            context.storage.add_block(block).unwrap();
+            context
+                .storage
+                .set_canonical_block(block_num, block_header.compute_block_hash())
+                .unwrap();
+            context
+                .storage
+                .update_latest_block_number(block_num)
+                .unwrap();
         }
+        // Each block pays tips of 1, 2 and 3 gwei over its base fee of 1 gwei
+        let tip = MaxPriorityFeePerGas {}.handle(context.clone()).unwrap();
+        assert_eq!(parse_json_hex(&tip).unwrap(), 2 * BASE_PRICE_IN_WEI);
+        let gas_price = GasPrice {}.handle(context.clone()).unwrap();
+        assert_eq!(parse_json_hex(&gas_price).unwrap(), 3 * BASE_PRICE_IN_WEI);
+
+        context.gas_price_oracle = Arc::new(GasPriceOracle::new(GasPriceOracleConfig {
+            blocks: 1,
+            percentile: 0,
+        }));
+        let tip = MaxPriorityFeePerGas {}.handle(context).unwrap();
+        assert_eq!(parse_json_hex(&tip).unwrap(), BASE_PRICE_IN_WEI);
+    }
+
+    fn default_context() -> RpcApiContext {
+        default_context_with_storage(setup_store())
     }
 }
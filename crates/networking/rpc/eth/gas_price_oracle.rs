@@ -0,0 +1,106 @@
+use std::sync::Mutex;
+
+use ethrex_core::types::{BlockHash, BlockNumber};
+use ethrex_storage::Store;
+use tracing::error;
+
+use crate::utils::RpcErr;
+
+/// Transactions with the lowest tips taken as samples from each block
+const TXS_SAMPLE_SIZE: usize = 3;
+
+/// Parameters of the estimation of the tips behind `eth_gasPrice` and `eth_maxPriorityFeePerGas`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasPriceOracleConfig {
+    /// Amount of recent blocks whose transactions are sampled
+    pub blocks: u64,
+    /// Percentile of the sampled tips to suggest, from 0 to 100
+    pub percentile: u64,
+}
+
+impl Default for GasPriceOracleConfig {
+    /// The same parameters as geth's oracle
+    fn default() -> Self {
+        GasPriceOracleConfig {
+            blocks: 20,
+            percentile: 60,
+        }
+    }
+}
+
+/// Suggests a priority fee based on the effective tips paid by the transactions of recent
+/// blocks.
+/// As the suggestion only depends on the canonical head, it is computed once per head.
+#[derive(Debug, Default)]
+pub struct GasPriceOracle {
+    config: GasPriceOracleConfig,
+    /// Head of the last suggestion and the tip suggested
+    last_tip: Mutex<Option<(BlockHash, u64)>>,
+}
+
+impl GasPriceOracle {
+    pub fn new(config: GasPriceOracleConfig) -> Self {
+        GasPriceOracle {
+            config,
+            last_tip: Mutex::new(None),
+        }
+    }
+
+    /// Returns the suggested tip, in wei, for a transaction to be included on top of the
+    /// latest block.
+    /// If the recent blocks have no transactions, the last suggested tip is kept.
+    pub fn suggest_tip(&self, storage: &Store) -> Result<u64, RpcErr> {
+        let Some(latest_block_number) = storage.get_latest_block_number()? else {
+            error!("FATAL: LATEST BLOCK NUMBER IS MISSING");
+            return Err(RpcErr::Internal("Error calculating gas price".to_string()));
+        };
+        let Some(head) = storage.get_canonical_block_hash(latest_block_number)? else {
+            return Err(RpcErr::Internal(format!(
+                "Could not get hash of block {latest_block_number}"
+            )));
+        };
+        let mut last_tip = self
+            .last_tip
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match *last_tip {
+            Some((last_head, tip)) if last_head == head => return Ok(tip),
+            _ => {}
+        }
+        let mut samples = self.sample_tips(storage, latest_block_number)?;
+        samples.sort_unstable();
+        let tip = match samples.len() {
+            0 => last_tip.map(|(_, tip)| tip).unwrap_or_default(),
+            len => samples[(len - 1) * self.config.percentile.min(100) as usize / 100],
+        };
+        *last_tip = Some((head, tip));
+        Ok(tip)
+    }
+
+    /// Collects the lowest effective tips of each of the recent blocks
+    fn sample_tips(&self, storage: &Store, latest: BlockNumber) -> Result<Vec<u64>, RpcErr> {
+        let oldest = latest.saturating_sub(self.config.blocks.saturating_sub(1));
+        let mut samples = vec![];
+        for block_number in oldest..=latest {
+            let (Some(header), Some(body)) = (
+                storage.get_block_header(block_number)?,
+                storage.get_block_body(block_number)?,
+            ) else {
+                error!("Block {block_number} is missing but is below the latest known block!");
+                return Err(RpcErr::Internal(
+                    "Error calculating gas price: missing data".to_string(),
+                ));
+            };
+            // Blocks before London have no base fee, the whole gas price is the tip
+            let base_fee = Some(header.base_fee_per_gas.unwrap_or_default());
+            let mut tips = body
+                .transactions
+                .iter()
+                .filter_map(|tx| tx.effective_gas_tip(base_fee))
+                .collect::<Vec<u64>>();
+            tips.sort_unstable();
+            samples.extend(tips.into_iter().take(TXS_SAMPLE_SIZE));
+        }
+        Ok(samples)
+    }
+}
@@ -3,14 +3,25 @@
 // - Go-Ethereum, specifically: https://github.com/ethereum/go-ethereum/blob/368e16f39d6c7e5cce72a92ec289adbfbaed4854/eth/filters/filter.go
 // - Ethereum's reference: https://ethereum.org/en/developers/docs/apis/json-rpc/#eth_newfilter
 use crate::{
-    types::{block_identifier::BlockIdentifier, receipt::RpcLog},
+    types::{
+        block_identifier::{ensure_history_available, BlockIdentifier},
+        receipt::RpcLog,
+    },
     RpcApiContext, RpcErr, RpcHandler,
 };
-use ethrex_core::{H160, H256};
+use ethrex_core::{
+    types::{BlockBody, BlockHash, BlockNumber, Receipt},
+    H160, H256,
+};
 use ethrex_storage::Store;
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashSet;
+
+/// Orphaned blocks walked back to report their logs as removed when the node doesn't limit the
+/// depth of the reorgs
+const MAX_REMOVED_LOGS_DEPTH: u64 = 128;
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum AddressFilter {
@@ -117,12 +128,8 @@ pub(crate) fn fetch_logs_with_filter(
     if (from..=to).is_empty() {
         return Err(RpcErr::BadParams("Empty range".to_string()));
     }
-    let address_filter: HashSet<_> = match &filter.address_filters {
-        Some(AddressFilter::Single(address)) => std::iter::once(address).collect(),
-        Some(AddressFilter::Many(addresses)) => addresses.iter().collect(),
-        None => HashSet::new(),
-    };
-
+    // The history is pruned up to a block, so checking the first one is enough
+    ensure_history_available(&storage, from)?;
     let mut logs: Vec<RpcLog> = Vec::new();
     // The idea here is to fetch every log and filter by address, if given.
     // For that, we'll need each block in range, and its transactions,
@@ -142,72 +149,146 @@ pub(crate) fn fetch_logs_with_filter(
                 "Could not get header for block {block_num}"
             )))?;
         let block_hash = block_header.compute_block_hash();
-
-        let mut block_log_index = 0_u64;
-
         // Since transactions share indices with their receipts,
         // we'll use them to fetch their receipts, which have the actual logs.
-        for (tx_index, tx) in block_body.transactions.iter().enumerate() {
-            let tx_hash = tx.compute_hash();
-            let receipt = storage
-                .get_receipt(block_num, tx_index as u64)?
-                .ok_or(RpcErr::Internal("Could not get receipt".to_owned()))?;
+        let receipts = (0..block_body.transactions.len())
+            .map(|tx_index| {
+                storage
+                    .get_receipt(block_num, tx_index as u64)?
+                    .ok_or(RpcErr::Internal("Could not get receipt".to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        logs.extend(filter_block_logs(
+            filter,
+            block_num,
+            block_hash,
+            &block_body,
+            &receipts,
+            false,
+        ));
+    }
+    Ok(logs)
+}
 
-            if receipt.succeeded {
-                for log in &receipt.logs {
-                    if address_filter.is_empty() || address_filter.contains(&log.address) {
-                        // Some extra data is needed when
-                        // forming the RPC response.
-                        logs.push(RpcLog {
-                            log: log.clone().into(),
-                            log_index: block_log_index,
-                            transaction_hash: tx_hash,
-                            transaction_index: tx_index as u64,
-                            block_number: block_num,
-                            block_hash,
-                            removed: false,
-                        });
-                    }
-                    block_log_index += 1;
-                }
+/// Returns the logs of the blocks that were part of the canonical chain up to `block_hash`
+/// and no longer are, from the newest to the oldest one, marked as `removed`.
+/// Also returns the number of the newest block that is still canonical (the common ancestor
+/// of both chains), so callers know where to resume fetching logs from.
+/// If more blocks than the maximum reorg depth were removed, only the logs of the newest ones are
+/// returned, along with the parent of the block where the walk stopped, so the logs of the new
+/// chain are still fetched from that block onwards.
+pub(crate) fn fetch_removed_logs(
+    filter: &LogsFilter,
+    block_hash: BlockHash,
+    storage: &Store,
+) -> Result<(Vec<RpcLog>, Option<BlockNumber>), RpcErr> {
+    let max_depth = storage.max_reorg_depth().unwrap_or(MAX_REMOVED_LOGS_DEPTH);
+    let mut logs = Vec::new();
+    let mut block_hash = block_hash;
+    let mut removed_blocks = 0;
+    loop {
+        let Some(block_header) = storage.get_block_header_by_hash(block_hash)? else {
+            // The block is unknown, so there are no logs we can report
+            return Ok((logs, None));
+        };
+        let block_num = block_header.number;
+        if storage.get_canonical_block_hash(block_num)? == Some(block_hash) {
+            return Ok((logs, Some(block_num)));
+        }
+        if removed_blocks == max_depth {
+            return Ok((logs, block_num.checked_sub(1)));
+        }
+        removed_blocks += 1;
+        let block_body = storage
+            .get_block_body_by_hash(block_hash)?
+            .ok_or(RpcErr::Internal(format!(
+                "Could not get body for block {block_hash:#x}"
+            )))?;
+        let receipts = (0..block_body.transactions.len())
+            .map(|tx_index| {
+                storage
+                    .get_receipt_by_hash(block_hash, tx_index as u64)?
+                    .ok_or(RpcErr::Internal("Could not get receipt".to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut block_logs =
+            filter_block_logs(filter, block_num, block_hash, &block_body, &receipts, true);
+        block_logs.reverse();
+        logs.extend(block_logs);
+        if block_num == 0 {
+            return Ok((logs, None));
+        }
+        block_hash = block_header.parent_hash;
+    }
+}
+
+/// Returns the logs of the given block that match the filter's addresses and topics
+fn filter_block_logs(
+    filter: &LogsFilter,
+    block_number: BlockNumber,
+    block_hash: BlockHash,
+    block_body: &BlockBody,
+    receipts: &[Receipt],
+    removed: bool,
+) -> Vec<RpcLog> {
+    let address_filter: HashSet<_> = match &filter.address_filters {
+        Some(AddressFilter::Single(address)) => std::iter::once(address).collect(),
+        Some(AddressFilter::Many(addresses)) => addresses.iter().collect(),
+        None => HashSet::new(),
+    };
+
+    let mut logs: Vec<RpcLog> = Vec::new();
+    let mut block_log_index = 0_u64;
+    for (tx_index, (tx, receipt)) in block_body.transactions.iter().zip(receipts).enumerate() {
+        if !receipt.succeeded {
+            continue;
+        }
+        let tx_hash = tx.compute_hash();
+        for log in &receipt.logs {
+            if (address_filter.is_empty() || address_filter.contains(&log.address))
+                && matches_topics(&filter.topics, &log.topics)
+            {
+                // Some extra data is needed when
+                // forming the RPC response.
+                logs.push(RpcLog {
+                    log: log.clone().into(),
+                    log_index: block_log_index,
+                    transaction_hash: tx_hash,
+                    transaction_index: tx_index as u64,
+                    block_number,
+                    block_hash,
+                    removed,
+                });
             }
+            block_log_index += 1;
         }
     }
-    // Now that we have the logs filtered by address,
-    // we still need to filter by topics if it was a given parameter.
+    logs
+}
 
-    let filtered_logs = if filter.topics.is_empty() {
-        logs
-    } else {
-        logs.into_iter()
-            .filter(|rpc_log| {
-                if filter.topics.len() > rpc_log.log.topics.len() {
-                    return false;
-                }
-                for (i, topic_filter) in filter.topics.iter().enumerate() {
-                    match topic_filter {
-                        TopicFilter::Topic(t) => {
-                            if let Some(topic) = t {
-                                if rpc_log.log.topics[i] != *topic {
-                                    return false;
-                                }
-                            }
-                        }
-                        TopicFilter::Topics(sub_topics) => {
-                            if !sub_topics.is_empty()
-                                && !sub_topics
-                                    .iter()
-                                    .any(|st| st.map_or(true, |t| rpc_log.log.topics[i] == t))
-                            {
-                                return false;
-                            }
-                        }
+fn matches_topics(topic_filters: &[TopicFilter], topics: &[H256]) -> bool {
+    if topic_filters.len() > topics.len() {
+        return false;
+    }
+    for (i, topic_filter) in topic_filters.iter().enumerate() {
+        match topic_filter {
+            TopicFilter::Topic(t) => {
+                if let Some(topic) = t {
+                    if topics[i] != *topic {
+                        return false;
                     }
                 }
-                true
-            })
-            .collect::<Vec<RpcLog>>()
-    };
-
-    Ok(filtered_logs)
+            }
+            TopicFilter::Topics(sub_topics) => {
+                if !sub_topics.is_empty()
+                    && !sub_topics
+                        .iter()
+                        .any(|st| st.is_none_or(|t| topics[i] == t))
+                {
+                    return false;
+                }
+            }
+        }
+    }
+    true
 }
@@ -4,5 +4,7 @@ pub(crate) mod client;
 pub(crate) mod fee_market;
 pub(crate) mod filter;
 pub(crate) mod gas_price;
+pub(crate) mod gas_price_oracle;
 pub(crate) mod logs;
+pub(crate) mod simulate;
 pub(crate) mod transaction;
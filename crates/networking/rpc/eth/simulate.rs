@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use ethrex_blockchain::payload_manager::SLOT_DURATION_SECONDS;
+use ethrex_core::{
+    serde_utils,
+    types::{
+        calculate_base_fee_per_gas, BlockHash, BlockHeader, BlockNumber, GenericTransaction,
+        INITIAL_BASE_FEE,
+    },
+    Address, H256, U256,
+};
+use ethrex_storage::Store;
+use ethrex_vm::{
+    apply_state_overrides, evm_state, get_nonce_and_balance, simulate_tx_in_block, ExecutionResult,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    types::{
+        block_identifier::{ensure_state_available, BlockIdentifier},
+        receipt::RpcLogInfo,
+    },
+    utils::RpcErr,
+    RpcApiContext, RpcHandler,
+};
+
+/// Account changes to apply before running a simulation, indexed by account address
+pub type StateOverride = HashMap<Address, AccountOverride>;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOverride {
+    #[serde(default)]
+    pub balance: Option<U256>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub nonce: Option<u64>,
+    #[serde(default, deserialize_with = "deser_opt_bytes")]
+    pub code: Option<Bytes>,
+    #[serde(default)]
+    pub state: Option<HashMap<H256, U256>>,
+    #[serde(default)]
+    pub state_diff: Option<HashMap<H256, U256>>,
+}
+
+fn deser_opt_bytes<'de, D>(d: D) -> Result<Option<Bytes>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    serde_utils::bytes::deserialize(d).map(Some)
+}
+
+impl From<AccountOverride> for ethrex_vm::AccountOverride {
+    fn from(value: AccountOverride) -> Self {
+        Self {
+            balance: value.balance,
+            nonce: value.nonce,
+            code: value.code,
+            state: value.state,
+            state_diff: value.state_diff,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCallResult {
+    #[serde(with = "serde_utils::bool")]
+    pub status: bool,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub gas_used: u64,
+    #[serde(with = "serde_utils::bytes")]
+    pub return_data: Bytes,
+    pub logs: Vec<RpcLogInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<ExecutionResult> for SimulatedCallResult {
+    fn from(result: ExecutionResult) -> Self {
+        let error = match &result {
+            ExecutionResult::Success { .. } => None,
+            ExecutionResult::Revert { .. } => Some("execution reverted".to_owned()),
+            ExecutionResult::Halt { reason, .. } => Some(reason.clone()),
+        };
+        Self {
+            status: result.is_success(),
+            gas_used: result.gas_used(),
+            return_data: result.output(),
+            logs: result.logs().into_iter().map(RpcLogInfo::from).collect(),
+            error,
+        }
+    }
+}
+
+/// Runs the given transactions in order on top of the state of the given block, each one
+/// seeing the changes made by the previous ones. Failed transactions don't abort the
+/// simulation, their result is reported and the next transaction is executed.
+pub(crate) fn simulate_calls(
+    transactions: &[GenericTransaction],
+    header: &BlockHeader,
+    storage: Store,
+    state_overrides: &StateOverride,
+) -> Result<Vec<SimulatedCallResult>, RpcErr> {
+    let spec_id = ethrex_vm::spec_id(&storage.get_chain_config()?, header.timestamp);
+    let mut state = evm_state(storage, header.compute_block_hash());
+    let overrides = state_overrides
+        .iter()
+        .map(|(address, account)| (*address, account.clone().into()))
+        .collect();
+    apply_state_overrides(&mut state, &overrides)?;
+    transactions
+        .iter()
+        .map(|tx| {
+            ethrex_vm::simulate_tx_from_generic_and_commit(tx, header, &mut state, spec_id)
+                .map(SimulatedCallResult::from)
+                .map_err(RpcErr::from)
+        })
+        .collect()
+}
+
+pub struct CallManyRequest {
+    pub transactions: Vec<GenericTransaction>,
+    pub block: Option<BlockIdentifier>,
+    pub state_overrides: StateOverride,
+}
+
+impl RpcHandler for CallManyRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<CallManyRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.is_empty() {
+            return Err(RpcErr::BadParams("No params provided".to_owned()));
+        }
+        if params.len() > 3 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one to three params and {} were provided",
+                params.len()
+            )));
+        }
+        let block = match params.get(1) {
+            // Differentiate between missing and bad block param
+            Some(value) => Some(BlockIdentifier::parse(value.clone(), 1)?),
+            None => None,
+        };
+        let state_overrides = match params.get(2) {
+            Some(value) => serde_json::from_value(value.clone())?,
+            None => StateOverride::new(),
+        };
+        Ok(CallManyRequest {
+            transactions: serde_json::from_value(params[0].clone())?,
+            block,
+            state_overrides,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let block = self.block.clone().unwrap_or_default();
+        info!(
+            "Requested simulation of {} transactions on block: {}",
+            self.transactions.len(),
+            block
+        );
+        let header = match block.resolve_block_header(&context.storage)? {
+            Some(header) => header,
+            // Block not found
+            _ => return Ok(Value::Null),
+        };
+        ensure_state_available(&context.storage, header.number)?;
+        let results = simulate_calls(
+            &self.transactions,
+            &header,
+            context.storage,
+            &self.state_overrides,
+        )?;
+        serde_json::to_value(results).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Maximum amount of blocks simulated by a `eth_simulateV1` request, counting the empty blocks
+/// filling the gaps between the requested ones
+pub const MAX_SIMULATED_BLOCKS: u64 = 256;
+
+// Error codes defined by the spec of eth_simulateV1
+const BLOCK_GAS_LIMIT_REACHED: i32 = -38015;
+const INVALID_BLOCK_NUMBER: i32 = -38020;
+const INVALID_BLOCK_TIMESTAMP: i32 = -38021;
+const CLIENT_LIMIT_EXCEEDED: i32 = -38026;
+const REVERTED: i32 = 3;
+const VM_ERROR: i32 = -32015;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationPayload {
+    pub block_state_calls: Vec<BlockStateCalls>,
+    /// Whether the calls are checked as transactions included in the blocks would be
+    #[serde(default)]
+    pub validation: bool,
+    #[serde(default)]
+    pub trace_transfers: bool,
+}
+
+/// Calls to simulate in a block, after applying the overrides
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockStateCalls {
+    #[serde(default)]
+    pub block_overrides: BlockOverrides,
+    #[serde(default)]
+    pub state_overrides: StateOverride,
+    #[serde(default)]
+    pub calls: Vec<GenericTransaction>,
+}
+
+/// Fields of a simulated block replacing the ones derived from its parent
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOverrides {
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub number: Option<u64>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub time: Option<u64>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub gas_limit: Option<u64>,
+    #[serde(default)]
+    pub fee_recipient: Option<Address>,
+    #[serde(default)]
+    pub prev_randao: Option<H256>,
+    #[serde(default, with = "serde_utils::u64::hex_str_opt")]
+    pub base_fee_per_gas: Option<u64>,
+}
+
+impl BlockOverrides {
+    /// Returns the header with the overridden fields replaced, used to run a call in a block
+    /// context different from the one of the block whose state it reads
+    pub fn apply(&self, header: &BlockHeader) -> BlockHeader {
+        BlockHeader {
+            number: self.number.unwrap_or(header.number),
+            timestamp: self.time.unwrap_or(header.timestamp),
+            gas_limit: self.gas_limit.unwrap_or(header.gas_limit),
+            coinbase: self.fee_recipient.unwrap_or(header.coinbase),
+            prev_randao: self.prev_randao.unwrap_or(header.prev_randao),
+            base_fee_per_gas: self.base_fee_per_gas.or(header.base_fee_per_gas),
+            ..header.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedBlock {
+    pub hash: BlockHash,
+    #[serde(flatten)]
+    pub header: BlockHeader,
+    pub calls: Vec<SimulatedCall>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCall {
+    #[serde(with = "serde_utils::bool")]
+    pub status: bool,
+    #[serde(with = "serde_utils::bytes")]
+    pub return_data: Bytes,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub gas_used: u64,
+    pub logs: Vec<SimulatedLog>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SimulatedCallError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCallError {
+    pub code: i32,
+    pub message: String,
+}
+
+/// A log emitted by a simulated call, the calls are not signed so it has no transaction hash
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedLog {
+    #[serde(flatten)]
+    pub log: RpcLogInfo,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub log_index: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub transaction_index: u64,
+    pub block_hash: BlockHash,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub block_number: BlockNumber,
+}
+
+/// Returns the header of the simulated block following the given one.
+/// Without validation the base fee is zero unless overridden, so calls without fees can run.
+fn simulated_header(
+    parent: &BlockHeader,
+    overrides: &BlockOverrides,
+    validation: bool,
+) -> Result<BlockHeader, RpcErr> {
+    let number = overrides.number.unwrap_or(parent.number + 1);
+    let timestamp = overrides
+        .time
+        .unwrap_or(parent.timestamp + SLOT_DURATION_SECONDS);
+    if timestamp <= parent.timestamp {
+        return Err(RpcErr::InvalidSimulation {
+            code: INVALID_BLOCK_TIMESTAMP,
+            message: format!(
+                "block timestamps must increase: block {number} has timestamp {timestamp}, its parent {}",
+                parent.timestamp
+            ),
+        });
+    }
+    let gas_limit = overrides.gas_limit.unwrap_or(parent.gas_limit);
+    let base_fee_per_gas = match overrides.base_fee_per_gas {
+        Some(base_fee) => base_fee,
+        None if validation => {
+            let parent_base_fee = parent.base_fee_per_gas.unwrap_or(INITIAL_BASE_FEE);
+            calculate_base_fee_per_gas(
+                gas_limit,
+                parent.gas_limit,
+                parent.gas_used,
+                parent_base_fee,
+            )
+            .unwrap_or(parent_base_fee)
+        }
+        None => 0,
+    };
+    Ok(BlockHeader {
+        parent_hash: parent.compute_block_hash(),
+        coinbase: overrides.fee_recipient.unwrap_or(parent.coinbase),
+        number,
+        gas_limit,
+        timestamp,
+        prev_randao: overrides.prev_randao.unwrap_or_default(),
+        base_fee_per_gas: Some(base_fee_per_gas),
+        blob_gas_used: parent.blob_gas_used.map(|_| 0),
+        excess_blob_gas: parent.excess_blob_gas,
+        parent_beacon_block_root: parent.parent_beacon_block_root,
+        ..Default::default()
+    })
+}
+
+impl From<ExecutionResult> for SimulatedCall {
+    fn from(result: ExecutionResult) -> Self {
+        let error = match &result {
+            ExecutionResult::Success { .. } => None,
+            ExecutionResult::Revert { .. } => Some(SimulatedCallError {
+                code: REVERTED,
+                message: "execution reverted".to_owned(),
+            }),
+            ExecutionResult::Halt { reason, .. } => Some(SimulatedCallError {
+                code: VM_ERROR,
+                message: reason.clone(),
+            }),
+        };
+        Self {
+            status: result.is_success(),
+            return_data: result.output(),
+            gas_used: result.gas_used(),
+            // Filled once the block is sealed and its hash known
+            logs: vec![],
+            error,
+        }
+    }
+}
+
+/// Simulates the given blocks in order on top of the state of the base block, each block and
+/// call seeing the changes made by the previous ones. No change reaches the DB.
+pub(crate) fn simulate_blocks(
+    payload: &SimulationPayload,
+    base: &BlockHeader,
+    storage: Store,
+) -> Result<Vec<SimulatedBlock>, RpcErr> {
+    let chain_config = storage.get_chain_config()?;
+    let mut state = evm_state(storage, base.compute_block_hash());
+    let mut parent = base.clone();
+    let mut blocks = vec![];
+    for block_calls in &payload.block_state_calls {
+        let overrides = &block_calls.block_overrides;
+        let number = overrides.number.unwrap_or(parent.number + 1);
+        if number <= parent.number {
+            return Err(RpcErr::InvalidSimulation {
+                code: INVALID_BLOCK_NUMBER,
+                message: format!(
+                    "block numbers must increase: block {number} follows block {}",
+                    parent.number
+                ),
+            });
+        }
+        if number - base.number > MAX_SIMULATED_BLOCKS {
+            return Err(RpcErr::InvalidSimulation {
+                code: CLIENT_LIMIT_EXCEEDED,
+                message: format!(
+                    "too many blocks: at most {MAX_SIMULATED_BLOCKS} can be simulated"
+                ),
+            });
+        }
+        // The skipped block numbers are simulated as empty blocks
+        while parent.number + 1 < number {
+            let header = simulated_header(&parent, &BlockOverrides::default(), payload.validation)?;
+            blocks.push(SimulatedBlock {
+                hash: header.compute_block_hash(),
+                header: header.clone(),
+                calls: vec![],
+            });
+            parent = header;
+        }
+        let mut header = simulated_header(&parent, overrides, payload.validation)?;
+        let state_overrides = block_calls
+            .state_overrides
+            .iter()
+            .map(|(address, account)| (*address, account.clone().into()))
+            .collect();
+        apply_state_overrides(&mut state, &state_overrides)?;
+        let spec_id = ethrex_vm::spec_id(&chain_config, header.timestamp);
+        let mut results = vec![];
+        for tx in &block_calls.calls {
+            let mut tx = tx.clone();
+            let available_gas = header.gas_limit - header.gas_used;
+            let gas = *tx.gas.get_or_insert(available_gas);
+            if gas > available_gas {
+                return Err(RpcErr::InvalidSimulation {
+                    code: BLOCK_GAS_LIMIT_REACHED,
+                    message: format!(
+                        "block gas limit reached: the call needs {gas} gas and {available_gas} are left in block {number}"
+                    ),
+                });
+            }
+            if payload.validation && tx.nonce.is_none() {
+                tx.nonce = Some(get_nonce_and_balance(&mut state, tx.from)?.0);
+            }
+            let result =
+                simulate_tx_in_block(&tx, &header, &mut state, spec_id, payload.validation)?;
+            header.gas_used += result.gas_used();
+            results.push(result);
+        }
+        let hash = header.compute_block_hash();
+        let mut log_index = 0;
+        let calls = results
+            .into_iter()
+            .enumerate()
+            .map(|(transaction_index, result)| {
+                let logs = result.logs();
+                let mut call = SimulatedCall::from(result);
+                call.logs = logs
+                    .into_iter()
+                    .map(|log| {
+                        log_index += 1;
+                        SimulatedLog {
+                            log: log.into(),
+                            log_index: log_index - 1,
+                            transaction_index: transaction_index as u64,
+                            block_hash: hash,
+                            block_number: number,
+                        }
+                    })
+                    .collect();
+                call
+            })
+            .collect();
+        blocks.push(SimulatedBlock {
+            hash,
+            header: header.clone(),
+            calls,
+        });
+        parent = header;
+    }
+    Ok(blocks)
+}
+
+pub struct SimulateV1Request {
+    pub payload: SimulationPayload,
+    pub block: Option<BlockIdentifier>,
+}
+
+impl RpcHandler for SimulateV1Request {
+    fn parse(params: &Option<Vec<Value>>) -> Result<SimulateV1Request, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.is_empty() || params.len() > 2 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one or two params and {} were provided",
+                params.len()
+            )));
+        }
+        let payload: SimulationPayload = serde_json::from_value(params[0].clone())?;
+        if payload.trace_transfers {
+            return Err(RpcErr::BadParams(
+                "Tracing transfers is not supported".to_owned(),
+            ));
+        }
+        let block = match params.get(1) {
+            // Differentiate between missing and bad block param
+            Some(value) => Some(BlockIdentifier::parse(value.clone(), 1)?),
+            None => None,
+        };
+        Ok(SimulateV1Request { payload, block })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let block = self.block.clone().unwrap_or_default();
+        info!(
+            "Requested simulation of {} blocks on block: {}",
+            self.payload.block_state_calls.len(),
+            block
+        );
+        let header = match block.resolve_block_header(&context.storage)? {
+            Some(header) => header,
+            // Block not found
+            _ => return Ok(Value::Null),
+        };
+        ensure_state_available(&context.storage, header.number)?;
+        let blocks = simulate_blocks(&self.payload, &header, context.storage)?;
+        serde_json::to_value(blocks).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
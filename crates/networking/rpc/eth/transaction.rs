@@ -1,14 +1,20 @@
 use crate::{
-    eth::block,
+    eth::{
+        block,
+        simulate::{BlockOverrides, StateOverride},
+    },
     types::{
-        block_identifier::BlockIdentifier,
+        block_identifier::{ensure_history_available, ensure_state_available, BlockIdentifier},
         transaction::{RpcTransaction, SendRawTransactionRequest},
     },
     utils::RpcErr,
     RpcApiContext, RpcHandler,
 };
 use ethrex_core::{
-    types::{AccessListEntry, BlockHash, BlockHeader, BlockNumber, GenericTransaction, TxKind},
+    types::{
+        AccessListEntry, BlockHash, BlockHeader, GenericTransaction, Transaction,
+        TransactionConditions, TxKind,
+    },
     H256, U256,
 };
 
@@ -16,7 +22,7 @@ use ethrex_blockchain::mempool;
 use ethrex_rlp::encode::RLPEncode;
 use ethrex_storage::Store;
 
-use ethrex_vm::{evm_state, ExecutionResult, SpecId};
+use ethrex_vm::{evm_state, EvmState, ExecutionResult, SpecId};
 use serde::Serialize;
 
 use serde_json::Value;
@@ -29,6 +35,10 @@ pub const TRANSACTION_GAS: u64 = 21_000; // Per transaction not creating a contr
 pub struct CallRequest {
     transaction: GenericTransaction,
     block: Option<BlockIdentifier>,
+    /// Account changes applied to the state of the block before running the call
+    state_overrides: StateOverride,
+    /// Fields of the block header replaced while running the call
+    block_overrides: Option<BlockOverrides>,
 }
 
 pub struct GetTransactionByBlockNumberAndIndexRequest {
@@ -56,6 +66,9 @@ pub struct CreateAccessListRequest {
 pub struct EstimateGasRequest {
     pub transaction: GenericTransaction,
     pub block: Option<BlockIdentifier>,
+    /// Whether the pending transactions of the sender are executed before the estimated one,
+    /// so it runs with the nonce and balance the sender will have when it is included
+    pub apply_pending: bool,
 }
 
 pub struct GetRawTransaction {
@@ -80,9 +93,9 @@ impl RpcHandler for CallRequest {
         if params.is_empty() {
             return Err(RpcErr::BadParams("No params provided".to_owned()));
         }
-        if params.len() > 2 {
+        if params.len() > 4 {
             return Err(RpcErr::BadParams(format!(
-                "Expected one or two params and {} were provided",
+                "Expected one to four params and {} were provided",
                 params.len()
             )));
         }
@@ -91,9 +104,19 @@ impl RpcHandler for CallRequest {
             Some(value) => Some(BlockIdentifier::parse(value.clone(), 1)?),
             None => None,
         };
+        let state_overrides = match params.get(2) {
+            Some(Value::Null) | None => StateOverride::new(),
+            Some(value) => serde_json::from_value(value.clone())?,
+        };
+        let block_overrides = match params.get(3) {
+            Some(Value::Null) | None => None,
+            Some(value) => Some(serde_json::from_value(value.clone())?),
+        };
         Ok(CallRequest {
             transaction: serde_json::from_value(params[0].clone())?,
             block,
+            state_overrides,
+            block_overrides,
         })
     }
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
@@ -104,8 +127,31 @@ impl RpcHandler for CallRequest {
             // Block not found
             _ => return Ok(Value::Null),
         };
+        ensure_state_available(&context.storage, header.number)?;
         // Run transaction
-        let result = simulate_tx(&self.transaction, &header, context.storage, SpecId::CANCUN)?;
+        let result = if self.state_overrides.is_empty() && self.block_overrides.is_none() {
+            simulate_tx(
+                &self.transaction,
+                &[],
+                &header,
+                &context.storage,
+                SpecId::CANCUN,
+            )?
+        } else {
+            // The overrides are applied to a view of the state of the block, never to the DB
+            let mut state = evm_state(context.storage.clone(), header.compute_block_hash());
+            let overrides = self
+                .state_overrides
+                .iter()
+                .map(|(address, account)| (*address, account.clone().into()))
+                .collect();
+            ethrex_vm::apply_state_overrides(&mut state, &overrides)?;
+            let header = match &self.block_overrides {
+                Some(block_overrides) => block_overrides.apply(&header),
+                None => header,
+            };
+            simulate_tx_on_state(&self.transaction, &header, &mut state, SpecId::CANCUN)?
+        };
         serde_json::to_value(format!("0x{:#x}", result.output()))
             .map_err(|error| RpcErr::Internal(error.to_string()))
     }
@@ -141,6 +187,7 @@ impl RpcHandler for GetTransactionByBlockNumberAndIndexRequest {
             Some(block_number) => block_number,
             _ => return Ok(Value::Null),
         };
+        ensure_history_available(&context.storage, block_number)?;
         let block_body = match context.storage.get_block_body(block_number)? {
             Some(block_body) => block_body,
             _ => return Ok(Value::Null),
@@ -158,6 +205,7 @@ impl RpcHandler for GetTransactionByBlockNumberAndIndexRequest {
             block_number,
             block_header.compute_block_hash(),
             self.transaction_index,
+            &context.storage,
         );
         serde_json::to_value(tx).map_err(|error| RpcErr::Internal(error.to_string()))
     }
@@ -192,6 +240,7 @@ impl RpcHandler for GetTransactionByBlockHashAndIndexRequest {
             Some(number) => number,
             _ => return Ok(Value::Null),
         };
+        ensure_history_available(&context.storage, block_number)?;
         let block_body = match context.storage.get_block_body(block_number)? {
             Some(block_body) => block_body,
             _ => return Ok(Value::Null),
@@ -200,8 +249,13 @@ impl RpcHandler for GetTransactionByBlockHashAndIndexRequest {
             Some(tx) => tx,
             None => return Ok(Value::Null),
         };
-        let tx =
-            RpcTransaction::build(tx.clone(), block_number, self.block, self.transaction_index);
+        let tx = RpcTransaction::build(
+            tx.clone(),
+            block_number,
+            self.block,
+            self.transaction_index,
+            &context.storage,
+        );
         serde_json::to_value(tx).map_err(|error| RpcErr::Internal(error.to_string()))
     }
 }
@@ -232,6 +286,7 @@ impl RpcHandler for GetTransactionByHashRequest {
                 Some(location) => location,
                 _ => return Ok(Value::Null),
             };
+        ensure_history_available(storage, block_number)?;
 
         let transaction: ethrex_core::types::Transaction =
             match storage.get_transaction_by_location(block_hash, index)? {
@@ -239,8 +294,13 @@ impl RpcHandler for GetTransactionByHashRequest {
                 _ => return Ok(Value::Null),
             };
 
-        let transaction =
-            RpcTransaction::build(transaction, block_number, block_hash, index as usize);
+        let transaction = RpcTransaction::build(
+            transaction,
+            block_number,
+            block_hash,
+            index as usize,
+            storage,
+        );
         serde_json::to_value(transaction).map_err(|error| RpcErr::Internal(error.to_string()))
     }
 }
@@ -271,12 +331,12 @@ impl RpcHandler for GetTransactionReceiptRequest {
                 Some(location) => location,
                 _ => return Ok(Value::Null),
             };
+        ensure_history_available(storage, block_number)?;
         let block = match storage.get_block_by_hash(block_hash)? {
             Some(block) => block,
             None => return Ok(Value::Null),
         };
-        let receipts =
-            block::get_all_block_rpc_receipts(block_number, block.header, block.body, storage)?;
+        let receipts = block::get_all_block_rpc_receipts(block.header, block.body, storage)?;
         serde_json::to_value(receipts.get(index as usize))
             .map_err(|error| RpcErr::Internal(error.to_string()))
     }
@@ -318,12 +378,14 @@ impl RpcHandler for CreateAccessListRequest {
             // Block not found
             _ => return Ok(Value::Null),
         };
+        ensure_state_available(&context.storage, block_number)?;
+        let spec_id = ethrex_vm::spec_id(&context.storage.get_chain_config()?, header.timestamp);
         // Run transaction and obtain access list
         let (gas_used, access_list, error) = match ethrex_vm::create_access_list(
             &self.transaction,
             &header,
             &mut evm_state(context.storage, header.compute_block_hash()),
-            SpecId::CANCUN,
+            spec_id,
         )? {
             (
                 ExecutionResult::Success {
@@ -411,9 +473,9 @@ impl RpcHandler for EstimateGasRequest {
         if params.is_empty() {
             return Err(RpcErr::BadParams("No params provided".to_owned()));
         }
-        if params.len() > 2 {
+        if params.len() > 3 {
             return Err(RpcErr::BadParams(format!(
-                "Expected one or two params and {} were provided",
+                "Expected one to three params and {} were provided",
                 params.len()
             )));
         }
@@ -422,9 +484,14 @@ impl RpcHandler for EstimateGasRequest {
             Some(value) => Some(BlockIdentifier::parse(value.clone(), 1)?),
             None => None,
         };
+        let apply_pending = match params.get(2) {
+            Some(value) => serde_json::from_value(value.clone())?,
+            None => false,
+        };
         Ok(EstimateGasRequest {
             transaction: serde_json::from_value(params[0].clone())?,
             block,
+            apply_pending,
         })
     }
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
@@ -436,32 +503,40 @@ impl RpcHandler for EstimateGasRequest {
             // Block not found
             _ => return Ok(Value::Null),
         };
+        ensure_state_available(storage, block_header.number)?;
 
-        let transaction = match self.transaction.nonce {
-            Some(_nonce) => self.transaction.clone(),
-            None => {
-                let transaction_nonce = storage
-                    .get_nonce_by_account_address(block_header.number, self.transaction.from)?;
-
-                let mut cloned_transaction = self.transaction.clone();
-                cloned_transaction.nonce = transaction_nonce;
-                cloned_transaction
-            }
+        let spec_id = ethrex_vm::spec_id(&storage.get_chain_config()?, block_header.timestamp);
+        let sender = self.transaction.from;
+        let pending = if self.apply_pending {
+            let nonce = storage
+                .get_nonce_by_account_address(block_header.number, sender)?
+                .unwrap_or_default();
+            mempool::executable_transactions(&sender, nonce, storage)?
+        } else {
+            vec![]
         };
+        let (sender_nonce, sender_balance) = ethrex_vm::get_nonce_and_balance(
+            &mut pending_state(&pending, &block_header, storage, spec_id)?,
+            sender,
+        )?;
 
-        let spec_id = ethrex_vm::spec_id(&storage.get_chain_config()?, block_header.timestamp);
+        let mut transaction = self.transaction.clone();
+        transaction.nonce = transaction.nonce.or(Some(sender_nonce));
 
         // If the transaction is a plain value transfer, short circuit estimation.
         if let TxKind::Call(address) = transaction.to {
-            let account_info = storage.get_account_info(block_header.number, address)?;
-            let code = account_info.map(|info| storage.get_account_code(info.code_hash));
-            if code.is_none() {
+            let code = match storage.get_account_info(block_header.number, address)? {
+                Some(info) => storage.get_account_code(info.code_hash)?,
+                None => None,
+            };
+            if code.is_none_or(|code| code.is_empty()) {
                 let mut value_transfer_transaction = transaction.clone();
                 value_transfer_transaction.gas = Some(TRANSACTION_GAS);
                 let result: Result<ExecutionResult, RpcErr> = simulate_tx(
                     &value_transfer_transaction,
+                    &pending,
                     &block_header,
-                    storage.clone(),
+                    storage,
                     spec_id,
                 );
                 if let Ok(ExecutionResult::Success { .. }) = result {
@@ -478,47 +553,52 @@ impl RpcHandler for EstimateGasRequest {
         };
 
         if transaction.gas_price != 0 {
-            highest_gas_limit = recap_with_account_balances(
-                highest_gas_limit,
-                &transaction,
-                storage,
-                block_header.number,
-            )?;
+            highest_gas_limit =
+                recap_with_account_balance(highest_gas_limit, &transaction, sender_balance);
         }
 
-        // Check whether the execution is possible
-        let mut transaction = transaction.clone();
+        // Check whether the execution is possible, if it fails with the highest limit it fails
+        // with any of them, and the revert data is returned along with the error
         transaction.gas = Some(highest_gas_limit);
-        let result = simulate_tx(&transaction, &block_header, storage.clone(), spec_id)?;
+        let result = simulate_tx(&transaction, &pending, &block_header, storage, spec_id)?;
 
         let gas_used = result.gas_used();
         let gas_refunded = result.gas_refunded();
+        let mut lowest_gas_limit = gas_used.saturating_sub(1);
+        let mut succeeds_with = |gas_limit: u64| {
+            transaction.gas = Some(gas_limit);
+            matches!(
+                simulate_tx(&transaction, &pending, &block_header, storage, spec_id),
+                Ok(ExecutionResult::Success { .. })
+            )
+        };
 
-        // Choose an optimistic start limit. See https://github.com/ethereum/go-ethereum/blob/a5a4fa7032bb248f5a7c40f4e8df2b131c4186a4/eth/gasestimator/gasestimator.go#L135
+        // Most transactions succeed with a limit just above the gas they used, so it is tried
+        // before searching. See https://github.com/ethereum/go-ethereum/blob/a5a4fa7032bb248f5a7c40f4e8df2b131c4186a4/eth/gasestimator/gasestimator.go#L135
         let optimistic_limit = (gas_used + gas_refunded + CALL_STIPEND) * 64 / 63;
-        let mut lowest_gas_limit = gas_used.saturating_sub(1);
-        let mut middle_gas_limit = (optimistic_limit + lowest_gas_limit) / 2;
+        if optimistic_limit < highest_gas_limit {
+            if succeeds_with(optimistic_limit) {
+                highest_gas_limit = optimistic_limit;
+            } else {
+                lowest_gas_limit = optimistic_limit;
+            }
+        }
 
+        // Binary search the lowest limit the transaction succeeds with
         while lowest_gas_limit + 1 < highest_gas_limit {
             if (highest_gas_limit - lowest_gas_limit) as f64 / (highest_gas_limit as f64)
                 < ESTIMATE_ERROR_RATIO
             {
                 break;
             };
-
-            if middle_gas_limit > lowest_gas_limit * 2 {
-                // Favor the low side, since most transactions don't need much higher gas limit than their gas used.
-                middle_gas_limit = lowest_gas_limit * 2;
-            }
-            transaction.gas = Some(middle_gas_limit);
-
-            let result = simulate_tx(&transaction, &block_header, storage.clone(), spec_id);
-            if let Ok(ExecutionResult::Success { .. }) = result {
+            // Favor the low side, since most transactions don't need much higher gas limit than their gas used.
+            let middle_gas_limit =
+                ((highest_gas_limit + lowest_gas_limit) / 2).min(lowest_gas_limit * 2);
+            if succeeds_with(middle_gas_limit) {
                 highest_gas_limit = middle_gas_limit;
             } else {
                 lowest_gas_limit = middle_gas_limit;
             };
-            middle_gas_limit = (highest_gas_limit + lowest_gas_limit) / 2;
         }
 
         serde_json::to_value(format!("{:#x}", highest_gas_limit))
@@ -526,33 +606,55 @@ impl RpcHandler for EstimateGasRequest {
     }
 }
 
-fn recap_with_account_balances(
+fn recap_with_account_balance(
     highest_gas_limit: u64,
     transaction: &GenericTransaction,
-    storage: &Store,
-    block_number: BlockNumber,
-) -> Result<u64, RpcErr> {
-    let account_balance = storage
-        .get_account_info(block_number, transaction.from)?
-        .map(|acc| acc.balance)
-        .unwrap_or_default();
+    account_balance: U256,
+) -> u64 {
     let account_gas =
         account_balance.saturating_sub(transaction.value) / U256::from(transaction.gas_price);
-    Ok(highest_gas_limit.min(account_gas.as_u64()))
+    highest_gas_limit.min(account_gas.try_into().unwrap_or(u64::MAX))
+}
+
+/// Returns the state of the block with the given pending transactions executed on top of it,
+/// the execution stops at the first one that can't be included
+fn pending_state(
+    pending: &[Transaction],
+    block_header: &BlockHeader,
+    storage: &Store,
+    spec_id: SpecId,
+) -> Result<EvmState, RpcErr> {
+    let mut state = evm_state(storage.clone(), block_header.compute_block_hash());
+    for transaction in pending {
+        if ethrex_vm::execute_tx(transaction, block_header, &mut state, spec_id).is_err() {
+            break;
+        }
+    }
+    Ok(state)
 }
 
 fn simulate_tx(
     transaction: &GenericTransaction,
+    pending: &[Transaction],
     block_header: &BlockHeader,
-    storage: Store,
+    storage: &Store,
     spec_id: SpecId,
 ) -> Result<ExecutionResult, RpcErr> {
-    match ethrex_vm::simulate_tx_from_generic(
+    simulate_tx_on_state(
         transaction,
         block_header,
-        &mut evm_state(storage, block_header.compute_block_hash()),
+        &mut pending_state(pending, block_header, storage, spec_id)?,
         spec_id,
-    )? {
+    )
+}
+
+fn simulate_tx_on_state(
+    transaction: &GenericTransaction,
+    block_header: &BlockHeader,
+    state: &mut EvmState,
+    spec_id: SpecId,
+) -> Result<ExecutionResult, RpcErr> {
+    match ethrex_vm::simulate_tx_from_generic(transaction, block_header, state, spec_id)? {
         ExecutionResult::Revert {
             gas_used: _,
             output,
@@ -564,6 +666,18 @@ fn simulate_tx(
     }
 }
 
+/// Decodes a hex encoded transaction, as sent to `eth_sendRawTransaction`
+fn parse_raw_transaction(param: &Value) -> Result<SendRawTransactionRequest, RpcErr> {
+    let str_data = serde_json::from_value::<String>(param.clone())?;
+    let str_data = str_data
+        .strip_prefix("0x")
+        .ok_or(RpcErr::BadParams("Params are note 0x prefixed".to_owned()))?;
+    let data = hex::decode(str_data).map_err(|error| RpcErr::BadParams(error.to_string()))?;
+
+    SendRawTransactionRequest::decode_canonical(&data)
+        .map_err(|error| RpcErr::BadParams(error.to_string()))
+}
+
 impl RpcHandler for SendRawTransactionRequest {
     fn parse(params: &Option<Vec<Value>>) -> Result<SendRawTransactionRequest, RpcErr> {
         let params = params
@@ -576,16 +690,7 @@ impl RpcHandler for SendRawTransactionRequest {
             )));
         };
 
-        let str_data = serde_json::from_value::<String>(params[0].clone())?;
-        let str_data = str_data
-            .strip_prefix("0x")
-            .ok_or(RpcErr::BadParams("Params are note 0x prefixed".to_owned()))?;
-        let data = hex::decode(str_data).map_err(|error| RpcErr::BadParams(error.to_string()))?;
-
-        let transaction = SendRawTransactionRequest::decode_canonical(&data)
-            .map_err(|error| RpcErr::BadParams(error.to_string()))?;
-
-        Ok(transaction)
+        parse_raw_transaction(&params[0])
     }
     fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
         let hash = if let SendRawTransactionRequest::EIP4844(wrapped_blob_tx) = self {
@@ -601,3 +706,42 @@ impl RpcHandler for SendRawTransactionRequest {
             .map_err(|error| RpcErr::Internal(error.to_string()))
     }
 }
+
+pub struct SendRawTransactionConditionalRequest {
+    pub transaction: SendRawTransactionRequest,
+    pub conditions: TransactionConditions,
+}
+
+impl RpcHandler for SendRawTransactionConditionalRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<SendRawTransactionConditionalRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 2 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected two params and {} were provided",
+                params.len()
+            )));
+        };
+        let transaction = parse_raw_transaction(&params[0])?;
+        if matches!(transaction, SendRawTransactionRequest::EIP4844(_)) {
+            return Err(RpcErr::BadParams(
+                "Blob transactions can't be sent as conditional transactions".to_owned(),
+            ));
+        }
+        Ok(SendRawTransactionConditionalRequest {
+            transaction,
+            conditions: serde_json::from_value(params[1].clone())?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        let hash = mempool::add_conditional_transaction(
+            self.transaction.to_transaction(),
+            self.conditions.clone(),
+            &context.storage,
+        )?;
+        serde_json::to_value(format!("{:#x}", hash))
+            .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
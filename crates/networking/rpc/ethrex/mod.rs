@@ -0,0 +1,960 @@
+use bytes::Bytes;
+use ethrex_blockchain::{
+    blob_sidecars::blob_sidecars_retention,
+    constants::{MAX_BLOB_NUMBER_PER_BLOCK, TARGET_BLOB_NUMBER_PER_BLOCK},
+    mempool::{self, InclusionBlocker},
+    payload::project_fees,
+};
+use ethrex_core::{
+    serde_utils,
+    types::{
+        compute_priority_fees, compute_receipt_proof, BlockHash, BlockHeader, BlockNumber,
+        ChainConfig, Index, Receipt, BLOB_BASE_FEE_UPDATE_FRACTION,
+    },
+    Address, H256, U256,
+};
+use ethrex_net::sync::SyncStrategy;
+use ethrex_rlp::encode::RLPEncode;
+use ethrex_storage::Store;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    types::{
+        account_proof::serialize_proofs,
+        block_identifier::{BlockIdentifier, BlockIdentifierOrHash},
+    },
+    utils::RpcErr,
+    RpcApiContext, RpcHandler, AUTHRPC_NAMESPACES, HTTP_NAMESPACES,
+};
+
+/// Maximum amount of transactions returned in a single page
+pub const MAX_ADDRESS_TRANSACTIONS_PAGE_SIZE: u64 = 1000;
+
+/// Maximum amount of blocks whose fee recipient earnings can be requested at once
+pub const MAX_FEE_RECIPIENT_EARNINGS_BLOCK_RANGE: u64 = 1024;
+
+/// Maximum amount of receipts that can be requested in a single light client bundle
+pub const MAX_LIGHT_CLIENT_BUNDLE_RECEIPTS: usize = 256;
+
+/// Maximum amount of blocks whose fees can be projected at once
+pub const MAX_FEE_PROJECTION_BLOCKS: u64 = 64;
+
+/// Maximum amount of accounts that can be requested in a single batch
+pub const MAX_ACCOUNTS_BATCH_SIZE: usize = 1024;
+
+pub struct GetTransactionsByAddressRequest {
+    pub address: Address,
+    pub page: u64,
+    pub page_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTransaction {
+    pub hash: H256,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub block_number: BlockNumber,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub transaction_index: Index,
+}
+
+fn parse_quantity(param: &Value, name: &str) -> Result<u64, RpcErr> {
+    let quantity: String = serde_json::from_value(param.clone())?;
+    let quantity = quantity
+        .strip_prefix("0x")
+        .ok_or(RpcErr::BadParams(format!("{name} is not 0x prefixed")))?;
+    u64::from_str_radix(quantity, 16).map_err(|_| RpcErr::BadParams(format!("Invalid {name}")))
+}
+
+fn get_block_receipts(
+    storage: &Store,
+    number: BlockNumber,
+    transaction_count: usize,
+) -> Result<Vec<Receipt>, RpcErr> {
+    (0..transaction_count as Index)
+        .map(|index| {
+            storage
+                .get_receipt(number, index)?
+                .ok_or(RpcErr::Internal(format!(
+                    "Missing receipt {index} of block {number}"
+                )))
+        })
+        .collect()
+}
+
+impl RpcHandler for GetTransactionsByAddressRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetTransactionsByAddressRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 3 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected three params and {} were provided",
+                params.len()
+            )));
+        };
+        let page_size = parse_quantity(&params[2], "pageSize")?;
+        if page_size == 0 || page_size > MAX_ADDRESS_TRANSACTIONS_PAGE_SIZE {
+            return Err(RpcErr::BadParams(format!(
+                "pageSize must be between 1 and {MAX_ADDRESS_TRANSACTIONS_PAGE_SIZE}"
+            )));
+        }
+        Ok(GetTransactionsByAddressRequest {
+            address: serde_json::from_value(params[0].clone())?,
+            page: parse_quantity(&params[1], "page")?,
+            page_size,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested page {} of the transactions of {:#x}",
+            self.page, self.address
+        );
+        if !context.storage.address_index_enabled() {
+            return Err(RpcErr::Internal(
+                "Address index is disabled, start the node with --index-addresses".to_owned(),
+            ));
+        }
+        let offset = self
+            .page
+            .checked_mul(self.page_size)
+            .ok_or(RpcErr::BadParams("page is too big".to_owned()))?;
+        let transactions: Vec<AddressTransaction> = context
+            .storage
+            .get_address_transactions(
+                self.address,
+                offset.try_into().unwrap_or(usize::MAX),
+                self.page_size as usize,
+            )?
+            .into_iter()
+            .map(
+                |(block_number, transaction_index, hash)| AddressTransaction {
+                    hash,
+                    block_number,
+                    transaction_index,
+                },
+            )
+            .collect();
+        serde_json::to_value(transactions).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+pub struct GetFeeRecipientEarningsRequest {
+    pub fee_recipient: Address,
+    pub from: BlockIdentifier,
+    pub to: BlockIdentifier,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeRecipientEarnings {
+    pub fee_recipient: Address,
+    pub total_earnings: U256,
+    pub blocks: Vec<BlockEarnings>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockEarnings {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub number: BlockNumber,
+    pub hash: BlockHash,
+    pub earnings: U256,
+}
+
+impl RpcHandler for GetFeeRecipientEarningsRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetFeeRecipientEarningsRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 3 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected three params and {} were provided",
+                params.len()
+            )));
+        };
+        Ok(GetFeeRecipientEarningsRequest {
+            fee_recipient: serde_json::from_value(params[0].clone())?,
+            from: BlockIdentifier::parse(params[1].clone(), 1)?,
+            to: BlockIdentifier::parse(params[2].clone(), 2)?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested earnings of fee recipient {:#x} from block {} to block {}",
+            self.fee_recipient, self.from, self.to
+        );
+        let storage = &context.storage;
+        let (Some(from), Some(to)) = (
+            self.from.resolve_block_number(storage)?,
+            self.to.resolve_block_number(storage)?,
+        ) else {
+            return Err(RpcErr::BadParams("Block not found".to_owned()));
+        };
+        if from > to {
+            return Err(RpcErr::BadParams(
+                "fromBlock must not be greater than toBlock".to_owned(),
+            ));
+        }
+        if to - from >= MAX_FEE_RECIPIENT_EARNINGS_BLOCK_RANGE {
+            return Err(RpcErr::BadParams(format!(
+                "Block range must not exceed {MAX_FEE_RECIPIENT_EARNINGS_BLOCK_RANGE} blocks"
+            )));
+        }
+        let mut earnings = FeeRecipientEarnings {
+            fee_recipient: self.fee_recipient,
+            total_earnings: U256::zero(),
+            blocks: vec![],
+        };
+        for number in from..=to {
+            let (Some(header), Some(body)) = (
+                storage.get_block_header(number)?,
+                storage.get_block_body(number)?,
+            ) else {
+                return Err(RpcErr::Internal(format!("Missing block {number}")));
+            };
+            if header.coinbase != self.fee_recipient {
+                continue;
+            }
+            let receipts = get_block_receipts(storage, number, body.transactions.len())?;
+            let block_earnings =
+                compute_priority_fees(&body.transactions, &receipts, header.base_fee_per_gas);
+            earnings.total_earnings += block_earnings;
+            earnings.blocks.push(BlockEarnings {
+                number,
+                hash: header.compute_block_hash(),
+                earnings: block_earnings,
+            });
+        }
+        serde_json::to_value(earnings).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+pub struct GetLightClientBundleRequest {
+    pub block: BlockIdentifierOrHash,
+    pub receipt_indices: Vec<Index>,
+}
+
+/// Header of a block together with some of its receipts and their proofs against the
+/// receipts root of the header, so they can be verified without any other request
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightClientBundle {
+    pub hash: BlockHash,
+    pub header: BlockHeader,
+    #[serde(with = "serde_utils::bytes")]
+    pub encoded_header: Bytes,
+    pub receipts: Vec<ReceiptWithProof>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptWithProof {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub transaction_index: Index,
+    /// Receipt as encoded in the receipts trie
+    #[serde(with = "serde_utils::bytes")]
+    pub receipt: Bytes,
+    #[serde(serialize_with = "serialize_proofs")]
+    pub proof: Vec<Vec<u8>>,
+}
+
+impl RpcHandler for GetLightClientBundleRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetLightClientBundleRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 2 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected two params and {} were provided",
+                params.len()
+            )));
+        };
+        let receipt_indices = serde_json::from_value::<Vec<Value>>(params[1].clone())?
+            .iter()
+            .map(|index| parse_quantity(index, "receipt index"))
+            .collect::<Result<Vec<_>, RpcErr>>()?;
+        if receipt_indices.len() > MAX_LIGHT_CLIENT_BUNDLE_RECEIPTS {
+            return Err(RpcErr::BadParams(format!(
+                "At most {MAX_LIGHT_CLIENT_BUNDLE_RECEIPTS} receipts can be requested"
+            )));
+        }
+        Ok(GetLightClientBundleRequest {
+            block: BlockIdentifierOrHash::parse(params[0].clone(), 0)?,
+            receipt_indices,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested light client bundle of block {} with receipts {:?}",
+            self.block, self.receipt_indices
+        );
+        let storage = &context.storage;
+        let Some(number) = self.block.resolve_block_number(storage)? else {
+            return Ok(Value::Null);
+        };
+        let Some(header) = storage.get_block_header(number)? else {
+            return Ok(Value::Null);
+        };
+        let mut bundle = LightClientBundle {
+            hash: header.compute_block_hash(),
+            encoded_header: header.encode_to_vec().into(),
+            header,
+            receipts: vec![],
+        };
+        // Only the header is needed if no receipts were requested
+        if !self.receipt_indices.is_empty() {
+            let Some(body) = storage.get_block_body(number)? else {
+                return Err(RpcErr::Internal(format!("Missing body of block {number}")));
+            };
+            let receipts = get_block_receipts(storage, number, body.transactions.len())?;
+            for index in self.receipt_indices.iter().copied() {
+                let Some(receipt) = receipts.get(index as usize) else {
+                    return Err(RpcErr::BadParams(format!(
+                        "Block {number} has no receipt with index {index}"
+                    )));
+                };
+                let proof = compute_receipt_proof(&receipts, index as usize)
+                    .map_err(|error| RpcErr::Internal(error.to_string()))?;
+                bundle.receipts.push(ReceiptWithProof {
+                    transaction_index: index,
+                    receipt: receipt.encode_to_vec().into(),
+                    proof,
+                });
+            }
+        }
+        serde_json::to_value(bundle).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+pub struct GetPendingNonceGapsRequest {
+    pub address: Address,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcNonceGap {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub from: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub to: u64,
+    pub blocked_transaction: H256,
+}
+
+impl RpcHandler for GetPendingNonceGapsRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetPendingNonceGapsRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 1 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one param and {} were provided",
+                params.len()
+            )));
+        };
+        Ok(GetPendingNonceGapsRequest {
+            address: serde_json::from_value(params[0].clone())?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!("Requested pending nonce gaps of {:#x}", self.address);
+        let gaps: Vec<RpcNonceGap> = mempool::nonce_gaps(&self.address, &context.storage)?
+            .into_iter()
+            .map(|gap| RpcNonceGap {
+                from: gap.from,
+                to: gap.to,
+                blocked_transaction: gap.blocked_transaction,
+            })
+            .collect();
+        serde_json::to_value(gaps).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Reports why a transaction is or isn't included in the next built payload
+pub struct InspectTransactionRequest {
+    pub transaction_hash: H256,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionPoolStatus {
+    /// In the mempool and includable in the next block
+    Pending,
+    /// In the mempool but blocked from being included
+    Queued,
+    Included,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionInspectionResponse {
+    pub status: TransactionPoolStatus,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "serde_utils::u64::hex_str_opt"
+    )]
+    pub block_number: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<BlockHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender: Option<Address>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "serde_utils::u64::hex_str_opt"
+    )]
+    pub nonce: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "serde_utils::u64::hex_str_opt"
+    )]
+    pub effective_tip: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_position: Option<usize>,
+    pub blockers: Vec<RpcInclusionBlocker>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum RpcInclusionBlocker {
+    #[serde(rename_all = "camelCase")]
+    NonceGap {
+        #[serde(with = "serde_utils::u64::hex_str")]
+        from: u64,
+        #[serde(with = "serde_utils::u64::hex_str")]
+        to: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    NonceTooLow {
+        #[serde(with = "serde_utils::u64::hex_str")]
+        account_nonce: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    InsufficientBalance { balance: U256, cost: U256 },
+    #[serde(rename_all = "camelCase")]
+    FeeCapBelowBaseFee {
+        #[serde(with = "serde_utils::u64::hex_str")]
+        base_fee: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    BlobFeeCapBelowBaseFee {
+        #[serde(with = "serde_utils::u64::hex_str")]
+        blob_base_fee: u64,
+    },
+}
+
+impl From<InclusionBlocker> for RpcInclusionBlocker {
+    fn from(blocker: InclusionBlocker) -> Self {
+        match blocker {
+            InclusionBlocker::NonceGap { from, to } => Self::NonceGap { from, to },
+            InclusionBlocker::NonceTooLow { account_nonce } => Self::NonceTooLow { account_nonce },
+            InclusionBlocker::InsufficientBalance { balance, cost } => {
+                Self::InsufficientBalance { balance, cost }
+            }
+            InclusionBlocker::FeeCapBelowBaseFee { base_fee } => {
+                Self::FeeCapBelowBaseFee { base_fee }
+            }
+            InclusionBlocker::BlobFeeCapBelowBaseFee { blob_base_fee } => {
+                Self::BlobFeeCapBelowBaseFee { blob_base_fee }
+            }
+        }
+    }
+}
+
+impl RpcHandler for InspectTransactionRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<InspectTransactionRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 1 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one param and {} were provided",
+                params.len()
+            )));
+        };
+        Ok(InspectTransactionRequest {
+            transaction_hash: serde_json::from_value(params[0].clone())?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested inspection of transaction {:#x}",
+            self.transaction_hash
+        );
+        let response = match mempool::inspect_transaction(self.transaction_hash, &context.storage)?
+        {
+            Some(inspection) => TransactionInspectionResponse {
+                status: if inspection.blockers.is_empty() {
+                    TransactionPoolStatus::Pending
+                } else {
+                    TransactionPoolStatus::Queued
+                },
+                block_number: None,
+                block_hash: None,
+                sender: Some(inspection.sender),
+                nonce: Some(inspection.nonce),
+                effective_tip: inspection.effective_tip,
+                priority_position: inspection.priority_position,
+                blockers: inspection.blockers.into_iter().map(Into::into).collect(),
+            },
+            None => {
+                let Some((block_number, block_hash, _)) = context
+                    .storage
+                    .get_transaction_location(self.transaction_hash)?
+                else {
+                    // Unknown transactions are returned as null
+                    return Ok(Value::Null);
+                };
+                TransactionInspectionResponse {
+                    status: TransactionPoolStatus::Included,
+                    block_number: Some(block_number),
+                    block_hash: Some(block_hash),
+                    sender: None,
+                    nonce: None,
+                    effective_tip: None,
+                    priority_position: None,
+                    blockers: vec![],
+                }
+            }
+        };
+        serde_json::to_value(response).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Projects the fees of the blocks following the latest one, by default assuming they are full
+pub struct ProjectFeesRequest {
+    pub block_count: u64,
+    pub gas_used_percent: u64,
+    pub blobs_per_block: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcFeeProjection {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub number: BlockNumber,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub base_fee_per_gas: u64,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "serde_utils::u64::hex_str_opt"
+    )]
+    pub blob_base_fee: Option<u64>,
+}
+
+impl RpcHandler for ProjectFeesRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<ProjectFeesRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.is_empty() || params.len() > 3 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one to three params and {} were provided",
+                params.len()
+            )));
+        };
+        let block_count = parse_quantity(&params[0], "blockCount")?;
+        if block_count == 0 || block_count > MAX_FEE_PROJECTION_BLOCKS {
+            return Err(RpcErr::BadParams(format!(
+                "blockCount must be between 1 and {MAX_FEE_PROJECTION_BLOCKS}"
+            )));
+        }
+        let gas_used_percent = match params.get(1) {
+            Some(param) => parse_quantity(param, "gasUsedPercent")?,
+            None => 100,
+        };
+        if gas_used_percent > 100 {
+            return Err(RpcErr::BadParams(
+                "gasUsedPercent can't be above 100".to_owned(),
+            ));
+        }
+        let blobs_per_block = match params.get(2) {
+            Some(param) => parse_quantity(param, "blobsPerBlock")?,
+            None => MAX_BLOB_NUMBER_PER_BLOCK,
+        };
+        if blobs_per_block > MAX_BLOB_NUMBER_PER_BLOCK {
+            return Err(RpcErr::BadParams(format!(
+                "blobsPerBlock can't be above {MAX_BLOB_NUMBER_PER_BLOCK}"
+            )));
+        }
+        Ok(ProjectFeesRequest {
+            block_count,
+            gas_used_percent,
+            blobs_per_block,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested fee projection of {} blocks {}% full with {} blobs",
+            self.block_count, self.gas_used_percent, self.blobs_per_block
+        );
+        let storage = &context.storage;
+        let latest = storage.get_latest_block_number()?.unwrap_or_default();
+        let Some(header) = storage.get_block_header(latest)? else {
+            return Err(RpcErr::Internal(format!(
+                "Missing header of block {latest}"
+            )));
+        };
+        let projections: Vec<RpcFeeProjection> = project_fees(
+            &header,
+            self.block_count,
+            self.gas_used_percent,
+            self.blobs_per_block,
+            context.payload_manager.config().gas_ceil,
+        )
+        .into_iter()
+        .map(|fees| RpcFeeProjection {
+            number: fees.number,
+            base_fee_per_gas: fees.base_fee_per_gas,
+            blob_base_fee: fees.base_fee_per_blob_gas,
+        })
+        .collect();
+        serde_json::to_value(projections).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Returns the archived blob sidecars of the blob transactions of a block, blocks of other
+/// forks can be requested by hash
+pub struct GetBlobSidecarsRequest {
+    pub block: BlockIdentifierOrHash,
+}
+
+impl RpcHandler for GetBlobSidecarsRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetBlobSidecarsRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 1 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected one param and {} were provided",
+                params.len()
+            )));
+        };
+        Ok(GetBlobSidecarsRequest {
+            block: BlockIdentifierOrHash::parse(params[0].clone(), 0)?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!("Requested blob sidecars of block {}", self.block);
+        let storage = &context.storage;
+        let block_hash = match &self.block {
+            BlockIdentifierOrHash::Hash(block_hash) => *block_hash,
+            BlockIdentifierOrHash::Identifier(block) => {
+                let Some(number) = block.resolve_block_number(storage)? else {
+                    return Ok(Value::Null);
+                };
+                let Some(block_hash) = storage.get_canonical_block_hash(number)? else {
+                    return Ok(Value::Null);
+                };
+                block_hash
+            }
+        };
+        match storage.get_blob_sidecars(block_hash)? {
+            Some(sidecars) => {
+                serde_json::to_value(sidecars).map_err(|error| RpcErr::Internal(error.to_string()))
+            }
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+/// Configuration of the node, so the infrastructure around it can configure itself against it
+pub struct GetNodeConfigRequest;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeConfig {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub chain_id: u64,
+    /// Fork schedule and chain id of the network
+    pub chain_config: ChainConfig,
+    /// Fork active at the latest block
+    pub active_fork: String,
+    /// Limits of the blobs of each block, none before Cancun
+    pub blob_schedule: Option<BlobSchedule>,
+    pub http_namespaces: Vec<&'static str>,
+    pub authrpc_namespaces: Vec<&'static str>,
+    pub sync_mode: &'static str,
+    pub pruning: PruningConfig,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobSchedule {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub target: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub max: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub base_fee_update_fraction: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruningConfig {
+    /// Earliest block whose body and receipts are stored
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub earliest_block: BlockNumber,
+    /// Seconds the blob sidecars of the included blocks are kept for
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub blob_sidecars_retention: u64,
+}
+
+impl RpcHandler for GetNodeConfigRequest {
+    fn parse(_params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        Ok(GetNodeConfigRequest)
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!("Requested node config");
+        let storage = &context.storage;
+        let chain_config = storage.get_chain_config()?;
+        let latest_number = storage.get_latest_block_number()?.ok_or(RpcErr::Internal(
+            "Latest block number not found".to_string(),
+        ))?;
+        let latest_header = storage
+            .get_block_header(latest_number)?
+            .ok_or(RpcErr::Internal(format!("Block {latest_number} not found")))?;
+        let blob_schedule = chain_config.cancun_time.is_some().then_some(BlobSchedule {
+            target: TARGET_BLOB_NUMBER_PER_BLOCK,
+            max: MAX_BLOB_NUMBER_PER_BLOCK,
+            base_fee_update_fraction: BLOB_BASE_FEE_UPDATE_FRACTION,
+        });
+        let sync_mode = match context.syncer.strategy() {
+            SyncStrategy::Full => "full",
+            SyncStrategy::Snap => "snap",
+            SyncStrategy::RpcBackfill => "rpcBackfill",
+        };
+        let node_config = NodeConfig {
+            chain_id: chain_config.chain_id,
+            active_fork: format!("{:?}", chain_config.get_fork(latest_header.timestamp))
+                .to_lowercase(),
+            chain_config,
+            blob_schedule,
+            http_namespaces: HTTP_NAMESPACES.to_vec(),
+            authrpc_namespaces: AUTHRPC_NAMESPACES.to_vec(),
+            sync_mode,
+            pruning: PruningConfig {
+                earliest_block: storage.get_earliest_block_number()?.unwrap_or_default(),
+                blob_sidecars_retention: blob_sidecars_retention(storage),
+            },
+        };
+        serde_json::to_value(node_config).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Progress of the generation of the snapshot of the state, null if there is no snapshot
+pub struct GetSnapshotStatusRequest;
+
+impl RpcHandler for GetSnapshotStatusRequest {
+    fn parse(_params: &Option<Vec<Value>>) -> Result<Self, RpcErr> {
+        Ok(GetSnapshotStatusRequest)
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!("Requested snapshot status");
+        serde_json::to_value(context.storage.snapshot_status()?)
+            .map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+/// Balances, nonces and code hashes of many accounts, all read from the same view of the state
+pub struct GetAccountsBatchRequest {
+    pub addresses: Vec<Address>,
+    pub block: BlockIdentifierOrHash,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchAccount {
+    pub address: Address,
+    pub balance: U256,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub nonce: u64,
+    pub code_hash: H256,
+}
+
+impl RpcHandler for GetAccountsBatchRequest {
+    fn parse(params: &Option<Vec<Value>>) -> Result<GetAccountsBatchRequest, RpcErr> {
+        let params = params
+            .as_ref()
+            .ok_or(RpcErr::BadParams("No params provided".to_owned()))?;
+        if params.len() != 2 {
+            return Err(RpcErr::BadParams(format!(
+                "Expected two params and {} were provided",
+                params.len()
+            )));
+        };
+        let addresses: Vec<Address> = serde_json::from_value(params[0].clone())?;
+        if addresses.len() > MAX_ACCOUNTS_BATCH_SIZE {
+            return Err(RpcErr::BadParams(format!(
+                "At most {MAX_ACCOUNTS_BATCH_SIZE} accounts can be requested"
+            )));
+        }
+        Ok(GetAccountsBatchRequest {
+            addresses,
+            block: BlockIdentifierOrHash::parse(params[1].clone(), 1)?,
+        })
+    }
+
+    fn handle(&self, context: RpcApiContext) -> Result<Value, RpcErr> {
+        info!(
+            "Requested {} accounts at block {}",
+            self.addresses.len(),
+            self.block
+        );
+        let state = self.block.resolve_state(&context.storage)?;
+        let accounts = self
+            .addresses
+            .iter()
+            .map(|address| {
+                // Missing accounts are reported as empty ones
+                let info = state.get_account_info(*address)?.unwrap_or_default();
+                Ok(BatchAccount {
+                    address: *address,
+                    balance: info.balance,
+                    nonce: info.nonce,
+                    code_hash: info.code_hash,
+                })
+            })
+            .collect::<Result<Vec<_>, RpcErr>>()?;
+        serde_json::to_value(accounts).map_err(|error| RpcErr::Internal(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn get_transactions_by_address_checks_page_size() {
+        let params = |page_size: &str| {
+            Some(vec![
+                json!("0x000000000000000000000000000000000000dead"),
+                json!("0x2"),
+                json!(page_size),
+            ])
+        };
+        let request = GetTransactionsByAddressRequest::parse(&params("0x64")).unwrap();
+        assert_eq!(request.address, Address::from_low_u64_be(0xdead));
+        assert_eq!((request.page, request.page_size), (2, 100));
+        assert!(GetTransactionsByAddressRequest::parse(&params("0x0")).is_err());
+        assert!(GetTransactionsByAddressRequest::parse(&params(&format!(
+            "{:#x}",
+            MAX_ADDRESS_TRANSACTIONS_PAGE_SIZE + 1
+        )))
+        .is_err());
+    }
+
+    #[test]
+    fn get_light_client_bundle_parses_receipt_indices() {
+        let request = GetLightClientBundleRequest::parse(&Some(vec![
+            json!("latest"),
+            json!(["0x0", "0x1f"]),
+        ]))
+        .unwrap();
+        assert_eq!(request.receipt_indices, vec![0, 31]);
+        assert!(
+            GetLightClientBundleRequest::parse(&Some(vec![json!("latest"), json!(["1"]),]))
+                .is_err()
+        );
+        let too_many = vec![json!("0x0"); MAX_LIGHT_CLIENT_BUNDLE_RECEIPTS + 1];
+        assert!(
+            GetLightClientBundleRequest::parse(&Some(vec![json!("latest"), json!(too_many),]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn get_accounts_batch_bounds_the_batch_size() {
+        let params = |addresses: usize| {
+            Some(vec![
+                json!(vec![Address::from_low_u64_be(0xdead); addresses]),
+                json!("latest"),
+            ])
+        };
+        let request = GetAccountsBatchRequest::parse(&params(2)).unwrap();
+        assert_eq!(request.addresses.len(), 2);
+        assert!(GetAccountsBatchRequest::parse(&params(MAX_ACCOUNTS_BATCH_SIZE)).is_ok());
+        assert!(GetAccountsBatchRequest::parse(&params(MAX_ACCOUNTS_BATCH_SIZE + 1)).is_err());
+        assert!(GetAccountsBatchRequest::parse(&Some(vec![json!([])])).is_err());
+    }
+
+    #[test]
+    fn inclusion_blockers_are_tagged_by_reason() {
+        let blockers: Vec<RpcInclusionBlocker> = vec![
+            InclusionBlocker::NonceGap { from: 1, to: 2 }.into(),
+            InclusionBlocker::FeeCapBelowBaseFee { base_fee: 16 }.into(),
+        ];
+        assert_eq!(
+            serde_json::to_value(blockers).unwrap(),
+            json!([
+                { "reason": "nonceGap", "from": "0x1", "to": "0x2" },
+                { "reason": "feeCapBelowBaseFee", "baseFee": "0x10" },
+            ])
+        );
+    }
+
+    #[test]
+    fn get_blob_sidecars_of_block_by_hash() {
+        use crate::utils::test_utils::default_context_with_storage;
+        use ethrex_core::types::{BlobsBundle, BYTES_PER_BLOB};
+        use ethrex_storage::EngineType;
+
+        let context = default_context_with_storage(
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB"),
+        );
+        let sidecars = BlobsBundle {
+            blobs: vec![[1; BYTES_PER_BLOB]],
+            commitments: vec![[2; 48]],
+            proofs: vec![[3; 48]],
+        };
+        let block_hash = H256::random();
+        context
+            .storage
+            .add_blob_sidecars(block_hash, 0, sidecars.clone())
+            .unwrap();
+        let sidecars_of = |block: Value| {
+            GetBlobSidecarsRequest::parse(&Some(vec![block]))
+                .unwrap()
+                .handle(context.clone())
+                .unwrap()
+        };
+        assert_eq!(
+            sidecars_of(json!(format!("{block_hash:#x}"))),
+            serde_json::to_value(sidecars).unwrap()
+        );
+        assert_eq!(
+            sidecars_of(json!(format!("{:#x}", H256::random()))),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn project_fees_defaults_to_full_blocks() {
+        let request = ProjectFeesRequest::parse(&Some(vec![json!("0x5")])).unwrap();
+        assert_eq!(
+            (
+                request.block_count,
+                request.gas_used_percent,
+                request.blobs_per_block
+            ),
+            (5, 100, MAX_BLOB_NUMBER_PER_BLOCK)
+        );
+        let request =
+            ProjectFeesRequest::parse(&Some(vec![json!("0x1"), json!("0x32"), json!("0x0")]))
+                .unwrap();
+        assert_eq!((request.gas_used_percent, request.blobs_per_block), (50, 0));
+        assert!(ProjectFeesRequest::parse(&Some(vec![json!("0x0")])).is_err());
+        assert!(ProjectFeesRequest::parse(&Some(vec![json!("0x1"), json!("0x65")])).is_err());
+    }
+}
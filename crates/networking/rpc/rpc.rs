@@ -1,14 +1,28 @@
-use crate::authentication::authenticate;
-use axum::{routing::post, Json, Router};
+use crate::authentication::{authenticate, authorize_api_token};
+use admin::UpdateForkScheduleRequest;
+use api_tokens::ApiTokens;
+use axum::{
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
 use bytes::Bytes;
+use debug::{receipts::GetReceiptsRangeRequest, trace::TraceChainRequest};
 use engine::{
+    anomalies::ReportedStall,
+    blobs::GetBlobsV1Request,
+    client_version::GetClientVersionV1Request,
     exchange_transition_config::ExchangeTransitionConfigV1Req,
-    fork_choice::ForkChoiceUpdatedV3,
-    payload::{GetPayloadV3Request, NewPayloadV3Request},
+    fork_choice::{ForkChoiceUpdatedV1, ForkChoiceUpdatedV2, ForkChoiceUpdatedV3, LastForkChoice},
+    metrics::{GetEngineMetricsRequest, SharedEngineMetrics},
+    payload::{
+        GetPayloadBodiesByHashV1Request, GetPayloadBodiesByRangeV1Request, GetPayloadV3Request,
+        GetPayloadV4Request, NewPayloadV3Request, NewPayloadV4Request, PayloadValidationCache,
+    },
     ExchangeCapabilitiesRequest,
 };
 use eth::{
@@ -24,38 +38,59 @@ use eth::{
     client::{ChainId, Syncing},
     fee_market::FeeHistoryRequest,
     filter::{self, ActiveFilters, DeleteFilterRequest, FilterChangesRequest, NewFilterRequest},
-    gas_price::GasPrice,
+    gas_price::{GasPrice, MaxPriorityFeePerGas},
+    gas_price_oracle::GasPriceOracle,
     logs::LogsFilter,
+    simulate::{CallManyRequest, SimulateV1Request},
     transaction::{
         CallRequest, CreateAccessListRequest, EstimateGasRequest, GetRawTransaction,
         GetTransactionByBlockHashAndIndexRequest, GetTransactionByBlockNumberAndIndexRequest,
         GetTransactionByHashRequest, GetTransactionReceiptRequest,
+        SendRawTransactionConditionalRequest,
     },
 };
-use ethrex_net::sync::SyncManager;
+use ethrex::{
+    GetAccountsBatchRequest, GetBlobSidecarsRequest, GetFeeRecipientEarningsRequest,
+    GetLightClientBundleRequest, GetNodeConfigRequest, GetPendingNonceGapsRequest,
+    GetSnapshotStatusRequest, GetTransactionsByAddressRequest, InspectTransactionRequest,
+    ProjectFeesRequest,
+};
+use ethrex_blockchain::{payload::PayloadBuilderConfig, payload_manager::PayloadManager};
+use ethrex_net::sync::{SyncHandle, SyncManager};
 use serde_json::Value;
 use std::{
     collections::HashMap,
     future::IntoFuture,
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
-use tokio::{net::TcpListener, sync::Mutex as TokioMutex};
-use tracing::info;
+use tls::TlsConfig;
+use tokio::net::TcpListener;
+use tracing::{info, info_span, Span};
 use types::transaction::SendRawTransactionRequest;
 use utils::{
     RpcErr, RpcErrorMetadata, RpcErrorResponse, RpcNamespace, RpcRequest, RpcRequestId,
     RpcSuccessResponse,
 };
 mod admin;
+pub mod api_tokens;
 mod authentication;
+mod debug;
 pub mod engine;
 mod eth;
+mod ethrex;
+pub mod tls;
 pub mod types;
 pub mod utils;
+pub mod version;
 mod web3;
 
+pub use eth::gas_price_oracle::GasPriceOracleConfig;
+
 use axum::extract::State;
 use ethrex_net::types::Node;
 use ethrex_storage::Store;
@@ -66,7 +101,15 @@ pub struct RpcApiContext {
     jwt_secret: Bytes,
     local_p2p_node: Node,
     active_filters: ActiveFilters,
-    syncer: Arc<TokioMutex<SyncManager>>,
+    syncer: SyncHandle,
+    last_fork_choice: LastForkChoice,
+    payload_validations: PayloadValidationCache,
+    payload_manager: PayloadManager,
+    /// Tokens required to call the HTTP RPC, which is open to anyone if not set
+    api_tokens: Option<Arc<ApiTokens>>,
+    gas_price_oracle: Arc<GasPriceOracle>,
+    engine_metrics: SharedEngineMetrics,
+    reported_stall: ReportedStall,
 }
 
 trait RpcHandler: Sized {
@@ -88,6 +131,7 @@ const FILTER_DURATION: Duration = {
     }
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn start_api(
     http_addr: SocketAddr,
     authrpc_addr: SocketAddr,
@@ -95,6 +139,11 @@ pub async fn start_api(
     jwt_secret: Bytes,
     local_p2p_node: Node,
     syncer: SyncManager,
+    http_tls: Option<TlsConfig>,
+    api_tokens: Option<ApiTokens>,
+    gas_price_oracle: GasPriceOracleConfig,
+    get_payload_deadline: Duration,
+    payload_builder: PayloadBuilderConfig,
 ) {
     // TODO: Refactor how filters are handled,
     // filters are used by the filters endpoints (eth_newFilter, eth_getFilterChanges, ...etc)
@@ -104,7 +153,14 @@ pub async fn start_api(
         jwt_secret,
         local_p2p_node,
         active_filters: active_filters.clone(),
-        syncer: Arc::new(TokioMutex::new(syncer)),
+        syncer: SyncHandle::spawn(syncer, storage.clone()),
+        last_fork_choice: Default::default(),
+        payload_validations: Default::default(),
+        payload_manager: PayloadManager::new(get_payload_deadline, payload_builder),
+        api_tokens: api_tokens.map(Arc::new),
+        gas_price_oracle: Arc::new(GasPriceOracle::new(gas_price_oracle)),
+        engine_metrics: Default::default(),
+        reported_stall: Default::default(),
     };
 
     // Periodically clean up the active filters for the filters endpoints.
@@ -132,11 +188,25 @@ pub async fn start_api(
     let authrpc_server = axum::serve(authrpc_listener, authrpc_router)
         .with_graceful_shutdown(shutdown_signal())
         .into_future();
-    let http_server = axum::serve(http_listener, http_router)
-        .with_graceful_shutdown(shutdown_signal())
-        .into_future();
+    let http_scheme = if http_tls.is_some() { "HTTPS" } else { "HTTP" };
+    let http_tls = http_tls.map(|tls| {
+        tls.acceptor()
+            .expect("Failed to load the TLS certificate of the HTTP server")
+    });
+    let http_server = async move {
+        match http_tls {
+            Some(acceptor) => {
+                tls::serve_tls(http_listener, http_router, acceptor, shutdown_signal()).await
+            }
+            None => {
+                axum::serve(http_listener, http_router)
+                    .with_graceful_shutdown(shutdown_signal())
+                    .await
+            }
+        }
+    };
 
-    info!("Starting HTTP server at {http_addr}");
+    info!("Starting {http_scheme} server at {http_addr}");
     info!("Starting Auth-RPC server at {}", authrpc_addr);
 
     let _ = tokio::try_join!(authrpc_server, http_server)
@@ -151,11 +221,21 @@ async fn shutdown_signal() {
 
 pub async fn handle_http_request(
     State(service_context): State<RpcApiContext>,
+    auth_header: Option<TypedHeader<Authorization<Bearer>>>,
     body: String,
-) -> Json<Value> {
+) -> Response {
     let req: RpcRequest = serde_json::from_str(&body).unwrap();
-    let res = map_http_requests(&req, service_context);
-    rpc_response(req.id, res)
+    if let Some(api_tokens) = &service_context.api_tokens {
+        if let Err(error) = authorize_api_token(api_tokens, auth_header.as_ref(), &req.method) {
+            return rpc_response(req.id, Err(error)).into_response();
+        }
+    }
+    // Chain traces can be arbitrarily large, so they are streamed as they are produced
+    if req.method == "debug_traceChain" {
+        return debug::trace::stream_trace_chain(req, service_context);
+    }
+    let res = request_span(&req).in_scope(|| map_http_requests(&req, service_context));
+    rpc_response(req.id, res).into_response()
 }
 
 pub async fn handle_authrpc_request(
@@ -168,12 +248,25 @@ pub async fn handle_authrpc_request(
         Err(error) => rpc_response(req.id, Err(error)),
         Ok(()) => {
             // Proceed with the request
-            let res = map_authrpc_requests(&req, service_context);
+            let res = request_span(&req).in_scope(|| map_authrpc_requests(&req, service_context));
             rpc_response(req.id, res)
         }
     }
 }
 
+/// Returns the span of the work done to answer a request, identified by a node-wide id
+/// as the ids chosen by the clients are not unique
+fn request_span(req: &RpcRequest) -> Span {
+    static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+    let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    info_span!("rpc_request", request_id, method = %req.method)
+}
+
+/// Namespaces served by the HTTP RPC
+pub const HTTP_NAMESPACES: [&str; 5] = ["eth", "admin", "debug", "web3", "ethrex"];
+/// Namespaces served by the authenticated RPC, besides admin_updateForkSchedule
+pub const AUTHRPC_NAMESPACES: [&str; 2] = ["engine", "eth"];
+
 /// Handle requests that can come from either clients or other users
 pub fn map_http_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Value, RpcErr> {
     match req.namespace() {
@@ -181,6 +274,7 @@ pub fn map_http_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Val
         Ok(RpcNamespace::Admin) => map_admin_requests(req, context),
         Ok(RpcNamespace::Debug) => map_debug_requests(req, context),
         Ok(RpcNamespace::Web3) => map_web3_requests(req, context),
+        Ok(RpcNamespace::Ethrex) => map_ethrex_requests(req, context),
         _ => Err(RpcErr::MethodNotFound(req.method.clone())),
     }
 }
@@ -190,6 +284,10 @@ pub fn map_authrpc_requests(req: &RpcRequest, context: RpcApiContext) -> Result<
     match req.namespace() {
         Ok(RpcNamespace::Engine) => map_engine_requests(req, context),
         Ok(RpcNamespace::Eth) => map_eth_requests(req, context),
+        // Changing the fork schedule is restricted to the authenticated RPC
+        Ok(RpcNamespace::Admin) if req.method == "admin_updateForkSchedule" => {
+            UpdateForkScheduleRequest::call(req, context)
+        }
         _ => Err(RpcErr::MethodNotFound(req.method.clone())),
     }
 }
@@ -219,6 +317,8 @@ pub fn map_eth_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Valu
         "eth_createAccessList" => CreateAccessListRequest::call(req, context),
         "eth_blockNumber" => BlockNumberRequest::call(req, context),
         "eth_call" => CallRequest::call(req, context),
+        "eth_callMany" => CallManyRequest::call(req, context),
+        "eth_simulateV1" => SimulateV1Request::call(req, context),
         "eth_blobBaseFee" => GetBlobBaseFee::call(req, context),
         "eth_getTransactionCount" => GetTransactionCountRequest::call(req, context),
         "eth_feeHistory" => FeeHistoryRequest::call(req, context),
@@ -234,8 +334,12 @@ pub fn map_eth_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Valu
             FilterChangesRequest::stateful_call(req, context.storage, context.active_filters)
         }
         "eth_sendRawTransaction" => SendRawTransactionRequest::call(req, context),
+        "eth_sendRawTransactionConditional" => {
+            SendRawTransactionConditionalRequest::call(req, context)
+        }
         "eth_getProof" => GetProofRequest::call(req, context),
         "eth_gasPrice" => GasPrice::call(req, context),
+        "eth_maxPriorityFeePerGas" => MaxPriorityFeePerGas::call(req, context),
         unknown_eth_method => Err(RpcErr::MethodNotFound(unknown_eth_method.to_owned())),
     }
 }
@@ -246,6 +350,8 @@ pub fn map_debug_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Va
         "debug_getRawBlock" => GetRawBlockRequest::call(req, context),
         "debug_getRawTransaction" => GetRawTransaction::call(req, context),
         "debug_getRawReceipts" => GetRawReceipts::call(req, context),
+        "debug_traceChain" => TraceChainRequest::call(req, context),
+        "debug_getReceiptsRange" => GetReceiptsRangeRequest::call(req, context),
         unknown_debug_method => Err(RpcErr::MethodNotFound(unknown_debug_method.to_owned())),
     }
 }
@@ -253,12 +359,20 @@ pub fn map_debug_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Va
 pub fn map_engine_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Value, RpcErr> {
     match req.method.as_str() {
         "engine_exchangeCapabilities" => ExchangeCapabilitiesRequest::call(req, context),
+        "engine_forkchoiceUpdatedV1" => ForkChoiceUpdatedV1::call(req, context),
+        "engine_forkchoiceUpdatedV2" => ForkChoiceUpdatedV2::call(req, context),
         "engine_forkchoiceUpdatedV3" => ForkChoiceUpdatedV3::call(req, context),
         "engine_newPayloadV3" => NewPayloadV3Request::call(req, context),
+        "engine_newPayloadV4" => NewPayloadV4Request::call(req, context),
         "engine_exchangeTransitionConfigurationV1" => {
             ExchangeTransitionConfigV1Req::call(req, context)
         }
         "engine_getPayloadV3" => GetPayloadV3Request::call(req, context),
+        "engine_getPayloadV4" => GetPayloadV4Request::call(req, context),
+        "engine_getPayloadBodiesByHashV1" => GetPayloadBodiesByHashV1Request::call(req, context),
+        "engine_getPayloadBodiesByRangeV1" => GetPayloadBodiesByRangeV1Request::call(req, context),
+        "engine_getClientVersionV1" => GetClientVersionV1Request::call(req, context),
+        "engine_getBlobsV1" => GetBlobsV1Request::call(req, context),
         unknown_engine_method => Err(RpcErr::MethodNotFound(unknown_engine_method.to_owned())),
     }
 }
@@ -277,6 +391,23 @@ pub fn map_web3_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Val
     }
 }
 
+pub fn map_ethrex_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Value, RpcErr> {
+    match req.method.as_str() {
+        "ethrex_getTransactionsByAddress" => GetTransactionsByAddressRequest::call(req, context),
+        "ethrex_getFeeRecipientEarnings" => GetFeeRecipientEarningsRequest::call(req, context),
+        "ethrex_getLightClientBundle" => GetLightClientBundleRequest::call(req, context),
+        "ethrex_pendingNonceGaps" => GetPendingNonceGapsRequest::call(req, context),
+        "ethrex_inspectTransaction" => InspectTransactionRequest::call(req, context),
+        "ethrex_projectFees" => ProjectFeesRequest::call(req, context),
+        "ethrex_getBlobSidecars" => GetBlobSidecarsRequest::call(req, context),
+        "ethrex_engineMetrics" => GetEngineMetricsRequest::call(req, context),
+        "ethrex_nodeConfig" => GetNodeConfigRequest::call(req, context),
+        "ethrex_snapshotStatus" => GetSnapshotStatusRequest::call(req, context),
+        "ethrex_getAccountsBatch" => GetAccountsBatchRequest::call(req, context),
+        unknown_ethrex_method => Err(RpcErr::MethodNotFound(unknown_ethrex_method.to_owned())),
+    }
+}
+
 fn rpc_response<E>(id: RpcRequestId, res: Result<Value, E>) -> Json<Value>
 where
     E: Into<RpcErrorMetadata>,
@@ -304,11 +435,22 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::test_utils::example_p2p_node;
-    use ethrex_core::types::{ChainConfig, Genesis};
+    use crate::engine::{
+        blobs::GET_BLOBS_REQUEST_MAX_SIZE, payload::GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE,
+        CAPABILITIES,
+    };
+    use crate::types::block::RpcBlock;
+    use crate::utils::test_utils::{
+        context_with_genesis, default_context_with_storage, read_execution_api_genesis_file,
+    };
+    use ethrex_core::types::{
+        BlobsBundle, Block, BlockBody, BlockHeader, ChainConfig, EIP1559Transaction,
+        GenesisAccount, Log, MempoolTransaction, Receipt, Signable, Transaction, TxKind,
+        BYTES_PER_BLOB, EMPTY_KECCACK_HASH, EMPTY_TRIE_HASH,
+    };
+    use ethrex_core::{Address, H256, U256};
     use ethrex_storage::EngineType;
-    use std::fs::File;
-    use std::io::BufReader;
+    use secp256k1::SecretKey;
 
     // Maps string rpc response to RpcSuccessResponse as serde Value
     // This is used to avoid failures due to field order and allow easier string comparisons for responses
@@ -320,17 +462,10 @@ mod tests {
     fn admin_nodeinfo_request() {
         let body = r#"{"jsonrpc":"2.0", "method":"admin_nodeInfo", "params":[], "id":1}"#;
         let request: RpcRequest = serde_json::from_str(body).unwrap();
-        let local_p2p_node = example_p2p_node();
         let storage =
             Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
         storage.set_chain_config(&example_chain_config()).unwrap();
-        let context = RpcApiContext {
-            local_p2p_node,
-            storage,
-            jwt_secret: Default::default(),
-            active_filters: Default::default(),
-            syncer: Arc::new(TokioMutex::new(SyncManager::dummy())),
-        };
+        let context = default_context_with_storage(storage);
         let result = map_http_requests(&request, context);
         let rpc_response = rpc_response(request.id, result);
         let expected_response = to_rpc_response_success_value(
@@ -339,14 +474,6 @@ mod tests {
         assert_eq!(rpc_response.to_string(), expected_response.to_string())
     }
 
-    // Reads genesis file taken from https://github.com/ethereum/execution-apis/blob/main/tests/genesis.json
-    fn read_execution_api_genesis_file() -> Genesis {
-        let file = File::open("../../../test_data/genesis-execution-api.json")
-            .expect("Failed to open genesis file");
-        let reader = BufReader::new(file);
-        serde_json::from_reader(reader).expect("Failed to deserialize genesis file")
-    }
-
     #[test]
     fn create_access_list_simple_transfer() {
         // Create Request
@@ -354,21 +481,8 @@ mod tests {
         let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_createAccessList","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","nonce":"0x0","to":"0x0100000000000000000000000000000000000000","value":"0xa"},"0x00"]}"#;
         let request: RpcRequest = serde_json::from_str(body).unwrap();
         // Setup initial storage
-        let storage =
-            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
-        let genesis = read_execution_api_genesis_file();
-        storage
-            .add_initial_state(genesis)
-            .expect("Failed to add genesis block to DB");
-        let local_p2p_node = example_p2p_node();
         // Process request
-        let context = RpcApiContext {
-            local_p2p_node,
-            storage,
-            jwt_secret: Default::default(),
-            active_filters: Default::default(),
-            syncer: Arc::new(TokioMutex::new(SyncManager::dummy())),
-        };
+        let context = context_with_genesis();
         let result = map_http_requests(&request, context);
         let response = rpc_response(request.id, result);
         let expected_response = to_rpc_response_success_value(
@@ -384,21 +498,8 @@ mod tests {
         let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_createAccessList","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","gas":"0xea60","gasPrice":"0x44103f2","input":"0x010203040506","nonce":"0x0","to":"0x7dcd17433742f4c0ca53122ab541d0ba67fc27df"},"0x00"]}"#;
         let request: RpcRequest = serde_json::from_str(body).unwrap();
         // Setup initial storage
-        let storage =
-            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
-        let genesis = read_execution_api_genesis_file();
-        storage
-            .add_initial_state(genesis)
-            .expect("Failed to add genesis block to DB");
-        let local_p2p_node = example_p2p_node();
         // Process request
-        let context = RpcApiContext {
-            local_p2p_node,
-            storage,
-            jwt_secret: Default::default(),
-            active_filters: Default::default(),
-            syncer: Arc::new(TokioMutex::new(SyncManager::dummy())),
-        };
+        let context = context_with_genesis();
         let result = map_http_requests(&request, context);
         let response =
             serde_json::from_value::<RpcSuccessResponse>(rpc_response(request.id, result).0)
@@ -415,6 +516,643 @@ mod tests {
         )
     }
 
+    #[test]
+    fn create_access_list_extends_the_declared_one() {
+        // The contract request above on the chain of the genesis, declaring one of the slots
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_createAccessList","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","chainId":"0xc72dd9d5e883e","gas":"0xea60","gasPrice":"0x44103f2","input":"0x010203040506","nonce":"0x0","to":"0x7dcd17433742f4c0ca53122ab541d0ba67fc27df","accessList":[{"address":"0x7dcd17433742f4c0ca53122ab541d0ba67fc27df","storageKeys":["0x0000000000000000000000000000000000000000000000000000000000000000"]}]},"0x00"]}"#;
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let context = context_with_genesis();
+        let result = map_http_requests(&request, context).expect("Request failed");
+        // The declared entry is completed instead of duplicated
+        assert_eq!(
+            result["accessList"],
+            serde_json::json!([{
+                "address": "0x7dcd17433742f4c0ca53122ab541d0ba67fc27df",
+                "storageKeys": [
+                    "0x0000000000000000000000000000000000000000000000000000000000000000",
+                    "0x13a08e3cd39a1bc7bf9103f63f83273cced2beada9f723945176d6b983c65bd2"
+                ]
+            }])
+        );
+        assert!(result.get("error").is_none_or(Value::is_null));
+    }
+
+    #[test]
+    fn call_many_builds_on_previous_transactions() {
+        // The second transfer can only succeed if it sees the balance received in the first one
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_callMany","params":[[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x1000000000000000000000000000000000000001","value":"0xa"},{"from":"0x1000000000000000000000000000000000000001","to":"0x0100000000000000000000000000000000000000","value":"0xa"}],"0x00"]}"#;
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let context = context_with_genesis();
+        let result = map_http_requests(&request, context);
+        let response = rpc_response(request.id, result);
+        let expected_response = to_rpc_response_success_value(
+            r#"{"jsonrpc":"2.0","id":1,"result":[{"status":"0x1","gasUsed":"0x5208","returnData":"0x","logs":[]},{"status":"0x1","gasUsed":"0x5208","returnData":"0x","logs":[]}]}"#,
+        );
+        assert_eq!(response.to_string(), expected_response.to_string());
+    }
+
+    #[test]
+    fn call_with_state_and_block_overrides() {
+        // The overridden code returns the number of the block it runs in
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_call","params":[{"from":"0x1000000000000000000000000000000000000001","to":"0x1000000000000000000000000000000000000002"},"latest",{"0x1000000000000000000000000000000000000002":{"code":"0x4360005260206000f3"}},{"number":"0x2a"}]}"#;
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let genesis = read_execution_api_genesis_file();
+        storage
+            .add_initial_state(genesis)
+            .expect("Failed to add genesis block to DB");
+        let context = default_context_with_storage(storage.clone());
+        let result = map_http_requests(&request, context);
+        let response =
+            serde_json::from_value::<RpcSuccessResponse>(rpc_response(request.id, result).0)
+                .expect("Request failed");
+        assert_eq!(
+            response.result,
+            "0x000000000000000000000000000000000000000000000000000000000000002a"
+        );
+        // The overrides are not persisted
+        let code = storage
+            .get_code_by_account_address(
+                0,
+                "0x1000000000000000000000000000000000000002"
+                    .parse::<Address>()
+                    .unwrap(),
+            )
+            .unwrap();
+        assert!(code.is_none_or(|code| code.is_empty()));
+    }
+
+    #[test]
+    fn call_many_with_state_overrides() {
+        // The overridden code returns the value stored in slot 0
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_callMany","params":[[{"from":"0x1000000000000000000000000000000000000001","to":"0x1000000000000000000000000000000000000002"}],"latest",{"0x1000000000000000000000000000000000000001":{"balance":"0xffffffffffffffff"},"0x1000000000000000000000000000000000000002":{"code":"0x60005460005260206000f3","stateDiff":{"0x0000000000000000000000000000000000000000000000000000000000000000":"0x2a"}}}]}"#;
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let context = context_with_genesis();
+        let result = map_http_requests(&request, context);
+        let response =
+            serde_json::from_value::<RpcSuccessResponse>(rpc_response(request.id, result).0)
+                .expect("Request failed");
+        assert_eq!(response.result[0]["status"], "0x1");
+        assert_eq!(
+            response.result[0]["returnData"],
+            "0x000000000000000000000000000000000000000000000000000000000000002a"
+        );
+    }
+
+    #[test]
+    fn simulate_v1_runs_blocks_in_sequence() {
+        // The second block is simulated after an empty one, its call spends the balance received
+        // in the first block and calls the overridden code, which emits a log
+        let simulation = r#"{"blockStateCalls":[{"calls":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x1000000000000000000000000000000000000001","value":"0xa"}]},{"blockOverrides":{"number":"0x3"},"stateOverrides":{"0x1000000000000000000000000000000000000002":{"code":"0x60006000a0"}},"calls":[{"from":"0x1000000000000000000000000000000000000001","to":"0x1000000000000000000000000000000000000002","value":"0xa"}]}]}"#;
+        let context = context_with_genesis();
+        let simulate = |simulation: &str| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_simulateV1","params":[{simulation},"0x00"]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            let result = map_http_requests(&request, context.clone());
+            rpc_response(request.id, result).0
+        };
+
+        let response = serde_json::from_value::<RpcSuccessResponse>(simulate(simulation))
+            .expect("Request failed");
+        let blocks = response.result.as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[1]["number"], "0x2");
+        assert_eq!(blocks[1]["parentHash"], blocks[0]["hash"]);
+        assert_eq!(blocks[2]["parentHash"], blocks[1]["hash"]);
+        assert_eq!(blocks[1]["calls"], serde_json::json!([]));
+        assert_eq!(blocks[0]["calls"][0]["status"], "0x1");
+        assert_eq!(blocks[0]["gasUsed"], "0x5208");
+        let call = &blocks[2]["calls"][0];
+        assert_eq!(call["status"], "0x1");
+        assert_eq!(
+            call["logs"][0]["address"],
+            "0x1000000000000000000000000000000000000002"
+        );
+        assert_eq!(call["logs"][0]["blockNumber"], "0x3");
+        assert_eq!(call["logs"][0]["blockHash"], blocks[2]["hash"]);
+
+        // Block numbers must increase
+        let simulation = r#"{"blockStateCalls":[{"blockOverrides":{"number":"0x2"}},{"blockOverrides":{"number":"0x2"}}]}"#;
+        let response = serde_json::from_value::<RpcErrorResponse>(simulate(simulation)).unwrap();
+        assert_eq!(response.error.code, -38020);
+
+        // With validation the calls must pay the base fee of their block
+        let simulation = r#"{"blockStateCalls":[{"calls":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x1000000000000000000000000000000000000001","value":"0xa"}]}],"validation":true}"#;
+        let response = serde_json::from_value::<RpcErrorResponse>(simulate(simulation)).unwrap();
+        assert!(response.error.message.contains("basefee"));
+    }
+
+    #[test]
+    fn estimate_gas_on_top_of_pending_transactions() {
+        // The pending transaction spends the whole balance of the sender
+        let private_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pending = Transaction::EIP1559Transaction(
+            EIP1559Transaction {
+                chain_id: 3503995874084926,
+                max_fee_per_gas: 1_000_000_000,
+                gas_limit: 21_000,
+                to: TxKind::Call(Address::repeat_byte(1)),
+                value: U256::from(1_000_000_000_000_000_000u64 - 21_000_000_000_000),
+                ..Default::default()
+            }
+            .sign(&private_key),
+        );
+        let sender = pending.sender();
+        let mut genesis = read_execution_api_genesis_file();
+        genesis.alloc.insert(
+            sender,
+            GenesisAccount {
+                code: Default::default(),
+                storage: Default::default(),
+                balance: U256::from(1_000_000_000_000_000_000u64),
+                nonce: 0,
+            },
+        );
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        storage
+            .add_initial_state(genesis)
+            .expect("Failed to add genesis block to DB");
+        storage
+            .add_transaction_to_pool(
+                pending.compute_hash(),
+                MempoolTransaction::new(pending, sender),
+            )
+            .unwrap();
+        let context = default_context_with_storage(storage);
+        let estimate = |apply_pending: bool| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_estimateGas","params":[{{"from":"{sender:#x}","to":"0x0100000000000000000000000000000000000000","value":"0x1"}},"latest",{apply_pending}]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            map_http_requests(&request, context.clone())
+        };
+        assert_eq!(estimate(false).unwrap(), "0x5208");
+        assert!(estimate(true).is_err());
+    }
+
+    #[test]
+    fn estimate_gas_surfaces_the_revert_reason() {
+        let context = context_with_genesis();
+        let estimate = |init_code: &str| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_estimateGas","params":[{{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","input":"{init_code}"}}]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            let result = map_http_requests(&request, context.clone());
+            rpc_response(request.id, result).0
+        };
+
+        // Deploys a contract of 10 bytes
+        let response = serde_json::from_value::<RpcSuccessResponse>(estimate(
+            "0x600a600c600039600a6000f300000000000000000000",
+        ))
+        .expect("Request failed");
+        let gas = u64::from_str_radix(
+            response.result.as_str().unwrap().trim_start_matches("0x"),
+            16,
+        )
+        .unwrap();
+        assert!((53_000..60_000).contains(&gas));
+
+        // Reverts with Error("nope")
+        let revert_data = "0x08c379a0000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000046e6f706500000000000000000000000000000000000000000000000000000000";
+        let response = serde_json::from_value::<RpcErrorResponse>(estimate(&format!(
+            "0x6064600c60003960646000fd{}",
+            revert_data.trim_start_matches("0x")
+        )))
+        .unwrap();
+        assert_eq!(response.error.code, 3);
+        assert_eq!(response.error.message, "execution reverted: nope");
+        assert_eq!(response.error.data.as_deref(), Some(revert_data));
+    }
+
+    #[test]
+    fn get_accounts_batch_reads_every_account() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let genesis = read_execution_api_genesis_file();
+        let funded = "0x0c2c51a0990aee1d73c1228de158688341557508"
+            .parse::<Address>()
+            .unwrap();
+        let funded_account = genesis.alloc[&funded].clone();
+        storage
+            .add_initial_state(genesis)
+            .expect("Failed to add genesis block to DB");
+        let context = default_context_with_storage(storage);
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"ethrex_getAccountsBatch","params":[["0x0c2c51a0990aee1d73c1228de158688341557508","0x000000000000000000000000000000000000dead"],"latest"]}"#;
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let accounts = map_http_requests(&request, context).unwrap();
+
+        assert_eq!(accounts.as_array().unwrap().len(), 2);
+        assert_eq!(
+            accounts[0]["address"],
+            "0x0c2c51a0990aee1d73c1228de158688341557508"
+        );
+        assert_eq!(
+            accounts[0]["balance"],
+            format!("{:#x}", funded_account.balance)
+        );
+        assert_eq!(accounts[0]["nonce"], format!("{:#x}", funded_account.nonce));
+        // Missing accounts are reported as empty ones
+        assert_eq!(accounts[1]["balance"], "0x0");
+        assert_eq!(accounts[1]["nonce"], "0x0");
+        assert_eq!(
+            accounts[1]["codeHash"],
+            format!("{:#x}", *EMPTY_KECCACK_HASH)
+        );
+    }
+
+    #[test]
+    fn get_balance_of_block_without_state() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        // Only the empty state of the second block is available
+        for (number, state_root) in [(0, H256::random()), (1, *EMPTY_TRIE_HASH)] {
+            let header = BlockHeader {
+                number,
+                state_root,
+                ..Default::default()
+            };
+            let hash = header.compute_block_hash();
+            storage
+                .add_block(Block::new(header, Default::default()))
+                .unwrap();
+            storage.set_canonical_block(number, hash).unwrap();
+        }
+        storage.update_latest_block_number(1).unwrap();
+        let context = default_context_with_storage(storage);
+        let balance_at = |block: &str| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0c2c51a0990aee1d73c1228de158688341557508","{block}"]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            let result = map_http_requests(&request, context.clone());
+            rpc_response(request.id, result).0
+        };
+        let response = balance_at("0x0");
+        assert_eq!(response["error"]["code"], -32000);
+        assert_eq!(response["error"]["data"], "0x1");
+        assert_eq!(balance_at("latest")["result"], "0x0");
+    }
+
+    #[test]
+    fn get_proof_of_missing_account() {
+        let context = context_with_genesis();
+        let proof_of = |address: &str| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_getProof","params":["{address}",["0x1"],"latest"]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            let result = map_http_requests(&request, context.clone());
+            rpc_response(request.id, result).0["result"].clone()
+        };
+        let existing = proof_of("0x0c2c51a0990aee1d73c1228de158688341557508");
+        let missing = proof_of("0x1111111111111111111111111111111111111111");
+        assert_eq!(existing["balance"], "0xc097ce7bc90715b34b9f1000000000");
+        // The absence is proven by a path from the same state root
+        assert_eq!(missing["balance"], "0x0");
+        assert_eq!(missing["nonce"], "0x0");
+        assert_eq!(
+            missing["storageHash"],
+            serde_json::to_value(*EMPTY_TRIE_HASH).unwrap()
+        );
+        assert_eq!(missing["accountProof"][0], existing["accountProof"][0]);
+        assert_eq!(missing["storageProof"][0]["value"], "0x0");
+    }
+
+    #[test]
+    fn get_non_canonical_block_by_hash() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        // Two blocks at the same height, only the first one is canonical
+        let hashes: Vec<_> = [0, 1]
+            .into_iter()
+            .map(|timestamp| {
+                let header = BlockHeader {
+                    number: 1,
+                    timestamp,
+                    ..Default::default()
+                };
+                let hash = header.compute_block_hash();
+                storage
+                    .add_block(Block::new(header, Default::default()))
+                    .unwrap();
+                hash
+            })
+            .collect();
+        storage.set_canonical_block(1, hashes[0]).unwrap();
+        storage.update_latest_block_number(1).unwrap();
+        let context = default_context_with_storage(storage);
+        for hash in hashes {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_getBlockByHash","params":["{hash:#x}",false]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            let result = map_http_requests(&request, context.clone()).unwrap();
+            let block: RpcBlock = serde_json::from_value(result).unwrap();
+            assert_eq!(block.header.compute_block_hash(), hash);
+        }
+    }
+
+    #[test]
+    fn get_block_receipts_by_number_and_hash() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let private_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let log = Log {
+            address: Address::repeat_byte(1),
+            topics: vec![],
+            data: Default::default(),
+        };
+        // Two blocks at the same height with two transactions each, only the first one is canonical
+        let hashes: Vec<_> = [0, 1]
+            .into_iter()
+            .map(|timestamp| {
+                let transactions: Vec<_> = (0..2)
+                    .map(|nonce| {
+                        Transaction::EIP1559Transaction(
+                            EIP1559Transaction {
+                                nonce,
+                                gas_limit: 21_000,
+                                to: TxKind::Call(Address::repeat_byte(1)),
+                                ..Default::default()
+                            }
+                            .sign(&private_key),
+                        )
+                    })
+                    .collect();
+                let header = BlockHeader {
+                    number: 1,
+                    timestamp,
+                    parent_hash: H256::repeat_byte(1),
+                    ..Default::default()
+                };
+                let hash = header.compute_block_hash();
+                let body = BlockBody {
+                    transactions: transactions.clone(),
+                    ..Default::default()
+                };
+                storage.add_block(Block::new(header, body)).unwrap();
+                for (index, tx) in transactions.iter().enumerate() {
+                    let receipt = Receipt::new(
+                        tx.tx_type(),
+                        true,
+                        21_000 * (index as u64 + 1),
+                        vec![log.clone(); timestamp as usize + 1],
+                    );
+                    storage.add_receipt(hash, index as u64, receipt).unwrap();
+                }
+                hash
+            })
+            .collect();
+        storage.set_canonical_block(1, hashes[0]).unwrap();
+        storage.update_latest_block_number(1).unwrap();
+        let context = default_context_with_storage(storage);
+        let get_receipts = |block: String| {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"eth_getBlockReceipts","params":["{block}"]}}"#
+            );
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            map_http_requests(&request, context.clone()).unwrap()
+        };
+
+        let receipts = get_receipts("0x1".to_owned());
+        assert_eq!(receipts, get_receipts(format!("{:#x}", hashes[0])));
+        assert_eq!(receipts.as_array().unwrap().len(), 2);
+        assert_eq!(receipts[1]["gasUsed"], "0x5208");
+        assert_eq!(receipts[1]["logs"][0]["logIndex"], "0x1");
+        // The receipts of the block of the other fork are its own
+        let receipts = get_receipts(format!("{:#x}", hashes[1]));
+        assert_eq!(receipts[0]["blockHash"], format!("{:#x}", hashes[1]));
+        assert_eq!(receipts[1]["logs"][1]["logIndex"], "0x3");
+        assert_eq!(
+            get_receipts(format!("{:#x}", H256::repeat_byte(2))),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn get_payload_bodies_by_hash_and_range() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let mut hashes = vec![];
+        for number in 1..=3 {
+            let header = BlockHeader {
+                number,
+                ..Default::default()
+            };
+            let hash = header.compute_block_hash();
+            let body = BlockBody {
+                withdrawals: Some(vec![]),
+                ..Default::default()
+            };
+            storage.add_block(Block::new(header, body)).unwrap();
+            storage.set_canonical_block(number, hash).unwrap();
+            hashes.push(hash);
+        }
+        storage.update_latest_block_number(3).unwrap();
+        let context = default_context_with_storage(storage);
+        let call = |method: &str, params: serde_json::Value| {
+            let request = RpcRequest {
+                method: method.to_string(),
+                params: Some(params.as_array().unwrap().clone()),
+                ..Default::default()
+            };
+            map_engine_requests(&request, context.clone())
+        };
+        let body = serde_json::json!({"transactions": [], "withdrawals": []});
+
+        // Unknown blocks are null
+        let bodies = call(
+            "engine_getPayloadBodiesByHashV1",
+            serde_json::json!([[hashes[2], H256::random()]]),
+        )
+        .unwrap();
+        assert_eq!(bodies, serde_json::json!([body, null]));
+        let too_many = vec![hashes[0]; GET_PAYLOAD_BODIES_REQUEST_MAX_SIZE + 1];
+        assert!(matches!(
+            call(
+                "engine_getPayloadBodiesByHashV1",
+                serde_json::json!([too_many])
+            ),
+            Err(RpcErr::TooLargeRequest(_))
+        ));
+
+        // The range is cut at the latest block
+        let bodies = call(
+            "engine_getPayloadBodiesByRangeV1",
+            serde_json::json!(["0x2", "0x10"]),
+        )
+        .unwrap();
+        assert_eq!(bodies, serde_json::json!([body, body]));
+        assert!(matches!(
+            call(
+                "engine_getPayloadBodiesByRangeV1",
+                serde_json::json!(["0x1", "0x401"])
+            ),
+            Err(RpcErr::TooLargeRequest(_))
+        ));
+        assert!(call(
+            "engine_getPayloadBodiesByRangeV1",
+            serde_json::json!(["0x0", "0x1"])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn get_blobs_serves_the_blobs_of_the_pool() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let bundle = BlobsBundle {
+            blobs: vec![[1; BYTES_PER_BLOB]],
+            commitments: vec![[2; 48]],
+            proofs: vec![[3; 48]],
+        };
+        let versioned_hash = bundle.generate_versioned_hashes()[0];
+        storage
+            .add_blobs_bundle_to_pool(H256::random(), bundle)
+            .unwrap();
+        let context = default_context_with_storage(storage);
+        let call = |versioned_hashes: Vec<H256>| {
+            let request = RpcRequest {
+                method: "engine_getBlobsV1".to_string(),
+                params: Some(vec![serde_json::json!(versioned_hashes)]),
+                ..Default::default()
+            };
+            map_engine_requests(&request, context.clone())
+        };
+
+        // Blobs that are not in the pool are null
+        let blobs = call(vec![H256::random(), versioned_hash]).unwrap();
+        assert_eq!(blobs[0], serde_json::Value::Null);
+        assert_eq!(
+            blobs[1]["blob"],
+            serde_json::json!(format!("0x{}", hex::encode([1; BYTES_PER_BLOB])))
+        );
+        assert_eq!(
+            blobs[1]["proof"],
+            serde_json::json!(format!("0x{}", hex::encode([3; 48])))
+        );
+        assert!(matches!(
+            call(vec![versioned_hash; GET_BLOBS_REQUEST_MAX_SIZE + 1]),
+            Err(RpcErr::TooLargeRequest(_))
+        ));
+    }
+
+    #[test]
+    fn exchange_capabilities_returns_the_served_engine_methods() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let context = default_context_with_storage(storage);
+        let request: RpcRequest = vec!["engine_newPayloadV3".to_string()].into();
+        let capabilities = map_engine_requests(&request, context.clone()).unwrap();
+        assert_eq!(capabilities, serde_json::json!(CAPABILITIES));
+        // Every advertised method must be routed
+        for method in CAPABILITIES {
+            let request = RpcRequest {
+                method: method.to_string(),
+                ..Default::default()
+            };
+            assert!(!matches!(
+                map_engine_requests(&request, context.clone()),
+                Err(RpcErr::MethodNotFound(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn node_config_reports_the_chain_and_node_settings() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let genesis = read_execution_api_genesis_file();
+        storage
+            .add_initial_state(genesis.clone())
+            .expect("Failed to add genesis block to DB");
+        let context = default_context_with_storage(storage);
+        let request: RpcRequest = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":1,"method":"ethrex_nodeConfig","params":[]}"#,
+        )
+        .unwrap();
+        let config = map_http_requests(&request, context).unwrap();
+        assert_eq!(
+            config["chainId"],
+            serde_json::json!(format!("{:#x}", genesis.config.chain_id))
+        );
+        assert_eq!(config["activeFork"], "cancun");
+        assert_eq!(config["blobSchedule"]["max"], "0x6");
+        assert_eq!(config["httpNamespaces"], serde_json::json!(HTTP_NAMESPACES));
+        assert_eq!(config["syncMode"], "full");
+        assert_eq!(config["pruning"]["earliestBlock"], "0x0");
+    }
+
+    #[test]
+    fn syncing_reports_the_sync_progress() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let context = default_context_with_storage(storage);
+        let request: RpcRequest =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"method":"eth_syncing","params":[]}"#)
+                .unwrap();
+        let syncing = map_http_requests(&request, context.clone()).unwrap();
+        assert_eq!(syncing, serde_json::json!(false));
+
+        let status = context.syncer.status();
+        status.start(10);
+        status.update(|progress| {
+            progress.current_block = 15;
+            progress.highest_block = 100;
+        });
+        let syncing = map_http_requests(&request, context.clone()).unwrap();
+        assert_eq!(
+            syncing,
+            serde_json::json!({
+                "startingBlock": "0xa",
+                "currentBlock": "0xf",
+                "highestBlock": "0x64",
+                "syncedAccounts": "0x0",
+                "syncedStorage": "0x0",
+                "healedTrienodes": "0x0",
+                "healingTrienodes": "0x0",
+            })
+        );
+        status.finish();
+        let syncing = map_http_requests(&request, context).unwrap();
+        assert_eq!(syncing, serde_json::json!(false));
+    }
+
+    #[test]
+    fn update_fork_schedule_only_through_authrpc() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        storage
+            .add_initial_state(read_execution_api_genesis_file())
+            .expect("Failed to add genesis block to DB");
+        let context = default_context_with_storage(storage.clone());
+        let update = |schedule: &str| -> RpcRequest {
+            serde_json::from_str(&format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"admin_updateForkSchedule","params":[{schedule}]}}"#
+            ))
+            .unwrap()
+        };
+        let schedule = r#"{"shanghaiTime":0,"cancunTime":0,"pragueTime":1000}"#;
+        assert!(matches!(
+            map_http_requests(&update(schedule), context.clone()),
+            Err(RpcErr::MethodNotFound(_))
+        ));
+        assert!(map_authrpc_requests(&update(schedule), context.clone()).is_ok());
+        assert_eq!(storage.get_chain_config().unwrap().prague_time, Some(1000));
+        // Cancun is active since genesis
+        let schedule = r#"{"shanghaiTime":0,"cancunTime":10,"pragueTime":1000}"#;
+        assert!(matches!(
+            map_authrpc_requests(&update(schedule), context),
+            Err(RpcErr::BadParams(_))
+        ));
+    }
+
     fn example_chain_config() -> ChainConfig {
         ChainConfig {
             chain_id: 3151908_u64,
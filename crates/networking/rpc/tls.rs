@@ -0,0 +1,264 @@
+use std::{
+    fs::File,
+    future::Future,
+    io::{self, BufReader},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use tokio::net::TcpListener;
+use tokio_rustls::{
+    rustls::{
+        crypto::ring::default_provider, pki_types::CertificateDer, server::WebPkiClientVerifier,
+        RootCertStore, ServerConfig,
+    },
+    TlsAcceptor,
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::debug;
+
+/// Certificate and key used to terminate TLS connections to the RPC server.
+/// If a client CA is given, only clients presenting a certificate signed by it are accepted.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub client_ca_path: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    pub fn acceptor(&self) -> Result<TlsAcceptor, io::Error> {
+        let provider = Arc::new(default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+        let builder = match &self.client_ca_path {
+            Some(client_ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(client_ca_path)? {
+                    roots.add(cert).map_err(io::Error::other)?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(roots.into(), provider)
+                    .build()
+                    .map_err(io::Error::other)?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(&self.key_path)?))?
+            .ok_or(io::Error::other("No private key found"))?;
+        let mut config = builder
+            .with_single_cert(read_certs(&self.cert_path)?, key)
+            .map_err(io::Error::other)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+fn read_certs(path: &PathBuf) -> Result<Vec<CertificateDer<'static>>, io::Error> {
+    rustls_pemfile::certs(&mut BufReader::new(File::open(path)?)).collect()
+}
+
+/// Serves the router over TLS until the shutdown signal is received. Once received, no more
+/// connections are accepted and the ones open are closed after serving their in-flight requests.
+pub async fn serve_tls(
+    listener: TcpListener,
+    router: Router,
+    acceptor: TlsAcceptor,
+    shutdown_signal: impl Future<Output = ()>,
+) -> Result<(), io::Error> {
+    let shutdown = CancellationToken::new();
+    let connections = TaskTracker::new();
+    tokio::pin!(shutdown_signal);
+    loop {
+        let (stream, peer_addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut shutdown_signal => break,
+        };
+        let (acceptor, router, shutdown) = (acceptor.clone(), router.clone(), shutdown.clone());
+        connections.spawn(async move {
+            let stream = tokio::select! {
+                accepted = acceptor.accept(stream) => match accepted {
+                    Ok(stream) => stream,
+                    Err(error) => {
+                        debug!("TLS handshake with {peer_addr} failed: {error}");
+                        return;
+                    }
+                },
+                _ = shutdown.cancelled() => return,
+            };
+            let connection = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), TowerToHyperService::new(router));
+            tokio::pin!(connection);
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                _ = shutdown.cancelled() => {
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            if let Err(error) = result {
+                debug!("Error serving TLS connection with {peer_addr}: {error}");
+            }
+        });
+    }
+    shutdown.cancel();
+    connections.close();
+    connections.wait().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::SocketAddr, time::Duration};
+
+    use axum::routing::post;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        sync::oneshot,
+    };
+    use tokio_rustls::{
+        rustls::{pki_types::ServerName, ClientConfig},
+        TlsConnector,
+    };
+
+    const TLS_TEST_DATA: &str = "../../../test_data/tls";
+
+    fn test_config() -> TlsConfig {
+        TlsConfig {
+            cert_path: test_data("cert.pem"),
+            key_path: test_data("key.pem"),
+            client_ca_path: None,
+        }
+    }
+
+    fn test_data(file: &str) -> PathBuf {
+        PathBuf::from(TLS_TEST_DATA).join(file)
+    }
+
+    /// Starts serving a router that answers "ok" after the given delay, until the returned
+    /// sender is used or dropped
+    async fn start_server(
+        config: TlsConfig,
+        delay: Duration,
+    ) -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new().route(
+            "/",
+            post(move || async move {
+                tokio::time::sleep(delay).await;
+                "ok"
+            }),
+        );
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            serve_tls(listener, router, config.acceptor().unwrap(), async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+            .unwrap();
+        });
+        (addr, shutdown_tx, server)
+    }
+
+    /// Sends a request to the server, presenting the given client certificate and key if any,
+    /// and returns the response
+    async fn send_request(
+        addr: SocketAddr,
+        client_cert: Option<(&str, &str)>,
+    ) -> Result<String, io::Error> {
+        let mut roots = RootCertStore::empty();
+        for cert in read_certs(&test_data("ca.pem"))? {
+            roots.add(cert).map_err(io::Error::other)?;
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots);
+        let config = match client_cert {
+            Some((cert, key)) => {
+                let key =
+                    rustls_pemfile::private_key(&mut BufReader::new(File::open(test_data(key))?))?
+                        .ok_or(io::Error::other("No private key found"))?;
+                builder
+                    .with_client_auth_cert(read_certs(&test_data(cert))?, key)
+                    .map_err(io::Error::other)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        stream
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    fn is_ok_response(response: &str) -> bool {
+        response.starts_with("HTTP/1.1 200") && response.ends_with("ok")
+    }
+
+    #[tokio::test]
+    async fn serves_requests_over_tls() {
+        let (addr, _shutdown, _) = start_server(test_config(), Duration::ZERO).await;
+        let response = send_request(addr, None).await.unwrap();
+        assert!(is_ok_response(&response));
+    }
+
+    #[tokio::test]
+    async fn client_certificates_are_verified() {
+        let config = TlsConfig {
+            client_ca_path: Some(test_data("client-ca.pem")),
+            ..test_config()
+        };
+        let (addr, _shutdown, _) = start_server(config, Duration::ZERO).await;
+
+        assert!(send_request(addr, None).await.is_err());
+        assert!(send_request(
+            addr,
+            Some(("untrusted-client.pem", "untrusted-client-key.pem"))
+        )
+        .await
+        .is_err());
+        let response = send_request(addr, Some(("client.pem", "client-key.pem")))
+            .await
+            .unwrap();
+        assert!(is_ok_response(&response));
+    }
+
+    #[tokio::test]
+    async fn in_flight_requests_are_served_on_shutdown() {
+        let (addr, shutdown, server) =
+            start_server(test_config(), Duration::from_millis(500)).await;
+        let request = tokio::spawn(send_request(addr, None));
+        // Let the request reach the server before shutting it down
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        shutdown.send(()).unwrap();
+
+        let response = request.await.unwrap().unwrap();
+        assert!(is_ok_response(&response));
+        server.await.unwrap();
+        // No more connections are accepted
+        assert!(send_request(addr, None).await.is_err());
+    }
+
+    #[test]
+    fn missing_key_is_an_error() {
+        let config = TlsConfig {
+            key_path: test_data("missing.pem"),
+            ..test_config()
+        };
+        assert!(config.acceptor().is_err());
+    }
+}
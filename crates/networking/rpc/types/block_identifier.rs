@@ -1,7 +1,11 @@
 use std::{fmt::Display, str::FromStr};
 
 use ethrex_core::types::{BlockHash, BlockHeader, BlockNumber};
-use ethrex_storage::{error::StoreError, Store};
+use ethrex_storage::{
+    error::StoreError,
+    state_provider::{StateProvider, StateProviderFactory},
+    Store,
+};
 use serde::Deserialize;
 use serde_json::Value;
 
@@ -84,6 +88,34 @@ impl BlockIdentifier {
     }
 }
 
+/// Fails if the state of the block is not stored, reporting the oldest block whose state is available
+pub fn ensure_state_available(storage: &Store, block_number: BlockNumber) -> Result<(), RpcErr> {
+    if storage.has_state(block_number)? {
+        return Ok(());
+    }
+    Err(RpcErr::MissingState {
+        block: block_number,
+        oldest_available: storage.get_oldest_block_with_state()?,
+    })
+}
+
+/// Fails if the body and receipts of the block were pruned and can't be read from the era1
+/// archive, reporting the first block whose history is available.
+/// Blocks before the earliest one were never stored, not pruned.
+pub fn ensure_history_available(storage: &Store, block_number: BlockNumber) -> Result<(), RpcErr> {
+    let earliest = storage.get_earliest_block_number()?.unwrap_or_default();
+    let history_start = storage.get_history_start_block_number()?;
+    if !(earliest..history_start).contains(&block_number)
+        || storage.has_archived_history(block_number)?
+    {
+        return Ok(());
+    }
+    Err(RpcErr::PrunedHistory {
+        block: block_number,
+        history_start,
+    })
+}
+
 impl BlockIdentifierOrHash {
     #[allow(unused)]
     pub fn resolve_block_number(&self, storage: &Store) -> Result<Option<BlockNumber>, StoreError> {
@@ -106,6 +138,33 @@ impl BlockIdentifierOrHash {
         }
     }
 
+    /// Resolves the number of the block whose state is requested,
+    /// failing if the block is unknown or its state is not available
+    pub fn resolve_state_block_number(&self, storage: &Store) -> Result<BlockNumber, RpcErr> {
+        let Some(block_number) = self.resolve_block_number(storage)? else {
+            return Err(RpcErr::Internal(
+                "Could not resolve block number".to_owned(),
+            ));
+        };
+        ensure_state_available(storage, block_number)?;
+        Ok(block_number)
+    }
+
+    /// Resolves the block whose state is requested and pins a view of its state, so every read
+    /// serving the request observes the same state even if blocks are imported meanwhile.
+    /// Fails if the block is unknown or its state is not available.
+    pub fn resolve_state(&self, storage: &Store) -> Result<StateProvider, RpcErr> {
+        let block_number = self.resolve_state_block_number(storage)?;
+        let factory = StateProviderFactory::new(storage.clone());
+        let state = match self {
+            BlockIdentifierOrHash::Hash(block_hash) => factory.state_by_hash(*block_hash)?,
+            BlockIdentifierOrHash::Identifier(_) => factory.state_by_number(block_number)?,
+        };
+        state.ok_or(RpcErr::Internal(
+            "Could not resolve block number".to_owned(),
+        ))
+    }
+
     #[allow(unused)]
     pub fn is_latest(&self, storage: &Store) -> Result<bool, StoreError> {
         if self == &BlockTag::Latest {
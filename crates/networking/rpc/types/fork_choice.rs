@@ -2,7 +2,7 @@ use super::payload::PayloadStatus;
 use ethrex_core::{serde_utils, types::Withdrawal, Address, H256};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ForkChoiceState {
     #[allow(unused)]
@@ -11,16 +11,22 @@ pub struct ForkChoiceState {
     pub finalized_block_hash: H256,
 }
 
-#[derive(Debug, Deserialize, Default, Serialize)]
+#[derive(Debug, Clone, Deserialize, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 #[allow(unused)]
-pub struct PayloadAttributesV3 {
+/// Payload attributes of every engine_forkchoiceUpdated version, the fields missing
+/// in earlier versions are optional
+pub struct PayloadAttributes {
     #[serde(with = "serde_utils::u64::hex_str")]
     pub timestamp: u64,
     pub prev_randao: H256,
     pub suggested_fee_recipient: Address,
-    pub withdrawals: Vec<Withdrawal>,
-    pub parent_beacon_block_root: H256,
+    /// Only present from Shanghai on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdrawals: Option<Vec<Withdrawal>>,
+    /// Only present from Cancun on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_beacon_block_root: Option<H256>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
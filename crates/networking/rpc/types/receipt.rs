@@ -4,6 +4,7 @@ use ethrex_core::{
     types::{BlockHash, BlockHeader, BlockNumber, Log, Receipt, Transaction, TxKind, TxType},
     Address, Bloom, Bytes, H256,
 };
+use ethrex_storage::Store;
 use ethrex_vm::RevmAddress;
 
 use serde::{Deserialize, Serialize};
@@ -167,9 +168,10 @@ impl RpcReceiptTxInfo {
         index: u64,
         gas_used: u64,
         block_blob_gas_price: u64,
+        storage: &Store,
     ) -> Self {
         let nonce = transaction.nonce();
-        let from = transaction.sender();
+        let from = storage.transaction_sender(&transaction);
         let transaction_hash = transaction.compute_hash();
         let effective_gas_price = transaction.gas_price();
         let transaction_index = index;
@@ -249,4 +251,24 @@ mod tests {
         let expected = r#"{"type":"0x3","status":"0x1","cumulativeGasUsed":"0x93","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","logs":[{"address":"0x0000000000000000000000000000000000000000","topics":[],"data":"0x73747261776265727279","logIndex":"0x0","removed":false,"transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000000","transactionIndex":"0x1","blockHash":"0x0000000000000000000000000000000000000000000000000000000000000000","blockNumber":"0x3"}],"transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000000","transactionIndex":"0x1","from":"0x0000000000000000000000000000000000000000","to":"0x7435ed30a8b4aeb0877cef0c6e8cffe834eb865f","contractAddress":null,"gasUsed":"0x93","effectiveGasPrice":"0x9d","blockHash":"0x0000000000000000000000000000000000000000000000000000000000000000","blockNumber":"0x3"}"#;
         assert_eq!(serde_json::to_string(&receipt).unwrap(), expected);
     }
+
+    #[test]
+    fn serialize_blob_receipt_fields() {
+        let tx_info = RpcReceiptTxInfo {
+            transaction_hash: H256::zero(),
+            transaction_index: 0,
+            from: Address::zero(),
+            to: Some(Address::zero()),
+            contract_address: None,
+            gas_used: 21000,
+            effective_gas_price: 7,
+            blob_gas_price: Some(1),
+            blob_gas_used: Some(2 * GAS_PER_BLOB),
+        };
+        let serialized = serde_json::to_value(&tx_info).unwrap();
+        assert_eq!(serialized["blobGasPrice"], "0x1");
+        assert_eq!(serialized["blobGasUsed"], "0x40000");
+        let deserialized: RpcReceiptTxInfo = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized.blob_gas_used, Some(2 * GAS_PER_BLOB));
+    }
 }
@@ -12,6 +12,7 @@ use ethrex_rlp::{
     error::RLPDecodeError,
     structs::{Decoder, Encoder},
 };
+use ethrex_storage::Store;
 use serde::{Deserialize, Serialize};
 
 #[allow(unused)]
@@ -35,8 +36,9 @@ impl RpcTransaction {
         block_number: BlockNumber,
         block_hash: BlockHash,
         transaction_index: usize,
+        storage: &Store,
     ) -> Self {
-        let from = tx.sender();
+        let from = storage.transaction_sender(&tx);
         let hash = tx.compute_hash();
         let transaction_index = transaction_index as u64;
         RpcTransaction {
@@ -1,10 +1,15 @@
+use ethrex_core::{
+    errors::{CodedError, ErrorCode},
+    types::BlockNumber,
+    U256,
+};
 use ethrex_storage::error::StoreError;
 use ethrex_vm::EvmError;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::authentication::AuthenticationError;
-use ethrex_blockchain::error::MempoolError;
+use ethrex_blockchain::error::{ChainError, MempoolError};
 
 #[derive(Debug, Deserialize)]
 pub enum RpcErr {
@@ -16,12 +21,32 @@ pub enum RpcErr {
     UnsuportedFork(String),
     Internal(String),
     Vm(String),
-    Revert { data: String },
-    Halt { reason: String, gas_used: u64 },
+    Revert {
+        data: String,
+    },
+    Halt {
+        reason: String,
+        gas_used: u64,
+    },
     AuthenticationError(AuthenticationError),
     InvalidForkChoiceState(String),
     InvalidPayloadAttributes(String),
     UnknownPayload(String),
+    TooLargeRequest(usize),
+    MissingState {
+        block: BlockNumber,
+        oldest_available: Option<BlockNumber>,
+    },
+    PrunedHistory {
+        block: BlockNumber,
+        history_start: BlockNumber,
+    },
+    /// A simulation of blocks that can't be run, with the code assigned by the spec of
+    /// `eth_simulateV1` to its cause
+    InvalidSimulation {
+        code: i32,
+        message: String,
+    },
 }
 
 impl From<RpcErr> for RpcErrorMetadata {
@@ -72,10 +97,10 @@ impl From<RpcErr> for RpcErrorMetadata {
                 // Could not find proper documentation about it.
                 code: 3,
                 data: Some(data.clone()),
-                message: format!(
-                    "execution reverted: {}",
-                    get_message_from_revert_data(&data)
-                ),
+                message: match get_message_from_revert_data(&data) {
+                    Some(reason) => format!("execution reverted: {reason}"),
+                    None => "execution reverted".to_owned(),
+                },
             },
             RpcErr::Halt { reason, gas_used } => RpcErrorMetadata {
                 // Just copy the `Revert` error code.
@@ -100,6 +125,18 @@ impl From<RpcErr> for RpcErrorMetadata {
                     data: None,
                     message: "Auth failed: Missing authentication header".to_string(),
                 },
+                AuthenticationError::UnknownApiToken => RpcErrorMetadata {
+                    code: -32000,
+                    data: None,
+                    message: "Auth failed: Unknown API token".to_string(),
+                },
+                AuthenticationError::NamespaceNotAllowed(namespace) => RpcErrorMetadata {
+                    code: -32000,
+                    data: None,
+                    message: format!(
+                        "Auth failed: The API token doesn't give access to the {namespace} namespace"
+                    ),
+                },
             },
             RpcErr::InvalidForkChoiceState(data) => RpcErrorMetadata {
                 code: -38002,
@@ -116,6 +153,37 @@ impl From<RpcErr> for RpcErrorMetadata {
                 data: None,
                 message: format!("Unknown payload: {context}"),
             },
+            RpcErr::TooLargeRequest(max_size) => RpcErrorMetadata {
+                code: -38004,
+                data: None,
+                message: format!("Too large request: at most {max_size} blocks can be requested"),
+            },
+            RpcErr::MissingState {
+                block,
+                oldest_available,
+            } => RpcErrorMetadata {
+                code: -32000,
+                // The oldest block with available state, so callers can retry on a newer block
+                data: oldest_available.map(|number| format!("{number:#x}")),
+                message: format!("missing trie node: state of block {block} is not available"),
+            },
+            RpcErr::PrunedHistory {
+                block,
+                history_start,
+            } => RpcErrorMetadata {
+                // Code used by other clients for the history dropped as per EIP-4444
+                code: 4444,
+                // The first block whose body and receipts are served
+                data: Some(format!("{history_start:#x}")),
+                message: format!(
+                    "pruned history unavailable: the body and receipts of block {block} were pruned, retrieve them from era1 archives"
+                ),
+            },
+            RpcErr::InvalidSimulation { code, message } => RpcErrorMetadata {
+                code,
+                data: None,
+                message,
+            },
         }
     }
 }
@@ -126,14 +194,30 @@ impl From<serde_json::Error> for RpcErr {
     }
 }
 
-// TODO: Actually return different errors for each case
-// here we are returning a BadParams error
+impl RpcErr {
+    /// Maps the error of a lower layer by its code, so an error is surfaced the same way
+    /// whichever layer it was propagated through
+    pub fn from_coded(error: &impl CodedError) -> Self {
+        match error.code() {
+            ErrorCode::Invalid => RpcErr::BadParams(error.to_string()),
+            ErrorCode::Execution => RpcErr::Vm(error.to_string()),
+            ErrorCode::Database
+            | ErrorCode::Corrupted
+            | ErrorCode::Unavailable
+            | ErrorCode::Internal => RpcErr::Internal(error.to_string()),
+        }
+    }
+}
+
 impl From<MempoolError> for RpcErr {
     fn from(err: MempoolError) -> Self {
-        match err {
-            MempoolError::StoreError(err) => Self::Internal(err.to_string()),
-            other_err => Self::BadParams(other_err.to_string()),
-        }
+        Self::from_coded(&err)
+    }
+}
+
+impl From<ChainError> for RpcErr {
+    fn from(err: ChainError) -> Self {
+        Self::from_coded(&err)
     }
 }
 
@@ -143,6 +227,7 @@ pub enum RpcNamespace {
     Admin,
     Debug,
     Web3,
+    Ethrex,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -170,6 +255,7 @@ impl RpcRequest {
                 "admin" => Ok(RpcNamespace::Admin),
                 "debug" => Ok(RpcNamespace::Debug),
                 "web3" => Ok(RpcNamespace::Web3),
+                "ethrex" => Ok(RpcNamespace::Ethrex),
                 _ => Err(RpcErr::MethodNotFound(self.method.clone())),
             }
         } else {
@@ -214,22 +300,34 @@ pub struct RpcErrorResponse {
 /// Failure to read from DB will always constitute an internal error
 impl From<StoreError> for RpcErr {
     fn from(value: StoreError) -> Self {
-        RpcErr::Internal(value.to_string())
+        Self::from_coded(&value)
     }
 }
 
 impl From<EvmError> for RpcErr {
     fn from(value: EvmError) -> Self {
-        RpcErr::Vm(value.to_string())
+        Self::from_coded(&value)
     }
 }
 
-fn get_message_from_revert_data(_data: &str) -> String {
-    // TODO
-    // Hive tests are not failing when revert message does not match, but currently it is not matching
-    // It should be fixed
-    // See https://github.com/ethereum/go-ethereum/blob/8fd43c80132434dca896d8ae5004ae2aac1450d3/accounts/abi/abi.go#L275
-    "".to_owned()
+/// Selector of `Error(string)`, the error returned by solidity's `revert` and `require`
+const REVERT_ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Decodes the reason of a revert from the hex encoded output of the reverted call, if it is an
+/// ABI encoded `Error(string)`.
+/// See https://github.com/ethereum/go-ethereum/blob/8fd43c80132434dca896d8ae5004ae2aac1450d3/accounts/abi/abi.go#L275
+fn get_message_from_revert_data(data: &str) -> Option<String> {
+    let data = hex::decode(data.trim_start_matches("0x")).ok()?;
+    let encoded = data.strip_prefix(&REVERT_ERROR_SELECTOR)?;
+    let word = |offset: usize| -> Option<usize> {
+        let word = encoded.get(offset..offset.checked_add(32)?)?;
+        U256::from_big_endian(word).try_into().ok()
+    };
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let reason = encoded.get(start..start.checked_add(len)?)?;
+    String::from_utf8(reason.to_vec()).ok()
 }
 
 pub fn parse_json_hex(hex: &serde_json::Value) -> Result<u64, String> {
@@ -246,11 +344,15 @@ pub fn parse_json_hex(hex: &serde_json::Value) -> Result<u64, String> {
 pub mod test_utils {
     use std::{net::SocketAddr, str::FromStr};
 
-    use ethrex_core::H512;
-    use ethrex_net::{sync::SyncManager, types::Node};
+    use ethrex_blockchain::payload_manager::DEFAULT_GET_PAYLOAD_DEADLINE;
+    use ethrex_core::{types::Genesis, H512};
+    use ethrex_net::{
+        sync::{SyncHandle, SyncManager},
+        types::Node,
+    };
     use ethrex_storage::{EngineType, Store};
 
-    use crate::start_api;
+    use crate::{start_api, RpcApiContext};
 
     pub const TEST_GENESIS: &str = include_str!("../../../test_data/genesis-l1.json");
     pub fn example_p2p_node() -> Node {
@@ -263,6 +365,42 @@ pub mod test_utils {
         }
     }
 
+    // Reads genesis file taken from https://github.com/ethereum/execution-apis/blob/main/tests/genesis.json
+    pub fn read_execution_api_genesis_file() -> Genesis {
+        let file = std::fs::File::open("../../../test_data/genesis-execution-api.json")
+            .expect("Failed to open genesis file");
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .expect("Failed to deserialize genesis file")
+    }
+
+    /// Context serving the given store, with every other setting left to its default
+    pub fn default_context_with_storage(storage: Store) -> RpcApiContext {
+        RpcApiContext {
+            storage,
+            jwt_secret: Default::default(),
+            local_p2p_node: example_p2p_node(),
+            active_filters: Default::default(),
+            syncer: SyncHandle::dummy(),
+            last_fork_choice: Default::default(),
+            payload_validations: Default::default(),
+            payload_manager: Default::default(),
+            api_tokens: Default::default(),
+            gas_price_oracle: Default::default(),
+            engine_metrics: Default::default(),
+            reported_stall: Default::default(),
+        }
+    }
+
+    /// Context serving a store that holds the genesis of the execution-apis tests
+    pub fn context_with_genesis() -> RpcApiContext {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        storage
+            .add_initial_state(read_execution_api_genesis_file())
+            .expect("Failed to add genesis block to DB");
+        default_context_with_storage(storage)
+    }
+
     // Util to start an api for testing on ports 8500 and 8501,
     // mostly for when hive is missing some endpoints to test
     // like eth_uninstallFilter.
@@ -292,6 +430,11 @@ pub mod test_utils {
             jwt_secret,
             local_p2p_node,
             SyncManager::dummy(),
+            None,
+            None,
+            Default::default(),
+            DEFAULT_GET_PAYLOAD_DEADLINE,
+            Default::default(),
         )
         .await;
     }
@@ -0,0 +1,24 @@
+/// Two letter code of the client, as listed by the engine API spec
+pub const CLIENT_CODE: &str = "EX";
+pub const CLIENT_NAME: &str = "ethrex";
+pub const CLIENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit the node was built from, empty if it wasn't built from a git checkout
+pub const GIT_COMMIT: &str = env!("ETHREX_GIT_COMMIT");
+
+/// Returns the first four bytes of the commit the node was built from, zero if unknown
+pub fn short_commit() -> String {
+    match GIT_COMMIT.get(..8) {
+        Some(commit) => format!("0x{commit}"),
+        None => "0x00000000".to_string(),
+    }
+}
+
+/// Returns the version of the node as `ethrex/v<version>-<commit>/<os>-<arch>`
+pub fn client_version() -> String {
+    format!(
+        "{CLIENT_NAME}/v{CLIENT_VERSION}-{}/{}-{}",
+        short_commit().trim_start_matches("0x"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )
+}
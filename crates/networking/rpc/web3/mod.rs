@@ -1,8 +1,11 @@
 use ethrex_storage::Store;
 use serde_json::Value;
 
-use crate::utils::{RpcErr, RpcRequest};
+use crate::{
+    utils::{RpcErr, RpcRequest},
+    version,
+};
 
 pub fn client_version(_req: &RpcRequest, _store: Store) -> Result<Value, RpcErr> {
-    Ok(Value::String("ethrex@0.1.0".to_owned()))
+    Ok(Value::String(version::client_version()))
 }
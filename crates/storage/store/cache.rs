@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ethrex_core::types::{BlockBody, BlockHash, BlockHeader, BlockNumber, Index, Receipt};
+
+/// Amount of blocks behind the latest one that are kept in the [HeadCache]
+pub const HEAD_CACHE_SIZE: u64 = 128;
+
+/// In-memory cache for the headers, bodies and receipts of the latest canonical blocks.
+/// Entries are indexed by block number and are only valid as long as the cached hash is
+/// the canonical one for that number, so they must be invalidated whenever the canonical
+/// chain changes (see [HeadCache::set_canonical] and [HeadCache::unset_canonical]).
+///
+/// Every canonical chain update bumps the cache generation. Values read from the database
+/// are only inserted if the generation they were read under is still the current one, so a
+/// read that raced with a reorg can't bring back a block that is no longer canonical.
+#[derive(Debug, Default)]
+pub struct HeadCache {
+    latest: Option<BlockNumber>,
+    generation: u64,
+    blocks: BTreeMap<BlockNumber, CachedBlock>,
+    numbers: HashMap<BlockHash, BlockNumber>,
+}
+
+#[derive(Debug)]
+struct CachedBlock {
+    hash: BlockHash,
+    header: Option<BlockHeader>,
+    body: Option<BlockBody>,
+    receipts: HashMap<Index, Receipt>,
+}
+
+impl CachedBlock {
+    fn new(hash: BlockHash) -> Self {
+        Self {
+            hash,
+            header: None,
+            body: None,
+            receipts: HashMap::new(),
+        }
+    }
+}
+
+impl HeadCache {
+    pub fn new(latest: Option<BlockNumber>) -> Self {
+        Self {
+            latest,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the current generation, to be passed to the `add_*` methods once the values
+    /// have been read from the database
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns true if the given block number is close enough to the head to be cached
+    pub fn in_range(&self, number: BlockNumber) -> bool {
+        self.latest
+            .is_some_and(|latest| number <= latest && latest - number < HEAD_CACHE_SIZE)
+    }
+
+    /// Returns the cache entry for the given block, creating it if the block is in range and
+    /// the generation is still the current one.
+    /// A previous entry with a different hash is discarded.
+    fn entry(
+        &mut self,
+        generation: u64,
+        number: BlockNumber,
+        hash: BlockHash,
+    ) -> Option<&mut CachedBlock> {
+        if generation != self.generation || !self.in_range(number) {
+            return None;
+        }
+        if self.get_hash(number).is_some_and(|cached| cached != hash) {
+            self.remove(number);
+        }
+        self.numbers.insert(hash, number);
+        Some(
+            self.blocks
+                .entry(number)
+                .or_insert_with(|| CachedBlock::new(hash)),
+        )
+    }
+
+    fn remove(&mut self, number: BlockNumber) {
+        if let Some(block) = self.blocks.remove(&number) {
+            self.numbers.remove(&block.hash);
+        }
+    }
+
+    pub fn get_header(&self, number: BlockNumber) -> Option<BlockHeader> {
+        self.blocks.get(&number)?.header.clone()
+    }
+
+    pub fn get_header_by_hash(&self, hash: BlockHash) -> Option<BlockHeader> {
+        self.get_header(*self.numbers.get(&hash)?)
+    }
+
+    pub fn get_body(&self, number: BlockNumber) -> Option<BlockBody> {
+        self.blocks.get(&number)?.body.clone()
+    }
+
+    pub fn get_body_by_hash(&self, hash: BlockHash) -> Option<BlockBody> {
+        self.get_body(*self.numbers.get(&hash)?)
+    }
+
+    pub fn get_receipt(&self, number: BlockNumber, index: Index) -> Option<Receipt> {
+        self.blocks.get(&number)?.receipts.get(&index).cloned()
+    }
+
+    pub fn add_header(
+        &mut self,
+        generation: u64,
+        number: BlockNumber,
+        hash: BlockHash,
+        header: &BlockHeader,
+    ) {
+        if let Some(block) = self.entry(generation, number, hash) {
+            block.header = Some(header.clone());
+        }
+    }
+
+    pub fn add_body(
+        &mut self,
+        generation: u64,
+        number: BlockNumber,
+        hash: BlockHash,
+        body: &BlockBody,
+    ) {
+        if let Some(block) = self.entry(generation, number, hash) {
+            block.body = Some(body.clone());
+        }
+    }
+
+    pub fn add_receipt(
+        &mut self,
+        generation: u64,
+        number: BlockNumber,
+        hash: BlockHash,
+        index: Index,
+        receipt: &Receipt,
+    ) {
+        if let Some(block) = self.entry(generation, number, hash) {
+            block.receipts.insert(index, receipt.clone());
+        }
+    }
+
+    /// Returns the number of the cached block with the given hash
+    pub fn get_number(&self, hash: BlockHash) -> Option<BlockNumber> {
+        self.numbers.get(&hash).copied()
+    }
+
+    /// Returns the hash of the cached block with the given number
+    pub fn get_hash(&self, number: BlockNumber) -> Option<BlockHash> {
+        self.blocks.get(&number).map(|block| block.hash)
+    }
+
+    /// Drops the cached block for the given number if it is no longer the canonical one
+    pub fn set_canonical(&mut self, number: BlockNumber, hash: BlockHash) {
+        self.generation += 1;
+        if self.get_hash(number).is_some_and(|cached| cached != hash) {
+            self.remove(number);
+        }
+    }
+
+    /// Drops the cached block for the given number
+    pub fn unset_canonical(&mut self, number: BlockNumber) {
+        self.generation += 1;
+        self.remove(number);
+    }
+
+    /// Updates the head of the chain, dropping all blocks that fall out of range
+    pub fn set_latest(&mut self, latest: BlockNumber) {
+        self.latest = Some(latest);
+        let oldest = latest.saturating_sub(HEAD_CACHE_SIZE - 1);
+        self.blocks
+            .retain(|number, _| (oldest..=latest).contains(number));
+        self.numbers
+            .retain(|_, number| (oldest..=latest).contains(number));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethrex_core::{types::TxType, H256};
+
+    fn header(number: BlockNumber) -> BlockHeader {
+        BlockHeader {
+            number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn in_range_at_window_edge() {
+        let cache = HeadCache::new(Some(HEAD_CACHE_SIZE + 10));
+        assert!(cache.in_range(HEAD_CACHE_SIZE + 10));
+        assert!(cache.in_range(11));
+        assert!(!cache.in_range(10));
+        assert!(!cache.in_range(HEAD_CACHE_SIZE + 11));
+        assert!(!HeadCache::new(None).in_range(0));
+    }
+
+    #[test]
+    fn set_latest_evicts_old_blocks() {
+        let mut cache = HeadCache::new(Some(HEAD_CACHE_SIZE));
+        let old_hash = H256::random();
+        let new_hash = H256::random();
+        cache.add_header(0, 1, old_hash, &header(1));
+        cache.add_header(0, HEAD_CACHE_SIZE, new_hash, &header(HEAD_CACHE_SIZE));
+
+        cache.set_latest(HEAD_CACHE_SIZE + 1);
+        assert!(cache.get_header(1).is_none());
+        assert!(cache.get_header_by_hash(old_hash).is_none());
+        assert!(cache.get_header_by_hash(new_hash).is_some());
+
+        // Moving the head backwards drops the blocks above it
+        cache.set_latest(1);
+        assert!(cache.get_header_by_hash(new_hash).is_none());
+    }
+
+    #[test]
+    fn entry_replaces_block_with_different_hash() {
+        let mut cache = HeadCache::new(Some(5));
+        let hash = H256::random();
+        let other_hash = H256::random();
+        cache.add_header(0, 5, hash, &header(5));
+        cache.add_body(0, 5, hash, &BlockBody::default());
+
+        cache.add_header(0, 5, other_hash, &header(5));
+        assert!(cache.get_header_by_hash(hash).is_none());
+        assert!(cache.get_body(5).is_none());
+        assert!(cache.get_header_by_hash(other_hash).is_some());
+        assert_eq!(cache.get_hash(5), Some(other_hash));
+    }
+
+    #[test]
+    fn stale_generation_is_not_cached() {
+        let mut cache = HeadCache::new(Some(5));
+        let hash = H256::random();
+        let generation = cache.generation();
+        cache.unset_canonical(5);
+        cache.add_header(generation, 5, hash, &header(5));
+        assert!(cache.get_header(5).is_none());
+
+        cache.add_header(cache.generation(), 5, hash, &header(5));
+        assert!(cache.get_header(5).is_some());
+        cache.set_canonical(5, H256::random());
+        assert!(cache.get_header_by_hash(hash).is_none());
+    }
+
+    #[test]
+    fn receipts_are_cached_per_index() {
+        let mut cache = HeadCache::new(Some(5));
+        let hash = H256::random();
+        let receipt = Receipt::new(TxType::EIP1559, true, 21000, vec![]);
+        cache.add_receipt(0, 5, hash, 1, &receipt);
+        assert_eq!(cache.get_receipt(5, 1), Some(receipt.clone()));
+        assert!(cache.get_receipt(5, 0).is_none());
+
+        // Receipts belong to the block they were cached with
+        cache.add_header(0, 5, H256::random(), &header(5));
+        assert!(cache.get_receipt(5, 1).is_none());
+    }
+}
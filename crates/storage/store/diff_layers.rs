@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ethereum_types::{Address, H256, U256};
+use ethrex_core::types::{AccountInfo, BlockHash, BlockNumber};
+
+use crate::AccountUpdate;
+
+/// Amount of blocks behind the highest one whose state changes are kept in the [StateDiffLayers]
+pub const DIFF_LAYERS_SIZE: u64 = 128;
+
+/// In-memory layers holding the state changes of the latest blocks, so reads of the accounts and
+/// storage slots they modified don't need to traverse the state trie of the requested block.
+///
+/// Layers are indexed by block hash and linked to their parent, so blocks from every fork are
+/// kept and no invalidation is needed on reorgs. A read walks the layers from the requested block
+/// backwards and stops at the first one that modified the value. If the walk reaches a block
+/// without a layer the value is unknown and must be read from the state trie.
+#[derive(Debug, Default)]
+pub struct StateDiffLayers {
+    highest: BlockNumber,
+    layers: HashMap<BlockHash, DiffLayer>,
+    numbers: BTreeMap<BlockNumber, Vec<BlockHash>>,
+}
+
+#[derive(Debug)]
+struct DiffLayer {
+    parent_hash: BlockHash,
+    accounts: HashMap<Address, AccountUpdate>,
+}
+
+impl StateDiffLayers {
+    /// Adds the account updates applied by the given block on top of its parent's state,
+    /// dropping the layers that fall out of range
+    pub fn add_layer(
+        &mut self,
+        number: BlockNumber,
+        hash: BlockHash,
+        parent_hash: BlockHash,
+        account_updates: &[AccountUpdate],
+    ) {
+        if number + DIFF_LAYERS_SIZE <= self.highest {
+            return;
+        }
+        let accounts = account_updates
+            .iter()
+            .map(|update| (update.address, update.clone()))
+            .collect();
+        if self
+            .layers
+            .insert(
+                hash,
+                DiffLayer {
+                    parent_hash,
+                    accounts,
+                },
+            )
+            .is_none()
+        {
+            self.numbers.entry(number).or_default().push(hash);
+        }
+        if number > self.highest {
+            self.highest = number;
+            let oldest = self.highest.saturating_sub(DIFF_LAYERS_SIZE - 1);
+            let pruned = self.numbers.split_off(&oldest);
+            for hash in std::mem::replace(&mut self.numbers, pruned)
+                .into_values()
+                .flatten()
+            {
+                self.layers.remove(&hash);
+            }
+        }
+    }
+
+    /// Returns the account updates of the layers from the given block backwards, newest first
+    fn updates(
+        &self,
+        block_hash: BlockHash,
+        address: Address,
+    ) -> impl Iterator<Item = &AccountUpdate> {
+        let mut hash = block_hash;
+        std::iter::from_fn(move || {
+            let layer = self.layers.get(&hash)?;
+            hash = layer.parent_hash;
+            Some(layer)
+        })
+        .filter_map(move |layer| layer.accounts.get(&address))
+    }
+
+    /// Returns the info of the account at the given block, `Some(None)` if the account was
+    /// removed, or `None` if it is not known from the layers
+    pub fn get_account_info(
+        &self,
+        block_hash: BlockHash,
+        address: Address,
+    ) -> Option<Option<AccountInfo>> {
+        let update = self.updates(block_hash, address).next()?;
+        if update.removed {
+            return Some(None);
+        }
+        // An update that only modified the storage may have created the account,
+        // so the info has to be read from the trie
+        update.info.clone().map(Some)
+    }
+
+    /// Returns the value of the storage slot at the given block,
+    /// or `None` if it is not known from the layers
+    pub fn get_storage(
+        &self,
+        block_hash: BlockHash,
+        address: Address,
+        storage_key: H256,
+    ) -> Option<U256> {
+        self.updates(block_hash, address).find_map(|update| {
+            if update.removed {
+                Some(U256::zero())
+            } else {
+                update.added_storage.get(&storage_key).copied()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(address: Address, balance: u64, storage: &[(u64, u64)]) -> AccountUpdate {
+        AccountUpdate {
+            info: Some(AccountInfo {
+                balance: balance.into(),
+                ..Default::default()
+            }),
+            added_storage: storage
+                .iter()
+                .map(|(key, value)| (H256::from_low_u64_be(*key), (*value).into()))
+                .collect(),
+            ..AccountUpdate::new(address)
+        }
+    }
+
+    #[test]
+    fn reads_resolve_to_the_latest_modification() {
+        let address = Address::from_low_u64_be(1);
+        let (hash_1, hash_2, hash_3) = (H256::random(), H256::random(), H256::random());
+        let mut layers = StateDiffLayers::default();
+        layers.add_layer(1, hash_1, H256::zero(), &[update(address, 10, &[(1, 1)])]);
+        layers.add_layer(2, hash_2, hash_1, &[update(address, 20, &[(2, 2)])]);
+        layers.add_layer(3, hash_3, hash_2, &[AccountUpdate::new(Address::zero())]);
+
+        let balance = |hash| {
+            layers
+                .get_account_info(hash, address)
+                .map(|info| info.unwrap().balance)
+        };
+        assert_eq!(balance(hash_3), Some(20.into()));
+        assert_eq!(balance(hash_1), Some(10.into()));
+        assert_eq!(
+            layers.get_storage(hash_3, address, H256::from_low_u64_be(1)),
+            Some(1.into())
+        );
+        assert_eq!(
+            layers.get_storage(hash_1, address, H256::from_low_u64_be(2)),
+            None
+        );
+        // Values not modified by any layer have to be read from the trie
+        assert!(layers
+            .get_account_info(hash_3, Address::from_low_u64_be(2))
+            .is_none());
+        assert!(layers.get_account_info(H256::random(), address).is_none());
+    }
+
+    #[test]
+    fn removed_accounts_have_empty_storage() {
+        let address = Address::from_low_u64_be(1);
+        let (hash_1, hash_2) = (H256::random(), H256::random());
+        let mut layers = StateDiffLayers::default();
+        layers.add_layer(1, hash_1, H256::zero(), &[update(address, 10, &[(1, 1)])]);
+        layers.add_layer(2, hash_2, hash_1, &[AccountUpdate::removed(address)]);
+        assert_eq!(layers.get_account_info(hash_2, address), Some(None));
+        assert_eq!(
+            layers.get_storage(hash_2, address, H256::from_low_u64_be(1)),
+            Some(U256::zero())
+        );
+    }
+
+    #[test]
+    fn old_layers_are_dropped() {
+        let address = Address::from_low_u64_be(1);
+        let mut layers = StateDiffLayers::default();
+        let mut parent_hash = H256::zero();
+        let mut hashes = vec![];
+        for number in 1..=DIFF_LAYERS_SIZE + 1 {
+            let hash = H256::random();
+            layers.add_layer(number, hash, parent_hash, &[update(address, number, &[])]);
+            hashes.push(hash);
+            parent_hash = hash;
+        }
+        assert!(layers.get_account_info(hashes[0], address).is_none());
+        assert!(layers.get_account_info(hashes[1], address).is_some());
+        // Blocks that are already out of range are not added
+        layers.add_layer(1, H256::random(), H256::zero(), &[update(address, 1, &[])]);
+        assert_eq!(layers.layers.len(), DIFF_LAYERS_SIZE as usize);
+    }
+}
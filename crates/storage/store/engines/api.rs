@@ -1,12 +1,14 @@
 use bytes::Bytes;
-use ethereum_types::{H256, U256};
+use ethereum_types::{Address, H256, U256};
 use ethrex_core::types::{
-    Block, BlockBody, BlockHash, BlockHeader, BlockNumber, ChainConfig, Index, Receipt, Transaction,
+    AccountState, BlobsBundle, Block, BlockBody, BlockHash, BlockHeader, BlockNumber, ChainConfig,
+    Index, Receipt, Transaction,
 };
 use std::{fmt::Debug, panic::RefUnwindSafe};
 
 use crate::error::StoreError;
-use ethrex_trie::Trie;
+use crate::snapshot::{SnapshotBatch, SnapshotStatus};
+use ethrex_trie::{StateCommitment, Trie};
 
 pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
     /// Add block header
@@ -84,6 +86,26 @@ pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
         transaction_hash: H256,
     ) -> Result<Option<(BlockNumber, BlockHash, Index)>, StoreError>;
 
+    /// Store a transaction sent or received by the given address, used by the optional address index
+    fn add_address_transaction(
+        &self,
+        address: Address,
+        transaction_hash: H256,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<(), StoreError>;
+
+    /// Obtain the location and hash of the indexed transactions of the given address, including
+    /// those in non-canonical blocks, ordered from the newest by block number, index and block hash.
+    /// Only the ones located before the given position are returned, at most `limit` of them
+    fn get_address_transactions(
+        &self,
+        address: Address,
+        before: Option<(BlockNumber, Index, BlockHash)>,
+        limit: usize,
+    ) -> Result<Vec<(BlockNumber, BlockHash, Index, H256)>, StoreError>;
+
     /// Add receipt
     fn add_receipt(
         &self,
@@ -92,10 +114,20 @@ pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
         receipt: Receipt,
     ) -> Result<(), StoreError>;
 
-    /// Obtain receipt for a canonical block represented by the block number.
-    fn get_receipt(
+    /// Replace a stored receipt, used to repair receipts written with an outdated encoding
+    fn replace_receipt(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
+        index: Index,
+        receipt: Receipt,
+    ) -> Result<(), StoreError> {
+        self.add_receipt(block_hash, index, receipt)
+    }
+
+    /// Obtain receipt for a block represented by its hash, canonical or not.
+    fn get_receipt_by_hash(
+        &self,
+        block_hash: BlockHash,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError>;
 
@@ -150,6 +182,13 @@ pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
         block_number: BlockNumber,
     ) -> Result<Option<BlockHash>, StoreError>;
 
+    /// Obtain the number of a canonical block from its hash, None if the block is not indexed as
+    /// canonical. The index is kept along the canonical block hashes.
+    fn get_canonical_block_number(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockNumber>, StoreError>;
+
     /// Stores the chain configuration values, should only be called once after reading the genesis file
     /// Ignores previously stored values if present
     fn set_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError>;
@@ -163,6 +202,15 @@ pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
     // Obtain earliest block number
     fn get_earliest_block_number(&self) -> Result<Option<BlockNumber>, StoreError>;
 
+    // Update the number of the first block whose body and receipts are kept
+    fn update_history_start_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError>;
+
+    // Obtain the number of the first block whose body and receipts are kept, if history was pruned
+    fn get_history_start_block_number(&self) -> Result<Option<BlockNumber>, StoreError>;
+
     // Update finalized block number
     fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError>;
 
@@ -208,7 +256,25 @@ pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
     // Used for internal store operations
     fn open_state_trie(&self, state_root: H256) -> Trie;
 
-    // Set the canonical block hash for a given block number.
+    // Obtain the structure committing to the world state with the given root, used when
+    // updating the state. Defaults to the state trie, engines can override it to plug in a
+    // different commitment scheme
+    fn open_state_commitment(&self, state_root: H256) -> Box<dyn StateCommitment> {
+        Box::new(self.open_state_trie(state_root))
+    }
+
+    // Obtain the structure committing to an account's storage with the given root, used
+    // when updating the state. Defaults to the storage trie
+    fn open_storage_commitment(
+        &self,
+        hashed_address: H256,
+        storage_root: H256,
+    ) -> Box<dyn StateCommitment> {
+        Box::new(self.open_storage_trie(hashed_address, storage_root))
+    }
+
+    // Set the canonical block hash for a given block number, replacing the previous one in the
+    // canonical block numbers index.
     fn set_canonical_block(&self, number: BlockNumber, hash: BlockHash) -> Result<(), StoreError>;
 
     // Unsets canonical block for a block number.
@@ -217,4 +283,64 @@ pub trait StoreEngine: Debug + Send + Sync + RefUnwindSafe {
     fn add_payload(&self, payload_id: u64, block: Block) -> Result<(), StoreError>;
 
     fn get_payload(&self, payload_id: u64) -> Result<Option<Block>, StoreError>;
+
+    fn remove_payload(&self, payload_id: u64) -> Result<(), StoreError>;
+
+    // Obtain the ids of every stored payload
+    fn get_payload_ids(&self) -> Result<Vec<u64>, StoreError>;
+
+    /// Add the blob sidecars of the blob transactions of a block, along with the block's timestamp
+    fn add_blob_sidecars(
+        &self,
+        block_hash: BlockHash,
+        timestamp: u64,
+        sidecars: BlobsBundle,
+    ) -> Result<(), StoreError>;
+
+    /// Obtain the blob sidecars of the blob transactions of a block
+    fn get_blob_sidecars(&self, block_hash: BlockHash) -> Result<Option<BlobsBundle>, StoreError>;
+
+    /// Remove the body of a block and the receipts of its transactions, keeping its header
+    fn remove_block_history(
+        &self,
+        block_hash: BlockHash,
+        transaction_count: Index,
+    ) -> Result<(), StoreError>;
+
+    /// Remove the blob sidecars of a block
+    fn remove_blob_sidecars(&self, block_hash: BlockHash) -> Result<(), StoreError>;
+
+    /// Obtain the hash and timestamp of every block with stored blob sidecars
+    fn get_blob_sidecar_blocks(&self) -> Result<Vec<(BlockHash, u64)>, StoreError>;
+
+    /// Apply the changes of the batch to the snapshot and store its new status, in a single
+    /// transaction
+    fn write_snapshot_batch(
+        &self,
+        batch: SnapshotBatch,
+        status: &SnapshotStatus,
+    ) -> Result<(), StoreError>;
+
+    /// Obtain the status of the snapshot, if there is one
+    fn get_snapshot_status(&self) -> Result<Option<SnapshotStatus>, StoreError>;
+
+    /// Remove every account and storage slot of the snapshot, along with its status
+    fn clear_snapshot(&self) -> Result<(), StoreError>;
+
+    /// Obtain up to `limit` accounts of the snapshot in hashed address order, starting from the
+    /// given hashed address
+    fn get_snapshot_accounts(
+        &self,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, AccountState)>, StoreError>;
+
+    /// Obtain up to `limit` storage slots of an account of the snapshot in hashed key order,
+    /// starting from the given hashed key
+    fn get_snapshot_storage(
+        &self,
+        hashed_address: H256,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, U256)>, StoreError>;
 }
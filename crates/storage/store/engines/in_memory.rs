@@ -1,17 +1,19 @@
 use crate::error::StoreError;
 use bytes::Bytes;
-use ethereum_types::{H256, U256};
+use ethereum_types::{Address, H256, U256};
 use ethrex_core::types::{
-    Block, BlockBody, BlockHash, BlockHeader, BlockNumber, ChainConfig, Index, Receipt,
+    AccountState, BlobsBundle, Block, BlockBody, BlockHash, BlockHeader, BlockNumber, ChainConfig,
+    Index, Receipt,
 };
 use ethrex_trie::{InMemoryTrieDB, Trie};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt::Debug,
     sync::{Arc, Mutex, MutexGuard},
 };
 
 use super::api::StoreEngine;
+use crate::snapshot::{SnapshotBatch, SnapshotStatus};
 
 pub type NodeMap = Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>;
 
@@ -23,12 +25,15 @@ struct StoreInner {
     chain_data: ChainData,
     block_numbers: HashMap<BlockHash, BlockNumber>,
     canonical_hashes: HashMap<BlockNumber, BlockHash>,
+    canonical_numbers: HashMap<BlockHash, BlockNumber>,
     bodies: HashMap<BlockHash, BlockBody>,
     headers: HashMap<BlockHash, BlockHeader>,
     // Maps code hashes to code
     account_codes: HashMap<H256, Bytes>,
     // Maps transaction hashes to their blocks (height+hash) and index within the blocks.
     transaction_locations: HashMap<H256, Vec<(BlockNumber, BlockHash, Index)>>,
+    // Maps addresses to the transactions they sent or received, only filled if the address index is enabled
+    address_transactions: HashMap<Address, BTreeSet<(BlockNumber, Index, BlockHash, H256)>>,
     receipts: HashMap<BlockHash, HashMap<Index, Receipt>>,
     state_trie_nodes: NodeMap,
     // A storage trie for each hashed account address
@@ -38,18 +43,25 @@ struct StoreInner {
     // Stores local blocks by payload id
     payloads: HashMap<u64, Block>,
     pending_blocks: HashMap<BlockHash, Block>,
+    // Maps block hashes to the block's timestamp and the blob sidecars of its transactions
+    blob_sidecars: HashMap<BlockHash, (u64, BlobsBundle)>,
+    // Flat state of the snapshot, ordered by hashed address and hashed key
+    snapshot_accounts: BTreeMap<H256, AccountState>,
+    snapshot_storage: BTreeMap<(H256, H256), U256>,
 }
 
 #[derive(Default, Debug)]
 struct ChainData {
     chain_config: Option<ChainConfig>,
     earliest_block_number: Option<BlockNumber>,
+    history_start_block_number: Option<BlockNumber>,
     finalized_block_number: Option<BlockNumber>,
     safe_block_number: Option<BlockNumber>,
     latest_block_number: Option<BlockNumber>,
     // TODO (#307): Remove TotalDifficulty.
     latest_total_difficulty: Option<U256>,
     pending_block_number: Option<BlockNumber>,
+    snapshot_status: Option<SnapshotStatus>,
 }
 
 impl Store {
@@ -174,6 +186,45 @@ impl StoreEngine for Store {
             }))
     }
 
+    fn add_address_transaction(
+        &self,
+        address: Address,
+        transaction_hash: H256,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<(), StoreError> {
+        self.inner()
+            .address_transactions
+            .entry(address)
+            .or_default()
+            .insert((block_number, index, block_hash, transaction_hash));
+        Ok(())
+    }
+
+    fn get_address_transactions(
+        &self,
+        address: Address,
+        before: Option<(BlockNumber, Index, BlockHash)>,
+        limit: usize,
+    ) -> Result<Vec<(BlockNumber, BlockHash, Index, H256)>, StoreError> {
+        let store = self.inner();
+        let Some(transactions) = store.address_transactions.get(&address) else {
+            return Ok(vec![]);
+        };
+        let transactions = match before {
+            Some((number, index, hash)) => {
+                transactions.range(..(number, index, hash, H256::zero()))
+            }
+            None => transactions.range(..),
+        };
+        Ok(transactions
+            .rev()
+            .take(limit)
+            .map(|&(number, index, hash, transaction_hash)| (number, hash, index, transaction_hash))
+            .collect())
+    }
+
     fn add_receipt(
         &self,
         block_hash: BlockHash,
@@ -186,21 +237,17 @@ impl StoreEngine for Store {
         Ok(())
     }
 
-    fn get_receipt(
+    fn get_receipt_by_hash(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
-        let store = self.inner();
-        if let Some(hash) = store.canonical_hashes.get(&block_number) {
-            Ok(store
-                .receipts
-                .get(hash)
-                .and_then(|entry| entry.get(&index))
-                .cloned())
-        } else {
-            Ok(None)
-        }
+        Ok(self
+            .inner()
+            .receipts
+            .get(&block_hash)
+            .and_then(|entry| entry.get(&index))
+            .cloned())
     }
 
     fn add_account_code(&self, code_hash: H256, code: Bytes) -> Result<(), StoreError> {
@@ -234,6 +281,21 @@ impl StoreEngine for Store {
         Ok(self.inner().chain_data.earliest_block_number)
     }
 
+    fn update_history_start_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        self.inner()
+            .chain_data
+            .history_start_block_number
+            .replace(block_number);
+        Ok(())
+    }
+
+    fn get_history_start_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self.inner().chain_data.history_start_block_number)
+    }
+
     fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
         self.inner()
             .chain_data
@@ -324,7 +386,11 @@ impl StoreEngine for Store {
     }
 
     fn set_canonical_block(&self, number: BlockNumber, hash: BlockHash) -> Result<(), StoreError> {
-        self.inner().canonical_hashes.insert(number, hash);
+        let mut store = self.inner();
+        if let Some(previous) = store.canonical_hashes.insert(number, hash) {
+            store.canonical_numbers.remove(&previous);
+        }
+        store.canonical_numbers.insert(hash, number);
         Ok(())
     }
 
@@ -335,8 +401,18 @@ impl StoreEngine for Store {
         Ok(self.inner().canonical_hashes.get(&block_number).cloned())
     }
 
+    fn get_canonical_block_number(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self.inner().canonical_numbers.get(&block_hash).copied())
+    }
+
     fn unset_canonical_block(&self, number: BlockNumber) -> Result<(), StoreError> {
-        self.inner().canonical_hashes.remove(&number);
+        let mut store = self.inner();
+        if let Some(previous) = store.canonical_hashes.remove(&number) {
+            store.canonical_numbers.remove(&previous);
+        }
         Ok(())
     }
 
@@ -348,6 +424,132 @@ impl StoreEngine for Store {
     fn get_payload(&self, payload_id: u64) -> Result<Option<Block>, StoreError> {
         Ok(self.inner().payloads.get(&payload_id).cloned())
     }
+
+    fn remove_payload(&self, payload_id: u64) -> Result<(), StoreError> {
+        self.inner().payloads.remove(&payload_id);
+        Ok(())
+    }
+
+    fn get_payload_ids(&self) -> Result<Vec<u64>, StoreError> {
+        Ok(self.inner().payloads.keys().copied().collect())
+    }
+
+    fn add_blob_sidecars(
+        &self,
+        block_hash: BlockHash,
+        timestamp: u64,
+        sidecars: BlobsBundle,
+    ) -> Result<(), StoreError> {
+        self.inner()
+            .blob_sidecars
+            .insert(block_hash, (timestamp, sidecars));
+        Ok(())
+    }
+
+    fn get_blob_sidecars(&self, block_hash: BlockHash) -> Result<Option<BlobsBundle>, StoreError> {
+        Ok(self
+            .inner()
+            .blob_sidecars
+            .get(&block_hash)
+            .map(|(_, sidecars)| sidecars.clone()))
+    }
+
+    fn remove_block_history(
+        &self,
+        block_hash: BlockHash,
+        _transaction_count: Index,
+    ) -> Result<(), StoreError> {
+        let mut store = self.inner();
+        store.bodies.remove(&block_hash);
+        store.receipts.remove(&block_hash);
+        Ok(())
+    }
+
+    fn remove_blob_sidecars(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        self.inner().blob_sidecars.remove(&block_hash);
+        Ok(())
+    }
+
+    fn get_blob_sidecar_blocks(&self) -> Result<Vec<(BlockHash, u64)>, StoreError> {
+        Ok(self
+            .inner()
+            .blob_sidecars
+            .iter()
+            .map(|(block_hash, (timestamp, _))| (*block_hash, *timestamp))
+            .collect())
+    }
+
+    fn write_snapshot_batch(
+        &self,
+        batch: SnapshotBatch,
+        status: &SnapshotStatus,
+    ) -> Result<(), StoreError> {
+        let mut store = self.inner();
+        for (hashed_address, account) in batch.accounts {
+            match account {
+                Some(account) => {
+                    store.snapshot_accounts.insert(hashed_address, account);
+                }
+                None => {
+                    store.snapshot_accounts.remove(&hashed_address);
+                    store
+                        .snapshot_storage
+                        .retain(|(address, _), _| *address != hashed_address);
+                }
+            }
+        }
+        for (hashed_address, hashed_key, value) in batch.storage_slots {
+            match value {
+                Some(value) => store
+                    .snapshot_storage
+                    .insert((hashed_address, hashed_key), value),
+                None => store.snapshot_storage.remove(&(hashed_address, hashed_key)),
+            };
+        }
+        store.chain_data.snapshot_status = Some(status.clone());
+        Ok(())
+    }
+
+    fn get_snapshot_status(&self) -> Result<Option<SnapshotStatus>, StoreError> {
+        Ok(self.inner().chain_data.snapshot_status.clone())
+    }
+
+    fn clear_snapshot(&self) -> Result<(), StoreError> {
+        let mut store = self.inner();
+        store.snapshot_accounts.clear();
+        store.snapshot_storage.clear();
+        store.chain_data.snapshot_status = None;
+        Ok(())
+    }
+
+    fn get_snapshot_accounts(
+        &self,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, AccountState)>, StoreError> {
+        Ok(self
+            .inner()
+            .snapshot_accounts
+            .range(start..)
+            .take(limit)
+            .map(|(hashed_address, account)| (*hashed_address, account.clone()))
+            .collect())
+    }
+
+    fn get_snapshot_storage(
+        &self,
+        hashed_address: H256,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, U256)>, StoreError> {
+        Ok(self
+            .inner()
+            .snapshot_storage
+            .range((hashed_address, start)..=(hashed_address, H256::repeat_byte(0xff)))
+            .take(limit)
+            .map(|((_, hashed_key), value)| (*hashed_key, *value))
+            .collect())
+    }
 }
 
 impl Debug for Store {
@@ -2,14 +2,16 @@ use super::api::StoreEngine;
 use super::utils::ChainDataIndex;
 use crate::error::StoreError;
 use crate::rlp::{
-    AccountCodeHashRLP, AccountCodeRLP, BlockBodyRLP, BlockHashRLP, BlockHeaderRLP, BlockRLP,
-    BlockTotalDifficultyRLP, ReceiptRLP, Rlp, TransactionHashRLP, TupleRLP,
+    AccountCodeHashRLP, AccountCodeRLP, BlobsBundleRLP, BlockBodyRLP, BlockHashRLP, BlockHeaderRLP,
+    BlockRLP, BlockTotalDifficultyRLP, ReceiptRLP, Rlp, TransactionHashRLP, TupleRLP,
 };
+use crate::snapshot::{SnapshotBatch, SnapshotStatus};
 use anyhow::Result;
 use bytes::Bytes;
-use ethereum_types::{H256, U256};
+use ethereum_types::{Address, H256, U256};
 use ethrex_core::types::{
-    Block, BlockBody, BlockHash, BlockHeader, BlockNumber, ChainConfig, Index, Receipt, Transaction,
+    AccountState, BlobsBundle, Block, BlockBody, BlockHash, BlockHeader, BlockNumber, ChainConfig,
+    Index, Receipt, Transaction,
 };
 use ethrex_rlp::decode::RLPDecode;
 use ethrex_rlp::encode::RLPEncode;
@@ -162,16 +164,32 @@ impl StoreEngine for Store {
         self.write::<Receipts>((block_hash, index).into(), receipt.into())
     }
 
-    fn get_receipt(
+    fn replace_receipt(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
+        index: Index,
+        receipt: Receipt,
+    ) -> Result<(), StoreError> {
+        // Receipts are stored in a dupsort table, so the old one has to be removed first
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.delete::<Receipts>((block_hash, index).into(), None)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.upsert::<Receipts>((block_hash, index).into(), receipt.into())
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_receipt_by_hash(
+        &self,
+        block_hash: BlockHash,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
-        if let Some(hash) = self.get_block_hash_by_block_number(block_number)? {
-            Ok(self.read::<Receipts>((hash, index).into())?.map(|b| b.to()))
-        } else {
-            Ok(None)
-        }
+        Ok(self
+            .read::<Receipts>((block_hash, index).into())?
+            .map(|b| b.to()))
     }
 
     fn add_transaction_location(
@@ -204,6 +222,58 @@ impl StoreEngine for Store {
             }))
     }
 
+    fn add_address_transaction(
+        &self,
+        address: Address,
+        transaction_hash: H256,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<(), StoreError> {
+        self.write::<AddressTransactions>(
+            (
+                address.0,
+                address_transaction_position(block_number, index, block_hash),
+            ),
+            transaction_hash.0,
+        )
+    }
+
+    fn get_address_transactions(
+        &self,
+        address: Address,
+        before: Option<(BlockNumber, Index, BlockHash)>,
+        limit: usize,
+    ) -> Result<Vec<(BlockNumber, BlockHash, Index, H256)>, StoreError> {
+        let end = (
+            address.0,
+            before.map_or([0xff; 48], |(number, index, hash)| {
+                address_transaction_position(number, index, hash)
+            }),
+        );
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let mut cursor = txn
+            .cursor::<AddressTransactions>()
+            .map_err(StoreError::LibmdbxError)?;
+        // Walks back from the first entry at or after the end, or from the last one if there is none
+        let mut entry = match cursor.seek_closest(end).map_err(StoreError::LibmdbxError)? {
+            Some(entry) => Some(entry),
+            None => cursor.last().map_err(StoreError::LibmdbxError)?,
+        };
+        let mut transactions = vec![];
+        while let Some((key, transaction_hash)) = entry {
+            if (key.0 != address.0 && key < end) || transactions.len() == limit {
+                break;
+            }
+            if key < end {
+                let (number, index, hash) = decode_address_transaction_position(key.1);
+                transactions.push((number, hash, index, H256(transaction_hash)));
+            }
+            entry = cursor.prev().map_err(StoreError::LibmdbxError)?;
+        }
+        Ok(transactions)
+    }
+
     /// Stores the chain config serialized as json
     fn set_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError> {
         self.write::<ChainData>(
@@ -242,6 +312,25 @@ impl StoreEngine for Store {
         }
     }
 
+    fn update_history_start_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        self.write::<ChainData>(
+            ChainDataIndex::HistoryStartBlockNumber,
+            block_number.encode_to_vec(),
+        )
+    }
+
+    fn get_history_start_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::HistoryStartBlockNumber)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(rlp)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
     fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
         self.write::<ChainData>(
             ChainDataIndex::FinalizedBlockNumber,
@@ -339,7 +428,22 @@ impl StoreEngine for Store {
     }
 
     fn set_canonical_block(&self, number: BlockNumber, hash: BlockHash) -> Result<(), StoreError> {
-        self.write::<CanonicalBlockHashes>(number, hash.into())
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        if let Some(previous) = txn
+            .get::<CanonicalBlockHashes>(number)
+            .map_err(StoreError::LibmdbxError)?
+        {
+            txn.delete::<CanonicalBlockNumbers>(previous, None)
+                .map_err(StoreError::LibmdbxError)?;
+        }
+        txn.upsert::<CanonicalBlockHashes>(number, hash.into())
+            .map_err(StoreError::LibmdbxError)?;
+        txn.upsert::<CanonicalBlockNumbers>(hash.into(), number)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
     }
 
     fn get_canonical_block_hash(
@@ -350,6 +454,13 @@ impl StoreEngine for Store {
             .map(|o| o.map(|hash_rlp| hash_rlp.to()))
     }
 
+    fn get_canonical_block_number(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        self.read::<CanonicalBlockNumbers>(block_hash.into())
+    }
+
     fn add_payload(&self, payload_id: u64, block: Block) -> Result<(), StoreError> {
         self.write::<Payloads>(payload_id, block.into())
     }
@@ -358,6 +469,91 @@ impl StoreEngine for Store {
         Ok(self.read::<Payloads>(payload_id)?.map(|b| b.to()))
     }
 
+    fn remove_payload(&self, payload_id: u64) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.delete::<Payloads>(payload_id, None)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_payload_ids(&self) -> Result<Vec<u64>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let cursor = txn.cursor::<Payloads>().map_err(StoreError::LibmdbxError)?;
+        cursor
+            .walk(None)
+            .map(|res| res.map(|(payload_id, _)| payload_id))
+            .collect::<Result<_, _>>()
+            .map_err(StoreError::LibmdbxError)
+    }
+
+    fn add_blob_sidecars(
+        &self,
+        block_hash: BlockHash,
+        timestamp: u64,
+        sidecars: BlobsBundle,
+    ) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.upsert::<BlobSidecars>(block_hash.into(), sidecars.into())
+            .map_err(StoreError::LibmdbxError)?;
+        txn.upsert::<BlobSidecarTimestamps>(block_hash.into(), timestamp)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_blob_sidecars(&self, block_hash: BlockHash) -> Result<Option<BlobsBundle>, StoreError> {
+        Ok(self
+            .read::<BlobSidecars>(block_hash.into())?
+            .map(|sidecars| sidecars.to()))
+    }
+
+    fn remove_block_history(
+        &self,
+        block_hash: BlockHash,
+        transaction_count: Index,
+    ) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.delete::<Bodies>(block_hash.into(), None)
+            .map_err(StoreError::LibmdbxError)?;
+        for index in 0..transaction_count {
+            txn.delete::<Receipts>((block_hash, index).into(), None)
+                .map_err(StoreError::LibmdbxError)?;
+        }
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn remove_blob_sidecars(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.delete::<BlobSidecars>(block_hash.into(), None)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.delete::<BlobSidecarTimestamps>(block_hash.into(), None)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_blob_sidecar_blocks(&self) -> Result<Vec<(BlockHash, u64)>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let cursor = txn
+            .cursor::<BlobSidecarTimestamps>()
+            .map_err(StoreError::LibmdbxError)?;
+        cursor
+            .walk(None)
+            .map(|res| res.map(|(block_hash, timestamp)| (block_hash.to(), timestamp)))
+            .collect::<Result<_, _>>()
+            .map_err(StoreError::LibmdbxError)
+    }
+
     fn get_transaction_by_hash(
         &self,
         transaction_hash: H256,
@@ -401,12 +597,20 @@ impl StoreEngine for Store {
     }
 
     fn unset_canonical_block(&self, number: BlockNumber) -> Result<(), StoreError> {
-        self.db
+        let txn = self
+            .db
             .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        if let Some(previous) = txn
+            .get::<CanonicalBlockHashes>(number)
             .map_err(StoreError::LibmdbxError)?
-            .delete::<CanonicalBlockHashes>(number, None)
-            .map(|_| ())
-            .map_err(StoreError::LibmdbxError)
+        {
+            txn.delete::<CanonicalBlockNumbers>(previous, None)
+                .map_err(StoreError::LibmdbxError)?;
+        }
+        txn.delete::<CanonicalBlockHashes>(number, None)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
     }
 
     fn add_pending_block(&self, block: Block) -> std::result::Result<(), StoreError> {
@@ -421,6 +625,121 @@ impl StoreEngine for Store {
             .read::<PendingBlocks>(block_hash.into())?
             .map(|b| b.to()))
     }
+
+    fn write_snapshot_batch(
+        &self,
+        batch: SnapshotBatch,
+        status: &SnapshotStatus,
+    ) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        for (hashed_address, account) in batch.accounts {
+            match account {
+                Some(account) => txn
+                    .upsert::<SnapshotAccounts>(hashed_address.0, account.encode_to_vec())
+                    .map_err(StoreError::LibmdbxError)?,
+                None => {
+                    txn.delete::<SnapshotAccounts>(hashed_address.0, None)
+                        .map_err(StoreError::LibmdbxError)?;
+                    let slots = txn
+                        .cursor::<SnapshotStorage>()
+                        .map_err(StoreError::LibmdbxError)?
+                        .walk(Some((hashed_address.0, [0; 32])))
+                        .map_while(|res| res.ok())
+                        .take_while(|((address, _), _)| *address == hashed_address.0)
+                        .map(|(key, _)| key)
+                        .collect::<Vec<_>>();
+                    for key in slots {
+                        txn.delete::<SnapshotStorage>(key, None)
+                            .map_err(StoreError::LibmdbxError)?;
+                    }
+                }
+            }
+        }
+        for (hashed_address, hashed_key, value) in batch.storage_slots {
+            let key = (hashed_address.0, hashed_key.0);
+            match value {
+                Some(value) => txn
+                    .upsert::<SnapshotStorage>(key, value.into())
+                    .map_err(StoreError::LibmdbxError)?,
+                None => {
+                    txn.delete::<SnapshotStorage>(key, None)
+                        .map_err(StoreError::LibmdbxError)?;
+                }
+            }
+        }
+        txn.upsert::<ChainData>(
+            ChainDataIndex::SnapshotStatus,
+            serde_json::to_vec(status).map_err(|_| StoreError::DecodeError)?,
+        )
+        .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_snapshot_status(&self) -> Result<Option<SnapshotStatus>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::SnapshotStatus)? {
+            None => Ok(None),
+            Some(json) => serde_json::from_slice(&json)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn clear_snapshot(&self) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.clear_table::<SnapshotAccounts>()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.clear_table::<SnapshotStorage>()
+            .map_err(StoreError::LibmdbxError)?;
+        txn.delete::<ChainData>(ChainDataIndex::SnapshotStatus, None)
+            .map_err(StoreError::LibmdbxError)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_snapshot_accounts(
+        &self,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, AccountState)>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        txn.cursor::<SnapshotAccounts>()
+            .map_err(StoreError::LibmdbxError)?
+            .walk(Some(start.0))
+            .take(limit)
+            .map(|res| {
+                let (hashed_address, account) = res.map_err(StoreError::LibmdbxError)?;
+                Ok((H256(hashed_address), AccountState::decode(&account)?))
+            })
+            .collect()
+    }
+
+    fn get_snapshot_storage(
+        &self,
+        hashed_address: H256,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, U256)>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let mut slots = vec![];
+        for res in txn
+            .cursor::<SnapshotStorage>()
+            .map_err(StoreError::LibmdbxError)?
+            .walk(Some((hashed_address.0, start.0)))
+            .take(limit)
+        {
+            let ((address, hashed_key), value) = res.map_err(StoreError::LibmdbxError)?;
+            if address != hashed_address.0 {
+                break;
+            }
+            slots.push((H256(hashed_key), value.into()));
+        }
+        Ok(slots)
+    }
 }
 
 impl Debug for Store {
@@ -436,6 +755,11 @@ table!(
     ( CanonicalBlockHashes ) BlockNumber => BlockHashRLP
 );
 
+table!(
+    /// Canonical block hash to number table, the reverse of [CanonicalBlockHashes].
+    ( CanonicalBlockNumbers ) BlockHashRLP => BlockNumber
+);
+
 table!(
     /// Block hash to number table.
     ( BlockNumbers ) BlockHashRLP => BlockNumber
@@ -476,6 +800,12 @@ dupsort!(
     ( TransactionLocations ) TransactionHashRLP => Rlp<(BlockNumber, BlockHash, Index)>
 );
 
+table!(
+    /// Transactions sent or received by each address, only filled if the address index is enabled.
+    /// Keyed by address and position, see `address_transaction_position`, so they are read in order
+    ( AddressTransactions ) ([u8; 20], [u8; 48]) => [u8; 32]
+);
+
 table!(
     /// Stores chain data, each value is unique and stored as its rlp encoding
     /// See [ChainDataIndex] for available chain values
@@ -501,6 +831,56 @@ table!(
     ( PendingBlocks ) BlockHashRLP => BlockRLP
 );
 
+// Blob sidecars
+
+table!(
+    /// Blob sidecars of the blob transactions of each block
+    ( BlobSidecars ) BlockHashRLP => BlobsBundleRLP
+);
+
+table!(
+    /// Timestamp of each block with stored blob sidecars, kept apart so they can be pruned
+    /// without reading the blobs
+    ( BlobSidecarTimestamps ) BlockHashRLP => u64
+);
+
+// Snapshot
+
+table!(
+    /// Accounts of the snapshot by hashed address, stored as the rlp encoding of their state
+    ( SnapshotAccounts ) [u8; 32] => Vec<u8>
+);
+
+table!(
+    /// Storage slots of the snapshot by hashed address and hashed key
+    ( SnapshotStorage ) ([u8; 32], [u8; 32]) => AccountStorageValueBytes
+);
+
+/// Encodes the location of an indexed transaction so the entries of an address are sorted by
+/// block number, index and block hash
+fn address_transaction_position(
+    block_number: BlockNumber,
+    index: Index,
+    block_hash: BlockHash,
+) -> [u8; 48] {
+    let mut position = [0; 48];
+    position[..8].copy_from_slice(&block_number.to_be_bytes());
+    position[8..16].copy_from_slice(&index.to_be_bytes());
+    position[16..].copy_from_slice(block_hash.as_bytes());
+    position
+}
+
+fn decode_address_transaction_position(position: [u8; 48]) -> (BlockNumber, Index, BlockHash) {
+    let (mut block_number, mut index) = ([0; 8], [0; 8]);
+    block_number.copy_from_slice(&position[..8]);
+    index.copy_from_slice(&position[8..16]);
+    (
+        BlockNumber::from_be_bytes(block_number),
+        Index::from_be_bytes(index),
+        BlockHash::from_slice(&position[16..]),
+    )
+}
+
 // Storage values are stored as bytes instead of using their rlp encoding
 // As they are stored in a dupsort table, they need to have a fixed size, and encoding them doesn't preserve their size
 pub struct AccountStorageKeyBytes(pub [u8; 32]);
@@ -580,12 +960,18 @@ pub fn init_db(path: Option<impl AsRef<Path>>) -> Database {
         table_info!(AccountCodes),
         table_info!(Receipts),
         table_info!(TransactionLocations),
+        table_info!(AddressTransactions),
         table_info!(ChainData),
         table_info!(StateTrieNodes),
         table_info!(StorageTriesNodes),
         table_info!(CanonicalBlockHashes),
+        table_info!(CanonicalBlockNumbers),
         table_info!(Payloads),
         table_info!(PendingBlocks),
+        table_info!(BlobSidecars),
+        table_info!(BlobSidecarTimestamps),
+        table_info!(SnapshotAccounts),
+        table_info!(SnapshotStorage),
     ]
     .into_iter()
     .collect();
@@ -1,10 +1,10 @@
-use std::{borrow::Borrow, panic::RefUnwindSafe, sync::Arc};
+use std::{borrow::Borrow, panic::RefUnwindSafe, path::Path, sync::Arc};
 
-use ethrex_core::types::BlockBody;
+use ethrex_core::types::{AccountState, BlockBody};
 use ethrex_core::U256;
 use ethrex_core::{
-    types::{Block, BlockHash, BlockHeader, BlockNumber, ChainConfig, Index, Receipt},
-    H256,
+    types::{BlobsBundle, Block, BlockHash, BlockHeader, BlockNumber, ChainConfig, Index, Receipt},
+    Address, H256,
 };
 use ethrex_rlp::decode::RLPDecode;
 use ethrex_rlp::encode::RLPEncode;
@@ -12,9 +12,12 @@ use ethrex_trie::{
     db::{redb::RedBTrie, redb_multitable::RedBMultiTableTrieDB},
     Trie,
 };
-use redb::{AccessGuard, Database, Key, MultimapTableDefinition, TableDefinition, TypeName, Value};
+use redb::{
+    AccessGuard, Database, Key, MultimapTableDefinition, ReadableTable, TableDefinition, TypeName,
+    Value,
+};
 
-use crate::rlp::{BlockRLP, BlockTotalDifficultyRLP, Rlp, TransactionHashRLP};
+use crate::rlp::{BlobsBundleRLP, BlockRLP, BlockTotalDifficultyRLP, Rlp, TransactionHashRLP};
 use crate::{
     error::StoreError,
     rlp::{
@@ -24,6 +27,7 @@ use crate::{
 };
 
 use super::{api::StoreEngine, utils::ChainDataIndex};
+use crate::snapshot::{SnapshotBatch, SnapshotStatus};
 
 const STATE_TRIE_NODES_TABLE: TableDefinition<&[u8], &[u8]> =
     TableDefinition::new("StateTrieNodes");
@@ -41,6 +45,8 @@ const RECEIPTS_TABLE: TableDefinition<TupleRLP<BlockHash, Index>, ReceiptRLP> =
     TableDefinition::new("Receipts");
 const CANONICAL_BLOCK_HASHES_TABLE: TableDefinition<BlockNumber, BlockHashRLP> =
     TableDefinition::new("CanonicalBlockHashes");
+const CANONICAL_BLOCK_NUMBERS_TABLE: TableDefinition<BlockHashRLP, BlockNumber> =
+    TableDefinition::new("CanonicalBlockNumbers");
 pub const STORAGE_TRIE_NODES_TABLE: MultimapTableDefinition<([u8; 32], [u8; 33]), &[u8]> =
     MultimapTableDefinition::new("StorageTrieNodes");
 const CHAIN_DATA_TABLE: TableDefinition<ChainDataIndex, Vec<u8>> =
@@ -48,10 +54,21 @@ const CHAIN_DATA_TABLE: TableDefinition<ChainDataIndex, Vec<u8>> =
 const PAYLOADS_TABLE: TableDefinition<BlockNumber, BlockRLP> = TableDefinition::new("Payloads");
 const PENDING_BLOCKS_TABLE: TableDefinition<BlockHashRLP, BlockRLP> =
     TableDefinition::new("PendingBlocks");
+const BLOB_SIDECARS_TABLE: TableDefinition<BlockHashRLP, BlobsBundleRLP> =
+    TableDefinition::new("BlobSidecars");
+const BLOB_SIDECAR_TIMESTAMPS_TABLE: TableDefinition<BlockHashRLP, u64> =
+    TableDefinition::new("BlobSidecarTimestamps");
 const TRANSACTION_LOCATIONS_TABLE: MultimapTableDefinition<
     TransactionHashRLP,
     Rlp<(BlockNumber, BlockHash, Index)>,
 > = MultimapTableDefinition::new("TransactionLocations");
+/// Transaction hashes by address, block number, index and block hash, so they are read in order
+const ADDRESS_TRANSACTIONS_TABLE: TableDefinition<([u8; 20], u64, u64, [u8; 32]), [u8; 32]> =
+    TableDefinition::new("AddressTransactions");
+const SNAPSHOT_ACCOUNTS_TABLE: TableDefinition<[u8; 32], &[u8]> =
+    TableDefinition::new("SnapshotAccounts");
+const SNAPSHOT_STORAGE_TABLE: TableDefinition<([u8; 32], [u8; 32]), [u8; 32]> =
+    TableDefinition::new("SnapshotStorage");
 
 #[derive(Debug)]
 pub struct RedBStore {
@@ -60,9 +77,9 @@ pub struct RedBStore {
 
 impl RefUnwindSafe for RedBStore {}
 impl RedBStore {
-    pub fn new() -> Result<Self, StoreError> {
+    pub fn new(path: &str) -> Result<Self, StoreError> {
         Ok(Self {
-            db: Arc::new(init_db()?),
+            db: Arc::new(init_db(path)?),
         })
     }
 
@@ -313,6 +330,45 @@ impl StoreEngine for RedBStore {
             }))
     }
 
+    fn add_address_transaction(
+        &self,
+        address: Address,
+        transaction_hash: H256,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<(), StoreError> {
+        self.write(
+            ADDRESS_TRANSACTIONS_TABLE,
+            (address.0, block_number, index, block_hash.0),
+            transaction_hash.0,
+        )
+    }
+
+    fn get_address_transactions(
+        &self,
+        address: Address,
+        before: Option<(BlockNumber, Index, BlockHash)>,
+        limit: usize,
+    ) -> Result<Vec<(BlockNumber, BlockHash, Index, H256)>, StoreError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(ADDRESS_TRANSACTIONS_TABLE)?;
+        let start = (address.0, 0, 0, [0; 32]);
+        let range = match before {
+            Some((number, index, hash)) => {
+                table.range(start..(address.0, number, index, hash.0))?
+            }
+            None => table.range(start..=(address.0, u64::MAX, u64::MAX, [0xff; 32]))?,
+        };
+        let mut transactions = vec![];
+        for entry in range.rev().take(limit) {
+            let (key, transaction_hash) = entry?;
+            let (_, number, index, hash) = key.value();
+            transactions.push((number, H256(hash), index, H256(transaction_hash.value())));
+        }
+        Ok(transactions)
+    }
+
     fn add_receipt(
         &self,
         block_hash: BlockHash,
@@ -326,21 +382,17 @@ impl StoreEngine for RedBStore {
         )
     }
 
-    fn get_receipt(
+    fn get_receipt_by_hash(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
-        if let Some(hash) = self.get_block_hash_by_block_number(block_number)? {
-            Ok(self
-                .read(
-                    RECEIPTS_TABLE,
-                    <(H256, u64) as Into<TupleRLP<BlockHash, Index>>>::into((hash, index)),
-                )?
-                .map(|b| b.value().to()))
-        } else {
-            Ok(None)
-        }
+        Ok(self
+            .read(
+                RECEIPTS_TABLE,
+                <(H256, u64) as Into<TupleRLP<BlockHash, Index>>>::into((block_hash, index)),
+            )?
+            .map(|b| b.value().to()))
     }
 
     fn add_account_code(
@@ -375,6 +427,18 @@ impl StoreEngine for RedBStore {
             .map(|o| o.map(|hash_rlp| hash_rlp.value().to()))
     }
 
+    fn get_canonical_block_number(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self
+            .read(
+                CANONICAL_BLOCK_NUMBERS_TABLE,
+                <H256 as Into<BlockHashRLP>>::into(block_hash),
+            )?
+            .map(|number| number.value()))
+    }
+
     fn set_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError> {
         self.write(
             CHAIN_DATA_TABLE,
@@ -414,6 +478,26 @@ impl StoreEngine for RedBStore {
         }
     }
 
+    fn update_history_start_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        self.write(
+            CHAIN_DATA_TABLE,
+            ChainDataIndex::HistoryStartBlockNumber,
+            block_number.encode_to_vec(),
+        )
+    }
+
+    fn get_history_start_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        match self.read(CHAIN_DATA_TABLE, ChainDataIndex::HistoryStartBlockNumber)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(&rlp.value())
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
     fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
         self.write(
             CHAIN_DATA_TABLE,
@@ -517,15 +601,33 @@ impl StoreEngine for RedBStore {
     }
 
     fn set_canonical_block(&self, number: BlockNumber, hash: BlockHash) -> Result<(), StoreError> {
-        self.write(
-            CANONICAL_BLOCK_HASHES_TABLE,
-            number,
-            <H256 as Into<BlockHashRLP>>::into(hash),
-        )
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut hashes = write_txn.open_table(CANONICAL_BLOCK_HASHES_TABLE)?;
+            let mut numbers = write_txn.open_table(CANONICAL_BLOCK_NUMBERS_TABLE)?;
+            let key = <H256 as Into<BlockHashRLP>>::into(hash);
+            if let Some(previous) = hashes.insert(number, &key)? {
+                numbers.remove(previous.value())?;
+            }
+            numbers.insert(key, number)?;
+        }
+        write_txn.commit()?;
+        Ok(())
     }
 
     fn unset_canonical_block(&self, number: BlockNumber) -> Result<(), StoreError> {
-        self.delete(CANONICAL_BLOCK_HASHES_TABLE, number)
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut hashes = write_txn.open_table(CANONICAL_BLOCK_HASHES_TABLE)?;
+            let previous = hashes.remove(number)?.map(|previous| previous.value());
+            if let Some(previous) = previous {
+                write_txn
+                    .open_table(CANONICAL_BLOCK_NUMBERS_TABLE)?
+                    .remove(previous)?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
     }
 
     fn add_payload(&self, payload_id: u64, block: Block) -> Result<(), StoreError> {
@@ -541,6 +643,201 @@ impl StoreEngine for RedBStore {
             .read(PAYLOADS_TABLE, payload_id)?
             .map(|b| b.value().to()))
     }
+
+    fn remove_payload(&self, payload_id: u64) -> Result<(), StoreError> {
+        self.delete(PAYLOADS_TABLE, payload_id)
+    }
+
+    fn get_payload_ids(&self) -> Result<Vec<u64>, StoreError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(PAYLOADS_TABLE)?;
+        let ids = table
+            .iter()?
+            .map(|entry| entry.map(|(payload_id, _)| payload_id.value()))
+            .collect::<Result<_, _>>()?;
+        Ok(ids)
+    }
+
+    fn add_blob_sidecars(
+        &self,
+        block_hash: BlockHash,
+        timestamp: u64,
+        sidecars: BlobsBundle,
+    ) -> Result<(), StoreError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let key = <H256 as Into<BlockHashRLP>>::into(block_hash);
+            write_txn
+                .open_table(BLOB_SIDECARS_TABLE)?
+                .insert(&key, <BlobsBundle as Into<BlobsBundleRLP>>::into(sidecars))?;
+            write_txn
+                .open_table(BLOB_SIDECAR_TIMESTAMPS_TABLE)?
+                .insert(key, timestamp)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn get_blob_sidecars(&self, block_hash: BlockHash) -> Result<Option<BlobsBundle>, StoreError> {
+        Ok(self
+            .read(
+                BLOB_SIDECARS_TABLE,
+                <H256 as Into<BlockHashRLP>>::into(block_hash),
+            )?
+            .map(|sidecars| sidecars.value().to()))
+    }
+
+    fn remove_block_history(
+        &self,
+        block_hash: BlockHash,
+        transaction_count: Index,
+    ) -> Result<(), StoreError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            write_txn
+                .open_table(BLOCK_BODIES_TABLE)?
+                .remove(<H256 as Into<BlockHashRLP>>::into(block_hash))?;
+            let mut receipts = write_txn.open_table(RECEIPTS_TABLE)?;
+            for index in 0..transaction_count {
+                receipts.remove(<(H256, u64) as Into<TupleRLP<BlockHash, Index>>>::into((
+                    block_hash, index,
+                )))?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn remove_blob_sidecars(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let key = <H256 as Into<BlockHashRLP>>::into(block_hash);
+            write_txn.open_table(BLOB_SIDECARS_TABLE)?.remove(&key)?;
+            write_txn
+                .open_table(BLOB_SIDECAR_TIMESTAMPS_TABLE)?
+                .remove(key)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn get_blob_sidecar_blocks(&self) -> Result<Vec<(BlockHash, u64)>, StoreError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(BLOB_SIDECAR_TIMESTAMPS_TABLE)?;
+        let blocks = table
+            .iter()?
+            .map(|entry| {
+                entry.map(|(block_hash, timestamp)| (block_hash.value().to(), timestamp.value()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(blocks)
+    }
+
+    fn write_snapshot_batch(
+        &self,
+        batch: SnapshotBatch,
+        status: &SnapshotStatus,
+    ) -> Result<(), StoreError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut accounts = write_txn.open_table(SNAPSHOT_ACCOUNTS_TABLE)?;
+            let mut storage = write_txn.open_table(SNAPSHOT_STORAGE_TABLE)?;
+            for (hashed_address, account) in batch.accounts {
+                match account {
+                    Some(account) => {
+                        accounts.insert(hashed_address.0, account.encode_to_vec().as_slice())?;
+                    }
+                    None => {
+                        accounts.remove(hashed_address.0)?;
+                        storage.retain_in(
+                            (hashed_address.0, [0; 32])..=(hashed_address.0, [0xff; 32]),
+                            |_, _| false,
+                        )?;
+                    }
+                }
+            }
+            for (hashed_address, hashed_key, value) in batch.storage_slots {
+                let key = (hashed_address.0, hashed_key.0);
+                match value {
+                    Some(value) => {
+                        let mut value_bytes = [0; 32];
+                        value.to_big_endian(&mut value_bytes);
+                        storage.insert(key, value_bytes)?;
+                    }
+                    None => {
+                        storage.remove(key)?;
+                    }
+                }
+            }
+            write_txn.open_table(CHAIN_DATA_TABLE)?.insert(
+                ChainDataIndex::SnapshotStatus,
+                serde_json::to_vec(status).map_err(|_| StoreError::DecodeError)?,
+            )?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn get_snapshot_status(&self) -> Result<Option<SnapshotStatus>, StoreError> {
+        match self.read(CHAIN_DATA_TABLE, ChainDataIndex::SnapshotStatus)? {
+            None => Ok(None),
+            Some(json) => serde_json::from_slice(&json.value())
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn clear_snapshot(&self) -> Result<(), StoreError> {
+        let write_txn = self.db.begin_write()?;
+        write_txn
+            .open_table(SNAPSHOT_ACCOUNTS_TABLE)?
+            .retain(|_, _| false)?;
+        write_txn
+            .open_table(SNAPSHOT_STORAGE_TABLE)?
+            .retain(|_, _| false)?;
+        write_txn
+            .open_table(CHAIN_DATA_TABLE)?
+            .remove(ChainDataIndex::SnapshotStatus)?;
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn get_snapshot_accounts(
+        &self,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, AccountState)>, StoreError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(SNAPSHOT_ACCOUNTS_TABLE)?;
+        let mut accounts = vec![];
+        for entry in table.range(start.0..)?.take(limit) {
+            let (hashed_address, account) = entry?;
+            accounts.push((
+                H256(hashed_address.value()),
+                AccountState::decode(account.value())?,
+            ));
+        }
+        Ok(accounts)
+    }
+
+    fn get_snapshot_storage(
+        &self,
+        hashed_address: H256,
+        start: H256,
+        limit: usize,
+    ) -> Result<Vec<(H256, U256)>, StoreError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(SNAPSHOT_STORAGE_TABLE)?;
+        let mut slots = vec![];
+        for entry in table
+            .range((hashed_address.0, start.0)..=(hashed_address.0, [0xff; 32]))?
+            .take(limit)
+        {
+            let (key, value) = entry?;
+            slots.push((H256(key.value().1), U256::from_big_endian(&value.value())));
+        }
+        Ok(slots)
+    }
 }
 
 impl redb::Value for ChainDataIndex {
@@ -582,21 +879,31 @@ impl redb::Key for ChainDataIndex {
     }
 }
 
-pub fn init_db() -> Result<Database, StoreError> {
-    let db = Database::create("ethrex.redb")?;
+/// Opens or creates the database inside the given directory, so stores using different
+/// directories don't share any data
+pub fn init_db(path: impl AsRef<Path>) -> Result<Database, StoreError> {
+    std::fs::create_dir_all(&path)
+        .map_err(|error| StoreError::Custom(format!("Failed to create database dir: {error}")))?;
+    let db = Database::create(path.as_ref().join("ethrex.redb"))?;
 
     let table_creation_txn = db.begin_write()?;
     table_creation_txn.open_table(STATE_TRIE_NODES_TABLE)?;
     table_creation_txn.open_table(BLOCK_NUMBERS_TABLE)?;
     table_creation_txn.open_table(BLOCK_TOTAL_DIFFICULTIES_TABLE)?;
     table_creation_txn.open_table(CANONICAL_BLOCK_HASHES_TABLE)?;
+    table_creation_txn.open_table(CANONICAL_BLOCK_NUMBERS_TABLE)?;
     table_creation_txn.open_table(RECEIPTS_TABLE)?;
     table_creation_txn.open_multimap_table(STORAGE_TRIE_NODES_TABLE)?;
     table_creation_txn.open_table(CHAIN_DATA_TABLE)?;
     table_creation_txn.open_table(BLOCK_BODIES_TABLE)?;
     table_creation_txn.open_table(PAYLOADS_TABLE)?;
     table_creation_txn.open_table(PENDING_BLOCKS_TABLE)?;
+    table_creation_txn.open_table(BLOB_SIDECARS_TABLE)?;
+    table_creation_txn.open_table(BLOB_SIDECAR_TIMESTAMPS_TABLE)?;
     table_creation_txn.open_multimap_table(TRANSACTION_LOCATIONS_TABLE)?;
+    table_creation_txn.open_table(ADDRESS_TRANSACTIONS_TABLE)?;
+    table_creation_txn.open_table(SNAPSHOT_ACCOUNTS_TABLE)?;
+    table_creation_txn.open_table(SNAPSHOT_STORAGE_TABLE)?;
     table_creation_txn.commit()?;
 
     Ok(db)
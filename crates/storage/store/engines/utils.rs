@@ -10,6 +10,8 @@ pub enum ChainDataIndex {
     PendingBlockNumber = 5,
     // TODO (#307): Remove TotalDifficulty.
     LatestTotalDifficulty = 6,
+    HistoryStartBlockNumber = 7,
+    SnapshotStatus = 8,
 }
 
 impl From<u8> for ChainDataIndex {
@@ -30,6 +32,10 @@ impl From<u8> for ChainDataIndex {
             x if x == ChainDataIndex::LatestTotalDifficulty as u8 => {
                 ChainDataIndex::LatestTotalDifficulty
             }
+            x if x == ChainDataIndex::HistoryStartBlockNumber as u8 => {
+                ChainDataIndex::HistoryStartBlockNumber
+            }
+            x if x == ChainDataIndex::SnapshotStatus as u8 => ChainDataIndex::SnapshotStatus,
             _ => panic!("Invalid value when casting to ChainDataIndex: {}", value),
         }
     }
@@ -0,0 +1,548 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use ethrex_core::types::{
+    compute_receipts_root, compute_transactions_root, BlockBody, BlockHash, BlockHeader,
+    BlockNumber, Receipt,
+};
+use ethrex_rlp::decode::{decode_rlp_item, get_item_with_prefix, RLPDecode};
+use sha2::{Digest as _, Sha256};
+use sha3::Keccak256;
+use tracing::info;
+
+use crate::{error::StoreError, Store};
+
+/// Amount of blocks stored in each era1 file
+pub const BLOCKS_PER_ERA: u64 = 8192;
+
+// Types of the e2store records of an era1 file
+const VERSION: u16 = 0x3265;
+const COMPRESSED_HEADER: u16 = 0x03;
+const COMPRESSED_BODY: u16 = 0x04;
+const COMPRESSED_RECEIPTS: u16 = 0x05;
+const TOTAL_DIFFICULTY: u16 = 0x06;
+const ACCUMULATOR: u16 = 0x07;
+const BLOCK_INDEX: u16 = 0x3266;
+
+/// Size of the header every e2store record starts with: type, length and reserved bytes
+const RECORD_HEADER_SIZE: u64 = 8;
+/// Depth of the merkle tree of the accumulator, which holds up to [BLOCKS_PER_ERA] records
+const ACCUMULATOR_DEPTH: usize = 13;
+
+/// Block read from an era1 file
+#[derive(Debug, Clone)]
+pub struct EraBlock {
+    pub header: BlockHeader,
+    pub body: BlockBody,
+    pub receipts: Vec<Receipt>,
+}
+
+/// Directory of era1 files the pruned pre-merge history is served from.
+/// The files are only read when one of their blocks is requested, and their accumulator is
+/// checked against their headers the first time.
+#[derive(Debug)]
+pub struct EraArchive {
+    directory: PathBuf,
+    /// Eras whose file matched its accumulator
+    verified_eras: Mutex<HashSet<u64>>,
+    /// Last read block, as its receipts are usually requested one by one
+    last_block: Mutex<Option<(BlockNumber, Arc<EraBlock>)>>,
+}
+
+impl EraArchive {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            verified_eras: Default::default(),
+            last_block: Default::default(),
+        }
+    }
+
+    /// Returns whether the archive has the file of the era of the given block
+    pub fn has_block(&self, block_number: BlockNumber) -> Result<bool, StoreError> {
+        Ok(self.era_file(block_number / BLOCKS_PER_ERA)?.is_some())
+    }
+
+    /// Returns the block with the given number, if the archive has the file of its era
+    pub fn get_block(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<Arc<EraBlock>>, StoreError> {
+        if let Some((number, block)) = lock(&self.last_block).as_ref() {
+            if *number == block_number {
+                return Ok(Some(block.clone()));
+            }
+        }
+        let era = block_number / BLOCKS_PER_ERA;
+        let Some(path) = self.era_file(era)? else {
+            return Ok(None);
+        };
+        let mut file = EraFile::open(&path)?;
+        if !lock(&self.verified_eras).contains(&era) {
+            file.verify_accumulator()?;
+            info!("Verified the accumulator of era1 file {}", path.display());
+            lock(&self.verified_eras).insert(era);
+        }
+        let block = Arc::new(file.read_block(block_number)?);
+        *lock(&self.last_block) = Some((block_number, block.clone()));
+        Ok(Some(block))
+    }
+
+    /// Returns the path of the file of the given era, named `<network>-<era>-<short root>.era1`
+    fn era_file(&self, era: u64) -> Result<Option<PathBuf>, StoreError> {
+        for entry in std::fs::read_dir(&self.directory).map_err(era_error)? {
+            let path = entry.map_err(era_error)?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(stem) = name.strip_suffix(".era1") else {
+                continue;
+            };
+            let mut parts = stem.rsplit('-').skip(1);
+            if parts.next().and_then(|part| part.parse().ok()) == Some(era) {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Era1 file opened to read its blocks, with its block index already read
+struct EraFile {
+    file: File,
+    path: PathBuf,
+    start: BlockNumber,
+    /// Position of the first record of each block
+    offsets: Vec<u64>,
+    /// Position of the block index, the last record of the file
+    index_position: u64,
+}
+
+impl EraFile {
+    fn open(path: &Path) -> Result<Self, StoreError> {
+        let mut file = File::open(path).map_err(era_error)?;
+        let length = file.metadata().map_err(era_error)?.len();
+        let invalid = |reason: &str| {
+            StoreError::Era(format!("invalid era1 file {}: {reason}", path.display()))
+        };
+        // The block index ends with the amount of blocks of the file
+        let count_position = length.checked_sub(8).ok_or_else(|| invalid("too short"))?;
+        file.seek(SeekFrom::Start(count_position))
+            .map_err(era_error)?;
+        let mut count = [0; 8];
+        file.read_exact(&mut count).map_err(era_error)?;
+        let count = u64::from_le_bytes(count);
+        if count > BLOCKS_PER_ERA {
+            return Err(invalid("too many blocks"));
+        }
+        let index_position = length
+            .checked_sub(RECORD_HEADER_SIZE + 16 + 8 * count)
+            .ok_or_else(|| invalid("too short"))?;
+        file.seek(SeekFrom::Start(index_position))
+            .map_err(era_error)?;
+        let (record_type, index) = read_record(&mut file)?;
+        if record_type != BLOCK_INDEX || index.len() as u64 != 16 + 8 * count {
+            return Err(invalid("missing block index"));
+        }
+        let start = u64::from_le_bytes(index[..8].try_into().unwrap_or_default());
+        // Offsets are relative to the position of the block index
+        let offsets = index[8..index.len() - 8]
+            .chunks_exact(8)
+            .map(|offset| {
+                let offset = i64::from_le_bytes(offset.try_into().unwrap_or_default());
+                index_position
+                    .checked_add_signed(offset)
+                    .filter(|position| *position < index_position)
+                    .ok_or_else(|| invalid("block offset out of bounds"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            start,
+            offsets,
+            index_position,
+        })
+    }
+
+    fn invalid(&self, reason: &str) -> StoreError {
+        StoreError::Era(format!(
+            "invalid era1 file {}: {reason}",
+            self.path.display()
+        ))
+    }
+
+    /// Reads the block, checking its body and receipts match the roots of its header
+    fn read_block(&mut self, block_number: BlockNumber) -> Result<EraBlock, StoreError> {
+        let position = block_number
+            .checked_sub(self.start)
+            .and_then(|index| self.offsets.get(index as usize))
+            .ok_or_else(|| self.invalid(&format!("block {block_number} is not in the file")))?;
+        self.file
+            .seek(SeekFrom::Start(*position))
+            .map_err(era_error)?;
+        let header = BlockHeader::decode(&self.read_compressed(COMPRESSED_HEADER)?)?;
+        let body = BlockBody::decode(&self.read_compressed(COMPRESSED_BODY)?)?;
+        let receipts = decode_receipts(&self.read_compressed(COMPRESSED_RECEIPTS)?)?;
+        if header.number != block_number {
+            return Err(self.invalid(&format!("expected block {block_number}")));
+        }
+        if compute_transactions_root(&body.transactions) != header.transactions_root {
+            return Err(self.invalid(&format!("body of block {block_number} doesn't match")));
+        }
+        if compute_receipts_root(&receipts) != header.receipts_root {
+            return Err(self.invalid(&format!("receipts of block {block_number} don't match")));
+        }
+        Ok(EraBlock {
+            header,
+            body,
+            receipts,
+        })
+    }
+
+    /// Checks the accumulator of the file is the one of its headers and total difficulties
+    fn verify_accumulator(&mut self) -> Result<(), StoreError> {
+        self.file.seek(SeekFrom::Start(0)).map_err(era_error)?;
+        let (record_type, _) = read_record(&mut self.file)?;
+        if record_type != VERSION {
+            return Err(self.invalid("missing version"));
+        }
+        let mut header_records = vec![];
+        let mut block_hash = None;
+        let mut accumulator = None;
+        while self.file.stream_position().map_err(era_error)? < self.index_position {
+            let (record_type, data) = read_record(&mut self.file)?;
+            match record_type {
+                COMPRESSED_HEADER => {
+                    block_hash = Some(Keccak256::digest(decompress(&data)?));
+                }
+                TOTAL_DIFFICULTY => {
+                    let Some(block_hash) = block_hash.take() else {
+                        return Err(self.invalid("total difficulty without header"));
+                    };
+                    // Header records are hashed as an SSZ container of the block hash and the
+                    // little-endian total difficulty
+                    header_records.push(sha256(&block_hash, &data));
+                }
+                ACCUMULATOR => accumulator = Some(data),
+                _ => (),
+            }
+        }
+        if header_records.len() != self.offsets.len() {
+            return Err(self.invalid("block count doesn't match the index"));
+        }
+        if accumulator.as_deref() != Some(accumulator_root(&header_records).as_slice()) {
+            return Err(self.invalid("accumulator doesn't match the headers"));
+        }
+        Ok(())
+    }
+
+    fn read_compressed(&mut self, expected_type: u16) -> Result<Vec<u8>, StoreError> {
+        let (record_type, data) = read_record(&mut self.file)?;
+        if record_type != expected_type {
+            return Err(self.invalid(&format!("unexpected record type {record_type:#x}")));
+        }
+        decompress(&data)
+    }
+}
+
+/// Reads the e2store record at the current position of the file
+fn read_record(file: &mut File) -> Result<(u16, Vec<u8>), StoreError> {
+    let mut header = [0; RECORD_HEADER_SIZE as usize];
+    file.read_exact(&mut header).map_err(era_error)?;
+    let record_type = u16::from_le_bytes([header[0], header[1]]);
+    let length = u32::from_le_bytes([header[2], header[3], header[4], header[5]]);
+    let mut data = vec![];
+    file.take(length.into())
+        .read_to_end(&mut data)
+        .map_err(era_error)?;
+    if data.len() != length as usize {
+        return Err(StoreError::Era("truncated era1 record".to_string()));
+    }
+    Ok((record_type, data))
+}
+
+/// Decompresses the snappy frames of a record
+fn decompress(data: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let mut decompressed = vec![];
+    snap::read::FrameDecoder::new(data)
+        .read_to_end(&mut decompressed)
+        .map_err(era_error)?;
+    Ok(decompressed)
+}
+
+/// Decodes the receipts of a block, where the typed ones are wrapped in byte strings
+fn decode_receipts(rlp: &[u8]) -> Result<Vec<Receipt>, StoreError> {
+    let (is_list, mut items, _) = decode_rlp_item(rlp)?;
+    if !is_list {
+        return Err(StoreError::DecodeError);
+    }
+    let mut receipts = vec![];
+    while !items.is_empty() {
+        let (is_list, payload, rest) = decode_rlp_item(items)?;
+        let receipt = if is_list {
+            Receipt::decode(get_item_with_prefix(items)?.0)?
+        } else {
+            Receipt::decode(payload)?
+        };
+        receipts.push(receipt);
+        items = rest;
+    }
+    Ok(receipts)
+}
+
+fn sha256(left: &[u8], right: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+/// Computes the SSZ root of the list of header records, limited to [BLOCKS_PER_ERA] records
+fn accumulator_root(header_records: &[[u8; 32]]) -> [u8; 32] {
+    let mut layer = header_records.to_vec();
+    let mut zero_hash = [0; 32];
+    for _ in 0..ACCUMULATOR_DEPTH {
+        if layer.len() % 2 == 1 {
+            layer.push(zero_hash);
+        }
+        layer = layer
+            .chunks_exact(2)
+            .map(|pair| sha256(&pair[0], &pair[1]))
+            .collect();
+        zero_hash = sha256(&zero_hash, &zero_hash);
+    }
+    let root = layer.first().copied().unwrap_or(zero_hash);
+    let mut length = [0; 32];
+    length[..8].copy_from_slice(&(header_records.len() as u64).to_le_bytes());
+    sha256(&root, &length)
+}
+
+fn era_error(error: impl std::fmt::Display) -> StoreError {
+    StoreError::Era(error.to_string())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Store {
+    /// Serves the pruned history from the era1 files of the given directory
+    pub fn set_era_archive(&mut self, directory: impl Into<PathBuf>) {
+        self.era_archive = Some(Arc::new(EraArchive::new(directory)));
+    }
+
+    /// Returns whether the pruned body and receipts of the block can be read from the era1
+    /// archive
+    pub fn has_archived_history(&self, block_number: BlockNumber) -> Result<bool, StoreError> {
+        match &self.era_archive {
+            Some(archive) => archive.has_block(block_number),
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the canonical block from the era1 archive if its history was pruned.
+    /// The header of the archived block must be the canonical one.
+    pub(crate) fn get_archived_block(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<Arc<EraBlock>>, StoreError> {
+        let Some(archive) = &self.era_archive else {
+            return Ok(None);
+        };
+        if block_number >= self.get_history_start_block_number()? {
+            return Ok(None);
+        }
+        let Some(block) = archive.get_block(block_number)? else {
+            return Ok(None);
+        };
+        if self.engine.get_canonical_block_hash(block_number)?
+            != Some(block.header.compute_block_hash())
+        {
+            return Err(StoreError::Era(format!(
+                "archived block {block_number} is not the canonical one"
+            )));
+        }
+        Ok(Some(block))
+    }
+
+    /// Returns the block with the given hash from the era1 archive if it is canonical and its
+    /// history was pruned
+    pub(crate) fn get_archived_block_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<Arc<EraBlock>>, StoreError> {
+        if self.era_archive.is_none() {
+            return Ok(None);
+        }
+        let Some(block_number) = self.engine.get_block_number(block_hash)? else {
+            return Ok(None);
+        };
+        if self.engine.get_canonical_block_hash(block_number)? != Some(block_hash) {
+            return Ok(None);
+        }
+        self.get_archived_block(block_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EngineType;
+    use bytes::Bytes;
+    use ethereum_types::U256;
+    use ethrex_core::types::{Block, LegacyTransaction, Transaction, TxKind, TxType};
+    use ethrex_rlp::encode::RLPEncode;
+    use std::io::Write;
+
+    fn write_record(file: &mut Vec<u8>, record_type: u16, data: &[u8]) {
+        file.extend_from_slice(&record_type.to_le_bytes());
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&[0, 0]);
+        file.extend_from_slice(data);
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = snap::write::FrameEncoder::new(vec![]);
+        encoder.write_all(data).unwrap();
+        encoder.into_inner().unwrap()
+    }
+
+    /// Writes an era1 file with the given blocks, returning its bytes
+    fn era_file(blocks: &[(Block, Vec<Receipt>)], accumulator: Option<[u8; 32]>) -> Vec<u8> {
+        let mut file = vec![];
+        write_record(&mut file, VERSION, &[]);
+        let mut offsets = vec![];
+        let mut header_records = vec![];
+        for (block, receipts) in blocks {
+            offsets.push(file.len());
+            write_record(
+                &mut file,
+                COMPRESSED_HEADER,
+                &compress(&block.header.encode_to_vec()),
+            );
+            write_record(
+                &mut file,
+                COMPRESSED_BODY,
+                &compress(&block.body.encode_to_vec()),
+            );
+            write_record(
+                &mut file,
+                COMPRESSED_RECEIPTS,
+                &compress(&receipts.encode_to_vec()),
+            );
+            let mut total_difficulty = [0; 32];
+            U256::from(block.header.number + 1).to_little_endian(&mut total_difficulty);
+            write_record(&mut file, TOTAL_DIFFICULTY, &total_difficulty);
+            header_records.push(sha256(block.hash().as_bytes(), &total_difficulty));
+        }
+        let accumulator = accumulator.unwrap_or_else(|| accumulator_root(&header_records));
+        write_record(&mut file, ACCUMULATOR, &accumulator);
+        let index_position = file.len();
+        let mut index = blocks[0].0.header.number.to_le_bytes().to_vec();
+        for offset in offsets {
+            index.extend_from_slice(&(offset as i64 - index_position as i64).to_le_bytes());
+        }
+        index.extend_from_slice(&(blocks.len() as u64).to_le_bytes());
+        write_record(&mut file, BLOCK_INDEX, &index);
+        file
+    }
+
+    /// Stores the headers of a pruned chain of pre-merge blocks, returning the blocks
+    fn pruned_chain(store: &Store) -> Vec<(Block, Vec<Receipt>)> {
+        let mut parent_hash = BlockHash::zero();
+        let mut blocks = vec![];
+        for number in 0..3 {
+            let transaction = Transaction::LegacyTransaction(LegacyTransaction {
+                nonce: number,
+                to: TxKind::Create,
+                data: Bytes::from(vec![1; 8]),
+                ..Default::default()
+            });
+            let receipts = vec![Receipt::new(TxType::Legacy, true, 21_000, vec![])];
+            let body = BlockBody {
+                transactions: vec![transaction],
+                ommers: vec![],
+                withdrawals: None,
+            };
+            let header = BlockHeader {
+                number,
+                parent_hash,
+                difficulty: U256::one(),
+                transactions_root: compute_transactions_root(&body.transactions),
+                receipts_root: compute_receipts_root(&receipts),
+                ..Default::default()
+            };
+            let block = Block::new(header, body);
+            parent_hash = block.hash();
+            store
+                .add_block_header(block.hash(), block.header.clone())
+                .unwrap();
+            store.add_block_number(block.hash(), number).unwrap();
+            store.set_canonical_block(number, block.hash()).unwrap();
+            blocks.push((block, receipts));
+        }
+        store.update_earliest_block_number(0).unwrap();
+        store.update_latest_block_number(2).unwrap();
+        store.engine.update_history_start_block_number(2).unwrap();
+        blocks
+    }
+
+    fn archive_directory(name: &str, file: &[u8]) -> PathBuf {
+        let directory = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("mainnet-00000-01020304.era1"), file).unwrap();
+        directory
+    }
+
+    #[test]
+    fn pruned_history_is_served_from_era_files() {
+        let mut store = Store::new("temp.db", EngineType::InMemory).unwrap();
+        let blocks = pruned_chain(&store);
+        assert!(store.get_block_body(0).unwrap().is_none());
+
+        let directory = archive_directory("ethrex-era-test", &era_file(&blocks, None));
+        store.set_era_archive(&directory);
+        assert!(store.has_archived_history(0).unwrap());
+        assert!(!store.has_archived_history(BLOCKS_PER_ERA).unwrap());
+        for (block, receipts) in &blocks[..2] {
+            let number = block.header.number;
+            assert_eq!(
+                store.get_block_body(number).unwrap(),
+                Some(block.body.clone())
+            );
+            assert_eq!(
+                store.get_block_body_by_hash(block.hash()).unwrap(),
+                Some(block.body.clone())
+            );
+            assert_eq!(
+                store.get_receipt(number, 0).unwrap().as_ref(),
+                receipts.first()
+            );
+            assert!(store.get_receipt(number, 1).unwrap().is_none());
+        }
+        // The history after the pruned one is never read from the archive
+        assert!(store.get_block_body(2).unwrap().is_none());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn era_files_must_match_their_accumulator() {
+        let mut store = Store::new("temp.db", EngineType::InMemory).unwrap();
+        let blocks = pruned_chain(&store);
+        let directory =
+            archive_directory("ethrex-era-invalid-test", &era_file(&blocks, Some([1; 32])));
+        store.set_era_archive(&directory);
+        assert!(matches!(store.get_block_body(0), Err(StoreError::Era(_))));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
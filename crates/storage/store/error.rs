@@ -1,3 +1,4 @@
+use ethrex_core::errors::{CodedError, ErrorCode};
 use ethrex_rlp::error::RLPDecodeError;
 use ethrex_trie::TrieError;
 #[cfg(feature = "redb")]
@@ -32,6 +33,8 @@ pub enum StoreError {
     RedbCastError,
     #[error("{0}")]
     Custom(String),
+    #[error("Era1 archive error: {0}")]
+    Era(String),
     #[error(transparent)]
     RLPDecode(#[from] RLPDecodeError),
     #[error(transparent)]
@@ -39,3 +42,19 @@ pub enum StoreError {
     #[error("missing store: is an execution DB being used instead?")]
     MissingStore,
 }
+
+impl CodedError for StoreError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            StoreError::DecodeError | StoreError::RLPDecode(_) => ErrorCode::Corrupted,
+            StoreError::Trie(error) => error.code(),
+            StoreError::Custom(_) | StoreError::MissingStore => ErrorCode::Internal,
+            // The archived history is only read from the era1 files, failures don't go away
+            StoreError::Era(_) => ErrorCode::Corrupted,
+            #[cfg(feature = "redb")]
+            StoreError::RedbCastError => ErrorCode::Corrupted,
+            #[cfg(any(feature = "libmdbx", feature = "redb"))]
+            _ => ErrorCode::Database,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use ethrex_core::types::{BlockNumber, Index};
+use tracing::info;
+
+use crate::{error::StoreError, Store};
+
+/// The progress of the history pruning is logged once every this amount of blocks
+const PRUNE_LOG_INTERVAL: u64 = 100_000;
+
+impl Store {
+    /// Returns the number of the first canonical block whose body and receipts are stored.
+    /// The history of the previous blocks was pruned, only their headers are kept.
+    pub fn get_history_start_block_number(&self) -> Result<BlockNumber, StoreError> {
+        match self.engine.get_history_start_block_number()? {
+            Some(block_number) => Ok(block_number),
+            None => Ok(self.get_earliest_block_number()?.unwrap_or_default()),
+        }
+    }
+
+    /// Returns the number of the first canonical block without difficulty, the merge block.
+    /// None if the chain didn't reach the merge yet.
+    pub fn get_merge_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        let Some(latest) = self.get_latest_block_number()? else {
+            return Ok(None);
+        };
+        let is_post_merge = |block_number| -> Result<bool, StoreError> {
+            // A missing header is taken as post-merge so its history is never pruned
+            Ok(self
+                .get_block_header(block_number)?
+                .is_none_or(|header| header.difficulty.is_zero()))
+        };
+        if !is_post_merge(latest)? {
+            return Ok(None);
+        }
+        let (mut low, mut high) = (self.get_earliest_block_number()?.unwrap_or(0), latest);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if is_post_merge(middle)? {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        Ok(Some(low))
+    }
+
+    /// Drops the bodies and receipts of the canonical blocks before the merge, keeping their
+    /// headers, as done by EIP-4444 nodes. The pruned history can be retrieved from era1 archives.
+    /// Pruning again after an interruption picks up the blocks that were left.
+    /// Returns the amount of pruned blocks.
+    pub fn prune_pre_merge_history(&self) -> Result<u64, StoreError> {
+        let Some(merge_block_number) = self.get_merge_block_number()? else {
+            return Ok(0);
+        };
+        // Advertising the new start first means the history is never served while being pruned
+        self.engine
+            .update_history_start_block_number(merge_block_number)?;
+        let earliest = self.get_earliest_block_number()?.unwrap_or_default();
+        let mut pruned = 0;
+        for block_number in earliest..merge_block_number {
+            if block_number % PRUNE_LOG_INTERVAL == 0 {
+                info!("Pruning the history of block {block_number} of {merge_block_number}");
+            }
+            let Some(block_hash) = self.get_canonical_block_hash(block_number)? else {
+                continue;
+            };
+            let Some(body) = self.engine.get_block_body_by_hash(block_hash)? else {
+                continue;
+            };
+            self.engine
+                .remove_block_history(block_hash, body.transactions.len() as Index)?;
+            // The cached body and receipts of blocks close to the head would still be served
+            self.head_cache()?.unset_canonical(block_number);
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+}
@@ -4,7 +4,7 @@ use std::marker::PhantomData;
 use bytes::Bytes;
 use ethereum_types::U256;
 use ethrex_core::{
-    types::{Block, BlockBody, BlockHash, BlockHeader, Receipt},
+    types::{BlobsBundle, Block, BlockBody, BlockHash, BlockHeader, Receipt},
     H256,
 };
 use ethrex_rlp::{decode::RLPDecode, encode::RLPEncode};
@@ -33,6 +33,9 @@ pub type ReceiptRLP = Rlp<Receipt>;
 // Transaction types
 pub type TransactionHashRLP = Rlp<H256>;
 
+// Blob types
+pub type BlobsBundleRLP = Rlp<BlobsBundle>;
+
 // Wrapper for tuples. Used mostly for indexed keys.
 pub type TupleRLP<A, B> = Rlp<(A, B)>;
 
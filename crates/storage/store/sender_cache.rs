@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use ethereum_types::{Address, H256};
+
+/// Maximum amount of recovered senders kept in memory
+pub const SENDER_CACHE_CAPACITY: usize = 32_768;
+
+/// Least recently used cache of transaction senders keyed by transaction hash.
+/// The hash commits to the signature, so a cached sender can't be wrong for a transaction.
+#[derive(Debug)]
+pub(crate) struct SenderCache {
+    capacity: usize,
+    /// Sender of each transaction and the tick it was last used at
+    senders: BTreeMap<H256, (Address, u64)>,
+    /// Transactions by the tick they were last used at, oldest first
+    usage: BTreeMap<u64, H256>,
+    tick: u64,
+}
+
+impl SenderCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            senders: BTreeMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub fn get(&mut self, tx_hash: H256) -> Option<Address> {
+        let (sender, last_used) = self.senders.get_mut(&tx_hash)?;
+        self.usage.remove(last_used);
+        self.tick += 1;
+        *last_used = self.tick;
+        self.usage.insert(self.tick, tx_hash);
+        Some(*sender)
+    }
+
+    /// Caches the sender of a transaction, evicting the least recently used one if full
+    pub fn insert(&mut self, tx_hash: H256, sender: Address) {
+        self.tick += 1;
+        if let Some((_, last_used)) = self.senders.insert(tx_hash, (sender, self.tick)) {
+            self.usage.remove(&last_used);
+        }
+        self.usage.insert(self.tick, tx_hash);
+        while self.senders.len() > self.capacity {
+            let Some((_, evicted)) = self.usage.pop_first() else {
+                break;
+            };
+            self.senders.remove(&evicted);
+        }
+    }
+}
+
+impl Default for SenderCache {
+    fn default() -> Self {
+        Self::new(SENDER_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_senders_are_evicted() {
+        let mut cache = SenderCache::new(2);
+        let hashes = [
+            H256::repeat_byte(1),
+            H256::repeat_byte(2),
+            H256::repeat_byte(3),
+        ];
+        cache.insert(hashes[0], Address::repeat_byte(1));
+        cache.insert(hashes[1], Address::repeat_byte(2));
+        // Using the first sender makes the second one the least recently used
+        assert_eq!(cache.get(hashes[0]), Some(Address::repeat_byte(1)));
+        cache.insert(hashes[2], Address::repeat_byte(3));
+        assert_eq!(cache.get(hashes[1]), None);
+        assert_eq!(cache.get(hashes[0]), Some(Address::repeat_byte(1)));
+        assert_eq!(cache.get(hashes[2]), Some(Address::repeat_byte(3)));
+        assert_eq!((cache.senders.len(), cache.usage.len()), (2, 2));
+    }
+}
@@ -0,0 +1,377 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use ethereum_types::BigEndianHash;
+use ethrex_core::{
+    serde_utils,
+    types::{AccountState, BlockHash, BlockHeader, BlockNumber},
+    H256, U256,
+};
+use ethrex_rlp::decode::RLPDecode;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    engines::api::StoreEngine, error::StoreError, hash_address, hash_key, AccountUpdate, Store,
+};
+
+/// Leaves, accounts and storage slots, generated on each batch. Each batch is stored along with
+/// the progress made so an interrupted generation resumes from the last one stored
+pub const SNAPSHOT_BATCH_LEAVES: usize = 10_000;
+
+/// Leaves read from the engine at once while iterating the snapshot
+const SNAPSHOT_PAGE_SIZE: usize = 256;
+
+/// Flat copy of the accounts and storage slots of the state of a block, indexed by hashed address
+/// and hashed key like the tries, so ranges of them can be read without traversing the tries.
+///
+/// The snapshot is generated from the state trie in the background and follows the chain while
+/// it is generated: the blocks built on top of the snapshot block update the leaves before the
+/// generation marker, and the generation reads the ones after it from the state of the new block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotStatus {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub block_number: BlockNumber,
+    pub block_hash: BlockHash,
+    pub state_root: H256,
+    /// Hashed address of the next account to generate, None once the snapshot is complete
+    pub generator_marker: Option<H256>,
+    /// Hashed key of the next storage slot to generate of the account at the generator marker,
+    /// if its storage trie was split across batches
+    #[serde(default)]
+    pub storage_marker: Option<H256>,
+    /// Accounts generated so far
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub accounts: u64,
+    /// Storage slots generated so far
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub storage_slots: u64,
+}
+
+impl SnapshotStatus {
+    pub fn is_complete(&self) -> bool {
+        self.generator_marker.is_none()
+    }
+
+    /// Whether the leaf of the given account was already generated, so it must be kept up to
+    /// date as the snapshot follows the chain
+    fn is_generated(&self, hashed_address: H256) -> bool {
+        self.generator_marker.is_none_or(|marker| {
+            hashed_address < marker || (hashed_address == marker && self.storage_marker.is_some())
+        })
+    }
+
+    /// Whether the given storage slot of a generated account was already generated
+    fn is_slot_generated(&self, hashed_address: H256, hashed_key: H256) -> bool {
+        self.generator_marker != Some(hashed_address)
+            || self
+                .storage_marker
+                .is_some_and(|marker| hashed_key < marker)
+    }
+}
+
+/// Changes written to the snapshot on a single transaction: accounts and storage slots set to
+/// None are removed, removing an account removes its storage slots too
+#[derive(Debug, Default)]
+pub struct SnapshotBatch {
+    pub accounts: Vec<(H256, Option<AccountState>)>,
+    pub storage_slots: Vec<(H256, H256, Option<U256>)>,
+}
+
+impl Store {
+    pub fn snapshot_status(&self) -> Result<Option<SnapshotStatus>, StoreError> {
+        self.engine.get_snapshot_status()
+    }
+
+    /// Drops the current snapshot, if any, and starts generating a new one from the state of the
+    /// given block
+    pub fn start_snapshot(&self, header: &BlockHeader) -> Result<SnapshotStatus, StoreError> {
+        let _lock = self.snapshot_lock()?;
+        self.restart_snapshot(header)
+    }
+
+    fn restart_snapshot(&self, header: &BlockHeader) -> Result<SnapshotStatus, StoreError> {
+        let status = SnapshotStatus {
+            block_number: header.number,
+            block_hash: header.compute_block_hash(),
+            state_root: header.state_root,
+            generator_marker: Some(H256::zero()),
+            storage_marker: None,
+            accounts: 0,
+            storage_slots: 0,
+        };
+        self.engine.clear_snapshot()?;
+        self.engine
+            .write_snapshot_batch(SnapshotBatch::default(), &status)?;
+        Ok(status)
+    }
+
+    /// Generates the next [SNAPSHOT_BATCH_LEAVES] leaves of the snapshot and returns the updated
+    /// status. The storage trie of an account may be split across batches.
+    /// Returns None if there is no snapshot being generated.
+    pub fn generate_snapshot_batch(&self) -> Result<Option<SnapshotStatus>, StoreError> {
+        let Some(status) = self.engine.get_snapshot_status()? else {
+            return Ok(None);
+        };
+        let Some((batch, new_status)) = self.read_snapshot_batch(&status)? else {
+            return Ok(Some(status));
+        };
+        // The batch is read without holding the lock so blocks can advance the snapshot meanwhile,
+        // in which case it is discarded and read again from the state of the new block
+        let _lock = self.snapshot_lock()?;
+        let current_status = self.engine.get_snapshot_status()?;
+        if current_status.as_ref() != Some(&status) {
+            return Ok(current_status);
+        }
+        self.engine.write_snapshot_batch(batch, &new_status)?;
+        Ok(Some(new_status))
+    }
+
+    /// Reads the next batch of leaves from the state of the snapshot block, along with the status
+    /// once the batch is written. Returns None if the snapshot is complete.
+    fn read_snapshot_batch(
+        &self,
+        status: &SnapshotStatus,
+    ) -> Result<Option<(SnapshotBatch, SnapshotStatus)>, StoreError> {
+        let Some(marker) = status.generator_marker else {
+            return Ok(None);
+        };
+        let mut new_status = SnapshotStatus {
+            generator_marker: None,
+            storage_marker: None,
+            ..status.clone()
+        };
+        let mut batch = SnapshotBatch::default();
+        let mut leaves = SNAPSHOT_BATCH_LEAVES;
+        for (hashed_address, account) in self.iter_accounts_from(status.state_root, marker)? {
+            if leaves == 0 {
+                new_status.generator_marker = Some(hashed_address);
+                break;
+            }
+            // The account may have been removed since its storage trie was split
+            let storage_marker = status.storage_marker.filter(|_| hashed_address == marker);
+            if storage_marker.is_none() {
+                new_status.accounts += 1;
+            }
+            batch.accounts.push((hashed_address, Some(account)));
+            leaves -= 1;
+            let mut slots = self
+                .iter_storage_from(
+                    status.state_root,
+                    hashed_address,
+                    storage_marker.unwrap_or_default(),
+                )?
+                .into_iter()
+                .flatten();
+            for (hashed_key, value) in slots.by_ref().take(leaves) {
+                batch
+                    .storage_slots
+                    .push((hashed_address, hashed_key, Some(value)));
+                leaves -= 1;
+            }
+            if let Some((hashed_key, _)) = slots.next() {
+                new_status.generator_marker = Some(hashed_address);
+                new_status.storage_marker = Some(hashed_key);
+                break;
+            }
+        }
+        new_status.storage_slots += batch.storage_slots.len() as u64;
+        Ok(Some((batch, new_status)))
+    }
+
+    /// Moves the snapshot to the state of the given block if it is built on top of the snapshot
+    /// block, applying the block's account updates to the leaves already generated.
+    /// Must only be called once the updates were applied and the resulting state root validated.
+    pub fn advance_snapshot(
+        &self,
+        header: &BlockHeader,
+        block_hash: BlockHash,
+        account_updates: &[AccountUpdate],
+    ) -> Result<(), StoreError> {
+        let _lock = self.snapshot_lock()?;
+        let Some(mut status) = self.engine.get_snapshot_status()? else {
+            return Ok(());
+        };
+        if status.block_hash != header.parent_hash {
+            return Ok(());
+        }
+        let state_trie = self.engine.open_state_trie(header.state_root);
+        let mut batch = SnapshotBatch::default();
+        for update in account_updates {
+            let hashed_address = H256::from_slice(&hash_address(&update.address));
+            if !status.is_generated(hashed_address) {
+                continue;
+            }
+            let account = match state_trie.get(&hashed_address.as_bytes().to_vec())? {
+                Some(encoded_state) if !update.removed => {
+                    Some(AccountState::decode(&encoded_state)?)
+                }
+                _ => None,
+            };
+            if account.is_some() {
+                for (key, value) in &update.added_storage {
+                    let hashed_key = H256::from_slice(&hash_key(key));
+                    if !status.is_slot_generated(hashed_address, hashed_key) {
+                        continue;
+                    }
+                    let value = (!value.is_zero()).then_some(*value);
+                    batch
+                        .storage_slots
+                        .push((hashed_address, hashed_key, value));
+                }
+            } else if status.generator_marker == Some(hashed_address) {
+                // The storage generated so far is removed along with the account, so the
+                // generation starts it again
+                status.storage_marker = None;
+            }
+            batch.accounts.push((hashed_address, account));
+        }
+        status.block_number = header.number;
+        status.block_hash = block_hash;
+        status.state_root = header.state_root;
+        self.engine.write_snapshot_batch(batch, &status)
+    }
+
+    /// Restarts the snapshot from the state of the given head if a fork choice update moved the
+    /// canonical chain off the snapshot block, as blocks advance the snapshot before they are
+    /// made canonical. The snapshot is dropped if the state of the head is not stored.
+    pub fn restart_snapshot_off_canonical_chain(
+        &self,
+        head: &BlockHeader,
+    ) -> Result<(), StoreError> {
+        let _lock = self.snapshot_lock()?;
+        let Some(status) = self.engine.get_snapshot_status()? else {
+            return Ok(());
+        };
+        if self.snapshot_follows_canonical_chain(&status)? {
+            return Ok(());
+        }
+        if !self
+            .engine
+            .open_state_trie(head.state_root)
+            .has_root_node()?
+        {
+            return self.engine.clear_snapshot();
+        }
+        info!(
+            "Restarting the snapshot at block {} as block {} left the canonical chain",
+            head.number, status.block_number
+        );
+        self.restart_snapshot(head)?;
+        Ok(())
+    }
+
+    /// Whether the snapshot block is part of the canonical chain, or descends from its head as
+    /// the snapshot advances with the blocks added before they are made canonical
+    pub fn snapshot_follows_canonical_chain(
+        &self,
+        status: &SnapshotStatus,
+    ) -> Result<bool, StoreError> {
+        let (mut number, mut hash) = (status.block_number, status.block_hash);
+        loop {
+            if let Some(canonical_hash) = self.get_canonical_block_hash(number)? {
+                return Ok(canonical_hash == hash);
+            }
+            let Some(header) = self.get_block_header_by_hash(hash)? else {
+                return Ok(false);
+            };
+            let Some(parent_number) = number.checked_sub(1) else {
+                return Ok(false);
+            };
+            (number, hash) = (parent_number, header.parent_hash);
+        }
+    }
+
+    /// Returns an iterator across the accounts of the snapshot starting from the given hashed
+    /// address, if the snapshot is complete and holds the state with the given root
+    pub fn iter_snapshot_accounts_from(
+        &self,
+        state_root: H256,
+        starting_hash: H256,
+    ) -> Result<Option<impl Iterator<Item = (H256, AccountState)>>, StoreError> {
+        if !self.has_complete_snapshot(state_root)? {
+            return Ok(None);
+        }
+        Ok(Some(SnapshotIter::new(
+            self.engine.clone(),
+            starting_hash,
+            |engine, start| engine.get_snapshot_accounts(start, SNAPSHOT_PAGE_SIZE),
+        )))
+    }
+
+    /// Returns an iterator across the storage slots of an account of the snapshot starting from
+    /// the given hashed key, if the snapshot is complete and holds the state with the given root
+    pub fn iter_snapshot_storage_from(
+        &self,
+        state_root: H256,
+        hashed_address: H256,
+        starting_hash: H256,
+    ) -> Result<Option<impl Iterator<Item = (H256, U256)>>, StoreError> {
+        if !self.has_complete_snapshot(state_root)? {
+            return Ok(None);
+        }
+        Ok(Some(SnapshotIter::new(
+            self.engine.clone(),
+            starting_hash,
+            move |engine, start| {
+                engine.get_snapshot_storage(hashed_address, start, SNAPSHOT_PAGE_SIZE)
+            },
+        )))
+    }
+
+    fn has_complete_snapshot(&self, state_root: H256) -> Result<bool, StoreError> {
+        Ok(self
+            .engine
+            .get_snapshot_status()?
+            .is_some_and(|status| status.is_complete() && status.state_root == state_root))
+    }
+}
+
+/// Iterates the leaves of the snapshot reading them from the engine a page at a time.
+/// The iteration stops at the first read failure.
+struct SnapshotIter<T, F> {
+    engine: Arc<dyn StoreEngine>,
+    read_page: F,
+    page: VecDeque<(H256, T)>,
+    /// Key to read the next page from, None once the last page was read
+    next_start: Option<H256>,
+}
+
+impl<T, F> SnapshotIter<T, F>
+where
+    F: Fn(&dyn StoreEngine, H256) -> Result<Vec<(H256, T)>, StoreError>,
+{
+    fn new(engine: Arc<dyn StoreEngine>, starting_hash: H256, read_page: F) -> Self {
+        SnapshotIter {
+            engine,
+            read_page,
+            page: VecDeque::new(),
+            next_start: Some(starting_hash),
+        }
+    }
+}
+
+impl<T, F> Iterator for SnapshotIter<T, F>
+where
+    F: Fn(&dyn StoreEngine, H256) -> Result<Vec<(H256, T)>, StoreError>,
+{
+    type Item = (H256, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() {
+            let start = self.next_start.take()?;
+            let page = (self.read_page)(self.engine.as_ref(), start).ok()?;
+            if page.len() == SNAPSHOT_PAGE_SIZE {
+                self.next_start = page.last().and_then(|(last, _)| next_hash(*last));
+            }
+            self.page = page.into();
+        }
+        self.page.pop_front()
+    }
+}
+
+/// Returns the hash following the given one, None if it is the highest one
+fn next_hash(hash: H256) -> Option<H256> {
+    let next = hash.into_uint().checked_add(U256::one())?;
+    Some(H256::from_uint(&next))
+}
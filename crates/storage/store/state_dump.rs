@@ -0,0 +1,148 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+};
+
+use bytes::Bytes;
+use ethrex_core::{
+    serde_utils,
+    types::{code_hash, AccountState, BlockHash, BlockNumber, EMPTY_TRIE_HASH},
+    H256, U256,
+};
+use ethrex_rlp::encode::RLPEncode;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::StoreError, Store};
+
+/// First line of a state dump, identifying the state held by the rest of the lines
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateDumpHeader {
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub block_number: BlockNumber,
+    pub block_hash: BlockHash,
+    pub state_root: H256,
+}
+
+/// Account of a state dump. The tries don't keep the preimages of their keys, so accounts
+/// and storage slots are identified by their hashed address and key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpAccount {
+    pub hashed_address: H256,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub nonce: u64,
+    pub balance: U256,
+    #[serde(with = "serde_utils::bytes")]
+    pub code: Bytes,
+    pub storage: BTreeMap<H256, U256>,
+}
+
+fn dump_error(error: impl ToString) -> StoreError {
+    StoreError::Custom(format!("Invalid state dump: {}", error.to_string()))
+}
+
+impl Store {
+    /// Writes the state of the canonical block with the given number as JSON lines: the
+    /// [StateDumpHeader] followed by one [DumpAccount] per line, sorted by hashed address.
+    /// Returns the amount of exported accounts.
+    pub fn export_state(
+        &self,
+        block_number: BlockNumber,
+        mut writer: impl Write,
+    ) -> Result<usize, StoreError> {
+        let header = self
+            .get_block_header(block_number)?
+            .ok_or(StoreError::Custom(format!(
+                "Block {block_number} is not in the canonical chain"
+            )))?;
+        if !self.has_state(block_number)? {
+            return Err(StoreError::Custom(format!(
+                "State of block {block_number} is not available"
+            )));
+        }
+        let dump_header = StateDumpHeader {
+            block_number,
+            block_hash: header.compute_block_hash(),
+            state_root: header.state_root,
+        };
+        write_line(&mut writer, &dump_header)?;
+        let mut exported = 0;
+        for (hashed_address, account) in self.iter_accounts(header.state_root) {
+            let code = self
+                .get_account_code(account.code_hash)?
+                .unwrap_or_default();
+            let storage = self
+                .iter_storage(header.state_root, hashed_address)?
+                .into_iter()
+                .flatten()
+                .collect();
+            write_line(
+                &mut writer,
+                &DumpAccount {
+                    hashed_address,
+                    nonce: account.nonce,
+                    balance: account.balance,
+                    code,
+                    storage,
+                },
+            )?;
+            exported += 1;
+        }
+        writer
+            .flush()
+            .map_err(|error| StoreError::Custom(error.to_string()))?;
+        Ok(exported)
+    }
+
+    /// Rebuilds the tries of a state written by [Store::export_state], checking they
+    /// commit to the state root of the dump. Only the state is stored, not the block.
+    pub fn import_state(&self, reader: impl BufRead) -> Result<StateDumpHeader, StoreError> {
+        let mut lines = reader.lines();
+        let header_line = lines
+            .next()
+            .ok_or(dump_error("missing header"))?
+            .map_err(dump_error)?;
+        let dump_header: StateDumpHeader =
+            serde_json::from_str(&header_line).map_err(dump_error)?;
+        let mut state_trie = self.engine.open_state_commitment(*EMPTY_TRIE_HASH);
+        for line in lines {
+            let account: DumpAccount =
+                serde_json::from_str(&line.map_err(dump_error)?).map_err(dump_error)?;
+            let code_hash = code_hash(&account.code);
+            self.add_account_code(code_hash, account.code)?;
+            let mut storage_trie = self
+                .engine
+                .open_storage_commitment(account.hashed_address, *EMPTY_TRIE_HASH);
+            for (hashed_key, value) in account.storage {
+                storage_trie.insert(hashed_key.as_bytes().to_vec(), value.encode_to_vec())?;
+            }
+            let account_state = AccountState {
+                nonce: account.nonce,
+                balance: account.balance,
+                storage_root: storage_trie.commit()?,
+                code_hash,
+            };
+            state_trie.insert(
+                account.hashed_address.as_bytes().to_vec(),
+                account_state.encode_to_vec(),
+            )?;
+        }
+        let state_root = state_trie.commit()?;
+        if state_root != dump_header.state_root {
+            return Err(dump_error(format!(
+                "the accounts commit to {state_root:#x} instead of {:#x}",
+                dump_header.state_root
+            )));
+        }
+        Ok(dump_header)
+    }
+}
+
+fn write_line(writer: &mut impl Write, value: &impl Serialize) -> Result<(), StoreError> {
+    serde_json::to_writer(&mut *writer, value)
+        .map_err(|error| StoreError::Custom(error.to_string()))?;
+    writer
+        .write_all(b"\n")
+        .map_err(|error| StoreError::Custom(error.to_string()))
+}
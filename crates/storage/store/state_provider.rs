@@ -0,0 +1,263 @@
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use ethrex_core::types::{AccountInfo, AccountState, BlockHash, BlockHeader, BlockNumber};
+use ethrex_rlp::decode::RLPDecode;
+use ethrex_trie::Trie;
+
+use crate::{error::StoreError, hash_address, hash_address_fixed, hash_key, Store};
+
+/// Hands out [StateProvider]s, immutable views of the state pinned to a block.
+///
+/// Readers serving a request over several reads, such as the RPC, should read through a single
+/// view instead of resolving the block on every read, as blocks imported or a reorg applied
+/// meanwhile would make each read observe a different state.
+#[derive(Debug, Clone)]
+pub struct StateProviderFactory {
+    store: Store,
+}
+
+impl StateProviderFactory {
+    pub fn new(store: Store) -> Self {
+        Self { store }
+    }
+
+    /// Returns the view of the state at the given block, or `None` if the block is unknown
+    pub fn state_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<StateProvider>, StoreError> {
+        Ok(self
+            .store
+            .get_block_header_by_hash(block_hash)?
+            .map(|header| StateProvider::new(self.store.clone(), block_hash, &header)))
+    }
+
+    /// Returns the view of the state at the canonical block with the given number, which stays
+    /// pinned to that block even if it stops being canonical
+    pub fn state_by_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<StateProvider>, StoreError> {
+        match self.store.get_canonical_block_hash(block_number)? {
+            Some(block_hash) => self.state_by_hash(block_hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the view of the state at the latest canonical block
+    pub fn latest(&self) -> Result<Option<StateProvider>, StoreError> {
+        match self.store.get_latest_block_number()? {
+            Some(block_number) => self.state_by_number(block_number),
+            None => Ok(None),
+        }
+    }
+}
+
+/// View of the state at a block, returned by a [StateProviderFactory].
+///
+/// The view reads the tries from the state root of the block, whose nodes are never modified
+/// once written, so its reads are consistent with each other while blocks are being imported.
+#[derive(Debug, Clone)]
+pub struct StateProvider {
+    store: Store,
+    block_hash: BlockHash,
+    block_number: BlockNumber,
+    state_root: H256,
+}
+
+impl StateProvider {
+    fn new(store: Store, block_hash: BlockHash, header: &BlockHeader) -> Self {
+        Self {
+            store,
+            block_hash,
+            block_number: header.number,
+            state_root: header.state_root,
+        }
+    }
+
+    pub fn block_hash(&self) -> BlockHash {
+        self.block_hash
+    }
+
+    pub fn block_number(&self) -> BlockNumber {
+        self.block_number
+    }
+
+    pub fn state_root(&self) -> H256 {
+        self.state_root
+    }
+
+    /// Returns true if the state trie of the block is stored
+    pub fn has_state(&self) -> Result<bool, StoreError> {
+        Ok(self.state_trie().has_root_node()?)
+    }
+
+    pub fn get_account_state(&self, address: Address) -> Result<Option<AccountState>, StoreError> {
+        let Some(encoded_state) = self.state_trie().get(&hash_address(&address))? else {
+            return Ok(None);
+        };
+        Ok(Some(AccountState::decode(&encoded_state)?))
+    }
+
+    pub fn get_account_info(&self, address: Address) -> Result<Option<AccountInfo>, StoreError> {
+        if let Some(info) = self
+            .store
+            .diff_layers()?
+            .get_account_info(self.block_hash, address)
+        {
+            return Ok(info);
+        }
+        Ok(self
+            .get_account_state(address)?
+            .map(|account_state| AccountInfo {
+                code_hash: account_state.code_hash,
+                balance: account_state.balance,
+                nonce: account_state.nonce,
+            }))
+    }
+
+    pub fn get_code(&self, address: Address) -> Result<Option<Bytes>, StoreError> {
+        let Some(info) = self.get_account_info(address)? else {
+            return Ok(None);
+        };
+        self.store.get_account_code(info.code_hash)
+    }
+
+    pub fn get_storage_at(
+        &self,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<U256>, StoreError> {
+        if let Some(value) =
+            self.store
+                .diff_layers()?
+                .get_storage(self.block_hash, address, storage_key)
+        {
+            return Ok(Some(value));
+        }
+        let Some(storage_trie) = self.storage_trie(address)? else {
+            return Ok(None);
+        };
+        storage_trie
+            .get(&hash_key(&storage_key))?
+            .map(|rlp| U256::decode(&rlp).map_err(StoreError::RLPDecode))
+            .transpose()
+    }
+
+    /// Constructs a merkle proof of the account, proving its absence if it doesn't exist
+    pub fn get_account_proof(&self, address: Address) -> Result<Vec<Vec<u8>>, StoreError> {
+        Ok(self.state_trie().get_proof(&hash_address(&address))?)
+    }
+
+    /// Constructs a merkle proof of the storage slot of the account, proving its absence if
+    /// it is empty
+    pub fn get_storage_proof(
+        &self,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Vec<Vec<u8>>, StoreError> {
+        let storage_root = self
+            .get_account_state(address)?
+            .unwrap_or_default()
+            .storage_root;
+        self.store
+            .get_storage_proof(address, storage_root, &storage_key)
+    }
+
+    fn state_trie(&self) -> Trie {
+        self.store.engine.open_state_trie(self.state_root)
+    }
+
+    fn storage_trie(&self, address: Address) -> Result<Option<Trie>, StoreError> {
+        let Some(account_state) = self.get_account_state(address)? else {
+            return Ok(None);
+        };
+        Ok(Some(self.store.engine.open_storage_trie(
+            hash_address_fixed(&address),
+            account_state.storage_root,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccountUpdate, EngineType};
+    use ethrex_core::types::{Block, EMPTY_TRIE_HASH};
+
+    #[test]
+    fn views_stay_pinned_to_their_block() {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let address = Address::repeat_byte(1);
+        let slot = H256::repeat_byte(2);
+        let genesis = BlockHeader {
+            state_root: *EMPTY_TRIE_HASH,
+            ..Default::default()
+        };
+        let genesis_hash = genesis.compute_block_hash();
+        store
+            .add_block(Block::new(genesis, Default::default()))
+            .unwrap();
+        store.set_canonical_block(0, genesis_hash).unwrap();
+        store.update_latest_block_number(0).unwrap();
+        let factory = StateProviderFactory::new(store.clone());
+        let genesis_state = factory.latest().unwrap().unwrap();
+
+        // A block funding the account is imported and becomes canonical
+        let mut update = AccountUpdate::new(address);
+        update.info = Some(AccountInfo {
+            balance: 100.into(),
+            ..Default::default()
+        });
+        update.added_storage.insert(slot, 7.into());
+        let state_root = store
+            .apply_account_updates(genesis_hash, &[update])
+            .unwrap()
+            .unwrap();
+        let header = BlockHeader {
+            number: 1,
+            parent_hash: genesis_hash,
+            state_root,
+            ..Default::default()
+        };
+        let hash = header.compute_block_hash();
+        store
+            .add_block(Block::new(header, Default::default()))
+            .unwrap();
+        store.set_canonical_block(1, hash).unwrap();
+        store.update_latest_block_number(1).unwrap();
+
+        assert!(genesis_state.get_account_info(address).unwrap().is_none());
+        assert!(genesis_state
+            .get_storage_at(address, slot)
+            .unwrap()
+            .is_none());
+        let latest = factory.latest().unwrap().unwrap();
+        assert_eq!(latest.block_hash(), hash);
+        assert_eq!(
+            latest.get_account_info(address).unwrap().unwrap().balance,
+            100.into()
+        );
+        assert_eq!(
+            latest.get_storage_at(address, slot).unwrap(),
+            Some(7.into())
+        );
+
+        // The view is kept on the block even if a reorg unsets it as canonical
+        store.unset_canonical_block(1).unwrap();
+        assert!(factory.state_by_number(1).unwrap().is_none());
+        assert_eq!(
+            latest.get_storage_at(address, slot).unwrap(),
+            Some(7.into())
+        );
+        assert_eq!(
+            latest.get_account_proof(address).unwrap()[0],
+            store
+                .state_trie(hash)
+                .unwrap()
+                .unwrap()
+                .get_proof(&hash_address(&address))
+                .unwrap()[0]
+        );
+    }
+}
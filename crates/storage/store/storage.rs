@@ -1,16 +1,21 @@
+use self::cache::HeadCache;
+use self::diff_layers::StateDiffLayers;
 use self::engines::in_memory::Store as InMemoryStore;
 #[cfg(feature = "libmdbx")]
 use self::engines::libmdbx::Store as LibmdbxStore;
+use self::era::EraArchive;
 use self::error::StoreError;
+use self::sender_cache::SenderCache;
 use bytes::Bytes;
 use engines::api::StoreEngine;
 #[cfg(feature = "redb")]
 use engines::redb::RedBStore;
 use ethereum_types::{Address, H256, U256};
 use ethrex_core::types::{
-    code_hash, AccountInfo, AccountState, BlobsBundle, Block, BlockBody, BlockHash, BlockHeader,
-    BlockNumber, ChainConfig, Genesis, GenesisAccount, Index, MempoolTransaction, Receipt,
-    Transaction, TxType, EMPTY_TRIE_HASH,
+    code_hash, AccountInfo, AccountState, Blob, BlobsBundle, Block, BlockBody, BlockHash,
+    BlockHeader, BlockNumber, ChainConfig, ChainEvent, Genesis, GenesisAccount, Index,
+    MempoolTransaction, Proof, Receipt, StateAccessStats, Transaction, TransactionConditions,
+    TxKind, TxType, EMPTY_TRIE_HASH,
 };
 use ethrex_rlp::decode::RLPDecode;
 use ethrex_rlp::encode::RLPEncode;
@@ -19,12 +24,22 @@ use serde::{Deserialize, Serialize};
 use sha3::{Digest as _, Keccak256};
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::sync::{Arc, Mutex};
-use tracing::info;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::{debug_span, info};
 
+mod cache;
+mod diff_layers;
 mod engines;
+pub mod era;
 pub mod error;
+pub mod history;
 mod rlp;
+mod sender_cache;
+pub mod snapshot;
+pub mod state_dump;
+pub mod state_provider;
+pub mod trie_range;
+pub mod verify;
 
 #[derive(Debug, Clone)]
 pub struct Store {
@@ -32,8 +47,41 @@ pub struct Store {
     engine: Arc<dyn StoreEngine>,
     pub mempool: Arc<Mutex<HashMap<H256, MempoolTransaction>>>,
     pub blobs_bundle_pool: Arc<Mutex<HashMap<H256, BlobsBundle>>>,
+    pub tx_conditions_pool: Arc<Mutex<HashMap<H256, TransactionConditions>>>,
+    address_index: bool,
+    strict_validation: bool,
+    /// Maximum amount of canonical blocks a fork choice update may remove
+    max_reorg_depth: Option<u64>,
+    /// Seconds the archived blob sidecars are kept for, if not the default window
+    blob_sidecars_retention: Option<u64>,
+    chain_event_listener: Option<ChainEventListener>,
+    /// Last block each account and storage slot was accessed in, only collected if enabled
+    state_access_stats: Option<Arc<Mutex<StateAccessStats>>>,
+    /// Senders recovered from the signature of each transaction, shared by the mempool, the
+    /// block execution and the RPC so the same transaction is only recovered once as it moves
+    /// through them
+    sender_cache: Arc<Mutex<SenderCache>>,
+    head_cache: Arc<Mutex<HeadCache>>,
+    diff_layers: Arc<Mutex<StateDiffLayers>>,
+    /// Held while the snapshot is generated or advanced, so both never run at the same time
+    snapshot_lock: Arc<Mutex<()>>,
+    era_archive: Option<Arc<EraArchive>>,
 }
 
+/// Receives the changes of the canonical chain, see [Store::set_chain_event_listener]
+#[derive(Clone)]
+struct ChainEventListener(Arc<dyn Fn(ChainEvent) + Send + Sync>);
+
+impl Debug for ChainEventListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ChainEventListener")
+    }
+}
+
+// The store never reads the state of the listener, so a panic within it can't break the store
+impl std::panic::UnwindSafe for ChainEventListener {}
+impl std::panic::RefUnwindSafe for ChainEventListener {}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub enum EngineType {
@@ -83,19 +131,57 @@ impl Store {
                 engine: Arc::new(LibmdbxStore::new(path)?),
                 mempool: Arc::new(Mutex::new(HashMap::new())),
                 blobs_bundle_pool: Arc::new(Mutex::new(HashMap::new())),
+                tx_conditions_pool: Arc::new(Mutex::new(HashMap::new())),
+                address_index: false,
+                strict_validation: false,
+                max_reorg_depth: None,
+                blob_sidecars_retention: None,
+                chain_event_listener: None,
+                state_access_stats: None,
+                sender_cache: Default::default(),
+                head_cache: Default::default(),
+                diff_layers: Default::default(),
+                snapshot_lock: Default::default(),
+                era_archive: None,
             },
             EngineType::InMemory => Self {
                 engine: Arc::new(InMemoryStore::new()),
                 mempool: Arc::new(Mutex::new(HashMap::new())),
                 blobs_bundle_pool: Arc::new(Mutex::new(HashMap::new())),
+                tx_conditions_pool: Arc::new(Mutex::new(HashMap::new())),
+                address_index: false,
+                strict_validation: false,
+                max_reorg_depth: None,
+                blob_sidecars_retention: None,
+                chain_event_listener: None,
+                state_access_stats: None,
+                sender_cache: Default::default(),
+                head_cache: Default::default(),
+                diff_layers: Default::default(),
+                snapshot_lock: Default::default(),
+                era_archive: None,
             },
             #[cfg(feature = "redb")]
             EngineType::RedB => Self {
-                engine: Arc::new(RedBStore::new()?),
+                engine: Arc::new(RedBStore::new(path)?),
                 mempool: Arc::new(Mutex::new(HashMap::new())),
                 blobs_bundle_pool: Arc::new(Mutex::new(HashMap::new())),
+                tx_conditions_pool: Arc::new(Mutex::new(HashMap::new())),
+                address_index: false,
+                strict_validation: false,
+                max_reorg_depth: None,
+                blob_sidecars_retention: None,
+                chain_event_listener: None,
+                state_access_stats: None,
+                sender_cache: Default::default(),
+                head_cache: Default::default(),
+                diff_layers: Default::default(),
+                snapshot_lock: Default::default(),
+                era_archive: None,
             },
         };
+        *store.head_cache()? = HeadCache::new(store.engine.get_latest_block_number()?);
+        store.index_canonical_block_numbers()?;
         info!("Started store engine");
         Ok(store)
     }
@@ -116,6 +202,9 @@ impl Store {
         block_hash: BlockHash,
         address: Address,
     ) -> Result<Option<AccountInfo>, StoreError> {
+        if let Some(info) = self.diff_layers()?.get_account_info(block_hash, address) {
+            return Ok(info);
+        }
         let Some(state_trie) = self.state_trie(block_hash)? else {
             return Ok(None);
         };
@@ -143,21 +232,71 @@ impl Store {
         &self,
         block_number: BlockNumber,
     ) -> Result<Option<BlockHeader>, StoreError> {
-        self.engine.get_block_header(block_number)
+        let generation = {
+            let cache = self.head_cache()?;
+            if let Some(header) = cache.get_header(block_number) {
+                return Ok(Some(header));
+            }
+            cache.generation()
+        };
+        let header = self.engine.get_block_header(block_number)?;
+        if let Some(header) = &header {
+            self.head_cache()?.add_header(
+                generation,
+                block_number,
+                header.compute_block_hash(),
+                header,
+            );
+        }
+        Ok(header)
     }
 
     pub fn get_block_header_by_hash(
         &self,
         block_hash: BlockHash,
     ) -> Result<Option<BlockHeader>, StoreError> {
-        self.engine.get_block_header_by_hash(block_hash)
+        let generation = {
+            let cache = self.head_cache()?;
+            if let Some(header) = cache.get_header_by_hash(block_hash) {
+                return Ok(Some(header));
+            }
+            cache.generation()
+        };
+        let header = self.engine.get_block_header_by_hash(block_hash)?;
+        if let Some(header) = &header {
+            self.cache_if_canonical(header.number, block_hash, |cache| {
+                cache.add_header(generation, header.number, block_hash, header)
+            })?;
+        }
+        Ok(header)
     }
 
     pub fn get_block_body_by_hash(
         &self,
         block_hash: BlockHash,
     ) -> Result<Option<BlockBody>, StoreError> {
-        self.engine.get_block_body_by_hash(block_hash)
+        let (generation, cached_number) = {
+            let cache = self.head_cache()?;
+            if let Some(body) = cache.get_body_by_hash(block_hash) {
+                return Ok(Some(body));
+            }
+            (cache.generation(), cache.get_number(block_hash))
+        };
+        let Some(body) = self.engine.get_block_body_by_hash(block_hash)? else {
+            return Ok(self
+                .get_archived_block_by_hash(block_hash)?
+                .map(|block| block.body.clone()));
+        };
+        let number = match cached_number {
+            Some(number) => Some(number),
+            None => self.engine.get_canonical_block_number(block_hash)?,
+        };
+        if let Some(number) = number {
+            self.cache_if_canonical(number, block_hash, |cache| {
+                cache.add_body(generation, number, block_hash, &body)
+            })?;
+        }
+        Ok(Some(body))
     }
 
     pub fn add_block_body(
@@ -172,7 +311,27 @@ impl Store {
         &self,
         block_number: BlockNumber,
     ) -> Result<Option<BlockBody>, StoreError> {
-        self.engine.get_block_body(block_number)
+        let (generation, cached_hash) = {
+            let cache = self.head_cache()?;
+            if let Some(body) = cache.get_body(block_number) {
+                return Ok(Some(body));
+            }
+            (cache.generation(), cache.get_hash(block_number))
+        };
+        let Some(body) = self.engine.get_block_body(block_number)? else {
+            return Ok(self
+                .get_archived_block(block_number)?
+                .map(|block| block.body.clone()));
+        };
+        let hash = match cached_hash {
+            Some(hash) => Some(hash),
+            None => self.engine.get_canonical_block_hash(block_number)?,
+        };
+        if let Some(hash) = hash {
+            self.head_cache()?
+                .add_body(generation, block_number, hash, &body);
+        }
+        Ok(Some(body))
     }
 
     pub fn add_pending_block(&self, block: Block) -> Result<(), StoreError> {
@@ -254,6 +413,18 @@ impl Store {
         Ok(())
     }
 
+    /// Get a transaction from the pool given its hash
+    pub fn get_transaction_from_pool(
+        &self,
+        hash: H256,
+    ) -> Result<Option<MempoolTransaction>, StoreError> {
+        let mempool = self
+            .mempool
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))?;
+        Ok(mempool.get(&hash).cloned())
+    }
+
     /// Add a blobs bundle to the pool by its blob transaction hash
     pub fn add_blobs_bundle_to_pool(
         &self,
@@ -280,6 +451,71 @@ impl Store {
             .cloned())
     }
 
+    /// Returns the blob and proof of each of the versioned hashes whose blob is in the pool
+    pub fn get_blobs_and_proofs_from_pool(
+        &self,
+        versioned_hashes: &[H256],
+    ) -> Result<Vec<Option<(Blob, Proof)>>, StoreError> {
+        let mut found = vec![None; versioned_hashes.len()];
+        let pool = self
+            .blobs_bundle_pool
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))?;
+        for bundle in pool.values() {
+            for (index, versioned_hash) in bundle.generate_versioned_hashes().iter().enumerate() {
+                let (Some(blob), Some(proof)) = (bundle.blobs.get(index), bundle.proofs.get(index))
+                else {
+                    continue;
+                };
+                for (requested, found) in versioned_hashes.iter().zip(found.iter_mut()) {
+                    if requested == versioned_hash {
+                        *found = Some((*blob, *proof));
+                    }
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Add the inclusion conditions of a conditional transaction to the pool by its hash
+    pub fn add_transaction_conditions_to_pool(
+        &self,
+        tx_hash: H256,
+        conditions: TransactionConditions,
+    ) -> Result<(), StoreError> {
+        self.tx_conditions_pool
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))?
+            .insert(tx_hash, conditions);
+        Ok(())
+    }
+
+    /// Get the inclusion conditions of a transaction in the pool given its hash,
+    /// if it was submitted as a conditional transaction
+    pub fn get_transaction_conditions_from_pool(
+        &self,
+        tx_hash: H256,
+    ) -> Result<Option<TransactionConditions>, StoreError> {
+        Ok(self
+            .tx_conditions_pool
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))?
+            .get(&tx_hash)
+            .cloned())
+    }
+
+    /// Remove the inclusion conditions of a transaction from the pool
+    pub fn remove_transaction_conditions_from_pool(
+        &self,
+        tx_hash: &H256,
+    ) -> Result<(), StoreError> {
+        self.tx_conditions_pool
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))?
+            .remove(tx_hash);
+        Ok(())
+    }
+
     /// Remove a transaction from the pool
     pub fn remove_transaction_from_pool(&self, hash: &H256) -> Result<(), StoreError> {
         let mut mempool = self
@@ -293,6 +529,7 @@ impl Store {
                     .map_err(|error| StoreError::Custom(error.to_string()))?
                     .remove(&tx.compute_hash());
             }
+            self.remove_transaction_conditions_from_pool(hash)?;
 
             mempool.remove(hash);
         };
@@ -377,9 +614,11 @@ impl Store {
         block_hash: BlockHash,
         account_updates: &[AccountUpdate],
     ) -> Result<Option<H256>, StoreError> {
-        let Some(mut state_trie) = self.state_trie(block_hash)? else {
+        let _span = debug_span!("apply_account_updates", updates = account_updates.len()).entered();
+        let Some(header) = self.get_block_header_by_hash(block_hash)? else {
             return Ok(None);
         };
+        let mut state_trie = self.engine.open_state_commitment(header.state_root);
         for update in account_updates.iter() {
             let hashed_address = hash_address(&update.address);
             if update.removed {
@@ -403,7 +642,7 @@ impl Store {
                 }
                 // Store the added storage in the account's storage trie and compute its new root
                 if !update.added_storage.is_empty() {
-                    let mut storage_trie = self.engine.open_storage_trie(
+                    let mut storage_trie = self.engine.open_storage_commitment(
                         H256::from_slice(&hashed_address),
                         account_state.storage_root,
                     );
@@ -415,12 +654,30 @@ impl Store {
                             storage_trie.insert(hashed_key, storage_value.encode_to_vec())?;
                         }
                     }
-                    account_state.storage_root = storage_trie.hash()?;
+                    account_state.storage_root = storage_trie.commit()?;
                 }
                 state_trie.insert(hashed_address, account_state.encode_to_vec())?;
             }
         }
-        Ok(Some(state_trie.hash()?))
+        Ok(Some(state_trie.commit()?))
+    }
+
+    /// Keeps the account updates applied by the given block in memory, so reads of the state they
+    /// modified at recent blocks don't need to traverse the state trie.
+    /// Must only be called once the updates were applied and the resulting state root validated.
+    pub fn add_state_diff_layer(
+        &self,
+        header: &BlockHeader,
+        block_hash: BlockHash,
+        account_updates: &[AccountUpdate],
+    ) -> Result<(), StoreError> {
+        self.diff_layers()?.add_layer(
+            header.number,
+            block_hash,
+            header.parent_hash,
+            account_updates,
+        );
+        Ok(())
     }
 
     /// Adds all genesis accounts and returns the genesis block's state_root
@@ -428,7 +685,7 @@ impl Store {
         &self,
         genesis_accounts: HashMap<Address, GenesisAccount>,
     ) -> Result<H256, StoreError> {
-        let mut genesis_state_trie = self.engine.open_state_trie(*EMPTY_TRIE_HASH);
+        let mut genesis_state_trie = self.engine.open_state_commitment(*EMPTY_TRIE_HASH);
         for (address, account) in genesis_accounts {
             let hashed_address = hash_address(&address);
             // Store account code (as this won't be stored in the trie)
@@ -437,14 +694,14 @@ impl Store {
             // Store the account's storage in a clean storage trie and compute its root
             let mut storage_trie = self
                 .engine
-                .open_storage_trie(H256::from_slice(&hashed_address), *EMPTY_TRIE_HASH);
+                .open_storage_commitment(H256::from_slice(&hashed_address), *EMPTY_TRIE_HASH);
             for (storage_key, storage_value) in account.storage {
                 if !storage_value.is_zero() {
                     let hashed_key = hash_key(&storage_key);
                     storage_trie.insert(hashed_key, storage_value.encode_to_vec())?;
                 }
             }
-            let storage_root = storage_trie.hash()?;
+            let storage_root = storage_trie.commit()?;
             // Add account to trie
             let account_state = AccountState {
                 nonce: account.nonce,
@@ -454,7 +711,7 @@ impl Store {
             };
             genesis_state_trie.insert(hashed_address, account_state.encode_to_vec())?;
         }
-        Ok(genesis_state_trie.hash()?)
+        Ok(genesis_state_trie.commit()?)
     }
 
     pub fn add_receipt(
@@ -471,10 +728,76 @@ impl Store {
         block_number: BlockNumber,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
-        self.engine.get_receipt(block_number, index)
+        let (generation, cached_hash) = {
+            let cache = self.head_cache()?;
+            if let Some(receipt) = cache.get_receipt(block_number, index) {
+                return Ok(Some(receipt));
+            }
+            (cache.generation(), cache.get_hash(block_number))
+        };
+        let hash = match cached_hash {
+            Some(hash) => hash,
+            None => match self.engine.get_canonical_block_hash(block_number)? {
+                Some(hash) => hash,
+                None => return Ok(None),
+            },
+        };
+        let Some(receipt) = self.engine.get_receipt_by_hash(hash, index)? else {
+            return Ok(self
+                .get_archived_block(block_number)?
+                .and_then(|block| block.receipts.get(index as usize).cloned()));
+        };
+        self.head_cache()?
+            .add_receipt(generation, block_number, hash, index, &receipt);
+        Ok(Some(receipt))
+    }
+
+    /// Returns the receipt for the given block hash and transaction index,
+    /// regardless of whether the block is canonical or not
+    pub fn get_receipt_by_hash(
+        &self,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<Option<Receipt>, StoreError> {
+        match self.engine.get_receipt_by_hash(block_hash, index)? {
+            Some(receipt) => Ok(Some(receipt)),
+            None => Ok(self
+                .get_archived_block_by_hash(block_hash)?
+                .and_then(|block| block.receipts.get(index as usize).cloned())),
+        }
+    }
+
+    /// Recomputes the bloom of every receipt in the canonical chain from its logs, rewriting the
+    /// ones that don't match. Used to repair the stored receipts after the bloom encoding changes.
+    /// Returns the amount of receipts that were repaired.
+    pub fn rebuild_receipt_blooms(&self) -> Result<usize, StoreError> {
+        let Some(latest) = self.get_latest_block_number()? else {
+            return Ok(0);
+        };
+        let earliest = self.get_earliest_block_number()?.unwrap_or_default();
+        let mut repaired = 0;
+        for number in earliest..=latest {
+            let Some(block_hash) = self.get_canonical_block_hash(number)? else {
+                continue;
+            };
+            let Some(body) = self.get_block_body_by_hash(block_hash)? else {
+                continue;
+            };
+            for index in 0..body.transactions.len() as Index {
+                let Some(mut receipt) = self.get_receipt_by_hash(block_hash, index)? else {
+                    continue;
+                };
+                if receipt.rebuild_bloom() {
+                    self.engine.replace_receipt(block_hash, index, receipt)?;
+                    repaired += 1;
+                }
+            }
+        }
+        Ok(repaired)
     }
 
     pub fn add_block(&self, block: Block) -> Result<(), StoreError> {
+        let _span = debug_span!("store_block", number = block.header.number).entered();
         // TODO Maybe add both in a single tx?
         let header = block.header;
         let number = header.number;
@@ -483,6 +806,9 @@ impl Store {
             latest_total_difficulty.unwrap_or(U256::zero()) + header.difficulty;
         let hash = header.compute_block_hash();
         self.add_transaction_locations(&block.body.transactions, number, hash)?;
+        if self.address_index {
+            self.add_address_transactions(&block.body.transactions, number, hash)?;
+        }
         self.add_block_body(hash, block.body)?;
         self.add_block_header(hash, header)?;
         self.add_block_number(hash, number)?;
@@ -507,6 +833,155 @@ impl Store {
         Ok(())
     }
 
+    /// Indexes the transactions by the addresses that sent and received them
+    fn add_address_transactions(
+        &self,
+        transactions: &[Transaction],
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+    ) -> Result<(), StoreError> {
+        for (index, transaction) in transactions.iter().enumerate() {
+            let transaction_hash = transaction.compute_hash();
+            let sender = self.transaction_sender(transaction);
+            self.engine.add_address_transaction(
+                sender,
+                transaction_hash,
+                block_number,
+                block_hash,
+                index as Index,
+            )?;
+            if let TxKind::Call(recipient) = transaction.to() {
+                if recipient != sender {
+                    self.engine.add_address_transaction(
+                        recipient,
+                        transaction_hash,
+                        block_number,
+                        block_hash,
+                        index as Index,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Enables the address index, so the transactions of every block added from now on
+    /// can be looked up by their sender and recipient
+    pub fn enable_address_index(&mut self) {
+        self.address_index = true;
+    }
+
+    pub fn address_index_enabled(&self) -> bool {
+        self.address_index
+    }
+
+    /// Enables the strict validation of the blocks added from now on, which checks the roots
+    /// and gas used in their header against their body and receipts
+    pub fn enable_strict_validation(&mut self) {
+        self.strict_validation = true;
+    }
+
+    pub fn strict_validation_enabled(&self) -> bool {
+        self.strict_validation
+    }
+
+    /// Sets the maximum amount of canonical blocks a fork choice update may remove
+    pub fn set_max_reorg_depth(&mut self, depth: u64) {
+        self.max_reorg_depth = Some(depth);
+    }
+
+    pub fn max_reorg_depth(&self) -> Option<u64> {
+        self.max_reorg_depth
+    }
+
+    /// Overrides the time the archived blob sidecars are kept for
+    pub fn set_blob_sidecars_retention(&mut self, seconds: u64) {
+        self.blob_sidecars_retention = Some(seconds);
+    }
+
+    pub fn blob_sidecars_retention(&self) -> Option<u64> {
+        self.blob_sidecars_retention
+    }
+
+    /// Sets the function the changes of the canonical chain made by fork choice updates are sent
+    /// to, in the order they are made. It is called while applying the updates, so it should
+    /// hand the events off quickly.
+    pub fn set_chain_event_listener(
+        &mut self,
+        listener: impl Fn(ChainEvent) + Send + Sync + 'static,
+    ) {
+        self.chain_event_listener = Some(ChainEventListener(Arc::new(listener)));
+    }
+
+    pub fn chain_event_listener(&self) -> Option<&(dyn Fn(ChainEvent) + Send + Sync)> {
+        self.chain_event_listener
+            .as_ref()
+            .map(|listener| listener.0.as_ref())
+    }
+
+    /// Enables the collection of the state accessed by the blocks executed from now on,
+    /// starting from the given stats
+    pub fn enable_state_access_stats(&mut self, initial: StateAccessStats) {
+        self.state_access_stats = Some(Arc::new(Mutex::new(initial)));
+    }
+
+    pub fn state_access_stats(&self) -> Option<&Mutex<StateAccessStats>> {
+        self.state_access_stats.as_deref()
+    }
+
+    /// Returns the address that signed the transaction, only recovering it from the signature
+    /// if it is not in the sender cache. The cache is not locked during the recovery so it can
+    /// run in parallel.
+    pub fn transaction_sender(&self, transaction: &Transaction) -> Address {
+        let tx_hash = transaction.compute_hash();
+        if let Some(sender) = self.sender_cache().get(tx_hash) {
+            return sender;
+        }
+        let sender = transaction.sender();
+        self.sender_cache().insert(tx_hash, sender);
+        sender
+    }
+
+    /// Returns the canonical transactions sent or received by the given address,
+    /// newest first, skipping the first `offset` ones and returning at most `limit`.
+    /// Each transaction is returned with its block number and index within the block.
+    pub fn get_address_transactions(
+        &self,
+        address: Address,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(BlockNumber, Index, H256)>, StoreError> {
+        let mut transactions = Vec::new();
+        let mut skipped = 0;
+        let mut before = None;
+        // The index is read a page at a time as it holds the transactions of non-canonical
+        // blocks too, which are skipped
+        while transactions.len() < limit {
+            let page = self
+                .engine
+                .get_address_transactions(address, before, limit)?;
+            let Some(&(block_number, block_hash, index, _)) = page.last() else {
+                break;
+            };
+            before = Some((block_number, index, block_hash));
+            let is_last_page = page.len() < limit;
+            for (block_number, block_hash, index, transaction_hash) in page {
+                if self.get_canonical_block_hash(block_number)? != Some(block_hash) {
+                    continue;
+                }
+                if skipped < offset {
+                    skipped += 1;
+                } else if transactions.len() < limit {
+                    transactions.push((block_number, index, transaction_hash));
+                }
+            }
+            if is_last_page {
+                break;
+            }
+        }
+        Ok(transactions)
+    }
+
     pub fn add_initial_state(&self, genesis: Genesis) -> Result<(), StoreError> {
         info!("Storing initial state from genesis");
 
@@ -560,7 +1035,25 @@ impl Store {
     }
 
     pub fn get_block_by_hash(&self, block_hash: H256) -> Result<Option<Block>, StoreError> {
-        self.engine.get_block_by_hash(block_hash)
+        let generation = {
+            let cache = self.head_cache()?;
+            if let Some((header, body)) = cache
+                .get_header_by_hash(block_hash)
+                .zip(cache.get_body_by_hash(block_hash))
+            {
+                return Ok(Some(Block::new(header, body)));
+            }
+            cache.generation()
+        };
+        let block = self.engine.get_block_by_hash(block_hash)?;
+        if let Some(block) = &block {
+            let number = block.header.number;
+            self.cache_if_canonical(number, block_hash, |cache| {
+                cache.add_header(generation, number, block_hash, &block.header);
+                cache.add_body(generation, number, block_hash, &block.body);
+            })?;
+        }
+        Ok(block)
     }
 
     pub fn get_storage_at(
@@ -581,6 +1074,12 @@ impl Store {
         address: Address,
         storage_key: H256,
     ) -> Result<Option<U256>, StoreError> {
+        if let Some(value) = self
+            .diff_layers()?
+            .get_storage(block_hash, address, storage_key)
+        {
+            return Ok(Some(value));
+        }
         let Some(storage_trie) = self.storage_trie(block_hash, address)? else {
             return Ok(None);
         };
@@ -631,7 +1130,9 @@ impl Store {
     }
 
     pub fn update_latest_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
-        self.engine.update_latest_block_number(block_number)
+        self.engine.update_latest_block_number(block_number)?;
+        self.head_cache()?.set_latest(block_number);
+        Ok(())
     }
 
     // TODO(#790): This should not return an option.
@@ -660,6 +1161,10 @@ impl Store {
         number: BlockNumber,
         hash: BlockHash,
     ) -> Result<(), StoreError> {
+        // Keep the cache locked until the write is done so that no concurrent read can
+        // populate it with the previous canonical block
+        let mut cache = self.head_cache()?;
+        cache.set_canonical(number, hash);
         self.engine.set_canonical_block(number, hash)
     }
 
@@ -670,13 +1175,106 @@ impl Store {
         self.engine.get_canonical_block_hash(block_number)
     }
 
+    /// Returns the number of the block if it is part of the canonical chain, without reading its
+    /// header
+    pub fn get_canonical_block_number(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        self.engine.get_canonical_block_number(block_hash)
+    }
+
+    /// Walks the ancestors of the block until one of the canonical chain, returning its hash.
+    /// The block itself is returned if it is canonical, None if an ancestor is not stored.
+    pub fn find_canonical_ancestor(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockHash>, StoreError> {
+        let mut hash = block_hash;
+        while self.get_canonical_block_number(hash)?.is_none() {
+            let Some(header) = self.get_block_header_by_hash(hash)? else {
+                return Ok(None);
+            };
+            hash = header.parent_hash;
+        }
+        Ok(Some(hash))
+    }
+
+    /// Fills the canonical block numbers index of databases created before it existed, which is
+    /// detected by the latest block missing from it
+    fn index_canonical_block_numbers(&self) -> Result<(), StoreError> {
+        let Some(latest) = self.engine.get_latest_block_number()? else {
+            return Ok(());
+        };
+        let Some(latest_hash) = self.engine.get_canonical_block_hash(latest)? else {
+            return Ok(());
+        };
+        if self
+            .engine
+            .get_canonical_block_number(latest_hash)?
+            .is_some()
+        {
+            return Ok(());
+        }
+        let earliest = self.engine.get_earliest_block_number()?.unwrap_or_default();
+        info!("Indexing the numbers of the canonical blocks {earliest} to {latest}");
+        for number in earliest..=latest {
+            if let Some(hash) = self.engine.get_canonical_block_hash(number)? {
+                self.engine.set_canonical_block(number, hash)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Marks a block number as not having any canonical blocks associated with it.
     /// Used for reorgs.
     /// Note: Should we also remove all others up to the head here?
     pub fn unset_canonical_block(&self, number: BlockNumber) -> Result<(), StoreError> {
+        let mut cache = self.head_cache()?;
+        cache.unset_canonical(number);
         self.engine.unset_canonical_block(number)
     }
 
+    fn head_cache(&self) -> Result<MutexGuard<'_, HeadCache>, StoreError> {
+        self.head_cache
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))
+    }
+
+    fn sender_cache(&self) -> MutexGuard<'_, SenderCache> {
+        self.sender_cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn diff_layers(&self) -> Result<MutexGuard<'_, StateDiffLayers>, StoreError> {
+        self.diff_layers
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))
+    }
+
+    fn snapshot_lock(&self) -> Result<MutexGuard<'_, ()>, StoreError> {
+        self.snapshot_lock
+            .lock()
+            .map_err(|error| StoreError::Custom(error.to_string()))
+    }
+
+    /// Runs `insert` on the head cache if the block is close enough to the head and part of
+    /// the canonical chain. The cache is kept locked while checking the canonical chain so it
+    /// can't be updated in between.
+    fn cache_if_canonical(
+        &self,
+        number: BlockNumber,
+        hash: BlockHash,
+        insert: impl FnOnce(&mut HeadCache),
+    ) -> Result<(), StoreError> {
+        let mut cache = self.head_cache()?;
+        if cache.in_range(number) && self.engine.get_canonical_block_number(hash)? == Some(number) {
+            insert(&mut cache);
+        }
+        Ok(())
+    }
+
     // Obtain the storage trie for the given block
     pub fn state_trie(&self, block_hash: BlockHash) -> Result<Option<Trie>, StoreError> {
         let Some(header) = self.get_block_header_by_hash(block_hash)? else {
@@ -685,6 +1283,39 @@ impl Store {
         Ok(Some(self.engine.open_state_trie(header.state_root)))
     }
 
+    /// Returns true if the state trie of the canonical block is stored, which is not the case for
+    /// blocks older than the ones the node started executing from, such as those before a sync pivot
+    pub fn has_state(&self, block_number: BlockNumber) -> Result<bool, StoreError> {
+        let Some(header) = self.get_block_header(block_number)? else {
+            return Ok(false);
+        };
+        Ok(self
+            .engine
+            .open_state_trie(header.state_root)
+            .has_root_node()?)
+    }
+
+    /// Returns the number of the oldest canonical block whose state is stored,
+    /// as the state is available for every block from that one up to the latest
+    pub fn get_oldest_block_with_state(&self) -> Result<Option<BlockNumber>, StoreError> {
+        let Some(latest) = self.get_latest_block_number()? else {
+            return Ok(None);
+        };
+        if !self.has_state(latest)? {
+            return Ok(None);
+        }
+        let (mut low, mut high) = (self.get_earliest_block_number()?.unwrap_or(0), latest);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.has_state(middle)? {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        Ok(Some(low))
+    }
+
     // Obtain the storage trie for the given account on the given block
     pub fn storage_trie(
         &self,
@@ -803,6 +1434,45 @@ impl Store {
         ))
     }
 
+    // Returns an iterator across the accounts in the state trie given by the state_root, starting from
+    // the given hashed address. The trie is seeked to the starting position instead of walked from the start
+    // Does not check that the state_root is valid
+    pub fn iter_accounts_from(
+        &self,
+        state_root: H256,
+        starting_hash: H256,
+    ) -> Result<impl Iterator<Item = (H256, AccountState)>, StoreError> {
+        let mut iter = self.engine.open_state_trie(state_root).into_iter();
+        iter.advance(starting_hash.as_bytes().to_vec())?;
+        Ok(iter.content().map_while(|(path, value)| {
+            Some((H256::from_slice(&path), AccountState::decode(&value).ok()?))
+        }))
+    }
+
+    // Returns an iterator across the storage slots of the account given by hashed_address, starting from
+    // the given hashed key. The trie is seeked to the starting position instead of walked from the start
+    // Does not check that the state_root is valid
+    pub fn iter_storage_from(
+        &self,
+        state_root: H256,
+        hashed_address: H256,
+        starting_hash: H256,
+    ) -> Result<Option<impl Iterator<Item = (H256, U256)>>, StoreError> {
+        let state_trie = self.engine.open_state_trie(state_root);
+        let Some(account_rlp) = state_trie.get(&hashed_address.as_bytes().to_vec())? else {
+            return Ok(None);
+        };
+        let storage_root = AccountState::decode(&account_rlp)?.storage_root;
+        let mut iter = self
+            .engine
+            .open_storage_trie(hashed_address, storage_root)
+            .into_iter();
+        iter.advance(starting_hash.as_bytes().to_vec())?;
+        Ok(Some(iter.content().map_while(|(path, value)| {
+            Some((H256::from_slice(&path), U256::decode(&value).ok()?))
+        })))
+    }
+
     pub fn get_account_range_proof(
         &self,
         state_root: H256,
@@ -896,6 +1566,56 @@ impl Store {
         self.engine.get_payload(payload_id)
     }
 
+    pub fn remove_payload(&self, payload_id: u64) -> Result<(), StoreError> {
+        self.engine.remove_payload(payload_id)
+    }
+
+    /// Removes the payloads whose block timestamp is older than the given one
+    pub fn remove_payloads_older_than(&self, timestamp: u64) -> Result<(), StoreError> {
+        for payload_id in self.engine.get_payload_ids()? {
+            if self
+                .engine
+                .get_payload(payload_id)?
+                .is_some_and(|payload| payload.header.timestamp < timestamp)
+            {
+                self.engine.remove_payload(payload_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the blob sidecars of the blob transactions of a block so they can be served
+    /// after the transactions leave the mempool
+    pub fn add_blob_sidecars(
+        &self,
+        block_hash: BlockHash,
+        timestamp: u64,
+        sidecars: BlobsBundle,
+    ) -> Result<(), StoreError> {
+        self.engine
+            .add_blob_sidecars(block_hash, timestamp, sidecars)
+    }
+
+    pub fn get_blob_sidecars(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlobsBundle>, StoreError> {
+        self.engine.get_blob_sidecars(block_hash)
+    }
+
+    /// Removes the blob sidecars of the blocks whose timestamp is older than the given one,
+    /// returning the amount of blocks whose sidecars were removed
+    pub fn prune_blob_sidecars(&self, timestamp: u64) -> Result<usize, StoreError> {
+        let mut pruned = 0;
+        for (block_hash, block_timestamp) in self.engine.get_blob_sidecar_blocks()? {
+            if block_timestamp < timestamp {
+                self.engine.remove_blob_sidecars(block_hash)?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
     /// Creates a new state trie with an empty state root, for testing purposes only
     pub fn new_state_trie_for_test(&self) -> Trie {
         self.engine.open_state_trie(*EMPTY_TRIE_HASH)
@@ -926,9 +1646,9 @@ mod tests {
     use std::{fs, panic, str::FromStr};
 
     use bytes::Bytes;
-    use ethereum_types::{H256, U256};
+    use ethereum_types::{BigEndianHash, H256, U256};
     use ethrex_core::{
-        types::{Transaction, TxType, BYTES_PER_BLOB},
+        types::{Log, Transaction, TxType, BYTES_PER_BLOB},
         Bloom,
     };
     use ethrex_rlp::decode::RLPDecode;
@@ -952,6 +1672,51 @@ mod tests {
         test_store_suite(EngineType::RedB);
     }
 
+    #[test]
+    fn test_in_memory_stores_are_isolated() {
+        test_stores_are_isolated(EngineType::InMemory);
+    }
+
+    #[cfg(feature = "libmdbx")]
+    #[test]
+    fn test_libmdbx_stores_are_isolated() {
+        test_stores_are_isolated(EngineType::Libmdbx);
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_redb_stores_are_isolated() {
+        test_stores_are_isolated(EngineType::RedB);
+    }
+
+    // Stores of different paths in the same process must not share any data
+    fn test_stores_are_isolated(engine_type: EngineType) {
+        let paths = [
+            format!("store-test-db-{engine_type:?}-a"),
+            format!("store-test-db-{engine_type:?}-b"),
+        ];
+        paths.iter().for_each(|path| remove_test_dbs(path));
+        let store_a = Store::new(&paths[0], engine_type).expect("Failed to create test db");
+        let store_b = Store::new(&paths[1], engine_type).expect("Failed to create test db");
+        let block_hash = H256::random();
+        store_a.add_block_number(block_hash, 1).unwrap();
+        store_a.update_latest_block_number(1).unwrap();
+
+        assert_eq!(store_a.get_block_number(block_hash).unwrap(), Some(1));
+        assert_eq!(store_b.get_block_number(block_hash).unwrap(), None);
+        assert_eq!(store_b.get_latest_block_number().unwrap(), None);
+
+        let tx = Transaction::decode_canonical(&hex!("f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4")).unwrap();
+        assert_eq!(store_a.transaction_sender(&tx), tx.sender());
+        assert_eq!(
+            store_a.sender_cache().get(tx.compute_hash()),
+            Some(tx.sender())
+        );
+        assert_eq!(store_b.sender_cache().get(tx.compute_hash()), None);
+        drop((store_a, store_b));
+        paths.iter().for_each(|path| remove_test_dbs(path));
+    }
+
     // Creates an empty store, runs the test and then removes the store (if needed)
     fn run_test(test_func: &dyn Fn(Store), engine_type: EngineType) {
         // Remove preexistent DBs in case of a failed previous test
@@ -979,7 +1744,23 @@ mod tests {
         run_test(&test_chain_config_storage, engine_type);
         run_test(&test_genesis_block, engine_type);
         run_test(&test_filter_mempool_transactions, engine_type);
+        run_test(&test_store_address_transactions, engine_type);
+        run_test(&test_rebuild_receipt_blooms, engine_type);
         run_test(&blobs_bundle_loadtest, engine_type);
+        run_test(&test_head_cache_reorg, engine_type);
+        run_test(&test_head_cache_skips_non_canonical, engine_type);
+        run_test(&test_find_canonical_ancestor, engine_type);
+        run_test(&test_canonical_block_numbers, engine_type);
+        run_test(&test_oldest_block_with_state, engine_type);
+        run_test(&test_verify_chain, engine_type);
+        run_test(&test_remove_expired_payloads, engine_type);
+        run_test(&test_prune_blob_sidecars, engine_type);
+        run_test(&test_state_dump_roundtrip, engine_type);
+        run_test(&test_trie_ranges, engine_type);
+        run_test(&test_snapshot, engine_type);
+        run_test(&test_snapshot_split_storage, engine_type);
+        run_test(&test_snapshot_off_canonical_chain, engine_type);
+        run_test(&test_prune_pre_merge_history, engine_type);
     }
 
     fn test_genesis_block(store: Store) {
@@ -1067,6 +1848,7 @@ mod tests {
             blob_gas_used: Some(0x00),
             excess_blob_gas: Some(0x00),
             parent_beacon_block_root: Some(H256::zero()),
+            requests_hash: None,
         };
         let block_body = BlockBody {
             transactions: vec![Transaction::decode(&hex::decode("b86f02f86c8330182480114e82f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee53800080c080a0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4").unwrap()).unwrap(),
@@ -1151,6 +1933,724 @@ mod tests {
         assert_eq!(stored_receipt, receipt);
     }
 
+    fn test_head_cache_reorg(store: Store) {
+        let (header, body) = create_block_for_testing();
+        let block_number = header.number;
+        let hash = header.compute_block_hash();
+        store
+            .add_block(Block::new(header.clone(), body.clone()))
+            .unwrap();
+        store.set_canonical_block(block_number, hash).unwrap();
+        store.update_latest_block_number(block_number).unwrap();
+
+        // Reading the canonical block populates the head cache
+        let block = store.get_block_by_hash(hash).unwrap().unwrap();
+        assert_eq!(block.header, header);
+        assert_eq!(
+            store.head_cache().unwrap().get_header(block_number),
+            Some(header.clone())
+        );
+        assert_eq!(
+            store.head_cache().unwrap().get_body_by_hash(hash),
+            Some(body)
+        );
+
+        // Replace the canonical block with a sibling
+        let (mut other_header, other_body) = create_block_for_testing();
+        other_header.timestamp += 1;
+        let other_hash = other_header.compute_block_hash();
+        store
+            .add_block(Block::new(other_header.clone(), other_body))
+            .unwrap();
+        store.set_canonical_block(block_number, other_hash).unwrap();
+        assert!(store
+            .head_cache()
+            .unwrap()
+            .get_header_by_hash(hash)
+            .is_none());
+
+        assert_eq!(
+            store.get_block_header(block_number).unwrap(),
+            Some(other_header.clone())
+        );
+        assert_eq!(
+            store.head_cache().unwrap().get_hash(block_number),
+            Some(other_hash)
+        );
+
+        // Unsetting the canonical block should not leave stale entries behind
+        store.unset_canonical_block(block_number).unwrap();
+        assert!(store.head_cache().unwrap().get_hash(block_number).is_none());
+        assert_eq!(store.get_block_header(block_number).unwrap(), None);
+        assert_eq!(store.get_block_body(block_number).unwrap(), None);
+    }
+
+    fn test_head_cache_skips_non_canonical(store: Store) {
+        let (header, body) = create_block_for_testing();
+        let block_number = header.number;
+        let hash = header.compute_block_hash();
+        store.add_block(Block::new(header, body)).unwrap();
+        store.set_canonical_block(block_number, hash).unwrap();
+        store.update_latest_block_number(block_number).unwrap();
+
+        let (mut sibling_header, sibling_body) = create_block_for_testing();
+        sibling_header.timestamp += 1;
+        let sibling_hash = sibling_header.compute_block_hash();
+        store
+            .add_block(Block::new(sibling_header.clone(), sibling_body))
+            .unwrap();
+
+        assert_eq!(
+            store.get_block_header_by_hash(sibling_hash).unwrap(),
+            Some(sibling_header)
+        );
+        assert!(store.get_block_by_hash(sibling_hash).unwrap().is_some());
+        let cache = store.head_cache().unwrap();
+        assert!(cache.get_header_by_hash(sibling_hash).is_none());
+        assert!(cache.get_body_by_hash(sibling_hash).is_none());
+        assert!(cache.get_hash(block_number).is_none());
+    }
+
+    fn test_find_canonical_ancestor(store: Store) {
+        let (header, _) = create_block_for_testing();
+        let hash = header.compute_block_hash();
+        store.add_block_header(hash, header.clone()).unwrap();
+        store.set_canonical_block(header.number, hash).unwrap();
+        // A side chain of two blocks forking off the canonical block
+        let mut side_chain = vec![];
+        let mut parent_hash = hash;
+        for number in header.number + 1..header.number + 3 {
+            let side_header = BlockHeader {
+                number,
+                parent_hash,
+                ..header.clone()
+            };
+            parent_hash = side_header.compute_block_hash();
+            store.add_block_header(parent_hash, side_header).unwrap();
+            side_chain.push(parent_hash);
+        }
+
+        assert_eq!(store.find_canonical_ancestor(hash).unwrap(), Some(hash));
+        assert_eq!(
+            store.find_canonical_ancestor(side_chain[1]).unwrap(),
+            Some(hash)
+        );
+        assert_eq!(store.find_canonical_ancestor(H256::random()).unwrap(), None);
+    }
+
+    fn test_canonical_block_numbers(store: Store) {
+        let (block_a, block_b) = (H256::random(), H256::random());
+        store.set_canonical_block(1, block_a).unwrap();
+        assert_eq!(store.get_canonical_block_number(block_a).unwrap(), Some(1));
+
+        // Reorged out blocks leave the index
+        store.set_canonical_block(1, block_b).unwrap();
+        assert_eq!(store.get_canonical_block_number(block_a).unwrap(), None);
+        assert_eq!(store.get_canonical_block_number(block_b).unwrap(), Some(1));
+        store.unset_canonical_block(1).unwrap();
+        assert_eq!(store.get_canonical_block_number(block_b).unwrap(), None);
+    }
+
+    fn test_oldest_block_with_state(store: Store) {
+        let mut state_trie = store.new_state_trie_for_test();
+        state_trie
+            .insert(vec![1; 32], AccountState::default().encode_to_vec())
+            .unwrap();
+        let state_root = state_trie.hash().unwrap();
+        for number in 0..5 {
+            let (mut header, body) = create_block_for_testing();
+            header.number = number;
+            // The state of the first blocks was never stored
+            header.state_root = if number < 2 {
+                H256::random()
+            } else {
+                state_root
+            };
+            let hash = header.compute_block_hash();
+            store.add_block(Block::new(header, body)).unwrap();
+            store.set_canonical_block(number, hash).unwrap();
+        }
+        assert_eq!(store.get_oldest_block_with_state().unwrap(), None);
+        store.update_latest_block_number(4).unwrap();
+
+        assert!(!store.has_state(1).unwrap());
+        assert!(store.has_state(2).unwrap());
+        assert!(!store.has_state(5).unwrap());
+        assert_eq!(store.get_oldest_block_with_state().unwrap(), Some(2));
+    }
+
+    fn test_state_dump_roundtrip(store: Store) {
+        let accounts = HashMap::from([
+            (
+                Address::random(),
+                GenesisAccount {
+                    code: Bytes::from_static(&[0x60, 0x00]),
+                    storage: HashMap::from([(H256::random(), U256::from(7))]),
+                    balance: U256::from(100),
+                    nonce: 1,
+                },
+            ),
+            (
+                Address::random(),
+                GenesisAccount {
+                    code: Bytes::new(),
+                    storage: HashMap::new(),
+                    balance: U256::from(5),
+                    nonce: 0,
+                },
+            ),
+        ]);
+        let state_root = store.setup_genesis_state_trie(accounts).unwrap();
+        let (mut header, _) = create_block_for_testing();
+        header.number = 0;
+        header.state_root = state_root;
+        let hash = header.compute_block_hash();
+        store.add_block_header(hash, header).unwrap();
+        store.set_canonical_block(0, hash).unwrap();
+
+        let mut dump = vec![];
+        assert_eq!(store.export_state(0, &mut dump).unwrap(), 2);
+        let imported = Store::new("state-dump", EngineType::InMemory).unwrap();
+        let dump_header = imported.import_state(dump.as_slice()).unwrap();
+        assert_eq!(
+            (dump_header.block_hash, dump_header.state_root),
+            (hash, state_root)
+        );
+        let mut exported_again = vec![];
+        let mut lines = dump.split(|byte| *byte == b'\n');
+        lines.next();
+        for (hashed_address, _) in imported.iter_accounts(state_root) {
+            let account: crate::state_dump::DumpAccount =
+                serde_json::from_slice(lines.next().unwrap()).unwrap();
+            assert_eq!(account.hashed_address, hashed_address);
+            exported_again.push(account);
+        }
+        assert!(exported_again
+            .iter()
+            .any(|account| account.storage.len() == 1));
+
+        // Dumps whose accounts don't match their state root are rejected
+        let tampered = String::from_utf8(dump)
+            .unwrap()
+            .replace(r#""balance":"0x5""#, r#""balance":"0x6""#);
+        assert!(imported.import_state(tampered.as_bytes()).is_err());
+    }
+
+    fn test_snapshot(store: Store) {
+        use crate::snapshot::SNAPSHOT_BATCH_LEAVES;
+
+        // Each account takes two leaves, its own and its storage slot
+        let addresses: Vec<_> = (0..SNAPSHOT_BATCH_LEAVES as u64 / 2 + 100)
+            .map(Address::from_low_u64_be)
+            .collect();
+        let accounts = addresses
+            .iter()
+            .enumerate()
+            .map(|(i, address)| {
+                let account = GenesisAccount {
+                    code: Bytes::new(),
+                    storage: HashMap::from([(H256::from_low_u64_be(1), U256::from(i + 1))]),
+                    balance: U256::from(i),
+                    nonce: 0,
+                };
+                (*address, account)
+            })
+            .collect();
+        let state_root = store.setup_genesis_state_trie(accounts).unwrap();
+        let (mut header, _) = create_block_for_testing();
+        header.number = 0;
+        header.state_root = state_root;
+        let block_hash = header.compute_block_hash();
+        store.add_block_header(block_hash, header.clone()).unwrap();
+
+        store.start_snapshot(&header).unwrap();
+        let status = store.generate_snapshot_batch().unwrap().unwrap();
+        assert_eq!(status.accounts, SNAPSHOT_BATCH_LEAVES as u64 / 2);
+        assert_eq!(status.storage_slots, SNAPSHOT_BATCH_LEAVES as u64 / 2);
+        assert_eq!(status.storage_marker, None);
+        // Snapshots are only read from once complete
+        assert!(store
+            .iter_snapshot_accounts_from(state_root, H256::zero())
+            .unwrap()
+            .is_none());
+
+        // A block on top of the snapshot block updates the accounts already generated, the
+        // rest are generated from its state
+        let marker = status.generator_marker.unwrap();
+        let is_generated = |address: &&Address| H256::from_slice(&hash_address(address)) < marker;
+        let mut generated = addresses.iter().filter(is_generated);
+        let (updated, removed) = (*generated.next().unwrap(), *generated.next().unwrap());
+        let not_generated = *addresses
+            .iter()
+            .find(|address| !is_generated(address))
+            .unwrap();
+        let info = |balance: u64| AccountInfo {
+            code_hash: code_hash(&Bytes::new()),
+            balance: U256::from(balance),
+            nonce: 1,
+        };
+        let account_updates = vec![
+            AccountUpdate {
+                address: updated,
+                info: Some(info(7)),
+                added_storage: HashMap::from([
+                    (H256::from_low_u64_be(1), U256::zero()),
+                    (H256::from_low_u64_be(2), U256::from(3)),
+                ]),
+                ..AccountUpdate::new(updated)
+            },
+            AccountUpdate::removed(removed),
+            AccountUpdate {
+                info: Some(info(9)),
+                ..AccountUpdate::new(not_generated)
+            },
+        ];
+        let new_state_root = store
+            .apply_account_updates(block_hash, &account_updates)
+            .unwrap()
+            .unwrap();
+        let (mut child, _) = create_block_for_testing();
+        child.number = 1;
+        child.parent_hash = block_hash;
+        child.state_root = new_state_root;
+        let child_hash = child.compute_block_hash();
+        store
+            .advance_snapshot(&child, child_hash, &account_updates)
+            .unwrap();
+        // Blocks of other forks are ignored
+        store
+            .advance_snapshot(&header, H256::random(), &account_updates)
+            .unwrap();
+        while !store
+            .generate_snapshot_batch()
+            .unwrap()
+            .unwrap()
+            .is_complete()
+        {}
+
+        let status = store.snapshot_status().unwrap().unwrap();
+        assert_eq!((status.block_number, status.block_hash), (1, child_hash));
+        assert!(store
+            .iter_snapshot_accounts_from(state_root, H256::zero())
+            .unwrap()
+            .is_none());
+        let snapshot_accounts: Vec<_> = store
+            .iter_snapshot_accounts_from(new_state_root, H256::zero())
+            .unwrap()
+            .unwrap()
+            .collect();
+        let trie_accounts: Vec<_> = store.iter_accounts(new_state_root).collect();
+        assert_eq!(snapshot_accounts.len(), addresses.len() - 1);
+        assert_eq!(snapshot_accounts, trie_accounts);
+        for (hashed_address, _) in trie_accounts {
+            let snapshot_storage: Vec<_> = store
+                .iter_snapshot_storage_from(new_state_root, hashed_address, H256::zero())
+                .unwrap()
+                .unwrap()
+                .collect();
+            let trie_storage: Vec<_> = store
+                .iter_storage(new_state_root, hashed_address)
+                .unwrap()
+                .unwrap()
+                .collect();
+            assert_eq!(snapshot_storage, trie_storage);
+        }
+        let removed = H256::from_slice(&hash_address(&removed));
+        assert_eq!(
+            store
+                .iter_snapshot_storage_from(new_state_root, removed, H256::zero())
+                .unwrap()
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    fn test_snapshot_split_storage(store: Store) {
+        use crate::snapshot::SNAPSHOT_BATCH_LEAVES;
+
+        let address = Address::from_low_u64_be(1);
+        let storage = (1..=SNAPSHOT_BATCH_LEAVES as u64 + 10)
+            .map(|i| (H256::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let account = GenesisAccount {
+            code: Bytes::new(),
+            storage,
+            balance: U256::one(),
+            nonce: 0,
+        };
+        let state_root = store
+            .setup_genesis_state_trie(HashMap::from([(address, account)]))
+            .unwrap();
+        let (mut header, _) = create_block_for_testing();
+        header.number = 0;
+        header.state_root = state_root;
+        let block_hash = header.compute_block_hash();
+        store.add_block_header(block_hash, header.clone()).unwrap();
+
+        // The storage trie doesn't fit in a single batch along with the account
+        store.start_snapshot(&header).unwrap();
+        let status = store.generate_snapshot_batch().unwrap().unwrap();
+        let hashed_address = H256::from_slice(&hash_address(&address));
+        assert_eq!(status.generator_marker, Some(hashed_address));
+        assert_eq!(status.accounts, 1);
+        assert_eq!(status.storage_slots, SNAPSHOT_BATCH_LEAVES as u64 - 1);
+        let storage_marker = status.storage_marker.unwrap();
+
+        // Slots before the storage marker are updated by the blocks, the rest are generated
+        let (mut generated, mut not_generated) = (None, None);
+        for i in 1..=SNAPSHOT_BATCH_LEAVES as u64 + 10 {
+            let key = H256::from_low_u64_be(i);
+            if H256::from_slice(&hash_key(&key)) < storage_marker {
+                generated.get_or_insert(key);
+            } else {
+                not_generated.get_or_insert(key);
+            }
+        }
+        let account_updates = vec![AccountUpdate {
+            info: Some(AccountInfo {
+                code_hash: code_hash(&Bytes::new()),
+                balance: U256::from(7),
+                nonce: 1,
+            }),
+            added_storage: HashMap::from([
+                (generated.unwrap(), U256::zero()),
+                (not_generated.unwrap(), U256::from(3)),
+            ]),
+            ..AccountUpdate::new(address)
+        }];
+        let new_state_root = store
+            .apply_account_updates(block_hash, &account_updates)
+            .unwrap()
+            .unwrap();
+        let (mut child, _) = create_block_for_testing();
+        child.number = 1;
+        child.parent_hash = block_hash;
+        child.state_root = new_state_root;
+        store
+            .advance_snapshot(&child, child.compute_block_hash(), &account_updates)
+            .unwrap();
+        while !store
+            .generate_snapshot_batch()
+            .unwrap()
+            .unwrap()
+            .is_complete()
+        {}
+
+        let status = store.snapshot_status().unwrap().unwrap();
+        assert_eq!(status.accounts, 1);
+        let snapshot_accounts: Vec<_> = store
+            .iter_snapshot_accounts_from(new_state_root, H256::zero())
+            .unwrap()
+            .unwrap()
+            .collect();
+        let trie_accounts: Vec<_> = store.iter_accounts(new_state_root).collect();
+        assert_eq!(snapshot_accounts, trie_accounts);
+        let snapshot_storage: Vec<_> = store
+            .iter_snapshot_storage_from(new_state_root, hashed_address, H256::zero())
+            .unwrap()
+            .unwrap()
+            .collect();
+        let trie_storage: Vec<_> = store
+            .iter_storage(new_state_root, hashed_address)
+            .unwrap()
+            .unwrap()
+            .collect();
+        assert_eq!(snapshot_storage.len(), SNAPSHOT_BATCH_LEAVES + 9);
+        assert_eq!(snapshot_storage, trie_storage);
+    }
+
+    fn test_snapshot_off_canonical_chain(store: Store) {
+        let state_root = store
+            .setup_genesis_state_trie(HashMap::from([(
+                Address::from_low_u64_be(1),
+                GenesisAccount {
+                    code: Bytes::new(),
+                    storage: HashMap::new(),
+                    balance: U256::one(),
+                    nonce: 0,
+                },
+            )]))
+            .unwrap();
+        let (mut genesis, _) = create_block_for_testing();
+        genesis.number = 0;
+        genesis.state_root = state_root;
+        let genesis_hash = genesis.compute_block_hash();
+        store
+            .add_block_header(genesis_hash, genesis.clone())
+            .unwrap();
+        store.set_canonical_block(0, genesis_hash).unwrap();
+        store.update_latest_block_number(0).unwrap();
+        // Two forks built on top of the genesis block
+        let fork = |gas_limit| {
+            let mut header = genesis.clone();
+            header.number = 1;
+            header.parent_hash = genesis_hash;
+            header.gas_limit = gas_limit;
+            let hash = header.compute_block_hash();
+            store.add_block_header(hash, header.clone()).unwrap();
+            (header, hash)
+        };
+        let (block_a, hash_a) = fork(1);
+        let (block_b, hash_b) = fork(2);
+
+        store.start_snapshot(&genesis).unwrap();
+        store.advance_snapshot(&block_a, hash_a, &[]).unwrap();
+        // The snapshot block descends from the canonical head
+        store
+            .restart_snapshot_off_canonical_chain(&genesis)
+            .unwrap();
+        let status = store.snapshot_status().unwrap().unwrap();
+        assert_eq!(status.block_hash, hash_a);
+        assert!(store.snapshot_follows_canonical_chain(&status).unwrap());
+
+        // The fork choice moves to the other fork
+        store.set_canonical_block(1, hash_b).unwrap();
+        store.update_latest_block_number(1).unwrap();
+        assert!(!store.snapshot_follows_canonical_chain(&status).unwrap());
+        store
+            .restart_snapshot_off_canonical_chain(&block_b)
+            .unwrap();
+        let status = store.snapshot_status().unwrap().unwrap();
+        assert_eq!((status.block_number, status.block_hash), (1, hash_b));
+        assert_eq!(status.generator_marker, Some(H256::zero()));
+        assert!(store.snapshot_follows_canonical_chain(&status).unwrap());
+    }
+
+    fn test_trie_ranges(store: Store) {
+        let accounts = (0..10)
+            .map(|i| {
+                (
+                    Address::from_low_u64_be(i),
+                    GenesisAccount {
+                        code: Bytes::new(),
+                        storage: HashMap::from([(H256::from_low_u64_be(i), U256::from(i + 1))]),
+                        balance: U256::from(i),
+                        nonce: 0,
+                    },
+                )
+            })
+            .collect();
+        let state_root = store.setup_genesis_state_trie(accounts).unwrap();
+        let all: Vec<_> = store.iter_accounts(state_root).collect();
+        assert_eq!(all.len(), 10);
+
+        // Ranges continue from the key after the last leaf of the previous one
+        let first = store
+            .account_range(state_root, H256::zero(), 4, true)
+            .unwrap();
+        assert_eq!(first.leaves, all[..4]);
+        assert!(first.has_more);
+        let proof = first.proof.unwrap();
+        let values: Vec<_> = first
+            .leaves
+            .iter()
+            .map(|(_, account)| account.encode_to_vec())
+            .collect();
+        let keys: Vec<_> = first.leaves.iter().map(|(hash, _)| *hash).collect();
+        assert!(
+            ethrex_trie::verify_range(state_root, &H256::zero(), &keys, &values, &proof).unwrap()
+        );
+        let next_start = H256::from_uint(&(first.leaves[3].0.into_uint() + 1));
+        let rest = store
+            .account_range(state_root, next_start, 10, false)
+            .unwrap();
+        assert_eq!(rest.leaves, all[4..]);
+        assert!(!rest.has_more && rest.proof.is_none());
+
+        // Prefix iteration only yields the keys starting with the prefix
+        let (hashed_address, _) = all[5];
+        let with_prefix: Vec<_> = store
+            .iter_accounts_with_prefix(state_root, &hashed_address.as_bytes()[..1])
+            .unwrap()
+            .collect();
+        assert!(with_prefix.contains(&all[5]));
+        assert!(with_prefix
+            .iter()
+            .all(|(hash, _)| hash.as_bytes()[0] == hashed_address.as_bytes()[0]));
+        assert!(store
+            .iter_accounts_with_prefix(state_root, &[0; 33])
+            .is_err());
+
+        let slots = store
+            .storage_range(state_root, hashed_address, H256::zero(), 1, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(slots.leaves.len(), 1);
+        assert!(!slots.has_more && slots.proof.is_some());
+        let slot_prefix = slots.leaves[0].0.as_bytes()[..2].to_vec();
+        assert_eq!(
+            store
+                .iter_storage_with_prefix(state_root, hashed_address, &slot_prefix)
+                .unwrap()
+                .unwrap()
+                .collect::<Vec<_>>(),
+            slots.leaves
+        );
+        assert!(store
+            .storage_range(state_root, H256::zero(), H256::zero(), 1, false)
+            .unwrap()
+            .is_none());
+    }
+
+    fn test_remove_expired_payloads(store: Store) {
+        for (payload_id, timestamp) in [(1, 10), (2, 20), (3, 30)] {
+            let (mut header, body) = create_block_for_testing();
+            header.timestamp = timestamp;
+            store
+                .add_payload(payload_id, Block::new(header, body))
+                .unwrap();
+        }
+        store.remove_payloads_older_than(20).unwrap();
+        assert!(store.get_payload(1).unwrap().is_none());
+        assert!(store.get_payload(2).unwrap().is_some());
+
+        store.remove_payload(3).unwrap();
+        assert!(store.get_payload(3).unwrap().is_none());
+        assert_eq!(store.engine.get_payload_ids().unwrap(), vec![2]);
+    }
+
+    fn test_prune_blob_sidecars(store: Store) {
+        let sidecars = BlobsBundle {
+            blobs: vec![[1; BYTES_PER_BLOB]],
+            commitments: vec![[2; 48]],
+            proofs: vec![[3; 48]],
+        };
+        let hashes = [H256::random(), H256::random()];
+        for (block_hash, timestamp) in hashes.into_iter().zip([10, 20]) {
+            store
+                .add_blob_sidecars(block_hash, timestamp, sidecars.clone())
+                .unwrap();
+        }
+        assert_eq!(store.prune_blob_sidecars(20).unwrap(), 1);
+        assert!(store.get_blob_sidecars(hashes[0]).unwrap().is_none());
+        assert_eq!(store.get_blob_sidecars(hashes[1]).unwrap(), Some(sidecars));
+        assert_eq!(
+            store.engine.get_blob_sidecar_blocks().unwrap(),
+            vec![(hashes[1], 20)]
+        );
+    }
+
+    fn test_verify_chain(store: Store) {
+        use crate::verify::{IntegrityIssue, VerifyOptions};
+
+        let mut parent_hash = H256::zero();
+        let mut hashes = vec![];
+        for number in 0..4 {
+            let (mut header, mut body) = create_block_for_testing();
+            header.number = number;
+            header.parent_hash = parent_hash;
+            header.state_root = *EMPTY_TRIE_HASH;
+            if number == 2 {
+                // Break the link with the previous block
+                header.parent_hash = H256::random();
+            }
+            if number != 1 {
+                body.transactions.clear();
+            }
+            let hash = header.compute_block_hash();
+            if number == 1 {
+                // Store the block without its transaction lookups
+                store.add_block_header(hash, header).unwrap();
+                store.add_block_body(hash, body.clone()).unwrap();
+                store.add_block_number(hash, number).unwrap();
+                store
+                    .add_receipt(hash, 0, Receipt::new(TxType::EIP1559, true, 0, vec![]))
+                    .unwrap();
+            } else {
+                store.add_block(Block::new(header, body)).unwrap();
+            }
+            store.set_canonical_block(number, hash).unwrap();
+            hashes.push(hash);
+            parent_hash = hash;
+        }
+        store.update_latest_block_number(3).unwrap();
+
+        let report = store.verify_chain(&VerifyOptions::default()).unwrap();
+        assert_eq!(report.checked_blocks, 4);
+        let (_, body) = create_block_for_testing();
+        let tx_hashes: Vec<_> = body
+            .transactions
+            .iter()
+            .map(|tx| tx.compute_hash())
+            .collect();
+        assert_eq!(
+            report.issues,
+            vec![
+                IntegrityIssue::TransactionLocationMismatch(1, 0, tx_hashes[0]),
+                IntegrityIssue::MissingReceipt(1, hashes[1], 1),
+                IntegrityIssue::TransactionLocationMismatch(1, 1, tx_hashes[1]),
+                IntegrityIssue::BrokenParentLink(
+                    2,
+                    store.get_block_header(2).unwrap().unwrap().parent_hash
+                ),
+            ]
+        );
+
+        // Only the transaction lookups can be repaired
+        let options = VerifyOptions {
+            repair: true,
+            state_sample_interval: Some(1),
+        };
+        let report = store.verify_chain(&options).unwrap();
+        assert_eq!(report.repaired, 2);
+        assert_eq!(report.issues.len(), 2);
+        assert!(store.verify_chain(&options).unwrap().repaired == 0);
+    }
+
+    fn test_prune_pre_merge_history(store: Store) {
+        use crate::verify::{IntegrityIssue, VerifyOptions};
+
+        let mut parent_hash = H256::zero();
+        let mut hashes = vec![];
+        for number in 0..4 {
+            let (mut header, body) = create_block_for_testing();
+            header.number = number;
+            header.parent_hash = parent_hash;
+            header.difficulty = if number < 2 {
+                U256::one()
+            } else {
+                U256::zero()
+            };
+            let hash = header.compute_block_hash();
+            store.add_block(Block::new(header, body.clone())).unwrap();
+            for index in 0..body.transactions.len() as Index {
+                store
+                    .add_receipt(hash, index, Receipt::new(TxType::EIP1559, true, 0, vec![]))
+                    .unwrap();
+            }
+            store.set_canonical_block(number, hash).unwrap();
+            hashes.push(hash);
+            parent_hash = hash;
+        }
+        store.update_earliest_block_number(0).unwrap();
+        store.update_latest_block_number(3).unwrap();
+        assert_eq!(store.get_merge_block_number().unwrap(), Some(2));
+        assert_eq!(store.get_history_start_block_number().unwrap(), 0);
+
+        assert_eq!(store.prune_pre_merge_history().unwrap(), 2);
+        assert_eq!(store.get_history_start_block_number().unwrap(), 2);
+        for (number, hash) in hashes.iter().enumerate() {
+            let pruned = number < 2;
+            assert!(store.get_block_header_by_hash(*hash).unwrap().is_some());
+            assert_eq!(
+                store.get_block_body_by_hash(*hash).unwrap().is_none(),
+                pruned
+            );
+            assert_eq!(
+                store.get_receipt_by_hash(*hash, 0).unwrap().is_none(),
+                pruned
+            );
+        }
+        // The pruned blocks are not reported as missing history
+        let report = store.verify_chain(&VerifyOptions::default()).unwrap();
+        assert!(!report.issues.iter().any(|issue| matches!(
+            issue,
+            IntegrityIssue::MissingBody(..) | IntegrityIssue::MissingReceipt(..)
+        )));
+        assert_eq!(store.prune_pre_merge_history().unwrap(), 0);
+    }
+
     fn test_store_account_code(store: Store) {
         let code_hash = H256::random();
         let code = Bytes::from("kiwi");
@@ -1249,6 +2749,104 @@ mod tests {
         assert_eq!(txs, HashMap::from([(blob_tx.sender(), vec![blob_tx])]));
     }
 
+    fn test_store_address_transactions(mut store: Store) {
+        store.enable_address_index();
+        let tx = Transaction::decode_canonical(&hex!("f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4")).unwrap();
+        let tx_hash = tx.compute_hash();
+        let sender = tx.sender();
+        let TxKind::Call(recipient) = tx.to() else {
+            panic!("Expected a call transaction");
+        };
+        let block_at = |number, extra_data: &'static [u8]| {
+            Block::new(
+                BlockHeader {
+                    number,
+                    extra_data: Bytes::from_static(extra_data),
+                    ..Default::default()
+                },
+                BlockBody {
+                    transactions: vec![tx.clone()],
+                    ..Default::default()
+                },
+            )
+        };
+        let (first, second, orphan) = (block_at(1, b""), block_at(2, b""), block_at(2, b"orphan"));
+        for block in [&first, &second, &orphan] {
+            store.add_block(block.clone()).unwrap();
+        }
+        store.set_canonical_block(1, first.hash()).unwrap();
+        store.set_canonical_block(2, second.hash()).unwrap();
+
+        let expected = vec![(2, 0, tx_hash), (1, 0, tx_hash)];
+        assert_eq!(
+            store.get_address_transactions(sender, 0, 10).unwrap(),
+            expected
+        );
+        assert_eq!(
+            store.get_address_transactions(recipient, 0, 10).unwrap(),
+            expected
+        );
+        assert_eq!(
+            store.get_address_transactions(sender, 1, 10).unwrap(),
+            vec![(1, 0, tx_hash)]
+        );
+        // Pages smaller than the transactions of non-canonical blocks read from the index
+        assert_eq!(
+            store.get_address_transactions(sender, 0, 1).unwrap(),
+            vec![(2, 0, tx_hash)]
+        );
+        assert_eq!(
+            store.get_address_transactions(sender, 1, 1).unwrap(),
+            vec![(1, 0, tx_hash)]
+        );
+        assert!(store
+            .get_address_transactions(sender, 2, 1)
+            .unwrap()
+            .is_empty());
+        assert!(store
+            .get_address_transactions(Address::random(), 0, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    fn test_rebuild_receipt_blooms(store: Store) {
+        let tx = Transaction::decode_canonical(&hex!("f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4")).unwrap();
+        let block = Block::new(
+            BlockHeader {
+                number: 1,
+                ..Default::default()
+            },
+            BlockBody {
+                transactions: vec![tx],
+                ..Default::default()
+            },
+        );
+        let block_hash = block.hash();
+        store.add_block(block).unwrap();
+        store.set_canonical_block(1, block_hash).unwrap();
+        store.update_latest_block_number(1).unwrap();
+        let receipt = Receipt::new(
+            TxType::Legacy,
+            true,
+            21000,
+            vec![Log {
+                address: Address::random(),
+                topics: vec![H256::random()],
+                data: Bytes::new(),
+            }],
+        );
+        let mut outdated = receipt.clone();
+        outdated.bloom = Bloom::zero();
+        store.add_receipt(block_hash, 0, outdated).unwrap();
+
+        assert_eq!(store.rebuild_receipt_blooms().unwrap(), 1);
+        assert_eq!(
+            store.get_receipt_by_hash(block_hash, 0).unwrap(),
+            Some(receipt)
+        );
+        assert_eq!(store.rebuild_receipt_blooms().unwrap(), 0);
+    }
+
     fn blobs_bundle_loadtest(store: Store) {
         // Write a bundle of 6 blobs 10 times
         // If this test fails please adjust the max_size in the DB config
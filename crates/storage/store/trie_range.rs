@@ -0,0 +1,126 @@
+use ethrex_core::{types::AccountState, H256, U256};
+
+use crate::{error::StoreError, Store};
+
+/// Consecutive leaves of a trie in key order, as returned by [Store::account_range] and
+/// [Store::storage_range]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieRange<T> {
+    pub leaves: Vec<(H256, T)>,
+    /// Proof of the starting key and the last leaf of the range, only built if requested
+    pub proof: Option<Vec<Vec<u8>>>,
+    /// Whether there are more leaves after the last one of the range, the next range
+    /// can be requested starting from the key after it
+    pub has_more: bool,
+}
+
+impl Store {
+    /// Returns up to `limit` accounts of the state trie given by the state_root, starting from
+    /// the given hashed address, and the proof of the range if `with_proof` is set
+    /// Does not check that the state_root is valid
+    pub fn account_range(
+        &self,
+        state_root: H256,
+        starting_hash: H256,
+        limit: usize,
+        with_proof: bool,
+    ) -> Result<TrieRange<AccountState>, StoreError> {
+        let (leaves, has_more) =
+            take_range(self.iter_accounts_from(state_root, starting_hash)?, limit);
+        let proof = if with_proof {
+            Some(self.get_account_range_proof(
+                state_root,
+                starting_hash,
+                leaves.last().map(|(hash, _)| *hash),
+            )?)
+        } else {
+            None
+        };
+        Ok(TrieRange {
+            leaves,
+            proof,
+            has_more,
+        })
+    }
+
+    /// Returns up to `limit` storage slots of the account given by hashed_address, starting from
+    /// the given hashed key, and the proof of the range if `with_proof` is set.
+    /// Returns None if the account doesn't exist
+    /// Does not check that the state_root is valid
+    pub fn storage_range(
+        &self,
+        state_root: H256,
+        hashed_address: H256,
+        starting_hash: H256,
+        limit: usize,
+        with_proof: bool,
+    ) -> Result<Option<TrieRange<U256>>, StoreError> {
+        let Some(iter) = self.iter_storage_from(state_root, hashed_address, starting_hash)? else {
+            return Ok(None);
+        };
+        let (leaves, has_more) = take_range(iter, limit);
+        let proof = if with_proof {
+            self.get_storage_range_proof(
+                state_root,
+                hashed_address,
+                starting_hash,
+                leaves.last().map(|(hash, _)| *hash),
+            )?
+        } else {
+            None
+        };
+        Ok(Some(TrieRange {
+            leaves,
+            proof,
+            has_more,
+        }))
+    }
+
+    /// Returns an iterator across the accounts of the state trie given by the state_root whose
+    /// hashed address starts with the given prefix, seeking the trie to the first of them
+    /// Does not check that the state_root is valid
+    pub fn iter_accounts_with_prefix<'a>(
+        &self,
+        state_root: H256,
+        prefix: &'a [u8],
+    ) -> Result<impl Iterator<Item = (H256, AccountState)> + 'a, StoreError> {
+        Ok(self
+            .iter_accounts_from(state_root, prefix_start(prefix)?)?
+            .take_while(move |(hash, _)| hash.as_bytes().starts_with(prefix)))
+    }
+
+    /// Returns an iterator across the storage slots of the account given by hashed_address whose
+    /// hashed key starts with the given prefix, seeking the trie to the first of them.
+    /// Returns None if the account doesn't exist
+    /// Does not check that the state_root is valid
+    pub fn iter_storage_with_prefix<'a>(
+        &self,
+        state_root: H256,
+        hashed_address: H256,
+        prefix: &'a [u8],
+    ) -> Result<Option<impl Iterator<Item = (H256, U256)> + 'a>, StoreError> {
+        Ok(self
+            .iter_storage_from(state_root, hashed_address, prefix_start(prefix)?)?
+            .map(|iter| iter.take_while(move |(hash, _)| hash.as_bytes().starts_with(prefix))))
+    }
+}
+
+/// Collects up to `limit` leaves, checking if there are more after them
+fn take_range<T>(iter: impl Iterator<Item = (H256, T)>, limit: usize) -> (Vec<(H256, T)>, bool) {
+    let mut iter = iter.peekable();
+    let leaves = iter.by_ref().take(limit).collect();
+    (leaves, iter.peek().is_some())
+}
+
+/// Returns the lowest key starting with the given prefix
+fn prefix_start(prefix: &[u8]) -> Result<H256, StoreError> {
+    if prefix.len() > H256::len_bytes() {
+        return Err(StoreError::Custom(format!(
+            "Key prefix of {} bytes is longer than a key",
+            prefix.len()
+        )));
+    }
+    let mut start = H256::zero();
+    start.0[..prefix.len()].copy_from_slice(prefix);
+    Ok(start)
+}
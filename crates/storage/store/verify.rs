@@ -0,0 +1,157 @@
+use std::fmt::{self, Display};
+
+use ethrex_core::types::{BlockHash, BlockNumber, Index};
+use ethrex_core::H256;
+
+use crate::{error::StoreError, Store};
+
+/// Problem found in the stored canonical chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    MissingCanonicalHash(BlockNumber),
+    MissingHeader(BlockNumber, BlockHash),
+    /// The stored header doesn't hash to the canonical hash of its number
+    HeaderHashMismatch(BlockNumber, BlockHash),
+    /// The parent hash of the header is not the canonical hash of the previous block
+    BrokenParentLink(BlockNumber, BlockHash),
+    MissingBody(BlockNumber, BlockHash),
+    MissingReceipt(BlockNumber, BlockHash, Index),
+    /// The transaction can't be looked up at its position in the canonical block.
+    /// Repaired by storing the location again.
+    TransactionLocationMismatch(BlockNumber, Index, H256),
+    /// The root node of the state trie of the block is not stored
+    MissingState(BlockNumber, BlockHash),
+}
+
+impl Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingCanonicalHash(number) => {
+                write!(f, "block {number} has no canonical hash")
+            }
+            Self::MissingHeader(number, hash) => {
+                write!(f, "block {number} ({hash:#x}) has no header")
+            }
+            Self::HeaderHashMismatch(number, hash) => {
+                write!(f, "header of block {number} doesn't hash to {hash:#x}")
+            }
+            Self::BrokenParentLink(number, parent_hash) => write!(
+                f,
+                "parent {parent_hash:#x} of block {number} is not the canonical previous block"
+            ),
+            Self::MissingBody(number, hash) => {
+                write!(f, "block {number} ({hash:#x}) has no body")
+            }
+            Self::MissingReceipt(number, hash, index) => {
+                write!(f, "block {number} ({hash:#x}) has no receipt {index}")
+            }
+            Self::TransactionLocationMismatch(number, index, tx_hash) => write!(
+                f,
+                "transaction {tx_hash:#x} is not looked up at index {index} of block {number}"
+            ),
+            Self::MissingState(number, hash) => {
+                write!(f, "block {number} ({hash:#x}) has no state")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Checks the state of one every this amount of blocks, the state is not checked if not set
+    pub state_sample_interval: Option<u64>,
+    /// Fixes the issues that can be repaired from the stored data
+    pub repair: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    pub checked_blocks: u64,
+    pub issues: Vec<IntegrityIssue>,
+    pub repaired: usize,
+}
+
+impl Store {
+    /// Walks the canonical chain from the earliest to the latest block checking the header
+    /// linkage, the presence of the bodies and receipts after the pruned history, the transaction lookups and,
+    /// if requested, the state of a sample of the blocks
+    pub fn verify_chain(&self, options: &VerifyOptions) -> Result<IntegrityReport, StoreError> {
+        let mut report = IntegrityReport::default();
+        let Some(latest) = self.get_latest_block_number()? else {
+            return Ok(report);
+        };
+        let earliest = self.get_earliest_block_number()?.unwrap_or_default();
+        // Only the headers of the blocks whose history was pruned are kept
+        let history_start = self.get_history_start_block_number()?;
+        let mut parent_hash = None;
+        for number in earliest..=latest {
+            report.checked_blocks += 1;
+            let block_hash = self.get_canonical_block_hash(number)?;
+            let checked_parent_hash = std::mem::replace(&mut parent_hash, block_hash);
+            let Some(block_hash) = block_hash else {
+                report
+                    .issues
+                    .push(IntegrityIssue::MissingCanonicalHash(number));
+                continue;
+            };
+            let Some(header) = self.get_block_header_by_hash(block_hash)? else {
+                report
+                    .issues
+                    .push(IntegrityIssue::MissingHeader(number, block_hash));
+                continue;
+            };
+            if header.compute_block_hash() != block_hash {
+                report
+                    .issues
+                    .push(IntegrityIssue::HeaderHashMismatch(number, block_hash));
+            }
+            if number > earliest && checked_parent_hash != Some(header.parent_hash) {
+                report
+                    .issues
+                    .push(IntegrityIssue::BrokenParentLink(number, header.parent_hash));
+            }
+            if options
+                .state_sample_interval
+                .is_some_and(|interval| number % interval.max(1) == 0)
+                && !self.has_state(number)?
+            {
+                report
+                    .issues
+                    .push(IntegrityIssue::MissingState(number, block_hash));
+            }
+            if number < history_start {
+                continue;
+            }
+
+            let Some(body) = self.get_block_body_by_hash(block_hash)? else {
+                report
+                    .issues
+                    .push(IntegrityIssue::MissingBody(number, block_hash));
+                continue;
+            };
+            for (index, transaction) in body.transactions.iter().enumerate() {
+                let index = index as Index;
+                if self.get_receipt_by_hash(block_hash, index)?.is_none() {
+                    report
+                        .issues
+                        .push(IntegrityIssue::MissingReceipt(number, block_hash, index));
+                }
+                let tx_hash = transaction.compute_hash();
+                if self.get_transaction_location(tx_hash)? == Some((number, block_hash, index)) {
+                    continue;
+                }
+                if options.repair {
+                    self.add_transaction_location(tx_hash, number, block_hash, index)?;
+                    report.repaired += 1;
+                } else {
+                    report
+                        .issues
+                        .push(IntegrityIssue::TransactionLocationMismatch(
+                            number, index, tx_hash,
+                        ));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
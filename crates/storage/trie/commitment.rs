@@ -0,0 +1,79 @@
+use ethereum_types::H256;
+
+use crate::{NodeRLP, PathRLP, Trie, TrieError, ValueRLP};
+
+/// Authenticated key-value structure committing to the world state or an account's storage.
+///
+/// The storage layer only updates the state through this trait, so other commitment
+/// schemes (such as a verkle or binary trie) can be plugged in for devnets by opening
+/// them instead of the Merkle Patricia Trie.
+pub trait StateCommitment {
+    /// Retrieves the value stored under the given key
+    fn get(&self, key: &PathRLP) -> Result<Option<ValueRLP>, TrieError>;
+
+    /// Stores the value under the given key, replacing the previous one
+    fn insert(&mut self, key: PathRLP, value: ValueRLP) -> Result<(), TrieError>;
+
+    /// Removes the value stored under the given key, returning it if present
+    fn remove(&mut self, key: PathRLP) -> Result<Option<ValueRLP>, TrieError>;
+
+    /// Persists the pending changes and returns the resulting root commitment
+    fn commit(&mut self) -> Result<H256, TrieError>;
+
+    /// Returns the root commitment without persisting the pending changes
+    fn root(&self) -> H256;
+
+    /// Returns a proof of the presence or absence of the given key
+    fn get_proof(&self, key: &PathRLP) -> Result<Vec<NodeRLP>, TrieError>;
+}
+
+impl StateCommitment for Trie {
+    fn get(&self, key: &PathRLP) -> Result<Option<ValueRLP>, TrieError> {
+        Trie::get(self, key)
+    }
+
+    fn insert(&mut self, key: PathRLP, value: ValueRLP) -> Result<(), TrieError> {
+        Trie::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: PathRLP) -> Result<Option<ValueRLP>, TrieError> {
+        Trie::remove(self, key)
+    }
+
+    fn commit(&mut self) -> Result<H256, TrieError> {
+        self.hash()
+    }
+
+    fn root(&self) -> H256 {
+        self.hash_no_commit()
+    }
+
+    fn get_proof(&self, key: &PathRLP) -> Result<Vec<NodeRLP>, TrieError> {
+        Trie::get_proof(self, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trie_commitment_matches_trie() {
+        let mut trie = Trie::new_temp();
+        let mut commitment: Box<dyn StateCommitment> = Box::new(Trie::new_temp());
+        for i in 0..10_u8 {
+            trie.insert(vec![i], vec![i, i]).unwrap();
+            commitment.insert(vec![i], vec![i, i]).unwrap();
+        }
+        trie.remove(vec![3]).unwrap();
+        assert_eq!(commitment.remove(vec![3]).unwrap(), Some(vec![3, 3]));
+
+        assert_eq!(commitment.root(), trie.hash_no_commit());
+        assert_eq!(commitment.commit().unwrap(), trie.hash().unwrap());
+        assert_eq!(commitment.get(&vec![4]).unwrap(), Some(vec![4, 4]));
+        assert_eq!(
+            commitment.get_proof(&vec![4]).unwrap(),
+            trie.get_proof(&vec![4]).unwrap()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+mod commitment;
 pub mod db;
 mod error;
 mod nibbles;
@@ -23,8 +24,13 @@ pub use self::db::{libmdbx::LibmdbxTrieDB, libmdbx_dupsort::LibmdbxDupsortTrieDB
 pub use self::db::{in_memory::InMemoryTrieDB, TrieDB};
 pub use self::verify_range::verify_range;
 
+pub use self::commitment::StateCommitment;
 pub use self::error::TrieError;
-use self::{node::LeafNode, state::TrieState, trie_iter::TrieIterator};
+use self::{
+    node::{BranchNode, LeafNode},
+    state::TrieState,
+    trie_iter::TrieIterator,
+};
 
 use lazy_static::lazy_static;
 
@@ -86,6 +92,11 @@ impl Trie {
 
     /// Insert an RLP-encoded value into the trie.
     pub fn insert(&mut self, path: PathRLP, value: ValueRLP) -> Result<(), TrieError> {
+        self.insert_nibbles(Nibbles::from_bytes(&path), value)
+    }
+
+    /// Insert an RLP-encoded value into the trie given its path as nibbles
+    fn insert_nibbles(&mut self, path: Nibbles, value: ValueRLP) -> Result<(), TrieError> {
         let root = self.root.take();
         if let Some(root_node) = root
             .map(|root| self.state.get_node(root))
@@ -93,12 +104,11 @@ impl Trie {
             .flatten()
         {
             // If the trie is not empty, call the root node's insertion logic
-            let root_node =
-                root_node.insert(&mut self.state, Nibbles::from_bytes(&path), value.clone())?;
+            let root_node = root_node.insert(&mut self.state, path, value.clone())?;
             self.root = Some(root_node.insert_self(&mut self.state)?)
         } else {
             // If the trie is empty, just add a leaf.
-            let new_leaf = Node::from(LeafNode::new(Nibbles::from_bytes(&path), value));
+            let new_leaf = Node::from(LeafNode::new(path, value));
             self.root = Some(new_leaf.insert_self(&mut self.state)?)
         }
         Ok(())
@@ -147,6 +157,14 @@ impl Trie {
             .unwrap_or(*EMPTY_TRIE_HASH)
     }
 
+    /// Returns true if the trie is empty or its root node is stored in the DB
+    pub fn has_root_node(&self) -> Result<bool, TrieError> {
+        match &self.root {
+            Some(root) => Ok(self.state.get_node(root.clone())?.is_some()),
+            None => Ok(true),
+        }
+    }
+
     /// Obtain a merkle proof for the given path.
     /// The proof will contain all the encoded nodes traversed until reaching the node where the path is stored (including this last node).
     /// The proof will still be constructed even if the path is not stored in the trie, proving its absence.
@@ -233,6 +251,59 @@ impl Trie {
             .unwrap_or(*EMPTY_TRIE_HASH)
     }
 
+    /// Builds an in-memory trie from the given elements and returns its hash, building the
+    /// subtries under each child of the root branch in separate threads.
+    /// Falls back to [Trie::compute_hash_from_unsorted_iter] if the root is not a branch.
+    pub fn compute_hash_in_parallel(elements: Vec<(PathRLP, ValueRLP)>) -> H256 {
+        // The root is only a branch if the paths don't all share their first nibble.
+        // An empty path would be the value of the root branch instead of a child.
+        let first_nibble = |path: &PathRLP| path.first().map(|byte| byte >> 4);
+        if elements.iter().any(|(path, _)| path.is_empty())
+            || elements
+                .iter()
+                .all(|(path, _)| first_nibble(path) == first_nibble(&elements[0].0))
+        {
+            return Self::compute_hash_from_unsorted_iter(elements.into_iter());
+        }
+        let mut subtries: [Vec<(Nibbles, ValueRLP)>; 16] = Default::default();
+        for (path, value) in elements {
+            let path = Nibbles::from_bytes(&path);
+            subtries[path.at(0)].push((path.offset(1), value));
+        }
+        let choices = std::thread::scope(|scope| {
+            let builders = subtries.map(|subtrie| {
+                (!subtrie.is_empty()).then(|| {
+                    scope.spawn(move || {
+                        let mut trie = Trie::stateless();
+                        for (path, value) in subtrie {
+                            // Unwraping here won't panic as our in_memory trie DB won't fail
+                            trie.insert_nibbles(path, value).unwrap();
+                        }
+                        trie.root.unwrap_or_default()
+                    })
+                })
+            });
+            builders.map(|builder| {
+                builder
+                    .map(|builder| builder.join().expect("Subtrie builder panicked"))
+                    .unwrap_or_default()
+            })
+        });
+        BranchNode::new(Box::new(choices)).compute_hash().finalize()
+    }
+
+    /// Builds an in-memory trie from the given elements and returns the proof of the given path
+    pub fn compute_proof_from_unsorted_iter(
+        iter: impl Iterator<Item = (PathRLP, ValueRLP)>,
+        path: &PathRLP,
+    ) -> Result<Vec<NodeRLP>, TrieError> {
+        let mut trie = Trie::stateless();
+        for (path, value) in iter {
+            trie.insert(path, value)?;
+        }
+        trie.get_proof(path)
+    }
+
     /// Creates a new stateless trie. This trie won't be able to store any nodes so all data will be lost after calculating the hash
     /// Only use it for proof verification or computing a hash from an iterator
     pub(crate) fn stateless() -> Trie {
@@ -891,6 +962,20 @@ mod test {
             prop_assert_eq!(hash, cita_hash);
         }
 
+        #[test]
+        fn proptest_compare_hash_in_parallel(data in btree_set(vec(any::<u8>(), 1..100), 1..100)) {
+            let mut cita_trie = cita_trie();
+            for val in data.iter() {
+                cita_trie.insert(val.clone(), val.clone()).unwrap();
+            }
+
+            let hash = Trie::compute_hash_in_parallel(
+                data.into_iter().map(|val| (val.clone(), val)).collect(),
+            );
+            let cita_hash = cita_trie.root().unwrap();
+            prop_assert_eq!(hash.0.to_vec(), cita_hash);
+        }
+
         #[test]
         fn proptest_compare_hash_with_removals(mut data in vec((vec(any::<u8>(), 5..100), any::<bool>()), 1..100)) {
             let mut trie = Trie::new_temp();
@@ -1,4 +1,8 @@
-use crate::{nibbles::Nibbles, node::Node, node_hash::NodeHash, PathRLP, Trie, ValueRLP};
+use std::cmp::Ordering;
+
+use crate::{
+    nibbles::Nibbles, node::Node, node_hash::NodeHash, PathRLP, Trie, TrieError, ValueRLP,
+};
 
 pub struct TrieIterator {
     trie: Trie,
@@ -54,6 +58,75 @@ impl Iterator for TrieIterator {
 }
 
 impl TrieIterator {
+    /// Moves the iterator so that it only yields the nodes leading to paths equal or greater
+    /// than the given one, without traversing the nodes before it
+    pub fn advance(&mut self, key: PathRLP) -> Result<(), TrieError> {
+        self.stack.clear();
+        let Some(root) = &self.trie.root else {
+            return Ok(());
+        };
+        let target = Nibbles::from_raw(&key, false);
+        let mut path = Nibbles::default();
+        let mut next_node_hash = root.clone();
+        while let Some(next_node) = self.trie.state.get_node(next_node_hash.clone())? {
+            let remaining = target.offset(path.len());
+            match &next_node {
+                Node::Branch(branch_node) => {
+                    let Some(&choice) = remaining.as_ref().first() else {
+                        // The key ends at this node, so all of its children come after it
+                        self.stack.push((path, next_node_hash));
+                        break;
+                    };
+                    let choice = choice as usize;
+                    // Add the children after the key to the stack (in reverse order so we process first child first)
+                    for (sibling_choice, sibling) in branch_node
+                        .choices
+                        .iter()
+                        .enumerate()
+                        .skip(choice + 1)
+                        .rev()
+                    {
+                        if sibling.is_valid() {
+                            let mut sibling_path = path.clone();
+                            sibling_path.append(sibling_choice as u8);
+                            self.stack.push((sibling_path, sibling.clone()))
+                        }
+                    }
+                    let child = &branch_node.choices[choice];
+                    if !child.is_valid() {
+                        break;
+                    }
+                    path.append(choice as u8);
+                    next_node_hash = child.clone();
+                }
+                Node::Extension(extension_node) => {
+                    match remaining.compare_prefix(&extension_node.prefix) {
+                        // All paths below this node come after the key
+                        Ordering::Less => {
+                            self.stack.push((path, next_node_hash));
+                            break;
+                        }
+                        Ordering::Equal => {
+                            path.extend(&extension_node.prefix);
+                            next_node_hash = extension_node.child.clone();
+                        }
+                        // All paths below this node come before the key
+                        Ordering::Greater => break,
+                    }
+                }
+                Node::Leaf(leaf) => {
+                    let mut leaf_path = path.clone();
+                    leaf_path.extend(&leaf.partial);
+                    if leaf_path.to_bytes() >= key {
+                        self.stack.push((path, next_node_hash));
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
     // TODO: construct path from nibbles
     pub fn content(self) -> impl Iterator<Item = (PathRLP, ValueRLP)> {
         self.filter_map(|(p, n)| match n {
@@ -90,6 +163,51 @@ mod tests {
         let content = trie.into_iter().content().collect::<Vec<_>>();
         assert_eq!(content, expected_content);
     }
+    #[test]
+    fn trie_iter_advance() {
+        let content = vec![
+            (vec![0, 9], vec![3, 4]),
+            (vec![1, 2], vec![5, 6]),
+            (vec![1, 3], vec![5, 6]),
+            (vec![2, 7], vec![7, 8]),
+        ];
+        for start in [
+            vec![0, 0],
+            vec![1, 2],
+            vec![1, 2, 1],
+            vec![1, 4],
+            vec![3, 0],
+        ] {
+            let expected = content
+                .iter()
+                .filter(|(path, _)| *path >= start)
+                .cloned()
+                .collect::<Vec<_>>();
+            let mut trie = Trie::new_temp();
+            for (path, value) in content.clone() {
+                trie.insert(path, value).unwrap()
+            }
+            let mut iter = trie.into_iter();
+            iter.advance(start).unwrap();
+            assert_eq!(iter.content().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    proptest! {
+
+        #[test]
+        fn proptest_trie_iter_advance(data in btree_map(vec(any::<u8>(), 5..100), vec(any::<u8>(), 5..100), 5..100), start in vec(any::<u8>(), 0..20)) {
+            let expected_content = data.clone().into_iter().filter(|(path, _)| *path >= start).collect::<Vec<_>>();
+            let mut trie = Trie::new_temp();
+            for (path, value) in data.into_iter() {
+                trie.insert(path, value).unwrap()
+            }
+            let mut iter = trie.into_iter();
+            iter.advance(start).unwrap();
+            let content = iter.content().collect::<Vec<_>>();
+            assert_eq!(content, expected_content);
+        }
+    }
     proptest! {
 
         #[test]
@@ -837,5 +837,48 @@ mod tests {
             // Verify the range proof
             assert!(verify_range(root, &keys[0], &keys, &values, &proof).is_err());
         }
+
+        #[test]
+        // Regular Case: Two Edge Proofs, both keys exist, but a leaf in the middle of the range is missing
+        fn proptest_verify_range_regular_case_non_contiguous_range(data in btree_set(vec(any::<u8>(), 32), 200), start in 1_usize..=100_usize, end in 101..200_usize, removed in 1_usize..100_usize) {
+            // Build trie
+            let mut trie = Trie::new_temp();
+            for val in data.iter() {
+                trie.insert(val.clone(), val.clone()).unwrap()
+            }
+            let root = trie.hash().unwrap();
+            // Select range to prove
+            let mut values = data.into_iter().collect::<Vec<_>>()[start..=end].to_vec();
+            // Generate proofs
+            let mut proof = trie.get_proof(&values[0]).unwrap();
+            proof.extend(trie.get_proof(values.last().unwrap()).unwrap());
+            // Remove a leaf that is not at the edges of the range
+            values.remove(removed.min(values.len() - 2));
+            let keys = values.iter().map(|a| H256::from_slice(a)).collect::<Vec<_>>();
+            // Verify the range proof
+            assert!(verify_range(root, &keys[0], &keys, &values, &proof).is_err());
+        }
+
+        #[test]
+        // Regular Case: Two Edge Proofs, both keys exist, but one of the values in the range was modified
+        fn proptest_verify_range_regular_case_modified_value(data in btree_set(vec(any::<u8>(), 32), 200), start in 1_usize..=100_usize, end in 101..200_usize, modified in 0_usize..100_usize) {
+            // Build trie
+            let mut trie = Trie::new_temp();
+            for val in data.iter() {
+                trie.insert(val.clone(), val.clone()).unwrap()
+            }
+            let root = trie.hash().unwrap();
+            // Select range to prove
+            let mut values = data.into_iter().collect::<Vec<_>>()[start..=end].to_vec();
+            let keys = values.iter().map(|a| H256::from_slice(a)).collect::<Vec<_>>();
+            // Generate proofs
+            let mut proof = trie.get_proof(&values[0]).unwrap();
+            proof.extend(trie.get_proof(values.last().unwrap()).unwrap());
+            // Modify one of the values
+            let modified = modified.min(values.len() - 1);
+            values[modified][0] ^= 1;
+            // Verify the range proof
+            assert!(verify_range(root, &keys[0], &keys, &values, &proof).is_err());
+        }
     }
 }
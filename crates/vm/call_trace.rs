@@ -0,0 +1,49 @@
+use alloy_rpc_types_trace::geth::CallFrame;
+use bytes::Bytes;
+use ethrex_core::{serde_utils, Address, U256};
+use serde::Serialize;
+
+/// Call trace of a transaction, in the format returned by geth's `callTracer`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallTrace {
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub from: Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<U256>,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub gas: u64,
+    #[serde(with = "serde_utils::u64::hex_str")]
+    pub gas_used: u64,
+    #[serde(with = "serde_utils::bytes")]
+    pub input: Bytes,
+    #[serde(with = "serde_utils::bytes", skip_serializing_if = "Bytes::is_empty")]
+    pub output: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallTrace>,
+}
+
+impl From<CallFrame> for CallTrace {
+    fn from(frame: CallFrame) -> Self {
+        Self {
+            call_type: frame.typ,
+            from: Address::from_slice(frame.from.as_slice()),
+            to: frame.to.map(|to| Address::from_slice(to.as_slice())),
+            value: frame.value.map(|value| U256(*value.as_limbs())),
+            gas: frame.gas.saturating_to(),
+            gas_used: frame.gas_used.saturating_to(),
+            input: frame.input.0,
+            output: frame.output.map(|output| output.0).unwrap_or_default(),
+            error: frame.error,
+            revert_reason: frame.revert_reason,
+            calls: frame.calls.into_iter().map(CallTrace::from).collect(),
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use ethereum_types::{H160, H256};
-use ethrex_core::types::BlockHash;
+use ethrex_core::{
+    errors::{CodedError, ErrorCode},
+    types::BlockHash,
+};
 use ethrex_storage::error::StoreError;
 use ethrex_trie::TrieError;
 use revm::primitives::{
@@ -7,6 +10,8 @@ use revm::primitives::{
 };
 use thiserror::Error;
 
+use crate::{trace_limits::TraceInterruption, CallTrace};
+
 #[derive(Debug, Error)]
 pub enum EvmError {
     #[error("Invalid Transaction: {0}")]
@@ -21,6 +26,14 @@ pub enum EvmError {
     Custom(String),
     #[error("{0}")]
     Precompile(String),
+    /// The tracing of a block stopped before its transactions finished executing
+    #[error("Tracing stopped by {reason} after {} transactions", traces.len() - 1)]
+    TraceInterrupted {
+        reason: TraceInterruption,
+        /// Traces of the transactions executed, the last one being the partial trace of the
+        /// interrupted transaction
+        traces: Vec<CallTrace>,
+    },
 }
 
 #[derive(Debug, Error)]
@@ -81,6 +94,31 @@ pub enum StateProofsError {
     StorageProofNotFound(RevmAddress, RevmU256),
 }
 
+impl CodedError for EvmError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            EvmError::Transaction(_) | EvmError::Header(_) => ErrorCode::Invalid,
+            EvmError::DB(error) => error.code(),
+            EvmError::ExecutionDB(error) => error.code(),
+            EvmError::Custom(_) | EvmError::Precompile(_) | EvmError::TraceInterrupted { .. } => {
+                ErrorCode::Execution
+            }
+        }
+    }
+}
+
+impl CodedError for ExecutionDBError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            ExecutionDBError::Store(error) => error.code(),
+            ExecutionDBError::Evm(error) => error.code(),
+            ExecutionDBError::Trie(error) => error.code(),
+            // The execution DB is built ahead of the execution, anything missing won't show up later
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
 impl From<RevmError<StoreError>> for EvmError {
     fn from(value: RevmError<StoreError>) -> Self {
         match value {
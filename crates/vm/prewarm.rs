@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ethrex_core::{types::Transaction, Address, H256};
+use ethrex_storage::error::StoreError;
+use revm::{
+    db::{states::plain_account::PlainStorage, State},
+    primitives::{AccountInfo as RevmAccountInfo, Address as RevmAddress, U256 as RevmU256},
+    Database,
+};
+
+use crate::db::StoreWrapper;
+
+/// Maximum amount of threads reading the state declared in the access lists
+pub const PREWARM_MAX_THREADS: usize = 8;
+
+type PrefetchedAccount = (RevmAddress, Option<RevmAccountInfo>, PlainStorage);
+
+/// Reads the accounts and storage slots declared in the access lists of the transactions from the
+/// store in parallel and loads them into the cache of the state, so the execution doesn't wait on
+/// their cold reads.
+/// Must be called before executing the transactions, the accounts already cached are skipped as
+/// they may have been modified.
+pub fn prewarm_access_lists(
+    transactions: &[Transaction],
+    state: &mut State<StoreWrapper>,
+) -> Result<(), StoreError> {
+    let mut declared: BTreeMap<Address, BTreeSet<H256>> = BTreeMap::new();
+    for (address, keys) in transactions.iter().flat_map(Transaction::access_list) {
+        if !state
+            .cache
+            .accounts
+            .contains_key(&RevmAddress::from(address.0))
+        {
+            declared.entry(address).or_default().extend(keys);
+        }
+    }
+    if declared.is_empty() {
+        return Ok(());
+    }
+    let declared: Vec<(Address, BTreeSet<H256>)> = declared.into_iter().collect();
+    let threads = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .clamp(1, PREWARM_MAX_THREADS);
+    let chunk_size = declared.len().div_ceil(threads);
+    let store = &state.database.store;
+    let block_hash = state.database.block_hash;
+    let prefetched: Vec<Result<Vec<PrefetchedAccount>, StoreError>> = std::thread::scope(|scope| {
+        let readers: Vec<_> = declared
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut database = StoreWrapper {
+                        store: store.clone(),
+                        block_hash,
+                    };
+                    chunk
+                        .iter()
+                        .map(|(address, keys)| prefetch_account(&mut database, *address, keys))
+                        .collect()
+                })
+            })
+            .collect();
+        readers
+            .into_iter()
+            .map(|reader| {
+                reader
+                    .join()
+                    .unwrap_or_else(|_| Err(StoreError::Custom("State prefetch panicked".into())))
+            })
+            .collect()
+    });
+    for accounts in prefetched {
+        for (address, info, storage) in accounts? {
+            match info {
+                Some(info) => state
+                    .cache
+                    .insert_account_with_storage(address, info, storage),
+                None => state.cache.insert_not_existing(address),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn prefetch_account(
+    database: &mut StoreWrapper,
+    address: Address,
+    keys: &BTreeSet<H256>,
+) -> Result<PrefetchedAccount, StoreError> {
+    let address = RevmAddress::from(address.0);
+    let info = database.basic(address)?;
+    let mut storage = PlainStorage::default();
+    // The storage of a missing account is empty
+    if info.is_some() {
+        for key in keys {
+            let index = RevmU256::from_be_bytes(key.0);
+            storage.insert(index, database.storage(address, index)?);
+        }
+    }
+    Ok((address, info, storage))
+}
@@ -0,0 +1,162 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use revm::{
+    interpreter::{
+        CallInputs, CallOutcome, CreateInputs, CreateOutcome, EOFCreateInputs, InstructionResult,
+        Interpreter,
+    },
+    primitives::{Address, Log, U256},
+    Database, EvmContext, Inspector,
+};
+use revm_inspectors::tracing::TracingInspector;
+
+/// Steps executed between checks of the trace limits, as reading the clock on every step would
+/// slow down the tracing noticeably
+const LIMITS_CHECK_INTERVAL: u64 = 1024;
+
+/// Bounds the re-execution of traced transactions, so tracing a huge transaction can't hold
+/// the caller indefinitely
+#[derive(Clone, Copy)]
+pub struct TraceLimits<'a> {
+    /// Maximum time a single transaction can be traced for
+    pub timeout: Duration,
+    /// Checked while tracing, the execution stops as soon as it returns true
+    pub is_cancelled: &'a dyn Fn() -> bool,
+}
+
+impl TraceLimits<'_> {
+    /// Limits that only bound the time spent tracing each transaction
+    pub fn with_timeout(timeout: Duration) -> Self {
+        TraceLimits {
+            timeout,
+            is_cancelled: &|| false,
+        }
+    }
+}
+
+/// Why the tracing of a transaction was stopped before the transaction finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceInterruption {
+    Timeout,
+    Cancelled,
+}
+
+impl Display for TraceInterruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceInterruption::Timeout => write!(f, "execution timeout"),
+            TraceInterruption::Cancelled => write!(f, "execution cancelled"),
+        }
+    }
+}
+
+/// [TracingInspector] that halts the execution once the trace limits are exceeded.
+/// Every frame halts on its next step after the interruption, so the calls traced until then
+/// are kept and can be returned as a partial trace.
+pub(crate) struct LimitedTracingInspector<'a> {
+    pub(crate) inner: TracingInspector,
+    deadline: Instant,
+    is_cancelled: &'a dyn Fn() -> bool,
+    steps: u64,
+    pub(crate) interruption: Option<TraceInterruption>,
+}
+
+impl<'a> LimitedTracingInspector<'a> {
+    pub(crate) fn new(inner: TracingInspector, limits: TraceLimits<'a>) -> Self {
+        LimitedTracingInspector {
+            inner,
+            deadline: Instant::now() + limits.timeout,
+            is_cancelled: limits.is_cancelled,
+            steps: 0,
+            interruption: None,
+        }
+    }
+
+    fn check_limits(&mut self) -> Option<TraceInterruption> {
+        if self.interruption.is_none() && self.steps.is_multiple_of(LIMITS_CHECK_INTERVAL) {
+            if (self.is_cancelled)() {
+                self.interruption = Some(TraceInterruption::Cancelled);
+            } else if Instant::now() >= self.deadline {
+                self.interruption = Some(TraceInterruption::Timeout);
+            }
+        }
+        self.steps += 1;
+        self.interruption
+    }
+}
+
+impl<DB: Database> Inspector<DB> for LimitedTracingInspector<'_> {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if self.check_limits().is_some() {
+            interp.instruction_result = InstructionResult::OutOfGas;
+            return;
+        }
+        self.inner.step(interp, context)
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        self.inner.step_end(interp, context)
+    }
+
+    fn log(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>, log: &Log) {
+        self.inner.log(interp, context, log)
+    }
+
+    fn call(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut CallInputs,
+    ) -> Option<CallOutcome> {
+        self.inner.call(context, inputs)
+    }
+
+    fn call_end(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &CallInputs,
+        outcome: CallOutcome,
+    ) -> CallOutcome {
+        self.inner.call_end(context, inputs, outcome)
+    }
+
+    fn create(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut CreateInputs,
+    ) -> Option<CreateOutcome> {
+        self.inner.create(context, inputs)
+    }
+
+    fn create_end(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &CreateInputs,
+        outcome: CreateOutcome,
+    ) -> CreateOutcome {
+        self.inner.create_end(context, inputs, outcome)
+    }
+
+    fn eofcreate(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut EOFCreateInputs,
+    ) -> Option<CreateOutcome> {
+        self.inner.eofcreate(context, inputs)
+    }
+
+    fn eofcreate_end(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &EOFCreateInputs,
+        outcome: CreateOutcome,
+    ) -> CreateOutcome {
+        self.inner.eofcreate_end(context, inputs, outcome)
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        <TracingInspector as Inspector<DB>>::selfdestruct(&mut self.inner, contract, target, value)
+    }
+}
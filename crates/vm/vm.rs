@@ -1,18 +1,25 @@
+mod call_trace;
 pub mod db;
 pub mod errors;
 pub mod execution_db;
 mod execution_result;
 #[cfg(feature = "l2")]
 mod mods;
+pub mod prewarm;
+mod trace_limits;
 
 use db::StoreWrapper;
 use execution_db::ExecutionDB;
-use std::cmp::min;
+use std::{cmp::min, collections::HashMap};
+use trace_limits::LimitedTracingInspector;
 
+use alloy_rpc_types_trace::geth::CallConfig;
 use ethrex_core::{
     types::{
-        AccountInfo, Block, BlockHash, BlockHeader, ChainConfig, Fork, GenericTransaction,
-        PrivilegedTxType, Receipt, Transaction, TxKind, Withdrawal, GWEI_TO_WEI, INITIAL_BASE_FEE,
+        deposit_requests, AccountInfo, Block, BlockHash, BlockHeader, ChainConfig, EncodedRequests,
+        Fork, GenericTransaction, PrivilegedTxType, Receipt, Transaction, TxKind, Withdrawal,
+        CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS, CONSOLIDATION_REQUEST_TYPE, GWEI_TO_WEI,
+        INITIAL_BASE_FEE, WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS, WITHDRAWAL_REQUEST_TYPE,
     },
     Address, BigEndianHash, H256, U256,
 };
@@ -23,24 +30,34 @@ use revm::{
     inspector_handle_register,
     inspectors::TracerEip3155,
     precompile::{PrecompileSpecId, Precompiles},
-    primitives::{BlobExcessGasAndPrice, BlockEnv, TxEnv, B256},
+    primitives::{
+        Account as RevmAccount, AccountStatus as EvmAccountStatus, BlobExcessGasAndPrice, BlockEnv,
+        Bytecode, EvmStorage, EvmStorageSlot, HashMap as RevmHashMap, TxEnv, B256,
+    },
     Database, DatabaseCommit, Evm,
 };
-use revm_inspectors::access_list::AccessListInspector;
+use revm_inspectors::{
+    access_list::AccessListInspector,
+    tracing::{TracingInspector, TracingInspectorConfig},
+};
 // Rename imported types for clarity
 use revm_primitives::{
     ruint::Uint, AccessList as RevmAccessList, AccessListItem, Bytes, FixedBytes,
     TxKind as RevmTxKind,
 };
 // Export needed types
+pub use call_trace::CallTrace;
 pub use errors::EvmError;
 pub use execution_result::*;
 pub use revm::primitives::{Address as RevmAddress, SpecId, U256 as RevmU256};
+pub use trace_limits::{TraceInterruption, TraceLimits};
 
 type AccessList = Vec<(Address, Vec<H256>)>;
 
 pub const WITHDRAWAL_MAGIC_DATA: &[u8] = b"burn";
 pub const DEPOSIT_MAGIC_DATA: &[u8] = b"mint";
+/// Maximum amount of times a transaction is run while creating its access list
+pub const MAX_ACCESS_LIST_RUNS: usize = 8;
 
 /// State used when running the EVM. The state can be represented with a [StoreWrapper] database, or
 /// with a [ExecutionDB] in case we only want to store the necessary data for some particular
@@ -85,7 +102,7 @@ cfg_if::cfg_if! {
             vm::VM,
             Environment,
         };
-        use std::{collections::HashMap, sync::Arc};
+        use std::sync::Arc;
         use ethrex_core::types::code_hash;
 
         /// Executes all transactions in a block and returns their receipts.
@@ -93,6 +110,7 @@ cfg_if::cfg_if! {
             block: &Block,
             state: &mut EvmState,
         ) -> Result<(Vec<Receipt>, Vec<AccountUpdate>), EvmError> {
+            let _span = tracing::debug_span!("execute_block", number = block.header.number).entered();
             let block_header = &block.header;
             //eip 4788: execute beacon_root_contract_call before block transactions
             cfg_if::cfg_if! {
@@ -202,6 +220,7 @@ cfg_if::cfg_if! {
     } else if #[cfg(not(feature = "levm"))] {
         /// Executes all transactions in a block and returns their receipts.
         pub fn execute_block(block: &Block, state: &mut EvmState) -> Result<Vec<Receipt>, EvmError> {
+            let _span = tracing::debug_span!("execute_block", number = block.header.number).entered();
             let block_header = &block.header;
             let spec_id = spec_id(&state.chain_config()?, block_header.timestamp);
             //eip 4788: execute beacon_root_contract_call before block transactions
@@ -213,6 +232,9 @@ cfg_if::cfg_if! {
                     }
                 }
             }
+            if let EvmState::Store(db) = state {
+                prewarm::prewarm_access_lists(&block.body.transactions, db)?;
+            }
             let mut receipts = Vec::new();
             let mut cumulative_gas_used = 0;
 
@@ -245,7 +267,11 @@ pub fn execute_tx(
     spec_id: SpecId,
 ) -> Result<ExecutionResult, EvmError> {
     let block_env = block_env(header);
-    let tx_env = tx_env(tx);
+    let tx_env = match state.database() {
+        // Senders recovered when the transactions entered the mempool are not recovered again
+        Some(store) => tx_env_with_sender(tx, || store.transaction_sender(tx)),
+        None => tx_env(tx),
+    };
     run_evm(tx_env, block_env, state, spec_id)
 }
 
@@ -261,6 +287,229 @@ pub fn simulate_tx_from_generic(
     run_without_commit(tx_env, block_env, state, spec_id)
 }
 
+// Executes a single GenericTransaction and commits the result to the given state (but not to the DB),
+// so that following simulations on the same state can build upon it
+pub fn simulate_tx_from_generic_and_commit(
+    tx: &GenericTransaction,
+    header: &BlockHeader,
+    state: &mut EvmState,
+    spec_id: SpecId,
+) -> Result<ExecutionResult, EvmError> {
+    let block_env = block_env(header);
+    let tx_env = tx_env_from_generic(tx, header.base_fee_per_gas.unwrap_or(INITIAL_BASE_FEE));
+    run_simulation(tx_env, block_env, state, spec_id, true)
+}
+
+/// Executes a GenericTransaction as part of a simulated block and commits the result to the given
+/// state (but not to the DB).
+/// If `validate` is set the transaction is checked as one included in the block would be: its
+/// nonce, its balance, its gas limit and its fees against the base fee, otherwise it runs as a call.
+pub fn simulate_tx_in_block(
+    tx: &GenericTransaction,
+    header: &BlockHeader,
+    state: &mut EvmState,
+    spec_id: SpecId,
+    validate: bool,
+) -> Result<ExecutionResult, EvmError> {
+    if !validate {
+        return simulate_tx_from_generic_and_commit(tx, header, state, spec_id);
+    }
+    let block_env = block_env(header);
+    let tx_env = tx_env_from_generic(tx, header.base_fee_per_gas.unwrap_or(INITIAL_BASE_FEE));
+    let chain_id = state.chain_config()?.chain_id;
+    let evm_builder = Evm::builder()
+        .with_block_env(block_env)
+        .with_tx_env(tx_env)
+        .with_spec_id(spec_id)
+        .modify_cfg_env(|env| env.chain_id = chain_id);
+    let tx_result = match state {
+        EvmState::Store(db) => evm_builder
+            .with_db(db)
+            .build()
+            .transact_commit()
+            .map_err(EvmError::from)?,
+        EvmState::Execution(db) => evm_builder
+            .with_db(db)
+            .build()
+            .transact_commit()
+            .map_err(EvmError::from)?,
+    };
+    Ok(tx_result.into())
+}
+
+/// Changes applied to an account before running a simulation
+#[derive(Debug, Default, Clone)]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<u64>,
+    pub code: Option<bytes::Bytes>,
+    /// Replaces the whole account storage
+    pub state: Option<HashMap<H256, U256>>,
+    /// Replaces only the given storage slots
+    pub state_diff: Option<HashMap<H256, U256>>,
+}
+
+/// Applies the given account overrides to the state (but not to the DB)
+pub fn apply_state_overrides(
+    state: &mut EvmState,
+    overrides: &HashMap<Address, AccountOverride>,
+) -> Result<(), EvmError> {
+    match state {
+        EvmState::Store(db) => apply_state_overrides_to_db(db, overrides),
+        EvmState::Execution(db) => apply_state_overrides_to_db(db, overrides),
+    }
+}
+
+/// Returns the nonce and balance of an account, including the changes applied to the state
+/// but not to the DB
+pub fn get_nonce_and_balance(
+    state: &mut EvmState,
+    address: Address,
+) -> Result<(u64, U256), EvmError> {
+    let address = RevmAddress(address.0.into());
+    let info = match state {
+        EvmState::Store(db) => db.basic(address)?,
+        EvmState::Execution(db) => db.basic(address)?,
+    }
+    .unwrap_or_default();
+    Ok((info.nonce, U256(info.balance.into_limbs())))
+}
+
+fn apply_state_overrides_to_db<DB>(
+    db: &mut DB,
+    overrides: &HashMap<Address, AccountOverride>,
+) -> Result<(), EvmError>
+where
+    DB: Database + DatabaseCommit,
+    EvmError: From<DB::Error>,
+{
+    let mut changes = RevmHashMap::default();
+    for (address, account_override) in overrides {
+        let address = RevmAddress(address.0.into());
+        let mut info = db.basic(address)?.unwrap_or_default();
+        if let Some(balance) = account_override.balance {
+            info.balance = RevmU256::from_limbs(balance.0);
+        }
+        if let Some(nonce) = account_override.nonce {
+            info.nonce = nonce;
+        }
+        if let Some(code) = &account_override.code {
+            let bytecode = Bytecode::new_raw(code.clone().into());
+            info.code_hash = bytecode.hash_slow();
+            info.code = Some(bytecode);
+        }
+        let mut status = EvmAccountStatus::Touched;
+        let mut storage = EvmStorage::default();
+        // Marking the account as created discards its previous storage
+        if let Some(state) = &account_override.state {
+            status |= EvmAccountStatus::Created;
+            for (key, value) in state {
+                let key = RevmU256::from_be_bytes(key.0);
+                let slot =
+                    EvmStorageSlot::new_changed(RevmU256::ZERO, RevmU256::from_limbs(value.0));
+                storage.insert(key, slot);
+            }
+        } else if let Some(state_diff) = &account_override.state_diff {
+            for (key, value) in state_diff {
+                let key = RevmU256::from_be_bytes(key.0);
+                let original = db.storage(address, key)?;
+                let slot = EvmStorageSlot::new_changed(original, RevmU256::from_limbs(value.0));
+                storage.insert(key, slot);
+            }
+        }
+        changes.insert(
+            address,
+            RevmAccount {
+                info,
+                storage,
+                status,
+            },
+        );
+    }
+    db.commit(changes);
+    Ok(())
+}
+
+/// Executes all transactions in a block, returning the call trace of each one.
+/// The resulting state changes are committed to the given state (but not to the DB).
+pub fn trace_block_calls(
+    block: &Block,
+    state: &mut EvmState,
+    limits: TraceLimits,
+) -> Result<Vec<CallTrace>, EvmError> {
+    let block_header = &block.header;
+    let spec_id = spec_id(&state.chain_config()?, block_header.timestamp);
+    cfg_if::cfg_if! {
+        if #[cfg(not(feature = "l2"))] {
+            //eip 4788: execute beacon_root_contract_call before block transactions
+            if block_header.parent_beacon_block_root.is_some() && spec_id == SpecId::CANCUN {
+                beacon_root_contract_call(state, block_header, spec_id)?;
+            }
+        }
+    }
+    let mut traces = Vec::with_capacity(block.body.transactions.len());
+    for tx in block.body.transactions.iter() {
+        let (trace, interruption) =
+            trace_tx_calls(tx_env(tx), block_env(block_header), state, spec_id, limits)?;
+        traces.push(trace);
+        if let Some(reason) = interruption {
+            return Err(EvmError::TraceInterrupted { reason, traces });
+        }
+    }
+    Ok(traces)
+}
+
+/// Runs the transaction with a call tracer and commits the result to the state.
+/// If the trace limits are exceeded the execution is halted, returning the partial trace along
+/// with the reason of the interruption.
+fn trace_tx_calls(
+    tx_env: TxEnv,
+    block_env: BlockEnv,
+    state: &mut EvmState,
+    spec_id: SpecId,
+    limits: TraceLimits,
+) -> Result<(CallTrace, Option<TraceInterruption>), EvmError> {
+    let call_config = CallConfig::default();
+    let mut inspector = LimitedTracingInspector::new(
+        TracingInspector::new(TracingInspectorConfig::from_geth_call_config(&call_config)),
+        limits,
+    );
+    let chain_id = state.chain_config()?.chain_id;
+    let evm_builder = Evm::builder()
+        .with_block_env(block_env)
+        .with_tx_env(tx_env)
+        .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+        .with_spec_id(spec_id)
+        .with_external_context(&mut inspector);
+    let tx_result = match state {
+        EvmState::Store(db) => {
+            let mut evm = evm_builder
+                .with_db(db)
+                .append_handler_register(inspector_handle_register)
+                .build();
+            evm.transact_commit().map_err(EvmError::from)?
+        }
+        EvmState::Execution(db) => {
+            let mut evm = evm_builder
+                .with_db(db)
+                .append_handler_register(inspector_handle_register)
+                .build();
+            evm.transact_commit().map_err(EvmError::from)?
+        }
+    };
+    let interruption = inspector.interruption;
+    let mut trace: CallTrace = inspector
+        .inner
+        .into_geth_builder()
+        .geth_call_traces(call_config, tx_result.gas_used())
+        .into();
+    if let Some(interruption) = interruption {
+        // The execution was halted as out of gas, report the actual reason instead
+        trace.error = Some(interruption.to_string());
+    }
+    Ok((trace, interruption))
+}
+
 /// When basefee tracking is disabled  (ie. env.disable_base_fee = true; env.disable_block_gas_limit = true;)
 /// and no gas prices were specified, lower the basefee to 0 to avoid breaking EVM invariants (basefee < feecap)
 /// See https://github.com/ethereum/go-ethereum/blob/00294e9d28151122e955c7db4344f06724295ec5/core/vm/evm.go#L137
@@ -335,20 +584,24 @@ pub fn create_access_list(
 ) -> Result<(ExecutionResult, AccessList), EvmError> {
     let mut tx_env = tx_env_from_generic(tx, header.base_fee_per_gas.unwrap_or(INITIAL_BASE_FEE));
     let block_env = block_env(header);
-    // Run tx with access list inspector
-
-    let (execution_result, access_list) =
-        create_access_list_inner(tx_env.clone(), block_env.clone(), state, spec_id)?;
-
-    // Run the tx with the resulting access list and estimate its gas used
-    let execution_result = if execution_result.is_success() {
-        tx_env.access_list.extend(access_list.0.clone());
+    // Declaring the accessed state changes the gas available to the transaction, which may
+    // change what it accesses, so it is run with the resulting access list until it settles
+    for _ in 0..MAX_ACCESS_LIST_RUNS {
+        let (execution_result, access_list) =
+            create_access_list_inner(tx_env.clone(), block_env.clone(), state, spec_id)?;
+        if !execution_result.is_success() || access_list.0 == tx_env.access_list {
+            return Ok((execution_result, from_revm_access_list(access_list.0)));
+        }
+        tx_env.access_list = access_list.0;
+    }
+    // Estimate the gas used with the last access list
+    let access_list = tx_env.access_list.clone();
+    let execution_result = run_without_commit(tx_env, block_env, state, spec_id)?;
+    Ok((execution_result, from_revm_access_list(access_list)))
+}
 
-        run_without_commit(tx_env, block_env, state, spec_id)?
-    } else {
-        execution_result
-    };
-    let access_list: Vec<(Address, Vec<H256>)> = access_list
+fn from_revm_access_list(access_list: Vec<AccessListItem>) -> AccessList {
+    access_list
         .iter()
         .map(|item| {
             (
@@ -359,18 +612,23 @@ pub fn create_access_list(
                     .collect(),
             )
         })
-        .collect();
-    Ok((execution_result, access_list))
+        .collect()
 }
 
 /// Runs the transaction and returns the access list for it
 fn create_access_list_inner(
     tx_env: TxEnv,
-    block_env: BlockEnv,
+    mut block_env: BlockEnv,
     state: &mut EvmState,
     spec_id: SpecId,
 ) -> Result<(ExecutionResult, RevmAccessList), EvmError> {
     let mut access_list_inspector = access_list_inspector(&tx_env, state, spec_id)?;
+    adjust_disabled_base_fee(
+        &mut block_env,
+        tx_env.gas_price,
+        tx_env.max_fee_per_blob_gas,
+    );
+    let chain_config = state.chain_config()?;
     #[allow(unused_mut)]
     let mut evm_builder = Evm::builder()
         .with_block_env(block_env)
@@ -378,7 +636,8 @@ fn create_access_list_inner(
         .with_spec_id(spec_id)
         .modify_cfg_env(|env| {
             env.disable_base_fee = true;
-            env.disable_block_gas_limit = true
+            env.disable_block_gas_limit = true;
+            env.chain_id = chain_config.chain_id;
         })
         .with_external_context(&mut access_list_inspector);
 
@@ -407,10 +666,22 @@ fn create_access_list_inner(
 
 /// Runs the transaction and returns the result, but does not commit it.
 fn run_without_commit(
+    tx_env: TxEnv,
+    block_env: BlockEnv,
+    state: &mut EvmState,
+    spec_id: SpecId,
+) -> Result<ExecutionResult, EvmError> {
+    run_simulation(tx_env, block_env, state, spec_id, false)
+}
+
+/// Runs the transaction without base fee and block gas limit checks,
+/// committing the result to the state if `commit` is set.
+fn run_simulation(
     tx_env: TxEnv,
     mut block_env: BlockEnv,
     state: &mut EvmState,
     spec_id: SpecId,
+    commit: bool,
 ) -> Result<ExecutionResult, EvmError> {
     adjust_disabled_base_fee(
         &mut block_env,
@@ -431,14 +702,22 @@ fn run_without_commit(
     let tx_result = match state {
         EvmState::Store(db) => {
             let mut evm = evm_builder.with_db(db).build();
-            evm.transact().map_err(EvmError::from)?
+            if commit {
+                evm.transact_commit().map_err(EvmError::from)?
+            } else {
+                evm.transact().map_err(EvmError::from)?.result
+            }
         }
         EvmState::Execution(db) => {
             let mut evm = evm_builder.with_db(db).build();
-            evm.transact().map_err(EvmError::from)?
+            if commit {
+                evm.transact_commit().map_err(EvmError::from)?
+            } else {
+                evm.transact().map_err(EvmError::from)?.result
+            }
         }
     };
-    Ok(tx_result.result.into())
+    Ok(tx_result.into())
 }
 
 /// Merges transitions stored when executing transactions and returns the resulting account updates
@@ -571,6 +850,37 @@ pub fn get_state_transitions(state: &mut EvmState) -> Vec<AccountUpdate> {
     }
 }
 
+/// Returns the accounts loaded from the database while executing, along with the storage
+/// slots loaded for each of them, whether they were only read or also written
+pub fn get_accessed_state(state: &EvmState) -> Vec<(Address, Vec<H256>)> {
+    let to_key = |key: &RevmU256| H256::from_uint(&U256::from_little_endian(key.as_le_slice()));
+    match state {
+        EvmState::Store(db) => db
+            .cache
+            .accounts
+            .iter()
+            .map(|(address, account)| {
+                let slots = account
+                    .account
+                    .as_ref()
+                    .map(|account| account.storage.keys().map(to_key).collect())
+                    .unwrap_or_default();
+                (Address::from_slice(address.0.as_slice()), slots)
+            })
+            .collect(),
+        EvmState::Execution(db) => db
+            .accounts
+            .iter()
+            .map(|(address, account)| {
+                (
+                    Address::from_slice(address.0.as_slice()),
+                    account.storage.keys().map(to_key).collect(),
+                )
+            })
+            .collect(),
+    }
+}
+
 /// Processes a block's withdrawals, updating the account balances in the state
 pub fn process_withdrawals(
     state: &mut EvmState,
@@ -612,6 +922,11 @@ pub fn evm_state(store: Store, block_hash: BlockHash) -> EvmState {
     )
 }
 
+lazy_static! {
+    static ref SYSTEM_ADDRESS: RevmAddress =
+        RevmAddress::from_slice(&hex::decode("fffffffffffffffffffffffffffffffffffffffe").unwrap());
+}
+
 /// Calls the eip4788 beacon block root system call contract
 /// As of the Cancun hard-fork, parent_beacon_block_root needs to be present in the block header.
 pub fn beacon_root_contract_call(
@@ -620,9 +935,6 @@ pub fn beacon_root_contract_call(
     spec_id: SpecId,
 ) -> Result<ExecutionResult, EvmError> {
     lazy_static! {
-        static ref SYSTEM_ADDRESS: RevmAddress = RevmAddress::from_slice(
-            &hex::decode("fffffffffffffffffffffffffffffffffffffffe").unwrap()
-        );
         static ref CONTRACT_ADDRESS: RevmAddress = RevmAddress::from_slice(
             &hex::decode("000F3df6D732807Ef1319fB7B8bB8522d0Beac02").unwrap(),
         );
@@ -635,12 +947,74 @@ pub fn beacon_root_contract_call(
         }
         Some(beacon_root) => beacon_root,
     };
+    system_contract_call(
+        state,
+        header,
+        spec_id,
+        *CONTRACT_ADDRESS,
+        Bytes::copy_from_slice(beacon_root.as_bytes()),
+    )
+}
 
+/// Collects the deposit requests of EIP-6110 from the logs of the block's receipts and reads the
+/// withdrawal and consolidation requests queued during the block in the system contracts of
+/// EIP-7002 and EIP-7251, encoded as committed to by the requests hash of EIP-7685.
+/// Must be called after executing the block's transactions, as reading them dequeues the requests.
+pub fn extract_requests(
+    state: &mut EvmState,
+    header: &BlockHeader,
+    spec_id: SpecId,
+    receipts: &[Receipt],
+) -> Result<Vec<EncodedRequests>, EvmError> {
+    let deposit_contract =
+        state
+            .chain_config()?
+            .deposit_contract_address
+            .ok_or(EvmError::Custom(
+                "The chain config doesn't set the deposit contract".to_string(),
+            ))?;
+    let deposits = deposit_requests(receipts, deposit_contract)
+        .ok_or(EvmError::Custom("Invalid deposit event layout".to_string()))?;
+    let system_requests = [
+        (
+            WITHDRAWAL_REQUEST_TYPE,
+            *WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+        ),
+        (
+            CONSOLIDATION_REQUEST_TYPE,
+            *CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+        ),
+    ]
+    .into_iter()
+    .map(|(request_type, address)| {
+        let contract_address = RevmAddress(address.0.into());
+        match system_contract_call(state, header, spec_id, contract_address, Bytes::new())? {
+            ExecutionResult::Success { output, .. } => {
+                let (Output::Call(data) | Output::Create(data, _)) = output;
+                Ok(EncodedRequests::new(request_type, &data))
+            }
+            result => Err(EvmError::Custom(format!(
+                "System call to request contract {address:#x} failed: {result:?}"
+            ))),
+        }
+    })
+    .collect::<Result<Vec<_>, EvmError>>()?;
+    Ok([vec![deposits], system_requests].concat())
+}
+
+/// Calls a system contract from the system address without charging any gas to the block
+fn system_contract_call(
+    state: &mut EvmState,
+    header: &BlockHeader,
+    spec_id: SpecId,
+    contract_address: RevmAddress,
+    data: Bytes,
+) -> Result<ExecutionResult, EvmError> {
     let tx_env = TxEnv {
         caller: *SYSTEM_ADDRESS,
-        transact_to: RevmTxKind::Call(*CONTRACT_ADDRESS),
+        transact_to: RevmTxKind::Call(contract_address),
         gas_limit: 30_000_000,
-        data: revm::primitives::Bytes::copy_from_slice(beacon_root.as_bytes()),
+        data,
         ..Default::default()
     };
     let mut block_env = block_env(header);
@@ -696,6 +1070,11 @@ pub fn block_env(header: &BlockHeader) -> BlockEnv {
 }
 
 pub fn tx_env(tx: &Transaction) -> TxEnv {
+    tx_env_with_sender(tx, || tx.sender())
+}
+
+/// Same as [tx_env], getting the sender of the transaction from the given function
+fn tx_env_with_sender(tx: &Transaction, sender: impl FnOnce() -> Address) -> TxEnv {
     let mut max_fee_per_blob_gas_bytes: [u8; 32] = [0; 32];
     let max_fee_per_blob_gas = match tx.max_fee_per_blob_gas() {
         Some(x) => {
@@ -709,7 +1088,7 @@ pub fn tx_env(tx: &Transaction) -> TxEnv {
             Transaction::PrivilegedL2Transaction(tx) if tx.tx_type == PrivilegedTxType::Deposit => {
                 RevmAddress::ZERO
             }
-            _ => RevmAddress(tx.sender().0.into()),
+            _ => RevmAddress(sender().0.into()),
         },
         gas_limit: tx.gas_limit(),
         gas_price: RevmU256::from(tx.gas_price()),
@@ -850,10 +1229,11 @@ fn access_list_inspector(
 }
 
 /// Returns the spec id according to the block timestamp and the stored chain config
+/// Prague blocks are executed with the Cancun rules, only their execution requests are supported
 /// WARNING: Assumes at least Merge fork is active
 pub fn spec_id(chain_config: &ChainConfig, block_timestamp: u64) -> SpecId {
     match chain_config.get_fork(block_timestamp) {
-        Fork::Cancun => SpecId::CANCUN,
+        Fork::Prague | Fork::Cancun => SpecId::CANCUN,
         Fork::Shanghai => SpecId::SHANGHAI,
         Fork::Paris => SpecId::MERGE,
     }